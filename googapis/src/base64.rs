@@ -0,0 +1,102 @@
+// Base64 as used by Google APIs, see https://datatracker.ietf.org/doc/html/rfc4648: `bytes`
+// fields are padded standard base64 in JSON, JWTs and JWKS are unpadded URL-safe base64.
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// Encodes padded standard base64.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    encode_with(bytes, STANDARD, true)
+}
+
+// Encodes unpadded URL-safe base64.
+pub(crate) fn encode_url_safe(bytes: &[u8]) -> String {
+    encode_with(bytes, URL_SAFE, false)
+}
+
+fn encode_with(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(alphabet[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else if pad {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+// Decodes padded standard base64 only.
+pub(crate) fn decode_standard(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(4) || s.bytes().any(|b| b == b'-' || b == b'_') {
+        return None;
+    }
+    decode(s)
+}
+
+// Decodes standard or URL-safe base64, with or without padding.
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    let data = s.trim_end_matches('=');
+    let padding = s.len() - data.len();
+    if data.len() % 4 == 1 || (padding > 0 && (padding > 2 || !s.len().is_multiple_of(4))) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for b in data.bytes() {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        acc = acc << 6 | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"ab"), "YWI=");
+        assert_eq!(encode(b"hello"), "aGVsbG8=");
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+        assert_eq!(encode_url_safe(&[0xfb, 0xff]), "-_8");
+        assert_eq!(encode_url_safe(b"abc"), "YWJj");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("_w==").unwrap(), [0xff]);
+        assert_eq!(decode("/w").unwrap(), [0xff]);
+        assert_eq!(decode("YWJj").unwrap(), b"abc");
+        assert_eq!(decode("YWI=").unwrap(), b"ab");
+        for invalid in &["Y", "YWI==", "YW=I", "Y===", "YW.I"] {
+            assert_eq!(decode(invalid), None, "{}", invalid);
+        }
+
+        assert_eq!(decode_standard("+/8=").unwrap(), [0xfb, 0xff]);
+        for invalid in &["-_8=", "+/8", "aGVsbG8"] {
+            assert_eq!(decode_standard(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,961 @@
+use std::{collections::HashMap, error, fmt};
+
+use super::{
+    constant::ConstantKind,
+    decl::{function_decl::Overload as OverloadDecl, DeclKind},
+    expr::{create_struct::entry::KeyKind, ExprKind},
+    parser::qualified_name,
+    r#type::{self as ty, AbstractType, PrimitiveType, TypeKind, WellKnownType},
+    CheckedExpr, Decl, Expr, ParsedExpr, Reference, Type,
+};
+
+/// An error returned when an expression does not type check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckError {
+    /// The id of the expression with the error.
+    pub id: i64,
+    /// The offset of the expression in the source, in code points, if known.
+    pub offset: Option<i32>,
+    pub message: String,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "offset {}: {}", offset, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl error::Error for CheckError {}
+
+// The types of the checker, converted from and to `Type`.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Dyn,
+    Error,
+    Null,
+    Bool,
+    Int,
+    Uint,
+    Double,
+    String,
+    Bytes,
+    Any,
+    Timestamp,
+    Duration,
+    Wrapper(Box<Ty>),
+    List(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    Message(String),
+    Type(Box<Ty>),
+    Param(String),
+    Abstract(String, Vec<Ty>),
+}
+
+impl Ty {
+    fn list(elem: Ty) -> Ty {
+        Ty::List(Box::new(elem))
+    }
+
+    fn map(key: Ty, value: Ty) -> Ty {
+        Ty::Map(Box::new(key), Box::new(value))
+    }
+
+    fn is_dyn(&self) -> bool {
+        matches!(self, Ty::Dyn | Ty::Error)
+    }
+
+    fn from_primitive(p: i32) -> Ty {
+        match PrimitiveType::from_i32(p) {
+            Some(PrimitiveType::Bool) => Ty::Bool,
+            Some(PrimitiveType::Int64) => Ty::Int,
+            Some(PrimitiveType::Uint64) => Ty::Uint,
+            Some(PrimitiveType::Double) => Ty::Double,
+            Some(PrimitiveType::String) => Ty::String,
+            Some(PrimitiveType::Bytes) => Ty::Bytes,
+            _ => Ty::Dyn,
+        }
+    }
+
+    fn from_proto(t: &Type) -> Ty {
+        let boxed = |t: &Option<Box<Type>>| t.as_deref().map_or(Ty::Dyn, Ty::from_proto);
+        match &t.type_kind {
+            None | Some(TypeKind::Dyn(())) | Some(TypeKind::Function(_)) => Ty::Dyn,
+            Some(TypeKind::Error(())) => Ty::Error,
+            Some(TypeKind::Null(_)) => Ty::Null,
+            Some(TypeKind::Primitive(p)) => Ty::from_primitive(*p),
+            Some(TypeKind::Wrapper(p)) => Ty::Wrapper(Box::new(Ty::from_primitive(*p))),
+            Some(TypeKind::WellKnown(w)) => match WellKnownType::from_i32(*w) {
+                Some(WellKnownType::Timestamp) => Ty::Timestamp,
+                Some(WellKnownType::Duration) => Ty::Duration,
+                Some(WellKnownType::Any) => Ty::Any,
+                _ => Ty::Dyn,
+            },
+            Some(TypeKind::ListType(l)) => Ty::list(boxed(&l.elem_type)),
+            Some(TypeKind::MapType(m)) => Ty::map(boxed(&m.key_type), boxed(&m.value_type)),
+            Some(TypeKind::MessageType(name)) => match name.as_str() {
+                "google.protobuf.Timestamp" => Ty::Timestamp,
+                "google.protobuf.Duration" => Ty::Duration,
+                "google.protobuf.Any" => Ty::Any,
+                _ => Ty::Message(name.clone()),
+            },
+            Some(TypeKind::TypeParam(name)) => Ty::Param(name.clone()),
+            Some(TypeKind::Type(t)) => Ty::Type(Box::new(Ty::from_proto(t))),
+            Some(TypeKind::AbstractType(a)) => Ty::Abstract(
+                a.name.clone(),
+                a.parameter_types.iter().map(Ty::from_proto).collect(),
+            ),
+        }
+    }
+
+    fn to_proto(&self) -> Type {
+        let primitive = |p: PrimitiveType| TypeKind::Primitive(p as i32);
+        let kind = match self {
+            Ty::Dyn => TypeKind::Dyn(()),
+            Ty::Error => TypeKind::Error(()),
+            Ty::Null => TypeKind::Null(0),
+            Ty::Bool => primitive(PrimitiveType::Bool),
+            Ty::Int => primitive(PrimitiveType::Int64),
+            Ty::Uint => primitive(PrimitiveType::Uint64),
+            Ty::Double => primitive(PrimitiveType::Double),
+            Ty::String => primitive(PrimitiveType::String),
+            Ty::Bytes => primitive(PrimitiveType::Bytes),
+            Ty::Any => TypeKind::WellKnown(WellKnownType::Any as i32),
+            Ty::Timestamp => TypeKind::WellKnown(WellKnownType::Timestamp as i32),
+            Ty::Duration => TypeKind::WellKnown(WellKnownType::Duration as i32),
+            Ty::Wrapper(t) => match t.to_proto().type_kind {
+                Some(TypeKind::Primitive(p)) => TypeKind::Wrapper(p),
+                _ => TypeKind::Dyn(()),
+            },
+            Ty::List(elem) => TypeKind::ListType(Box::new(ty::ListType {
+                elem_type: Some(Box::new(elem.to_proto())),
+            })),
+            Ty::Map(key, value) => TypeKind::MapType(Box::new(ty::MapType {
+                key_type: Some(Box::new(key.to_proto())),
+                value_type: Some(Box::new(value.to_proto())),
+            })),
+            Ty::Message(name) => TypeKind::MessageType(name.clone()),
+            Ty::Type(t) => TypeKind::Type(Box::new(t.to_proto())),
+            Ty::Param(name) => TypeKind::TypeParam(name.clone()),
+            Ty::Abstract(name, params) => TypeKind::AbstractType(AbstractType {
+                name: name.clone(),
+                parameter_types: params.iter().map(Ty::to_proto).collect(),
+            }),
+        };
+        Type {
+            type_kind: Some(kind),
+        }
+    }
+
+    // Replaces the type parameters bound in `subst`, and the others by `dyn`.
+    fn substitute(&self, subst: &HashMap<String, Ty>) -> Ty {
+        match self {
+            Ty::Param(p) => subst.get(p).map_or(Ty::Dyn, |t| t.substitute(subst)),
+            Ty::Wrapper(t) => Ty::Wrapper(Box::new(t.substitute(subst))),
+            Ty::List(t) => Ty::list(t.substitute(subst)),
+            Ty::Map(k, v) => Ty::map(k.substitute(subst), v.substitute(subst)),
+            Ty::Type(t) => Ty::Type(Box::new(t.substitute(subst))),
+            Ty::Abstract(name, params) => Ty::Abstract(
+                name.clone(),
+                params.iter().map(|t| t.substitute(subst)).collect(),
+            ),
+            t => t.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Dyn => f.write_str("dyn"),
+            Ty::Error => f.write_str("!error!"),
+            Ty::Null => f.write_str("null_type"),
+            Ty::Bool => f.write_str("bool"),
+            Ty::Int => f.write_str("int"),
+            Ty::Uint => f.write_str("uint"),
+            Ty::Double => f.write_str("double"),
+            Ty::String => f.write_str("string"),
+            Ty::Bytes => f.write_str("bytes"),
+            Ty::Any => f.write_str("google.protobuf.Any"),
+            Ty::Timestamp => f.write_str("google.protobuf.Timestamp"),
+            Ty::Duration => f.write_str("google.protobuf.Duration"),
+            Ty::Wrapper(t) => write!(f, "wrapper({})", t),
+            Ty::List(t) => write!(f, "list({})", t),
+            Ty::Map(k, v) => write!(f, "map({}, {})", k, v),
+            Ty::Message(name) => f.write_str(name),
+            Ty::Type(t) => write!(f, "type({})", t),
+            Ty::Param(name) => f.write_str(name),
+            Ty::Abstract(name, params) => {
+                f.write_str(name)?;
+                if !params.is_empty() {
+                    let params = params.iter().map(Ty::to_string).collect::<Vec<_>>();
+                    write!(f, "({})", params.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Parses the types of the standard declarations, e.g. `map(A, list(int))`. Single capital
+// letters are type parameters.
+fn parse_ty(s: &str) -> Ty {
+    let s = s.trim();
+    if let Some(args) = s.strip_suffix(')') {
+        let (name, args) = args.split_at(args.find('(').unwrap());
+        let args = &args[1..];
+        // Splits at the top level comma, if any.
+        let mut depth = 0;
+        let comma = args.char_indices().find(|&(_, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            c == ',' && depth == 0
+        });
+        return match (name, comma) {
+            ("list", None) => Ty::list(parse_ty(args)),
+            ("type", None) => Ty::Type(Box::new(parse_ty(args))),
+            ("map", Some((i, _))) => Ty::map(parse_ty(&args[..i]), parse_ty(&args[i + 1..])),
+            _ => unreachable!("invalid type {}", s),
+        };
+    }
+    match s {
+        "dyn" => Ty::Dyn,
+        "null" => Ty::Null,
+        "bool" => Ty::Bool,
+        "int" => Ty::Int,
+        "uint" => Ty::Uint,
+        "double" => Ty::Double,
+        "string" => Ty::String,
+        "bytes" => Ty::Bytes,
+        "timestamp" => Ty::Timestamp,
+        "duration" => Ty::Duration,
+        p if p.len() == 1 => Ty::Param(p.to_owned()),
+        _ => unreachable!("invalid type {}", s),
+    }
+}
+
+// The standard functions: name, overload id, whether it is called on a target, and signature.
+// The target of instance functions is their first parameter.
+#[rustfmt::skip]
+const STANDARD: &[(&str, &str, bool, &str)] = &[
+    ("_?_:_", "conditional", false, "bool, A, A -> A"),
+    ("_&&_", "logical_and", false, "bool, bool -> bool"),
+    ("_||_", "logical_or", false, "bool, bool -> bool"),
+    ("!_", "logical_not", false, "bool -> bool"),
+    ("@not_strictly_false", "not_strictly_false", false, "bool -> bool"),
+    ("_==_", "equals", false, "A, A -> bool"),
+    ("_!=_", "not_equals", false, "A, A -> bool"),
+    ("-_", "negate_int64", false, "int -> int"),
+    ("-_", "negate_double", false, "double -> double"),
+    ("_+_", "add_int64", false, "int, int -> int"),
+    ("_+_", "add_uint64", false, "uint, uint -> uint"),
+    ("_+_", "add_double", false, "double, double -> double"),
+    ("_+_", "add_string", false, "string, string -> string"),
+    ("_+_", "add_bytes", false, "bytes, bytes -> bytes"),
+    ("_+_", "add_list", false, "list(A), list(A) -> list(A)"),
+    ("_+_", "add_timestamp_duration", false, "timestamp, duration -> timestamp"),
+    ("_+_", "add_duration_timestamp", false, "duration, timestamp -> timestamp"),
+    ("_+_", "add_duration_duration", false, "duration, duration -> duration"),
+    ("_-_", "subtract_int64", false, "int, int -> int"),
+    ("_-_", "subtract_uint64", false, "uint, uint -> uint"),
+    ("_-_", "subtract_double", false, "double, double -> double"),
+    ("_-_", "subtract_timestamp_timestamp", false, "timestamp, timestamp -> duration"),
+    ("_-_", "subtract_timestamp_duration", false, "timestamp, duration -> timestamp"),
+    ("_-_", "subtract_duration_duration", false, "duration, duration -> duration"),
+    ("_*_", "multiply_int64", false, "int, int -> int"),
+    ("_*_", "multiply_uint64", false, "uint, uint -> uint"),
+    ("_*_", "multiply_double", false, "double, double -> double"),
+    ("_/_", "divide_int64", false, "int, int -> int"),
+    ("_/_", "divide_uint64", false, "uint, uint -> uint"),
+    ("_/_", "divide_double", false, "double, double -> double"),
+    ("_%_", "modulo_int64", false, "int, int -> int"),
+    ("_%_", "modulo_uint64", false, "uint, uint -> uint"),
+    ("_[_]", "index_list", false, "list(A), int -> A"),
+    ("_[_]", "index_map", false, "map(A, B), A -> B"),
+    ("@in", "in_list", false, "A, list(A) -> bool"),
+    ("@in", "in_map", false, "A, map(A, B) -> bool"),
+    ("size", "size_string", false, "string -> int"),
+    ("size", "size_bytes", false, "bytes -> int"),
+    ("size", "size_list", false, "list(A) -> int"),
+    ("size", "size_map", false, "map(A, B) -> int"),
+    ("size", "string_size", true, "string -> int"),
+    ("size", "bytes_size", true, "bytes -> int"),
+    ("size", "list_size", true, "list(A) -> int"),
+    ("size", "map_size", true, "map(A, B) -> int"),
+    ("contains", "contains_string", true, "string, string -> bool"),
+    ("startsWith", "starts_with_string", true, "string, string -> bool"),
+    ("endsWith", "ends_with_string", true, "string, string -> bool"),
+    ("matches", "matches", false, "string, string -> bool"),
+    ("matches", "matches_string", true, "string, string -> bool"),
+    ("int", "int64_to_int64", false, "int -> int"),
+    ("int", "uint64_to_int64", false, "uint -> int"),
+    ("int", "double_to_int64", false, "double -> int"),
+    ("int", "string_to_int64", false, "string -> int"),
+    ("int", "timestamp_to_int64", false, "timestamp -> int"),
+    ("uint", "uint64_to_uint64", false, "uint -> uint"),
+    ("uint", "int64_to_uint64", false, "int -> uint"),
+    ("uint", "double_to_uint64", false, "double -> uint"),
+    ("uint", "string_to_uint64", false, "string -> uint"),
+    ("double", "double_to_double", false, "double -> double"),
+    ("double", "int64_to_double", false, "int -> double"),
+    ("double", "uint64_to_double", false, "uint -> double"),
+    ("double", "string_to_double", false, "string -> double"),
+    ("string", "string_to_string", false, "string -> string"),
+    ("string", "int64_to_string", false, "int -> string"),
+    ("string", "uint64_to_string", false, "uint -> string"),
+    ("string", "double_to_string", false, "double -> string"),
+    ("string", "bool_to_string", false, "bool -> string"),
+    ("string", "bytes_to_string", false, "bytes -> string"),
+    ("string", "timestamp_to_string", false, "timestamp -> string"),
+    ("string", "duration_to_string", false, "duration -> string"),
+    ("bytes", "bytes_to_bytes", false, "bytes -> bytes"),
+    ("bytes", "string_to_bytes", false, "string -> bytes"),
+    ("bool", "bool_to_bool", false, "bool -> bool"),
+    ("bool", "string_to_bool", false, "string -> bool"),
+    ("timestamp", "timestamp_to_timestamp", false, "timestamp -> timestamp"),
+    ("timestamp", "string_to_timestamp", false, "string -> timestamp"),
+    ("timestamp", "int64_to_timestamp", false, "int -> timestamp"),
+    ("duration", "duration_to_duration", false, "duration -> duration"),
+    ("duration", "string_to_duration", false, "string -> duration"),
+    ("type", "type", false, "A -> type(A)"),
+    ("dyn", "to_dyn", false, "A -> dyn"),
+    ("getFullYear", "timestamp_to_year", true, "timestamp -> int"),
+    ("getFullYear", "timestamp_to_year_with_tz", true, "timestamp, string -> int"),
+    ("getMonth", "timestamp_to_month", true, "timestamp -> int"),
+    ("getMonth", "timestamp_to_month_with_tz", true, "timestamp, string -> int"),
+    ("getDayOfYear", "timestamp_to_day_of_year", true, "timestamp -> int"),
+    ("getDayOfYear", "timestamp_to_day_of_year_with_tz", true, "timestamp, string -> int"),
+    ("getDate", "timestamp_to_day_of_month_1_based", true, "timestamp -> int"),
+    ("getDate", "timestamp_to_day_of_month_1_based_with_tz", true, "timestamp, string -> int"),
+    ("getDayOfMonth", "timestamp_to_day_of_month", true, "timestamp -> int"),
+    ("getDayOfMonth", "timestamp_to_day_of_month_with_tz", true, "timestamp, string -> int"),
+    ("getDayOfWeek", "timestamp_to_day_of_week", true, "timestamp -> int"),
+    ("getDayOfWeek", "timestamp_to_day_of_week_with_tz", true, "timestamp, string -> int"),
+    ("getHours", "timestamp_to_hours", true, "timestamp -> int"),
+    ("getHours", "timestamp_to_hours_with_tz", true, "timestamp, string -> int"),
+    ("getHours", "duration_to_hours", true, "duration -> int"),
+    ("getMinutes", "timestamp_to_minutes", true, "timestamp -> int"),
+    ("getMinutes", "timestamp_to_minutes_with_tz", true, "timestamp, string -> int"),
+    ("getMinutes", "duration_to_minutes", true, "duration -> int"),
+    ("getSeconds", "timestamp_to_seconds", true, "timestamp -> int"),
+    ("getSeconds", "timestamp_to_seconds_with_tz", true, "timestamp, string -> int"),
+    ("getSeconds", "duration_to_seconds", true, "duration -> int"),
+    ("getMilliseconds", "timestamp_to_milliseconds", true, "timestamp -> int"),
+    ("getMilliseconds", "timestamp_to_milliseconds_with_tz", true, "timestamp, string -> int"),
+    ("getMilliseconds", "duration_to_milliseconds", true, "duration -> int"),
+];
+
+// The relations are declared for every comparable type, and between numbers.
+const ORDERED: &[(&str, &str)] = &[
+    ("bool", "bool"),
+    ("int64", "int"),
+    ("uint64", "uint"),
+    ("double", "double"),
+    ("string", "string"),
+    ("bytes", "bytes"),
+    ("timestamp", "timestamp"),
+    ("duration", "duration"),
+];
+const NUMBERS: &[(&str, &str)] = &[("int64", "int"), ("uint64", "uint"), ("double", "double")];
+const RELATIONS: &[(&str, &str)] = &[
+    ("_<_", "less"),
+    ("_<=_", "less_equals"),
+    ("_>_", "greater"),
+    ("_>=_", "greater_equals"),
+];
+
+// The identifiers of the types, e.g. `int` in `type(1) == int`.
+const TYPE_IDENTS: &[(&str, &str)] = &[
+    ("bool", "bool"),
+    ("int", "int"),
+    ("uint", "uint"),
+    ("double", "double"),
+    ("string", "string"),
+    ("bytes", "bytes"),
+    ("null_type", "null"),
+    ("list", "list(dyn)"),
+    ("map", "map(dyn, dyn)"),
+    ("type", "type(dyn)"),
+];
+
+#[derive(Debug, Clone)]
+struct Overload {
+    id: String,
+    params: Vec<Ty>,
+    result: Ty,
+    instance: bool,
+}
+
+impl Overload {
+    fn parse(id: &str, instance: bool, signature: &str) -> Overload {
+        let (params, result) = signature.split_at(signature.find("->").unwrap());
+        let mut depth = 0;
+        let mut start = 0;
+        let mut split = Vec::new();
+        for (i, c) in params.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    split.push(parse_ty(&params[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        split.push(parse_ty(&params[start..]));
+        Overload {
+            id: id.to_owned(),
+            params: split,
+            result: parse_ty(&result[2..]),
+            instance,
+        }
+    }
+
+    fn from_decl(o: &OverloadDecl) -> Overload {
+        Overload {
+            id: o.overload_id.clone(),
+            params: o.params.iter().map(Ty::from_proto).collect(),
+            result: o.result_type.as_ref().map_or(Ty::Dyn, Ty::from_proto),
+            instance: o.is_instance_function,
+        }
+    }
+}
+
+fn standard_functions() -> HashMap<String, Vec<Overload>> {
+    let mut functions = HashMap::<String, Vec<Overload>>::new();
+    let mut add = |name: &str, overload: Overload| {
+        functions.entry(name.to_owned()).or_default().push(overload);
+    };
+    for &(name, id, instance, signature) in STANDARD {
+        add(name, Overload::parse(id, instance, signature));
+    }
+    for &(function, prefix) in RELATIONS {
+        for &(id, t) in ORDERED {
+            let signature = format!("{}, {} -> bool", t, t);
+            add(
+                function,
+                Overload::parse(&format!("{}_{}", prefix, id), false, &signature),
+            );
+        }
+        for &(a_id, a) in NUMBERS {
+            for &(b_id, b) in NUMBERS.iter().filter(|&&(_, b)| b != a) {
+                let id = format!("{}_{}_{}", prefix, a_id, b_id);
+                let signature = format!("{}, {} -> bool", a, b);
+                add(function, Overload::parse(&id, false, &signature));
+            }
+        }
+    }
+    functions
+}
+
+// Whether a value of type `arg` can be passed as `param`, binding the type parameters of
+// `param` in `subst`.
+fn assignable(subst: &mut HashMap<String, Ty>, param: &Ty, arg: &Ty) -> bool {
+    match (param, arg) {
+        (Ty::Param(p), _) => match subst.get(p).cloned() {
+            // Keeps the most precise binding, e.g. the element type of `[] + [1]`.
+            Some(bound) if bound.is_dyn() => {
+                if !arg.is_dyn() {
+                    subst.insert(p.clone(), arg.clone());
+                }
+                true
+            }
+            Some(_) if arg.is_dyn() => true,
+            Some(bound) => {
+                if assignable(subst, &bound, arg) {
+                    true
+                } else if assignable(subst, arg, &bound) {
+                    subst.insert(p.clone(), arg.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+            None => {
+                subst.insert(p.clone(), arg.clone());
+                true
+            }
+        },
+        (_, Ty::Param(_)) => true,
+        (Ty::Dyn, _) | (_, Ty::Dyn) | (Ty::Error, _) | (_, Ty::Error) => true,
+        // Wrappers and messages are nullable.
+        (Ty::Wrapper(_), Ty::Null) | (Ty::Message(_), Ty::Null) | (Ty::Any, _) => true,
+        (Ty::Wrapper(p), Ty::Wrapper(a)) => assignable(subst, p, a),
+        (Ty::Wrapper(p), a) => assignable(subst, p, a),
+        (Ty::List(p), Ty::List(a)) => assignable(subst, p, a),
+        (Ty::Map(pk, pv), Ty::Map(ak, av)) => {
+            assignable(subst, pk, ak) && assignable(subst, pv, av)
+        }
+        (Ty::Type(p), Ty::Type(a)) => assignable(subst, p, a),
+        (Ty::Abstract(pn, pp), Ty::Abstract(an, ap)) => {
+            pn == an
+                && pp.len() == ap.len()
+                && pp.iter().zip(ap).all(|(p, a)| assignable(subst, p, a))
+        }
+        (p, a) => p == a,
+    }
+}
+
+// The most precise type of both.
+fn join(a: Ty, b: &Ty) -> Ty {
+    if a == *b || b.is_dyn() && a.is_dyn() {
+        a
+    } else {
+        Ty::Dyn
+    }
+}
+
+struct Checker<'a> {
+    idents: HashMap<String, Ty>,
+    functions: HashMap<String, Vec<Overload>>,
+    // The variables of the enclosing comprehensions, innermost last.
+    scopes: Vec<(String, Ty)>,
+    positions: Option<&'a HashMap<i64, i32>>,
+    types: HashMap<i64, Type>,
+    references: HashMap<i64, Reference>,
+    errors: Vec<CheckError>,
+}
+
+/// Type checks a parsed expression against `decls`, the declarations of the identifiers and the
+/// functions it uses on top of the standard definitions.
+///
+/// Messages are opaque: their fields, and the fields of `dyn` values and maps with string keys,
+/// are `dyn`. Function declarations add overloads to the functions of the same name.
+///
+/// # Example
+/// ```ignore
+/// let parsed = parse("resource.name.startsWith('projects/') && request.time < expiry")?;
+/// let checked = check(&parsed, &[ident("resource", map(string, string)), ...])?;
+/// ```
+pub fn check(parsed: &ParsedExpr, decls: &[Decl]) -> Result<CheckedExpr, CheckError> {
+    let mut checker = Checker {
+        idents: TYPE_IDENTS
+            .iter()
+            .map(|&(name, t)| (name.to_owned(), Ty::Type(Box::new(parse_ty(t)))))
+            .collect(),
+        functions: standard_functions(),
+        scopes: Vec::new(),
+        positions: parsed.source_info.as_ref().map(|s| &s.positions),
+        types: HashMap::new(),
+        references: HashMap::new(),
+        errors: Vec::new(),
+    };
+    for decl in decls {
+        match &decl.decl_kind {
+            Some(DeclKind::Ident(ident)) => {
+                let t = ident.r#type.as_ref().map_or(Ty::Dyn, Ty::from_proto);
+                checker.idents.insert(decl.name.clone(), t);
+            }
+            Some(DeclKind::Function(function)) => checker
+                .functions
+                .entry(decl.name.clone())
+                .or_default()
+                .extend(function.overloads.iter().map(Overload::from_decl)),
+            None => {}
+        }
+    }
+
+    let expr = parsed.expr.clone().unwrap_or_default();
+    checker.check(&expr);
+    if !checker.errors.is_empty() {
+        return Err(checker.errors.swap_remove(0));
+    }
+    Ok(CheckedExpr {
+        reference_map: checker.references,
+        type_map: checker.types,
+        source_info: parsed.source_info.clone(),
+        expr: Some(expr),
+    })
+}
+
+impl Checker<'_> {
+    fn error(&mut self, id: i64, message: String) -> Ty {
+        let offset = self.positions.and_then(|p| p.get(&id).copied());
+        self.errors.push(CheckError {
+            id,
+            offset,
+            message,
+        });
+        Ty::Error
+    }
+
+    fn local(&self, name: &str) -> Option<&Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, t)| t)
+    }
+
+    fn check(&mut self, e: &Expr) -> Ty {
+        let t = self.check_kind(e);
+        self.types.insert(e.id, t.to_proto());
+        t
+    }
+
+    fn check_opt(&mut self, e: &Option<Box<Expr>>) -> Ty {
+        match e {
+            Some(e) => self.check(e),
+            None => Ty::Dyn,
+        }
+    }
+
+    fn check_kind(&mut self, e: &Expr) -> Ty {
+        let kind = match &e.expr_kind {
+            Some(kind) => kind,
+            None => return self.error(e.id, "empty expression".to_owned()),
+        };
+        match kind {
+            ExprKind::ConstExpr(c) => match &c.constant_kind {
+                Some(ConstantKind::NullValue(_)) | None => Ty::Null,
+                Some(ConstantKind::BoolValue(_)) => Ty::Bool,
+                Some(ConstantKind::Int64Value(_)) => Ty::Int,
+                Some(ConstantKind::Uint64Value(_)) => Ty::Uint,
+                Some(ConstantKind::DoubleValue(_)) => Ty::Double,
+                Some(ConstantKind::StringValue(_)) => Ty::String,
+                Some(ConstantKind::BytesValue(_)) => Ty::Bytes,
+                Some(ConstantKind::DurationValue(_)) => Ty::Duration,
+                Some(ConstantKind::TimestampValue(_)) => Ty::Timestamp,
+            },
+            ExprKind::IdentExpr(ident) => {
+                if let Some(t) = self.local(&ident.name) {
+                    return t.clone();
+                }
+                self.global(e.id, ident.name.trim_start_matches('.'))
+                    .unwrap_or_else(|| {
+                        self.error(e.id, format!("undeclared reference to '{}'", ident.name))
+                    })
+            }
+            ExprKind::SelectExpr(select) => {
+                // `a.b.c` may be the name of a declared identifier.
+                if !select.test_only {
+                    if let Some(name) = qualified_name(e) {
+                        let root = name.split('.').next().unwrap();
+                        if self.local(root).is_none() {
+                            if let Some(t) = self.global(e.id, name.trim_start_matches('.')) {
+                                return t;
+                            }
+                        }
+                    }
+                }
+                let operand = self.check_opt(&select.operand);
+                let t = match operand {
+                    Ty::Dyn | Ty::Error | Ty::Message(_) | Ty::Any => Ty::Dyn,
+                    Ty::Map(key, value) if matches!(*key, Ty::String | Ty::Dyn) => *value,
+                    t => {
+                        let message = format!("type '{}' does not support field selection", t);
+                        return self.error(e.id, message);
+                    }
+                };
+                if select.test_only {
+                    Ty::Bool
+                } else {
+                    t
+                }
+            }
+            ExprKind::CallExpr(call) => {
+                let mut args = Vec::new();
+                if let Some(target) = &call.target {
+                    args.push(self.check(target));
+                }
+                for arg in call.args.iter() {
+                    args.push(self.check(arg));
+                }
+                self.resolve(e.id, &call.function, call.target.is_some(), &args)
+            }
+            ExprKind::ListExpr(list) => {
+                let mut elem = None;
+                for element in list.elements.iter() {
+                    let t = self.check(element);
+                    elem = Some(elem.map_or(t.clone(), |elem| join(elem, &t)));
+                }
+                Ty::list(elem.unwrap_or(Ty::Dyn))
+            }
+            ExprKind::StructExpr(create) => {
+                let mut key_ty = None;
+                let mut value_ty = None;
+                for entry in create.entries.iter() {
+                    if let Some(KeyKind::MapKey(key)) = &entry.key_kind {
+                        let t = self.check(key);
+                        if !matches!(t, Ty::Int | Ty::Uint | Ty::Bool | Ty::String | Ty::Dyn) {
+                            self.error(key.id, format!("unsupported map key type '{}'", t));
+                        }
+                        key_ty = Some(key_ty.map_or(t.clone(), |k| join(k, &t)));
+                    }
+                    if let Some(value) = &entry.value {
+                        let t = self.check(value);
+                        value_ty = Some(value_ty.map_or(t.clone(), |v| join(v, &t)));
+                    }
+                }
+                if create.message_name.is_empty() {
+                    Ty::map(key_ty.unwrap_or(Ty::Dyn), value_ty.unwrap_or(Ty::Dyn))
+                } else {
+                    let name = create.message_name.trim_start_matches('.').to_owned();
+                    self.references.insert(
+                        e.id,
+                        Reference {
+                            name: name.clone(),
+                            ..Default::default()
+                        },
+                    );
+                    Ty::Message(name)
+                }
+            }
+            ExprKind::ComprehensionExpr(c) => {
+                let range = self.check_opt(&c.iter_range);
+                let var = match range {
+                    Ty::List(elem) => *elem,
+                    Ty::Map(key, _) => *key,
+                    Ty::Dyn | Ty::Error => Ty::Dyn,
+                    t => {
+                        let message = format!("expression of type '{}' cannot be iterated", t);
+                        return self.error(e.id, message);
+                    }
+                };
+                let init = self.check_opt(&c.accu_init);
+                self.scopes.push((c.accu_var.clone(), init.clone()));
+                self.scopes.push((c.iter_var.clone(), var));
+                let condition = self.check_opt(&c.loop_condition);
+                if !assignable(&mut HashMap::new(), &Ty::Bool, &condition) {
+                    let id = c.loop_condition.as_ref().map_or(e.id, |c| c.id);
+                    self.error(
+                        id,
+                        format!("expected type 'bool' but found '{}'", condition),
+                    );
+                }
+                let step = self.check_opt(&c.loop_step);
+                self.scopes.pop();
+                // An empty list accumulator takes the type of the lists built by the steps.
+                let accu = match (&init, &step) {
+                    (Ty::List(elem), Ty::List(_)) if elem.is_dyn() => step,
+                    _ => init,
+                };
+                self.scopes.last_mut().unwrap().1 = accu;
+                let result = self.check_opt(&c.result);
+                self.scopes.pop();
+                result
+            }
+        }
+    }
+
+    // The type of a declared identifier, recorded in the references.
+    fn global(&mut self, id: i64, name: &str) -> Option<Ty> {
+        let t = self.idents.get(name)?.clone();
+        self.references.insert(
+            id,
+            Reference {
+                name: name.to_owned(),
+                ..Default::default()
+            },
+        );
+        Some(t)
+    }
+
+    fn resolve(&mut self, id: i64, function: &str, instance: bool, args: &[Ty]) -> Ty {
+        let overloads = match self.functions.get(function) {
+            Some(overloads) => overloads,
+            None => return self.error(id, format!("undeclared reference to '{}'", function)),
+        };
+        let mut result: Option<Ty> = None;
+        let mut ids = Vec::new();
+        for o in overloads
+            .iter()
+            .filter(|o| o.instance == instance && o.params.len() == args.len())
+        {
+            let mut subst = HashMap::new();
+            if o.params
+                .iter()
+                .zip(args)
+                .all(|(p, a)| assignable(&mut subst, p, a))
+            {
+                let t = o.result.substitute(&subst);
+                result = Some(result.map_or(t.clone(), |r| join(r, &t)));
+                ids.push(o.id.clone());
+            }
+        }
+        match result {
+            Some(t) => {
+                self.references.insert(
+                    id,
+                    Reference {
+                        name: function.to_owned(),
+                        overload_id: ids,
+                        value: None,
+                    },
+                );
+                t
+            }
+            None => {
+                let args = args.iter().map(Ty::to_string).collect::<Vec<_>>();
+                let message = if instance {
+                    format!(
+                        "found no matching overload for '{}' applied to '{}.({})'",
+                        function,
+                        args[0],
+                        args[1..].join(", ")
+                    )
+                } else {
+                    format!(
+                        "found no matching overload for '{}' applied to '({})'",
+                        function,
+                        args.join(", ")
+                    )
+                };
+                self.error(id, message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{decl::IdentDecl, parse};
+    use super::*;
+
+    fn ident(name: &str, t: &str) -> Decl {
+        Decl {
+            name: name.to_owned(),
+            decl_kind: Some(DeclKind::Ident(IdentDecl {
+                r#type: Some(parse_ty(t).to_proto()),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn decls() -> Vec<Decl> {
+        vec![
+            ident("i", "int"),
+            ident("s", "string"),
+            ident("l", "list(int)"),
+            ident("m", "map(string, list(string))"),
+            ident("d", "dyn"),
+            ident("request.time", "timestamp"),
+        ]
+    }
+
+    fn ty(source: &str) -> String {
+        let checked = check(&parse(source).unwrap(), &decls())
+            .unwrap_or_else(|e| panic!("{}: {}", source, e));
+        let id = checked.expr.as_ref().unwrap().id;
+        Ty::from_proto(&checked.type_map[&id]).to_string()
+    }
+
+    fn err(source: &str) -> String {
+        check(&parse(source).unwrap(), &decls())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_ty() {
+        assert_eq!(
+            parse_ty("map(A, list(int))"),
+            Ty::map(Ty::Param("A".into()), Ty::list(Ty::Int))
+        );
+        assert_eq!(parse_ty("type(dyn)"), Ty::Type(Box::new(Ty::Dyn)));
+        for t in &["int", "list(string)", "map(string, list(A))"] {
+            assert_eq!(Ty::from_proto(&parse_ty(t).to_proto()), parse_ty(t));
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(ty("1 + i"), "int");
+        assert_eq!(ty("1.0 * 2.0"), "double");
+        assert_eq!(ty("s + 'a'"), "string");
+        assert_eq!(ty("i < 1u || s == 'a'"), "bool");
+        assert_eq!(ty("l[0]"), "int");
+        assert_eq!(ty("m['a']"), "list(string)");
+        assert_eq!(ty("m.a"), "list(string)");
+        assert_eq!(ty("has(m.a)"), "bool");
+        assert_eq!(ty("[1, 2]"), "list(int)");
+        assert_eq!(ty("[1, 'a']"), "list(dyn)");
+        assert_eq!(ty("{'a': 1}"), "map(string, int)");
+        assert_eq!(ty("d.x.y"), "dyn");
+        assert_eq!(ty("d + 1"), "int");
+        assert_eq!(ty("size(s) + s.size()"), "int");
+        assert_eq!(ty("i > 0 ? s : 'b'"), "string");
+        assert_eq!(ty("l.all(x, x > 0)"), "bool");
+        assert_eq!(ty("l.map(x, string(x))"), "list(string)");
+        assert_eq!(ty("l.filter(x, x > 0)"), "list(int)");
+        assert_eq!(ty("m.exists(k, m[k].size() > 0)"), "bool");
+        assert_eq!(ty("request.time.getHours('UTC')"), "int");
+        assert_eq!(
+            ty("request.time - duration('1h')"),
+            "google.protobuf.Timestamp"
+        );
+        assert_eq!(ty("type(1)"), "type(int)");
+        assert_eq!(ty("int"), "type(int)");
+    }
+
+    #[test]
+    fn test_check_references() {
+        let checked = check(&parse("i + size(s)").unwrap(), &decls()).unwrap();
+        let mut references = checked
+            .reference_map
+            .values()
+            .map(|r| format!("{}{:?}", r.name, r.overload_id))
+            .collect::<Vec<_>>();
+        references.sort();
+        assert_eq!(
+            references,
+            vec!["_+_[\"add_int64\"]", "i[]", "s[]", "size[\"size_string\"]"]
+        );
+        assert_eq!(checked.type_map.len(), 4);
+
+        let checked = check(&parse("request.time").unwrap(), &decls()).unwrap();
+        let id = checked.expr.as_ref().unwrap().id;
+        assert_eq!(checked.reference_map[&id].name, "request.time");
+    }
+
+    #[test]
+    fn test_check_functions() {
+        let f = Decl {
+            name: "twice".to_owned(),
+            decl_kind: Some(DeclKind::Function(super::super::decl::FunctionDecl {
+                overloads: vec![OverloadDecl {
+                    overload_id: "twice_int".to_owned(),
+                    params: vec![parse_ty("int").to_proto()],
+                    result_type: Some(parse_ty("int").to_proto()),
+                    ..Default::default()
+                }],
+            })),
+        };
+        let checked = check(&parse("twice(1) + 1").unwrap(), std::slice::from_ref(&f)).unwrap();
+        let id = checked.expr.as_ref().unwrap().id;
+        assert_eq!(Ty::from_proto(&checked.type_map[&id]), Ty::Int);
+        assert!(check(&parse("twice('a')").unwrap(), &[f]).is_err());
+    }
+
+    #[test]
+    fn test_check_errors() {
+        assert_eq!(err("x + 1"), "offset 0: undeclared reference to 'x'");
+        assert_eq!(
+            err("i + s"),
+            "offset 2: found no matching overload for '_+_' applied to '(int, string)'"
+        );
+        assert_eq!(
+            err("i.startsWith('a')"),
+            "offset 1: found no matching overload for 'startsWith' applied to 'int.(string)'"
+        );
+        assert_eq!(
+            err("i.f"),
+            "offset 1: type 'int' does not support field selection"
+        );
+        assert_eq!(err("f(1)"), "offset 0: undeclared reference to 'f'");
+        assert_eq!(
+            err("i.all(x, x)"),
+            "offset 1: expression of type 'int' cannot be iterated"
+        );
+        assert_eq!(
+            err("l.all(x, x)"),
+            "offset 1: found no matching overload for '_&&_' applied to '(bool, int)'"
+        );
+    }
+}
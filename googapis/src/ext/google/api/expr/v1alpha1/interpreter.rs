@@ -0,0 +1,952 @@
+use std::{cmp::Ordering, collections::HashMap, convert::TryFrom, error, fmt};
+
+use prost::Message;
+use prost_types::{Any, Duration, Timestamp};
+
+use crate::wkt::{civil_from_days, days_from_civil, DurationExt, TimestampExt};
+
+use super::{
+    constant::ConstantKind,
+    expr::{create_struct::entry::KeyKind, Call, Comprehension, ExprKind},
+    map_value,
+    parser::qualified_name,
+    value::Kind,
+    Expr, ListValue, MapValue, Value,
+};
+
+const TIMESTAMP_URL: &str = "type.googleapis.com/google.protobuf.Timestamp";
+const DURATION_URL: &str = "type.googleapis.com/google.protobuf.Duration";
+
+/// An error returned when the evaluation of an expression fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    /// The id of the expression that failed.
+    pub id: i64,
+    pub message: String,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for EvalError {}
+
+type Function = Box<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+/// The variables and the extension functions of an evaluation.
+///
+/// # Example
+/// ```ignore
+/// let activation = Activation::new()
+///     .bind("request.time", Timestamp::from(SystemTime::now()))
+///     .bind("resource.name", "projects/p/secrets/s")
+///     .function("double", |args| match args[0].kind { ... });
+/// ```
+#[derive(Default)]
+pub struct Activation {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Function>,
+}
+
+impl fmt::Debug for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Activation")
+            .field("variables", &self.variables)
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Activation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a variable. The name may be qualified, e.g. `request.time`, in which case it takes
+    /// precedence over the field `time` of a variable `request`.
+    pub fn bind(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds a function, called with the target first for member calls. Extension functions
+    /// take precedence over the standard functions of the same name.
+    pub fn function<F>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(name.into(), Box::new(f));
+        self
+    }
+}
+
+// The values of the evaluation, converted from and to `Value`.
+#[derive(Debug, Clone)]
+enum Val {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Double(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Val>),
+    Map(Vec<(Val, Val)>),
+    Timestamp(Timestamp),
+    Duration(Duration),
+    Type(String),
+    Object(Any),
+}
+
+impl Val {
+    fn from_value(v: Value) -> Val {
+        match v.kind {
+            None | Some(Kind::NullValue(_)) => Val::Null,
+            Some(Kind::BoolValue(b)) => Val::Bool(b),
+            Some(Kind::Int64Value(i)) => Val::Int(i),
+            Some(Kind::Uint64Value(u)) => Val::Uint(u),
+            Some(Kind::DoubleValue(d)) => Val::Double(d),
+            Some(Kind::StringValue(s)) => Val::String(s),
+            Some(Kind::BytesValue(b)) => Val::Bytes(b),
+            Some(Kind::EnumValue(e)) => Val::Int(e.value.into()),
+            Some(Kind::ObjectValue(any)) => match any.type_url.as_str() {
+                TIMESTAMP_URL => {
+                    Timestamp::decode(&*any.value).map_or(Val::Object(any), Val::Timestamp)
+                }
+                DURATION_URL => {
+                    Duration::decode(&*any.value).map_or(Val::Object(any), Val::Duration)
+                }
+                _ => Val::Object(any),
+            },
+            Some(Kind::MapValue(m)) => Val::Map(
+                m.entries
+                    .into_iter()
+                    .map(|e| {
+                        let key = e.key.map_or(Val::Null, Val::from_value);
+                        (key, e.value.map_or(Val::Null, Val::from_value))
+                    })
+                    .collect(),
+            ),
+            Some(Kind::ListValue(l)) => {
+                Val::List(l.values.into_iter().map(Val::from_value).collect())
+            }
+            Some(Kind::TypeValue(t)) => Val::Type(t),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        let kind = match self {
+            Val::Null => Kind::NullValue(0),
+            Val::Bool(b) => Kind::BoolValue(b),
+            Val::Int(i) => Kind::Int64Value(i),
+            Val::Uint(u) => Kind::Uint64Value(u),
+            Val::Double(d) => Kind::DoubleValue(d),
+            Val::String(s) => Kind::StringValue(s),
+            Val::Bytes(b) => Kind::BytesValue(b),
+            Val::List(l) => Kind::ListValue(ListValue {
+                values: l.into_iter().map(Val::into_value).collect(),
+            }),
+            Val::Map(m) => Kind::MapValue(MapValue {
+                entries: m
+                    .into_iter()
+                    .map(|(k, v)| map_value::Entry {
+                        key: Some(k.into_value()),
+                        value: Some(v.into_value()),
+                    })
+                    .collect(),
+            }),
+            Val::Timestamp(t) => Kind::ObjectValue(Any {
+                type_url: TIMESTAMP_URL.to_owned(),
+                value: t.encode_to_vec(),
+            }),
+            Val::Duration(d) => Kind::ObjectValue(Any {
+                type_url: DURATION_URL.to_owned(),
+                value: d.encode_to_vec(),
+            }),
+            Val::Type(t) => Kind::TypeValue(t),
+            Val::Object(any) => Kind::ObjectValue(any),
+        };
+        Value { kind: Some(kind) }
+    }
+
+    fn type_name(&self) -> String {
+        match self {
+            Val::Null => "null_type".to_owned(),
+            Val::Bool(_) => "bool".to_owned(),
+            Val::Int(_) => "int".to_owned(),
+            Val::Uint(_) => "uint".to_owned(),
+            Val::Double(_) => "double".to_owned(),
+            Val::String(_) => "string".to_owned(),
+            Val::Bytes(_) => "bytes".to_owned(),
+            Val::List(_) => "list".to_owned(),
+            Val::Map(_) => "map".to_owned(),
+            Val::Timestamp(_) => "google.protobuf.Timestamp".to_owned(),
+            Val::Duration(_) => "google.protobuf.Duration".to_owned(),
+            Val::Type(_) => "type".to_owned(),
+            Val::Object(any) => any.type_url.rsplit('/').next().unwrap().to_owned(),
+        }
+    }
+}
+
+/// Evaluates an expression, parsed or checked, with the variables and the functions of
+/// `activation`.
+///
+/// Errors are absorbed by the logical operators as in CEL: `false && error` is `false` and
+/// `true || error` is `true`. Integer arithmetic is checked, timestamps and durations support
+/// the arithmetic and the accessors of the standard definitions, and time zones are UTC
+/// offsets, e.g. `-08:00`, or IANA names when the `time-zone` feature is enabled. The
+/// `matches` function requires the `regex` feature. Message construction is not supported.
+///
+/// Timestamps and durations are returned as `google.protobuf.Timestamp` and
+/// `google.protobuf.Duration` objects.
+pub fn eval(expr: &Expr, activation: &Activation) -> Result<Value, EvalError> {
+    let mut interpreter = Interpreter {
+        activation,
+        scopes: Vec::new(),
+    };
+    interpreter.eval(expr).map(Val::into_value)
+}
+
+struct Interpreter<'a> {
+    activation: &'a Activation,
+    // The variables of the enclosing comprehensions, innermost last. The accumulators may hold
+    // an error, absorbed by a later step as in `[0, 1].exists(x, 1 / x == 1)`.
+    scopes: Vec<(String, Result<Val, EvalError>)>,
+}
+
+fn err<T>(id: i64, message: impl Into<String>) -> Result<T, EvalError> {
+    Err(EvalError {
+        id,
+        message: message.into(),
+    })
+}
+
+impl Interpreter<'_> {
+    fn local(&self, name: &str) -> Option<&Result<Val, EvalError>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    fn variable(&self, name: &str) -> Option<Val> {
+        let name = name.trim_start_matches('.');
+        if let Some(v) = self.activation.variables.get(name) {
+            return Some(Val::from_value(v.clone()));
+        }
+        let t = match name {
+            "bool" | "int" | "uint" | "double" | "string" | "bytes" | "null_type" | "list"
+            | "map" | "type" => name,
+            _ => return None,
+        };
+        Some(Val::Type(t.to_owned()))
+    }
+
+    fn eval_opt(&mut self, id: i64, e: &Option<Box<Expr>>) -> Result<Val, EvalError> {
+        match e {
+            Some(e) => self.eval(e),
+            None => err(id, "empty expression"),
+        }
+    }
+
+    fn eval(&mut self, e: &Expr) -> Result<Val, EvalError> {
+        let kind = match &e.expr_kind {
+            Some(kind) => kind,
+            None => return err(e.id, "empty expression"),
+        };
+        match kind {
+            ExprKind::ConstExpr(c) => Ok(match c.constant_kind.clone() {
+                Some(ConstantKind::NullValue(_)) | None => Val::Null,
+                Some(ConstantKind::BoolValue(b)) => Val::Bool(b),
+                Some(ConstantKind::Int64Value(i)) => Val::Int(i),
+                Some(ConstantKind::Uint64Value(u)) => Val::Uint(u),
+                Some(ConstantKind::DoubleValue(d)) => Val::Double(d),
+                Some(ConstantKind::StringValue(s)) => Val::String(s),
+                Some(ConstantKind::BytesValue(b)) => Val::Bytes(b),
+                Some(ConstantKind::DurationValue(d)) => Val::Duration(d),
+                Some(ConstantKind::TimestampValue(t)) => Val::Timestamp(t),
+            }),
+            ExprKind::IdentExpr(ident) => {
+                if let Some(v) = self.local(&ident.name) {
+                    return v.clone();
+                }
+                match self.variable(&ident.name) {
+                    Some(v) => Ok(v),
+                    None => err(e.id, format!("undeclared reference to '{}'", ident.name)),
+                }
+            }
+            ExprKind::SelectExpr(select) => {
+                if !select.test_only {
+                    if let Some(name) = qualified_name(e) {
+                        let root = name.split('.').next().unwrap();
+                        if self.local(root).is_none() {
+                            if let Some(v) = self.variable(&name) {
+                                return Ok(v);
+                            }
+                        }
+                    }
+                }
+                let operand = self.eval_opt(e.id, &select.operand)?;
+                let entries = match operand {
+                    Val::Map(entries) => entries,
+                    v => {
+                        let message =
+                            format!("type '{}' does not support field selection", v.type_name());
+                        return err(e.id, message);
+                    }
+                };
+                let value = entries
+                    .into_iter()
+                    .find(|(k, _)| matches!(k, Val::String(k) if *k == select.field))
+                    .map(|(_, v)| v);
+                match (value, select.test_only) {
+                    (value, true) => Ok(Val::Bool(value.is_some())),
+                    (Some(value), false) => Ok(value),
+                    (None, false) => err(e.id, format!("no such key: {}", select.field)),
+                }
+            }
+            ExprKind::CallExpr(call) => self.call(e.id, call),
+            ExprKind::ListExpr(list) => Ok(Val::List(
+                list.elements
+                    .iter()
+                    .map(|e| self.eval(e))
+                    .collect::<Result<_, _>>()?,
+            )),
+            ExprKind::StructExpr(create) => {
+                if !create.message_name.is_empty() {
+                    let message = format!(
+                        "construction of message '{}' is not supported",
+                        create.message_name
+                    );
+                    return err(e.id, message);
+                }
+                let mut entries: Vec<(Val, Val)> = Vec::new();
+                for entry in create.entries.iter() {
+                    let key = match &entry.key_kind {
+                        Some(KeyKind::MapKey(key)) => self.eval(key)?,
+                        _ => return err(entry.id, "invalid map entry"),
+                    };
+                    if !matches!(
+                        key,
+                        Val::Bool(_) | Val::Int(_) | Val::Uint(_) | Val::String(_)
+                    ) {
+                        let message = format!("unsupported map key type '{}'", key.type_name());
+                        return err(entry.id, message);
+                    }
+                    if entries.iter().any(|(k, _)| equals(k, &key)) {
+                        return err(entry.id, "duplicate map key");
+                    }
+                    let value = match &entry.value {
+                        Some(value) => self.eval(value)?,
+                        None => return err(entry.id, "empty expression"),
+                    };
+                    entries.push((key, value));
+                }
+                Ok(Val::Map(entries))
+            }
+            ExprKind::ComprehensionExpr(c) => self.comprehension(e.id, c),
+        }
+    }
+
+    fn comprehension(&mut self, id: i64, c: &Comprehension) -> Result<Val, EvalError> {
+        let range = match self.eval_opt(id, &c.iter_range)? {
+            Val::List(elements) => elements,
+            Val::Map(entries) => entries.into_iter().map(|(k, _)| k).collect(),
+            v => return err(id, format!("type '{}' cannot be iterated", v.type_name())),
+        };
+        let init = self.eval_opt(id, &c.accu_init)?;
+        self.scopes.push((c.accu_var.clone(), Ok(init)));
+        let result = self.iterate(id, c, range);
+        self.scopes.pop();
+        result
+    }
+
+    fn iterate(&mut self, id: i64, c: &Comprehension, range: Vec<Val>) -> Result<Val, EvalError> {
+        for v in range {
+            self.scopes.push((c.iter_var.clone(), Ok(v)));
+            let step = match self.eval_opt(id, &c.loop_condition)? {
+                Val::Bool(false) => None,
+                Val::Bool(true) => Some(self.eval_opt(id, &c.loop_step)),
+                v => Some(err(
+                    id,
+                    format!("loop condition of type '{}'", v.type_name()),
+                )),
+            };
+            self.scopes.pop();
+            match step {
+                Some(accu) => self.scopes.last_mut().unwrap().1 = accu,
+                None => break,
+            }
+        }
+        self.eval_opt(id, &c.result)
+    }
+
+    fn call(&mut self, id: i64, call: &Call) -> Result<Val, EvalError> {
+        let args = &call.args;
+        // The logical operators are not strict.
+        match (call.function.as_str(), args.len()) {
+            ("_&&_", 2) | ("_||_", 2) => {
+                let absorbing = call.function == "_||_";
+                let left = self.eval(&args[0]);
+                if let Ok(Val::Bool(b)) = left {
+                    if b == absorbing {
+                        return Ok(Val::Bool(b));
+                    }
+                }
+                let right = self.eval(&args[1]);
+                if let Ok(Val::Bool(b)) = right {
+                    if b == absorbing {
+                        return Ok(Val::Bool(b));
+                    }
+                }
+                return match (left?, right?) {
+                    (Val::Bool(_), Val::Bool(b)) => Ok(Val::Bool(b)),
+                    (l, r) => no_overload(id, &call.function, &[l, r]),
+                };
+            }
+            ("_?_:_", 3) => {
+                return match self.eval(&args[0])? {
+                    Val::Bool(true) => self.eval(&args[1]),
+                    Val::Bool(false) => self.eval(&args[2]),
+                    v => no_overload(id, &call.function, &[v]),
+                };
+            }
+            ("@not_strictly_false", 1) => {
+                return match self.eval(&args[0]) {
+                    Ok(Val::Bool(b)) => Ok(Val::Bool(b)),
+                    _ => Ok(Val::Bool(true)),
+                };
+            }
+            _ => {}
+        }
+
+        let mut values = Vec::with_capacity(args.len() + 1);
+        if let Some(target) = &call.target {
+            values.push(self.eval(target)?);
+        }
+        for arg in args.iter() {
+            values.push(self.eval(arg)?);
+        }
+        if let Some(f) = self.activation.functions.get(&call.function) {
+            let args = values.into_iter().map(Val::into_value).collect::<Vec<_>>();
+            return match f(&args) {
+                Ok(v) => Ok(Val::from_value(v)),
+                Err(message) => err(id, message),
+            };
+        }
+        builtin(&call.function, values).map_err(|message| EvalError { id, message })
+    }
+}
+
+fn no_overload<T>(id: i64, function: &str, args: &[Val]) -> Result<T, EvalError> {
+    err(id, no_overload_message(function, args))
+}
+
+fn no_overload_message(function: &str, args: &[Val]) -> String {
+    let args = args.iter().map(Val::type_name).collect::<Vec<_>>();
+    format!(
+        "no such overload: {}({})",
+        function.trim_matches('_').trim_start_matches('@'),
+        args.join(", ")
+    )
+}
+
+fn num_cmp(a: &Val, b: &Val) -> Option<Ordering> {
+    match (a, b) {
+        (Val::Int(a), Val::Int(b)) => Some(a.cmp(b)),
+        (Val::Uint(a), Val::Uint(b)) => Some(a.cmp(b)),
+        (Val::Double(a), Val::Double(b)) => a.partial_cmp(b),
+        (Val::Int(a), Val::Uint(b)) if *a < 0 => Some(Ordering::Less),
+        (Val::Int(a), Val::Uint(b)) => Some((*a as u64).cmp(b)),
+        (Val::Uint(_), Val::Int(_)) => num_cmp(b, a).map(Ordering::reverse),
+        (Val::Int(a), Val::Double(b)) => (*a as f64).partial_cmp(b),
+        (Val::Uint(a), Val::Double(b)) => (*a as f64).partial_cmp(b),
+        (Val::Double(_), Val::Int(_)) | (Val::Double(_), Val::Uint(_)) => {
+            num_cmp(b, a).map(Ordering::reverse)
+        }
+        _ => None,
+    }
+}
+
+fn compare(a: &Val, b: &Val) -> Option<Ordering> {
+    match (a, b) {
+        (Val::Bool(a), Val::Bool(b)) => Some(a.cmp(b)),
+        (Val::String(a), Val::String(b)) => Some(a.cmp(b)),
+        (Val::Bytes(a), Val::Bytes(b)) => Some(a.cmp(b)),
+        (Val::Timestamp(a), Val::Timestamp(b)) => {
+            Some((a.seconds, a.nanos).cmp(&(b.seconds, b.nanos)))
+        }
+        (Val::Duration(a), Val::Duration(b)) => {
+            Some((a.seconds, a.nanos).cmp(&(b.seconds, b.nanos)))
+        }
+        _ => num_cmp(a, b),
+    }
+}
+
+// Values of different types are not equal, except numbers of the same value.
+fn equals(a: &Val, b: &Val) -> bool {
+    match (a, b) {
+        (Val::Null, Val::Null) => true,
+        (Val::List(a), Val::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equals(a, b))
+        }
+        (Val::Map(a), Val::Map(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(k, v)| {
+                    b.iter()
+                        .find(|(l, _)| equals(k, l))
+                        .is_some_and(|(_, w)| equals(v, w))
+                })
+        }
+        (Val::Type(a), Val::Type(b)) => a == b,
+        (Val::Object(a), Val::Object(b)) => a == b,
+        _ => compare(a, b) == Some(Ordering::Equal),
+    }
+}
+
+fn nanos(seconds: i64, nanos: i32) -> i128 {
+    i128::from(seconds) * 1_000_000_000 + i128::from(nanos)
+}
+
+fn split_nanos(n: i128) -> Option<(i64, i32)> {
+    let seconds = i64::try_from(n.div_euclid(1_000_000_000)).ok()?;
+    Some((seconds, n.rem_euclid(1_000_000_000) as i32))
+}
+
+fn timestamp(n: i128) -> Result<Val, String> {
+    let (seconds, nanos) = split_nanos(n).ok_or("timestamp overflow")?;
+    let t = Timestamp { seconds, nanos };
+    if t.validate().is_err() {
+        return Err("timestamp overflow".to_owned());
+    }
+    Ok(Val::Timestamp(t))
+}
+
+fn duration(n: i128) -> Result<Val, String> {
+    // Durations are truncated toward zero, unlike timestamps.
+    let seconds = i64::try_from(n / 1_000_000_000).map_err(|_| "duration overflow")?;
+    let d = Duration {
+        seconds,
+        nanos: (n % 1_000_000_000) as i32,
+    };
+    if d.validate().is_err() {
+        return Err("duration overflow".to_owned());
+    }
+    Ok(Val::Duration(d))
+}
+
+fn overflow<T>(v: Option<T>) -> Result<T, String> {
+    v.ok_or_else(|| "integer overflow".to_owned())
+}
+
+fn arithmetic(function: &str, a: Val, b: Val) -> Result<Val, String> {
+    Ok(match (function, a, b) {
+        ("_+_", Val::Int(a), Val::Int(b)) => Val::Int(overflow(a.checked_add(b))?),
+        ("_-_", Val::Int(a), Val::Int(b)) => Val::Int(overflow(a.checked_sub(b))?),
+        ("_*_", Val::Int(a), Val::Int(b)) => Val::Int(overflow(a.checked_mul(b))?),
+        ("_/_", Val::Int(_), Val::Int(0)) | ("_%_", Val::Int(_), Val::Int(0)) => {
+            return Err("division by zero".to_owned())
+        }
+        ("_/_", Val::Int(a), Val::Int(b)) => Val::Int(overflow(a.checked_div(b))?),
+        ("_%_", Val::Int(a), Val::Int(b)) => Val::Int(overflow(a.checked_rem(b))?),
+        ("_+_", Val::Uint(a), Val::Uint(b)) => Val::Uint(overflow(a.checked_add(b))?),
+        ("_-_", Val::Uint(a), Val::Uint(b)) => Val::Uint(overflow(a.checked_sub(b))?),
+        ("_*_", Val::Uint(a), Val::Uint(b)) => Val::Uint(overflow(a.checked_mul(b))?),
+        ("_/_", Val::Uint(_), Val::Uint(0)) | ("_%_", Val::Uint(_), Val::Uint(0)) => {
+            return Err("division by zero".to_owned())
+        }
+        ("_/_", Val::Uint(a), Val::Uint(b)) => Val::Uint(a / b),
+        ("_%_", Val::Uint(a), Val::Uint(b)) => Val::Uint(a % b),
+        ("_+_", Val::Double(a), Val::Double(b)) => Val::Double(a + b),
+        ("_-_", Val::Double(a), Val::Double(b)) => Val::Double(a - b),
+        ("_*_", Val::Double(a), Val::Double(b)) => Val::Double(a * b),
+        ("_/_", Val::Double(a), Val::Double(b)) => Val::Double(a / b),
+        ("_+_", Val::String(a), Val::String(b)) => Val::String(a + &b),
+        ("_+_", Val::Bytes(mut a), Val::Bytes(b)) => {
+            a.extend(b);
+            Val::Bytes(a)
+        }
+        ("_+_", Val::List(mut a), Val::List(b)) => {
+            a.extend(b);
+            Val::List(a)
+        }
+        ("_+_", Val::Timestamp(t), Val::Duration(d))
+        | ("_+_", Val::Duration(d), Val::Timestamp(t)) => {
+            timestamp(nanos(t.seconds, t.nanos) + nanos(d.seconds, d.nanos))?
+        }
+        ("_-_", Val::Timestamp(t), Val::Duration(d)) => {
+            timestamp(nanos(t.seconds, t.nanos) - nanos(d.seconds, d.nanos))?
+        }
+        ("_-_", Val::Timestamp(a), Val::Timestamp(b)) => {
+            duration(nanos(a.seconds, a.nanos) - nanos(b.seconds, b.nanos))?
+        }
+        ("_+_", Val::Duration(a), Val::Duration(b)) => {
+            duration(nanos(a.seconds, a.nanos) + nanos(b.seconds, b.nanos))?
+        }
+        ("_-_", Val::Duration(a), Val::Duration(b)) => {
+            duration(nanos(a.seconds, a.nanos) - nanos(b.seconds, b.nanos))?
+        }
+        (_, a, b) => return Err(no_overload_message(function, &[a, b])),
+    })
+}
+
+fn index(container: Val, key: Val) -> Result<Val, String> {
+    match container {
+        Val::List(mut elements) => {
+            let i = match key {
+                Val::Int(i) => i,
+                Val::Uint(u) => i64::try_from(u).unwrap_or(i64::MAX),
+                Val::Double(d) if d.fract() == 0.0 => d as i64,
+                key => return Err(no_overload_message("_[_]", &[Val::List(elements), key])),
+            };
+            if i < 0 || i as usize >= elements.len() {
+                return Err(format!("index out of range: {}", i));
+            }
+            Ok(elements.swap_remove(i as usize))
+        }
+        Val::Map(entries) => match entries.iter().position(|(k, _)| equals(k, &key)) {
+            Some(i) => Ok(entries.into_iter().nth(i).unwrap().1),
+            None => Err(format!("no such key: {}", display(&key))),
+        },
+        container => Err(no_overload_message("_[_]", &[container, key])),
+    }
+}
+
+fn display(v: &Val) -> String {
+    match v {
+        Val::String(s) => format!("{:?}", s),
+        Val::Int(i) => i.to_string(),
+        Val::Uint(u) => format!("{}u", u),
+        Val::Bool(b) => b.to_string(),
+        v => v.type_name(),
+    }
+}
+
+fn builtin(function: &str, args: Vec<Val>) -> Result<Val, String> {
+    let mut it = args.into_iter();
+    let (a, b, c) = (it.next(), it.next(), it.next());
+    Ok(match (function, a, b, c) {
+        ("!_", Some(Val::Bool(b)), None, None) => Val::Bool(!b),
+        ("-_", Some(Val::Int(i)), None, None) => Val::Int(overflow(i.checked_neg())?),
+        ("-_", Some(Val::Double(d)), None, None) => Val::Double(-d),
+        ("_==_", Some(a), Some(b), None) => Val::Bool(equals(&a, &b)),
+        ("_!=_", Some(a), Some(b), None) => Val::Bool(!equals(&a, &b)),
+        ("_<_", Some(a), Some(b), None)
+        | ("_<=_", Some(a), Some(b), None)
+        | ("_>_", Some(a), Some(b), None)
+        | ("_>=_", Some(a), Some(b), None) => {
+            let ordering = match compare(&a, &b) {
+                Some(ordering) => ordering,
+                // NaN is not ordered.
+                None if num_cmp(&a, &Val::Int(0)).is_some()
+                    && num_cmp(&b, &Val::Int(0)).is_some() =>
+                {
+                    return Ok(Val::Bool(false))
+                }
+                None => return Err(no_overload_message(function, &[a, b])),
+            };
+            Val::Bool(match function {
+                "_<_" => ordering == Ordering::Less,
+                "_<=_" => ordering != Ordering::Greater,
+                "_>_" => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+        ("_+_", Some(a), Some(b), None)
+        | ("_-_", Some(a), Some(b), None)
+        | ("_*_", Some(a), Some(b), None)
+        | ("_/_", Some(a), Some(b), None)
+        | ("_%_", Some(a), Some(b), None) => arithmetic(function, a, b)?,
+        ("_[_]", Some(a), Some(b), None) => index(a, b)?,
+        ("@in", Some(a), Some(Val::List(l)), None) => Val::Bool(l.iter().any(|v| equals(&a, v))),
+        ("@in", Some(a), Some(Val::Map(m)), None) => {
+            Val::Bool(m.iter().any(|(k, _)| equals(&a, k)))
+        }
+        ("size", Some(v), None, None) => Val::Int(match v {
+            Val::String(s) => s.chars().count() as i64,
+            Val::Bytes(b) => b.len() as i64,
+            Val::List(l) => l.len() as i64,
+            Val::Map(m) => m.len() as i64,
+            v => return Err(no_overload_message(function, &[v])),
+        }),
+        ("contains", Some(Val::String(s)), Some(Val::String(t)), None) => Val::Bool(s.contains(&t)),
+        ("startsWith", Some(Val::String(s)), Some(Val::String(t)), None) => {
+            Val::Bool(s.starts_with(&t))
+        }
+        ("endsWith", Some(Val::String(s)), Some(Val::String(t)), None) => {
+            Val::Bool(s.ends_with(&t))
+        }
+        ("matches", Some(Val::String(s)), Some(Val::String(re)), None) => {
+            Val::Bool(matches(&s, &re)?)
+        }
+        ("type", Some(v), None, None) => Val::Type(v.type_name()),
+        ("dyn", Some(v), None, None) => v,
+        ("int", Some(v), None, None)
+        | ("uint", Some(v), None, None)
+        | ("double", Some(v), None, None)
+        | ("string", Some(v), None, None)
+        | ("bytes", Some(v), None, None)
+        | ("bool", Some(v), None, None)
+        | ("timestamp", Some(v), None, None)
+        | ("duration", Some(v), None, None) => convert(function, v)?,
+        (_, Some(Val::Timestamp(t)), tz, None) if function.starts_with("get") => {
+            let offset = match tz {
+                None => 0,
+                Some(Val::String(tz)) => offset(&tz, t.seconds)?,
+                Some(v) => return Err(no_overload_message(function, &[Val::Timestamp(t), v])),
+            };
+            timestamp_field(function, &t, offset)
+                .ok_or_else(|| no_overload_message(function, &[Val::Timestamp(t.clone())]))?
+        }
+        (_, Some(Val::Duration(d)), None, None) if function.starts_with("get") => {
+            Val::Int(match function {
+                "getHours" => d.seconds / 3600,
+                "getMinutes" => d.seconds / 60,
+                "getSeconds" => d.seconds,
+                "getMilliseconds" => d.seconds * 1000 + i64::from(d.nanos / 1_000_000),
+                _ => return Err(no_overload_message(function, &[Val::Duration(d)])),
+            })
+        }
+        (_, a, b, c) => {
+            let args = a
+                .into_iter()
+                .chain(b)
+                .chain(c)
+                .chain(it)
+                .collect::<Vec<_>>();
+            return Err(no_overload_message(function, &args));
+        }
+    })
+}
+
+#[cfg(feature = "regex")]
+fn matches(s: &str, re: &str) -> Result<bool, String> {
+    let re = regex::Regex::new(re).map_err(|e| format!("invalid regular expression: {}", e))?;
+    Ok(re.is_match(s))
+}
+
+#[cfg(not(feature = "regex"))]
+fn matches(_s: &str, _re: &str) -> Result<bool, String> {
+    Err("matches requires the regex feature".to_owned())
+}
+
+fn convert(function: &str, v: Val) -> Result<Val, String> {
+    let range = || format!("{} conversion out of range", function);
+    Ok(match (function, v) {
+        ("int", Val::Int(i)) => Val::Int(i),
+        ("int", Val::Uint(u)) => Val::Int(i64::try_from(u).map_err(|_| range())?),
+        // The bounds are exclusive since they are not exactly representable.
+        ("int", Val::Double(d))
+            if d > -9.223_372_036_854_776e18 && d < 9.223_372_036_854_776e18 =>
+        {
+            Val::Int(d as i64)
+        }
+        ("int", Val::String(s)) => Val::Int(s.parse().map_err(|_| range())?),
+        ("int", Val::Timestamp(t)) => Val::Int(t.seconds),
+        ("uint", Val::Uint(u)) => Val::Uint(u),
+        ("uint", Val::Int(i)) => Val::Uint(u64::try_from(i).map_err(|_| range())?),
+        ("uint", Val::Double(d)) if d > -1.0 && d < 1.844_674_407_370_955_2e19 => {
+            Val::Uint(d as u64)
+        }
+        ("uint", Val::String(s)) => Val::Uint(s.parse().map_err(|_| range())?),
+        ("double", Val::Double(d)) => Val::Double(d),
+        ("double", Val::Int(i)) => Val::Double(i as f64),
+        ("double", Val::Uint(u)) => Val::Double(u as f64),
+        ("double", Val::String(s)) => Val::Double(s.parse().map_err(|_| range())?),
+        ("string", Val::String(s)) => Val::String(s),
+        ("string", Val::Int(i)) => Val::String(i.to_string()),
+        ("string", Val::Uint(u)) => Val::String(u.to_string()),
+        ("string", Val::Double(d)) => Val::String(d.to_string()),
+        ("string", Val::Bool(b)) => Val::String(b.to_string()),
+        ("string", Val::Bytes(b)) => {
+            Val::String(String::from_utf8(b).map_err(|_| "invalid UTF-8 in bytes".to_owned())?)
+        }
+        ("string", Val::Timestamp(t)) => Val::String(t.to_rfc3339().map_err(|e| e.to_string())?),
+        ("string", Val::Duration(d)) => Val::String(d.to_json_string().map_err(|e| e.to_string())?),
+        ("bytes", Val::Bytes(b)) => Val::Bytes(b),
+        ("bytes", Val::String(s)) => Val::Bytes(s.into_bytes()),
+        ("bool", Val::Bool(b)) => Val::Bool(b),
+        ("bool", Val::String(s)) => Val::Bool(match s.as_str() {
+            "1" | "t" | "true" | "TRUE" | "True" => true,
+            "0" | "f" | "false" | "FALSE" | "False" => false,
+            _ => return Err(format!("cannot convert '{}' to bool", s)),
+        }),
+        ("timestamp", Val::Timestamp(t)) => Val::Timestamp(t),
+        ("timestamp", Val::String(s)) => Val::Timestamp(
+            Timestamp::parse_rfc3339(&s).map_err(|_| format!("invalid timestamp '{}'", s))?,
+        ),
+        ("timestamp", Val::Int(seconds)) => timestamp(nanos(seconds, 0))?,
+        ("duration", Val::Duration(d)) => Val::Duration(d),
+        ("duration", Val::String(s)) => {
+            duration(parse_duration(&s).ok_or_else(|| format!("invalid duration '{}'", s))?)?
+        }
+        (_, v) => return Err(no_overload_message(function, &[v])),
+    })
+}
+
+// Parses a duration in the format of Go, e.g. `1h30m` or `-1.5s`, to nanoseconds.
+fn parse_duration(s: &str) -> Option<i128> {
+    let (sign, mut s) = match s.strip_prefix('-') {
+        Some(s) => (-1, s),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if s == "0" {
+        return Some(0);
+    }
+    if s.is_empty() {
+        return None;
+    }
+    let mut total = 0i128;
+    while !s.is_empty() {
+        let digits = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, rest) = s.split_at(digits);
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let (unit, rest) = rest.split_at(unit_len);
+        let unit: i128 = match unit {
+            "h" => 3_600_000_000_000,
+            "m" => 60_000_000_000,
+            "s" => 1_000_000_000,
+            "ms" => 1_000_000,
+            "us" | "µs" => 1_000,
+            "ns" => 1,
+            _ => return None,
+        };
+        let (int, frac) = match number.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (number, ""),
+        };
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let int: i128 = if int.is_empty() { 0 } else { int.parse().ok()? };
+        let mut value = int.checked_mul(unit)?;
+        let mut scale = unit;
+        for d in frac.chars() {
+            scale /= 10;
+            value += i128::from(d.to_digit(10)?) * scale;
+        }
+        total = total.checked_add(value)?;
+        s = rest;
+    }
+    Some(sign * total)
+}
+
+// The offset from UTC in seconds of a time zone at `seconds` since the epoch.
+fn offset(tz: &str, seconds: i64) -> Result<i64, String> {
+    if tz == "UTC" || tz == "Z" {
+        return Ok(0);
+    }
+    let (sign, hm) = match tz.as_bytes().first() {
+        Some(b'+') => (1, &tz[1..]),
+        Some(b'-') => (-1, &tz[1..]),
+        _ => (1, tz),
+    };
+    if let Some((h, m)) = hm.split_once(':') {
+        if let (Ok(h), Ok(m)) = (h.parse::<i64>(), m.parse::<i64>()) {
+            if h.to_string().len() <= 2 && (0..24).contains(&h) && (0..60).contains(&m) {
+                return Ok(sign * (h * 3600 + m * 60));
+            }
+        }
+    }
+    zone_offset(tz, seconds)
+}
+
+#[cfg(feature = "time-zone")]
+fn zone_offset(tz: &str, seconds: i64) -> Result<i64, String> {
+    use chrono::{Offset, TimeZone};
+
+    let zone = tz
+        .parse::<chrono_tz::Tz>()
+        .map_err(|_| format!("unknown time zone '{}'", tz))?;
+    let utc = chrono::DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| "timestamp out of range".to_owned())?;
+    Ok(zone
+        .offset_from_utc_datetime(&utc.naive_utc())
+        .fix()
+        .local_minus_utc()
+        .into())
+}
+
+#[cfg(not(feature = "time-zone"))]
+fn zone_offset(tz: &str, _seconds: i64) -> Result<i64, String> {
+    Err(format!(
+        "unknown time zone '{}', IANA time zones require the time-zone feature",
+        tz
+    ))
+}
+
+fn timestamp_field(function: &str, t: &Timestamp, offset: i64) -> Option<Val> {
+    let local = t.seconds + offset;
+    let days = local.div_euclid(86_400);
+    let time = local.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let day_of_year = days - days_from_civil(year, 1, 1);
+    Some(Val::Int(match function {
+        "getFullYear" => year,
+        "getMonth" => month - 1,
+        "getDayOfYear" => day_of_year,
+        "getDate" => day,
+        "getDayOfMonth" => day - 1,
+        // 1970-01-01 was a Thursday.
+        "getDayOfWeek" => (days + 4).rem_euclid(7),
+        "getHours" => time / 3600,
+        "getMinutes" => time % 3600 / 60,
+        "getSeconds" => time % 60,
+        "getMilliseconds" => i64::from(t.nanos / 1_000_000),
+        _ => return None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0"), Some(0));
+        assert_eq!(parse_duration("1h30m"), Some(5_400_000_000_000));
+        assert_eq!(parse_duration("-1.5s"), Some(-1_500_000_000));
+        assert_eq!(parse_duration("2ms3us4ns"), Some(2_003_004));
+        assert_eq!(parse_duration(".5m"), Some(30_000_000_000));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1"), None);
+        assert_eq!(parse_duration("1d"), None);
+        assert_eq!(parse_duration("h"), None);
+    }
+
+    #[test]
+    fn test_offset() {
+        assert_eq!(offset("UTC", 0), Ok(0));
+        assert_eq!(offset("+05:30", 0), Ok(19_800));
+        assert_eq!(offset("-08:00", 0), Ok(-28_800));
+        assert_eq!(offset("02:00", 0), Ok(7_200));
+        assert!(offset("+25:00", 0).is_err());
+        #[cfg(feature = "time-zone")]
+        {
+            // 2021-01-01 and 2021-07-01.
+            assert_eq!(offset("America/Los_Angeles", 1_609_459_200), Ok(-28_800));
+            assert_eq!(offset("America/Los_Angeles", 1_625_097_600), Ok(-25_200));
+        }
+    }
+
+    #[test]
+    fn test_equals() {
+        assert!(equals(&Val::Int(1), &Val::Uint(1)));
+        assert!(equals(&Val::Double(1.0), &Val::Int(1)));
+        assert!(!equals(&Val::Int(-1), &Val::Uint(u64::MAX)));
+        assert!(!equals(&Val::Int(1), &Val::String("1".into())));
+        assert!(!equals(&Val::Double(f64::NAN), &Val::Double(f64::NAN)));
+        assert!(equals(
+            &Val::Map(vec![(Val::Int(1), Val::Bool(true))]),
+            &Val::Map(vec![(Val::Uint(1), Val::Bool(true))]),
+        ));
+        assert!(!equals(&Val::List(vec![Val::Null]), &Val::List(vec![])));
+    }
+}
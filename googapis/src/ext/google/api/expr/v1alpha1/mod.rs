@@ -0,0 +1,312 @@
+mod checker;
+mod interpreter;
+mod parser;
+
+use prost::Message;
+
+pub use self::{
+    checker::{check, CheckError},
+    interpreter::{eval, Activation, EvalError},
+    parser::{parse, ParseError},
+};
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        value::Kind::BoolValue(b).into()
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        value::Kind::Int64Value(i).into()
+    }
+}
+
+impl From<u64> for Value {
+    fn from(u: u64) -> Self {
+        value::Kind::Uint64Value(u).into()
+    }
+}
+
+impl From<f64> for Value {
+    fn from(d: f64) -> Self {
+        value::Kind::DoubleValue(d).into()
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        value::Kind::StringValue(s.to_owned()).into()
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        value::Kind::StringValue(s).into()
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Self {
+        value::Kind::BytesValue(b).into()
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Self {
+        value::Kind::ListValue(ListValue { values }).into()
+    }
+}
+
+/// A map with string keys.
+impl<V: Into<Value>> From<Vec<(&str, V)>> for Value {
+    fn from(entries: Vec<(&str, V)>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(k, v)| map_value::Entry {
+                key: Some(k.into()),
+                value: Some(v.into()),
+            })
+            .collect();
+        value::Kind::MapValue(MapValue { entries }).into()
+    }
+}
+
+/// A `google.protobuf.Timestamp` object.
+impl From<prost_types::Timestamp> for Value {
+    fn from(t: prost_types::Timestamp) -> Self {
+        value::Kind::ObjectValue(prost_types::Any {
+            type_url: "type.googleapis.com/google.protobuf.Timestamp".to_owned(),
+            value: t.encode_to_vec(),
+        })
+        .into()
+    }
+}
+
+/// A `google.protobuf.Duration` object.
+impl From<prost_types::Duration> for Value {
+    fn from(d: prost_types::Duration) -> Self {
+        value::Kind::ObjectValue(prost_types::Any {
+            type_url: "type.googleapis.com/google.protobuf.Duration".to_owned(),
+            value: d.encode_to_vec(),
+        })
+        .into()
+    }
+}
+
+impl From<value::Kind> for Value {
+    fn from(kind: value::Kind) -> Self {
+        Value { kind: Some(kind) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn run(source: &str, activation: &Activation) -> Result<Value, EvalError> {
+        let parsed = parse(source).unwrap();
+        eval(parsed.expr.as_ref().unwrap(), activation)
+    }
+
+    fn ints(values: &[i64]) -> Value {
+        values
+            .iter()
+            .map(|&i| i.into())
+            .collect::<Vec<Value>>()
+            .into()
+    }
+
+    fn ok(source: &str) -> Value {
+        run(source, &Activation::new()).unwrap_or_else(|e| panic!("{}: {}", source, e))
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(ok("1 + 2 * 3"), 7i64.into());
+        assert_eq!(ok("(1 + 2) * 3 == 9"), true.into());
+        assert_eq!(ok("7 / 2 + 7 % 2"), 4i64.into());
+        assert_eq!(ok("1u + 2u"), 3u64.into());
+        assert_eq!(ok("0.5 + 1.0"), 1.5.into());
+        assert_eq!(ok("-(-1)"), 1i64.into());
+        assert_eq!(ok("'a' + \"b\" + '''c'''"), "abc".into());
+        assert_eq!(ok("size('héllo') + size(b'\\xff')"), 6i64.into());
+        assert_eq!(ok("[1, 2] + [3]"), ints(&[1, 2, 3]));
+        assert_eq!(ok("{'a': 1}['a']"), 1i64.into());
+        assert_eq!(ok("{'a': {'b': true}}.a.b"), true.into());
+        assert_eq!(ok("has({'a': 1}.a) && !has({'a': 1}.b)"), true.into());
+        assert_eq!(ok("2 in [1, 2] && 'a' in {'a': 1}"), true.into());
+        assert_eq!(
+            ok("1 == 1u && 1 == 1.0 && 2u < 3.5 && -1 < 1u"),
+            true.into()
+        );
+        assert_eq!(ok("1 == 'a'"), false.into());
+        assert_eq!(ok("true ? 'y' : 'n'"), "y".into());
+        assert_eq!(
+            ok("'abc'.startsWith('ab') && 'abc'.endsWith('bc')"),
+            true.into()
+        );
+        assert_eq!(ok("'abc'.contains('d')"), false.into());
+        assert_eq!(ok("int('12') + int(2.9) + int(3u)"), 17i64.into());
+        assert_eq!(
+            ok("string(1.5) + string(true) + string(b'x')"),
+            "1.5truex".into()
+        );
+        assert_eq!(ok("type(1) == int && type('') == string"), true.into());
+        assert_eq!(ok("dyn(1) == 1"), true.into());
+        assert_eq!(ok("null == null"), true.into());
+    }
+
+    #[test]
+    fn test_eval_macros() {
+        assert_eq!(ok("[1, 2, 3].all(x, x > 0)"), true.into());
+        assert_eq!(ok("[1, 2, 3].exists(x, x > 2)"), true.into());
+        assert_eq!(ok("[1, 2, 3].exists_one(x, x > 1)"), false.into());
+        assert_eq!(ok("[1, 2, 3].map(x, x * 2)"), ints(&[2, 4, 6]));
+        assert_eq!(ok("[1, 2, 3].map(x, x > 1, x * 2)"), ints(&[4, 6]));
+        assert_eq!(ok("[1, 2, 3].filter(x, x != 2)"), ints(&[1, 3]));
+        assert_eq!(ok("{'a': 1, 'b': 2}.all(k, k != 'c')"), true.into());
+        assert_eq!(ok("[[1], [2, 3]].exists(l, l.all(x, x > 1))"), true.into());
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let err = |source: &str| run(source, &Activation::new()).unwrap_err().message;
+        assert_eq!(err("9223372036854775807 + 1"), "integer overflow");
+        assert_eq!(err("0u - 1u"), "integer overflow");
+        assert_eq!(err("1 / 0"), "division by zero");
+        assert_eq!(err("[1][1]"), "index out of range: 1");
+        assert_eq!(err("{'a': 1}['b']"), "no such key: \"b\"");
+        assert_eq!(err("x"), "undeclared reference to 'x'");
+        assert_eq!(err("1 + 'a'"), "no such overload: +(int, string)");
+        assert_eq!(err("int('x')"), "int conversion out of range");
+        assert_eq!(err("int(1e19)"), "no such overload: int(double)");
+
+        // Errors are absorbed by the logical operators.
+        assert_eq!(ok("false && 1 / 0 == 1"), false.into());
+        assert_eq!(ok("1 / 0 == 1 || true"), true.into());
+        assert_eq!(ok("[0, 1].exists(x, 1 / x == 1)"), true.into());
+        assert_eq!(err("true && 1 / 0 == 1"), "division by zero");
+
+        // The id is the one of the failing expression.
+        let parsed = parse("1 +\n  x").unwrap();
+        let e = eval(parsed.expr.as_ref().unwrap(), &Activation::new()).unwrap_err();
+        assert_eq!(parsed.source_info.unwrap().positions[&e.id], 6);
+    }
+
+    #[test]
+    fn test_eval_deep() {
+        // The deepest expressions accepted by the parser do not overflow the stack.
+        let sources = [
+            format!("{}true", "!".repeat(98)),
+            format!("{}1", "1 + ".repeat(98)),
+            format!("{}1{}{}", "[".repeat(48), "]".repeat(48), "[0]".repeat(48)),
+        ];
+        for source in sources.iter() {
+            let parsed = parse(source).unwrap();
+            check(&parsed, &[]).unwrap();
+            eval(parsed.expr.as_ref().unwrap(), &Activation::new()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_eval_time() {
+        // 2021-03-04T05:06:07.890Z, a Thursday.
+        let t = prost_types::Timestamp {
+            seconds: 1_614_834_367,
+            nanos: 890_000_000,
+        };
+        let activation = Activation::new().bind("t", t);
+        let ok =
+            |source: &str| run(source, &activation).unwrap_or_else(|e| panic!("{}: {}", source, e));
+        assert_eq!(ok("t.getFullYear()"), 2021i64.into());
+        assert_eq!(ok("t.getMonth()"), 2i64.into());
+        assert_eq!(ok("t.getDayOfYear()"), 62i64.into());
+        assert_eq!(ok("t.getDate()"), 4i64.into());
+        assert_eq!(ok("t.getDayOfMonth()"), 3i64.into());
+        assert_eq!(ok("t.getDayOfWeek()"), 4i64.into());
+        assert_eq!(ok("t.getHours()"), 5i64.into());
+        assert_eq!(ok("t.getHours('-08:00')"), 21i64.into());
+        assert_eq!(ok("t.getDate('-08:00')"), 3i64.into());
+        assert_eq!(ok("t.getMinutes('+05:30')"), 36i64.into());
+        assert_eq!(ok("t.getSeconds()"), 7i64.into());
+        assert_eq!(ok("t.getMilliseconds()"), 890i64.into());
+        assert_eq!(
+            ok("t > timestamp('2021-01-01T00:00:00Z') && t - duration('1h') < t"),
+            true.into()
+        );
+        assert_eq!(
+            ok("string(t + duration('1h30m'))"),
+            "2021-03-04T06:36:07.890Z".into()
+        );
+        assert_eq!(
+            ok("t - timestamp('2021-03-04T05:06:06.890Z') == duration('1s')"),
+            true.into()
+        );
+        assert_eq!(ok("duration('1h30m').getMinutes()"), 90i64.into());
+        assert_eq!(ok("string(duration('-1.5s'))"), "-1.500s".into());
+        assert_eq!(ok("int(timestamp('1970-01-01T00:01:00Z'))"), 60i64.into());
+        assert!(run("t.getHours('Mars/Olympus')", &activation).is_err());
+        #[cfg(feature = "time-zone")]
+        assert_eq!(ok("t.getHours('Asia/Tokyo')"), 14i64.into());
+    }
+
+    #[test]
+    fn test_eval_iam_condition() {
+        let condition = "request.time < timestamp('2030-01-01T00:00:00Z') && \
+            resource.type == 'secretmanager.googleapis.com/Secret' && \
+            resource.name.startsWith('projects/p/secrets/prod-')";
+        let parsed = parse(condition).unwrap();
+        let expr = parsed.expr.as_ref().unwrap();
+        let activation = |name: &str| {
+            Activation::new()
+                .bind(
+                    "request.time",
+                    prost_types::Timestamp::from(SystemTime::now()),
+                )
+                .bind(
+                    "resource",
+                    vec![
+                        ("type", "secretmanager.googleapis.com/Secret"),
+                        ("name", name),
+                    ],
+                )
+        };
+        assert_eq!(
+            eval(expr, &activation("projects/p/secrets/prod-db")),
+            Ok(true.into())
+        );
+        assert_eq!(
+            eval(expr, &activation("projects/p/secrets/dev-db")),
+            Ok(false.into())
+        );
+    }
+
+    #[test]
+    fn test_eval_functions() {
+        let activation =
+            Activation::new()
+                .bind("x", 2i64)
+                .function("twice", |args| match &args[0].kind {
+                    Some(value::Kind::Int64Value(i)) => Ok((i * 2).into()),
+                    _ => Err("twice expects an int".to_owned()),
+                });
+        assert_eq!(run("twice(x) + x.twice()", &activation), Ok(8i64.into()));
+        assert_eq!(
+            run("twice('a')", &activation).unwrap_err().message,
+            "twice expects an int"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_eval_matches() {
+        assert_eq!(
+            ok("'projects/p1'.matches('^projects/[a-z0-9]+$')"),
+            true.into()
+        );
+        assert_eq!(ok("matches('abc', 'd')"), false.into());
+        assert!(run("'a'.matches('(')", &Activation::new()).is_err());
+    }
+}
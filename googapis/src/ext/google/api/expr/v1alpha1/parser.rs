@@ -0,0 +1,1087 @@
+use std::{collections::HashMap, convert::TryFrom, error, fmt};
+
+use super::{
+    constant::ConstantKind,
+    expr::{
+        create_struct::{entry::KeyKind, Entry},
+        Call, Comprehension, CreateList, CreateStruct, ExprKind, Ident, Select,
+    },
+    Constant, Expr, ParsedExpr, SourceInfo,
+};
+
+// The variable holding the result of the comprehensions the macros expand to.
+pub(super) const ACCUMULATOR: &str = "__result__";
+
+// The depth above which expressions are rejected instead of overflowing the stack, when parsed
+// or later when checked and evaluated. Operator chains count as nested.
+const MAX_DEPTH: usize = 100;
+
+const RESERVED: &[&str] = &[
+    "as",
+    "break",
+    "const",
+    "continue",
+    "else",
+    "for",
+    "function",
+    "if",
+    "import",
+    "let",
+    "loop",
+    "package",
+    "namespace",
+    "return",
+    "var",
+    "void",
+    "while",
+];
+
+/// An error returned when an expression cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The offset of the error in the source, in code points.
+    pub offset: usize,
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    /// The column of the error in code points, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(u64),
+    Uint(u64),
+    Double(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Ident(String),
+    Punct(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(v) => write!(f, "{}", v),
+            Token::Uint(v) => write!(f, "{}u", v),
+            Token::Double(v) => write!(f, "{:?}", v),
+            Token::String(v) => write!(f, "{:?}", v),
+            Token::Bytes(_) => f.write_str("bytes literal"),
+            Token::Ident(v) => f.write_str(v),
+            Token::Punct(v) => f.write_str(v),
+            Token::Eof => f.write_str("end of input"),
+        }
+    }
+}
+
+// Longest first, so that `<=` is not read as `<`.
+const PUNCTS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "?", ":", ".", ",",
+    "(", ")", "[", "]", "{", "}",
+];
+
+struct Lexer<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl Lexer<'_> {
+    fn peek(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).copied()
+    }
+
+    fn error(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        error(self.chars, offset, message)
+    }
+
+    // Returns the next token and its offset.
+    fn next(&mut self) -> Result<(usize, Token), ParseError> {
+        loop {
+            match self.peek(0) {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('/') if self.peek(1) == Some('/') => {
+                    while !matches!(self.peek(0), None | Some('\n')) {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        let c = match self.peek(0) {
+            None => return Ok((start, Token::Eof)),
+            Some(c) => c,
+        };
+        let token = if c.is_ascii_digit() || (c == '.' && self.is_digit(1)) {
+            self.number()?
+        } else if c == '"' || c == '\'' {
+            Token::String(self.string(false, false)?)
+        } else if c == '_' || c.is_ascii_alphabetic() {
+            let mut end = self.pos;
+            while self
+                .chars
+                .get(end)
+                .is_some_and(|&c| c == '_' || c.is_ascii_alphanumeric())
+            {
+                end += 1;
+            }
+            let word = self.chars[self.pos..end].iter().collect::<String>();
+            let prefix = word.to_ascii_lowercase();
+            if matches!(prefix.as_str(), "r" | "b" | "rb" | "br")
+                && matches!(self.chars.get(end), Some('"' | '\''))
+            {
+                self.pos = end;
+                let raw = prefix.contains('r');
+                let s = self.string(raw, prefix.contains('b'))?;
+                if prefix.contains('b') {
+                    Token::Bytes(s.chars().map(|c| c as u8).collect())
+                } else {
+                    Token::String(s)
+                }
+            } else {
+                self.pos = end;
+                Token::Ident(word)
+            }
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|p| p.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c)))
+                .ok_or_else(|| self.error(start, format!("unexpected character {:?}", c)))?;
+            self.pos += punct.len();
+            Token::Punct(punct)
+        };
+        Ok((start, token))
+    }
+
+    fn is_digit(&self, n: usize) -> bool {
+        self.peek(n).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    fn number(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        let out_of_range = |l: &Self| l.error(start, "integer literal out of range");
+        if self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X')) {
+            self.pos += 2;
+            let digits = self.take_while(|c| c.is_ascii_hexdigit());
+            if digits.is_empty() {
+                return Err(self.error(start, "invalid hexadecimal literal"));
+            }
+            let v = u64::from_str_radix(&digits, 16).map_err(|_| out_of_range(self))?;
+            return Ok(self.int_suffix(v));
+        }
+
+        let mut text = self.take_while(|c| c.is_ascii_digit());
+        let mut double = false;
+        if self.peek(0) == Some('.') && self.is_digit(1) {
+            self.pos += 1;
+            text.push('.');
+            text += &self.take_while(|c| c.is_ascii_digit());
+            double = true;
+        }
+        if matches!(self.peek(0), Some('e' | 'E')) {
+            let sign = matches!(self.peek(1), Some('+' | '-')) as usize;
+            if self.is_digit(1 + sign) {
+                text.push('e');
+                if sign == 1 {
+                    text.push(self.peek(1).unwrap());
+                }
+                self.pos += 1 + sign;
+                text += &self.take_while(|c| c.is_ascii_digit());
+                double = true;
+            }
+        }
+        if double {
+            let v = text
+                .parse()
+                .map_err(|_| self.error(start, "invalid double literal"))?;
+            Ok(Token::Double(v))
+        } else {
+            let v = text.parse().map_err(|_| out_of_range(self))?;
+            Ok(self.int_suffix(v))
+        }
+    }
+
+    fn int_suffix(&mut self, v: u64) -> Token {
+        if matches!(self.peek(0), Some('u' | 'U')) {
+            self.pos += 1;
+            Token::Uint(v)
+        } else {
+            Token::Int(v)
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek(0).filter(|&c| f(c)) {
+            s.push(c);
+            self.pos += 1;
+        }
+        s
+    }
+
+    // Reads a quoted string. The characters of bytes literals are octets: the non-ASCII
+    // characters of the source are written as their UTF-8 encoding, escapes as is.
+    fn string(&mut self, raw: bool, bytes: bool) -> Result<String, ParseError> {
+        let start = self.pos;
+        let quote = self.peek(0).unwrap();
+        let triple = self.peek(1) == Some(quote) && self.peek(2) == Some(quote);
+        self.pos += if triple { 3 } else { 1 };
+        let mut s = String::new();
+        let push = |s: &mut String, c: char| {
+            if bytes && !c.is_ascii() {
+                let mut buf = [0; 4];
+                s.extend(c.encode_utf8(&mut buf).bytes().map(char::from));
+            } else {
+                s.push(c);
+            }
+        };
+        loop {
+            let c = match self.peek(0) {
+                None => return Err(self.error(start, "unterminated string")),
+                Some('\n' | '\r') if !triple => {
+                    return Err(self.error(start, "unterminated string"))
+                }
+                Some(c) => c,
+            };
+            if c == quote
+                && (!triple || (self.peek(1) == Some(quote) && self.peek(2) == Some(quote)))
+            {
+                self.pos += if triple { 3 } else { 1 };
+                return Ok(s);
+            }
+            self.pos += 1;
+            if c != '\\' || raw {
+                push(&mut s, c);
+                continue;
+            }
+            let escape = self.pos - 1;
+            let invalid = |l: &Self| l.error(escape, "invalid escape sequence");
+            let e = self.peek(0).ok_or_else(|| invalid(self))?;
+            self.pos += 1;
+            let c = match e {
+                'a' => '\x07',
+                'b' => '\x08',
+                'f' => '\x0c',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'v' => '\x0b',
+                '\\' | '?' | '"' | '\'' | '`' => e,
+                'x' | 'X' | 'u' | 'U' | '0'..='7' => {
+                    let (len, radix) = match e {
+                        'x' | 'X' => (2, 16),
+                        'u' | 'U' if bytes => return Err(invalid(self)),
+                        'u' => (4, 16),
+                        'U' => (8, 16),
+                        _ => {
+                            self.pos -= 1;
+                            (3, 8)
+                        }
+                    };
+                    let digits = (0..len)
+                        .map(|i| self.peek(i).filter(|c| c.is_digit(radix)))
+                        .collect::<Option<String>>()
+                        .ok_or_else(|| invalid(self))?;
+                    self.pos += len;
+                    let v = u32::from_str_radix(&digits, radix).unwrap();
+                    if bytes && matches!(e, 'x' | 'X' | '0'..='7') {
+                        s.push(char::from(u8::try_from(v).map_err(|_| invalid(self))?));
+                        continue;
+                    }
+                    std::char::from_u32(v).ok_or_else(|| invalid(self))?
+                }
+                _ => return Err(invalid(self)),
+            };
+            push(&mut s, c);
+        }
+    }
+}
+
+fn error(chars: &[char], offset: usize, message: impl Into<String>) -> ParseError {
+    let before = &chars[..offset.min(chars.len())];
+    let line_start = before.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+    ParseError {
+        offset,
+        line: before.iter().filter(|&&c| c == '\n').count() + 1,
+        column: offset - line_start + 1,
+        message: message.into(),
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    token: Token,
+    offset: usize,
+    depth: usize,
+    next_id: i64,
+    positions: HashMap<i64, i32>,
+    macro_calls: HashMap<i64, Expr>,
+}
+
+/// Parses a CEL expression, expanding the `has`, `all`, `exists`, `exists_one`, `map` and
+/// `filter` macros into field tests and comprehensions.
+///
+/// Every node has a distinct id, starting at 1, and its offset in code points in
+/// [`SourceInfo::positions`].
+pub fn parse(source: &str) -> Result<ParsedExpr, ParseError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut lexer = Lexer {
+        chars: &chars,
+        pos: 0,
+    };
+    let (offset, token) = lexer.next()?;
+    let mut p = Parser {
+        lexer,
+        token,
+        offset,
+        depth: 0,
+        next_id: 1,
+        positions: HashMap::new(),
+        macro_calls: HashMap::new(),
+    };
+    let expr = p.expr()?;
+    if p.token != Token::Eof {
+        return Err(p.unexpected());
+    }
+
+    let line_offsets = chars
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c == '\n')
+        .map(|(i, _)| i as i32 + 1)
+        .collect();
+    Ok(ParsedExpr {
+        expr: Some(expr),
+        source_info: Some(SourceInfo {
+            syntax_version: "cel1".to_owned(),
+            location: String::new(),
+            line_offsets,
+            positions: p.positions,
+            macro_calls: p.macro_calls,
+        }),
+    })
+}
+
+impl Parser<'_> {
+    fn advance(&mut self) -> Result<Token, ParseError> {
+        let (offset, token) = self.lexer.next()?;
+        self.offset = offset;
+        Ok(std::mem::replace(&mut self.token, token))
+    }
+
+    fn eat(&mut self, punct: &str) -> Result<bool, ParseError> {
+        if self.is(punct) {
+            self.advance()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        if self.eat(punct)? {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}', found {}", punct, self.token)))
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        self.lexer.error(self.offset, message)
+    }
+
+    fn unexpected(&self) -> ParseError {
+        self.error(format!("unexpected {}", self.token))
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(&self.token, Token::Punct(p) if *p == punct)
+    }
+
+    fn node(&mut self, offset: usize, kind: ExprKind) -> Expr {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.positions.insert(id, offset as i32);
+        Expr {
+            id,
+            expr_kind: Some(kind),
+        }
+    }
+
+    fn ident(&mut self, offset: usize, name: &str) -> Expr {
+        let name = name.to_owned();
+        self.node(offset, ExprKind::IdentExpr(Ident { name }))
+    }
+
+    fn constant(&mut self, offset: usize, kind: ConstantKind) -> Expr {
+        let constant = Constant {
+            constant_kind: Some(kind),
+        };
+        self.node(offset, ExprKind::ConstExpr(constant))
+    }
+
+    fn call(
+        &mut self,
+        offset: usize,
+        function: &str,
+        target: Option<Expr>,
+        args: Vec<Expr>,
+    ) -> Expr {
+        let call = Call {
+            target: target.map(Box::new),
+            function: function.to_owned(),
+            args,
+        };
+        self.node(offset, ExprKind::CallExpr(Box::new(call)))
+    }
+
+    // Enters a nested expression, left with `self.depth -= 1`.
+    fn nest(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        self.nest()?;
+        let cond = self.or()?;
+        let e = if self.is("?") {
+            let offset = self.offset;
+            self.advance()?;
+            let then = self.or()?;
+            self.expect(":")?;
+            let otherwise = self.expr()?;
+            self.call(offset, "_?_:_", None, vec![cond, then, otherwise])
+        } else {
+            cond
+        };
+        self.depth -= 1;
+        Ok(e)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.and()?;
+        let depth = self.depth;
+        while self.is("||") {
+            let offset = self.offset;
+            self.nest()?;
+            self.advance()?;
+            let rhs = self.and()?;
+            e = self.call(offset, "_||_", None, vec![e, rhs]);
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.relation()?;
+        let depth = self.depth;
+        while self.is("&&") {
+            let offset = self.offset;
+            self.nest()?;
+            self.advance()?;
+            let rhs = self.relation()?;
+            e = self.call(offset, "_&&_", None, vec![e, rhs]);
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn relation(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.addition()?;
+        let depth = self.depth;
+        loop {
+            let function = match &self.token {
+                Token::Punct("<") => "_<_",
+                Token::Punct("<=") => "_<=_",
+                Token::Punct(">") => "_>_",
+                Token::Punct(">=") => "_>=_",
+                Token::Punct("==") => "_==_",
+                Token::Punct("!=") => "_!=_",
+                Token::Ident(w) if w == "in" => "@in",
+                _ => break,
+            };
+            let offset = self.offset;
+            self.nest()?;
+            self.advance()?;
+            let rhs = self.addition()?;
+            e = self.call(offset, function, None, vec![e, rhs]);
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn addition(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.multiplication()?;
+        let depth = self.depth;
+        loop {
+            let function = match self.token {
+                Token::Punct("+") => "_+_",
+                Token::Punct("-") => "_-_",
+                _ => break,
+            };
+            let offset = self.offset;
+            self.nest()?;
+            self.advance()?;
+            let rhs = self.multiplication()?;
+            e = self.call(offset, function, None, vec![e, rhs]);
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn multiplication(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.unary()?;
+        let depth = self.depth;
+        loop {
+            let function = match self.token {
+                Token::Punct("*") => "_*_",
+                Token::Punct("/") => "_/_",
+                Token::Punct("%") => "_%_",
+                _ => break,
+            };
+            let offset = self.offset;
+            self.nest()?;
+            self.advance()?;
+            let rhs = self.unary()?;
+            e = self.call(offset, function, None, vec![e, rhs]);
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let offset = self.offset;
+        if self.eat("!")? {
+            self.nest()?;
+            let e = self.unary()?;
+            self.depth -= 1;
+            return Ok(self.call(offset, "!_", None, vec![e]));
+        }
+        if self.eat("-")? {
+            // A negative number literal is a constant, so that `-9223372036854775808` is valid.
+            match self.token {
+                Token::Int(v) if v <= i64::MIN.unsigned_abs() => {
+                    self.advance()?;
+                    let e =
+                        self.constant(offset, ConstantKind::Int64Value((v as i64).wrapping_neg()));
+                    return self.member(e);
+                }
+                Token::Double(v) => {
+                    self.advance()?;
+                    let e = self.constant(offset, ConstantKind::DoubleValue(-v));
+                    return self.member(e);
+                }
+                _ => {}
+            }
+            self.nest()?;
+            let e = self.unary()?;
+            self.depth -= 1;
+            return Ok(self.call(offset, "-_", None, vec![e]));
+        }
+        let e = self.primary()?;
+        self.member(e)
+    }
+
+    fn member(&mut self, mut e: Expr) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        loop {
+            let offset = self.offset;
+            if self.is(".") || self.is("[") {
+                self.nest()?;
+            }
+            if self.eat(".")? {
+                let field_offset = self.offset;
+                let field = match self.advance()? {
+                    Token::Ident(name) => name,
+                    _ => return Err(self.lexer.error(field_offset, "expected a field name")),
+                };
+                if self.eat("(")? {
+                    let args = self.list(")")?;
+                    e = self.member_call(offset, e, &field, args)?;
+                } else if self.is("{") && qualified_name(&e).is_some() {
+                    let name = format!("{}.{}", qualified_name(&e).unwrap(), field);
+                    e = self.message(&name)?;
+                } else {
+                    let select = Select {
+                        operand: Some(Box::new(e)),
+                        field,
+                        test_only: false,
+                    };
+                    e = self.node(offset, ExprKind::SelectExpr(Box::new(select)));
+                }
+            } else if self.eat("[")? {
+                let index = self.expr()?;
+                self.expect("]")?;
+                e = self.call(offset, "_[_]", None, vec![e, index]);
+            } else {
+                self.depth = depth;
+                return Ok(e);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let offset = self.offset;
+        let e = match self.advance()? {
+            Token::Int(v) => {
+                let v = i64::try_from(v)
+                    .map_err(|_| self.lexer.error(offset, "integer literal out of range"))?;
+                self.constant(offset, ConstantKind::Int64Value(v))
+            }
+            Token::Uint(v) => self.constant(offset, ConstantKind::Uint64Value(v)),
+            Token::Double(v) => self.constant(offset, ConstantKind::DoubleValue(v)),
+            Token::String(v) => self.constant(offset, ConstantKind::StringValue(v)),
+            Token::Bytes(v) => self.constant(offset, ConstantKind::BytesValue(v)),
+            Token::Punct("(") => {
+                let e = self.expr()?;
+                self.expect(")")?;
+                e
+            }
+            Token::Punct("[") => {
+                let elements = self.list("]")?;
+                self.node(offset, ExprKind::ListExpr(CreateList { elements }))
+            }
+            Token::Punct("{") => self.map(offset)?,
+            Token::Punct(".") => {
+                let name_offset = self.offset;
+                match self.advance()? {
+                    Token::Ident(name) => self.name(offset, &format!(".{}", name))?,
+                    _ => return Err(self.lexer.error(name_offset, "expected an identifier")),
+                }
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" | "false" => self.constant(offset, ConstantKind::BoolValue(name == "true")),
+                "null" => self.constant(offset, ConstantKind::NullValue(0)),
+                "in" => return Err(self.lexer.error(offset, "unexpected 'in'")),
+                _ if RESERVED.contains(&name.as_str()) => {
+                    return Err(self
+                        .lexer
+                        .error(offset, format!("reserved identifier '{}'", name)))
+                }
+                _ => self.name(offset, &name)?,
+            },
+            token => return Err(self.lexer.error(offset, format!("unexpected {}", token))),
+        };
+        Ok(e)
+    }
+
+    // An identifier, a global call or a message construction.
+    fn name(&mut self, offset: usize, name: &str) -> Result<Expr, ParseError> {
+        if self.eat("(")? {
+            let args = self.list(")")?;
+            self.global_call(offset, name, args)
+        } else if self.is("{") {
+            self.message(name)
+        } else {
+            Ok(self.ident(offset, name))
+        }
+    }
+
+    // Parses expressions separated by commas up to `close`, allowing a trailing comma.
+    fn list(&mut self, close: &str) -> Result<Vec<Expr>, ParseError> {
+        let mut elements = Vec::new();
+        while !self.eat(close)? {
+            elements.push(self.expr()?);
+            if !self.eat(",")? {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(elements)
+    }
+
+    fn map(&mut self, offset: usize) -> Result<Expr, ParseError> {
+        let mut entries = Vec::new();
+        while !self.eat("}")? {
+            let key = self.expr()?;
+            let colon = self.offset;
+            self.expect(":")?;
+            let value = self.expr()?;
+            entries.push(self.entry(colon, KeyKind::MapKey(key), value));
+            if !self.eat(",")? {
+                self.expect("}")?;
+                break;
+            }
+        }
+        let create = CreateStruct {
+            message_name: String::new(),
+            entries,
+        };
+        Ok(self.node(offset, ExprKind::StructExpr(create)))
+    }
+
+    fn message(&mut self, name: &str) -> Result<Expr, ParseError> {
+        let offset = self.offset;
+        self.expect("{")?;
+        let mut entries = Vec::new();
+        while !self.eat("}")? {
+            let field_offset = self.offset;
+            let field = match self.advance()? {
+                Token::Ident(field) => field,
+                _ => return Err(self.lexer.error(field_offset, "expected a field name")),
+            };
+            self.expect(":")?;
+            let value = self.expr()?;
+            entries.push(self.entry(field_offset, KeyKind::FieldKey(field), value));
+            if !self.eat(",")? {
+                self.expect("}")?;
+                break;
+            }
+        }
+        let create = CreateStruct {
+            message_name: name.to_owned(),
+            entries,
+        };
+        Ok(self.node(offset, ExprKind::StructExpr(create)))
+    }
+
+    fn entry(&mut self, offset: usize, key: KeyKind, value: Expr) -> Entry {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.positions.insert(id, offset as i32);
+        Entry {
+            id,
+            value: Some(value),
+            key_kind: Some(key),
+        }
+    }
+
+    fn global_call(
+        &mut self,
+        offset: usize,
+        function: &str,
+        args: Vec<Expr>,
+    ) -> Result<Expr, ParseError> {
+        if function == "has" && args.len() == 1 {
+            let call = self.call(offset, function, None, args.clone());
+            let mut arg = args.into_iter().next().unwrap();
+            match &mut arg.expr_kind {
+                Some(ExprKind::SelectExpr(select)) => select.test_only = true,
+                _ => return Err(self.lexer.error(offset, "invalid argument to has() macro")),
+            }
+            // The expansion takes the id of the call.
+            self.positions.remove(&arg.id);
+            arg.id = call.id;
+            self.macro_calls.insert(call.id, call);
+            return Ok(arg);
+        }
+        Ok(self.call(offset, function, None, args))
+    }
+
+    fn member_call(
+        &mut self,
+        offset: usize,
+        target: Expr,
+        function: &str,
+        args: Vec<Expr>,
+    ) -> Result<Expr, ParseError> {
+        let expand = matches!(
+            (function, args.len()),
+            ("all" | "exists" | "exists_one" | "filter", 2) | ("map", 2 | 3)
+        );
+        if !expand {
+            return Ok(self.call(offset, function, Some(target), args));
+        }
+        let call = self.call(offset, function, Some(target.clone()), args.clone());
+        let var = match &args[0].expr_kind {
+            Some(ExprKind::IdentExpr(ident)) => ident.name.clone(),
+            _ => {
+                let offset = self.positions[&args[0].id] as usize;
+                return Err(self.lexer.error(offset, "argument must be a simple name"));
+            }
+        };
+        let mut args = args.into_iter().skip(1);
+        let first = args.next().unwrap();
+        let second = args.next();
+
+        let accu = |p: &mut Self| p.ident(offset, ACCUMULATOR);
+        let (init, condition, step, result) = match function {
+            "all" | "exists" => {
+                let all = function == "all";
+                let init = self.constant(offset, ConstantKind::BoolValue(all));
+                let mut condition = accu(self);
+                if !all {
+                    condition = self.call(offset, "!_", None, vec![condition]);
+                }
+                let condition = self.call(offset, "@not_strictly_false", None, vec![condition]);
+                let acc = accu(self);
+                let op = if all { "_&&_" } else { "_||_" };
+                let step = self.call(offset, op, None, vec![acc, first]);
+                (init, condition, step, accu(self))
+            }
+            "exists_one" => {
+                let init = self.constant(offset, ConstantKind::Int64Value(0));
+                let condition = self.constant(offset, ConstantKind::BoolValue(true));
+                let acc = accu(self);
+                let one = self.constant(offset, ConstantKind::Int64Value(1));
+                let incremented = self.call(offset, "_+_", None, vec![acc, one]);
+                let acc = accu(self);
+                let step = self.call(offset, "_?_:_", None, vec![first, incremented, acc]);
+                let acc = accu(self);
+                let one = self.constant(offset, ConstantKind::Int64Value(1));
+                let result = self.call(offset, "_==_", None, vec![acc, one]);
+                (init, condition, step, result)
+            }
+            _ => {
+                let init = self.node(offset, ExprKind::ListExpr(CreateList::default()));
+                let condition = self.constant(offset, ConstantKind::BoolValue(true));
+                let (filter, element) = match (function, second) {
+                    ("filter", _) => (Some(first), self.ident(offset, &var)),
+                    (_, Some(transform)) => (Some(first), transform),
+                    (_, None) => (None, first),
+                };
+                let element = self.node(
+                    offset,
+                    ExprKind::ListExpr(CreateList {
+                        elements: vec![element],
+                    }),
+                );
+                let acc = accu(self);
+                let appended = self.call(offset, "_+_", None, vec![acc, element]);
+                let step = match filter {
+                    Some(filter) => {
+                        let acc = accu(self);
+                        self.call(offset, "_?_:_", None, vec![filter, appended, acc])
+                    }
+                    None => appended,
+                };
+                (init, condition, step, accu(self))
+            }
+        };
+        let comprehension = Comprehension {
+            iter_var: var,
+            iter_range: Some(Box::new(target)),
+            accu_var: ACCUMULATOR.to_owned(),
+            accu_init: Some(Box::new(init)),
+            loop_condition: Some(Box::new(condition)),
+            loop_step: Some(Box::new(step)),
+            result: Some(Box::new(result)),
+        };
+        let mut e = self.node(offset, ExprKind::ComprehensionExpr(Box::new(comprehension)));
+        // The expansion takes the id of the call.
+        self.positions.remove(&e.id);
+        e.id = call.id;
+        self.macro_calls.insert(call.id, call);
+        Ok(e)
+    }
+}
+
+/// The dotted name of an identifier or a chain of field selections on one, e.g. `a.b.c`.
+pub(super) fn qualified_name(e: &Expr) -> Option<String> {
+    match e.expr_kind.as_ref()? {
+        ExprKind::IdentExpr(ident) => Some(ident.name.clone()),
+        ExprKind::SelectExpr(select) if !select.test_only => {
+            let operand = qualified_name(select.operand.as_deref()?)?;
+            Some(format!("{}.{}", operand, select.field))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Prints an expression in a compact prefix form, e.g. `_+_(1, x)`.
+    fn show(e: &Expr) -> String {
+        let list = |es: &[Expr]| es.iter().map(show).collect::<Vec<_>>().join(", ");
+        match e.expr_kind.as_ref().unwrap() {
+            ExprKind::ConstExpr(c) => match c.constant_kind.as_ref().unwrap() {
+                ConstantKind::NullValue(_) => "null".to_owned(),
+                ConstantKind::BoolValue(b) => b.to_string(),
+                ConstantKind::Int64Value(i) => i.to_string(),
+                ConstantKind::Uint64Value(u) => format!("{}u", u),
+                ConstantKind::DoubleValue(d) => format!("{:?}", d),
+                ConstantKind::StringValue(s) => format!("{:?}", s),
+                ConstantKind::BytesValue(b) => format!("b{:?}", b),
+                c => format!("{:?}", c),
+            },
+            ExprKind::IdentExpr(i) => i.name.clone(),
+            ExprKind::SelectExpr(s) => {
+                let operand = show(s.operand.as_ref().unwrap());
+                if s.test_only {
+                    format!("has({}.{})", operand, s.field)
+                } else {
+                    format!("{}.{}", operand, s.field)
+                }
+            }
+            ExprKind::CallExpr(c) => match &c.target {
+                Some(t) => format!("{}.{}({})", show(t), c.function, list(&c.args)),
+                None => format!("{}({})", c.function, list(&c.args)),
+            },
+            ExprKind::ListExpr(l) => format!("[{}]", list(&l.elements)),
+            ExprKind::StructExpr(s) => {
+                let entries = s
+                    .entries
+                    .iter()
+                    .map(|e| {
+                        let key = match e.key_kind.as_ref().unwrap() {
+                            KeyKind::FieldKey(f) => f.clone(),
+                            KeyKind::MapKey(k) => show(k),
+                        };
+                        format!("{}: {}", key, show(e.value.as_ref().unwrap()))
+                    })
+                    .collect::<Vec<_>>();
+                format!("{}{{{}}}", s.message_name, entries.join(", "))
+            }
+            ExprKind::ComprehensionExpr(c) => format!(
+                "__comprehension__({}, {}, {}, {}, {}, {}, {})",
+                c.iter_var,
+                show(c.iter_range.as_ref().unwrap()),
+                c.accu_var,
+                show(c.accu_init.as_ref().unwrap()),
+                show(c.loop_condition.as_ref().unwrap()),
+                show(c.loop_step.as_ref().unwrap()),
+                show(c.result.as_ref().unwrap()),
+            ),
+        }
+    }
+
+    fn p(source: &str) -> String {
+        let parsed = parse(source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+        show(parsed.expr.as_ref().unwrap())
+    }
+
+    fn err(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(p("1 + 2 * 3"), "_+_(1, _*_(2, 3))");
+        assert_eq!(p("1 - 2 - 3"), "_-_(_-_(1, 2), 3)");
+        assert_eq!(p("a || b && c"), "_||_(a, _&&_(b, c))");
+        assert_eq!(p("a ? b : c ? d : e"), "_?_:_(a, b, _?_:_(c, d, e))");
+        assert_eq!(p("!a == -b"), "_==_(!_(a), -_(b))");
+        assert_eq!(p("--a"), "-_(-_(a))");
+        assert_eq!(p("-1 + -2.5"), "_+_(-1, -2.5)");
+        assert_eq!(p("-9223372036854775808"), "-9223372036854775808");
+        assert_eq!(p("x in [1, 2u, 0x10]"), "@in(x, [1, 2u, 16])");
+        assert_eq!(p("a.b.c[0].d(e)"), "_[_](a.b.c, 0).d(e)");
+        assert_eq!(p(".a.b"), ".a.b");
+        assert_eq!(p("f(a, b,)"), "f(a, b)");
+        assert_eq!(p("{'a': 1, 2: [],}"), "{\"a\": 1, 2: []}");
+        assert_eq!(p("a.B{f: 1, g: x}"), "a.B{f: 1, g: x}");
+        assert_eq!(p("null != true"), "_!=_(null, true)");
+        assert_eq!(p("1e3 + .5 + 1.5e-1"), "_+_(_+_(1000.0, 0.5), 0.15)");
+        assert_eq!(p("1 // comment\n+ 2"), "_+_(1, 2)");
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        assert_eq!(p(r#""a\n\"é\x41\101""#), r#""a\n\"éAA""#);
+        assert_eq!(p(r"r'a\n'"), r#""a\\n""#);
+        assert_eq!(p("'''a\n'b'''"), r#""a\n'b""#);
+        assert_eq!(p(r"b'\xff\377a'"), "b[255, 255, 97]");
+        assert_eq!(p("b'é'"), "b[195, 169]");
+        assert_eq!(p("rb'\\x'"), "b[92, 120]");
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        assert_eq!(p("has(a.b)"), "has(a.b)");
+        assert_eq!(
+            p("l.all(x, x > 0)"),
+            "__comprehension__(x, l, __result__, true, @not_strictly_false(__result__), \
+             _&&_(__result__, _>_(x, 0)), __result__)"
+        );
+        assert_eq!(
+            p("l.exists(x, x > 0)"),
+            "__comprehension__(x, l, __result__, false, @not_strictly_false(!_(__result__)), \
+             _||_(__result__, _>_(x, 0)), __result__)"
+        );
+        assert_eq!(
+            p("l.exists_one(x, x > 0)"),
+            "__comprehension__(x, l, __result__, 0, true, \
+             _?_:_(_>_(x, 0), _+_(__result__, 1), __result__), _==_(__result__, 1))"
+        );
+        assert_eq!(
+            p("l.map(x, x * 2)"),
+            "__comprehension__(x, l, __result__, [], true, \
+             _+_(__result__, [_*_(x, 2)]), __result__)"
+        );
+        assert_eq!(
+            p("l.filter(x, x > 0)"),
+            "__comprehension__(x, l, __result__, [], true, \
+             _?_:_(_>_(x, 0), _+_(__result__, [x]), __result__), __result__)"
+        );
+        // Macros with other arities are function calls.
+        assert_eq!(p("l.all(x)"), "l.all(x)");
+
+        let parsed = parse("has(a.b) || l.all(x, x)").unwrap();
+        let source_info = parsed.source_info.unwrap();
+        let mut calls = source_info
+            .macro_calls
+            .values()
+            .map(show)
+            .collect::<Vec<_>>();
+        calls.sort();
+        assert_eq!(calls, vec!["has(a.b)", "l.all(x, x)"]);
+    }
+
+    #[test]
+    fn test_parse_source_info() {
+        let parsed = parse("a +\n  f(b)").unwrap();
+        let source_info = parsed.source_info.unwrap();
+        assert_eq!(source_info.syntax_version, "cel1");
+        assert_eq!(source_info.line_offsets, vec![4]);
+
+        let mut offsets = source_info.positions.values().copied().collect::<Vec<_>>();
+        offsets.sort_unstable();
+        // `a`, `+`, `f`, `(` and `b`: calls are at their name or their operator.
+        assert_eq!(offsets.len(), 4);
+        let expr = parsed.expr.unwrap();
+        assert_eq!(source_info.positions[&expr.id], 2);
+        let mut ids = vec![expr.id];
+        if let Some(ExprKind::CallExpr(c)) = &expr.expr_kind {
+            ids.extend(c.args.iter().map(|a| a.id));
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(err("1 +"), "1:4: unexpected end of input");
+        assert_eq!(err("a.\n  1"), "2:3: expected a field name");
+        assert_eq!(err("'abc"), "1:1: unterminated string");
+        assert_eq!(err("'\\q'"), "1:2: invalid escape sequence");
+        assert_eq!(err("b'\\u00e9'"), "1:3: invalid escape sequence");
+        assert_eq!(
+            err("18446744073709551616"),
+            "1:1: integer literal out of range"
+        );
+        assert_eq!(
+            err("9223372036854775808"),
+            "1:1: integer literal out of range"
+        );
+        assert_eq!(err("a # b"), "1:3: unexpected character '#'");
+        assert_eq!(err("if"), "1:1: reserved identifier 'if'");
+        assert_eq!(err("l.all(1, x)"), "1:7: argument must be a simple name");
+        assert_eq!(err("has(a)"), "1:1: invalid argument to has() macro");
+        assert_eq!(err("f(a"), "1:4: expected ')', found end of input");
+        assert_eq!(err(&"(".repeat(300)), "1:101: expression nested too deeply");
+        assert_eq!(err(&"!".repeat(300)), "1:101: expression nested too deeply");
+        assert_eq!(
+            err(&"1".repeat(300).replace('1', "1+")),
+            "1:200: expression nested too deeply"
+        );
+        assert_eq!(
+            err(&"a.b".repeat(300).replace("ba", "b.a")),
+            "1:200: expression nested too deeply"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::sync::mpsc;
+use tonic::codegen::futures_core::Stream;
+
+use super::{
+    admin_service_client::AdminServiceClient, cursor_service_client::CursorServiceClient,
+    partition_assignment_service_client::PartitionAssignmentServiceClient,
+    publisher_service_client::PublisherServiceClient,
+    subscriber_service_client::SubscriberServiceClient, GetTopicPartitionsRequest,
+    PartitionAssignment, PartitionAssignmentRequest, PublishRequest, PublishResponse,
+    StreamingCommitCursorRequest, StreamingCommitCursorResponse, SubscribeRequest,
+    SubscribeResponse,
+};
+
+/// The stream of responses of a bidirectional streaming call.
+pub type LiteStream<T> = Pin<Box<dyn Stream<Item = Result<T, tonic::Status>> + Send>>;
+
+/// The future returned by the methods of [`LiteClient`].
+pub type LiteFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, tonic::Status>> + Send + 'a>>;
+
+/// A client of the Pub/Sub Lite methods used by the [`Publisher`](super::Publisher) and the
+/// [`Subscriber`](super::Subscriber), implemented by [`LiteClients`]. They clone it for every
+/// partition, and open a new stream with it after retryable errors.
+pub trait LiteClient: Clone + Send + 'static {
+    /// The number of partitions of the topic, see `AdminService.GetTopicPartitions`.
+    fn get_topic_partitions(&mut self, topic: String) -> LiteFuture<'_, i64>;
+
+    fn publish(
+        &mut self,
+        requests: LiteRequests<PublishRequest>,
+    ) -> LiteFuture<'_, LiteStream<PublishResponse>>;
+
+    fn subscribe(
+        &mut self,
+        requests: LiteRequests<SubscribeRequest>,
+    ) -> LiteFuture<'_, LiteStream<SubscribeResponse>>;
+
+    fn streaming_commit_cursor(
+        &mut self,
+        requests: LiteRequests<StreamingCommitCursorRequest>,
+    ) -> LiteFuture<'_, LiteStream<StreamingCommitCursorResponse>>;
+
+    fn assign_partitions(
+        &mut self,
+        requests: LiteRequests<PartitionAssignmentRequest>,
+    ) -> LiteFuture<'_, LiteStream<PartitionAssignment>>;
+}
+
+/// The clients of the Pub/Sub Lite services, sharing a channel to the regional endpoint,
+/// `https://{region}-pubsublite.googleapis.com`.
+#[derive(Debug, Clone)]
+pub struct LiteClients<T> {
+    pub admin: AdminServiceClient<T>,
+    pub publisher: PublisherServiceClient<T>,
+    pub subscriber: SubscriberServiceClient<T>,
+    pub cursor: CursorServiceClient<T>,
+    pub partition_assignment: PartitionAssignmentServiceClient<T>,
+}
+
+impl<T> LiteClients<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    pub fn new(channel: T) -> Self {
+        LiteClients {
+            admin: AdminServiceClient::new(channel.clone()),
+            publisher: PublisherServiceClient::new(channel.clone()),
+            subscriber: SubscriberServiceClient::new(channel.clone()),
+            cursor: CursorServiceClient::new(channel.clone()),
+            partition_assignment: PartitionAssignmentServiceClient::new(channel),
+        }
+    }
+}
+
+impl<T> LiteClient for LiteClients<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn get_topic_partitions(&mut self, topic: String) -> LiteFuture<'_, i64> {
+        Box::pin(async move {
+            let request = GetTopicPartitionsRequest { name: topic };
+            let response = self.admin.get_topic_partitions(request).await?;
+            Ok(response.into_inner().partition_count)
+        })
+    }
+
+    fn publish(
+        &mut self,
+        requests: LiteRequests<PublishRequest>,
+    ) -> LiteFuture<'_, LiteStream<PublishResponse>> {
+        Box::pin(async move {
+            let stream = self.publisher.publish(requests).await?;
+            Ok(Box::pin(stream.into_inner()) as LiteStream<_>)
+        })
+    }
+
+    fn subscribe(
+        &mut self,
+        requests: LiteRequests<SubscribeRequest>,
+    ) -> LiteFuture<'_, LiteStream<SubscribeResponse>> {
+        Box::pin(async move {
+            let stream = self.subscriber.subscribe(requests).await?;
+            Ok(Box::pin(stream.into_inner()) as LiteStream<_>)
+        })
+    }
+
+    fn streaming_commit_cursor(
+        &mut self,
+        requests: LiteRequests<StreamingCommitCursorRequest>,
+    ) -> LiteFuture<'_, LiteStream<StreamingCommitCursorResponse>> {
+        Box::pin(async move {
+            let stream = self.cursor.streaming_commit_cursor(requests).await?;
+            Ok(Box::pin(stream.into_inner()) as LiteStream<_>)
+        })
+    }
+
+    fn assign_partitions(
+        &mut self,
+        requests: LiteRequests<PartitionAssignmentRequest>,
+    ) -> LiteFuture<'_, LiteStream<PartitionAssignment>> {
+        Box::pin(async move {
+            let stream = self
+                .partition_assignment
+                .assign_partitions(requests)
+                .await?;
+            Ok(Box::pin(stream.into_inner()) as LiteStream<_>)
+        })
+    }
+}
+
+/// The requests of a bidirectional streaming call: the initial request, then the ones sent
+/// while the stream is open.
+#[derive(Debug)]
+pub struct LiteRequests<T> {
+    rx: mpsc::UnboundedReceiver<T>,
+}
+
+impl<T> LiteRequests<T> {
+    pub(super) fn channel() -> (mpsc::UnboundedSender<T>, Self) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, LiteRequests { rx })
+    }
+}
+
+impl<T> Stream for LiteRequests<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+pub(super) async fn next<T>(
+    stream: &mut Option<LiteStream<T>>,
+) -> Option<Result<T, tonic::Status>> {
+    match stream {
+        Some(stream) => poll_fn(|cx| stream.as_mut().poll_next(cx)).await,
+        None => None,
+    }
+}
+
+// Opens a stream with its initial request, and waits for the initial response.
+pub(super) async fn connect<Req, Resp, F>(
+    initial: Req,
+    open: impl FnOnce(LiteRequests<Req>) -> F,
+    is_initial: impl FnOnce(&Resp) -> bool,
+) -> Result<(mpsc::UnboundedSender<Req>, LiteStream<Resp>), tonic::Status>
+where
+    F: Future<Output = Result<LiteStream<Resp>, tonic::Status>>,
+{
+    let (tx, requests) = LiteRequests::channel();
+    let _ = tx.send(initial);
+    let mut stream = Some(open(requests).await?);
+    match next(&mut stream).await {
+        Some(Ok(response)) if is_initial(&response) => Ok((tx, stream.unwrap())),
+        Some(Ok(_)) => Err(tonic::Status::internal("expected an initial response")),
+        Some(Err(status)) => Err(status),
+        None => Err(tonic::Status::unavailable("stream closed")),
+    }
+}
+
+// The codes of the errors after which Pub/Sub Lite streams are opened again, as in the
+// official clients.
+pub(super) const RETRYABLE_CODES: [tonic::Code; 6] = [
+    tonic::Code::DeadlineExceeded,
+    tonic::Code::ResourceExhausted,
+    tonic::Code::Aborted,
+    tonic::Code::Internal,
+    tonic::Code::Unavailable,
+    tonic::Code::Unknown,
+];
+
+pub(super) fn clone_status(s: &tonic::Status) -> tonic::Status {
+    tonic::Status::with_details_and_metadata(
+        s.code(),
+        s.message(),
+        s.details().to_vec().into(),
+        s.metadata().clone(),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+pub(super) mod testing {
+    use super::*;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+    };
+
+    use super::super::{
+        partition_assignment_request, publish_request, publish_response,
+        streaming_commit_cursor_request, streaming_commit_cursor_response, subscribe_request,
+        subscribe_response, InitialCommitCursorResponse, InitialPublishResponse,
+        InitialSubscribeResponse,
+    };
+
+    pub type Responses<T> = mpsc::UnboundedSender<Result<T, tonic::Status>>;
+
+    struct Responder<T> {
+        rx: mpsc::UnboundedReceiver<Result<T, tonic::Status>>,
+    }
+
+    impl<T> Stream for Responder<T> {
+        type Item = Result<T, tonic::Status>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.rx.poll_recv(cx)
+        }
+    }
+
+    struct Inner<Req, Resp> {
+        streams: HashMap<i64, VecDeque<Responder<Resp>>>,
+        requests: HashMap<i64, Vec<Arc<Mutex<Vec<Req>>>>>,
+    }
+
+    // The streams of a method by partition, queued by `stream`, with their requests.
+    pub struct Streams<Req, Resp> {
+        inner: Arc<Mutex<Inner<Req, Resp>>>,
+        partition: fn(&Req) -> i64,
+        initial: Option<fn() -> Resp>,
+    }
+
+    impl<Req, Resp> Clone for Streams<Req, Resp> {
+        fn clone(&self) -> Self {
+            Streams {
+                inner: self.inner.clone(),
+                partition: self.partition,
+                initial: self.initial,
+            }
+        }
+    }
+
+    impl<Req: Clone + Send + 'static, Resp: Send + 'static> Streams<Req, Resp> {
+        fn new(partition: fn(&Req) -> i64, initial: Option<fn() -> Resp>) -> Self {
+            let inner = Inner {
+                streams: HashMap::new(),
+                requests: HashMap::new(),
+            };
+            Streams {
+                inner: Arc::new(Mutex::new(inner)),
+                partition,
+                initial,
+            }
+        }
+
+        // Queues a stream of the partition, which sends the initial response if any.
+        pub fn stream(&self, partition: i64) -> Responses<Resp> {
+            let (tx, rx) = mpsc::unbounded_channel();
+            if let Some(initial) = self.initial {
+                let _ = tx.send(Ok(initial()));
+            }
+            let mut inner = self.inner.lock().unwrap();
+            let streams = inner.streams.entry(partition).or_default();
+            streams.push_back(Responder { rx });
+            tx
+        }
+
+        // The requests of the streams of the partition, in order.
+        pub fn requests(&self, partition: i64) -> Vec<Vec<Req>> {
+            let inner = self.inner.lock().unwrap();
+            let requests = inner.requests.get(&partition).into_iter().flatten();
+            requests.map(|r| r.lock().unwrap().clone()).collect()
+        }
+
+        fn open(&self, mut requests: LiteRequests<Req>) -> Result<LiteStream<Resp>, tonic::Status> {
+            let initial = requests.rx.try_recv().expect("initial request");
+            let partition = (self.partition)(&initial);
+            let mut inner = self.inner.lock().unwrap();
+            let responder = inner
+                .streams
+                .get_mut(&partition)
+                .and_then(|s| s.pop_front())
+                .ok_or_else(|| tonic::Status::unavailable("no stream"))?;
+            let log = Arc::new(Mutex::new(vec![initial]));
+            let requests_log = inner.requests.entry(partition).or_default();
+            requests_log.push(log.clone());
+            tokio::spawn(async move {
+                while let Some(r) = poll_fn(|cx| Pin::new(&mut requests).poll_next(cx)).await {
+                    log.lock().unwrap().push(r);
+                }
+            });
+            Ok(Box::pin(responder))
+        }
+    }
+
+    // Serves the streams queued in its fields. The partition assignment streams are those of
+    // partition 0.
+    #[derive(Clone)]
+    pub struct Fake {
+        pub partitions: Arc<Mutex<VecDeque<Result<i64, tonic::Status>>>>,
+        pub publish: Streams<PublishRequest, PublishResponse>,
+        pub subscribe: Streams<SubscribeRequest, SubscribeResponse>,
+        pub commit: Streams<StreamingCommitCursorRequest, StreamingCommitCursorResponse>,
+        pub assign: Streams<PartitionAssignmentRequest, PartitionAssignment>,
+    }
+
+    impl Default for Fake {
+        fn default() -> Self {
+            Fake {
+                partitions: Default::default(),
+                publish: Streams::new(
+                    |r| match &r.request_type {
+                        Some(publish_request::RequestType::InitialRequest(r)) => r.partition,
+                        _ => panic!("not an initial request"),
+                    },
+                    Some(|| PublishResponse {
+                        response_type: Some(publish_response::ResponseType::InitialResponse(
+                            InitialPublishResponse {},
+                        )),
+                    }),
+                ),
+                subscribe: Streams::new(
+                    |r| match &r.request {
+                        Some(subscribe_request::Request::Initial(r)) => r.partition,
+                        _ => panic!("not an initial request"),
+                    },
+                    Some(|| SubscribeResponse {
+                        response: Some(subscribe_response::Response::Initial(
+                            InitialSubscribeResponse { cursor: None },
+                        )),
+                    }),
+                ),
+                commit: Streams::new(
+                    |r| match &r.request {
+                        Some(streaming_commit_cursor_request::Request::Initial(r)) => r.partition,
+                        _ => panic!("not an initial request"),
+                    },
+                    Some(|| StreamingCommitCursorResponse {
+                        request: Some(streaming_commit_cursor_response::Request::Initial(
+                            InitialCommitCursorResponse {},
+                        )),
+                    }),
+                ),
+                assign: Streams::new(
+                    |r| match &r.request {
+                        Some(partition_assignment_request::Request::Initial(_)) => 0,
+                        _ => panic!("not an initial request"),
+                    },
+                    None,
+                ),
+            }
+        }
+    }
+
+    impl LiteClient for Fake {
+        fn get_topic_partitions(&mut self, _: String) -> LiteFuture<'_, i64> {
+            let count = self.partitions.lock().unwrap().pop_front();
+            Box::pin(async move { count.unwrap_or(Ok(1)) })
+        }
+
+        fn publish(
+            &mut self,
+            requests: LiteRequests<PublishRequest>,
+        ) -> LiteFuture<'_, LiteStream<PublishResponse>> {
+            let stream = self.publish.open(requests);
+            Box::pin(async move { stream })
+        }
+
+        fn subscribe(
+            &mut self,
+            requests: LiteRequests<SubscribeRequest>,
+        ) -> LiteFuture<'_, LiteStream<SubscribeResponse>> {
+            let stream = self.subscribe.open(requests);
+            Box::pin(async move { stream })
+        }
+
+        fn streaming_commit_cursor(
+            &mut self,
+            requests: LiteRequests<StreamingCommitCursorRequest>,
+        ) -> LiteFuture<'_, LiteStream<StreamingCommitCursorResponse>> {
+            let stream = self.commit.open(requests);
+            Box::pin(async move { stream })
+        }
+
+        fn assign_partitions(
+            &mut self,
+            requests: LiteRequests<PartitionAssignmentRequest>,
+        ) -> LiteFuture<'_, LiteStream<PartitionAssignment>> {
+            let stream = self.assign.open(requests);
+            Box::pin(async move { stream })
+        }
+    }
+}
@@ -0,0 +1,16 @@
+#[cfg(feature = "tokio")]
+mod client;
+#[cfg(feature = "tokio")]
+mod publisher;
+#[cfg(feature = "tokio")]
+mod subscriber;
+
+#[cfg(feature = "tokio")]
+pub use self::client::{LiteClient, LiteClients, LiteFuture, LiteRequests, LiteStream};
+#[cfg(feature = "tokio")]
+pub use self::publisher::{
+    BatchSettings, MessageMetadata, PublishError, PublishResult, Publisher, PublisherSettings,
+    MAX_PUBLISH_BYTES, MAX_PUBLISH_MESSAGES,
+};
+#[cfg(feature = "tokio")]
+pub use self::subscriber::{ReceivedMessage, Subscriber, SubscriberSettings};
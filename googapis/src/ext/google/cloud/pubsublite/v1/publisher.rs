@@ -0,0 +1,868 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{self, Instant},
+};
+
+use super::{
+    client::{self, LiteClient, LiteStream, RETRYABLE_CODES},
+    publish_request, publish_response, Cursor, InitialPublishRequest, MessagePublishRequest,
+    PubSubMessage, PublishRequest, PublishResponse,
+};
+
+/// The maximum number of messages of a `MessagePublishRequest`.
+pub const MAX_PUBLISH_MESSAGES: usize = 1000;
+/// The maximum size of a `MessagePublishRequest` in bytes.
+pub const MAX_PUBLISH_BYTES: usize = 3_500_000;
+
+/// When a batch of messages is sent on the stream of a partition. The limits are capped at
+/// [`MAX_PUBLISH_MESSAGES`] and [`MAX_PUBLISH_BYTES`].
+#[derive(Debug, Clone)]
+pub struct BatchSettings {
+    pub max_messages: usize,
+    /// The maximum size of the `MessagePublishRequest` of a batch.
+    pub max_bytes: usize,
+    /// How long the first message of a batch waits for others.
+    pub max_delay: Duration,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        BatchSettings {
+            max_messages: 100,
+            max_bytes: 1_000_000,
+            max_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PublisherSettings {
+    pub batch: BatchSettings,
+    /// How often the number of partitions of the topic is fetched again, as it may grow.
+    pub partition_poll_interval: Duration,
+    /// The backoff between reconnections of a stream, doubled after each retryable error.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How long a partition is retried before its pending messages fail.
+    pub timeout: Duration,
+}
+
+impl Default for PublisherSettings {
+    fn default() -> Self {
+        PublisherSettings {
+            batch: BatchSettings::default(),
+            partition_poll_interval: Duration::from_secs(60),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// An error returned when publishing a message.
+#[derive(Debug)]
+pub enum PublishError {
+    /// The stream of the partition of the message, or fetching the number of partitions,
+    /// failed after retries.
+    Status(tonic::Status),
+    /// The message, of the size in bytes, does not fit in a `MessagePublishRequest`.
+    TooLarge(usize),
+    /// The publisher stopped before the message was published.
+    Closed,
+}
+
+impl Clone for PublishError {
+    fn clone(&self) -> Self {
+        match self {
+            PublishError::Status(s) => PublishError::Status(client::clone_status(s)),
+            PublishError::TooLarge(size) => PublishError::TooLarge(*size),
+            PublishError::Closed => PublishError::Closed,
+        }
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::Status(s) => write!(f, "publish failed: {}", s),
+            PublishError::TooLarge(size) => write!(f, "message too large: {} bytes", size),
+            PublishError::Closed => f.write_str("publisher closed"),
+        }
+    }
+}
+
+impl error::Error for PublishError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PublishError::Status(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Where a message was published.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageMetadata {
+    pub partition: i64,
+    /// The offset of the message within the partition.
+    pub cursor: Cursor,
+}
+
+/// The result of [`Publisher::publish`], resolving to the partition and cursor of the message
+/// once it is published.
+#[derive(Debug)]
+pub struct PublishResult {
+    rx: oneshot::Receiver<Result<MessageMetadata, PublishError>>,
+}
+
+impl PublishResult {
+    fn err(e: PublishError) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let _ = tx.send(Err(e));
+        PublishResult { rx }
+    }
+}
+
+impl Future for PublishResult {
+    type Output = Result<MessageMetadata, PublishError>;
+
+    #[allow(clippy::result_large_err)]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|r| r.unwrap_or(Err(PublishError::Closed)))
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    message: PubSubMessage,
+    size: usize,
+    result: oneshot::Sender<Result<MessageMetadata, PublishError>>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Publish(Pending),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Publishes messages to the partitions of a Pub/Sub Lite topic.
+///
+/// A message with a key is routed to the partition given by the SHA-256 hash of the key, as a
+/// big-endian integer, modulo the number of partitions, like the official clients do: the
+/// messages of a key go to the same partition, in order, until partitions are added. Messages
+/// without key are spread over the partitions in turn.
+///
+/// Each partition has its own `Publish` stream, on which the messages are sent in batches, see
+/// [`BatchSettings`]. After retryable errors the stream is opened again and the batches not
+/// yet acknowledged are sent again, so a message may be published more than once.
+///
+/// # Example
+/// ```ignore
+/// use googapis::google::cloud::pubsublite::v1::{LiteClients, PubSubMessage, Publisher};
+///
+/// let publisher = Publisher::new(LiteClients::new(channel), "projects/p/locations/l/topics/t");
+/// let message = PubSubMessage { key: b"k".to_vec(), data, ..Default::default() };
+/// let metadata = publisher.publish(message).await?;
+/// println!("published at {}:{}", metadata.partition, metadata.cursor.offset);
+/// ```
+#[derive(Debug)]
+pub struct Publisher {
+    topic: String,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Publisher {
+    /// A publisher with the default settings. It must be created in a Tokio runtime.
+    pub fn new<C: LiteClient>(client: C, topic: impl Into<String>) -> Self {
+        Self::with_settings(client, topic, PublisherSettings::default())
+    }
+
+    /// A publisher with `settings`. It must be created in a Tokio runtime.
+    pub fn with_settings<C: LiteClient>(
+        client: C,
+        topic: impl Into<String>,
+        mut settings: PublisherSettings,
+    ) -> Self {
+        let topic = topic.into();
+        let batch = &mut settings.batch;
+        batch.max_messages = batch.max_messages.clamp(1, MAX_PUBLISH_MESSAGES);
+        batch.max_bytes = batch.max_bytes.min(MAX_PUBLISH_BYTES);
+        let (commands, rx) = mpsc::unbounded_channel();
+        let router = Router {
+            client,
+            topic: topic.clone(),
+            settings: Arc::new(settings),
+            partition_count: 0,
+            unrouted: Vec::new(),
+            flushes: Vec::new(),
+            next_partition: 0,
+            partitions: HashMap::new(),
+        };
+        tokio::spawn(router.run(rx));
+        Publisher { topic, commands }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Queues the message for publishing.
+    pub fn publish(&self, message: PubSubMessage) -> PublishResult {
+        let size = prost::encoding::message::encoded_len(1, &message);
+        if size > MAX_PUBLISH_BYTES {
+            return PublishResult::err(PublishError::TooLarge(size));
+        }
+        let (result, rx) = oneshot::channel();
+        let pending = Pending {
+            message,
+            size,
+            result,
+        };
+        if self.commands.send(Command::Publish(pending)).is_err() {
+            return PublishResult::err(PublishError::Closed);
+        }
+        PublishResult { rx }
+    }
+
+    /// Sends the pending messages without waiting for their batches to fill, and waits until
+    /// they are published or failed.
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.commands.send(Command::Flush(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// Publishes the pending messages and stops the publisher.
+    pub async fn shutdown(self) {
+        self.flush().await;
+    }
+}
+
+// The task routing the messages of a `Publisher` to its partitions.
+struct Router<C> {
+    client: C,
+    topic: String,
+    settings: Arc<PublisherSettings>,
+    // 0 until fetched.
+    partition_count: i64,
+    // The messages waiting for the number of partitions, and the flushes waiting for them.
+    unrouted: Vec<Pending>,
+    flushes: Vec<oneshot::Sender<()>>,
+    next_partition: i64,
+    partitions: HashMap<i64, mpsc::UnboundedSender<Command>>,
+}
+
+impl<C: LiteClient> Router<C> {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        let (counts_tx, mut counts) = mpsc::unbounded_channel();
+        let mut poll = Instant::now();
+        let mut fetching = false;
+        let mut open = true;
+        loop {
+            if !open && !fetching && self.unrouted.is_empty() {
+                // The partitions publish their pending messages, then stop.
+                return;
+            }
+            if !self.unrouted.is_empty() && !fetching {
+                poll = Instant::now();
+            }
+            tokio::select! {
+                command = commands.recv(), if open => match command {
+                    Some(Command::Publish(pending)) if self.partition_count == 0 => {
+                        self.unrouted.push(pending);
+                    }
+                    Some(Command::Publish(pending)) => self.route(pending),
+                    Some(Command::Flush(tx)) if self.unrouted.is_empty() => self.flush(tx),
+                    Some(Command::Flush(tx)) => self.flushes.push(tx),
+                    None => open = false,
+                },
+                Some(count) = counts.recv() => {
+                    fetching = false;
+                    self.partitions_fetched(count);
+                }
+                _ = time::sleep_until(poll), if !fetching => {
+                    fetching = true;
+                    poll = Instant::now() + self.settings.partition_poll_interval;
+                    let count = partition_count(self.client.clone(), self.topic.clone(), self.settings.clone());
+                    let counts_tx = counts_tx.clone();
+                    tokio::spawn(async move {
+                        let _ = counts_tx.send(count.await);
+                    });
+                }
+            }
+        }
+    }
+
+    fn partitions_fetched(&mut self, count: Result<i64, tonic::Status>) {
+        match count {
+            Ok(count) => {
+                // Partitions are never removed.
+                self.partition_count = self.partition_count.max(count);
+                if self.partition_count == 0 {
+                    let status = tonic::Status::failed_precondition("the topic has no partitions");
+                    self.fail(status);
+                    return;
+                }
+                for pending in std::mem::take(&mut self.unrouted) {
+                    self.route(pending);
+                }
+                for tx in std::mem::take(&mut self.flushes) {
+                    self.flush(tx);
+                }
+            }
+            // Messages are routed with the last count known, if any.
+            Err(status) if self.partition_count == 0 => self.fail(status),
+            Err(_) => {}
+        }
+    }
+
+    fn fail(&mut self, status: tonic::Status) {
+        let e = PublishError::Status(status);
+        for pending in self.unrouted.drain(..) {
+            let _ = pending.result.send(Err(e.clone()));
+        }
+        for tx in self.flushes.drain(..) {
+            let _ = tx.send(());
+        }
+    }
+
+    fn route(&mut self, pending: Pending) {
+        let key = &pending.message.key;
+        let partition = if key.is_empty() {
+            self.next_partition = (self.next_partition + 1) % self.partition_count;
+            self.next_partition
+        } else {
+            key_partition(key, self.partition_count)
+        };
+        let (client, topic, settings) = (&self.client, &self.topic, &self.settings);
+        let tx = self.partitions.entry(partition).or_insert_with(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let publisher = PartitionPublisher {
+                client: client.clone(),
+                topic: topic.clone(),
+                partition,
+                settings: settings.clone(),
+                requests: None,
+                queue: VecDeque::new(),
+                in_flight: VecDeque::new(),
+                flushes: Vec::new(),
+            };
+            tokio::spawn(publisher.run(rx));
+            tx
+        });
+        let _ = tx.send(Command::Publish(pending));
+    }
+
+    // Flushes every partition, then notifies `tx`.
+    fn flush(&self, tx: oneshot::Sender<()>) {
+        let mut flushes = Vec::new();
+        for partition in self.partitions.values() {
+            let (tx, rx) = oneshot::channel();
+            let _ = partition.send(Command::Flush(tx));
+            flushes.push(rx);
+        }
+        tokio::spawn(async move {
+            for rx in flushes {
+                let _ = rx.await;
+            }
+            let _ = tx.send(());
+        });
+    }
+}
+
+// Fetches the number of partitions of the topic, retrying the retryable errors.
+async fn partition_count<C: LiteClient>(
+    mut client: C,
+    topic: String,
+    settings: Arc<PublisherSettings>,
+) -> Result<i64, tonic::Status> {
+    let deadline = Instant::now() + settings.timeout;
+    let mut backoff = settings.initial_backoff;
+    loop {
+        let status = match client.get_topic_partitions(topic.clone()).await {
+            Ok(count) => return Ok(count),
+            Err(status) => status,
+        };
+        if !RETRYABLE_CODES.contains(&status.code()) || Instant::now() + backoff > deadline {
+            return Err(status);
+        }
+        time::sleep(backoff).await;
+        backoff = (backoff * 2).min(settings.max_backoff);
+    }
+}
+
+// The partition of a key, as in the official clients.
+fn key_partition(key: &[u8], partition_count: i64) -> i64 {
+    let count = partition_count as u128;
+    let hash = sha256(key);
+    hash.iter().fold(0, |r, &b| (r << 8 | b as u128) % count) as i64
+}
+
+// The task publishing the messages of a partition on its stream.
+struct PartitionPublisher<C> {
+    client: C,
+    topic: String,
+    partition: i64,
+    settings: Arc<PublisherSettings>,
+    requests: Option<mpsc::UnboundedSender<PublishRequest>>,
+    // The messages not sent yet, with when they were queued.
+    queue: VecDeque<(Pending, Instant)>,
+    // The batches sent and not acknowledged yet, in order.
+    in_flight: VecDeque<Vec<Pending>>,
+    flushes: Vec<oneshot::Sender<()>>,
+}
+
+impl<C: LiteClient> PartitionPublisher<C> {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        let mut stream: Option<LiteStream<PublishResponse>> = None;
+        let mut reconnect = None;
+        let mut backoff = self.settings.initial_backoff;
+        // When the partition started failing with retryable errors.
+        let mut failing_since = None;
+        let mut open = true;
+        loop {
+            let idle = self.queue.is_empty() && self.in_flight.is_empty();
+            if !idle && stream.is_none() && reconnect.is_none() {
+                reconnect = Some(Instant::now());
+            }
+            self.dispatch(!open || !self.flushes.is_empty());
+            if self.queue.is_empty() && self.in_flight.is_empty() {
+                for flush in self.flushes.drain(..) {
+                    let _ = flush.send(());
+                }
+                if !open {
+                    return;
+                }
+            }
+
+            let deadline = self
+                .queue
+                .front()
+                .filter(|_| self.requests.is_some())
+                .map(|(_, queued)| *queued + self.settings.batch.max_delay);
+            let status = tokio::select! {
+                command = commands.recv(), if open => {
+                    match command {
+                        Some(Command::Publish(pending)) => {
+                            self.queue.push_back((pending, Instant::now()));
+                        }
+                        Some(Command::Flush(tx)) => self.flushes.push(tx),
+                        None => open = false,
+                    }
+                    continue;
+                }
+                response = client::next(&mut stream), if stream.is_some() => match response {
+                    Some(Ok(response)) => {
+                        backoff = self.settings.initial_backoff;
+                        failing_since = None;
+                        match response.response_type {
+                            Some(publish_response::ResponseType::MessageResponse(r)) => {
+                                self.complete(r.start_cursor.unwrap_or_default());
+                                continue;
+                            }
+                            _ => tonic::Status::internal("unexpected publish response"),
+                        }
+                    }
+                    Some(Err(status)) => status,
+                    None => tonic::Status::unavailable("stream closed"),
+                },
+                _ = time::sleep_until(reconnect.unwrap_or_else(Instant::now)), if reconnect.is_some() => {
+                    reconnect = None;
+                    match self.connect().await {
+                        Ok(s) => {
+                            stream = Some(s);
+                            continue;
+                        }
+                        Err(status) => status,
+                    }
+                }
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => continue,
+            };
+
+            // The stream failed.
+            stream = None;
+            self.requests = None;
+            let since = *failing_since.get_or_insert_with(Instant::now);
+            if !RETRYABLE_CODES.contains(&status.code())
+                || since.elapsed() + backoff > self.settings.timeout
+            {
+                self.fail(status);
+                failing_since = None;
+                backoff = self.settings.initial_backoff;
+            } else {
+                reconnect = Some(Instant::now() + backoff);
+                backoff = (backoff * 2).min(self.settings.max_backoff);
+            }
+        }
+    }
+
+    // Opens the stream, and sends again the batches not acknowledged on the previous one.
+    async fn connect(&mut self) -> Result<LiteStream<PublishResponse>, tonic::Status> {
+        let initial = PublishRequest {
+            request_type: Some(publish_request::RequestType::InitialRequest(
+                InitialPublishRequest {
+                    topic: self.topic.clone(),
+                    partition: self.partition,
+                },
+            )),
+        };
+        let client = &mut self.client;
+        let (tx, stream) = client::connect(
+            initial,
+            |requests| client.publish(requests),
+            |r: &PublishResponse| {
+                matches!(
+                    r.response_type,
+                    Some(publish_response::ResponseType::InitialResponse(_))
+                )
+            },
+        )
+        .await?;
+        for batch in &self.in_flight {
+            let _ = tx.send(batch_request(batch.iter().map(|p| p.message.clone())));
+        }
+        self.requests = Some(tx);
+        Ok(stream)
+    }
+
+    // Sends the batches which are full or past their delay.
+    fn dispatch(&mut self, force: bool) {
+        let requests = match &self.requests {
+            Some(requests) => requests,
+            None => return,
+        };
+        let batch = &self.settings.batch;
+        let now = Instant::now();
+        while let Some((_, queued)) = self.queue.front() {
+            let mut n = 0;
+            let mut bytes = 0;
+            for (pending, _) in &self.queue {
+                if n == batch.max_messages || n > 0 && bytes + pending.size > batch.max_bytes {
+                    break;
+                }
+                n += 1;
+                bytes += pending.size;
+            }
+            let full = n == batch.max_messages || n < self.queue.len();
+            if !force && !full && *queued + batch.max_delay > now {
+                return;
+            }
+            let messages = self.queue.drain(..n).map(|(p, _)| p).collect::<Vec<_>>();
+            let _ = requests.send(batch_request(messages.iter().map(|p| p.message.clone())));
+            self.in_flight.push_back(messages);
+        }
+    }
+
+    // Resolves the messages of the oldest batch, which start at `start`.
+    fn complete(&mut self, start: Cursor) {
+        let batch = match self.in_flight.pop_front() {
+            Some(batch) => batch,
+            None => return,
+        };
+        for (i, pending) in batch.into_iter().enumerate() {
+            let _ = pending.result.send(Ok(MessageMetadata {
+                partition: self.partition,
+                cursor: Cursor {
+                    offset: start.offset + i as i64,
+                },
+            }));
+        }
+    }
+
+    fn fail(&mut self, status: tonic::Status) {
+        let e = PublishError::Status(status);
+        let queued = self.queue.drain(..).map(|(p, _)| p);
+        for pending in self.in_flight.drain(..).flatten().chain(queued) {
+            let _ = pending.result.send(Err(e.clone()));
+        }
+    }
+}
+
+fn batch_request(messages: impl Iterator<Item = PubSubMessage>) -> PublishRequest {
+    PublishRequest {
+        request_type: Some(publish_request::RequestType::MessagePublishRequest(
+            MessagePublishRequest {
+                messages: messages.collect(),
+            },
+        )),
+    }
+}
+
+// FIPS 180-4.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 32];
+    for (chunk, h) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::*;
+
+    use super::super::{client::testing::Fake, MessagePublishResponse};
+
+    fn message(data: &str, key: &str) -> PubSubMessage {
+        PubSubMessage {
+            key: key.as_bytes().to_vec(),
+            data: data.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn published(offset: i64) -> Result<PublishResponse, tonic::Status> {
+        Ok(PublishResponse {
+            response_type: Some(publish_response::ResponseType::MessageResponse(
+                MessagePublishResponse {
+                    start_cursor: Some(Cursor { offset }),
+                },
+            )),
+        })
+    }
+
+    // The data of the messages of each batch of a stream.
+    fn batches(requests: &[PublishRequest]) -> Vec<Vec<String>> {
+        requests
+            .iter()
+            .filter_map(|r| match &r.request_type {
+                Some(publish_request::RequestType::MessagePublishRequest(r)) => Some(
+                    r.messages
+                        .iter()
+                        .map(|m| String::from_utf8_lossy(&m.data).into_owned())
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn settings(max_messages: usize) -> PublisherSettings {
+        PublisherSettings {
+            batch: BatchSettings {
+                max_messages,
+                max_delay: Duration::from_secs(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sha256() {
+        let hex = |d: [u8; 32]| d.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        assert_eq!(
+            hex(sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(sha256(&[b'a'; 64])),
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"
+        );
+    }
+
+    #[test]
+    fn test_key_partition() {
+        // The SHA-256 hashes of the keys, modulo the number of partitions.
+        assert_eq!(key_partition(b"oaisdhfoiahsd", 29), 18);
+        assert_eq!(key_partition(b"P(#*YNPOIUDF", 29), 9);
+        assert_eq!(key_partition(b"LCIUNDFPOASIUN", 29), 8);
+        assert_eq!(key_partition(b";odsfiupoius", 29), 9);
+        assert_eq!(key_partition(b"OPISUDfpoiu2903", 29), 16);
+        assert_eq!(key_partition(b"OPISUDfpoiu2903", 1), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish() {
+        let fake = Fake::default();
+        fake.partitions.lock().unwrap().push_back(Ok(2));
+        let first = fake.publish.stream(0);
+        let second = fake.publish.stream(1);
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings(2));
+
+        // Messages with a key go to its partition, the others to each partition in turn.
+        let results = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|data| publisher.publish(message(data, "")))
+            .collect::<Vec<_>>();
+        let keyed = publisher.publish(message("f", "OPISUDfpoiu2903"));
+        time::sleep(Duration::from_millis(10)).await;
+        first.send(published(10)).unwrap();
+        second.send(published(20)).unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+        second.send(published(22)).unwrap();
+
+        let mut metadata = Vec::new();
+        for result in results {
+            let m = result.await.unwrap();
+            metadata.push((m.partition, m.cursor.offset));
+        }
+        assert_eq!(metadata, vec![(1, 20), (0, 10), (1, 21), (0, 11), (1, 22)]);
+        let keyed = keyed.await.unwrap();
+        assert_eq!((keyed.partition, keyed.cursor.offset), (1, 23));
+        assert_eq!(fake.publish.requests(0).len(), 1);
+        let requests = &fake.publish.requests(0)[0];
+        match &requests[0].request_type {
+            Some(publish_request::RequestType::InitialRequest(r)) => {
+                assert_eq!((r.topic.as_str(), r.partition), ("t", 0));
+            }
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(batches(requests), vec![vec!["b", "d"]]);
+        assert_eq!(
+            batches(&fake.publish.requests(1)[0]),
+            vec![vec!["a", "c"], vec!["e", "f"]]
+        );
+
+        assert!(matches!(
+            publisher
+                .publish(message(&"x".repeat(MAX_PUBLISH_BYTES), ""))
+                .await,
+            Err(PublishError::TooLarge(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_flush() {
+        let fake = Fake::default();
+        let responses = fake.publish.stream(0);
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings(100));
+        let result = publisher.publish(message("a", ""));
+        let responder = tokio::spawn(async move {
+            time::sleep(Duration::from_millis(1)).await;
+            responses.send(published(5)).unwrap();
+            responses
+        });
+        let start = Instant::now();
+        publisher.flush().await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(result.await.unwrap().cursor.offset, 5);
+
+        // Pending messages are published on shutdown.
+        let responses = responder.await.unwrap();
+        let result = publisher.publish(message("b", ""));
+        let shutdown = tokio::spawn(publisher.shutdown());
+        time::sleep(Duration::from_millis(1)).await;
+        responses.send(published(6)).unwrap();
+        shutdown.await.unwrap();
+        assert_eq!(result.await.unwrap().cursor.offset, 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_reconnect() {
+        let fake = Fake::default();
+        let first = fake.publish.stream(0);
+        let second = fake.publish.stream(0);
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings(1));
+        let a = publisher.publish(message("a", ""));
+        let b = publisher.publish(message("b", ""));
+        time::sleep(Duration::from_millis(10)).await;
+        first.send(published(0)).unwrap();
+        first
+            .send(Err(tonic::Status::unavailable("going away")))
+            .unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+        // The unacknowledged batch is sent again on the new stream.
+        second.send(published(1)).unwrap();
+        assert_eq!(a.await.unwrap().cursor.offset, 0);
+        assert_eq!(b.await.unwrap().cursor.offset, 1);
+        assert_eq!(batches(&fake.publish.requests(0)[1]), vec![vec!["b"]]);
+
+        // Non-retryable errors fail the pending messages.
+        let c = publisher.publish(message("c", ""));
+        time::sleep(Duration::from_millis(10)).await;
+        second
+            .send(Err(tonic::Status::permission_denied("denied")))
+            .unwrap();
+        match c.await {
+            Err(PublishError::Status(s)) => assert_eq!(s.code(), tonic::Code::PermissionDenied),
+            r => panic!("{:?}", r),
+        }
+
+        // So does the number of partitions failing to be fetched.
+        let fake = Fake::default();
+        fake.partitions
+            .lock()
+            .unwrap()
+            .push_back(Err(tonic::Status::not_found("no topic")));
+        let publisher = Publisher::new(fake, "t");
+        match publisher.publish(message("a", "")).await {
+            Err(PublishError::Status(s)) => assert_eq!(s.code(), tonic::Code::NotFound),
+            r => panic!("{:?}", r),
+        }
+    }
+}
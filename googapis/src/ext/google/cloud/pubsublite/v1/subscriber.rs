@@ -0,0 +1,874 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use super::{
+    client::{self, LiteClient, LiteStream, RETRYABLE_CODES},
+    partition_assignment_request, seek_request, streaming_commit_cursor_request,
+    streaming_commit_cursor_response, subscribe_request, subscribe_response, Cursor,
+    FlowControlRequest, InitialCommitCursorRequest, InitialPartitionAssignmentRequest,
+    InitialSubscribeRequest, PartitionAssignment, PartitionAssignmentAck,
+    PartitionAssignmentRequest, SeekRequest, SequencedCommitCursorRequest, SequencedMessage,
+    StreamingCommitCursorRequest, StreamingCommitCursorResponse, SubscribeRequest,
+    SubscribeResponse,
+};
+
+#[derive(Debug, Clone)]
+pub struct SubscriberSettings {
+    /// The partitions to receive messages from, or `None` for the ones assigned by the
+    /// partition assignment service, which spreads the partitions of the subscription over its
+    /// subscribers.
+    pub partitions: Option<Vec<i64>>,
+    /// The most messages of a partition returned by [`Subscriber::next`] and not yet acked,
+    /// which are the message tokens of its `FlowControlRequest`s.
+    pub max_outstanding_messages: i64,
+    /// The most bytes of messages of a partition returned by [`Subscriber::next`] and not yet
+    /// acked, which are the byte tokens of its `FlowControlRequest`s.
+    pub max_outstanding_bytes: i64,
+    /// How long acks wait to be committed and returned as flow control tokens together.
+    pub commit_delay: Duration,
+    /// The backoff between reconnections of a stream, doubled after each retryable error.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for SubscriberSettings {
+    fn default() -> Self {
+        SubscriberSettings {
+            partitions: None,
+            max_outstanding_messages: 1000,
+            max_outstanding_bytes: 1_000_000_000,
+            commit_delay: Duration::from_millis(50),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    // A message was acked, or dropped if not `ack`.
+    Done { offset: i64, size: i64, ack: bool },
+    // Stops receiving, after the outstanding messages are done if `wait`.
+    Stop { wait: bool },
+}
+
+/// A message returned by [`Subscriber::next`].
+///
+/// Pub/Sub Lite only commits cursors: dropping a message without acking it gives back its flow
+/// control tokens, but holds back the commits of its partition at its offset, so that it is
+/// received again by the next subscriber of the partition.
+#[derive(Debug)]
+pub struct ReceivedMessage {
+    partition: i64,
+    message: SequencedMessage,
+    commands: mpsc::UnboundedSender<Command>,
+    done: bool,
+}
+
+impl ReceivedMessage {
+    pub fn partition(&self) -> i64 {
+        self.partition
+    }
+
+    pub fn message(&self) -> &SequencedMessage {
+        &self.message
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.message.cursor.as_ref().map_or(0, |c| c.offset)
+    }
+
+    /// Acknowledges the message. The cursor of the partition is committed past it once the
+    /// previous messages of the partition are acked too.
+    pub fn ack(mut self) {
+        self.done(true);
+    }
+
+    fn done(&mut self, ack: bool) {
+        self.done = true;
+        let _ = self.commands.send(Command::Done {
+            offset: self.offset(),
+            size: self.message.size_bytes,
+            ack,
+        });
+    }
+}
+
+impl Drop for ReceivedMessage {
+    fn drop(&mut self) {
+        if !self.done {
+            self.done(false);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SubscriberCommand {
+    Stop(Option<oneshot::Sender<()>>),
+}
+
+/// Receives the messages of the partitions of a Pub/Sub Lite subscription.
+///
+/// The partitions are the ones of [`SubscriberSettings::partitions`], or else the ones assigned
+/// to the subscriber by the partition assignment service, which are stopped and started as the
+/// assignment changes. Each partition has its own `Subscribe` stream, which receives the
+/// messages from the committed cursor of the partition, within the flow control tokens given
+/// back as messages are acked. The cursor is committed on a `StreamingCommitCursor` stream past
+/// the messages acked in order. Streams are opened again after retryable errors.
+///
+/// # Example
+/// ```ignore
+/// use googapis::google::cloud::pubsublite::v1::{LiteClients, Subscriber};
+///
+/// let subscription = "projects/p/locations/l/subscriptions/s";
+/// let mut subscriber = Subscriber::new(LiteClients::new(channel), subscription);
+/// while let Some(message) = subscriber.next().await {
+///     let message = message?;
+///     process(message.message()).await;
+///     message.ack();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Subscriber {
+    deliveries: mpsc::UnboundedReceiver<Result<ReceivedMessage, tonic::Status>>,
+    commands: mpsc::UnboundedSender<SubscriberCommand>,
+}
+
+impl Subscriber {
+    /// A subscriber with the default settings. It must be created in a Tokio runtime.
+    pub fn new<C: LiteClient>(client: C, subscription: impl Into<String>) -> Self {
+        Self::with_settings(client, subscription, SubscriberSettings::default())
+    }
+
+    /// A subscriber with `settings`. It must be created in a Tokio runtime.
+    pub fn with_settings<C: LiteClient>(
+        client: C,
+        subscription: impl Into<String>,
+        settings: SubscriberSettings,
+    ) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let (deliveries_tx, deliveries) = mpsc::unbounded_channel();
+        let (errors_tx, errors) = mpsc::unbounded_channel();
+        let assigner = Assigner {
+            client,
+            subscription: subscription.into(),
+            settings: Arc::new(settings),
+            partitions: HashMap::new(),
+            deliveries: deliveries_tx,
+            errors: errors_tx,
+        };
+        tokio::spawn(assigner.run(rx, errors));
+        Subscriber {
+            deliveries,
+            commands,
+        }
+    }
+
+    /// The next message, or the error which stopped the subscriber.
+    pub async fn next(&mut self) -> Option<Result<ReceivedMessage, tonic::Status>> {
+        self.deliveries.recv().await
+    }
+
+    /// Stops receiving messages, and waits for the returned ones to be acked or dropped and for
+    /// the cursors to be committed.
+    pub async fn shutdown(mut self) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.commands.send(SubscriberCommand::Stop(Some(tx)));
+        self.deliveries.close();
+        while self.deliveries.try_recv().is_ok() {}
+        let _ = rx.await;
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let _ = self.commands.send(SubscriberCommand::Stop(None));
+    }
+}
+
+// A unique id per subscriber, see `InitialPartitionAssignmentRequest.client_id`.
+fn client_id() -> Vec<u8> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut id = Vec::with_capacity(16);
+    id.extend_from_slice(&process::id().to_be_bytes());
+    id.extend_from_slice(&nanos.to_be_bytes());
+    id.extend_from_slice(&(COUNT.fetch_add(1, Ordering::Relaxed) as u32).to_be_bytes());
+    id
+}
+
+#[derive(Debug)]
+struct Partition {
+    commands: mpsc::UnboundedSender<Command>,
+    task: JoinHandle<()>,
+}
+
+// The task starting and stopping the partitions of a `Subscriber`.
+struct Assigner<C> {
+    client: C,
+    subscription: String,
+    settings: Arc<SubscriberSettings>,
+    partitions: HashMap<i64, Partition>,
+    deliveries: mpsc::UnboundedSender<Result<ReceivedMessage, tonic::Status>>,
+    // The non-retryable errors of the partitions.
+    errors: mpsc::UnboundedSender<tonic::Status>,
+}
+
+impl<C: LiteClient> Assigner<C> {
+    async fn run(
+        mut self,
+        mut commands: mpsc::UnboundedReceiver<SubscriberCommand>,
+        mut errors: mpsc::UnboundedReceiver<tonic::Status>,
+    ) {
+        let mut stream: Option<LiteStream<PartitionAssignment>> = None;
+        let mut requests: Option<mpsc::UnboundedSender<PartitionAssignmentRequest>> = None;
+        let mut reconnect = None;
+        let mut backoff = self.settings.initial_backoff;
+        let client_id = client_id();
+        match self.settings.partitions.clone() {
+            Some(partitions) => self.assign(partitions).await,
+            None => reconnect = Some(Instant::now()),
+        }
+        loop {
+            tokio::select! {
+                command = commands.recv() => {
+                    let SubscriberCommand::Stop(waiter) = command.unwrap_or(SubscriberCommand::Stop(None));
+                    self.stop(true).await;
+                    if let Some(waiter) = waiter {
+                        let _ = waiter.send(());
+                    }
+                    return;
+                }
+                Some(status) = errors.recv() => {
+                    let _ = self.deliveries.send(Err(status));
+                    self.stop(false).await;
+                    return;
+                }
+                response = client::next(&mut stream), if stream.is_some() => match response {
+                    Some(Ok(assignment)) => {
+                        backoff = self.settings.initial_backoff;
+                        self.assign(assignment.partitions).await;
+                        let ack = partition_assignment_request::Request::Ack(PartitionAssignmentAck {});
+                        if let Some(requests) = &requests {
+                            let _ = requests.send(PartitionAssignmentRequest { request: Some(ack) });
+                        }
+                    }
+                    Some(Err(status)) if !RETRYABLE_CODES.contains(&status.code()) => {
+                        let _ = self.deliveries.send(Err(status));
+                        self.stop(false).await;
+                        return;
+                    }
+                    _ => {
+                        stream = None;
+                        requests = None;
+                        reconnect = Some(Instant::now() + backoff);
+                        backoff = (backoff * 2).min(self.settings.max_backoff);
+                    }
+                },
+                _ = time::sleep_until(reconnect.unwrap_or_else(Instant::now)), if reconnect.is_some() => {
+                    reconnect = None;
+                    let (tx, rx) = client::LiteRequests::channel();
+                    let initial = InitialPartitionAssignmentRequest {
+                        subscription: self.subscription.clone(),
+                        client_id: client_id.clone(),
+                    };
+                    let initial = partition_assignment_request::Request::Initial(initial);
+                    let _ = tx.send(PartitionAssignmentRequest { request: Some(initial) });
+                    match self.client.assign_partitions(rx).await {
+                        Ok(s) => {
+                            stream = Some(s);
+                            requests = Some(tx);
+                        }
+                        Err(status) if !RETRYABLE_CODES.contains(&status.code()) => {
+                            let _ = self.deliveries.send(Err(status));
+                            self.stop(false).await;
+                            return;
+                        }
+                        Err(_) => {
+                            reconnect = Some(Instant::now() + backoff);
+                            backoff = (backoff * 2).min(self.settings.max_backoff);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Stops the partitions not assigned anymore, once their acked messages are committed, and
+    // starts the new ones.
+    async fn assign(&mut self, partitions: Vec<i64>) {
+        let assigned = partitions.into_iter().collect::<HashSet<_>>();
+        let revoked = self
+            .partitions
+            .keys()
+            .filter(|p| !assigned.contains(p))
+            .copied()
+            .collect::<Vec<_>>();
+        for partition in revoked {
+            let partition = self.partitions.remove(&partition).unwrap();
+            let _ = partition.commands.send(Command::Stop { wait: false });
+            let _ = partition.task.await;
+        }
+        for partition in assigned {
+            if self.partitions.contains_key(&partition) {
+                continue;
+            }
+            let (commands, rx) = mpsc::unbounded_channel();
+            let subscriber = PartitionSubscriber {
+                client: self.client.clone(),
+                subscription: self.subscription.clone(),
+                partition,
+                settings: self.settings.clone(),
+                subscribe_requests: None,
+                commit_requests: None,
+                next_offset: None,
+                delivered: VecDeque::new(),
+                outstanding: (0, 0),
+                tokens: (0, 0),
+                commit: None,
+                committed: None,
+                unacknowledged: 0,
+                stopping: None,
+                deliveries: self.deliveries.clone(),
+                commands: commands.clone(),
+            };
+            let task = tokio::spawn(subscriber.run(rx, self.errors.clone()));
+            self.partitions
+                .insert(partition, Partition { commands, task });
+        }
+    }
+
+    async fn stop(&mut self, wait: bool) {
+        for partition in self.partitions.values() {
+            let _ = partition.commands.send(Command::Stop { wait });
+        }
+        for (_, partition) in self.partitions.drain() {
+            let _ = partition.task.await;
+        }
+    }
+}
+
+// The task receiving the messages of a partition and committing its cursor.
+struct PartitionSubscriber<C> {
+    client: C,
+    subscription: String,
+    partition: i64,
+    settings: Arc<SubscriberSettings>,
+    subscribe_requests: Option<mpsc::UnboundedSender<SubscribeRequest>>,
+    commit_requests: Option<mpsc::UnboundedSender<StreamingCommitCursorRequest>>,
+    // The offset after the last message received, where a new stream starts.
+    next_offset: Option<i64>,
+    // The offsets of the messages delivered and not committed yet, and whether they are acked.
+    delivered: VecDeque<(i64, bool)>,
+    // The messages and bytes delivered but not acked or dropped yet.
+    outstanding: (i64, i64),
+    // The flow control tokens to give back.
+    tokens: (i64, i64),
+    // The cursor to commit, and the last one sent.
+    commit: Option<i64>,
+    committed: Option<i64>,
+    // The commits sent and not acknowledged yet.
+    unacknowledged: i64,
+    // Whether to wait for the outstanding messages, once stopping.
+    stopping: Option<bool>,
+    deliveries: mpsc::UnboundedSender<Result<ReceivedMessage, tonic::Status>>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl<C: LiteClient> PartitionSubscriber<C> {
+    async fn run(
+        mut self,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+        errors: mpsc::UnboundedSender<tonic::Status>,
+    ) {
+        let mut subscribe: Option<LiteStream<SubscribeResponse>> = None;
+        let mut commit: Option<LiteStream<StreamingCommitCursorResponse>> = None;
+        let mut subscribe_at = Some(Instant::now());
+        let mut commit_at = Some(Instant::now());
+        let mut subscribe_backoff = self.settings.initial_backoff;
+        let mut commit_backoff = self.settings.initial_backoff;
+        let delay = self.settings.commit_delay;
+        let mut flush = time::interval_at(Instant::now() + delay, delay);
+        loop {
+            if let Some(wait) = self.stopping {
+                let done = !wait || self.outstanding.0 == 0;
+                if done && self.commit == self.committed && self.unacknowledged == 0 {
+                    return;
+                }
+                subscribe = None;
+                subscribe_at = None;
+            }
+            let status = tokio::select! {
+                Some(command) = commands.recv() => {
+                    match command {
+                        Command::Done { offset, size, ack } => self.done(offset, size, ack),
+                        Command::Stop { wait } => {
+                            self.stopping = Some(wait);
+                            self.subscribe_requests = None;
+                        }
+                    }
+                    continue;
+                }
+                response = client::next(&mut subscribe), if subscribe.is_some() => match response {
+                    Some(Ok(response)) => {
+                        subscribe_backoff = self.settings.initial_backoff;
+                        if let Some(subscribe_response::Response::Messages(m)) = response.response {
+                            self.receive(m.messages);
+                        }
+                        continue;
+                    }
+                    Some(Err(status)) if !RETRYABLE_CODES.contains(&status.code()) => status,
+                    _ => {
+                        subscribe = None;
+                        self.subscribe_requests = None;
+                        subscribe_at = Some(Instant::now() + subscribe_backoff);
+                        subscribe_backoff = (subscribe_backoff * 2).min(self.settings.max_backoff);
+                        continue;
+                    }
+                },
+                response = client::next(&mut commit), if commit.is_some() => match response {
+                    Some(Ok(response)) => {
+                        commit_backoff = self.settings.initial_backoff;
+                        if let Some(streaming_commit_cursor_response::Request::Commit(r)) = response.request {
+                            self.unacknowledged -= r.acknowledged_commits;
+                        }
+                        continue;
+                    }
+                    Some(Err(status)) if !RETRYABLE_CODES.contains(&status.code()) => status,
+                    _ => {
+                        commit = None;
+                        self.commit_requests = None;
+                        commit_at = Some(Instant::now() + commit_backoff);
+                        commit_backoff = (commit_backoff * 2).min(self.settings.max_backoff);
+                        continue;
+                    }
+                },
+                _ = time::sleep_until(subscribe_at.unwrap_or_else(Instant::now)), if subscribe_at.is_some() => {
+                    subscribe_at = None;
+                    match self.subscribe().await {
+                        Ok(s) => {
+                            subscribe = Some(s);
+                            continue;
+                        }
+                        Err(status) if !RETRYABLE_CODES.contains(&status.code()) => status,
+                        Err(_) => {
+                            subscribe_at = Some(Instant::now() + subscribe_backoff);
+                            subscribe_backoff = (subscribe_backoff * 2).min(self.settings.max_backoff);
+                            continue;
+                        }
+                    }
+                }
+                _ = time::sleep_until(commit_at.unwrap_or_else(Instant::now)), if commit_at.is_some() => {
+                    commit_at = None;
+                    match self.connect_commit().await {
+                        Ok(s) => {
+                            commit = Some(s);
+                            continue;
+                        }
+                        Err(status) if !RETRYABLE_CODES.contains(&status.code()) => status,
+                        Err(_) => {
+                            commit_at = Some(Instant::now() + commit_backoff);
+                            commit_backoff = (commit_backoff * 2).min(self.settings.max_backoff);
+                            continue;
+                        }
+                    }
+                }
+                _ = flush.tick() => {
+                    self.flush();
+                    continue;
+                }
+            };
+            let _ = errors.send(status);
+            return;
+        }
+    }
+
+    // Opens the subscribe stream where the previous one stopped, or else at the committed
+    // cursor, and gives it the tokens of the messages not outstanding.
+    async fn subscribe(&mut self) -> Result<LiteStream<SubscribeResponse>, tonic::Status> {
+        let initial_location = self.next_offset.map(|offset| SeekRequest {
+            target: Some(seek_request::Target::Cursor(Cursor { offset })),
+        });
+        let initial = subscribe_request::Request::Initial(InitialSubscribeRequest {
+            subscription: self.subscription.clone(),
+            partition: self.partition,
+            initial_location,
+        });
+        let client = &mut self.client;
+        let (tx, stream) = client::connect(
+            SubscribeRequest {
+                request: Some(initial),
+            },
+            |requests| client.subscribe(requests),
+            |r: &SubscribeResponse| {
+                matches!(r.response, Some(subscribe_response::Response::Initial(_)))
+            },
+        )
+        .await?;
+        let flow_control = FlowControlRequest {
+            allowed_messages: self.settings.max_outstanding_messages - self.outstanding.0,
+            allowed_bytes: self.settings.max_outstanding_bytes - self.outstanding.1,
+        };
+        let _ = tx.send(SubscribeRequest {
+            request: Some(subscribe_request::Request::FlowControl(flow_control)),
+        });
+        self.tokens = (0, 0);
+        self.subscribe_requests = Some(tx);
+        Ok(stream)
+    }
+
+    // Opens the commit stream, on which the cursor is committed again.
+    async fn connect_commit(
+        &mut self,
+    ) -> Result<LiteStream<StreamingCommitCursorResponse>, tonic::Status> {
+        let initial =
+            streaming_commit_cursor_request::Request::Initial(InitialCommitCursorRequest {
+                subscription: self.subscription.clone(),
+                partition: self.partition,
+            });
+        let client = &mut self.client;
+        let (tx, stream) = client::connect(
+            StreamingCommitCursorRequest {
+                request: Some(initial),
+            },
+            |requests| client.streaming_commit_cursor(requests),
+            |r: &StreamingCommitCursorResponse| {
+                matches!(
+                    r.request,
+                    Some(streaming_commit_cursor_response::Request::Initial(_))
+                )
+            },
+        )
+        .await?;
+        self.committed = None;
+        self.unacknowledged = 0;
+        self.commit_requests = Some(tx);
+        Ok(stream)
+    }
+
+    fn receive(&mut self, messages: Vec<SequencedMessage>) {
+        for message in messages {
+            if self.stopping.is_some() {
+                return;
+            }
+            let offset = message.cursor.as_ref().map_or(0, |c| c.offset);
+            self.next_offset = Some(offset + 1);
+            self.delivered.push_back((offset, false));
+            self.outstanding.0 += 1;
+            self.outstanding.1 += message.size_bytes;
+            let _ = self.deliveries.send(Ok(ReceivedMessage {
+                partition: self.partition,
+                message,
+                commands: self.commands.clone(),
+                done: false,
+            }));
+        }
+    }
+
+    fn done(&mut self, offset: i64, size: i64, ack: bool) {
+        let i = match self.delivered.binary_search_by_key(&offset, |(o, _)| *o) {
+            Ok(i) => i,
+            Err(_) => return,
+        };
+        self.outstanding.0 -= 1;
+        self.outstanding.1 -= size;
+        self.tokens.0 += 1;
+        self.tokens.1 += size;
+        if !ack {
+            return;
+        }
+        self.delivered[i].1 = true;
+        while let Some((offset, true)) = self.delivered.front().copied() {
+            self.delivered.pop_front();
+            self.commit = Some(offset + 1);
+        }
+    }
+
+    // Gives back the tokens of the messages done, and commits the cursor.
+    fn flush(&mut self) {
+        if let Some(requests) = &self.subscribe_requests {
+            if self.tokens != (0, 0) {
+                let flow_control = FlowControlRequest {
+                    allowed_messages: self.tokens.0,
+                    allowed_bytes: self.tokens.1,
+                };
+                let _ = requests.send(SubscribeRequest {
+                    request: Some(subscribe_request::Request::FlowControl(flow_control)),
+                });
+                self.tokens = (0, 0);
+            }
+        }
+        if let (Some(requests), Some(offset)) = (&self.commit_requests, self.commit) {
+            if self.committed != self.commit {
+                let commit = SequencedCommitCursorRequest {
+                    cursor: Some(Cursor { offset }),
+                };
+                let _ = requests.send(StreamingCommitCursorRequest {
+                    request: Some(streaming_commit_cursor_request::Request::Commit(commit)),
+                });
+                self.committed = self.commit;
+                self.unacknowledged += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::*;
+
+    use super::super::{client::testing::Fake, MessageResponse, SequencedCommitCursorResponse};
+
+    fn messages(offsets: &[i64]) -> Result<SubscribeResponse, tonic::Status> {
+        let messages = offsets
+            .iter()
+            .map(|&offset| SequencedMessage {
+                cursor: Some(Cursor { offset }),
+                size_bytes: 10,
+                ..Default::default()
+            })
+            .collect();
+        Ok(SubscribeResponse {
+            response: Some(subscribe_response::Response::Messages(MessageResponse {
+                messages,
+            })),
+        })
+    }
+
+    fn acknowledged(n: i64) -> Result<StreamingCommitCursorResponse, tonic::Status> {
+        Ok(StreamingCommitCursorResponse {
+            request: Some(streaming_commit_cursor_response::Request::Commit(
+                SequencedCommitCursorResponse {
+                    acknowledged_commits: n,
+                },
+            )),
+        })
+    }
+
+    fn assignment(partitions: &[i64]) -> Result<PartitionAssignment, tonic::Status> {
+        Ok(PartitionAssignment {
+            partitions: partitions.to_vec(),
+        })
+    }
+
+    // The flow control tokens and commits sent on the streams of a partition.
+    fn flow_control(fake: &Fake, partition: i64) -> Vec<Vec<(i64, i64)>> {
+        let requests = fake.subscribe.requests(partition).into_iter();
+        let tokens = requests.map(|stream| {
+            stream
+                .into_iter()
+                .filter_map(|r| match r.request {
+                    Some(subscribe_request::Request::FlowControl(f)) => {
+                        Some((f.allowed_messages, f.allowed_bytes))
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        tokens.collect()
+    }
+
+    fn commits(fake: &Fake, partition: i64) -> Vec<Vec<i64>> {
+        let requests = fake.commit.requests(partition).into_iter();
+        let commits = requests.map(|stream| {
+            stream
+                .into_iter()
+                .filter_map(|r| match r.request {
+                    Some(streaming_commit_cursor_request::Request::Commit(c)) => {
+                        Some(c.cursor.unwrap().offset)
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        commits.collect()
+    }
+
+    async fn next(subscriber: &mut Subscriber) -> Option<ReceivedMessage> {
+        time::timeout(Duration::from_secs(1), subscriber.next())
+            .await
+            .ok()
+            .map(|m| m.unwrap().unwrap())
+    }
+
+    fn settings(partitions: &[i64]) -> SubscriberSettings {
+        SubscriberSettings {
+            partitions: Some(partitions.to_vec()),
+            max_outstanding_messages: 2,
+            max_outstanding_bytes: 100,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe() {
+        let fake = Fake::default();
+        let responses = fake.subscribe.stream(3);
+        let _commit = fake.commit.stream(3);
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings(&[3]));
+        responses.send(messages(&[5, 6, 7])).unwrap();
+        let five = next(&mut subscriber).await.unwrap();
+        let six = next(&mut subscriber).await.unwrap();
+        let seven = next(&mut subscriber).await.unwrap();
+        assert_eq!((five.partition(), five.offset()), (3, 5));
+
+        // The cursor is committed past the messages acked in order.
+        six.ack();
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(commits(&fake, 3), vec![Vec::<i64>::new()]);
+        five.ack();
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(commits(&fake, 3), vec![vec![7]]);
+        // A dropped message holds back the commits.
+        drop(seven);
+        time::sleep(Duration::from_millis(100)).await;
+        responses.send(messages(&[8])).unwrap();
+        next(&mut subscriber).await.unwrap().ack();
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(commits(&fake, 3), vec![vec![7]]);
+
+        match &fake.subscribe.requests(3)[0][0].request {
+            Some(subscribe_request::Request::Initial(r)) => {
+                assert_eq!((r.subscription.as_str(), r.partition), ("s", 3));
+                assert_eq!(r.initial_location, None);
+            }
+            r => panic!("{:?}", r),
+        }
+        // The tokens of the messages done are given back.
+        assert_eq!(
+            flow_control(&fake, 3),
+            vec![vec![(2, 100), (1, 10), (1, 10), (1, 10), (1, 10)]]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_reconnect() {
+        let fake = Fake::default();
+        let first = fake.subscribe.stream(0);
+        let second = fake.subscribe.stream(0);
+        let first_commit = fake.commit.stream(0);
+        let second_commit = fake.commit.stream(0);
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings(&[0]));
+        first.send(messages(&[0, 1])).unwrap();
+        let zero = next(&mut subscriber).await.unwrap();
+        let _one = next(&mut subscriber).await.unwrap();
+        zero.ack();
+        time::sleep(Duration::from_millis(100)).await;
+        first
+            .send(Err(tonic::Status::unavailable("going away")))
+            .unwrap();
+        first_commit
+            .send(Err(tonic::Status::unavailable("going away")))
+            .unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+
+        // The new stream starts after the last message received, with the tokens left.
+        match &fake.subscribe.requests(0)[1][0].request {
+            Some(subscribe_request::Request::Initial(r)) => {
+                let target = seek_request::Target::Cursor(Cursor { offset: 2 });
+                assert_eq!(r.initial_location.as_ref().unwrap().target, Some(target));
+            }
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(flow_control(&fake, 0)[1], vec![(1, 90)]);
+        // The cursor is committed again on the new commit stream.
+        assert_eq!(commits(&fake, 0), vec![vec![1], vec![1]]);
+        second.send(messages(&[2])).unwrap();
+        assert_eq!(next(&mut subscriber).await.unwrap().offset(), 2);
+
+        // Non-retryable errors stop the subscriber.
+        second_commit
+            .send(Err(tonic::Status::permission_denied("denied")))
+            .unwrap();
+        let status = subscriber.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(subscriber.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_assignment() {
+        let fake = Fake::default();
+        let assignments = fake.assign.stream(0);
+        let zero = fake.subscribe.stream(0);
+        let one = fake.subscribe.stream(1);
+        let zero_commit = fake.commit.stream(0);
+        let _one_commit = fake.commit.stream(1);
+        let mut subscriber = Subscriber::new(fake.clone(), "s");
+        assignments.send(assignment(&[0, 1])).unwrap();
+        time::sleep(Duration::from_millis(10)).await;
+        zero.send(messages(&[0])).unwrap();
+        one.send(messages(&[10])).unwrap();
+        let mut offsets = vec![
+            next(&mut subscriber).await.unwrap(),
+            next(&mut subscriber).await.unwrap(),
+        ];
+        offsets.sort_by_key(|m| m.offset());
+        let ten = offsets.pop().unwrap();
+        offsets.pop().unwrap().ack();
+
+        // A revoked partition commits its acked messages, then stops.
+        let revoke = tokio::spawn(async move {
+            time::sleep(Duration::from_millis(10)).await;
+            assignments.send(assignment(&[1])).unwrap();
+            time::sleep(Duration::from_millis(100)).await;
+            zero_commit.send(acknowledged(1)).unwrap();
+            assignments
+        });
+        let _assignments = revoke.await.unwrap();
+        time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(commits(&fake, 0), vec![vec![1]]);
+        assert!(zero.is_closed());
+        assert!(!one.is_closed());
+
+        let requests = fake.assign.requests(0);
+        match &requests[0][0].request {
+            Some(partition_assignment_request::Request::Initial(r)) => {
+                assert_eq!(r.subscription, "s");
+                assert_eq!(r.client_id.len(), 16);
+            }
+            r => panic!("{:?}", r),
+        }
+        // Each assignment is acked once applied.
+        assert_eq!(requests[0].len(), 3);
+        ten.ack();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_shutdown() {
+        let fake = Fake::default();
+        let responses = fake.subscribe.stream(0);
+        let commit = fake.commit.stream(0);
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings(&[0]));
+        responses.send(messages(&[0, 1])).unwrap();
+        let zero = next(&mut subscriber).await.unwrap();
+        time::sleep(Duration::from_millis(10)).await;
+
+        let shutdown = tokio::spawn(subscriber.shutdown());
+        time::sleep(Duration::from_secs(1)).await;
+        assert!(!shutdown.is_finished());
+        assert!(responses.is_closed());
+        zero.ack();
+        time::sleep(Duration::from_millis(100)).await;
+        // It waits for the commit to be acknowledged.
+        assert!(!shutdown.is_finished());
+        commit.send(acknowledged(1)).unwrap();
+        shutdown.await.unwrap();
+        assert_eq!(commits(&fake, 0), vec![vec![1]]);
+    }
+}
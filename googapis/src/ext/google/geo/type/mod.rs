@@ -0,0 +1,112 @@
+use crate::google::r#type::LatLng;
+
+impl Viewport {
+    /// The smallest viewport containing the points, crossing the 180 degree longitude line when
+    /// that is narrower, or `None` if there are no points.
+    pub fn from_points(points: &[LatLng]) -> Option<Self> {
+        let (low_lat, high_lat) = points
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), p| {
+                (low.min(p.latitude), high.max(p.latitude))
+            });
+        let mut lngs = points.iter().map(|p| p.longitude).collect::<Vec<_>>();
+        lngs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let (&first, &last) = (lngs.first()?, lngs.last()?);
+
+        // The longitude range is the complement of the largest gap between the points.
+        let (mut low_lng, mut high_lng) = (first, last);
+        let mut gap = first + 360.0 - last;
+        for w in lngs.windows(2) {
+            if w[1] - w[0] > gap {
+                gap = w[1] - w[0];
+                low_lng = w[1];
+                high_lng = w[0];
+            }
+        }
+        Some(Viewport {
+            low: Some(LatLng {
+                latitude: low_lat,
+                longitude: low_lng,
+            }),
+            high: Some(LatLng {
+                latitude: high_lat,
+                longitude: high_lng,
+            }),
+        })
+    }
+
+    /// Whether the viewport is empty, or `low` or `high` is missing.
+    pub fn is_empty(&self) -> bool {
+        match (&self.low, &self.high) {
+            (Some(low), Some(high)) => {
+                low.latitude > high.latitude || low.longitude == 180.0 && high.longitude == -180.0
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the point is in the viewport, boundary included.
+    pub fn contains(&self, point: &LatLng) -> bool {
+        let (low, high) = match (&self.low, &self.high) {
+            (Some(low), Some(high)) if !self.is_empty() => (low, high),
+            _ => return false,
+        };
+        if !(low.latitude..=high.latitude).contains(&point.latitude) {
+            return false;
+        }
+        if low.longitude <= high.longitude {
+            (low.longitude..=high.longitude).contains(&point.longitude)
+        } else {
+            point.longitude >= low.longitude || point.longitude <= high.longitude
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(low: (f64, f64), high: (f64, f64)) -> Viewport {
+        Viewport {
+            low: Some(LatLng::new(low.0, low.1)),
+            high: Some(LatLng::new(high.0, high.1)),
+        }
+    }
+
+    #[test]
+    fn test_from_points() {
+        assert_eq!(Viewport::from_points(&[]), None);
+        let points = [
+            LatLng::new(40.5, -74.2),
+            LatLng::new(40.9, -73.7),
+            LatLng::new(40.7, -74.0),
+        ];
+        assert_eq!(
+            Viewport::from_points(&points),
+            Some(viewport((40.5, -74.2), (40.9, -73.7)))
+        );
+        // Fiji to Samoa.
+        let points = [LatLng::new(-18.1, 178.4), LatLng::new(-13.8, -171.8)];
+        let v = Viewport::from_points(&points).unwrap();
+        assert_eq!(v, viewport((-18.1, 178.4), (-13.8, -171.8)));
+        assert!(points.iter().all(|p| v.contains(p)));
+        assert!(!v.contains(&LatLng::new(-15.0, 0.0)));
+    }
+
+    #[test]
+    fn test_contains() {
+        let v = viewport((40.477398, -74.259087), (40.91618, -73.70018));
+        assert!(v.contains(&LatLng::new(40.7128, -74.006)));
+        assert!(v.contains(&LatLng::new(40.477398, -73.70018)));
+        assert!(!v.contains(&LatLng::new(41.0, -74.0)));
+        assert!(!v.contains(&LatLng::new(40.7, -73.0)));
+
+        let all = viewport((-90.0, -180.0), (90.0, 180.0));
+        assert!(all.contains(&LatLng::new(0.0, 180.0)));
+        let empty = viewport((-90.0, 180.0), (90.0, -180.0));
+        assert!(empty.is_empty() && !empty.contains(&LatLng::new(0.0, 180.0)));
+        let empty = viewport((1.0, 0.0), (0.0, 1.0));
+        assert!(empty.is_empty() && !empty.contains(&LatLng::new(0.5, 0.5)));
+        assert!(Viewport::default().is_empty());
+    }
+}
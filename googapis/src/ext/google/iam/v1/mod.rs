@@ -0,0 +1,9 @@
+mod client;
+mod policy;
+
+#[cfg(feature = "tokio")]
+pub use self::client::{modify_policy, MAX_ATTEMPTS};
+pub use self::{
+    client::{IamClient, PolicyFuture},
+    policy::CONDITIONAL_POLICY_VERSION,
+};
@@ -0,0 +1,221 @@
+use super::{binding_delta, Binding, BindingDelta, Policy, PolicyDelta};
+use crate::google::r#type::Expr;
+
+/// The policy version required by policies with conditional role bindings, see
+/// [policy versions](https://cloud.google.com/iam/docs/policies#versions).
+pub const CONDITIONAL_POLICY_VERSION: i32 = 3;
+
+impl Policy {
+    /// Grants `role` to `member` unconditionally. Returns `false` if the member already has it.
+    pub fn add_member(&mut self, role: &str, member: &str) -> bool {
+        self.add(role, member, None)
+    }
+
+    /// Grants `role` to `member` when `condition` holds, and upgrades the policy to
+    /// [`CONDITIONAL_POLICY_VERSION`]. Returns `false` if the member already has it.
+    pub fn add_conditional_member(&mut self, role: &str, member: &str, condition: Expr) -> bool {
+        self.version = self.version.max(CONDITIONAL_POLICY_VERSION);
+        self.add(role, member, Some(condition))
+    }
+
+    /// Revokes the unconditional grant of `role` to `member`, removing the binding if it has no
+    /// members left. Returns `false` if there is no such grant.
+    pub fn remove_member(&mut self, role: &str, member: &str) -> bool {
+        self.remove(role, member, None)
+    }
+
+    /// Revokes the grant of `role` to `member` under `condition`, removing the binding if it has
+    /// no members left. Returns `false` if there is no such grant.
+    pub fn remove_conditional_member(
+        &mut self,
+        role: &str,
+        member: &str,
+        condition: &Expr,
+    ) -> bool {
+        self.remove(role, member, Some(condition))
+    }
+
+    /// The members granted `role` unconditionally.
+    pub fn members<'a>(&'a self, role: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.bindings
+            .iter()
+            .filter(move |b| b.role == role && b.condition.is_none())
+            .flat_map(|b| b.members.iter().map(String::as_str))
+    }
+
+    /// Whether a binding has a condition, which requires [`CONDITIONAL_POLICY_VERSION`].
+    pub fn has_conditions(&self) -> bool {
+        self.bindings.iter().any(|b| b.condition.is_some())
+    }
+
+    /// The grants removed from `self` followed by the grants added by `new`, one delta per
+    /// member. The audit configs are not compared.
+    pub fn delta(&self, new: &Policy) -> PolicyDelta {
+        let old = grants(self);
+        let new = grants(new);
+        let removed = old
+            .iter()
+            .filter(|g| !new.contains(g))
+            .map(|g| g.delta(binding_delta::Action::Remove));
+        let added = new
+            .iter()
+            .filter(|g| !old.contains(g))
+            .map(|g| g.delta(binding_delta::Action::Add));
+        PolicyDelta {
+            binding_deltas: removed.chain(added).collect(),
+            audit_config_deltas: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, role: &str, member: &str, condition: Option<Expr>) -> bool {
+        match self
+            .bindings
+            .iter_mut()
+            .find(|b| b.role == role && b.condition == condition)
+        {
+            Some(b) if b.members.iter().any(|m| m == member) => false,
+            Some(b) => {
+                b.members.push(member.to_owned());
+                true
+            }
+            None => {
+                self.bindings.push(Binding {
+                    role: role.to_owned(),
+                    members: vec![member.to_owned()],
+                    condition,
+                });
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, role: &str, member: &str, condition: Option<&Expr>) -> bool {
+        let mut removed = false;
+        for b in self
+            .bindings
+            .iter_mut()
+            .filter(|b| b.role == role && b.condition.as_ref() == condition)
+        {
+            let len = b.members.len();
+            b.members.retain(|m| m != member);
+            removed |= b.members.len() != len;
+        }
+        self.bindings.retain(|b| !b.members.is_empty());
+        removed
+    }
+}
+
+// A member granted a role, under a condition.
+#[derive(PartialEq)]
+struct Grant<'a> {
+    role: &'a str,
+    member: &'a str,
+    condition: Option<&'a Expr>,
+}
+
+impl Grant<'_> {
+    fn delta(&self, action: binding_delta::Action) -> BindingDelta {
+        BindingDelta {
+            action: action as i32,
+            role: self.role.to_owned(),
+            member: self.member.to_owned(),
+            condition: self.condition.cloned(),
+        }
+    }
+}
+
+fn grants(policy: &Policy) -> Vec<Grant<'_>> {
+    let mut grants = Vec::<Grant<'_>>::new();
+    for b in policy.bindings.iter() {
+        for m in b.members.iter() {
+            let grant = Grant {
+                role: &b.role,
+                member: m,
+                condition: b.condition.as_ref(),
+            };
+            if !grants.contains(&grant) {
+                grants.push(grant);
+            }
+        }
+    }
+    grants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binding_delta::Action::{Add, Remove};
+
+    fn weekdays() -> Expr {
+        Expr {
+            expression: "request.time.getDayOfWeek() < 5".to_owned(),
+            title: "weekdays".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn delta(action: binding_delta::Action, role: &str, member: &str) -> BindingDelta {
+        BindingDelta {
+            action: action as i32,
+            role: role.to_owned(),
+            member: member.to_owned(),
+            condition: None,
+        }
+    }
+
+    #[test]
+    fn test_members() {
+        let mut policy = Policy::default();
+        assert!(policy.add_member("roles/viewer", "user:a@example.com"));
+        assert!(policy.add_member("roles/viewer", "user:b@example.com"));
+        assert!(!policy.add_member("roles/viewer", "user:a@example.com"));
+        assert!(policy.add_member("roles/owner", "user:a@example.com"));
+        assert_eq!(policy.bindings.len(), 2);
+        assert_eq!(policy.version, 0);
+        assert!(!policy.has_conditions());
+
+        assert!(policy.add_conditional_member("roles/viewer", "user:c@example.com", weekdays()));
+        assert!(!policy.add_conditional_member("roles/viewer", "user:c@example.com", weekdays()));
+        assert_eq!(policy.version, CONDITIONAL_POLICY_VERSION);
+        assert!(policy.has_conditions());
+        assert_eq!(
+            policy.members("roles/viewer").collect::<Vec<_>>(),
+            vec!["user:a@example.com", "user:b@example.com"]
+        );
+
+        assert!(!policy.remove_member("roles/viewer", "user:c@example.com"));
+        assert!(policy.remove_conditional_member(
+            "roles/viewer",
+            "user:c@example.com",
+            &weekdays()
+        ));
+        assert!(policy.remove_member("roles/owner", "user:a@example.com"));
+        assert!(!policy.remove_member("roles/owner", "user:a@example.com"));
+        assert_eq!(policy.bindings.len(), 1);
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut old = Policy::default();
+        old.add_member("roles/viewer", "user:a@example.com");
+        old.add_member("roles/viewer", "user:b@example.com");
+        let mut new = old.clone();
+        new.remove_member("roles/viewer", "user:a@example.com");
+        new.add_member("roles/owner", "user:a@example.com");
+        new.add_conditional_member("roles/viewer", "user:b@example.com", weekdays());
+
+        let d = old.delta(&new);
+        assert_eq!(
+            d.binding_deltas,
+            vec![
+                delta(Remove, "roles/viewer", "user:a@example.com"),
+                delta(Add, "roles/owner", "user:a@example.com"),
+                BindingDelta {
+                    condition: Some(weekdays()),
+                    ..delta(Add, "roles/viewer", "user:b@example.com")
+                },
+            ]
+        );
+        assert!(new.delta(&new).binding_deltas.is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::google::r#type::{LatLng, PolylineError, POLYLINE_PRECISION};
+
+impl Polyline {
+    /// A polyline with the points in the `encoded_polyline` form.
+    pub fn encoded(points: &[LatLng]) -> Result<Self, PolylineError> {
+        let encoded = LatLng::encode_polyline(points, POLYLINE_PRECISION)?;
+        Ok(Polyline {
+            polyline_type: Some(polyline::PolylineType::EncodedPolyline(encoded)),
+        })
+    }
+
+    /// A polyline with the points in the `geo_json_linestring` form.
+    pub fn geo_json(points: &[LatLng]) -> Self {
+        Polyline {
+            polyline_type: Some(polyline::PolylineType::GeoJsonLinestring(
+                LatLng::to_geo_json_line_string(points),
+            )),
+        }
+    }
+
+    /// The points of the polyline, in either form. An unset polyline has no points.
+    pub fn points(&self) -> Result<Vec<LatLng>, PolylineError> {
+        match &self.polyline_type {
+            Some(polyline::PolylineType::EncodedPolyline(encoded)) => {
+                LatLng::decode_polyline(encoded, POLYLINE_PRECISION)
+            }
+            Some(polyline::PolylineType::GeoJsonLinestring(s)) => {
+                LatLng::from_geo_json_line_string(s)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polyline() {
+        let points = vec![LatLng::new(38.5, -120.2), LatLng::new(40.7, -120.95)];
+        let encoded = Polyline::encoded(&points).unwrap();
+        assert_eq!(
+            encoded.polyline_type,
+            Some(polyline::PolylineType::EncodedPolyline(
+                "_p~iF~ps|U_ulLnnqC".to_owned()
+            ))
+        );
+        assert_eq!(encoded.points().unwrap(), points);
+        assert_eq!(Polyline::geo_json(&points).points().unwrap(), points);
+        assert_eq!(Polyline::default().points().unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    str,
+};
+
+use serde_json::{Map, Value};
+
+use super::validator::{Path, Reader, SchemaError, Segment};
+
+// A parsed Avro schema.
+#[derive(Debug, Clone)]
+pub(super) struct AvroSchema {
+    root: Type,
+    // The named types, referenced by their index.
+    named: Vec<Named>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Array(Box<Type>),
+    Map(Box<Type>),
+    Union(Vec<Type>),
+    Named(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Named {
+    full_name: String,
+    kind: NamedKind,
+}
+
+#[derive(Debug, Clone)]
+enum NamedKind {
+    Record(Vec<Field>),
+    Enum(Vec<String>),
+    Fixed(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    schema: Type,
+    default: Option<Value>,
+}
+
+fn invalid(message: impl Into<String>) -> SchemaError {
+    SchemaError::Definition(message.into())
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl AvroSchema {
+    pub(super) fn parse(definition: &str) -> Result<Self, SchemaError> {
+        let json: Value = serde_json::from_str(definition)
+            .map_err(|e| invalid(format!("invalid JSON: {}", e)))?;
+        let mut parser = Parser {
+            named: Vec::new(),
+            names: HashMap::new(),
+        };
+        let root = parser.parse(&json, "")?;
+        let schema = AvroSchema {
+            root,
+            named: parser.named,
+        };
+        schema.check_recursion()?;
+        // Defaults are checked once all the named types are known.
+        for named in &schema.named {
+            if let NamedKind::Record(fields) = &named.kind {
+                for field in fields {
+                    if let Some(default) = &field.default {
+                        let mut path = Path::default();
+                        path.push(Segment::Field(field.name.clone()));
+                        schema
+                            .json(&field.schema, default, true, &mut path)
+                            .map_err(|e| {
+                                invalid(format!("invalid default of {}: {}", named.full_name, e))
+                            })?;
+                    }
+                }
+            }
+        }
+        Ok(schema)
+    }
+
+    // Fails if a record contains itself through fields of record types only, as its values
+    // would be infinite. Unions, arrays and maps end recursions: they may hold no record.
+    fn check_recursion(&self) -> Result<(), SchemaError> {
+        for (i, named) in self.named.iter().enumerate() {
+            let mut stack = vec![i];
+            let mut seen = HashSet::new();
+            while let Some(j) = stack.pop() {
+                let fields = match &self.named[j].kind {
+                    NamedKind::Record(fields) => fields,
+                    _ => continue,
+                };
+                for field in fields {
+                    if let Type::Named(k) = field.schema {
+                        if k == i {
+                            return Err(invalid(format!(
+                                "record {} contains itself",
+                                named.full_name
+                            )));
+                        }
+                        if seen.insert(k) {
+                            stack.push(k);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The fewest bytes of the binary encoding of a value of `t`.
+    fn min_size(&self, t: &Type) -> usize {
+        match t {
+            Type::Null => 0,
+            Type::Float => 4,
+            Type::Double => 8,
+            Type::Named(i) => match &self.named[*i].kind {
+                NamedKind::Record(fields) => fields
+                    .iter()
+                    .map(|f| self.min_size(&f.schema))
+                    .fold(0, usize::saturating_add),
+                NamedKind::Enum(_) => 1,
+                NamedKind::Fixed(size) => *size,
+            },
+            // A varint, a length, a union index or the end of the blocks of an array or a map.
+            _ => 1,
+        }
+    }
+
+    pub(super) fn validate_binary(&self, message: &[u8]) -> Result<(), SchemaError> {
+        let mut reader = Reader::new(message);
+        let mut path = Path::default();
+        self.binary(&self.root, &mut reader, &mut path)?;
+        if !reader.is_empty() {
+            return Err(path.error(format!("trailing bytes at offset {}", reader.pos())));
+        }
+        Ok(())
+    }
+
+    pub(super) fn validate_json(&self, message: &Value) -> Result<(), SchemaError> {
+        self.json(&self.root, message, false, &mut Path::default())
+    }
+
+    // The name of a type in the JSON encoding of unions.
+    fn type_name(&self, t: &Type) -> &str {
+        match t {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Int => "int",
+            Type::Long => "long",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Bytes => "bytes",
+            Type::String => "string",
+            Type::Array(_) => "array",
+            Type::Map(_) => "map",
+            Type::Union(_) => "union",
+            Type::Named(i) => &self.named[*i].full_name,
+        }
+    }
+
+    fn binary(&self, t: &Type, r: &mut Reader<'_>, path: &mut Path) -> Result<(), SchemaError> {
+        path.check_depth()?;
+        match t {
+            Type::Null => {}
+            Type::Boolean => {
+                if r.take(1, path)?[0] > 1 {
+                    return Err(path.error("invalid boolean"));
+                }
+            }
+            Type::Int => {
+                let v = long(r, path)?;
+                if i32::try_from(v).is_err() {
+                    return Err(path.error(format!("int out of range: {}", v)));
+                }
+            }
+            Type::Long => {
+                long(r, path)?;
+            }
+            Type::Float => {
+                r.take(4, path)?;
+            }
+            Type::Double => {
+                r.take(8, path)?;
+            }
+            Type::Bytes => {
+                let n = length(r, path)?;
+                r.take(n, path)?;
+            }
+            Type::String => {
+                let n = length(r, path)?;
+                if str::from_utf8(r.take(n, path)?).is_err() {
+                    return Err(path.error("invalid UTF-8 string"));
+                }
+            }
+            Type::Array(items) => {
+                let size = self.min_size(items);
+                let mut index = 0;
+                while let Some(count) = block(r, size, path)? {
+                    // Items without data are always valid.
+                    if size == 0 {
+                        continue;
+                    }
+                    for _ in 0..count {
+                        path.push(Segment::Index(index));
+                        self.binary(items, r, path)?;
+                        path.pop();
+                        index += 1;
+                    }
+                }
+            }
+            Type::Map(values) => {
+                // The key length and the value.
+                let size = self.min_size(values).saturating_add(1);
+                while let Some(count) = block(r, size, path)? {
+                    for _ in 0..count {
+                        let n = length(r, path)?;
+                        let key = str::from_utf8(r.take(n, path)?)
+                            .map_err(|_| path.error("invalid UTF-8 map key"))?;
+                        path.push(Segment::Key(key.to_owned()));
+                        self.binary(values, r, path)?;
+                        path.pop();
+                    }
+                }
+            }
+            Type::Union(branches) => {
+                let index = long(r, path)?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| branches.get(i))
+                    .ok_or_else(|| path.error(format!("union index out of range: {}", index)))?;
+                self.binary(branch, r, path)?;
+            }
+            Type::Named(i) => match &self.named[*i].kind {
+                NamedKind::Record(fields) => {
+                    for field in fields {
+                        path.push(Segment::Field(field.name.clone()));
+                        self.binary(&field.schema, r, path)?;
+                        path.pop();
+                    }
+                }
+                NamedKind::Enum(symbols) => {
+                    let index = long(r, path)?;
+                    if usize::try_from(index).map_or(true, |i| i >= symbols.len()) {
+                        return Err(path.error(format!("enum index out of range: {}", index)));
+                    }
+                }
+                NamedKind::Fixed(size) => {
+                    r.take(*size, path)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // Validates the JSON encoding of a value, or a default value when `default` is set: the
+    // default of a union is a value of its first type, without the type name.
+    fn json(
+        &self,
+        t: &Type,
+        value: &Value,
+        default: bool,
+        path: &mut Path,
+    ) -> Result<(), SchemaError> {
+        let expected = |what: &str| path.error(format!("expected {}", what));
+        match t {
+            Type::Null if value.is_null() => {}
+            Type::Null => return Err(expected("null")),
+            Type::Boolean if value.is_boolean() => {}
+            Type::Boolean => return Err(expected("a boolean")),
+            Type::Int => match value.as_i64() {
+                Some(v) if i32::try_from(v).is_ok() => {}
+                Some(v) => return Err(path.error(format!("int out of range: {}", v))),
+                None => return Err(expected("an int")),
+            },
+            Type::Long if value.is_i64() => {}
+            Type::Long => return Err(expected("a long")),
+            Type::Float | Type::Double if value.is_number() => {}
+            Type::Float | Type::Double => return Err(expected("a number")),
+            Type::Bytes => {
+                bytes(value).ok_or_else(|| expected("a string of bytes"))?;
+            }
+            Type::String if value.is_string() => {}
+            Type::String => return Err(expected("a string")),
+            Type::Array(items) => {
+                let values = value.as_array().ok_or_else(|| expected("an array"))?;
+                for (i, v) in values.iter().enumerate() {
+                    path.push(Segment::Index(i));
+                    self.json(items, v, default, path)?;
+                    path.pop();
+                }
+            }
+            Type::Map(values) => {
+                let entries = value.as_object().ok_or_else(|| expected("an object"))?;
+                for (k, v) in entries {
+                    path.push(Segment::Key(k.clone()));
+                    self.json(values, v, default, path)?;
+                    path.pop();
+                }
+            }
+            Type::Union(branches) if default => self.json(&branches[0], value, default, path)?,
+            Type::Union(branches) => {
+                if value.is_null() && branches.contains(&Type::Null) {
+                    return Ok(());
+                }
+                let (name, v) = match value.as_object() {
+                    Some(o) if o.len() == 1 => o.iter().next().unwrap(),
+                    _ => return Err(expected("an object with the type name of a union branch")),
+                };
+                let branch = branches
+                    .iter()
+                    .find(|b| **b != Type::Null && self.type_name(b) == name)
+                    .ok_or_else(|| path.error(format!("unknown union branch {:?}", name)))?;
+                path.push(Segment::Key(name.clone()));
+                self.json(branch, v, default, path)?;
+                path.pop();
+            }
+            Type::Named(i) => match &self.named[*i].kind {
+                NamedKind::Record(fields) => {
+                    let object = value.as_object().ok_or_else(|| expected("an object"))?;
+                    self.record(fields, object, default, path)?;
+                }
+                NamedKind::Enum(symbols) => {
+                    let symbol = value.as_str().ok_or_else(|| expected("a string"))?;
+                    if !symbols.iter().any(|s| s == symbol) {
+                        return Err(path.error(format!("unknown enum symbol {:?}", symbol)));
+                    }
+                }
+                NamedKind::Fixed(size) => {
+                    let n = bytes(value).ok_or_else(|| expected("a string of bytes"))?;
+                    if n != *size {
+                        return Err(path.error(format!("expected {} bytes, got {}", size, n)));
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        fields: &[Field],
+        object: &Map<String, Value>,
+        default: bool,
+        path: &mut Path,
+    ) -> Result<(), SchemaError> {
+        for field in fields {
+            match object.get(&field.name) {
+                Some(v) => {
+                    path.push(Segment::Field(field.name.clone()));
+                    self.json(&field.schema, v, default, path)?;
+                    path.pop();
+                }
+                None if field.default.is_some() => {}
+                None => return Err(path.error(format!("missing field {:?}", field.name))),
+            }
+        }
+        if let Some(name) = object
+            .keys()
+            .find(|k| !fields.iter().any(|f| &f.name == *k))
+        {
+            return Err(path.error(format!("unknown field {:?}", name)));
+        }
+        Ok(())
+    }
+}
+
+fn long(r: &mut Reader<'_>, path: &Path) -> Result<i64, SchemaError> {
+    let v = r.varint(path)?;
+    Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+}
+
+fn length(r: &mut Reader<'_>, path: &Path) -> Result<usize, SchemaError> {
+    let n = long(r, path)?;
+    usize::try_from(n).map_err(|_| path.error(format!("negative length: {}", n)))
+}
+
+// The item count of the next block of an array or a map, or `None` after the last block. Items
+// of at least `size` bytes cannot be more than the input left allows.
+fn block(r: &mut Reader<'_>, size: usize, path: &Path) -> Result<Option<u64>, SchemaError> {
+    let count = match long(r, path)? {
+        0 => return Ok(None),
+        n if n < 0 => {
+            // The count is followed by the size of the block in bytes.
+            length(r, path)?;
+            n.unsigned_abs()
+        }
+        n => n as u64,
+    };
+    if size > 0 && count > (r.remaining() / size) as u64 {
+        return Err(path.error(format!(
+            "block of {} items longer than the {} bytes left",
+            count,
+            r.remaining()
+        )));
+    }
+    Ok(Some(count))
+}
+
+// The length of bytes encoded as a JSON string of code points up to U+00FF.
+fn bytes(value: &Value) -> Option<usize> {
+    let s = value.as_str()?;
+    if s.chars().any(|c| c > '\u{ff}') {
+        return None;
+    }
+    Some(s.chars().count())
+}
+
+struct Parser {
+    named: Vec<Named>,
+    names: HashMap<String, usize>,
+}
+
+impl Parser {
+    fn parse(&mut self, json: &Value, namespace: &str) -> Result<Type, SchemaError> {
+        match json {
+            Value::String(name) => self.reference(name, namespace),
+            Value::Array(branches) => {
+                let mut names = HashSet::new();
+                let mut types = Vec::with_capacity(branches.len());
+                for branch in branches {
+                    let t = self.parse(branch, namespace)?;
+                    let name = match &t {
+                        Type::Union(_) => return Err(invalid("unions cannot contain unions")),
+                        Type::Named(i) => self.named[*i].full_name.clone(),
+                        t => format!("{:?}", t),
+                    };
+                    if !names.insert(name) {
+                        return Err(invalid("a union has the same type twice"));
+                    }
+                    types.push(t);
+                }
+                if types.is_empty() {
+                    return Err(invalid("empty union"));
+                }
+                Ok(Type::Union(types))
+            }
+            Value::Object(o) => match o.get("type") {
+                Some(Value::String(t)) => match t.as_str() {
+                    "record" | "error" | "enum" | "fixed" => self.named(t, o, namespace),
+                    "array" => {
+                        let items = o.get("items").ok_or_else(|| invalid("missing items"))?;
+                        Ok(Type::Array(Box::new(self.parse(items, namespace)?)))
+                    }
+                    "map" => {
+                        let values = o.get("values").ok_or_else(|| invalid("missing values"))?;
+                        Ok(Type::Map(Box::new(self.parse(values, namespace)?)))
+                    }
+                    // Logical types are validated as their underlying type.
+                    _ => self.reference(t, namespace),
+                },
+                Some(t) => self.parse(t, namespace),
+                None => Err(invalid("missing type")),
+            },
+            _ => Err(invalid(format!("invalid schema: {}", json))),
+        }
+    }
+
+    fn reference(&self, name: &str, namespace: &str) -> Result<Type, SchemaError> {
+        let t = match name {
+            "null" => Type::Null,
+            "boolean" => Type::Boolean,
+            "int" => Type::Int,
+            "long" => Type::Long,
+            "float" => Type::Float,
+            "double" => Type::Double,
+            "bytes" => Type::Bytes,
+            "string" => Type::String,
+            _ => {
+                let qualified = format!("{}.{}", namespace, name);
+                let index = (!namespace.is_empty() && !name.contains('.'))
+                    .then(|| self.names.get(&qualified))
+                    .flatten()
+                    .or_else(|| self.names.get(name))
+                    .ok_or_else(|| invalid(format!("unknown type {:?}", name)))?;
+                Type::Named(*index)
+            }
+        };
+        Ok(t)
+    }
+
+    fn named(
+        &mut self,
+        t: &str,
+        o: &Map<String, Value>,
+        namespace: &str,
+    ) -> Result<Type, SchemaError> {
+        let name = o
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(format!("missing name of a {}", t)))?;
+        let (namespace, full_name) = match name.rfind('.') {
+            Some(i) => (&name[..i], name.to_owned()),
+            None => {
+                let namespace = match o.get("namespace") {
+                    Some(Value::String(ns)) => ns.as_str(),
+                    Some(_) => return Err(invalid("invalid namespace")),
+                    None => namespace,
+                };
+                let full_name = if namespace.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}.{}", namespace, name)
+                };
+                (namespace, full_name)
+            }
+        };
+        if !full_name.split('.').all(is_name) {
+            return Err(invalid(format!("invalid name {:?}", full_name)));
+        }
+        if self.names.contains_key(&full_name) {
+            return Err(invalid(format!("duplicate type {:?}", full_name)));
+        }
+        let index = self.named.len();
+        self.names.insert(full_name.clone(), index);
+        self.named.push(Named {
+            full_name: full_name.clone(),
+            kind: NamedKind::Fixed(0),
+        });
+
+        let kind = match t {
+            "enum" => {
+                let symbols = o
+                    .get("symbols")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid(format!("missing symbols of {}", full_name)))?;
+                let mut names = Vec::with_capacity(symbols.len());
+                for symbol in symbols {
+                    match symbol.as_str() {
+                        Some(s) if is_name(s) && !names.iter().any(|n| n == s) => {
+                            names.push(s.to_owned())
+                        }
+                        _ => {
+                            return Err(invalid(format!(
+                                "invalid symbol {} of {}",
+                                symbol, full_name
+                            )))
+                        }
+                    }
+                }
+                if let Some(default) = o.get("default") {
+                    if !default
+                        .as_str()
+                        .is_some_and(|d| names.iter().any(|n| n == d))
+                    {
+                        return Err(invalid(format!("invalid default of {}", full_name)));
+                    }
+                }
+                NamedKind::Enum(names)
+            }
+            "fixed" => {
+                let size = o
+                    .get("size")
+                    .and_then(Value::as_u64)
+                    .and_then(|s| usize::try_from(s).ok())
+                    .ok_or_else(|| invalid(format!("missing size of {}", full_name)))?;
+                NamedKind::Fixed(size)
+            }
+            _ => {
+                let fields = o
+                    .get("fields")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid(format!("missing fields of {}", full_name)))?;
+                let mut parsed: Vec<Field> = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let name = field
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .filter(|n| is_name(n))
+                        .ok_or_else(|| invalid(format!("invalid field of {}", full_name)))?;
+                    if parsed.iter().any(|f| f.name == name) {
+                        return Err(invalid(format!(
+                            "duplicate field {} of {}",
+                            name, full_name
+                        )));
+                    }
+                    let schema = field.get("type").ok_or_else(|| {
+                        invalid(format!("missing type of {}.{}", full_name, name))
+                    })?;
+                    parsed.push(Field {
+                        name: name.to_owned(),
+                        schema: self.parse(schema, namespace)?,
+                        default: field.get("default").cloned(),
+                    });
+                }
+                NamedKind::Record(parsed)
+            }
+        };
+        self.named[index].kind = kind;
+        Ok(Type::Named(index))
+    }
+}
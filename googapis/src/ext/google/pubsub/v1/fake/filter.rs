@@ -0,0 +1,163 @@
+// Subscription filters, see https://cloud.google.com/pubsub/docs/filtering. They are parsed with
+// `crate::filter` and restricted to the conditions on attributes accepted by the service. The
+// syntax tree does not keep parentheses, so unlike the service, `AND` and `OR` may be mixed
+// without them, `OR` binding tighter, and conditions separated by whitespace are joined by `AND`.
+
+use std::collections::HashMap;
+
+use crate::filter::{Arg, Comparable, Comparator, Filter, Literal};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum MessageFilter {
+    HasAttribute(String),
+    Equals(String, String),
+    HasPrefix(String, String),
+    Not(Box<MessageFilter>),
+    And(Vec<MessageFilter>),
+    Or(Vec<MessageFilter>),
+}
+
+impl MessageFilter {
+    pub(super) fn parse(s: &str) -> Result<Self, String> {
+        let filter = Filter::parse(s).map_err(|e| e.to_string())?;
+        Self::from_filter(&filter)
+    }
+
+    fn from_filter(filter: &Filter) -> Result<Self, String> {
+        let all = |filters: &[Filter]| {
+            filters
+                .iter()
+                .map(Self::from_filter)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match filter {
+            Filter::And(filters) => Ok(MessageFilter::And(all(filters)?)),
+            Filter::Or(filters) => Ok(MessageFilter::Or(all(filters)?)),
+            Filter::Not(filter) => Ok(MessageFilter::Not(Box::new(Self::from_filter(filter)?))),
+            Filter::Restriction {
+                comparable: Comparable::Member(literals),
+                comparator: Comparator::Has,
+                arg: Arg::Comparable(Comparable::Member(keys)),
+            } => match (literals.as_slice(), keys.as_slice()) {
+                ([Literal::Text(a)], [k]) if a == "attributes" => {
+                    Ok(MessageFilter::HasAttribute(key(k)?))
+                }
+                _ => Err(format!("expected attributes:<key>, found {}", filter)),
+            },
+            Filter::Restriction {
+                comparable,
+                comparator: Comparator::Eq,
+                arg,
+            } => Ok(MessageFilter::Equals(attribute(comparable)?, string(arg)?)),
+            Filter::Restriction {
+                comparable,
+                comparator: Comparator::Ne,
+                arg,
+            } => Ok(MessageFilter::Not(Box::new(MessageFilter::Equals(
+                attribute(comparable)?,
+                string(arg)?,
+            )))),
+            Filter::Global(Comparable::Function { name, args })
+                if name.len() == 1 && name[0] == "hasPrefix" =>
+            {
+                match args.as_slice() {
+                    [Arg::Comparable(comparable), prefix] => Ok(MessageFilter::HasPrefix(
+                        attribute(comparable)?,
+                        string(prefix)?,
+                    )),
+                    _ => Err(format!("invalid arguments of {}", filter)),
+                }
+            }
+            _ => Err(format!("unsupported condition {}", filter)),
+        }
+    }
+
+    pub(super) fn matches(&self, attributes: &HashMap<String, String>) -> bool {
+        match self {
+            MessageFilter::HasAttribute(key) => attributes.contains_key(key),
+            MessageFilter::Equals(key, value) => attributes.get(key) == Some(value),
+            MessageFilter::HasPrefix(key, prefix) => attributes
+                .get(key)
+                .is_some_and(|value| value.starts_with(prefix.as_str())),
+            MessageFilter::Not(filter) => !filter.matches(attributes),
+            MessageFilter::And(filters) => filters.iter().all(|f| f.matches(attributes)),
+            MessageFilter::Or(filters) => filters.iter().any(|f| f.matches(attributes)),
+        }
+    }
+}
+
+// An attribute key, which must be quoted unless it is made of letters, digits and underscores.
+fn key(literal: &Literal) -> Result<String, String> {
+    match literal {
+        Literal::Text(key) if key.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            Ok(key.clone())
+        }
+        Literal::String(key) => Ok(key.clone()),
+        _ => Err(format!("invalid attribute key {}", literal)),
+    }
+}
+
+// The key of `attributes.<key>`.
+fn attribute(comparable: &Comparable) -> Result<String, String> {
+    match comparable {
+        Comparable::Member(literals) => match literals.as_slice() {
+            [Literal::Text(a), k] if a == "attributes" => key(k),
+            _ => Err(format!("expected attributes.<key>, found {}", comparable)),
+        },
+        _ => Err(format!("expected attributes.<key>, found {}", comparable)),
+    }
+}
+
+fn string(arg: &Arg) -> Result<String, String> {
+    match arg {
+        Arg::Comparable(Comparable::Member(literals)) => match literals.as_slice() {
+            [Literal::String(s)] => Ok(s.clone()),
+            _ => Err(format!("expected a string, found {}", arg)),
+        },
+        _ => Err(format!("expected a string, found {}", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let attributes: HashMap<_, _> = vec![("a", "apple"), ("b-c", "")]
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        let matches = |s: &str| MessageFilter::parse(s).unwrap().matches(&attributes);
+        assert!(matches("attributes:a"));
+        assert!(matches("attributes.a = \"apple\""));
+        assert!(matches("attributes.\"b-c\" = ''"));
+        assert!(matches("hasPrefix(attributes.a, \"app\")"));
+        assert!(matches("attributes.z != \"apple\""));
+        assert!(matches("NOT attributes:z AND -attributes:y"));
+        assert!(matches(
+            "(attributes:z OR attributes:a) AND attributes:\"b-c\""
+        ));
+        assert!(!matches(
+            "attributes.a = \"pear\" OR hasPrefix(attributes.z, \"\")"
+        ));
+
+        // The service requires parentheses.
+        assert_eq!(
+            MessageFilter::parse("attributes:a AND attributes:b OR attributes:c"),
+            MessageFilter::parse("attributes:a AND (attributes:b OR attributes:c)")
+        );
+        for invalid in &[
+            "attributes",
+            "attributes.a = apple",
+            "attributes.a > \"b\"",
+            "(attributes:a",
+            "a = \"b\"",
+            "attributes:b-c",
+            "hasPrefix(attributes.a)",
+            "regex(attributes.a, \"b\")",
+        ] {
+            assert!(MessageFilter::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
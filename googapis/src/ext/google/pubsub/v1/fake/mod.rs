@@ -0,0 +1,1149 @@
+// The handlers return the `Status` of the responses.
+#![allow(clippy::result_large_err)]
+
+mod filter;
+mod publisher;
+mod subscriber;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    future::{self, Future},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use prost::Message;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{oneshot, Notify},
+    time::Instant,
+};
+use tonic::{
+    body::BoxBody,
+    codegen::{empty_body, futures_core::Stream, http, Body, BoxFuture, Never, Service, StdError},
+    Status,
+};
+
+use super::{PubsubMessage, ReceivedMessage, RetryPolicy, Snapshot, Subscription, Topic};
+
+// The topic of the subscriptions of a deleted topic.
+const DELETED_TOPIC: &str = "_deleted-topic_";
+const DEFAULT_ACK_DEADLINE_SECONDS: i32 = 10;
+const MAX_ACK_DEADLINE_SECONDS: i32 = 600;
+const DEFAULT_MAX_DELIVERY_ATTEMPTS: i32 = 5;
+const MAX_DELIVERY_ATTEMPTS: i32 = 100;
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(600);
+// The default message retention duration of subscriptions and the lifetime of snapshots.
+const RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+const MAX_FILTER_BYTES: usize = 256;
+
+use self::filter::MessageFilter;
+
+pub use self::{publisher::PublisherService, subscriber::SubscriberService};
+
+/// An in-memory implementation of the `google.pubsub.v1.Publisher` and `Subscriber` services,
+/// for hermetic tests.
+///
+/// It implements [`PublishClient`] and [`SubscribeClient`] to be used in process by the
+/// [`Publisher`](super::Publisher) and the [`Subscriber`](super::Subscriber), and
+/// [`FakePubsub::serve`] serves it on a local port for the generated clients. Clones share
+/// their topics, subscriptions and snapshots.
+///
+/// Topics and subscriptions support ordering keys, filters, `DeadLetterPolicy`,
+/// `RetryPolicy`, flow control of streams, detachment, and seeking to a time or a snapshot.
+/// Filters are parsed with the grammar of `googapis::filter`, which is more lenient than the
+/// service: `AND` and `OR` may be mixed without parentheses.
+/// Schemas, push subscriptions, message retention and expiration policies are stored but not
+/// enforced; acked messages are always retained for seeks, but seeking to a time only
+/// redelivers them when `retain_acked_messages` is set. Messages with an ordering key are
+/// leased one at a time. Ack deadlines and backoffs use the Tokio clock, so they can be
+/// tested with paused time.
+///
+/// ```no_run
+/// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use googapis::google::pubsub::v1::{publisher_client::PublisherClient, FakePubsub, Topic};
+///
+/// let server = FakePubsub::new().serve().await?;
+/// let channel = tonic::transport::Channel::from_shared(server.uri())?
+///     .connect()
+///     .await?;
+/// let mut client = PublisherClient::new(channel);
+/// client
+///     .create_topic(Topic {
+///         name: "projects/test/topics/test".to_owned(),
+///         ..Default::default()
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakePubsub {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    state: Mutex<State>,
+    // Notified when messages may be leased.
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    topics: BTreeMap<String, Topic>,
+    subscriptions: BTreeMap<String, SubscriptionState>,
+    snapshots: BTreeMap<String, SnapshotState>,
+    // The sequence number of the last message, shared by all topics.
+    seq: u64,
+    ack_ids: u64,
+    streams: u64,
+}
+
+#[derive(Debug)]
+struct SubscriptionState {
+    subscription: Subscription,
+    filter: Option<MessageFilter>,
+    // The messages by sequence number.
+    backlog: BTreeMap<u64, Entry>,
+    // The sequence numbers of the leased messages by ack id.
+    leases: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    message: PubsubMessage,
+    publish_time: SystemTime,
+    delivery_attempts: i32,
+    state: EntryState,
+}
+
+#[derive(Debug, Clone)]
+enum EntryState {
+    Available(Instant),
+    Leased {
+        ack_id: String,
+        deadline: Instant,
+        stream: Option<u64>,
+    },
+    Acked,
+}
+
+#[derive(Debug)]
+struct SnapshotState {
+    snapshot: Snapshot,
+    // The sequence number of the last message published when the snapshot was created.
+    seq: u64,
+    // The messages which were not acked.
+    backlog: BTreeMap<u64, Entry>,
+}
+
+// The messages leased by a delivery, and when the next message may be available.
+struct Delivery {
+    received: Vec<ReceivedMessage>,
+    wake: Option<Instant>,
+    dead_lettered: bool,
+}
+
+// The outstanding messages allowed to a stream, or to a pull without `stream`.
+struct Limits {
+    stream: Option<u64>,
+    messages: usize,
+    bytes: usize,
+    ack_deadline: Option<Duration>,
+}
+
+impl FakePubsub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves the `Publisher` and `Subscriber` services on a port of `127.0.0.1` until the
+    /// returned server is dropped. It must be called in a Tokio runtime.
+    pub async fn serve(&self) -> io::Result<FakeServer> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, rx) = oneshot::channel::<()>();
+        let router = tonic::transport::Server::builder()
+            .add_service(self.publisher_service())
+            .add_service(self.subscriber_service());
+        tokio::spawn(async move {
+            let _ = router
+                .serve_with_incoming_shutdown(Incoming(listener), async {
+                    let _ = rx.await;
+                })
+                .await;
+        });
+        Ok(FakeServer {
+            addr,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The `Publisher` service, to be added to a `tonic::transport::Server`.
+    pub fn publisher_service(&self) -> PublisherService {
+        PublisherService(self.clone())
+    }
+
+    /// The `Subscriber` service, to be added to a `tonic::transport::Server`.
+    pub fn subscriber_service(&self) -> SubscriberService {
+        SubscriberService(self.clone())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify(&self) {
+        self.inner.changed.notify_waiters();
+    }
+}
+
+impl State {
+    fn topic_mut(&mut self, name: &str) -> Result<&mut Topic, Status> {
+        self.topics
+            .get_mut(name)
+            .ok_or_else(|| Status::not_found("Topic not found"))
+    }
+
+    fn subscription_mut(&mut self, name: &str) -> Result<&mut SubscriptionState, Status> {
+        self.subscriptions
+            .get_mut(name)
+            .ok_or_else(|| Status::not_found("Subscription not found"))
+    }
+
+    fn snapshot_mut(&mut self, name: &str) -> Result<&mut SnapshotState, Status> {
+        self.snapshots
+            .get_mut(name)
+            .ok_or_else(|| Status::not_found("Snapshot not found"))
+    }
+
+    // Fills the defaults of a subscription and checks it, returning its filter.
+    fn normalize(
+        &mut self,
+        subscription: &mut Subscription,
+    ) -> Result<Option<MessageFilter>, Status> {
+        if subscription.ack_deadline_seconds == 0 {
+            subscription.ack_deadline_seconds = DEFAULT_ACK_DEADLINE_SECONDS;
+        }
+        if !(DEFAULT_ACK_DEADLINE_SECONDS..=MAX_ACK_DEADLINE_SECONDS)
+            .contains(&subscription.ack_deadline_seconds)
+        {
+            return Err(Status::invalid_argument(format!(
+                "invalid ack deadline: {}",
+                subscription.ack_deadline_seconds
+            )));
+        }
+        if subscription.message_retention_duration.is_none() {
+            subscription.message_retention_duration = Some(RETENTION.into());
+        }
+        if let Some(policy) = &mut subscription.dead_letter_policy {
+            if !self.topics.contains_key(&policy.dead_letter_topic) {
+                return Err(Status::not_found("Dead letter topic not found"));
+            }
+            if policy.max_delivery_attempts == 0 {
+                policy.max_delivery_attempts = DEFAULT_MAX_DELIVERY_ATTEMPTS;
+            }
+            if !(DEFAULT_MAX_DELIVERY_ATTEMPTS..=MAX_DELIVERY_ATTEMPTS)
+                .contains(&policy.max_delivery_attempts)
+            {
+                return Err(Status::invalid_argument(format!(
+                    "invalid max delivery attempts: {}",
+                    policy.max_delivery_attempts
+                )));
+            }
+        }
+        if subscription.filter.is_empty() {
+            return Ok(None);
+        }
+        if subscription.filter.len() > MAX_FILTER_BYTES {
+            return Err(Status::invalid_argument("filter too long"));
+        }
+        MessageFilter::parse(&subscription.filter)
+            .map(Some)
+            .map_err(|message| Status::invalid_argument(format!("invalid filter: {}", message)))
+    }
+
+    fn publish(&mut self, topic: &str, messages: Vec<PubsubMessage>) -> Vec<String> {
+        let publish_time = SystemTime::now();
+        let now = Instant::now();
+        let mut message_ids = Vec::with_capacity(messages.len());
+        for mut message in messages {
+            self.seq += 1;
+            message.message_id = self.seq.to_string();
+            message.publish_time = Some(publish_time.into());
+            for s in self.subscriptions.values_mut() {
+                if s.subscription.topic == topic && !s.subscription.detached && s.accepts(&message)
+                {
+                    s.backlog.insert(
+                        self.seq,
+                        Entry {
+                            message: message.clone(),
+                            publish_time,
+                            delivery_attempts: 0,
+                            state: EntryState::Available(now),
+                        },
+                    );
+                }
+            }
+            message_ids.push(message.message_id);
+        }
+        message_ids
+    }
+
+    fn deliver(
+        &mut self,
+        subscription: &str,
+        limits: &Limits,
+        now: Instant,
+    ) -> Result<Delivery, Status> {
+        let mut ack_ids = self.ack_ids;
+        let s = self.subscription_mut(subscription)?;
+        if s.subscription.detached {
+            return Err(Status::failed_precondition("Subscription is detached"));
+        }
+        s.expire(now);
+        let (mut messages, mut bytes) = match limits.stream {
+            Some(stream) => s.outstanding(stream),
+            None => (0, 0),
+        };
+        let ack_deadline = limits
+            .ack_deadline
+            .unwrap_or_else(|| Duration::from_secs(s.subscription.ack_deadline_seconds as u64));
+        let ordered = s.subscription.enable_message_ordering;
+        let dead_letter_policy = s.subscription.dead_letter_policy.clone();
+        let mut busy_keys = HashSet::new();
+        let mut received = Vec::new();
+        let mut dead_letters = Vec::new();
+        let mut wake = None;
+        for (seq, entry) in s.backlog.iter_mut() {
+            if messages >= limits.messages || bytes >= limits.bytes {
+                break;
+            }
+            if matches!(entry.state, EntryState::Acked) {
+                continue;
+            }
+            let key = &entry.message.ordering_key;
+            if ordered && !key.is_empty() && !busy_keys.insert(key.clone()) {
+                continue;
+            }
+            match entry.state {
+                EntryState::Available(at) if at <= now => {}
+                EntryState::Available(at) => {
+                    wake = Some(wake.map_or(at, |wake: Instant| wake.min(at)));
+                    continue;
+                }
+                _ => continue,
+            }
+            if let Some(policy) = &dead_letter_policy {
+                if entry.delivery_attempts >= policy.max_delivery_attempts {
+                    let mut message = entry.message.clone();
+                    message.attributes.insert(
+                        "CloudPubSubDeadLetterSourceSubscription".to_owned(),
+                        s.subscription.name.clone(),
+                    );
+                    message.attributes.insert(
+                        "CloudPubSubDeadLetterSourceDeliveryCount".to_owned(),
+                        entry.delivery_attempts.to_string(),
+                    );
+                    dead_letters.push((policy.dead_letter_topic.clone(), message));
+                    entry.state = EntryState::Acked;
+                    busy_keys.remove(key);
+                    continue;
+                }
+            }
+            ack_ids += 1;
+            let ack_id = format!("{}-{}", seq, ack_ids);
+            entry.delivery_attempts += 1;
+            entry.state = EntryState::Leased {
+                ack_id: ack_id.clone(),
+                deadline: now + ack_deadline,
+                stream: limits.stream,
+            };
+            s.leases.insert(ack_id.clone(), *seq);
+            messages += 1;
+            bytes += entry.message.encoded_len();
+            received.push(ReceivedMessage {
+                ack_id,
+                message: Some(entry.message.clone()),
+                delivery_attempt: if dead_letter_policy.is_some() {
+                    entry.delivery_attempts
+                } else {
+                    0
+                },
+            });
+        }
+        self.ack_ids = ack_ids;
+        let dead_lettered = !dead_letters.is_empty();
+        for (topic, message) in dead_letters {
+            if self.topics.contains_key(&topic) {
+                self.publish(&topic, vec![message]);
+            }
+        }
+        // The leases expire later, including the ones of messages not reached by the loop.
+        if let Some(s) = self.subscriptions.get(subscription) {
+            let deadlines = s.backlog.values().filter_map(|entry| match entry.state {
+                EntryState::Leased { deadline, .. } => Some(deadline),
+                _ => None,
+            });
+            wake = deadlines.chain(wake).min();
+        }
+        Ok(Delivery {
+            received,
+            wake,
+            dead_lettered,
+        })
+    }
+}
+
+impl SubscriptionState {
+    fn accepts(&self, message: &PubsubMessage) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|f| f.matches(&message.attributes))
+    }
+
+    // The leased entry of an ack id.
+    fn lease(&mut self, ack_id: &str) -> Option<&mut Entry> {
+        let seq = self.leases.get(ack_id)?;
+        self.backlog.get_mut(seq).filter(
+            |entry| matches!(&entry.state, EntryState::Leased { ack_id: id, .. } if id == ack_id),
+        )
+    }
+
+    // Releases the messages whose lease expired.
+    fn expire(&mut self, now: Instant) {
+        let retry_policy = self.subscription.retry_policy.as_ref();
+        for entry in self.backlog.values_mut() {
+            if matches!(entry.state, EntryState::Leased { deadline, .. } if deadline <= now) {
+                entry.release(retry_policy, now);
+            }
+        }
+        let backlog = &self.backlog;
+        self.leases.retain(|ack_id, seq| {
+            matches!(
+                backlog.get(seq).map(|entry| &entry.state),
+                Some(EntryState::Leased { ack_id: id, .. }) if id == ack_id
+            )
+        });
+    }
+
+    // The numbers of messages and of bytes leased to a stream.
+    fn outstanding(&self, stream: u64) -> (usize, usize) {
+        self.backlog
+            .values()
+            .filter(|entry| {
+                matches!(entry.state, EntryState::Leased { stream: Some(s), .. } if s == stream)
+            })
+            .fold((0, 0), |(messages, bytes), entry| {
+                (messages + 1, bytes + entry.message.encoded_len())
+            })
+    }
+}
+
+impl Entry {
+    // Makes a leased message available after the backoff of the retry policy.
+    fn release(&mut self, retry_policy: Option<&RetryPolicy>, now: Instant) {
+        let backoff = retry_policy.map_or(Duration::from_secs(0), |policy| {
+            let bound = |d: &Option<prost_types::Duration>, default| {
+                d.clone()
+                    .and_then(|d| Duration::try_from(d).ok())
+                    .unwrap_or(default)
+            };
+            let min = bound(&policy.minimum_backoff, DEFAULT_MIN_BACKOFF);
+            let max = bound(&policy.maximum_backoff, DEFAULT_MAX_BACKOFF);
+            let exponent = (self.delivery_attempts - 1).clamp(0, 16) as u32;
+            min.checked_mul(1 << exponent).map_or(max, |d| d.min(max))
+        });
+        self.state = EntryState::Available(now + backoff);
+    }
+}
+
+fn check_name(name: &str, collection: &str) -> Result<(), Status> {
+    let parts: Vec<_> = name.split('/').collect();
+    match parts.as_slice() {
+        ["projects", project, c, id]
+            if !project.is_empty() && *c == collection && is_resource_id(id) =>
+        {
+            Ok(())
+        }
+        _ => Err(Status::invalid_argument(format!(
+            "invalid resource name: {:?}",
+            name
+        ))),
+    }
+}
+
+// Resource ids have 3 to 255 characters, start with a letter and do not start with "goog".
+fn is_resource_id(id: &str) -> bool {
+    (3..=255).contains(&id.len())
+        && id.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !id.starts_with("goog")
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.~+%".contains(c))
+}
+
+// The prefix of the names of a collection of a project.
+fn collection_prefix(project: &str, collection: &str) -> Result<String, Status> {
+    match project.strip_prefix("projects/") {
+        Some(id) if !id.is_empty() && !id.contains('/') => {
+            Ok(format!("{}/{}/", project, collection))
+        }
+        _ => Err(Status::invalid_argument(format!(
+            "invalid project: {:?}",
+            project
+        ))),
+    }
+}
+
+fn mask_paths(mask: Option<prost_types::FieldMask>) -> Result<Vec<String>, Status> {
+    match mask {
+        Some(mask) if !mask.paths.is_empty() => Ok(mask.paths),
+        _ => Err(Status::invalid_argument("missing update mask")),
+    }
+}
+
+fn invalid_path(path: &str) -> Status {
+    Status::invalid_argument(format!("invalid update mask path: {:?}", path))
+}
+
+// The page token is the index of the first item of the page.
+fn page<T>(items: Vec<T>, page_size: i32, page_token: &str) -> Result<(Vec<T>, String), Status> {
+    let start = match page_token {
+        "" => 0,
+        token => token
+            .parse()
+            .ok()
+            .filter(|start| *start <= items.len())
+            .ok_or_else(|| Status::invalid_argument("invalid page token"))?,
+    };
+    let end = match page_size {
+        n if n > 0 => items.len().min(start + n as usize),
+        _ => items.len(),
+    };
+    let next_page_token = if end < items.len() {
+        end.to_string()
+    } else {
+        String::new()
+    };
+    let page = items.into_iter().skip(start).take(end - start).collect();
+    Ok((page, next_page_token))
+}
+
+/// A [`FakePubsub`] served on a local port, shut down when dropped.
+#[derive(Debug)]
+pub struct FakeServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl FakeServer {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URI to connect to, e.g. `http://127.0.0.1:4242`.
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+struct Incoming(TcpListener);
+
+impl Stream for Incoming {
+    type Item = io::Result<TcpStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0
+            .poll_accept(cx)
+            .map(|r| Some(r.map(|(stream, _)| stream)))
+    }
+}
+
+fn unary<B, F, Req, Resp>(req: http::Request<B>, f: F) -> BoxFuture<http::Response<BoxBody>, Never>
+where
+    B: Body + Send + Sync + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    F: FnOnce(Req) -> Result<Resp, Status> + Send + 'static,
+    Req: Message + Default + Send + 'static,
+    Resp: Message + Send + 'static,
+{
+    unary_async(req, move |r| future::ready(f(r)))
+}
+
+fn unary_async<B, F, Fut, Req, Resp>(
+    req: http::Request<B>,
+    f: F,
+) -> BoxFuture<http::Response<BoxBody>, Never>
+where
+    B: Body + Send + Sync + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    F: FnOnce(Req) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Resp, Status>> + Send + 'static,
+    Req: Message + Default + Send + 'static,
+    Resp: Message + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+        Ok(grpc.unary(Unary(Some(f)), req).await)
+    })
+}
+
+fn unimplemented() -> BoxFuture<http::Response<BoxBody>, Never> {
+    Box::pin(future::ready(Ok(http::Response::builder()
+        .status(200)
+        .header("grpc-status", "12")
+        .header("content-type", "application/grpc")
+        .body(empty_body())
+        .unwrap())))
+}
+
+// A unary method called once.
+struct Unary<F>(Option<F>);
+
+impl<F, Fut, Req, Resp> Service<tonic::Request<Req>> for Unary<F>
+where
+    F: FnOnce(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, Status>> + Send + 'static,
+    Resp: Send + 'static,
+{
+    type Response = tonic::Response<Resp>;
+    type Error = Status;
+    type Future = BoxFuture<tonic::Response<Resp>, Status>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Self::Future {
+        let f = self.0.take().expect("unary method called twice");
+        let response = f(request.into_inner());
+        Box::pin(async move { response.await.map(tonic::Response::new) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::time;
+
+    use super::super::{
+        publisher_client::PublisherClient, seek_request, subscriber_client::SubscriberClient,
+        AcknowledgeRequest, CreateSnapshotRequest, DeadLetterPolicy, DeleteSnapshotRequest,
+        DeleteTopicRequest, GetSubscriptionRequest, GetTopicRequest, ListSubscriptionsRequest,
+        ListTopicSnapshotsRequest, ListTopicsRequest, ModifyAckDeadlineRequest, PublishRequest,
+        Publisher, PullRequest, SeekRequest, Subscriber, UpdateSubscriptionRequest,
+    };
+
+    const TOPIC: &str = "projects/test/topics/topic";
+    const SUBSCRIPTION: &str = "projects/test/subscriptions/subscription";
+
+    fn message(data: &str, ordering_key: &str) -> PubsubMessage {
+        PubsubMessage {
+            data: data.into(),
+            ordering_key: ordering_key.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn topic(name: &str) -> Topic {
+        Topic {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    // A fake with `TOPIC` and `SUBSCRIPTION`, which has the settings of `subscription`.
+    fn setup(subscription: Subscription) -> FakePubsub {
+        let fake = FakePubsub::new();
+        fake.create_topic(topic(TOPIC)).unwrap();
+        fake.create_subscription(Subscription {
+            name: SUBSCRIPTION.to_owned(),
+            topic: TOPIC.to_owned(),
+            ..subscription
+        })
+        .unwrap();
+        fake
+    }
+
+    fn publish(fake: &FakePubsub, messages: Vec<PubsubMessage>) -> Vec<String> {
+        FakePubsub::publish(
+            fake,
+            PublishRequest {
+                topic: TOPIC.to_owned(),
+                messages,
+            },
+        )
+        .unwrap()
+        .message_ids
+    }
+
+    #[allow(deprecated)]
+    async fn pull(fake: &FakePubsub, subscription: &str) -> Vec<ReceivedMessage> {
+        fake.clone()
+            .pull(PullRequest {
+                subscription: subscription.to_owned(),
+                return_immediately: true,
+                max_messages: 10,
+            })
+            .await
+            .unwrap()
+            .received_messages
+    }
+
+    fn data(received: &[ReceivedMessage]) -> Vec<String> {
+        received
+            .iter()
+            .map(|m| String::from_utf8_lossy(&m.message.as_ref().unwrap().data).into_owned())
+            .collect()
+    }
+
+    fn ack(fake: &FakePubsub, received: &[ReceivedMessage]) {
+        fake.acknowledge(AcknowledgeRequest {
+            subscription: SUBSCRIPTION.to_owned(),
+            ack_ids: received.iter().map(|m| m.ack_id.clone()).collect(),
+        })
+        .unwrap();
+    }
+
+    fn modify(fake: &FakePubsub, received: &[ReceivedMessage], ack_deadline_seconds: i32) {
+        fake.modify_ack_deadline(ModifyAckDeadlineRequest {
+            subscription: SUBSCRIPTION.to_owned(),
+            ack_ids: received.iter().map(|m| m.ack_id.clone()).collect(),
+            ack_deadline_seconds,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_resources() {
+        let fake = setup(Subscription::default());
+        assert_eq!(
+            fake.create_topic(topic(TOPIC)).unwrap_err().code(),
+            tonic::Code::AlreadyExists
+        );
+        assert_eq!(
+            fake.create_topic(topic("projects/test/topics/goog"))
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+        for name in &["b", "c"] {
+            fake.create_topic(topic(&format!("projects/test/topics/{}-topic", name)))
+                .unwrap();
+        }
+        fake.create_topic(topic("projects/other/topics/topic"))
+            .unwrap();
+
+        let list = |page_token: &str| {
+            fake.list_topics(ListTopicsRequest {
+                project: "projects/test".to_owned(),
+                page_size: 2,
+                page_token: page_token.to_owned(),
+            })
+            .unwrap()
+        };
+        let first = list("");
+        let second = list(&first.next_page_token);
+        let names: Vec<_> = first
+            .topics
+            .iter()
+            .chain(&second.topics)
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "projects/test/topics/b-topic",
+                "projects/test/topics/c-topic",
+                TOPIC
+            ]
+        );
+        assert_eq!(second.next_page_token, "");
+
+        let subscription = fake
+            .get_subscription(GetSubscriptionRequest {
+                subscription: SUBSCRIPTION.to_owned(),
+            })
+            .unwrap();
+        assert_eq!(subscription.ack_deadline_seconds, 10);
+        let updated = fake
+            .update_subscription(UpdateSubscriptionRequest {
+                subscription: Some(Subscription {
+                    ack_deadline_seconds: 60,
+                    filter: "attributes:a".to_owned(),
+                    ..subscription.clone()
+                }),
+                update_mask: Some(prost_types::FieldMask {
+                    paths: vec!["ack_deadline_seconds".to_owned()],
+                }),
+            })
+            .unwrap();
+        assert_eq!(updated.ack_deadline_seconds, 60);
+        assert_eq!(updated.filter, "");
+        let update_filter = fake.update_subscription(UpdateSubscriptionRequest {
+            subscription: Some(subscription),
+            update_mask: Some(prost_types::FieldMask {
+                paths: vec!["filter".to_owned()],
+            }),
+        });
+        assert_eq!(
+            update_filter.unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+
+        fake.delete_topic(DeleteTopicRequest {
+            topic: TOPIC.to_owned(),
+        })
+        .unwrap();
+        let subscriptions = fake
+            .list_subscriptions(ListSubscriptionsRequest {
+                project: "projects/test".to_owned(),
+                ..Default::default()
+            })
+            .unwrap()
+            .subscriptions;
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].topic, DELETED_TOPIC);
+        assert_eq!(
+            fake.get_topic(GetTopicRequest {
+                topic: TOPIC.to_owned()
+            })
+            .unwrap_err()
+            .code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_deadlines() {
+        let fake = setup(Subscription::default());
+        publish(&fake, vec![message("a", ""), message("b", "")]);
+        let received = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&received), ["a", "b"]);
+        assert_eq!(received[0].delivery_attempt, 0);
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+
+        // Nacked messages are redelivered without a retry policy.
+        ack(&fake, &received[..1]);
+        modify(&fake, &received[1..], 0);
+        let redelivered = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&redelivered), ["b"]);
+
+        // Expired leases are redelivered, and their ack ids are stale.
+        modify(&fake, &redelivered, 30);
+        time::advance(Duration::from_secs(29)).await;
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+        time::advance(Duration::from_secs(1)).await;
+        let expired = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&expired), ["b"]);
+        ack(&fake, &redelivered);
+        time::advance(Duration::from_secs(10)).await;
+        let last = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&last), ["b"]);
+        ack(&fake, &last);
+        time::advance(Duration::from_secs(10)).await;
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+
+        // A pull which does not return immediately waits for messages.
+        let waiting = tokio::spawn(fake.clone().pull(PullRequest {
+            subscription: SUBSCRIPTION.to_owned(),
+            max_messages: 1,
+            ..Default::default()
+        }));
+        tokio::task::yield_now().await;
+        publish(&fake, vec![message("c", "")]);
+        let response = waiting.await.unwrap().unwrap();
+        assert_eq!(data(&response.received_messages), ["c"]);
+
+        let missing = fake.clone().pull(PullRequest {
+            subscription: "projects/test/subscriptions/missing".to_owned(),
+            max_messages: 1,
+            ..Default::default()
+        });
+        assert_eq!(missing.await.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_and_dead_letter_policies() {
+        const DEAD_LETTERS: &str = "projects/test/subscriptions/dead-letters";
+        let fake = FakePubsub::new();
+        fake.create_topic(topic(TOPIC)).unwrap();
+        fake.create_topic(topic("projects/test/topics/dead-letters"))
+            .unwrap();
+        fake.create_subscription(Subscription {
+            name: DEAD_LETTERS.to_owned(),
+            topic: "projects/test/topics/dead-letters".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        let subscription = Subscription {
+            name: SUBSCRIPTION.to_owned(),
+            topic: TOPIC.to_owned(),
+            dead_letter_policy: Some(DeadLetterPolicy {
+                dead_letter_topic: "projects/test/topics/dead-letters".to_owned(),
+                max_delivery_attempts: 0,
+            }),
+            retry_policy: Some(RetryPolicy {
+                minimum_backoff: Some(Duration::from_secs(1).into()),
+                maximum_backoff: Some(Duration::from_secs(3).into()),
+            }),
+            ..Default::default()
+        };
+        let mut invalid = subscription.clone();
+        invalid
+            .dead_letter_policy
+            .as_mut()
+            .unwrap()
+            .max_delivery_attempts = 4;
+        assert_eq!(
+            fake.create_subscription(invalid).unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        let created = fake.create_subscription(subscription).unwrap();
+        assert_eq!(
+            created.dead_letter_policy.unwrap().max_delivery_attempts,
+            DEFAULT_MAX_DELIVERY_ATTEMPTS
+        );
+
+        publish(&fake, vec![message("a", "")]);
+        for (attempt, backoff) in [1, 2, 3, 3, 3].iter().enumerate() {
+            let received = pull(&fake, SUBSCRIPTION).await;
+            assert_eq!(data(&received), ["a"]);
+            assert_eq!(received[0].delivery_attempt, attempt as i32 + 1);
+            modify(&fake, &received, 0);
+            time::advance(Duration::from_secs(backoff - 1)).await;
+            assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+            time::advance(Duration::from_secs(1)).await;
+        }
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+        let dead_letters = pull(&fake, DEAD_LETTERS).await;
+        assert_eq!(data(&dead_letters), ["a"]);
+        let attributes = &dead_letters[0].message.as_ref().unwrap().attributes;
+        assert_eq!(
+            attributes["CloudPubSubDeadLetterSourceSubscription"],
+            SUBSCRIPTION
+        );
+        assert_eq!(attributes["CloudPubSubDeadLetterSourceDeliveryCount"], "5");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ordering_keys_and_filters() {
+        let fake = setup(Subscription {
+            enable_message_ordering: true,
+            ..Default::default()
+        });
+        publish(
+            &fake,
+            vec![
+                message("a", "k1"),
+                message("b", "k1"),
+                message("c", "k2"),
+                message("d", ""),
+                message("e", "k2"),
+            ],
+        );
+        let received = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&received), ["a", "c", "d"]);
+        ack(&fake, &received[..1]);
+        modify(&fake, &received[1..2], 0);
+        let received = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&received), ["b", "c"]);
+
+        const FILTERED: &str = "projects/test/subscriptions/filtered";
+        fake.create_subscription(Subscription {
+            name: FILTERED.to_owned(),
+            topic: TOPIC.to_owned(),
+            filter: "attributes.color = \"red\"".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        let colored = |data: &str, color: &str| PubsubMessage {
+            attributes: vec![("color".to_owned(), color.to_owned())]
+                .into_iter()
+                .collect(),
+            ..message(data, "")
+        };
+        publish(&fake, vec![colored("f", "red"), colored("g", "blue")]);
+        assert_eq!(data(&pull(&fake, FILTERED).await), ["f"]);
+
+        let invalid = fake.create_subscription(Subscription {
+            name: "projects/test/subscriptions/invalid".to_owned(),
+            topic: TOPIC.to_owned(),
+            filter: "attributes.color = red".to_owned(),
+            ..Default::default()
+        });
+        assert_eq!(invalid.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshots_and_seek() {
+        const SNAPSHOT: &str = "projects/test/snapshots/snapshot";
+        let fake = setup(Subscription {
+            retain_acked_messages: true,
+            ..Default::default()
+        });
+        publish(&fake, vec![message("a", ""), message("b", "")]);
+        let received = pull(&fake, SUBSCRIPTION).await;
+        ack(&fake, &received[..1]);
+        let snapshot = fake
+            .create_snapshot(CreateSnapshotRequest {
+                name: SNAPSHOT.to_owned(),
+                subscription: SUBSCRIPTION.to_owned(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(snapshot.topic, TOPIC);
+        publish(&fake, vec![message("c", "")]);
+        ack(&fake, &received[1..]);
+        ack(&fake, &pull(&fake, SUBSCRIPTION).await);
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+
+        let seek = |target| {
+            fake.seek(SeekRequest {
+                subscription: SUBSCRIPTION.to_owned(),
+                target: Some(target),
+            })
+        };
+        seek(seek_request::Target::Snapshot(SNAPSHOT.to_owned())).unwrap();
+        let received = pull(&fake, SUBSCRIPTION).await;
+        assert_eq!(data(&received), ["b", "c"]);
+        ack(&fake, &received);
+
+        seek(seek_request::Target::Time(SystemTime::UNIX_EPOCH.into())).unwrap();
+        assert_eq!(data(&pull(&fake, SUBSCRIPTION).await), ["a", "b", "c"]);
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        seek(seek_request::Target::Time(future.into())).unwrap();
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+
+        let snapshots = fake
+            .list_topic_snapshots(ListTopicSnapshotsRequest {
+                topic: TOPIC.to_owned(),
+                ..Default::default()
+            })
+            .unwrap()
+            .snapshots;
+        assert_eq!(snapshots, [SNAPSHOT]);
+        fake.delete_snapshot(DeleteSnapshotRequest {
+            snapshot: SNAPSHOT.to_owned(),
+        })
+        .unwrap();
+        assert_eq!(
+            seek(seek_request::Target::Snapshot(SNAPSHOT.to_owned()))
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publisher_and_subscriber() {
+        let fake = setup(Subscription::default());
+        let publisher = Publisher::new(fake.clone(), TOPIC);
+        let mut results = Vec::new();
+        for data in &["a", "b", "c"] {
+            results.push(publisher.publish(message(data, "")).await);
+        }
+        for result in results {
+            result.await.unwrap();
+        }
+        publisher.shutdown().await;
+
+        let mut subscriber = Subscriber::new(fake.clone(), SUBSCRIPTION);
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let message = subscriber.next().await.unwrap().unwrap();
+            received.push(message.message().data.clone());
+            message.ack();
+        }
+        subscriber.shutdown().await;
+        received.sort();
+        assert_eq!(received, [b"a", b"b", b"c"]);
+        time::advance(Duration::from_secs(60)).await;
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_grpc() {
+        let fake = FakePubsub::new();
+        let server = fake.serve().await.unwrap();
+        let channel = tonic::transport::Channel::from_shared(server.uri())
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut publisher = PublisherClient::new(channel.clone());
+        let mut subscriber = SubscriberClient::new(channel);
+        publisher.create_topic(topic(TOPIC)).await.unwrap();
+        subscriber
+            .create_subscription(Subscription {
+                name: SUBSCRIPTION.to_owned(),
+                topic: TOPIC.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let missing = publisher
+            .get_topic(GetTopicRequest {
+                topic: "projects/test/topics/missing".to_owned(),
+            })
+            .await;
+        assert_eq!(missing.unwrap_err().code(), tonic::Code::NotFound);
+
+        let message_ids = publisher
+            .publish(PublishRequest {
+                topic: TOPIC.to_owned(),
+                messages: vec![message("a", ""), message("b", "")],
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .message_ids;
+        assert_eq!(message_ids.len(), 2);
+        let received = subscriber
+            .pull(PullRequest {
+                subscription: SUBSCRIPTION.to_owned(),
+                max_messages: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .received_messages;
+        assert_eq!(data(&received), ["a"]);
+        subscriber
+            .acknowledge(AcknowledgeRequest {
+                subscription: SUBSCRIPTION.to_owned(),
+                ack_ids: vec![received[0].ack_id.clone()],
+            })
+            .await
+            .unwrap();
+
+        let mut streaming = Subscriber::new(subscriber.clone(), SUBSCRIPTION);
+        let message = streaming.next().await.unwrap().unwrap();
+        assert_eq!(message.message().data, b"b");
+        assert_eq!(message.message().message_id, message_ids[1]);
+        message.ack();
+        streaming.shutdown().await;
+        assert!(pull(&fake, SUBSCRIPTION).await.is_empty());
+    }
+}
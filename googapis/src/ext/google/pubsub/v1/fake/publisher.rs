@@ -0,0 +1,215 @@
+use std::{
+    future,
+    task::{Context, Poll},
+};
+
+use tonic::{
+    body::BoxBody,
+    codegen::{http, Body, BoxFuture, Never, Service, StdError},
+    transport::NamedService,
+    Status,
+};
+
+use super::super::{
+    DeleteTopicRequest, DetachSubscriptionRequest, DetachSubscriptionResponse, GetTopicRequest,
+    ListTopicSnapshotsRequest, ListTopicSnapshotsResponse, ListTopicSubscriptionsRequest,
+    ListTopicSubscriptionsResponse, ListTopicsRequest, ListTopicsResponse, PublishClient,
+    PublishFuture, PublishRequest, PublishResponse, Topic, UpdateTopicRequest,
+};
+use super::{
+    check_name, collection_prefix, invalid_path, mask_paths, page, unary, unimplemented,
+    FakePubsub, DELETED_TOPIC,
+};
+
+impl FakePubsub {
+    pub(super) fn create_topic(&self, topic: Topic) -> Result<Topic, Status> {
+        check_name(&topic.name, "topics")?;
+        let mut state = self.state();
+        if state.topics.contains_key(&topic.name) {
+            return Err(Status::already_exists("Topic already exists"));
+        }
+        state.topics.insert(topic.name.clone(), topic.clone());
+        Ok(topic)
+    }
+
+    pub(super) fn update_topic(&self, request: UpdateTopicRequest) -> Result<Topic, Status> {
+        let update = request
+            .topic
+            .ok_or_else(|| Status::invalid_argument("missing topic"))?;
+        let paths = mask_paths(request.update_mask)?;
+        let mut state = self.state();
+        let topic = state.topic_mut(&update.name)?;
+        let mut updated = topic.clone();
+        for path in paths {
+            match path.as_str() {
+                "labels" => updated.labels = update.labels.clone(),
+                "message_storage_policy" => {
+                    updated.message_storage_policy = update.message_storage_policy.clone()
+                }
+                "kms_key_name" => updated.kms_key_name = update.kms_key_name.clone(),
+                "schema_settings" => updated.schema_settings = update.schema_settings.clone(),
+                _ => return Err(invalid_path(&path)),
+            }
+        }
+        *topic = updated.clone();
+        Ok(updated)
+    }
+
+    pub(super) fn publish(&self, request: PublishRequest) -> Result<PublishResponse, Status> {
+        if request.messages.is_empty() {
+            return Err(Status::invalid_argument("at least one message is required"));
+        }
+        if request
+            .messages
+            .iter()
+            .any(|m| m.data.is_empty() && m.attributes.is_empty())
+        {
+            return Err(Status::invalid_argument(
+                "a message must have data or attributes",
+            ));
+        }
+        let message_ids = {
+            let mut state = self.state();
+            state.topic_mut(&request.topic)?;
+            state.publish(&request.topic, request.messages)
+        };
+        self.notify();
+        Ok(PublishResponse { message_ids })
+    }
+
+    pub(super) fn get_topic(&self, request: GetTopicRequest) -> Result<Topic, Status> {
+        Ok(self.state().topic_mut(&request.topic)?.clone())
+    }
+
+    pub(super) fn list_topics(
+        &self,
+        request: ListTopicsRequest,
+    ) -> Result<ListTopicsResponse, Status> {
+        let prefix = collection_prefix(&request.project, "topics")?;
+        let topics = self
+            .state()
+            .topics
+            .range(prefix.clone()..)
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .map(|(_, topic)| topic.clone())
+            .collect();
+        let (topics, next_page_token) = page(topics, request.page_size, &request.page_token)?;
+        Ok(ListTopicsResponse {
+            topics,
+            next_page_token,
+        })
+    }
+
+    pub(super) fn list_topic_subscriptions(
+        &self,
+        request: ListTopicSubscriptionsRequest,
+    ) -> Result<ListTopicSubscriptionsResponse, Status> {
+        let mut state = self.state();
+        state.topic_mut(&request.topic)?;
+        let subscriptions = state
+            .subscriptions
+            .iter()
+            .filter(|(_, s)| s.subscription.topic == request.topic)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let (subscriptions, next_page_token) =
+            page(subscriptions, request.page_size, &request.page_token)?;
+        Ok(ListTopicSubscriptionsResponse {
+            subscriptions,
+            next_page_token,
+        })
+    }
+
+    pub(super) fn list_topic_snapshots(
+        &self,
+        request: ListTopicSnapshotsRequest,
+    ) -> Result<ListTopicSnapshotsResponse, Status> {
+        let mut state = self.state();
+        state.topic_mut(&request.topic)?;
+        let snapshots = state
+            .snapshots
+            .iter()
+            .filter(|(_, s)| s.snapshot.topic == request.topic)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let (snapshots, next_page_token) = page(snapshots, request.page_size, &request.page_token)?;
+        Ok(ListTopicSnapshotsResponse {
+            snapshots,
+            next_page_token,
+        })
+    }
+
+    pub(super) fn delete_topic(&self, request: DeleteTopicRequest) -> Result<(), Status> {
+        let mut state = self.state();
+        if state.topics.remove(&request.topic).is_none() {
+            return Err(Status::not_found("Topic not found"));
+        }
+        for s in state.subscriptions.values_mut() {
+            if s.subscription.topic == request.topic {
+                s.subscription.topic = DELETED_TOPIC.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn detach_subscription(
+        &self,
+        request: DetachSubscriptionRequest,
+    ) -> Result<DetachSubscriptionResponse, Status> {
+        {
+            let mut state = self.state();
+            let s = state.subscription_mut(&request.subscription)?;
+            s.subscription.detached = true;
+            s.backlog.clear();
+            s.leases.clear();
+        }
+        self.notify();
+        Ok(DetachSubscriptionResponse {})
+    }
+}
+
+impl PublishClient for FakePubsub {
+    fn publish(&mut self, request: PublishRequest) -> PublishFuture<'_> {
+        Box::pin(future::ready(FakePubsub::publish(self, request)))
+    }
+}
+
+/// The `google.pubsub.v1.Publisher` service of a [`FakePubsub`].
+#[derive(Debug, Clone)]
+pub struct PublisherService(pub(super) FakePubsub);
+
+impl NamedService for PublisherService {
+    const NAME: &'static str = "google.pubsub.v1.Publisher";
+}
+
+impl<B> Service<http::Request<B>> for PublisherService
+where
+    B: Body + Send + Sync + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Never>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let fake = self.0.clone();
+        let path = req.uri().path().to_owned();
+        let method = path.strip_prefix("/google.pubsub.v1.Publisher/");
+        match method.unwrap_or_default() {
+            "CreateTopic" => unary(req, move |r| fake.create_topic(r)),
+            "UpdateTopic" => unary(req, move |r| fake.update_topic(r)),
+            "Publish" => unary(req, move |r| FakePubsub::publish(&fake, r)),
+            "GetTopic" => unary(req, move |r| fake.get_topic(r)),
+            "ListTopics" => unary(req, move |r| fake.list_topics(r)),
+            "ListTopicSubscriptions" => unary(req, move |r| fake.list_topic_subscriptions(r)),
+            "ListTopicSnapshots" => unary(req, move |r| fake.list_topic_snapshots(r)),
+            "DeleteTopic" => unary(req, move |r| fake.delete_topic(r)),
+            "DetachSubscription" => unary(req, move |r| fake.detach_subscription(r)),
+            _ => unimplemented(),
+        }
+    }
+}
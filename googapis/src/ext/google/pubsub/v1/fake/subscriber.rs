@@ -0,0 +1,626 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    future::{self, Future},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+use tonic::{
+    body::BoxBody,
+    codegen::{futures_core::Stream, http, Body, BoxFuture, Never, Service, StdError},
+    transport::NamedService,
+    Status,
+};
+
+use super::super::{
+    seek_request, streaming_pull_response::SubscriptionProperties, AcknowledgeRequest,
+    CreateSnapshotRequest, DeleteSnapshotRequest, DeleteSubscriptionRequest, GetSnapshotRequest,
+    GetSubscriptionRequest, ListSnapshotsRequest, ListSnapshotsResponse, ListSubscriptionsRequest,
+    ListSubscriptionsResponse, ModifyAckDeadlineRequest, ModifyPushConfigRequest, PullRequest,
+    PullResponse, SeekRequest, SeekResponse, Snapshot, StreamingPullFuture, StreamingPullRequest,
+    StreamingPullRequests, StreamingPullResponse, StreamingPullStream, SubscribeClient,
+    Subscription, UpdateSnapshotRequest, UpdateSubscriptionRequest,
+};
+use super::{
+    check_name, collection_prefix, invalid_path, mask_paths, page, unary, unary_async,
+    unimplemented, Delivery, EntryState, FakePubsub, Limits, SnapshotState, SubscriptionState,
+    DEFAULT_ACK_DEADLINE_SECONDS, MAX_ACK_DEADLINE_SECONDS, RETENTION,
+};
+
+impl FakePubsub {
+    pub(super) fn create_subscription(
+        &self,
+        mut subscription: Subscription,
+    ) -> Result<Subscription, Status> {
+        check_name(&subscription.name, "subscriptions")?;
+        let mut state = self.state();
+        if state.subscriptions.contains_key(&subscription.name) {
+            return Err(Status::already_exists("Subscription already exists"));
+        }
+        state.topic_mut(&subscription.topic)?;
+        subscription.detached = false;
+        let filter = state.normalize(&mut subscription)?;
+        state.subscriptions.insert(
+            subscription.name.clone(),
+            SubscriptionState {
+                subscription: subscription.clone(),
+                filter,
+                backlog: BTreeMap::new(),
+                leases: HashMap::new(),
+            },
+        );
+        Ok(subscription)
+    }
+
+    pub(super) fn get_subscription(
+        &self,
+        request: GetSubscriptionRequest,
+    ) -> Result<Subscription, Status> {
+        Ok(self
+            .state()
+            .subscription_mut(&request.subscription)?
+            .subscription
+            .clone())
+    }
+
+    pub(super) fn update_subscription(
+        &self,
+        request: UpdateSubscriptionRequest,
+    ) -> Result<Subscription, Status> {
+        let update = request
+            .subscription
+            .ok_or_else(|| Status::invalid_argument("missing subscription"))?;
+        let paths = mask_paths(request.update_mask)?;
+        let mut state = self.state();
+        let mut updated = state.subscription_mut(&update.name)?.subscription.clone();
+        for path in paths {
+            match path.as_str() {
+                "push_config" => updated.push_config = update.push_config.clone(),
+                "ack_deadline_seconds" => {
+                    updated.ack_deadline_seconds = update.ack_deadline_seconds
+                }
+                "retain_acked_messages" => {
+                    updated.retain_acked_messages = update.retain_acked_messages
+                }
+                "message_retention_duration" => {
+                    updated.message_retention_duration = update.message_retention_duration.clone()
+                }
+                "labels" => updated.labels = update.labels.clone(),
+                "expiration_policy" => updated.expiration_policy = update.expiration_policy.clone(),
+                "dead_letter_policy" => {
+                    updated.dead_letter_policy = update.dead_letter_policy.clone()
+                }
+                "retry_policy" => updated.retry_policy = update.retry_policy.clone(),
+                _ => return Err(invalid_path(&path)),
+            }
+        }
+        state.normalize(&mut updated)?;
+        state.subscription_mut(&update.name)?.subscription = updated.clone();
+        drop(state);
+        self.notify();
+        Ok(updated)
+    }
+
+    pub(super) fn list_subscriptions(
+        &self,
+        request: ListSubscriptionsRequest,
+    ) -> Result<ListSubscriptionsResponse, Status> {
+        let prefix = collection_prefix(&request.project, "subscriptions")?;
+        let subscriptions = self
+            .state()
+            .subscriptions
+            .range(prefix.clone()..)
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .map(|(_, s)| s.subscription.clone())
+            .collect();
+        let (subscriptions, next_page_token) =
+            page(subscriptions, request.page_size, &request.page_token)?;
+        Ok(ListSubscriptionsResponse {
+            subscriptions,
+            next_page_token,
+        })
+    }
+
+    pub(super) fn delete_subscription(
+        &self,
+        request: DeleteSubscriptionRequest,
+    ) -> Result<(), Status> {
+        if self
+            .state()
+            .subscriptions
+            .remove(&request.subscription)
+            .is_none()
+        {
+            return Err(Status::not_found("Subscription not found"));
+        }
+        self.notify();
+        Ok(())
+    }
+
+    pub(super) fn modify_ack_deadline(
+        &self,
+        request: ModifyAckDeadlineRequest,
+    ) -> Result<(), Status> {
+        let deadlines = vec![request.ack_deadline_seconds; request.ack_ids.len()];
+        self.modify(&request.subscription, request.ack_ids, deadlines)
+    }
+
+    pub(super) fn acknowledge(&self, request: AcknowledgeRequest) -> Result<(), Status> {
+        {
+            let mut state = self.state();
+            let s = state.subscription_mut(&request.subscription)?;
+            for ack_id in request.ack_ids {
+                if let Some(entry) = s.lease(&ack_id) {
+                    entry.state = EntryState::Acked;
+                }
+            }
+        }
+        self.notify();
+        Ok(())
+    }
+
+    pub(super) fn modify(
+        &self,
+        subscription: &str,
+        ack_ids: Vec<String>,
+        deadlines: Vec<i32>,
+    ) -> Result<(), Status> {
+        if ack_ids.len() != deadlines.len() {
+            return Err(Status::invalid_argument(
+                "the numbers of ack ids and of deadlines differ",
+            ));
+        }
+        if let Some(d) = deadlines
+            .iter()
+            .find(|d| !(0..=MAX_ACK_DEADLINE_SECONDS).contains(d))
+        {
+            return Err(Status::invalid_argument(format!(
+                "invalid ack deadline: {}",
+                d
+            )));
+        }
+        {
+            let mut state = self.state();
+            let s = state.subscription_mut(subscription)?;
+            let now = Instant::now();
+            s.expire(now);
+            let retry_policy = s.subscription.retry_policy.clone();
+            for (ack_id, seconds) in ack_ids.iter().zip(deadlines) {
+                let entry = match s.lease(ack_id) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                if seconds == 0 {
+                    entry.release(retry_policy.as_ref(), now);
+                } else if let EntryState::Leased { deadline, .. } = &mut entry.state {
+                    *deadline = now + Duration::from_secs(seconds as u64);
+                }
+            }
+        }
+        self.notify();
+        Ok(())
+    }
+
+    #[allow(deprecated)]
+    pub(super) async fn pull(self, request: PullRequest) -> Result<PullResponse, Status> {
+        if request.max_messages <= 0 {
+            return Err(Status::invalid_argument("max_messages must be positive"));
+        }
+        let limits = Limits {
+            stream: None,
+            messages: request.max_messages as usize,
+            bytes: usize::MAX,
+            ack_deadline: None,
+        };
+        loop {
+            let changed = self.inner.changed.notified();
+            let delivery = self.deliver(&request.subscription, &limits)?;
+            if !delivery.received.is_empty() || request.return_immediately {
+                return Ok(PullResponse {
+                    received_messages: delivery.received,
+                });
+            }
+            tokio::select! {
+                _ = changed => {}
+                _ = sleep_until(delivery.wake) => {}
+            }
+        }
+    }
+
+    pub(super) fn deliver(&self, subscription: &str, limits: &Limits) -> Result<Delivery, Status> {
+        let delivery = self.state().deliver(subscription, limits, Instant::now())?;
+        if delivery.dead_lettered {
+            self.notify();
+        }
+        Ok(delivery)
+    }
+
+    pub(super) fn modify_push_config(
+        &self,
+        request: ModifyPushConfigRequest,
+    ) -> Result<(), Status> {
+        self.state()
+            .subscription_mut(&request.subscription)?
+            .subscription
+            .push_config = request.push_config;
+        Ok(())
+    }
+
+    pub(super) fn get_snapshot(&self, request: GetSnapshotRequest) -> Result<Snapshot, Status> {
+        Ok(self
+            .state()
+            .snapshot_mut(&request.snapshot)?
+            .snapshot
+            .clone())
+    }
+
+    pub(super) fn list_snapshots(
+        &self,
+        request: ListSnapshotsRequest,
+    ) -> Result<ListSnapshotsResponse, Status> {
+        let prefix = collection_prefix(&request.project, "snapshots")?;
+        let snapshots = self
+            .state()
+            .snapshots
+            .range(prefix.clone()..)
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .map(|(_, s)| s.snapshot.clone())
+            .collect();
+        let (snapshots, next_page_token) = page(snapshots, request.page_size, &request.page_token)?;
+        Ok(ListSnapshotsResponse {
+            snapshots,
+            next_page_token,
+        })
+    }
+
+    pub(super) fn create_snapshot(
+        &self,
+        request: CreateSnapshotRequest,
+    ) -> Result<Snapshot, Status> {
+        check_name(&request.name, "snapshots")?;
+        let mut state = self.state();
+        if state.snapshots.contains_key(&request.name) {
+            return Err(Status::already_exists("Snapshot already exists"));
+        }
+        let seq = state.seq;
+        let s = state.subscription_mut(&request.subscription)?;
+        s.expire(Instant::now());
+        let backlog: BTreeMap<_, _> = s
+            .backlog
+            .iter()
+            .filter(|(_, entry)| !matches!(entry.state, EntryState::Acked))
+            .map(|(seq, entry)| (*seq, entry.clone()))
+            .collect();
+        let oldest = backlog
+            .values()
+            .map(|entry| entry.publish_time)
+            .min()
+            .unwrap_or_else(SystemTime::now);
+        let snapshot = Snapshot {
+            name: request.name.clone(),
+            topic: s.subscription.topic.clone(),
+            expire_time: Some((oldest + RETENTION).into()),
+            labels: request.labels,
+        };
+        state.snapshots.insert(
+            request.name,
+            SnapshotState {
+                snapshot: snapshot.clone(),
+                seq,
+                backlog,
+            },
+        );
+        Ok(snapshot)
+    }
+
+    pub(super) fn update_snapshot(
+        &self,
+        request: UpdateSnapshotRequest,
+    ) -> Result<Snapshot, Status> {
+        let update = request
+            .snapshot
+            .ok_or_else(|| Status::invalid_argument("missing snapshot"))?;
+        let paths = mask_paths(request.update_mask)?;
+        let mut state = self.state();
+        let snapshot = &mut state.snapshot_mut(&update.name)?.snapshot;
+        let mut updated = snapshot.clone();
+        for path in paths {
+            match path.as_str() {
+                "labels" => updated.labels = update.labels.clone(),
+                "expire_time" => updated.expire_time = update.expire_time.clone(),
+                _ => return Err(invalid_path(&path)),
+            }
+        }
+        *snapshot = updated.clone();
+        Ok(updated)
+    }
+
+    pub(super) fn delete_snapshot(&self, request: DeleteSnapshotRequest) -> Result<(), Status> {
+        match self.state().snapshots.remove(&request.snapshot) {
+            Some(_) => Ok(()),
+            None => Err(Status::not_found("Snapshot not found")),
+        }
+    }
+
+    pub(super) fn seek(&self, request: SeekRequest) -> Result<SeekResponse, Status> {
+        {
+            let mut state = self.state();
+            let now = Instant::now();
+            match request.target {
+                Some(seek_request::Target::Time(time)) => {
+                    let time = SystemTime::try_from(time)
+                        .map_err(|_| Status::invalid_argument("invalid time"))?;
+                    let s = state.subscription_mut(&request.subscription)?;
+                    let retain_acked = s.subscription.retain_acked_messages;
+                    for entry in s.backlog.values_mut() {
+                        if entry.publish_time < time {
+                            entry.state = EntryState::Acked;
+                        } else if retain_acked || !matches!(entry.state, EntryState::Acked) {
+                            entry.state = EntryState::Available(now);
+                        }
+                    }
+                    s.leases.clear();
+                }
+                Some(seek_request::Target::Snapshot(name)) => {
+                    let snapshot = state.snapshot_mut(&name)?;
+                    let (topic, snapshot_seq) = (snapshot.snapshot.topic.clone(), snapshot.seq);
+                    let snapshot_backlog = snapshot.backlog.clone();
+                    let s = state.subscription_mut(&request.subscription)?;
+                    if s.subscription.topic != topic {
+                        return Err(Status::invalid_argument(
+                            "the snapshot and the subscription have different topics",
+                        ));
+                    }
+                    for (seq, entry) in s.backlog.iter_mut() {
+                        entry.state = if *seq > snapshot_seq || snapshot_backlog.contains_key(seq) {
+                            EntryState::Available(now)
+                        } else {
+                            EntryState::Acked
+                        };
+                    }
+                    for (seq, mut entry) in snapshot_backlog {
+                        if !s.backlog.contains_key(&seq) && s.accepts(&entry.message) {
+                            entry.state = EntryState::Available(now);
+                            s.backlog.insert(seq, entry);
+                        }
+                    }
+                    s.leases.clear();
+                }
+                None => return Err(Status::invalid_argument("missing seek target")),
+            }
+        }
+        self.notify();
+        Ok(SeekResponse {})
+    }
+
+    // Serves a `StreamingPull` call in a task.
+    fn streaming_pull_responses(&self, requests: RequestStream) -> Responses {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let fake = self.clone();
+        tokio::spawn(async move {
+            if let Err(status) = fake.run_stream(requests, &tx).await {
+                let _ = tx.send(Err(status));
+            }
+        });
+        Responses(rx)
+    }
+
+    async fn run_stream(
+        &self,
+        mut requests: RequestStream,
+        tx: &mpsc::UnboundedSender<Result<StreamingPullResponse, Status>>,
+    ) -> Result<(), Status> {
+        let first = match next_request(&mut requests).await {
+            Some(request) => request?,
+            None => return Ok(()),
+        };
+        let subscription = first.subscription.clone();
+        let limit = |n: i64| if n > 0 { n as usize } else { usize::MAX };
+        let mut limits = Limits {
+            stream: None,
+            messages: limit(first.max_outstanding_messages),
+            bytes: limit(first.max_outstanding_bytes),
+            ack_deadline: Some(stream_ack_deadline(first.stream_ack_deadline_seconds)?),
+        };
+        let ordering = {
+            let mut state = self.state();
+            state.streams += 1;
+            limits.stream = Some(state.streams);
+            state
+                .subscription_mut(&subscription)?
+                .subscription
+                .enable_message_ordering
+        };
+        self.handle_stream_request(&subscription, first)?;
+        loop {
+            let changed = self.inner.changed.notified();
+            let delivery = self.deliver(&subscription, &limits)?;
+            if !delivery.received.is_empty() {
+                let response = StreamingPullResponse {
+                    received_messages: delivery.received,
+                    subscription_properties: Some(SubscriptionProperties {
+                        message_ordering_enabled: ordering,
+                    }),
+                };
+                if tx.send(Ok(response)).is_err() {
+                    return Ok(());
+                }
+            }
+            // Requests sent before the responses were dropped are handled.
+            tokio::select! {
+                biased;
+                request = next_request(&mut requests) => match request {
+                    Some(request) => {
+                        let request = request?;
+                        if request.stream_ack_deadline_seconds != 0 {
+                            limits.ack_deadline =
+                                Some(stream_ack_deadline(request.stream_ack_deadline_seconds)?);
+                        }
+                        self.handle_stream_request(&subscription, request)?;
+                    }
+                    None => return Ok(()),
+                },
+                _ = changed => {}
+                _ = sleep_until(delivery.wake) => {}
+                _ = tx.closed() => return Ok(()),
+            }
+        }
+    }
+
+    fn handle_stream_request(
+        &self,
+        subscription: &str,
+        request: StreamingPullRequest,
+    ) -> Result<(), Status> {
+        if !request.ack_ids.is_empty() {
+            self.acknowledge(AcknowledgeRequest {
+                subscription: subscription.to_owned(),
+                ack_ids: request.ack_ids,
+            })?;
+        }
+        if !request.modify_deadline_ack_ids.is_empty()
+            || !request.modify_deadline_seconds.is_empty()
+        {
+            self.modify(
+                subscription,
+                request.modify_deadline_ack_ids,
+                request.modify_deadline_seconds,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SubscribeClient for FakePubsub {
+    fn streaming_pull(&mut self, requests: StreamingPullRequests) -> StreamingPullFuture<'_> {
+        let responses = self.streaming_pull_responses(Box::pin(Oks(requests)));
+        Box::pin(future::ready(
+            Ok(Box::pin(responses) as StreamingPullStream),
+        ))
+    }
+}
+
+fn stream_ack_deadline(seconds: i32) -> Result<Duration, Status> {
+    if !(DEFAULT_ACK_DEADLINE_SECONDS..=MAX_ACK_DEADLINE_SECONDS).contains(&seconds) {
+        return Err(Status::invalid_argument(format!(
+            "invalid stream ack deadline: {}",
+            seconds
+        )));
+    }
+    Ok(Duration::from_secs(seconds as u64))
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => future::pending().await,
+    }
+}
+
+type RequestStream = Pin<Box<dyn Stream<Item = Result<StreamingPullRequest, Status>> + Send>>;
+
+fn next_request(
+    requests: &mut RequestStream,
+) -> impl Future<Output = Option<Result<StreamingPullRequest, Status>>> + '_ {
+    future::poll_fn(move |cx| requests.as_mut().poll_next(cx))
+}
+
+// The requests of an in-process `StreamingPull` call.
+struct Oks(StreamingPullRequests);
+
+impl Stream for Oks {
+    type Item = Result<StreamingPullRequest, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx).map(|r| r.map(Ok))
+    }
+}
+
+// The responses of a `StreamingPull` call.
+struct Responses(mpsc::UnboundedReceiver<Result<StreamingPullResponse, Status>>);
+
+impl Stream for Responses {
+    type Item = Result<StreamingPullResponse, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// The `google.pubsub.v1.Subscriber` service of a [`FakePubsub`].
+#[derive(Debug, Clone)]
+pub struct SubscriberService(pub(super) FakePubsub);
+
+impl NamedService for SubscriberService {
+    const NAME: &'static str = "google.pubsub.v1.Subscriber";
+}
+
+impl<B> Service<http::Request<B>> for SubscriberService
+where
+    B: Body + Send + Sync + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Never>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let fake = self.0.clone();
+        let path = req.uri().path().to_owned();
+        let method = path.strip_prefix("/google.pubsub.v1.Subscriber/");
+        match method.unwrap_or_default() {
+            "CreateSubscription" => unary(req, move |r| fake.create_subscription(r)),
+            "GetSubscription" => unary(req, move |r| fake.get_subscription(r)),
+            "UpdateSubscription" => unary(req, move |r| fake.update_subscription(r)),
+            "ListSubscriptions" => unary(req, move |r| fake.list_subscriptions(r)),
+            "DeleteSubscription" => unary(req, move |r| fake.delete_subscription(r)),
+            "ModifyAckDeadline" => unary(req, move |r| fake.modify_ack_deadline(r)),
+            "Acknowledge" => unary(req, move |r| fake.acknowledge(r)),
+            "Pull" => unary_async(req, move |r| fake.pull(r)),
+            "StreamingPull" => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.streaming(StreamingPull(fake), req).await)
+            }),
+            "ModifyPushConfig" => unary(req, move |r| fake.modify_push_config(r)),
+            "GetSnapshot" => unary(req, move |r| fake.get_snapshot(r)),
+            "ListSnapshots" => unary(req, move |r| fake.list_snapshots(r)),
+            "CreateSnapshot" => unary(req, move |r| fake.create_snapshot(r)),
+            "UpdateSnapshot" => unary(req, move |r| fake.update_snapshot(r)),
+            "DeleteSnapshot" => unary(req, move |r| fake.delete_snapshot(r)),
+            "Seek" => unary(req, move |r| fake.seek(r)),
+            _ => unimplemented(),
+        }
+    }
+}
+
+struct StreamingPull(FakePubsub);
+
+impl Service<tonic::Request<tonic::Streaming<StreamingPullRequest>>> for StreamingPull {
+    type Response = tonic::Response<Responses>;
+    type Error = Status;
+    type Future = future::Ready<Result<Self::Response, Status>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(
+        &mut self,
+        request: tonic::Request<tonic::Streaming<StreamingPullRequest>>,
+    ) -> Self::Future {
+        let requests = Box::pin(request.into_inner());
+        future::ready(Ok(tonic::Response::new(
+            self.0.streaming_pull_responses(requests),
+        )))
+    }
+}
@@ -0,0 +1,34 @@
+#[cfg(feature = "serde_json")]
+mod avro;
+#[cfg(feature = "tokio")]
+mod fake;
+#[cfg(feature = "serde_json")]
+mod protobuf;
+#[cfg(feature = "tokio")]
+mod publisher;
+#[cfg(feature = "serde_json")]
+mod push;
+#[cfg(feature = "tokio")]
+mod subscriber;
+#[cfg(feature = "serde_json")]
+mod validator;
+
+#[cfg(feature = "tokio")]
+pub use self::fake::{FakePubsub, FakeServer, PublisherService, SubscriberService};
+#[cfg(feature = "tokio")]
+pub use self::publisher::{
+    BatchSettings, FlowControlSettings, LimitExceededBehavior, PublishClient, PublishError,
+    PublishFuture, PublishResult, Publisher, PublisherSettings, RetrySettings, MAX_PUBLISH_BYTES,
+    MAX_PUBLISH_MESSAGES,
+};
+#[cfg(all(feature = "serde_json", feature = "ring"))]
+pub use self::push::{PushClaims, PushVerifier, VerifiedPush};
+#[cfg(feature = "serde_json")]
+pub use self::push::{PushError, PushRequest};
+#[cfg(feature = "tokio")]
+pub use self::subscriber::{
+    PulledMessage, StreamingPullFuture, StreamingPullRequests, StreamingPullStream,
+    SubscribeClient, Subscriber, SubscriberSettings,
+};
+#[cfg(feature = "serde_json")]
+pub use self::validator::{SchemaError, SchemaValidator};
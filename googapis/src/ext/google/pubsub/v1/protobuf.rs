@@ -0,0 +1,1135 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt, str,
+};
+
+use serde_json::Value;
+
+use super::validator::{Path, Reader, SchemaError, Segment};
+
+// A parsed `.proto` definition, validating messages of its first top-level message type.
+#[derive(Debug, Clone)]
+pub(super) struct ProtoSchema {
+    root: usize,
+    messages: Vec<Message>,
+    enums: Vec<Enum>,
+}
+
+#[derive(Debug, Clone)]
+struct Message {
+    fields: Vec<Field>,
+    oneofs: Vec<String>,
+    map_entry: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Enum {
+    values: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    json_name: String,
+    number: u64,
+    label: Label,
+    ty: FieldType,
+    oneof: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Optional,
+    Required,
+    Repeated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Scalar(Scalar),
+    Message(usize),
+    Enum(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+}
+
+const SCALARS: [(&str, Scalar); 15] = [
+    ("double", Scalar::Double),
+    ("float", Scalar::Float),
+    ("int32", Scalar::Int32),
+    ("int64", Scalar::Int64),
+    ("uint32", Scalar::Uint32),
+    ("uint64", Scalar::Uint64),
+    ("sint32", Scalar::Sint32),
+    ("sint64", Scalar::Sint64),
+    ("fixed32", Scalar::Fixed32),
+    ("fixed64", Scalar::Fixed64),
+    ("sfixed32", Scalar::Sfixed32),
+    ("sfixed64", Scalar::Sfixed64),
+    ("bool", Scalar::Bool),
+    ("string", Scalar::String),
+    ("bytes", Scalar::Bytes),
+];
+
+impl Scalar {
+    fn name(self) -> &'static str {
+        SCALARS.iter().find(|(_, s)| *s == self).unwrap().0
+    }
+
+    // The range of integral types.
+    fn range(self) -> Option<(i128, i128)> {
+        match self {
+            Scalar::Int32 | Scalar::Sint32 | Scalar::Sfixed32 => {
+                Some((i32::MIN.into(), i32::MAX.into()))
+            }
+            Scalar::Uint32 | Scalar::Fixed32 => Some((0, u32::MAX.into())),
+            Scalar::Int64 | Scalar::Sint64 | Scalar::Sfixed64 => {
+                Some((i64::MIN.into(), i64::MAX.into()))
+            }
+            Scalar::Uint64 | Scalar::Fixed64 => Some((0, u64::MAX.into())),
+            _ => None,
+        }
+    }
+}
+
+impl FieldType {
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::Scalar(s) => s.name(),
+            FieldType::Message(_) => "message",
+            FieldType::Enum(_) => "enum",
+        }
+    }
+
+    fn wire_type(self) -> u64 {
+        match self {
+            FieldType::Scalar(Scalar::Double)
+            | FieldType::Scalar(Scalar::Fixed64)
+            | FieldType::Scalar(Scalar::Sfixed64) => 1,
+            FieldType::Scalar(Scalar::Float)
+            | FieldType::Scalar(Scalar::Fixed32)
+            | FieldType::Scalar(Scalar::Sfixed32) => 5,
+            FieldType::Scalar(Scalar::String)
+            | FieldType::Scalar(Scalar::Bytes)
+            | FieldType::Message(_) => 2,
+            _ => 0,
+        }
+    }
+}
+
+// The indefinite article of a type name.
+fn article(name: &str) -> &'static str {
+    if name.starts_with(['i', 'e']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+impl ProtoSchema {
+    pub(super) fn parse(definition: &str) -> Result<Self, SchemaError> {
+        Parser::new(definition)?.file()
+    }
+
+    pub(super) fn validate_binary(&self, message: &[u8]) -> Result<(), SchemaError> {
+        self.binary_message(self.root, message, &mut Path::default())
+    }
+
+    pub(super) fn validate_json(&self, message: &Value) -> Result<(), SchemaError> {
+        self.json_message(self.root, message, &mut Path::default())
+    }
+
+    fn required(
+        &self,
+        message: &Message,
+        present: &HashSet<u64>,
+        path: &Path,
+    ) -> Result<(), SchemaError> {
+        match message
+            .fields
+            .iter()
+            .find(|f| f.label == Label::Required && !present.contains(&f.number))
+        {
+            Some(field) => Err(path.error(format!("missing required field {}", field.name))),
+            None => Ok(()),
+        }
+    }
+
+    fn binary_message(&self, m: usize, bytes: &[u8], path: &mut Path) -> Result<(), SchemaError> {
+        path.check_depth()?;
+        let message = &self.messages[m];
+        let mut r = Reader::new(bytes);
+        let mut present = HashSet::new();
+        while !r.is_empty() {
+            let (number, wire) = key(&mut r, path)?;
+            match message.fields.iter().find(|f| f.number == number) {
+                Some(field) => {
+                    path.push(Segment::Field(field.name.clone()));
+                    self.binary_field(field, wire, &mut r, path)?;
+                    path.pop();
+                    present.insert(number);
+                }
+                None => skip(&mut r, number, wire, path)?,
+            }
+        }
+        self.required(message, &present, path)
+    }
+
+    fn binary_field(
+        &self,
+        field: &Field,
+        wire: u64,
+        r: &mut Reader<'_>,
+        path: &mut Path,
+    ) -> Result<(), SchemaError> {
+        let expected = field.ty.wire_type();
+        if field.label == Label::Repeated && wire == 2 && expected != 2 {
+            // A packed repeated field.
+            let n = length(r, path)?;
+            let mut packed = Reader::new(r.take(n, path)?);
+            while !packed.is_empty() {
+                self.binary_value(field.ty, expected, &mut packed, path)?;
+            }
+            return Ok(());
+        }
+        if wire != expected {
+            let name = field.ty.name();
+            return Err(path.error(format!(
+                "wire type {} for {} {} field",
+                wire,
+                article(name),
+                name
+            )));
+        }
+        self.binary_value(field.ty, wire, r, path)
+    }
+
+    fn binary_value(
+        &self,
+        ty: FieldType,
+        wire: u64,
+        r: &mut Reader<'_>,
+        path: &mut Path,
+    ) -> Result<(), SchemaError> {
+        match wire {
+            0 => {
+                let v = r.varint(path)?;
+                let (scalar, v) = match ty {
+                    FieldType::Scalar(s @ Scalar::Sint32)
+                    | FieldType::Scalar(s @ Scalar::Sint64) => {
+                        (s, i128::from((v >> 1) as i64 ^ -((v & 1) as i64)))
+                    }
+                    FieldType::Scalar(s @ Scalar::Uint32)
+                    | FieldType::Scalar(s @ Scalar::Uint64) => (s, i128::from(v)),
+                    FieldType::Scalar(s) => (s, i128::from(v as i64)),
+                    FieldType::Enum(_) => (Scalar::Int32, i128::from(v as i64)),
+                    FieldType::Message(_) => unreachable!(),
+                };
+                check_range(scalar, v, path)?;
+            }
+            1 => {
+                r.take(8, path)?;
+            }
+            5 => {
+                r.take(4, path)?;
+            }
+            _ => {
+                let n = length(r, path)?;
+                let bytes = r.take(n, path)?;
+                match ty {
+                    FieldType::Scalar(Scalar::String) if str::from_utf8(bytes).is_err() => {
+                        return Err(path.error("invalid UTF-8 string"));
+                    }
+                    FieldType::Message(m) => self.binary_message(m, bytes, path)?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn json_message(&self, m: usize, value: &Value, path: &mut Path) -> Result<(), SchemaError> {
+        let message = &self.messages[m];
+        let object = value
+            .as_object()
+            .ok_or_else(|| path.error("expected an object"))?;
+        let mut seen = HashSet::new();
+        let mut present = HashSet::new();
+        let mut oneofs = Vec::new();
+        for (name, v) in object {
+            path.push(Segment::Field(name.clone()));
+            let field = message
+                .fields
+                .iter()
+                .find(|f| f.json_name == *name || f.name == *name)
+                .ok_or_else(|| path.error("unknown field"))?;
+            if !seen.insert(field.number) {
+                return Err(path.error("duplicate field"));
+            }
+            // Null is the default value of any field.
+            if !v.is_null() {
+                if let Some(oneof) = field.oneof {
+                    if oneofs.contains(&oneof) {
+                        return Err(path.error(format!(
+                            "multiple fields of oneof {}",
+                            message.oneofs[oneof]
+                        )));
+                    }
+                    oneofs.push(oneof);
+                }
+                self.json_field(field, v, path)?;
+                present.insert(field.number);
+            }
+            path.pop();
+        }
+        self.required(message, &present, path)
+    }
+
+    fn json_field(&self, field: &Field, value: &Value, path: &mut Path) -> Result<(), SchemaError> {
+        match (field.label, field.ty) {
+            (Label::Repeated, FieldType::Message(m)) if self.messages[m].map_entry => {
+                let entry = &self.messages[m];
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| path.error("expected an object"))?;
+                for (k, v) in object {
+                    path.push(Segment::Key(k.clone()));
+                    match entry.fields[0].ty {
+                        FieldType::Scalar(Scalar::Bool) if k != "true" && k != "false" => {
+                            return Err(path.error("invalid bool key"));
+                        }
+                        FieldType::Scalar(s) if s.range().is_some() => {
+                            self.json_value(entry.fields[0].ty, &Value::String(k.clone()), path)
+                                .map_err(|_| path.error(format!("invalid {} key", s.name())))?;
+                        }
+                        _ => {}
+                    }
+                    self.json_value(entry.fields[1].ty, v, path)?;
+                    path.pop();
+                }
+            }
+            (Label::Repeated, ty) => {
+                let values = value
+                    .as_array()
+                    .ok_or_else(|| path.error("expected an array"))?;
+                for (i, v) in values.iter().enumerate() {
+                    path.push(Segment::Index(i));
+                    self.json_value(ty, v, path)?;
+                    path.pop();
+                }
+            }
+            (_, ty) => self.json_value(ty, value, path)?,
+        }
+        Ok(())
+    }
+
+    fn json_value(&self, ty: FieldType, value: &Value, path: &mut Path) -> Result<(), SchemaError> {
+        let expected = || {
+            let name = ty.name();
+            path.error(format!("expected {} {}", article(name), name))
+        };
+        match ty {
+            FieldType::Message(m) => self.json_message(m, value, path)?,
+            FieldType::Enum(e) => match value {
+                Value::String(name) if self.enums[e].values.contains(name) => {}
+                Value::String(name) => {
+                    return Err(path.error(format!("unknown enum value {:?}", name)));
+                }
+                Value::Number(n) => match n.as_i64() {
+                    Some(v) => check_range(Scalar::Int32, v.into(), path)?,
+                    None => return Err(expected()),
+                },
+                _ => return Err(expected()),
+            },
+            FieldType::Scalar(s) => match s {
+                Scalar::Bool if value.is_boolean() => {}
+                Scalar::String if value.is_string() => {}
+                Scalar::Bool | Scalar::String => return Err(expected()),
+                Scalar::Bytes => {
+                    let s = value
+                        .as_str()
+                        .ok_or_else(|| path.error("expected a string"))?;
+                    if !is_base64(s) {
+                        return Err(path.error("invalid base64"));
+                    }
+                }
+                Scalar::Float | Scalar::Double => {
+                    let v = match value {
+                        Value::Number(n) => n.as_f64(),
+                        Value::String(s) => s.parse::<f64>().ok().filter(|v| {
+                            v.is_finite() || ["NaN", "Infinity", "-Infinity"].contains(&s.as_str())
+                        }),
+                        _ => None,
+                    }
+                    .ok_or_else(expected)?;
+                    if s == Scalar::Float && v.is_finite() && v.abs() > f64::from(f32::MAX) {
+                        return Err(path.error(format!("float out of range: {}", v)));
+                    }
+                }
+                _ => {
+                    let v = integer(value).ok_or_else(expected)?;
+                    check_range(s, v, path)?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn check_range(scalar: Scalar, v: i128, path: &Path) -> Result<(), SchemaError> {
+    match scalar.range() {
+        Some((min, max)) if v < min || v > max => {
+            Err(path.error(format!("{} out of range: {}", scalar.name(), v)))
+        }
+        _ => Ok(()),
+    }
+}
+
+// An integer in the JSON mapping: a number or a string, without a fractional part.
+fn integer(value: &Value) -> Option<i128> {
+    let float = |v: f64| Some(v).filter(|v| v.fract() == 0.0 && v.abs() < 1e38);
+    match value {
+        Value::Number(n) => n
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from))
+            .or_else(|| n.as_f64().and_then(float).map(|v| v as i128)),
+        Value::String(s) => s
+            .parse::<i128>()
+            .ok()
+            .or_else(|| s.parse::<f64>().ok().and_then(float).map(|v| v as i128)),
+        _ => None,
+    }
+}
+
+// Whether `s` is standard or URL-safe base64, with or without padding.
+fn is_base64(s: &str) -> bool {
+    let data = s.trim_end_matches('=');
+    let padding = s.len() - data.len();
+    data.bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b"+/-_".contains(&b))
+        && data.len() % 4 != 1
+        && (padding == 0 || (padding <= 2 && s.len().is_multiple_of(4)))
+}
+
+fn key(r: &mut Reader<'_>, path: &Path) -> Result<(u64, u64), SchemaError> {
+    let pos = r.pos();
+    let key = r.varint(path)?;
+    let (number, wire) = (key >> 3, key & 7);
+    if number == 0 || number > u64::from(MAX_FIELD_NUMBER) {
+        return Err(path.error(format!("invalid field number {} at offset {}", number, pos)));
+    }
+    if wire > 5 {
+        return Err(path.error(format!("invalid wire type {} at offset {}", wire, pos)));
+    }
+    Ok((number, wire))
+}
+
+fn length(r: &mut Reader<'_>, path: &Path) -> Result<usize, SchemaError> {
+    let n = r.varint(path)?;
+    usize::try_from(n).map_err(|_| path.error(format!("invalid length: {}", n)))
+}
+
+// Skips an unknown field.
+fn skip(r: &mut Reader<'_>, number: u64, wire: u64, path: &Path) -> Result<(), SchemaError> {
+    match wire {
+        0 => {
+            r.varint(path)?;
+        }
+        1 => {
+            r.take(8, path)?;
+        }
+        2 => {
+            let n = length(r, path)?;
+            r.take(n, path)?;
+        }
+        3 => loop {
+            let (n, w) = key(r, path)?;
+            if w == 4 {
+                if n != number {
+                    return Err(path.error(format!("unmatched end of group {}", n)));
+                }
+                break;
+            }
+            skip(r, n, w, path)?;
+        },
+        4 => return Err(path.error(format!("unmatched end of group {}", number))),
+        _ => {
+            r.take(4, path)?;
+        }
+    }
+    Ok(())
+}
+
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Symbol(char),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "{:?}", s),
+            Token::Str(s) => write!(f, "string {:?}", s),
+            Token::Symbol(c) => write!(f, "{:?}", c),
+            Token::End => f.write_str("end of file"),
+        }
+    }
+}
+
+// Splits a definition into tokens and their line numbers.
+fn tokenize(definition: &str) -> Result<Vec<(Token, usize)>, SchemaError> {
+    let mut tokens = Vec::new();
+    let mut chars = definition.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => return Err(invalid(line, "unterminated comment")),
+                    }
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(e) => s.push(e),
+                            None => return Err(invalid(line, "unterminated string")),
+                        },
+                        Some('\n') | None => return Err(invalid(line, "unterminated string")),
+                        Some(c) => s.push(c),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_digit() => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    let exponent = (s.ends_with('e') || s.ends_with('E')) && !s.starts_with("0x");
+                    if c.is_ascii_alphanumeric() || c == '.' || (exponent && (c == '+' || c == '-'))
+                    {
+                        s.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Token::Number(s)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                        s.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Token::Ident(s)
+            }
+            c => Token::Symbol(c),
+        };
+        tokens.push((token, line));
+    }
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+fn invalid(line: usize, message: impl fmt::Display) -> SchemaError {
+    SchemaError::Definition(format!("line {}: {}", line, message))
+}
+
+fn parse_int(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        u64::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// The default JSON name of a field: its name in lower camel case.
+fn json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            json_name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Named {
+    Message(usize),
+    Enum(usize),
+}
+
+// A type reference to resolve once all the types are declared.
+struct Reference {
+    message: usize,
+    field: usize,
+    name: String,
+    scope: String,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    proto3: bool,
+    messages: Vec<Message>,
+    enums: Vec<Enum>,
+    names: HashMap<String, Named>,
+    references: Vec<Reference>,
+}
+
+impl Parser {
+    fn new(definition: &str) -> Result<Self, SchemaError> {
+        Ok(Parser {
+            tokens: tokenize(definition)?,
+            pos: 0,
+            proto3: false,
+            messages: Vec::new(),
+            enums: Vec::new(),
+            names: HashMap::new(),
+            references: Vec::new(),
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl fmt::Display) -> SchemaError {
+        invalid(self.line(), message)
+    }
+
+    fn unexpected(&self, expected: &str) -> SchemaError {
+        self.error(format!("expected {}, found {}", expected, self.peek()))
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if *self.peek() == Token::Symbol(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SchemaError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("{:?}", c)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, SchemaError> {
+        match self.peek() {
+            Token::Ident(s) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, SchemaError> {
+        let line = self.line();
+        let name = self.ident()?;
+        if name.contains('.') {
+            return Err(invalid(line, format!("invalid name {:?}", name)));
+        }
+        Ok(name)
+    }
+
+    fn number(&mut self) -> Result<u64, SchemaError> {
+        match self.next() {
+            Token::Number(s) => {
+                parse_int(&s).ok_or_else(|| self.error(format!("invalid number {:?}", s)))
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a number"))
+            }
+        }
+    }
+
+    // Skips a statement up to its semicolon, including aggregate option values.
+    fn skip_statement(&mut self) -> Result<(), SchemaError> {
+        loop {
+            match self.peek() {
+                Token::Symbol(';') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Token::Symbol('{') => self.skip_block()?,
+                Token::End => return Err(self.unexpected("';'")),
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn skip_block(&mut self) -> Result<(), SchemaError> {
+        self.expect('{')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => depth -= 1,
+                Token::End => return Err(self.unexpected("'}'")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn file(mut self) -> Result<ProtoSchema, SchemaError> {
+        let mut package = String::new();
+        let mut root = None;
+        if *self.peek() == Token::Ident("syntax".to_owned()) {
+            self.pos += 1;
+            self.expect('=')?;
+            self.proto3 = match self.next() {
+                Token::Str(s) if s == "proto2" => false,
+                Token::Str(s) if s == "proto3" => true,
+                t => return Err(self.error(format!("unsupported syntax {}", t))),
+            };
+            self.expect(';')?;
+        }
+        loop {
+            let line = self.line();
+            match self.next() {
+                Token::End => break,
+                Token::Symbol(';') => {}
+                Token::Ident(k) if k == "package" => {
+                    package = self.ident()?;
+                    self.expect(';')?;
+                }
+                Token::Ident(k) if k == "import" => {
+                    return Err(invalid(line, "imports are not supported"));
+                }
+                Token::Ident(k) if k == "option" => self.skip_statement()?,
+                Token::Ident(k) if k == "message" => {
+                    let m = self.message(&package)?;
+                    root.get_or_insert(m);
+                }
+                Token::Ident(k) if k == "enum" => self.enumeration(&package)?,
+                Token::Ident(k) if k == "service" || k == "extend" => {
+                    self.ident()?;
+                    self.skip_block()?;
+                }
+                t => return Err(invalid(line, format!("unexpected {}", t))),
+            }
+        }
+        let root = root.ok_or_else(|| invalid(self.line(), "no message type"))?;
+
+        for reference in std::mem::take(&mut self.references) {
+            let named = self
+                .resolve(&reference.name, &reference.scope)
+                .ok_or_else(|| {
+                    invalid(reference.line, format!("unknown type {:?}", reference.name))
+                })?;
+            self.messages[reference.message].fields[reference.field].ty = match named {
+                Named::Message(m) => FieldType::Message(m),
+                Named::Enum(e) => FieldType::Enum(e),
+            };
+        }
+        Ok(ProtoSchema {
+            root,
+            messages: self.messages,
+            enums: self.enums,
+        })
+    }
+
+    fn resolve(&self, name: &str, scope: &str) -> Option<Named> {
+        if let Some(name) = name.strip_prefix('.') {
+            return self.names.get(name).copied();
+        }
+        let mut scope = scope;
+        loop {
+            let full_name = if scope.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}.{}", scope, name)
+            };
+            if let Some(named) = self.names.get(&full_name) {
+                return Some(*named);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rfind('.').map_or("", |i| &scope[..i]);
+        }
+    }
+
+    fn declare(
+        &mut self,
+        scope: &str,
+        name: &str,
+        named: Named,
+        line: usize,
+    ) -> Result<String, SchemaError> {
+        let full_name = if scope.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}.{}", scope, name)
+        };
+        if self.names.insert(full_name.clone(), named).is_some() {
+            return Err(invalid(line, format!("duplicate type {:?}", full_name)));
+        }
+        Ok(full_name)
+    }
+
+    fn message(&mut self, scope: &str) -> Result<usize, SchemaError> {
+        let line = self.line();
+        let name = self.name()?;
+        let m = self.messages.len();
+        let full_name = self.declare(scope, &name, Named::Message(m), line)?;
+        self.messages.push(Message {
+            fields: Vec::new(),
+            oneofs: Vec::new(),
+            map_entry: false,
+        });
+        self.expect('{')?;
+        while !self.eat('}') {
+            let line = self.line();
+            match self.peek().clone() {
+                Token::Symbol(';') => self.pos += 1,
+                Token::Ident(k) if k == "message" => {
+                    self.pos += 1;
+                    self.message(&full_name)?;
+                }
+                Token::Ident(k) if k == "enum" => {
+                    self.pos += 1;
+                    self.enumeration(&full_name)?;
+                }
+                Token::Ident(k) if k == "option" || k == "reserved" || k == "extensions" => {
+                    self.skip_statement()?
+                }
+                Token::Ident(k) if k == "extend" => {
+                    self.pos += 1;
+                    self.ident()?;
+                    self.skip_block()?;
+                }
+                Token::Ident(k) if k == "oneof" => {
+                    self.pos += 1;
+                    let oneof = self.name()?;
+                    let index = self.messages[m].oneofs.len();
+                    self.messages[m].oneofs.push(oneof);
+                    self.expect('{')?;
+                    while !self.eat('}') {
+                        match self.peek() {
+                            Token::Symbol(';') => self.pos += 1,
+                            Token::Ident(k) if k == "option" => self.skip_statement()?,
+                            _ => self.field(m, &full_name, Label::Optional, Some(index))?,
+                        }
+                    }
+                }
+                Token::Ident(k) if k == "map" => {
+                    self.pos += 1;
+                    self.map(m, &full_name)?;
+                }
+                Token::Ident(k) if k == "group" => {
+                    return Err(invalid(line, "groups are not supported"));
+                }
+                Token::Ident(k) if k == "required" && self.proto3 => {
+                    return Err(invalid(line, "required fields are not allowed in proto3"));
+                }
+                Token::Ident(k) if k == "required" || k == "optional" || k == "repeated" => {
+                    self.pos += 1;
+                    let label = match k.as_str() {
+                        "required" => Label::Required,
+                        "optional" => Label::Optional,
+                        _ => Label::Repeated,
+                    };
+                    self.field(m, &full_name, label, None)?;
+                }
+                Token::Ident(_) => self.field(m, &full_name, Label::Optional, None)?,
+                _ => return Err(self.unexpected("a field")),
+            }
+        }
+
+        let mut numbers = HashSet::new();
+        let mut names = HashSet::new();
+        for field in &self.messages[m].fields {
+            if !numbers.insert(field.number) {
+                return Err(invalid(
+                    line,
+                    format!("duplicate field number {} in {}", field.number, full_name),
+                ));
+            }
+            if !names.insert(&field.json_name) {
+                return Err(invalid(
+                    line,
+                    format!("duplicate field {} in {}", field.json_name, full_name),
+                ));
+            }
+        }
+        Ok(m)
+    }
+
+    // Parses a field after its label.
+    fn field(
+        &mut self,
+        m: usize,
+        scope: &str,
+        label: Label,
+        oneof: Option<usize>,
+    ) -> Result<(), SchemaError> {
+        let line = self.line();
+        let ty = self.ident()?;
+        let scalar = SCALARS.iter().find(|(n, _)| *n == ty).map(|(_, s)| *s);
+        let name = self.name()?;
+        if scalar.is_none() {
+            self.references.push(Reference {
+                message: m,
+                field: self.messages[m].fields.len(),
+                name: ty,
+                scope: scope.to_owned(),
+                line,
+            });
+        }
+        self.field_number_and_options(
+            m,
+            Field {
+                json_name: json_name(&name),
+                name,
+                number: 0,
+                label,
+                // Replaced once the reference is resolved.
+                ty: FieldType::Scalar(scalar.unwrap_or(Scalar::Bytes)),
+                oneof,
+            },
+        )
+    }
+
+    fn field_number_and_options(&mut self, m: usize, mut field: Field) -> Result<(), SchemaError> {
+        self.expect('=')?;
+        let line = self.line();
+        field.number = self.number()?;
+        if field.number == 0
+            || field.number > u64::from(MAX_FIELD_NUMBER)
+            || (19000..20000).contains(&field.number)
+        {
+            return Err(invalid(
+                line,
+                format!("invalid field number {}", field.number),
+            ));
+        }
+        if self.eat('[') {
+            loop {
+                let mut option = String::new();
+                while !self.eat('=') {
+                    match self.next() {
+                        Token::Ident(s) => option.push_str(&s),
+                        Token::Symbol(c @ '(') | Token::Symbol(c @ ')') => option.push(c),
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.unexpected("an option"));
+                        }
+                    }
+                }
+                if *self.peek() == Token::Symbol('{') {
+                    self.skip_block()?;
+                } else {
+                    match self.next() {
+                        Token::Str(s) if option == "json_name" => field.json_name = s,
+                        Token::Symbol('-') => {
+                            self.next();
+                        }
+                        Token::Symbol(_) | Token::End => {
+                            self.pos -= 1;
+                            return Err(self.unexpected("an option value"));
+                        }
+                        _ => {}
+                    }
+                }
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(']')?;
+        }
+        self.expect(';')?;
+        self.messages[m].fields.push(field);
+        Ok(())
+    }
+
+    fn map(&mut self, m: usize, scope: &str) -> Result<(), SchemaError> {
+        let line = self.line();
+        self.expect('<')?;
+        let key = self.ident()?;
+        let key = match SCALARS.iter().find(|(n, _)| *n == key) {
+            Some((_, s)) if !matches!(s, Scalar::Float | Scalar::Double | Scalar::Bytes) => *s,
+            _ => return Err(invalid(line, format!("invalid map key type {:?}", key))),
+        };
+        self.expect(',')?;
+        let value = self.ident()?;
+        self.expect('>')?;
+        let name = self.name()?;
+
+        // Maps are repeated fields of synthetic entry messages.
+        let entry = self.messages.len();
+        let scalar = SCALARS.iter().find(|(n, _)| *n == value).map(|(_, s)| *s);
+        if scalar.is_none() {
+            self.references.push(Reference {
+                message: entry,
+                field: 1,
+                name: value,
+                scope: scope.to_owned(),
+                line,
+            });
+        }
+        let entry_field = |name: &str, number, ty| Field {
+            name: name.to_owned(),
+            json_name: name.to_owned(),
+            number,
+            label: Label::Optional,
+            ty,
+            oneof: None,
+        };
+        self.messages.push(Message {
+            fields: vec![
+                entry_field("key", 1, FieldType::Scalar(key)),
+                entry_field(
+                    "value",
+                    2,
+                    FieldType::Scalar(scalar.unwrap_or(Scalar::Bytes)),
+                ),
+            ],
+            oneofs: Vec::new(),
+            map_entry: true,
+        });
+        self.field_number_and_options(
+            m,
+            Field {
+                json_name: json_name(&name),
+                name,
+                number: 0,
+                label: Label::Repeated,
+                ty: FieldType::Message(entry),
+                oneof: None,
+            },
+        )
+    }
+
+    fn enumeration(&mut self, scope: &str) -> Result<(), SchemaError> {
+        let line = self.line();
+        let name = self.name()?;
+        let e = self.enums.len();
+        let full_name = self.declare(scope, &name, Named::Enum(e), line)?;
+        let mut values = Vec::new();
+        self.expect('{')?;
+        while !self.eat('}') {
+            match self.peek().clone() {
+                Token::Symbol(';') => self.pos += 1,
+                Token::Ident(k) if k == "option" || k == "reserved" => self.skip_statement()?,
+                _ => {
+                    let line = self.line();
+                    let value = self.name()?;
+                    self.expect('=')?;
+                    let negative = self.eat('-');
+                    let number = self.number()?;
+                    if i32::try_from(number).is_err() && !(negative && number == 1 << 31) {
+                        return Err(invalid(line, format!("enum value {} out of range", value)));
+                    }
+                    if values.is_empty() && self.proto3 && number != 0 {
+                        return Err(invalid(
+                            line,
+                            format!("the first value of {} must be zero", full_name),
+                        ));
+                    }
+                    if *self.peek() == Token::Symbol('[') {
+                        while !self.eat(']') {
+                            if self.next() == Token::End {
+                                return Err(self.unexpected("']'"));
+                            }
+                        }
+                    }
+                    self.expect(';')?;
+                    values.push(value);
+                }
+            }
+        }
+        if values.is_empty() {
+            return Err(invalid(line, format!("{} has no values", full_name)));
+        }
+        self.enums.push(Enum { values });
+        Ok(())
+    }
+}
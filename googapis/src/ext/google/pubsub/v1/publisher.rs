@@ -0,0 +1,860 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use prost::Message;
+use tokio::{
+    sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore},
+    time::{self, Instant},
+};
+
+use super::{publisher_client::PublisherClient, PublishRequest, PublishResponse, PubsubMessage};
+
+/// The maximum number of messages of a `PublishRequest`.
+pub const MAX_PUBLISH_MESSAGES: usize = 1000;
+/// The maximum size of a `PublishRequest` in bytes.
+pub const MAX_PUBLISH_BYTES: usize = 10_000_000;
+
+/// The future returned by [`PublishClient::publish`].
+pub type PublishFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<PublishResponse, tonic::Status>> + Send + 'a>>;
+
+/// A client of the `Publish` method of the `google.pubsub.v1.Publisher` service, implemented by
+/// `PublisherClient`. The [`Publisher`] clones it for every request.
+pub trait PublishClient: Clone + Send + 'static {
+    fn publish(&mut self, request: PublishRequest) -> PublishFuture<'_>;
+}
+
+impl<T> PublishClient for PublisherClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn publish(&mut self, request: PublishRequest) -> PublishFuture<'_> {
+        Box::pin(async move { Ok(PublisherClient::publish(self, request).await?.into_inner()) })
+    }
+}
+
+/// When a batch of messages is sent. The limits are capped at [`MAX_PUBLISH_MESSAGES`] and
+/// [`MAX_PUBLISH_BYTES`].
+#[derive(Debug, Clone)]
+pub struct BatchSettings {
+    pub max_messages: usize,
+    /// The maximum size of the `PublishRequest` of a batch.
+    pub max_bytes: usize,
+    /// How long the first message of a batch waits for others.
+    pub max_delay: Duration,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        BatchSettings {
+            max_messages: 100,
+            max_bytes: 1_000_000,
+            max_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+/// What [`Publisher::publish`] does when the outstanding messages are over a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceededBehavior {
+    /// Waits for outstanding messages to be published.
+    Block,
+    /// Fails with [`PublishError::FlowControl`].
+    Error,
+    /// Ignores the limits.
+    Ignore,
+}
+
+/// Limits of the messages published but not yet acknowledged by the service.
+#[derive(Debug, Clone)]
+pub struct FlowControlSettings {
+    pub max_outstanding_messages: usize,
+    /// The maximum sum of the sizes of the outstanding messages, as encoded in a
+    /// `PublishRequest`.
+    pub max_outstanding_bytes: usize,
+    pub limit_exceeded_behavior: LimitExceededBehavior,
+}
+
+impl Default for FlowControlSettings {
+    fn default() -> Self {
+        FlowControlSettings {
+            max_outstanding_messages: 10_000,
+            max_outstanding_bytes: 100 << 20,
+            limit_exceeded_behavior: LimitExceededBehavior::Block,
+        }
+    }
+}
+
+/// The retries of a failed `PublishRequest`, with exponential backoff. The default is the
+/// `Publish` method configuration of `google/pubsub/v1/pubsub_grpc_service_config.json`.
+#[derive(Debug, Clone)]
+pub struct RetrySettings {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: f64,
+    /// The timeout of each attempt.
+    pub attempt_timeout: Duration,
+    /// No attempt starts after this time since the first one.
+    pub total_timeout: Duration,
+    pub retryable_codes: Vec<tonic::Code>,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+            backoff_multiplier: 4.0,
+            attempt_timeout: Duration::from_secs(60),
+            total_timeout: Duration::from_secs(600),
+            retryable_codes: vec![
+                tonic::Code::Aborted,
+                tonic::Code::Cancelled,
+                tonic::Code::Internal,
+                tonic::Code::ResourceExhausted,
+                tonic::Code::Unknown,
+                tonic::Code::Unavailable,
+                tonic::Code::DeadlineExceeded,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PublisherSettings {
+    pub batch: BatchSettings,
+    pub flow_control: FlowControlSettings,
+    pub retry: RetrySettings,
+}
+
+/// An error returned when publishing a message.
+#[derive(Debug)]
+pub enum PublishError {
+    /// The `PublishRequest` of the message failed, after retries.
+    Status(tonic::Status),
+    /// A message with the ordering key failed to be published, see
+    /// [`Publisher::resume_publish`].
+    OrderingKeyPaused(String),
+    /// The outstanding messages are over the limits of [`FlowControlSettings`].
+    FlowControl,
+    /// The message, of the size in bytes, does not fit in a `PublishRequest`.
+    TooLarge(usize),
+    /// The publisher stopped before the message was published.
+    Closed,
+}
+
+impl Clone for PublishError {
+    fn clone(&self) -> Self {
+        match self {
+            PublishError::Status(s) => {
+                PublishError::Status(tonic::Status::with_details_and_metadata(
+                    s.code(),
+                    s.message(),
+                    s.details().to_vec().into(),
+                    s.metadata().clone(),
+                ))
+            }
+            PublishError::OrderingKeyPaused(key) => PublishError::OrderingKeyPaused(key.clone()),
+            PublishError::FlowControl => PublishError::FlowControl,
+            PublishError::TooLarge(size) => PublishError::TooLarge(*size),
+            PublishError::Closed => PublishError::Closed,
+        }
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::Status(s) => write!(f, "publish failed: {}", s),
+            PublishError::OrderingKeyPaused(key) => {
+                write!(f, "publishing paused for ordering key {:?}", key)
+            }
+            PublishError::FlowControl => f.write_str("flow control limits exceeded"),
+            PublishError::TooLarge(size) => write!(f, "message too large: {} bytes", size),
+            PublishError::Closed => f.write_str("publisher closed"),
+        }
+    }
+}
+
+impl error::Error for PublishError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PublishError::Status(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The result of [`Publisher::publish`], resolving to the message id once the message is
+/// published.
+#[derive(Debug)]
+pub struct PublishResult {
+    rx: oneshot::Receiver<Result<String, PublishError>>,
+}
+
+impl PublishResult {
+    fn err(e: PublishError) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let _ = tx.send(Err(e));
+        PublishResult { rx }
+    }
+}
+
+impl Future for PublishResult {
+    type Output = Result<String, PublishError>;
+
+    #[allow(clippy::result_large_err)]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|r| r.unwrap_or(Err(PublishError::Closed)))
+    }
+}
+
+// A message waiting to be published, with its flow control permits.
+#[derive(Debug)]
+struct Pending {
+    message: PubsubMessage,
+    size: usize,
+    result: oneshot::Sender<Result<String, PublishError>>,
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Publish(Pending),
+    Resume(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Publishes messages to a topic in batches, see [`BatchSettings`].
+///
+/// Messages with the same non-empty `ordering_key` are published in order: a batch of a key is
+/// sent once the previous one is published. If one fails, publishing the key is paused until
+/// [`Publisher::resume_publish`]. Messages without ordering key are sent concurrently.
+///
+/// Messages still pending when the publisher is dropped are published in the background.
+///
+/// # Example
+/// ```ignore
+/// use googapis::google::pubsub::v1::{publisher_client::PublisherClient, Publisher, PubsubMessage};
+///
+/// let publisher = Publisher::new(PublisherClient::new(channel), "projects/p/topics/t");
+/// let mut results = Vec::new();
+/// for data in payloads {
+///     let message = PubsubMessage { data, ..Default::default() };
+///     results.push(publisher.publish(message).await);
+/// }
+/// for result in results {
+///     let id = result.await?;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Publisher {
+    topic: String,
+    commands: mpsc::UnboundedSender<Command>,
+    flow_control: FlowControlSettings,
+    messages: Arc<Semaphore>,
+    bytes: Arc<Semaphore>,
+    base_size: usize,
+}
+
+impl Publisher {
+    /// A publisher with the default settings. It must be created in a Tokio runtime.
+    pub fn new<C: PublishClient>(client: C, topic: impl Into<String>) -> Self {
+        Self::with_settings(client, topic, PublisherSettings::default())
+    }
+
+    /// A publisher with `settings`. It must be created in a Tokio runtime.
+    pub fn with_settings<C: PublishClient>(
+        client: C,
+        topic: impl Into<String>,
+        settings: PublisherSettings,
+    ) -> Self {
+        let topic = topic.into();
+        let base_size = PublishRequest {
+            topic: topic.clone(),
+            messages: Vec::new(),
+        }
+        .encoded_len();
+        let (commands, rx) = mpsc::unbounded_channel();
+        let (done_tx, done) = mpsc::unbounded_channel();
+        let batcher = Batcher {
+            client,
+            topic: topic.clone(),
+            batch: BatchSettings {
+                max_messages: settings.batch.max_messages.clamp(1, MAX_PUBLISH_MESSAGES),
+                max_bytes: settings.batch.max_bytes.min(MAX_PUBLISH_BYTES),
+                max_delay: settings.batch.max_delay,
+            },
+            retry: Arc::new(settings.retry),
+            base_size,
+            queues: HashMap::new(),
+            in_flight: 0,
+            flushes: Vec::new(),
+            done_tx,
+        };
+        tokio::spawn(batcher.run(rx, done));
+
+        let permits = |n: usize| Arc::new(Semaphore::new(n.min(Semaphore::MAX_PERMITS)));
+        Publisher {
+            topic,
+            commands,
+            messages: permits(settings.flow_control.max_outstanding_messages),
+            bytes: permits(settings.flow_control.max_outstanding_bytes),
+            flow_control: settings.flow_control,
+            base_size,
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Queues the message for publishing, after waiting for flow control if the limit exceeded
+    /// behavior is `Block`.
+    pub async fn publish(&self, message: PubsubMessage) -> PublishResult {
+        let size = prost::encoding::message::encoded_len(2, &message);
+        if self.base_size + size > MAX_PUBLISH_BYTES {
+            return PublishResult::err(PublishError::TooLarge(size));
+        }
+        let permits = match self.acquire(size).await {
+            Ok(permits) => permits,
+            Err(e) => return PublishResult::err(e),
+        };
+        let (result, rx) = oneshot::channel();
+        let pending = Pending {
+            message,
+            size,
+            result,
+            _permits: permits,
+        };
+        if self.commands.send(Command::Publish(pending)).is_err() {
+            return PublishResult::err(PublishError::Closed);
+        }
+        PublishResult { rx }
+    }
+
+    async fn acquire(&self, size: usize) -> Result<Vec<OwnedSemaphorePermit>, PublishError> {
+        let fc = &self.flow_control;
+        if fc.limit_exceeded_behavior == LimitExceededBehavior::Ignore {
+            return Ok(Vec::new());
+        }
+        // A message larger than the limit would never be published.
+        if size > fc.max_outstanding_bytes || fc.max_outstanding_messages == 0 {
+            return Err(PublishError::FlowControl);
+        }
+        let size = size as u32;
+        if fc.limit_exceeded_behavior == LimitExceededBehavior::Block {
+            let message = self.messages.clone().acquire_owned().await;
+            let bytes = self.bytes.clone().acquire_many_owned(size).await;
+            message
+                .and_then(|m| bytes.map(|b| vec![m, b]))
+                .map_err(|_| PublishError::Closed)
+        } else {
+            let message = self.messages.clone().try_acquire_owned();
+            let bytes = self.bytes.clone().try_acquire_many_owned(size);
+            message
+                .and_then(|m| bytes.map(|b| vec![m, b]))
+                .map_err(|_| PublishError::FlowControl)
+        }
+    }
+
+    /// Resumes publishing messages with the ordering key after a failure.
+    pub fn resume_publish(&self, ordering_key: &str) {
+        let _ = self.commands.send(Command::Resume(ordering_key.to_owned()));
+    }
+
+    /// Sends the pending messages without waiting for their batches to fill, and waits until
+    /// they are published or failed.
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.commands.send(Command::Flush(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// Publishes the pending messages and stops the publisher.
+    pub async fn shutdown(self) {
+        self.flush().await;
+    }
+}
+
+#[derive(Debug)]
+struct Batch {
+    messages: Vec<Pending>,
+    bytes: usize,
+    deadline: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    batches: VecDeque<Batch>,
+    // Whether a batch of an ordering key is being sent.
+    in_flight: bool,
+    paused: bool,
+}
+
+// The task batching and sending the messages of a `Publisher`.
+struct Batcher<C> {
+    client: C,
+    topic: String,
+    batch: BatchSettings,
+    retry: Arc<RetrySettings>,
+    base_size: usize,
+    // The batches by ordering key, the empty key for messages without one.
+    queues: HashMap<String, Queue>,
+    in_flight: usize,
+    flushes: Vec<oneshot::Sender<()>>,
+    done_tx: mpsc::UnboundedSender<(String, bool)>,
+}
+
+impl<C: PublishClient> Batcher<C> {
+    async fn run(
+        mut self,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+        mut done: mpsc::UnboundedReceiver<(String, bool)>,
+    ) {
+        let mut open = true;
+        loop {
+            self.dispatch(!open || !self.flushes.is_empty());
+            let idle = self.in_flight == 0 && self.queues.values().all(|q| q.batches.is_empty());
+            if idle {
+                for flush in self.flushes.drain(..) {
+                    let _ = flush.send(());
+                }
+                if !open {
+                    return;
+                }
+            }
+
+            let deadline = self
+                .queues
+                .values()
+                .filter(|q| !q.in_flight)
+                .filter_map(|q| q.batches.front())
+                .map(|b| b.deadline)
+                .min();
+            tokio::select! {
+                command = commands.recv(), if open => match command {
+                    Some(command) => self.handle(command),
+                    None => open = false,
+                },
+                Some((key, ok)) = done.recv() => self.complete(key, ok),
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Publish(pending) => {
+                let key = &pending.message.ordering_key;
+                let queue = self.queues.entry(key.clone()).or_default();
+                if queue.paused {
+                    let e = PublishError::OrderingKeyPaused(key.clone());
+                    let _ = pending.result.send(Err(e));
+                    return;
+                }
+                let batch = &self.batch;
+                let fits = queue.batches.back().is_some_and(|b| {
+                    b.messages.len() < batch.max_messages
+                        && b.bytes + pending.size <= batch.max_bytes
+                });
+                if !fits {
+                    queue.batches.push_back(Batch {
+                        messages: Vec::new(),
+                        bytes: self.base_size,
+                        deadline: Instant::now() + batch.max_delay,
+                    });
+                }
+                let last = queue.batches.back_mut().unwrap();
+                last.bytes += pending.size;
+                last.messages.push(pending);
+            }
+            Command::Resume(key) => {
+                if let Some(queue) = self.queues.get_mut(&key) {
+                    queue.paused = false;
+                }
+            }
+            Command::Flush(tx) => self.flushes.push(tx),
+        }
+    }
+
+    // Sends the batches which are full, past their delay, or followed by another one.
+    fn dispatch(&mut self, force: bool) {
+        let now = Instant::now();
+        for (key, queue) in &mut self.queues {
+            while let Some(batch) = queue.batches.front() {
+                let ready = force
+                    || queue.batches.len() > 1
+                    || batch.messages.len() >= self.batch.max_messages
+                    || batch.deadline <= now;
+                if !ready || queue.in_flight {
+                    break;
+                }
+                let batch = queue.batches.pop_front().unwrap();
+                queue.in_flight = !key.is_empty();
+                self.in_flight += 1;
+
+                let request = send(
+                    self.client.clone(),
+                    self.topic.clone(),
+                    batch.messages,
+                    self.retry.clone(),
+                );
+                let (key, done) = (key.clone(), self.done_tx.clone());
+                tokio::spawn(async move {
+                    let ok = request.await;
+                    let _ = done.send((key, ok));
+                });
+            }
+        }
+        self.queues
+            .retain(|_, q| !q.batches.is_empty() || q.in_flight || q.paused);
+    }
+
+    fn complete(&mut self, key: String, ok: bool) {
+        self.in_flight -= 1;
+        if let Some(queue) = self.queues.get_mut(&key) {
+            queue.in_flight = false;
+            if !ok && !key.is_empty() {
+                queue.paused = true;
+                for pending in queue.batches.drain(..).flat_map(|b| b.messages) {
+                    let e = PublishError::OrderingKeyPaused(key.clone());
+                    let _ = pending.result.send(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// Publishes a batch and resolves the results of its messages. Returns whether it succeeded.
+#[allow(clippy::result_large_err)]
+async fn send<C: PublishClient>(
+    mut client: C,
+    topic: String,
+    batch: Vec<Pending>,
+    retry: Arc<RetrySettings>,
+) -> bool {
+    let mut results = Vec::with_capacity(batch.len());
+    let messages = batch
+        .into_iter()
+        .map(|p| {
+            results.push((p.result, p._permits));
+            p.message
+        })
+        .collect::<Vec<_>>();
+    let n = messages.len();
+    let response = publish(&mut client, PublishRequest { topic, messages }, &retry)
+        .await
+        .and_then(|r| {
+            if r.message_ids.len() == n {
+                Ok(r.message_ids)
+            } else {
+                Err(tonic::Status::internal(format!(
+                    "expected {} message ids, got {}",
+                    n,
+                    r.message_ids.len()
+                )))
+            }
+        });
+    match response {
+        Ok(ids) => {
+            for ((result, _), id) in results.into_iter().zip(ids) {
+                let _ = result.send(Ok(id));
+            }
+            true
+        }
+        Err(status) => {
+            let e = PublishError::Status(status);
+            for (result, _) in results {
+                let _ = result.send(Err(e.clone()));
+            }
+            false
+        }
+    }
+}
+
+async fn publish<C: PublishClient>(
+    client: &mut C,
+    request: PublishRequest,
+    retry: &RetrySettings,
+) -> Result<PublishResponse, tonic::Status> {
+    let deadline = Instant::now() + retry.total_timeout;
+    let mut backoff = retry.initial_backoff;
+    loop {
+        let status =
+            match time::timeout(retry.attempt_timeout, client.publish(request.clone())).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(status)) => status,
+                Err(_) => tonic::Status::deadline_exceeded("publish attempt timed out"),
+            };
+        if !retry.retryable_codes.contains(&status.code()) || Instant::now() + backoff > deadline {
+            return Err(status);
+        }
+        time::sleep(backoff).await;
+        backoff = backoff
+            .mul_f64(retry.backoff_multiplier)
+            .min(retry.max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records the requests, failing them with the queued statuses.
+    #[derive(Clone, Default)]
+    struct Fake {
+        requests: Arc<Mutex<Vec<PublishRequest>>>,
+        failures: Arc<Mutex<VecDeque<tonic::Status>>>,
+        // The requests being sent, and the most at once.
+        in_flight: Arc<Mutex<(usize, usize)>>,
+        delay: Duration,
+    }
+
+    impl PublishClient for Fake {
+        fn publish(&mut self, request: PublishRequest) -> PublishFuture<'_> {
+            Box::pin(async move {
+                {
+                    let mut in_flight = self.in_flight.lock().unwrap();
+                    in_flight.0 += 1;
+                    in_flight.1 = in_flight.1.max(in_flight.0);
+                }
+                time::sleep(self.delay).await;
+                self.in_flight.lock().unwrap().0 -= 1;
+                if let Some(status) = self.failures.lock().unwrap().pop_front() {
+                    return Err(status);
+                }
+                let mut requests = self.requests.lock().unwrap();
+                let message_ids = request
+                    .messages
+                    .iter()
+                    .map(|m| String::from_utf8_lossy(&m.data).into_owned())
+                    .collect();
+                requests.push(request);
+                Ok(PublishResponse { message_ids })
+            })
+        }
+    }
+
+    impl Fake {
+        fn batches(&self) -> Vec<Vec<String>> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|r| {
+                    r.messages
+                        .iter()
+                        .map(|m| String::from_utf8_lossy(&m.data).into_owned())
+                        .collect()
+                })
+                .collect()
+        }
+    }
+
+    fn message(data: &str, ordering_key: &str) -> PubsubMessage {
+        PubsubMessage {
+            data: data.as_bytes().to_vec(),
+            ordering_key: ordering_key.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn settings(max_messages: usize, max_delay: Duration) -> PublisherSettings {
+        PublisherSettings {
+            batch: BatchSettings {
+                max_messages,
+                max_delay,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_batches() {
+        let fake = Fake::default();
+        let publisher =
+            Publisher::with_settings(fake.clone(), "t", settings(3, Duration::from_secs(1)));
+        let mut results = Vec::new();
+        for i in 0..7 {
+            results.push(publisher.publish(message(&i.to_string(), "")).await);
+        }
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result.await.unwrap(), i.to_string());
+        }
+        assert_eq!(
+            fake.batches(),
+            vec![vec!["0", "1", "2"], vec!["3", "4", "5"], vec!["6"]]
+        );
+
+        // A lone message is sent after the delay.
+        let start = Instant::now();
+        assert_eq!(
+            publisher.publish(message("7", "")).await.await.unwrap(),
+            "7"
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        // Flushing does not wait.
+        let result = publisher.publish(message("8", "")).await;
+        publisher.flush().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(result.await.unwrap(), "8");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_bytes() {
+        let fake = Fake::default();
+        let mut settings = settings(100, Duration::from_secs(1));
+        settings.batch.max_bytes = 10;
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings);
+        // The topic takes 3 bytes of the request, and each message 6.
+        for data in &["aa", "bb", "cc"] {
+            drop(publisher.publish(message(data, "")).await);
+        }
+        publisher.shutdown().await;
+        assert_eq!(fake.batches(), vec![vec!["aa"], vec!["bb"], vec!["cc"]]);
+
+        let publisher = Publisher::new(fake, "t");
+        let large = PubsubMessage {
+            data: vec![0; MAX_PUBLISH_BYTES],
+            ..Default::default()
+        };
+        assert!(matches!(
+            publisher.publish(large).await.await,
+            Err(PublishError::TooLarge(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_ordering_keys() {
+        let fake = Fake {
+            delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings(1, Duration::ZERO));
+        let mut results = Vec::new();
+        for i in 0..5 {
+            results.push(publisher.publish(message(&i.to_string(), "k")).await);
+        }
+        for result in results {
+            result.await.unwrap();
+        }
+        assert_eq!(fake.in_flight.lock().unwrap().1, 1);
+        assert_eq!(fake.batches().concat(), vec!["0", "1", "2", "3", "4"]);
+
+        // A failure pauses the key, not the others.
+        fake.failures
+            .lock()
+            .unwrap()
+            .push_back(tonic::Status::invalid_argument("bad"));
+        let failed = publisher.publish(message("5", "k")).await;
+        let queued = publisher.publish(message("6", "k")).await;
+        match failed.await {
+            Err(PublishError::Status(s)) => assert_eq!(s.code(), tonic::Code::InvalidArgument),
+            r => panic!("{:?}", r),
+        }
+        assert!(matches!(queued.await, Err(PublishError::OrderingKeyPaused(k)) if k == "k"));
+        assert!(matches!(
+            publisher.publish(message("7", "k")).await.await,
+            Err(PublishError::OrderingKeyPaused(_))
+        ));
+        assert_eq!(
+            publisher.publish(message("8", "l")).await.await.unwrap(),
+            "8"
+        );
+
+        publisher.resume_publish("k");
+        assert_eq!(
+            publisher.publish(message("9", "k")).await.await.unwrap(),
+            "9"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_retry() {
+        let fake = Fake::default();
+        fake.failures.lock().unwrap().extend(vec![
+            tonic::Status::unavailable("retry"),
+            tonic::Status::unavailable("retry"),
+        ]);
+        let publisher = Publisher::new(fake.clone(), "t");
+        let start = Instant::now();
+        assert_eq!(
+            publisher.publish(message("0", "")).await.await.unwrap(),
+            "0"
+        );
+        // The delay, then backoffs of 100ms and 400ms.
+        assert_eq!(start.elapsed(), Duration::from_millis(510));
+
+        let mut settings = PublisherSettings::default();
+        settings.retry.total_timeout = Duration::from_millis(300);
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings);
+        fake.failures
+            .lock()
+            .unwrap()
+            .extend((0..3).map(|_| tonic::Status::unavailable("retry")));
+        match publisher.publish(message("1", "")).await.await {
+            Err(PublishError::Status(s)) => assert_eq!(s.code(), tonic::Code::Unavailable),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_flow_control() {
+        let fake = Fake::default();
+        let mut settings = settings(100, Duration::from_secs(1));
+        settings.flow_control = FlowControlSettings {
+            max_outstanding_messages: 2,
+            max_outstanding_bytes: 100,
+            limit_exceeded_behavior: LimitExceededBehavior::Error,
+        };
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings.clone());
+        let first = publisher.publish(message("0", "")).await;
+        let second = publisher.publish(message("1", "")).await;
+        assert!(matches!(
+            publisher.publish(message("2", "")).await.await,
+            Err(PublishError::FlowControl)
+        ));
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_eq!(
+            publisher.publish(message("3", "")).await.await.unwrap(),
+            "3"
+        );
+        assert!(matches!(
+            publisher.publish(message(&"x".repeat(100), "")).await.await,
+            Err(PublishError::FlowControl)
+        ));
+
+        // Blocked messages are sent once the outstanding ones are published.
+        settings.flow_control.limit_exceeded_behavior = LimitExceededBehavior::Block;
+        let publisher = Publisher::with_settings(fake.clone(), "t", settings);
+        let start = Instant::now();
+        let mut results = Vec::new();
+        for i in 4..7 {
+            results.push(publisher.publish(message(&i.to_string(), "")).await);
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        for result in results {
+            result.await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,775 @@
+use std::{collections::HashMap, convert::TryFrom, error, fmt};
+#[cfg(feature = "ring")]
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use prost_types::Timestamp;
+use serde_json::{Map, Value};
+
+use super::PubsubMessage;
+use crate::{base64, wkt::TimestampExt};
+
+/// An error returned when a push request is invalid or not authorized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushError {
+    /// The body is not a push envelope.
+    Envelope(String),
+    /// The request has no bearer token in its `Authorization` header.
+    MissingToken,
+    /// The token is not a JWT or uses another algorithm than `RS256`.
+    Token(String),
+    /// No key of the JWKS has the key ID of the token.
+    UnknownKey(String),
+    /// The signature of the token is invalid.
+    Signature,
+    /// A claim of the token is invalid, e.g. it expired or has another audience.
+    Claim(String),
+    /// The JWKS has no RSA signing key or is invalid.
+    Jwks(String),
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Envelope(message) => write!(f, "invalid push envelope: {}", message),
+            PushError::MissingToken => f.write_str("missing bearer token"),
+            PushError::Token(message) => write!(f, "invalid token: {}", message),
+            PushError::UnknownKey(kid) => write!(f, "unknown key ID: {:?}", kid),
+            PushError::Signature => f.write_str("invalid token signature"),
+            PushError::Claim(message) => write!(f, "invalid token claim: {}", message),
+            PushError::Jwks(message) => write!(f, "invalid JWKS: {}", message),
+        }
+    }
+}
+
+impl error::Error for PushError {}
+
+/// A message delivered by a push subscription, decoded from the JSON envelope of the request:
+///
+/// ```json
+/// {
+///   "message": {
+///     "data": "aGVsbG8=",
+///     "attributes": {"key": "value"},
+///     "messageId": "2070443601311540",
+///     "publishTime": "2021-02-26T19:13:55.749Z"
+///   },
+///   "subscription": "projects/myproject/subscriptions/mysubscription"
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushRequest {
+    pub message: PubsubMessage,
+    /// The name of the subscription.
+    pub subscription: String,
+    /// The delivery attempt of the message, when the subscription has a `DeadLetterPolicy`.
+    pub delivery_attempt: Option<i32>,
+}
+
+impl PushRequest {
+    /// Decodes the JSON body of a push request. The fields of the message are read in camel
+    /// case or snake case, e.g. `messageId` or `message_id`.
+    pub fn from_json(body: &[u8]) -> Result<Self, PushError> {
+        let invalid = |message: &str| PushError::Envelope(message.to_owned());
+        let envelope: Value = serde_json::from_slice(body)
+            .map_err(|e| PushError::Envelope(format!("invalid JSON: {}", e)))?;
+        let envelope = envelope
+            .as_object()
+            .ok_or_else(|| invalid("not an object"))?;
+        let message = envelope
+            .get("message")
+            .and_then(Value::as_object)
+            .ok_or_else(|| invalid("missing message"))?;
+        let subscription = envelope
+            .get("subscription")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("missing subscription"))?;
+        let delivery_attempt = match envelope.get("deliveryAttempt") {
+            None | Some(Value::Null) => None,
+            Some(attempt) => Some(
+                attempt
+                    .as_i64()
+                    .and_then(|a| i32::try_from(a).ok())
+                    .ok_or_else(|| invalid("invalid deliveryAttempt"))?,
+            ),
+        };
+
+        let data = match string(message, "data", "data")? {
+            Some(data) => base64::decode(data).ok_or_else(|| invalid("invalid base64 data"))?,
+            None => Vec::new(),
+        };
+        let mut attributes = HashMap::new();
+        match message.get("attributes") {
+            None | Some(Value::Null) => {}
+            Some(Value::Object(values)) => {
+                for (k, v) in values {
+                    let v = v.as_str().ok_or_else(|| invalid("invalid attributes"))?;
+                    attributes.insert(k.clone(), v.to_owned());
+                }
+            }
+            Some(_) => return Err(invalid("invalid attributes")),
+        }
+        let publish_time = match string(message, "publishTime", "publish_time")? {
+            Some(time) => {
+                Some(Timestamp::parse_rfc3339(time).map_err(|_| invalid("invalid publishTime"))?)
+            }
+            None => None,
+        };
+        Ok(PushRequest {
+            message: PubsubMessage {
+                data,
+                attributes,
+                message_id: string(message, "messageId", "message_id")?
+                    .unwrap_or_default()
+                    .to_owned(),
+                publish_time,
+                ordering_key: string(message, "orderingKey", "ordering_key")?
+                    .unwrap_or_default()
+                    .to_owned(),
+            },
+            subscription: subscription.to_owned(),
+            delivery_attempt,
+        })
+    }
+}
+
+// A string field of the message, by its JSON name or its proto name.
+fn string<'a>(
+    message: &'a Map<String, Value>,
+    json_name: &str,
+    name: &str,
+) -> Result<Option<&'a str>, PushError> {
+    match message.get(json_name).or_else(|| message.get(name)) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(PushError::Envelope(format!("invalid {}", json_name))),
+    }
+}
+
+/// The claims of a verified push token.
+#[cfg(feature = "ring")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushClaims {
+    pub issuer: String,
+    pub audience: String,
+    /// The unique ID of the service account of the push subscription.
+    pub subject: String,
+    /// The email of the service account of the push subscription.
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub issued_at: SystemTime,
+    pub expires_at: SystemTime,
+}
+
+/// A push request with a verified token.
+#[cfg(feature = "ring")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedPush {
+    pub request: PushRequest,
+    pub claims: PushClaims,
+}
+
+#[cfg(feature = "ring")]
+#[derive(Debug)]
+struct Key {
+    id: Option<String>,
+    n: Vec<u8>,
+    e: Vec<u8>,
+}
+
+/// Verifies the OIDC tokens of push requests, which push subscriptions with an
+/// `OidcToken` send as bearer tokens in the `Authorization` header.
+///
+/// The tokens are `RS256` JWTs signed by one of the keys of a JWKS, e.g. the keys at
+/// <https://www.googleapis.com/oauth2/v3/certs> for the tokens of Google; the verifier is
+/// rebuilt when the keys rotate. A token is valid when it is issued by `accounts.google.com`
+/// for the audience of the verifier, is not expired and, if set, has the verified email of the
+/// service account of the subscription.
+///
+/// With the `axum` feature, [`VerifiedPush`] is an extractor of the handlers of a router whose
+/// state provides a `PushVerifier`, rejecting invalid requests with `400 Bad Request` or
+/// `401 Unauthorized`:
+///
+/// ```no_run
+/// # use googapis::google::pubsub::v1::{PushVerifier, VerifiedPush};
+/// # #[cfg(feature = "axum")]
+/// # fn router(jwks: &str) -> axum::Router {
+/// let verifier = PushVerifier::new(jwks, "https://example.com/push")
+///     .unwrap()
+///     .with_email("push@myproject.iam.gserviceaccount.com");
+/// axum::Router::new()
+///     .route(
+///         "/push",
+///         axum::routing::post(|push: VerifiedPush| async move {
+///             println!("{:?}", push.request.message);
+///         }),
+///     )
+///     .with_state(verifier)
+/// # }
+/// ```
+#[cfg(feature = "ring")]
+#[derive(Debug, Clone)]
+pub struct PushVerifier {
+    keys: Arc<Vec<Key>>,
+    audience: String,
+    email: Option<String>,
+    leeway: Duration,
+}
+
+#[cfg(feature = "ring")]
+const ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+#[cfg(feature = "ring")]
+impl PushVerifier {
+    /// Creates a verifier of the tokens signed by the RSA keys of a JWKS for `audience`, the
+    /// audience of the `OidcToken` of the subscription or else its push endpoint.
+    pub fn new(jwks: &str, audience: impl Into<String>) -> Result<Self, PushError> {
+        let invalid = |message: &str| PushError::Jwks(message.to_owned());
+        let jwks: Value = serde_json::from_str(jwks)
+            .map_err(|e| PushError::Jwks(format!("invalid JSON: {}", e)))?;
+        let keys = jwks
+            .get("keys")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("missing keys"))?;
+        let mut rsa_keys = Vec::new();
+        for key in keys {
+            let field = |name| key.get(name).and_then(Value::as_str);
+            // Skips the keys of other types, algorithms or uses.
+            if field("kty") != Some("RSA")
+                || field("alg").is_some_and(|alg| alg != "RS256")
+                || field("use").is_some_and(|u| u != "sig")
+            {
+                continue;
+            }
+            let component = |name| {
+                field(name)
+                    .and_then(base64::decode)
+                    .ok_or_else(|| PushError::Jwks(format!("invalid RSA key {}", name)))
+            };
+            rsa_keys.push(Key {
+                id: field("kid").map(str::to_owned),
+                n: component("n")?,
+                e: component("e")?,
+            });
+        }
+        if rsa_keys.is_empty() {
+            return Err(invalid("no RS256 signing key"));
+        }
+        Ok(PushVerifier {
+            keys: Arc::new(rsa_keys),
+            audience: audience.into(),
+            email: None,
+            leeway: Duration::from_secs(60),
+        })
+    }
+
+    /// Requires the tokens to have the verified `email` of the service account of the
+    /// subscription.
+    pub fn with_email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Sets the tolerated clock skew when checking the expiration and issue times of the
+    /// tokens, 1 minute by default.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Verifies the value of the `Authorization` header of a push request and decodes its body.
+    pub fn verify(
+        &self,
+        authorization: Option<&str>,
+        body: &[u8],
+    ) -> Result<VerifiedPush, PushError> {
+        let token = authorization
+            .and_then(|a| {
+                let (scheme, token) = a.split_once(' ')?;
+                Some(token.trim()).filter(|_| scheme.eq_ignore_ascii_case("bearer"))
+            })
+            .ok_or(PushError::MissingToken)?;
+        let claims = self.verify_token(token, SystemTime::now())?;
+        Ok(VerifiedPush {
+            request: PushRequest::from_json(body)?,
+            claims,
+        })
+    }
+
+    /// Verifies a token at the time `now`.
+    pub fn verify_token(&self, token: &str, now: SystemTime) -> Result<PushClaims, PushError> {
+        let invalid = |message: &str| PushError::Token(message.to_owned());
+        let mut parts = token.split('.');
+        let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(h), Some(p), Some(s)) if parts.next().is_none() => (h, p, s),
+            _ => return Err(invalid("not a JWT")),
+        };
+        let json = |part: &str| -> Result<Map<String, Value>, PushError> {
+            base64::decode(part)
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .ok_or_else(|| invalid("not a JWT"))
+        };
+        let header = json(header)?;
+        match header.get("alg").and_then(Value::as_str) {
+            Some("RS256") => {}
+            alg => {
+                return Err(PushError::Token(format!(
+                    "unsupported algorithm {:?}",
+                    alg.unwrap_or_default()
+                )))
+            }
+        }
+        let signature = base64::decode(signature).ok_or_else(|| invalid("invalid signature"))?;
+        let kid = header.get("kid").and_then(Value::as_str);
+        let mut keys = self
+            .keys
+            .iter()
+            .filter(|k| kid.is_none() || k.id.as_deref() == kid)
+            .peekable();
+        if keys.peek().is_none() {
+            return Err(PushError::UnknownKey(kid.unwrap_or_default().to_owned()));
+        }
+        let (message, _) = token.rsplit_once('.').unwrap_or_default();
+        if !keys.any(|key| {
+            ring::signature::RsaPublicKeyComponents {
+                n: &key.n,
+                e: &key.e,
+            }
+            .verify(
+                &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+                message.as_bytes(),
+                &signature,
+            )
+            .is_ok()
+        }) {
+            return Err(PushError::Signature);
+        }
+
+        let claims = json(payload)?;
+        let claim = |message: String| PushError::Claim(message);
+        let string = |name: &str| claims.get(name).and_then(Value::as_str);
+        let time = |name: &str| {
+            let t = claims
+                .get(name)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| claim(format!("missing {}", name)))?;
+            UNIX_EPOCH
+                .checked_add(Duration::from_secs(t))
+                .ok_or_else(|| claim(format!("{} out of range", name)))
+        };
+        let issuer = string("iss").unwrap_or_default();
+        if !ISSUERS.contains(&issuer) {
+            return Err(claim(format!("unexpected issuer {:?}", issuer)));
+        }
+        let audience = match claims.get("aud") {
+            Some(Value::String(aud)) if *aud == self.audience => aud,
+            Some(Value::Array(auds)) if auds.iter().any(|a| *a == *self.audience) => &self.audience,
+            aud => {
+                return Err(claim(format!(
+                    "unexpected audience {}",
+                    aud.unwrap_or(&Value::Null)
+                )))
+            }
+        };
+        let expires_at = time("exp")?
+            .checked_add(self.leeway)
+            .ok_or_else(|| claim("exp out of range".to_owned()))?;
+        if expires_at <= now {
+            return Err(claim("expired token".to_owned()));
+        }
+        let issued_at = time("iat")?;
+        let latest = now
+            .checked_add(self.leeway)
+            .ok_or_else(|| claim("leeway out of range".to_owned()))?;
+        if issued_at > latest {
+            return Err(claim("token issued in the future".to_owned()));
+        }
+        let email = string("email");
+        // `email_verified` is a boolean in Google tokens, and a string in some others.
+        let email_verified = matches!(claims.get("email_verified"), Some(Value::Bool(true)))
+            || string("email_verified") == Some("true");
+        if let Some(expected) = &self.email {
+            if email != Some(expected) || !email_verified {
+                return Err(claim(format!(
+                    "unexpected email {:?}",
+                    email.unwrap_or_default()
+                )));
+            }
+        }
+        Ok(PushClaims {
+            issuer: issuer.to_owned(),
+            audience: audience.clone(),
+            subject: string("sub").unwrap_or_default().to_owned(),
+            email: email.map(str::to_owned),
+            email_verified,
+            issued_at,
+            expires_at,
+        })
+    }
+}
+
+#[cfg(feature = "axum")]
+impl axum::response::IntoResponse for PushError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            PushError::Envelope(_) => axum::http::StatusCode::BAD_REQUEST,
+            PushError::Jwks(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            _ => axum::http::StatusCode::UNAUTHORIZED,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[cfg(all(feature = "axum", feature = "ring"))]
+#[axum::async_trait]
+impl<S, B> axum::extract::FromRequest<S, B> for VerifiedPush
+where
+    PushVerifier: axum::extract::FromRef<S>,
+    B: axum::body::HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<axum::BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: axum::http::Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        use axum::{extract::FromRef, response::IntoResponse};
+
+        let verifier = PushVerifier::from_ref(state);
+        let authorization = req
+            .headers()
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        let body = axum::body::Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        verifier
+            .verify(authorization.as_deref(), &body)
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) const ENVELOPE: &str = r#"{
+        "message": {
+            "attributes": {"key": "value"},
+            "data": "SGVsbG8gQ2xvdWQgUHViL1N1YiEgSGVyZSBpcyBteSBtZXNzYWdlIQ==",
+            "messageId": "2070443601311540",
+            "message_id": "2070443601311540",
+            "publishTime": "2021-02-26T19:13:55.749Z",
+            "publish_time": "2021-02-26T19:13:55.749Z"
+        },
+        "subscription": "projects/myproject/subscriptions/mysubscription",
+        "deliveryAttempt": 2
+    }"#;
+
+    #[test]
+    fn test_from_json() {
+        let request = PushRequest::from_json(ENVELOPE.as_bytes()).unwrap();
+        assert_eq!(
+            request.message.data,
+            b"Hello Cloud Pub/Sub! Here is my message!"
+        );
+        assert_eq!(request.message.attributes["key"], "value");
+        assert_eq!(request.message.message_id, "2070443601311540");
+        assert_eq!(
+            request.message.publish_time,
+            Some(Timestamp {
+                seconds: 1_614_366_835,
+                nanos: 749_000_000
+            })
+        );
+        assert_eq!(
+            request.subscription,
+            "projects/myproject/subscriptions/mysubscription"
+        );
+        assert_eq!(request.delivery_attempt, Some(2));
+
+        let minimal =
+            r#"{"message": {"message_id": "1", "ordering_key": "k"}, "subscription": "s"}"#;
+        let request = PushRequest::from_json(minimal.as_bytes()).unwrap();
+        assert_eq!(request.message.data, b"");
+        assert_eq!(request.message.message_id, "1");
+        assert_eq!(request.message.ordering_key, "k");
+        assert_eq!(request.message.publish_time, None);
+        assert_eq!(request.delivery_attempt, None);
+
+        let invalid = [
+            ("\"data\": \"SGVs", "\"data\": \"S*Vs"),
+            (
+                "55.749Z\",\n            \"publish_time",
+                "55.749\",\n            \"publish_time",
+            ),
+            ("2021-02-26T19", "2021-02-30T19"),
+            ("55.749Z", "60.749Z"),
+            ("\"subscription\"", "\"sub\""),
+            ("{\"key\": \"value\"}", "{\"key\": 1}"),
+            ("\"deliveryAttempt\": 2", "\"deliveryAttempt\": \"2\""),
+        ];
+        for (from, to) in &invalid {
+            let body = ENVELOPE.replace(from, to);
+            assert_ne!(body, ENVELOPE);
+            assert!(
+                matches!(
+                    PushRequest::from_json(body.as_bytes()),
+                    Err(PushError::Envelope(_))
+                ),
+                "{}",
+                to
+            );
+        }
+    }
+}
+
+#[cfg(all(test, feature = "ring"))]
+mod token_tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{RsaKeyPair, RSA_PKCS1_SHA256},
+    };
+    use serde_json::json;
+
+    use super::{tests::ENVELOPE, *};
+
+    // A 2048-bit test key, as PKCS #8.
+    const KEY: &str = concat!(
+        "MIIEvAIBADANBgkqhkiG9w0BAQEFAASCBKYwggSiAgEAAoIBAQCr7CXiXDkCO8YK",
+        "IXAK3HhaBH8e/vTlcmaYcGOvksGelN/IAkVADyLXArZsAS0IyRe6bL9/ZnG0CZuT",
+        "G3q2VAonZA5zD7dGYIM+MxQOu4TXoYq5GZ/6qkeKYHqjjJ1PmZU/xr+0huQ7w0Qq",
+        "U7sUgpOxfINGv0EOjMuPIQFOc7WrE1zjgKccEwTP/HohUs3eMpFz9IE363Ju0S1q",
+        "Jbh58cfOnLxlTVU8QrVp7Mo8kZNHXFKKrgARwUdQpbixkDc0lXVnbbhxibah4n/8",
+        "x38HJmPrWs9nyoVQo5H4DV7rTNn/bCrdZxhCoam3k9eZt/qKYhEhxRj7LsCCTycv",
+        "YophQr/9AgMBAAECggEAGe5G3TMAGEGlbQQTxFec7CWPJOuF9ZDr6jnSwfnBLBGU",
+        "BTSudusxhpDurIYANw8u/dvcMF47xSYefzGJsIl8Hz/csWchQ24C2Eqs+kyMogId",
+        "YdjRw+WTX8rjLzA3eU5BcCVNPJs3PnpabttCiuU9BewGrZDi7PZjLOXorrQOJndy",
+        "2ejVud6cXEh4culDu50t/qmtF8H9M5Iaynj7uKd35aXGeJcuoG3Bhp3sFPcqDzbM",
+        "kkFqD9VMXfaH/EVO9XymM9o1rbyyJMEpY4FDbYdL+Oh2E8Wrhc1M9Viim6nS+MfW",
+        "TvPRaGhNyvk6csQywWIyiZzOHHU7fO9GH9TbPOHrwwKBgQDjv6oCCGaPc8hAc9zr",
+        "fLDDyFup43tVgq/rOh4o4rzOgE4+7RXTrM+qYvkFDBzSgNUc6AS27nhOq+yc5H1b",
+        "2je4eFeeu1Z3OIqIxW8D/ITTHI2iD/EzsqvFEX+lpD7xSvLiPhle/o0iloPJmjbA",
+        "FoEQyO0sA6Py90HtS8Ie/LGVEwKBgQDBP623voQJykjdUZ2LM6x3zNDVztBRQ/o/",
+        "E+w96aCnNsp0EVPjp+D5RvYMaZOyTTCZLIfySBcQf2OZIvLxuctMzq+A8HLZKYFQ",
+        "GD8bPH4OB3xhFg59K4czcf3iz4XNL3PJYGo/+5yClvs0TCNl+y+DlBXiwZiJ/M/G",
+        "0ySUst3IrwKBgAlIM3aXvw5TqrKtxjA2ycGA9fNrmQg4JlFQ8g+6hfVol0fLQ9t3",
+        "rJMGK54AC2Cs7YKbFi4ac/7I1QDj8xP+Rc0X4oJOokyDmmnOmiKwdP1hqYgUXEE+",
+        "I/AJMYmfsw0Ef/emjkQn3bquJbretSKJCEOV3MyxbUomt6IjN0sqA1bPAoGAFArT",
+        "rIPg09cb+YA2c7J3R7GBUztw1YzUTmh1jvJFDDKdiq4lYi4OIixe5miovcxNpwU8",
+        "UD9zzO05Wi+KvNFFKYEiKKBHeQhZ9gGdwehjNndGShdsh0TVb9t5tmVJfj9WpeMP",
+        "AfXJhK29/e2Lmk8T3XSlVHA5rs3+iqNpXtayM9cCgYAFlTRt6orrWRrGbwePClJK",
+        "FTMGrBn+ZXaYkBrokWlKguVseMBL8VQlRIpGdOz/3EUMKogZuQKxj0aNEwyxzSSy",
+        "gNTGCieJcBySZ8FW+aAcmeRQ4z9GZy7yKePG1Fw+RhOOkQcJWmUQp/vPqMQMRtJH",
+        "mnzOgbHTS14P+FQ/RioByw==",
+    );
+    // The modulus of `KEY`.
+    const N: &str = concat!(
+        "q-wl4lw5AjvGCiFwCtx4WgR_Hv705XJmmHBjr5LBnpTfyAJFQA8i1wK2bAEtCMkX",
+        "umy_f2ZxtAmbkxt6tlQKJ2QOcw-3RmCDPjMUDruE16GKuRmf-qpHimB6o4ydT5mV",
+        "P8a_tIbkO8NEKlO7FIKTsXyDRr9BDozLjyEBTnO1qxNc44CnHBMEz_x6IVLN3jKR",
+        "c_SBN-tybtEtaiW4efHHzpy8ZU1VPEK1aezKPJGTR1xSiq4AEcFHUKW4sZA3NJV1",
+        "Z224cYm2oeJ__Md_ByZj61rPZ8qFUKOR-A1e60zZ_2wq3WcYQqGpt5PXmbf6imIR",
+        "IcUY-y7Agk8nL2KKYUK__Q",
+    );
+    const AUDIENCE: &str = "https://example.com/push";
+    const EMAIL: &str = "push@myproject.iam.gserviceaccount.com";
+
+    fn jwks() -> String {
+        json!({"keys": [
+            {"kty": "EC", "kid": "ec", "crv": "P-256", "x": "AA", "y": "AA"},
+            {"kty": "RSA", "kid": "k1", "alg": "RS256", "use": "sig", "n": N, "e": "AQAB"},
+        ]})
+        .to_string()
+    }
+
+    fn sign(header: &Value, claims: &Value) -> String {
+        let key = RsaKeyPair::from_pkcs8(&base64::decode(KEY).unwrap()).unwrap();
+        let message = format!(
+            "{}.{}",
+            base64::encode_url_safe(header.to_string().as_bytes()),
+            base64::encode_url_safe(claims.to_string().as_bytes())
+        );
+        let mut signature = vec![0; key.public().modulus_len()];
+        key.sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            message.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+        format!("{}.{}", message, base64::encode_url_safe(&signature))
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "iss": "https://accounts.google.com",
+            "aud": AUDIENCE,
+            "sub": "1234567890",
+            "email": EMAIL,
+            "email_verified": true,
+            "iat": now() - 10,
+            "exp": now() + 3600,
+        })
+    }
+
+    fn header() -> Value {
+        json!({"alg": "RS256", "kid": "k1", "typ": "JWT"})
+    }
+
+    #[test]
+    fn test_verify() {
+        let verifier = PushVerifier::new(&jwks(), AUDIENCE)
+            .unwrap()
+            .with_email(EMAIL);
+        let token = sign(&header(), &claims());
+        let push = verifier
+            .verify(Some(&format!("Bearer {}", token)), ENVELOPE.as_bytes())
+            .unwrap();
+        assert_eq!(
+            push.request,
+            PushRequest::from_json(ENVELOPE.as_bytes()).unwrap()
+        );
+        assert_eq!(push.claims.subject, "1234567890");
+        assert_eq!(push.claims.email.as_deref(), Some(EMAIL));
+        assert!(push.claims.email_verified);
+        assert_eq!(push.claims.audience, AUDIENCE);
+
+        assert_eq!(
+            verifier.verify(None, ENVELOPE.as_bytes()),
+            Err(PushError::MissingToken)
+        );
+        assert_eq!(
+            verifier.verify(Some(&format!("Basic {}", token)), ENVELOPE.as_bytes()),
+            Err(PushError::MissingToken)
+        );
+        assert!(matches!(
+            verifier.verify(Some(&format!("bearer {}", token)), b"{}"),
+            Err(PushError::Envelope(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let verifier = PushVerifier::new(&jwks(), AUDIENCE)
+            .unwrap()
+            .with_email(EMAIL);
+        let verify = |token: &str| verifier.verify_token(token, SystemTime::now());
+        assert!(verify(&sign(&header(), &claims())).is_ok());
+        // Without a key ID, every key is tried.
+        assert!(verify(&sign(&json!({"alg": "RS256"}), &claims())).is_ok());
+
+        let token = sign(&header(), &claims());
+        let (message, _) = token.rsplit_once('.').unwrap();
+        let other = sign(&header(), &json!({}));
+        let (_, signature) = other.rsplit_once('.').unwrap();
+        assert_eq!(
+            verify(&format!("{}.{}", message, signature)),
+            Err(PushError::Signature)
+        );
+        assert_eq!(
+            verify(&sign(&json!({"alg": "RS256", "kid": "k2"}), &claims())),
+            Err(PushError::UnknownKey("k2".to_owned()))
+        );
+        assert!(matches!(
+            verify(&sign(&json!({"alg": "none"}), &claims())),
+            Err(PushError::Token(_))
+        ));
+        assert!(matches!(verify("a.b"), Err(PushError::Token(_))));
+
+        let cases = [
+            ("iss", json!("https://example.com")),
+            ("aud", json!("https://example.com/other")),
+            ("exp", json!(now() - 120)),
+            ("iat", json!(now() + 120)),
+            // Times past the end of `SystemTime`, without and with the leeway.
+            ("exp", json!(u64::MAX)),
+            ("exp", json!(i64::MAX)),
+            ("iat", json!(u64::MAX)),
+            ("email", json!("other@myproject.iam.gserviceaccount.com")),
+            ("email_verified", json!(false)),
+        ];
+        for (name, value) in &cases {
+            let mut claims = claims();
+            claims[*name] = value.clone();
+            assert!(
+                matches!(verify(&sign(&header(), &claims)), Err(PushError::Claim(_))),
+                "{}",
+                name
+            );
+        }
+        let mut claims = claims();
+        claims["aud"] = json!(["https://example.com/other", AUDIENCE]);
+        claims["exp"] = json!(now() - 30);
+        assert!(verify(&sign(&header(), &claims)).is_ok());
+
+        let verifier = PushVerifier::new(&jwks(), AUDIENCE)
+            .unwrap()
+            .with_leeway(Duration::MAX);
+        assert!(matches!(
+            verifier.verify_token(&sign(&header(), &claims), SystemTime::now()),
+            Err(PushError::Claim(_))
+        ));
+
+        for invalid in &[
+            "not json",
+            r#"{"keys": []}"#,
+            r#"{"keys": [{"kty": "RSA", "n": "*", "e": "AQAB"}]}"#,
+        ] {
+            assert!(matches!(
+                PushVerifier::new(invalid, AUDIENCE),
+                Err(PushError::Jwks(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn test_extractor() {
+        use axum::{extract::FromRequest, response::IntoResponse};
+
+        let verifier = PushVerifier::new(&jwks(), AUDIENCE).unwrap();
+        let request = |authorization: Option<String>, body: &str| {
+            let mut request = axum::http::Request::post("/push");
+            if let Some(authorization) = authorization {
+                request = request.header(axum::http::header::AUTHORIZATION, authorization);
+            }
+            request
+                .body(axum::body::Body::from(body.to_owned()))
+                .unwrap()
+        };
+
+        let token = sign(&header(), &claims());
+        let push = VerifiedPush::from_request(
+            request(Some(format!("Bearer {}", token)), ENVELOPE),
+            &verifier,
+        )
+        .await
+        .unwrap();
+        assert_eq!(push.request.message.message_id, "2070443601311540");
+
+        let rejection = VerifiedPush::from_request(request(None, ENVELOPE), &verifier)
+            .await
+            .unwrap_err();
+        assert_eq!(rejection.status(), axum::http::StatusCode::UNAUTHORIZED);
+        let rejection =
+            VerifiedPush::from_request(request(Some(format!("Bearer {}", token)), "{}"), &verifier)
+                .await
+                .unwrap_err();
+        assert_eq!(rejection.status(), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            PushError::Jwks(String::new()).into_response().status(),
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -0,0 +1,909 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::{poll_fn, Future},
+    pin::Pin,
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use prost::Message;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{self, Instant},
+};
+use tonic::codegen::futures_core::Stream;
+
+use super::{
+    subscriber_client::SubscriberClient, PubsubMessage, ReceivedMessage, StreamingPullRequest,
+    StreamingPullResponse,
+};
+
+/// The stream of responses of a `StreamingPull` call.
+pub type StreamingPullStream =
+    Pin<Box<dyn Stream<Item = Result<StreamingPullResponse, tonic::Status>> + Send>>;
+
+/// The future returned by [`SubscribeClient::streaming_pull`].
+pub type StreamingPullFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<StreamingPullStream, tonic::Status>> + Send + 'a>>;
+
+/// A client of the `StreamingPull` method of the `google.pubsub.v1.Subscriber` service,
+/// implemented by `SubscriberClient`. The [`Subscriber`] opens a new stream with it after
+/// retryable errors.
+pub trait SubscribeClient: Send + 'static {
+    fn streaming_pull(&mut self, requests: StreamingPullRequests) -> StreamingPullFuture<'_>;
+}
+
+impl<T> SubscribeClient for SubscriberClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn streaming_pull(&mut self, requests: StreamingPullRequests) -> StreamingPullFuture<'_> {
+        Box::pin(async move {
+            let stream = SubscriberClient::streaming_pull(self, requests).await?;
+            Ok(Box::pin(stream.into_inner()) as StreamingPullStream)
+        })
+    }
+}
+
+/// The requests of a `StreamingPull` call: the initial request, then acknowledgements and
+/// deadline modifications.
+#[derive(Debug)]
+pub struct StreamingPullRequests {
+    rx: mpsc::UnboundedReceiver<StreamingPullRequest>,
+}
+
+impl Stream for StreamingPullRequests {
+    type Item = StreamingPullRequest;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+// The codes of the `StreamingPull` method configuration of
+// `google/pubsub/v1/pubsub_grpc_service_config.json`.
+const RETRYABLE_CODES: [tonic::Code; 5] = [
+    tonic::Code::DeadlineExceeded,
+    tonic::Code::ResourceExhausted,
+    tonic::Code::Aborted,
+    tonic::Code::Internal,
+    tonic::Code::Unavailable,
+];
+// The most ack ids of a request.
+const MAX_ACK_IDS: usize = 2500;
+// The longest ack deadline accepted by the service.
+const MAX_ACK_DEADLINE_SECONDS: usize = 600;
+
+#[derive(Debug, Clone)]
+pub struct SubscriberSettings {
+    /// The most messages returned by [`Subscriber::next`] and not yet acked or nacked. It is
+    /// also the `max_outstanding_messages` of the stream.
+    pub max_outstanding_messages: usize,
+    /// The most bytes of messages returned by [`Subscriber::next`] and not yet acked or
+    /// nacked. It is also the `max_outstanding_bytes` of the stream.
+    pub max_outstanding_bytes: usize,
+    /// The bounds of the ack deadline, which is the 99th percentile of the time taken to ack
+    /// or nack messages.
+    pub min_ack_deadline: Duration,
+    pub max_ack_deadline: Duration,
+    /// How long the leases of a message are extended at most. Past it, the messages not
+    /// returned by [`Subscriber::next`] yet are nacked, and the returned ones report it with
+    /// [`PulledMessage::lease_expired`].
+    pub max_extension: Duration,
+    /// How long acks and nacks wait to be sent together.
+    pub ack_delay: Duration,
+    /// The backoff between reconnections of the stream, doubled after each retryable error.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for SubscriberSettings {
+    fn default() -> Self {
+        SubscriberSettings {
+            max_outstanding_messages: 1000,
+            max_outstanding_bytes: 1_000_000_000,
+            min_ack_deadline: Duration::from_secs(10),
+            max_ack_deadline: Duration::from_secs(MAX_ACK_DEADLINE_SECONDS as u64),
+            max_extension: Duration::from_secs(3600),
+            ack_delay: Duration::from_millis(100),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Ack(String),
+    Nack(String),
+    Stop(Option<oneshot::Sender<()>>),
+}
+
+/// A message returned by [`Subscriber::next`]. Its lease is extended until it is acked or
+/// nacked; dropping it nacks it.
+#[derive(Debug)]
+pub struct PulledMessage {
+    message: PubsubMessage,
+    ack_id: String,
+    delivery_attempt: i32,
+    expired: Arc<AtomicBool>,
+    commands: mpsc::UnboundedSender<Command>,
+    done: bool,
+}
+
+impl PulledMessage {
+    pub fn message(&self) -> &PubsubMessage {
+        &self.message
+    }
+
+    pub fn ack_id(&self) -> &str {
+        &self.ack_id
+    }
+
+    /// The delivery attempt of the message, or 0 if the subscription has no dead letter policy.
+    pub fn delivery_attempt(&self) -> i32 {
+        self.delivery_attempt
+    }
+
+    /// Whether the lease of the message is no longer extended, after
+    /// [`SubscriberSettings::max_extension`]. The service may then deliver the message again,
+    /// to this or another subscriber, and an ack may come too late to prevent it.
+    pub fn lease_expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Acknowledges the message, which is not delivered again unless its lease expired.
+    pub fn ack(mut self) {
+        self.done = true;
+        let _ = self.commands.send(Command::Ack(self.ack_id.clone()));
+    }
+
+    /// Lets the message be delivered again, right away.
+    pub fn nack(mut self) {
+        self.done = true;
+        let _ = self.commands.send(Command::Nack(self.ack_id.clone()));
+    }
+}
+
+impl Drop for PulledMessage {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.commands.send(Command::Nack(self.ack_id.clone()));
+        }
+    }
+}
+
+/// Receives the messages of a subscription over a `StreamingPull` stream.
+///
+/// The leases of the messages are extended by the ack deadline until they are acked or nacked,
+/// and acks and nacks are sent in batches. The stream is opened again after retryable errors,
+/// with the same client id so that the leases are kept.
+///
+/// If the subscription has message ordering enabled, a message is only returned once the
+/// previous ones with the same ordering key are acked, and nacking a message nacks the
+/// following ones with the same key.
+///
+/// Subscriptions with exactly-once delivery are not supported: the `google.pubsub.v1` protos
+/// this crate is generated from predate it, so acks are sent without waiting for the service to
+/// confirm them and may be lost like with at-least-once delivery.
+///
+/// # Example
+/// ```ignore
+/// use googapis::google::pubsub::v1::{subscriber_client::SubscriberClient, Subscriber};
+///
+/// let mut subscriber = Subscriber::new(SubscriberClient::new(channel), "projects/p/subscriptions/s");
+/// while let Some(message) = subscriber.next().await {
+///     let message = message?;
+///     process(message.message()).await;
+///     message.ack();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Subscriber {
+    deliveries: mpsc::UnboundedReceiver<Result<PulledMessage, tonic::Status>>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Subscriber {
+    /// A subscriber with the default settings. It must be created in a Tokio runtime.
+    pub fn new<C: SubscribeClient>(client: C, subscription: impl Into<String>) -> Self {
+        Self::with_settings(client, subscription, SubscriberSettings::default())
+    }
+
+    /// A subscriber with `settings`. It must be created in a Tokio runtime.
+    pub fn with_settings<C: SubscribeClient>(
+        client: C,
+        subscription: impl Into<String>,
+        settings: SubscriberSettings,
+    ) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let (deliveries_tx, deliveries) = mpsc::unbounded_channel();
+        let lease = Leaser {
+            client,
+            subscription: subscription.into(),
+            client_id: client_id(),
+            settings,
+            requests: None,
+            leases: HashMap::new(),
+            held: VecDeque::new(),
+            outstanding: (0, 0),
+            ordering: false,
+            busy_keys: HashSet::new(),
+            receipts: Vec::new(),
+            acks: Vec::new(),
+            nacks: Vec::new(),
+            latencies: vec![0; MAX_ACK_DEADLINE_SECONDS + 1],
+            stopping: false,
+            deliveries: deliveries_tx,
+            commands: commands.clone(),
+        };
+        tokio::spawn(lease.run(rx));
+        Subscriber {
+            deliveries,
+            commands,
+        }
+    }
+
+    /// The next message, or the error which stopped the subscriber.
+    pub async fn next(&mut self) -> Option<Result<PulledMessage, tonic::Status>> {
+        self.deliveries.recv().await
+    }
+
+    /// Stops receiving messages, nacks the ones not returned yet, and waits for the returned
+    /// ones to be acked or nacked and for the acks and nacks to be sent.
+    pub async fn shutdown(mut self) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.commands.send(Command::Stop(Some(tx)));
+        self.deliveries.close();
+        while self.deliveries.try_recv().is_ok() {}
+        let _ = rx.await;
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Stop(None));
+    }
+}
+
+// A unique id per subscriber, see `StreamingPullRequest.client_id`.
+fn client_id() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!(
+        "{:x}-{:x}-{:x}",
+        process::id(),
+        nanos,
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[derive(Debug)]
+struct Lease {
+    size: usize,
+    ordering_key: String,
+    received: Instant,
+    // When it was returned by `Subscriber::next`.
+    delivered: Option<Instant>,
+    // Shared with the `PulledMessage`, set after `max_extension`.
+    expired: Arc<AtomicBool>,
+}
+
+// The task receiving the messages of a `Subscriber` and managing their leases.
+struct Leaser<C> {
+    client: C,
+    subscription: String,
+    client_id: String,
+    settings: SubscriberSettings,
+    requests: Option<mpsc::UnboundedSender<StreamingPullRequest>>,
+    leases: HashMap<String, Lease>,
+    // The messages received but not delivered yet.
+    held: VecDeque<ReceivedMessage>,
+    // The messages and bytes delivered but not acked or nacked yet.
+    outstanding: (usize, usize),
+    ordering: bool,
+    // The ordering keys of the outstanding messages.
+    busy_keys: HashSet<String>,
+    // The ack ids of the messages received since the last modack.
+    receipts: Vec<String>,
+    acks: Vec<String>,
+    nacks: Vec<String>,
+    // The number of messages acked or nacked after each number of seconds.
+    latencies: Vec<u64>,
+    stopping: bool,
+    deliveries: mpsc::UnboundedSender<Result<PulledMessage, tonic::Status>>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl<C: SubscribeClient> Leaser<C> {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        let mut stream: Option<StreamingPullStream> = None;
+        let mut reconnect = Some(Instant::now());
+        let mut backoff = self.settings.initial_backoff;
+        let ack_delay = self.settings.ack_delay;
+        let mut flush = time::interval_at(Instant::now() + ack_delay, ack_delay);
+        let mut extend_at = Instant::now() + self.extension_period();
+        let mut waiters = Vec::new();
+        loop {
+            let connect = reconnect
+                .filter(|_| !self.stopping || !self.acks.is_empty() || !self.nacks.is_empty());
+            tokio::select! {
+                Some(command) = commands.recv() => match command {
+                    Command::Ack(ack_id) => self.done(ack_id, true),
+                    Command::Nack(ack_id) => self.done(ack_id, false),
+                    Command::Stop(waiter) => {
+                        self.stop();
+                        waiters.extend(waiter);
+                    }
+                },
+                response = next(&mut stream), if stream.is_some() => match response {
+                    Some(Ok(response)) => {
+                        backoff = self.settings.initial_backoff;
+                        self.receive(response);
+                    }
+                    Some(Err(status)) if !RETRYABLE_CODES.contains(&status.code()) => {
+                        let _ = self.deliveries.send(Err(status));
+                        return;
+                    }
+                    // The service closes streams from time to time.
+                    _ => {
+                        stream = None;
+                        self.requests = None;
+                        reconnect = Some(Instant::now() + backoff);
+                        backoff = (backoff * 2).min(self.settings.max_backoff);
+                    }
+                },
+                _ = time::sleep_until(connect.unwrap_or_else(Instant::now)), if connect.is_some() => {
+                    reconnect = None;
+                    match self.connect().await {
+                        Ok(s) => stream = Some(s),
+                        Err(status) if !RETRYABLE_CODES.contains(&status.code()) => {
+                            let _ = self.deliveries.send(Err(status));
+                            return;
+                        }
+                        Err(_) => {
+                            reconnect = Some(Instant::now() + backoff);
+                            backoff = (backoff * 2).min(self.settings.max_backoff);
+                        }
+                    }
+                }
+                _ = flush.tick() => {
+                    if Instant::now() >= extend_at {
+                        self.extend();
+                        extend_at = Instant::now() + self.extension_period();
+                    }
+                    self.flush();
+                    if self.stopping
+                        && self.outstanding.0 == 0
+                        && self.acks.is_empty()
+                        && self.nacks.is_empty()
+                    {
+                        for waiter in waiters {
+                            let _ = waiter.send(());
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<StreamingPullStream, tonic::Status> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let _ = tx.send(StreamingPullRequest {
+            subscription: self.subscription.clone(),
+            stream_ack_deadline_seconds: self.ack_deadline().as_secs() as i32,
+            client_id: self.client_id.clone(),
+            max_outstanding_messages: self.settings.max_outstanding_messages as i64,
+            max_outstanding_bytes: self.settings.max_outstanding_bytes as i64,
+            ..Default::default()
+        });
+        let stream = self
+            .client
+            .streaming_pull(StreamingPullRequests { rx })
+            .await?;
+        self.requests = Some(tx);
+        Ok(stream)
+    }
+
+    fn receive(&mut self, response: StreamingPullResponse) {
+        if let Some(properties) = response.subscription_properties {
+            self.ordering = properties.message_ordering_enabled;
+        }
+        for m in response.received_messages {
+            if self.stopping {
+                self.nacks.push(m.ack_id);
+                continue;
+            }
+            let lease = Lease {
+                size: m.message.as_ref().map_or(0, |m| m.encoded_len()),
+                ordering_key: m
+                    .message
+                    .as_ref()
+                    .map(|m| m.ordering_key.clone())
+                    .unwrap_or_default(),
+                received: Instant::now(),
+                delivered: None,
+                expired: Arc::new(AtomicBool::new(false)),
+            };
+            self.leases.insert(m.ack_id.clone(), lease);
+            self.receipts.push(m.ack_id.clone());
+            self.held.push_back(m);
+        }
+        self.deliver();
+    }
+
+    // Delivers the held messages allowed by flow control and ordering.
+    fn deliver(&mut self) {
+        let mut i = 0;
+        while i < self.held.len() && !self.stopping {
+            let settings = &self.settings;
+            let (messages, bytes) = self.outstanding;
+            if messages >= settings.max_outstanding_messages
+                || messages > 0 && bytes >= settings.max_outstanding_bytes
+            {
+                return;
+            }
+            let lease = &self.leases[&self.held[i].ack_id];
+            let key = &lease.ordering_key;
+            if self.ordering && !key.is_empty() && !self.busy_keys.insert(key.clone()) {
+                i += 1;
+                continue;
+            }
+            let m = self.held.remove(i).unwrap();
+            let lease = self.leases.get_mut(&m.ack_id).unwrap();
+            lease.delivered = Some(Instant::now());
+            self.outstanding.0 += 1;
+            self.outstanding.1 += lease.size;
+            let _ = self.deliveries.send(Ok(PulledMessage {
+                message: m.message.unwrap_or_default(),
+                ack_id: m.ack_id,
+                delivery_attempt: m.delivery_attempt,
+                expired: lease.expired.clone(),
+                commands: self.commands.clone(),
+                done: false,
+            }));
+        }
+    }
+
+    fn done(&mut self, ack_id: String, ack: bool) {
+        let lease = match self.leases.remove(&ack_id) {
+            Some(lease) => lease,
+            None => return,
+        };
+        if let Some(delivered) = lease.delivered {
+            let seconds = delivered.elapsed().as_secs_f64().ceil() as usize;
+            self.latencies[seconds.min(MAX_ACK_DEADLINE_SECONDS)] += 1;
+            self.outstanding.0 -= 1;
+            self.outstanding.1 -= lease.size;
+        }
+        if ack {
+            self.acks.push(ack_id);
+        } else {
+            self.nacks.push(ack_id);
+            // The service delivers the following messages of the key again.
+            if self.ordering && !lease.ordering_key.is_empty() {
+                let leases = &self.leases;
+                let (nacked, held): (VecDeque<_>, _) = self
+                    .held
+                    .drain(..)
+                    .partition(|m| leases[&m.ack_id].ordering_key == lease.ordering_key);
+                self.held = held;
+                for m in nacked.into_iter() {
+                    self.leases.remove(&m.ack_id);
+                    self.nacks.push(m.ack_id);
+                }
+            }
+        }
+        self.busy_keys.remove(&lease.ordering_key);
+        self.deliver();
+    }
+
+    fn stop(&mut self) {
+        self.stopping = true;
+        for m in std::mem::take(&mut self.held) {
+            self.leases.remove(&m.ack_id);
+            self.nacks.push(m.ack_id);
+        }
+    }
+
+    // The 99th percentile of the ack latencies, within the bounds of the settings.
+    fn ack_deadline(&self) -> Duration {
+        let total = self.latencies.iter().sum::<u64>();
+        let mut count = 0;
+        let mut p99 = 0;
+        for (seconds, n) in self.latencies.iter().enumerate() {
+            count += n;
+            if count * 100 >= total * 99 {
+                p99 = seconds;
+                break;
+            }
+        }
+        Duration::from_secs(p99 as u64)
+            .max(self.settings.min_ack_deadline)
+            .min(self.settings.max_ack_deadline)
+    }
+
+    // Leases are extended a little before they expire.
+    fn extension_period(&self) -> Duration {
+        self.ack_deadline().mul_f64(0.8)
+    }
+
+    // Extends the leases received less than `max_extension` ago. The expired messages which
+    // were not delivered are nacked, the delivered ones are flagged.
+    fn extend(&mut self) {
+        let max_extension = self.settings.max_extension;
+        let mut expired = HashSet::new();
+        self.receipts.clear();
+        for (ack_id, lease) in self.leases.iter() {
+            if lease.received.elapsed() < max_extension {
+                self.receipts.push(ack_id.clone());
+            } else if lease.delivered.is_some() {
+                lease.expired.store(true, Ordering::Relaxed);
+            } else {
+                expired.insert(ack_id.clone());
+            }
+        }
+        if expired.is_empty() {
+            return;
+        }
+        self.held.retain(|m| !expired.contains(&m.ack_id));
+        for ack_id in expired {
+            self.leases.remove(&ack_id);
+            self.nacks.push(ack_id);
+        }
+    }
+
+    // Sends the pending acks, nacks and modacks.
+    fn flush(&mut self) {
+        let requests = match &self.requests {
+            Some(requests) => requests,
+            None => return,
+        };
+        for ack_ids in self.acks.chunks(MAX_ACK_IDS) {
+            let _ = requests.send(StreamingPullRequest {
+                ack_ids: ack_ids.to_vec(),
+                ..Default::default()
+            });
+        }
+        let deadline = self.ack_deadline().as_secs() as i32;
+        let modacks = self.nacks.iter().map(|id| (id, 0));
+        // Acked and nacked messages are not leased again.
+        let receipts = self
+            .receipts
+            .iter()
+            .filter(|id| self.leases.contains_key(*id));
+        let modacks = modacks.chain(receipts.map(|id| (id, deadline)));
+        let modacks = modacks.collect::<Vec<_>>();
+        for modacks in modacks.chunks(MAX_ACK_IDS) {
+            let _ = requests.send(StreamingPullRequest {
+                modify_deadline_ack_ids: modacks.iter().map(|(id, _)| (*id).clone()).collect(),
+                modify_deadline_seconds: modacks.iter().map(|(_, s)| *s).collect(),
+                ..Default::default()
+            });
+        }
+        self.acks.clear();
+        self.nacks.clear();
+        self.receipts.clear();
+    }
+}
+
+async fn next(
+    stream: &mut Option<StreamingPullStream>,
+) -> Option<Result<StreamingPullResponse, tonic::Status>> {
+    match stream {
+        Some(stream) => poll_fn(|cx| stream.as_mut().poll_next(cx)).await,
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use super::super::streaming_pull_response::SubscriptionProperties;
+
+    type Responses = mpsc::UnboundedSender<Result<StreamingPullResponse, tonic::Status>>;
+
+    struct Responder {
+        rx: mpsc::UnboundedReceiver<Result<StreamingPullResponse, tonic::Status>>,
+    }
+
+    impl Stream for Responder {
+        type Item = Result<StreamingPullResponse, tonic::Status>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.rx.poll_recv(cx)
+        }
+    }
+
+    // Serves the streams queued by `stream`, recording their requests.
+    #[derive(Clone, Default)]
+    struct Fake {
+        streams: Arc<Mutex<VecDeque<Responder>>>,
+        requests: Arc<Mutex<Vec<Vec<StreamingPullRequest>>>>,
+    }
+
+    impl SubscribeClient for Fake {
+        fn streaming_pull(
+            &mut self,
+            mut requests: StreamingPullRequests,
+        ) -> StreamingPullFuture<'_> {
+            Box::pin(async move {
+                let responder = self
+                    .streams
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .ok_or_else(|| tonic::Status::unavailable("no stream"))?;
+                let log = self.requests.clone();
+                let i = {
+                    let mut log = log.lock().unwrap();
+                    log.push(Vec::new());
+                    log.len() - 1
+                };
+                tokio::spawn(async move {
+                    while let Some(r) = poll_fn(|cx| Pin::new(&mut requests).poll_next(cx)).await {
+                        log.lock().unwrap()[i].push(r);
+                    }
+                });
+                Ok(Box::pin(responder) as StreamingPullStream)
+            })
+        }
+    }
+
+    impl Fake {
+        fn stream(&self) -> Responses {
+            let (tx, rx) = mpsc::unbounded_channel();
+            self.streams.lock().unwrap().push_back(Responder { rx });
+            tx
+        }
+
+        fn requests(&self, stream: usize) -> Vec<StreamingPullRequest> {
+            self.requests.lock().unwrap()[stream].clone()
+        }
+
+        // The acks and modacks of a stream, in order.
+        fn acks(&self, stream: usize) -> Vec<(String, Option<i32>)> {
+            let mut acks = Vec::new();
+            for r in self.requests(stream) {
+                acks.extend(r.ack_ids.into_iter().map(|id| (id, None)));
+                acks.extend(
+                    r.modify_deadline_ack_ids
+                        .into_iter()
+                        .zip(r.modify_deadline_seconds.into_iter().map(Some)),
+                );
+            }
+            acks
+        }
+    }
+
+    fn response(messages: &[(&str, &str)], ordering: bool) -> StreamingPullResponse {
+        StreamingPullResponse {
+            received_messages: messages
+                .iter()
+                .map(|(ack_id, ordering_key)| ReceivedMessage {
+                    ack_id: ack_id.to_string(),
+                    message: Some(PubsubMessage {
+                        data: ack_id.as_bytes().to_vec(),
+                        ordering_key: ordering_key.to_string(),
+                        ..Default::default()
+                    }),
+                    delivery_attempt: 0,
+                })
+                .collect(),
+            subscription_properties: Some(SubscriptionProperties {
+                message_ordering_enabled: ordering,
+            }),
+        }
+    }
+
+    fn pair(id: &str, seconds: Option<i32>) -> (String, Option<i32>) {
+        (id.to_owned(), seconds)
+    }
+
+    async fn next_id(subscriber: &mut Subscriber) -> Option<PulledMessage> {
+        time::timeout(Duration::from_secs(1), subscriber.next())
+            .await
+            .ok()
+            .map(|m| m.unwrap().unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let mut subscriber = Subscriber::new(fake.clone(), "s");
+        responses
+            .send(Ok(response(&[("a", ""), ("b", ""), ("c", "")], false)))
+            .unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        assert_eq!(a.message().data, b"a");
+        let b = next_id(&mut subscriber).await.unwrap();
+        let c = next_id(&mut subscriber).await.unwrap();
+        a.ack();
+        b.nack();
+        drop(c);
+        time::sleep(Duration::from_millis(200)).await;
+
+        let initial = &fake.requests(0)[0];
+        assert_eq!(initial.subscription, "s");
+        assert_eq!(initial.stream_ack_deadline_seconds, 10);
+        assert_eq!(initial.max_outstanding_messages, 1000);
+        assert!(!initial.client_id.is_empty());
+        assert_eq!(
+            fake.acks(0),
+            vec![pair("a", None), pair("b", Some(0)), pair("c", Some(0))]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_flow_control() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let settings = SubscriberSettings {
+            max_outstanding_messages: 2,
+            ..Default::default()
+        };
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings);
+        responses
+            .send(Ok(response(&[("a", ""), ("b", ""), ("c", "")], false)))
+            .unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        let _b = next_id(&mut subscriber).await.unwrap();
+        assert!(next_id(&mut subscriber).await.is_none());
+        a.ack();
+        assert_eq!(next_id(&mut subscriber).await.unwrap().ack_id(), "c");
+        assert_eq!(fake.requests(0)[0].max_outstanding_messages, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_leases() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let settings = SubscriberSettings {
+            max_extension: Duration::from_secs(20),
+            ..Default::default()
+        };
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings);
+        responses.send(Ok(response(&[("a", "")], false))).unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        // Extended every 8 seconds, until 20 seconds after the receipt.
+        time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(fake.acks(0), vec![pair("a", Some(10)); 3]);
+        assert!(a.lease_expired());
+
+        // The deadline follows the time taken to ack the messages.
+        a.ack();
+        responses.send(Ok(response(&[("b", "")], false))).unwrap();
+        let b = next_id(&mut subscriber).await.unwrap();
+        time::sleep(Duration::from_secs(40)).await;
+        b.ack();
+        time::sleep(Duration::from_millis(200)).await;
+        responses.send(Ok(response(&[("c", "")], false))).unwrap();
+        let _c = next_id(&mut subscriber).await.unwrap();
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(fake.acks(0).last(), Some(&pair("c", Some(40))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_max_extension() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let settings = SubscriberSettings {
+            max_outstanding_messages: 1,
+            max_extension: Duration::from_secs(20),
+            ..Default::default()
+        };
+        let mut subscriber = Subscriber::with_settings(fake.clone(), "s", settings);
+        responses
+            .send(Ok(response(&[("a", ""), ("b", "")], false)))
+            .unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        assert!(!a.lease_expired());
+        time::sleep(Duration::from_secs(30)).await;
+        // The held message is nacked instead of being returned with an expired lease.
+        assert!(a.lease_expired());
+        assert!(fake.acks(0).contains(&pair("b", Some(0))));
+        a.ack();
+        assert!(next_id(&mut subscriber).await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_reconnect() {
+        let fake = Fake::default();
+        let first = fake.stream();
+        let second = fake.stream();
+        let mut subscriber = Subscriber::new(fake.clone(), "s");
+        first.send(Ok(response(&[("a", "")], false))).unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        first
+            .send(Err(tonic::Status::unavailable("going away")))
+            .unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+        a.ack();
+        second.send(Ok(response(&[("b", "")], false))).unwrap();
+        assert_eq!(next_id(&mut subscriber).await.unwrap().ack_id(), "b");
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(fake.requests(0)[0].client_id, fake.requests(1)[0].client_id);
+        assert!(fake.acks(1).contains(&pair("a", None)));
+
+        // Without stream to connect to, the reconnection fails and is retried.
+        drop(second);
+        time::sleep(Duration::from_secs(10)).await;
+        let third = fake.stream();
+        third
+            .send(Err(tonic::Status::permission_denied("denied")))
+            .unwrap();
+        let status = subscriber.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(subscriber.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_ordering() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let mut subscriber = Subscriber::new(fake.clone(), "s");
+        responses
+            .send(Ok(response(
+                &[("a1", "a"), ("a2", "a"), ("b1", "b"), ("a3", "a")],
+                true,
+            )))
+            .unwrap();
+        let a1 = next_id(&mut subscriber).await.unwrap();
+        let b1 = next_id(&mut subscriber).await.unwrap();
+        assert_eq!(b1.ack_id(), "b1");
+        assert!(next_id(&mut subscriber).await.is_none());
+        a1.ack();
+        let a2 = next_id(&mut subscriber).await.unwrap();
+        assert_eq!(a2.ack_id(), "a2");
+        a2.nack();
+        assert!(next_id(&mut subscriber).await.is_none());
+        time::sleep(Duration::from_millis(200)).await;
+        let acks = fake.acks(0);
+        assert_eq!(
+            acks[acks.len() - 3..],
+            [pair("a1", None), pair("a2", Some(0)), pair("a3", Some(0))]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscribe_shutdown() {
+        let fake = Fake::default();
+        let responses = fake.stream();
+        let mut subscriber = Subscriber::new(fake.clone(), "s");
+        responses
+            .send(Ok(response(&[("a", ""), ("b", "")], false)))
+            .unwrap();
+        let a = next_id(&mut subscriber).await.unwrap();
+        time::sleep(Duration::from_millis(200)).await;
+
+        let shutdown = tokio::spawn(subscriber.shutdown());
+        time::sleep(Duration::from_secs(1)).await;
+        assert!(!shutdown.is_finished());
+        // Messages received while stopping are nacked.
+        responses.send(Ok(response(&[("c", "")], false))).unwrap();
+        time::sleep(Duration::from_millis(200)).await;
+        a.ack();
+        shutdown.await.unwrap();
+        let acks = fake.acks(0);
+        assert!(acks.contains(&pair("a", None)));
+        assert!(acks.contains(&pair("b", Some(0))));
+        assert!(acks.contains(&pair("c", Some(0))));
+        // The stream is closed.
+        assert!(responses.is_closed());
+    }
+}
@@ -0,0 +1,678 @@
+use std::{error, fmt};
+
+use super::{avro::AvroSchema, protobuf::ProtoSchema, schema, Encoding, Schema};
+
+/// An error returned when a schema or a message is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// The schema type is unspecified or unknown.
+    Type(i32),
+    /// The encoding is unspecified or unknown.
+    Encoding(i32),
+    /// The schema definition is invalid.
+    Definition(String),
+    /// The message does not match the schema. The path locates the invalid value, e.g.
+    /// `items[2].name`, and is empty for the whole message.
+    Message { path: String, message: String },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Type(t) => write!(f, "invalid schema type: {}", t),
+            SchemaError::Encoding(e) => write!(f, "invalid encoding: {}", e),
+            SchemaError::Definition(message) => write!(f, "invalid schema definition: {}", message),
+            SchemaError::Message { path, message } if path.is_empty() => {
+                write!(f, "invalid message: {}", message)
+            }
+            SchemaError::Message { path, message } => {
+                write!(f, "invalid message at {}: {}", path, message)
+            }
+        }
+    }
+}
+
+impl error::Error for SchemaError {}
+
+/// A parsed [`Schema`] definition, which validates messages locally like the
+/// `ValidateMessage` method of the `SchemaService`.
+///
+/// Avro schemas are [Avro] JSON definitions; messages are validated against the [binary
+/// encoding] or the [JSON encoding]. Protocol buffer schemas are `.proto` definitions without
+/// imports, whose first top-level message type is the type of the messages; messages are
+/// validated against the wire format or the [JSON mapping] of proto3. Binary messages nested
+/// more than 100 levels deep are rejected.
+///
+/// [Avro]: https://avro.apache.org/docs/current/spec.html
+/// [binary encoding]: https://avro.apache.org/docs/current/spec.html#binary_encoding
+/// [JSON encoding]: https://avro.apache.org/docs/current/spec.html#json_encoding
+/// [JSON mapping]: https://developers.google.com/protocol-buffers/docs/proto3#json
+#[derive(Debug, Clone)]
+pub struct SchemaValidator {
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Avro(AvroSchema),
+    Protobuf(ProtoSchema),
+}
+
+impl SchemaValidator {
+    /// Parses the definition of a schema, failing like the `ValidateSchema` method.
+    pub fn new(schema: &Schema) -> Result<Self, SchemaError> {
+        let kind = match schema::Type::from_i32(schema.r#type) {
+            Some(schema::Type::Avro) => Kind::Avro(AvroSchema::parse(&schema.definition)?),
+            Some(schema::Type::ProtocolBuffer) => {
+                Kind::Protobuf(ProtoSchema::parse(&schema.definition)?)
+            }
+            _ => return Err(SchemaError::Type(schema.r#type)),
+        };
+        Ok(SchemaValidator { kind })
+    }
+
+    /// Checks that `message` is a valid value of the schema in `encoding`.
+    pub fn validate(&self, message: &[u8], encoding: Encoding) -> Result<(), SchemaError> {
+        match (&self.kind, encoding) {
+            (Kind::Avro(schema), Encoding::Binary) => schema.validate_binary(message),
+            (Kind::Avro(schema), Encoding::Json) => schema.validate_json(&parse_json(message)?),
+            (Kind::Protobuf(schema), Encoding::Binary) => schema.validate_binary(message),
+            (Kind::Protobuf(schema), Encoding::Json) => schema.validate_json(&parse_json(message)?),
+            (_, Encoding::Unspecified) => Err(SchemaError::Encoding(encoding as i32)),
+        }
+    }
+}
+
+impl Schema {
+    /// Validates `message` in `encoding` against this schema, see [`SchemaValidator`].
+    pub fn validate_message(&self, message: &[u8], encoding: Encoding) -> Result<(), SchemaError> {
+        SchemaValidator::new(self)?.validate(message, encoding)
+    }
+}
+
+fn parse_json(message: &[u8]) -> Result<serde_json::Value, SchemaError> {
+    serde_json::from_slice(message).map_err(|e| SchemaError::Message {
+        path: String::new(),
+        message: format!("invalid JSON: {}", e),
+    })
+}
+
+// The depth above which binary messages are rejected instead of overflowing the stack. JSON
+// messages are already limited by `serde_json`.
+const MAX_DEPTH: usize = 100;
+
+// The location of the value being validated.
+#[derive(Debug, Default)]
+pub(super) struct Path(Vec<Segment>);
+
+#[derive(Debug)]
+pub(super) enum Segment {
+    Field(String),
+    Index(usize),
+    Key(String),
+}
+
+impl Path {
+    pub(super) fn push(&mut self, segment: Segment) {
+        self.0.push(segment);
+    }
+
+    pub(super) fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    // Fails if the value being validated is nested too deeply.
+    pub(super) fn check_depth(&self) -> Result<(), SchemaError> {
+        if self.0.len() > MAX_DEPTH {
+            return Err(self.error(format!("nesting deeper than {} levels", MAX_DEPTH)));
+        }
+        Ok(())
+    }
+
+    pub(super) fn error(&self, message: impl Into<String>) -> SchemaError {
+        let mut path = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Field(name) if path.is_empty() => path.push_str(name),
+                Segment::Field(name) => {
+                    path.push('.');
+                    path.push_str(name);
+                }
+                Segment::Index(i) => path.push_str(&format!("[{}]", i)),
+                Segment::Key(key) => path.push_str(&format!("[{:?}]", key)),
+            }
+        }
+        SchemaError::Message {
+            path,
+            message: message.into(),
+        }
+    }
+}
+
+// A cursor over binary messages.
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub(super) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(super) fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    pub(super) fn take(&mut self, n: usize, path: &Path) -> Result<&'a [u8], SchemaError> {
+        if self.bytes.len() - self.pos < n {
+            return Err(path.error(format!(
+                "{} bytes expected at offset {}, {} left",
+                n,
+                self.pos,
+                self.bytes.len() - self.pos
+            )));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    pub(super) fn varint(&mut self, path: &Path) -> Result<u64, SchemaError> {
+        let start = self.pos;
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = match self.bytes.get(self.pos) {
+                Some(b) => *b,
+                None => {
+                    return Err(path.error(format!("truncated varint at offset {}", start)));
+                }
+            };
+            self.pos += 1;
+            value |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(path.error(format!("varint too long at offset {}", start)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn avro(definition: &str) -> Schema {
+        Schema {
+            name: "projects/test/schemas/avro".to_owned(),
+            r#type: schema::Type::Avro as i32,
+            definition: definition.to_owned(),
+        }
+    }
+
+    fn protobuf(definition: &str) -> Schema {
+        Schema {
+            name: "projects/test/schemas/protobuf".to_owned(),
+            r#type: schema::Type::ProtocolBuffer as i32,
+            definition: definition.to_owned(),
+        }
+    }
+
+    fn message_error(result: Result<(), SchemaError>) -> (String, String) {
+        match result {
+            Err(SchemaError::Message { path, message }) => (path, message),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    const AVRO: &str = r#"{
+        "type": "record",
+        "name": "Order",
+        "namespace": "com.example",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "customer", "type": ["null", "string"], "default": null},
+            {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["NEW", "SHIPPED"]}},
+            {"name": "items", "type": {"type": "array", "items": {
+                "type": "record",
+                "name": "Item",
+                "fields": [
+                    {"name": "sku", "type": "string"},
+                    {"name": "quantity", "type": "int", "default": 1},
+                    {"name": "next", "type": ["null", "Item"], "default": null}
+                ]
+            }}},
+            {"name": "tags", "type": {"type": "map", "values": "string"}, "default": {}},
+            {"name": "checksum", "type": {"type": "fixed", "name": "Md5", "size": 4}}
+        ]
+    }"#;
+
+    fn zigzag(v: i64, out: &mut Vec<u8>) {
+        let mut v = ((v << 1) ^ (v >> 63)) as u64;
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn avro_string(s: &str, out: &mut Vec<u8>) {
+        zigzag(s.len() as i64, out);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    #[test]
+    fn test_avro_binary() {
+        let schema = avro(AVRO);
+        let mut order = Vec::new();
+        zigzag(42, &mut order);
+        // customer: the string branch.
+        zigzag(1, &mut order);
+        avro_string("ada", &mut order);
+        // status: SHIPPED.
+        zigzag(1, &mut order);
+        // items: one block of one item, then the end.
+        zigzag(1, &mut order);
+        avro_string("sku-1", &mut order);
+        zigzag(3, &mut order);
+        zigzag(0, &mut order);
+        zigzag(0, &mut order);
+        // tags: one block of -1 entry with its size in bytes.
+        zigzag(-1, &mut order);
+        zigzag(4, &mut order);
+        avro_string("k", &mut order);
+        avro_string("v", &mut order);
+        zigzag(0, &mut order);
+        order.extend_from_slice(b"abcd");
+        schema.validate_message(&order, Encoding::Binary).unwrap();
+
+        let (path, message) =
+            message_error(schema.validate_message(&order[..order.len() - 1], Encoding::Binary));
+        assert_eq!(path, "checksum");
+        assert!(message.starts_with("4 bytes expected"), "{}", message);
+        let mut trailing = order.clone();
+        trailing.push(0);
+        let (path, _) = message_error(schema.validate_message(&trailing, Encoding::Binary));
+        assert_eq!(path, "");
+
+        // An unknown enum index.
+        let mut invalid = order.clone();
+        invalid[6] = 4;
+        let (path, message) = message_error(schema.validate_message(&invalid, Encoding::Binary));
+        assert_eq!(path, "status");
+        assert_eq!(message, "enum index out of range: 2");
+    }
+
+    #[test]
+    fn test_avro_blocks() {
+        // A block count bounded by the input left.
+        let ints = avro(r#"{"type": "array", "items": "int"}"#);
+        let mut message = Vec::new();
+        zigzag(1 << 62, &mut message);
+        let (_, error) = message_error(ints.validate_message(&message, Encoding::Binary));
+        assert!(
+            error.starts_with("block of 4611686018427387904 items"),
+            "{}",
+            error
+        );
+        let mut message = Vec::new();
+        zigzag(-2, &mut message);
+        zigzag(2, &mut message);
+        message.extend_from_slice(&[2, 4, 0]);
+        ints.validate_message(&message, Encoding::Binary).unwrap();
+
+        // Items without data are not read one by one.
+        let nulls = avro(
+            r#"{"type": "array", "items": {"type": "record", "name": "A", "fields": [{"name": "a", "type": "null"}]}}"#,
+        );
+        let mut message = Vec::new();
+        zigzag(1 << 62, &mut message);
+        zigzag(0, &mut message);
+        nulls.validate_message(&message, Encoding::Binary).unwrap();
+    }
+
+    #[test]
+    fn test_avro_depth() {
+        let schema = avro(
+            r#"{"type": "record", "name": "N", "fields": [{"name": "next", "type": ["null", "N"]}]}"#,
+        );
+        let mut message = vec![2; 99];
+        message.push(0);
+        schema.validate_message(&message, Encoding::Binary).unwrap();
+
+        let mut message = vec![2; 1000];
+        message.push(0);
+        let (path, message) = message_error(schema.validate_message(&message, Encoding::Binary));
+        assert_eq!(path.matches("next").count(), 101);
+        assert_eq!(message, "nesting deeper than 100 levels");
+    }
+
+    #[test]
+    fn test_avro_json() {
+        let schema = avro(AVRO);
+        let order = r#"{
+            "id": 42,
+            "customer": {"string": "ada"},
+            "status": "SHIPPED",
+            "items": [{"sku": "sku-1", "next": {"com.example.Item": {"sku": "sku-2", "quantity": 2, "next": null}}}],
+            "checksum": "abÿd"
+        }"#;
+        schema
+            .validate_message(order.as_bytes(), Encoding::Json)
+            .unwrap();
+
+        let invalid = order.replace("\"sku-2\"", "2");
+        let (path, message) =
+            message_error(schema.validate_message(invalid.as_bytes(), Encoding::Json));
+        assert_eq!(path, "items[0].next[\"com.example.Item\"].sku");
+        assert_eq!(message, "expected a string");
+        let invalid = order.replace("\"status\": \"SHIPPED\"", "\"status\": \"LOST\"");
+        let (path, message) =
+            message_error(schema.validate_message(invalid.as_bytes(), Encoding::Json));
+        assert_eq!(path, "status");
+        assert_eq!(message, "unknown enum symbol \"LOST\"");
+        let invalid = order.replace("{\"string\": \"ada\"}", "\"ada\"");
+        let (path, _) = message_error(schema.validate_message(invalid.as_bytes(), Encoding::Json));
+        assert_eq!(path, "customer");
+        let invalid = order.replace("\"id\": 42,", "");
+        let (path, message) =
+            message_error(schema.validate_message(invalid.as_bytes(), Encoding::Json));
+        assert_eq!(
+            (path.as_str(), message.as_str()),
+            ("", "missing field \"id\"")
+        );
+        let invalid = order.replace("\"id\": 42", "\"id\": 4.2");
+        assert!(schema
+            .validate_message(invalid.as_bytes(), Encoding::Json)
+            .is_err());
+    }
+
+    #[test]
+    fn test_avro_definition() {
+        let invalid = [
+            r#"{"type": "record", "name": "A", "fields": [{"name": "a", "type": "B"}]}"#,
+            r#"{"type": "record", "name": "A", "fields": [{"name": "a", "type": "int", "default": "x"}]}"#,
+            r#"{"type": "enum", "name": "E", "symbols": ["A", "A"]}"#,
+            r#"["int", "int"]"#,
+            r#"{"type": "fixed", "name": "F"}"#,
+            r#"{"type": "array"}"#,
+            r#"{"type": "record", "name": "1A", "fields": []}"#,
+            r#"{"type": "record", "name": "A", "fields": [{"name": "a", "type": "A"}]}"#,
+            r#"{"type": "record", "name": "A", "fields": [{"name": "b", "type": {"type": "record", "name": "B", "fields": [{"name": "a", "type": "A"}]}}]}"#,
+            "not json",
+        ];
+        for definition in &invalid {
+            assert!(
+                matches!(
+                    SchemaValidator::new(&avro(definition)),
+                    Err(SchemaError::Definition(_))
+                ),
+                "{}",
+                definition
+            );
+        }
+        let logical = avro(r#"{"type": "int", "logicalType": "date"}"#);
+        logical.validate_message(b"18000", Encoding::Json).unwrap();
+        assert_eq!(
+            logical.validate_message(b"1", Encoding::Unspecified),
+            Err(SchemaError::Encoding(0))
+        );
+        assert_eq!(
+            Schema::default().validate_message(b"", Encoding::Json),
+            Err(SchemaError::Type(0))
+        );
+    }
+
+    const PROTO: &str = r#"
+        syntax = "proto3";
+        package example.v1;
+
+        option java_multiple_files = true;
+
+        // An order.
+        message Order {
+            int64 id = 1;
+            optional string customer = 2;
+            Status status = 3;
+            repeated Item items = 4;
+            map<string, int32> tags = 5;
+            oneof payment {
+                string card = 6;
+                bytes token = 7 [json_name = "paymentToken"];
+            }
+            repeated sint32 deltas = 8 [packed = true];
+
+            message Item {
+                string sku = 1;
+                uint32 quantity = 2;
+                reserved 3, 5 to 7;
+                reserved "price";
+            }
+        }
+
+        /* The status of an order. */
+        enum Status {
+            STATUS_UNSPECIFIED = 0;
+            NEW = 1;
+            SHIPPED = 2;
+        }
+    "#;
+
+    fn key(number: u64, wire: u64, out: &mut Vec<u8>) {
+        varint(number << 3 | wire, out);
+    }
+
+    fn varint(mut v: u64, out: &mut Vec<u8>) {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn delimited(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        key(number, 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    #[test]
+    fn test_protobuf_binary() {
+        let schema = protobuf(PROTO);
+        let mut item = Vec::new();
+        delimited(1, b"sku-1", &mut item);
+        key(2, 0, &mut item);
+        varint(3, &mut item);
+        let mut entry = Vec::new();
+        delimited(1, b"k", &mut entry);
+        key(2, 0, &mut entry);
+        varint(-1i64 as u64, &mut entry);
+
+        let mut order = Vec::new();
+        key(1, 0, &mut order);
+        varint(42, &mut order);
+        key(3, 0, &mut order);
+        varint(2, &mut order);
+        delimited(4, &item, &mut order);
+        delimited(5, &entry, &mut order);
+        delimited(7, &[0xff, 0], &mut order);
+        delimited(8, &[1, 2, 3], &mut order);
+        // An unknown fixed32 field.
+        key(99, 5, &mut order);
+        order.extend_from_slice(&[0; 4]);
+        schema.validate_message(&order, Encoding::Binary).unwrap();
+
+        let mut invalid = order.clone();
+        delimited(2, &[0xff], &mut invalid);
+        let (path, message) = message_error(schema.validate_message(&invalid, Encoding::Binary));
+        assert_eq!(path, "customer");
+        assert_eq!(message, "invalid UTF-8 string");
+        let mut invalid = order.clone();
+        key(4, 0, &mut invalid);
+        varint(1, &mut invalid);
+        let (path, message) = message_error(schema.validate_message(&invalid, Encoding::Binary));
+        assert_eq!(path, "items");
+        assert_eq!(message, "wire type 0 for a message field");
+        let mut invalid = order.clone();
+        let mut bad_item = Vec::new();
+        key(2, 0, &mut bad_item);
+        varint(1 << 32, &mut bad_item);
+        delimited(4, &bad_item, &mut invalid);
+        let (path, message) = message_error(schema.validate_message(&invalid, Encoding::Binary));
+        assert_eq!(path, "items.quantity");
+        assert_eq!(message, "uint32 out of range: 4294967296");
+        let (path, _) =
+            message_error(schema.validate_message(&order[..order.len() - 1], Encoding::Binary));
+        assert_eq!(path, "");
+    }
+
+    #[test]
+    fn test_protobuf_depth() {
+        let schema = protobuf("syntax = \"proto3\"; message N { N next = 1; }");
+        let nested = |depth: usize| {
+            // The lengths of the messages, from the innermost one.
+            let mut lengths = vec![0];
+            for _ in 0..depth {
+                let mut header = Vec::new();
+                key(1, 2, &mut header);
+                varint(*lengths.last().unwrap() as u64, &mut header);
+                lengths.push(header.len() + lengths.last().unwrap());
+            }
+            let mut message = Vec::with_capacity(lengths.pop().unwrap());
+            for length in lengths.into_iter().rev() {
+                key(1, 2, &mut message);
+                varint(length as u64, &mut message);
+            }
+            message
+        };
+        schema
+            .validate_message(&nested(100), Encoding::Binary)
+            .unwrap();
+
+        let (path, message) =
+            message_error(schema.validate_message(&nested(200_000), Encoding::Binary));
+        assert_eq!(path.matches("next").count(), 101);
+        assert_eq!(message, "nesting deeper than 100 levels");
+    }
+
+    #[test]
+    fn test_protobuf_json() {
+        let schema = protobuf(PROTO);
+        let order = r#"{
+            "id": "42",
+            "customer": null,
+            "status": "SHIPPED",
+            "items": [{"sku": "sku-1", "quantity": 3}],
+            "tags": {"k": -1},
+            "paymentToken": "_w==",
+            "deltas": [1, -2, 3.0]
+        }"#;
+        schema
+            .validate_message(order.as_bytes(), Encoding::Json)
+            .unwrap();
+
+        let cases = [
+            (
+                "\"id\": \"42\"",
+                "\"id\": \"4x\"",
+                "id",
+                "expected an int64",
+            ),
+            (
+                "\"status\": \"SHIPPED\"",
+                "\"status\": \"LOST\"",
+                "status",
+                "unknown enum value \"LOST\"",
+            ),
+            (
+                "\"quantity\": 3",
+                "\"quantity\": -3",
+                "items[0].quantity",
+                "uint32 out of range: -3",
+            ),
+            (
+                "\"k\": -1",
+                "\"k\": \"x\"",
+                "tags[\"k\"]",
+                "expected an int32",
+            ),
+            (
+                "\"paymentToken\"",
+                "\"card\": \"x\", \"paymentToken\"",
+                "paymentToken",
+                "multiple fields of oneof payment",
+            ),
+            (
+                "\"paymentToken\": \"_w==\"",
+                "\"paymentToken\": \"_w=\"",
+                "paymentToken",
+                "invalid base64",
+            ),
+            ("\"id\"", "\"ID\"", "ID", "unknown field"),
+        ];
+        for (from, to, path, message) in &cases {
+            let invalid = order.replace(from, to);
+            assert_eq!(
+                message_error(schema.validate_message(invalid.as_bytes(), Encoding::Json)),
+                (path.to_string(), message.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_protobuf_definition() {
+        let required = r#"
+            syntax = "proto2";
+            message A {
+                required int32 a = 1;
+                optional B b = 2;
+                message B { required string c = 1; }
+            }
+        "#;
+        let schema = protobuf(required);
+        let mut b = Vec::new();
+        delimited(1, b"c", &mut b);
+        let mut a = Vec::new();
+        delimited(2, &b, &mut a);
+        let (path, message) = message_error(schema.validate_message(&a, Encoding::Binary));
+        assert_eq!(
+            (path.as_str(), message.as_str()),
+            ("", "missing required field a")
+        );
+        let (path, message) =
+            message_error(schema.validate_message(b"{\"a\": 1, \"b\": {}}", Encoding::Json));
+        assert_eq!(
+            (path.as_str(), message.as_str()),
+            ("b", "missing required field c")
+        );
+
+        let invalid = [
+            "syntax = \"proto3\"; import \"other.proto\"; message A {}",
+            "syntax = \"proto3\"; message A { required int32 a = 1; }",
+            "syntax = \"proto3\"; message A { B b = 1; }",
+            "syntax = \"proto3\"; message A { int32 a = 1; int32 b = 1; }",
+            "syntax = \"proto3\"; message A { int32 a = 0; }",
+            "syntax = \"proto3\"; message A { int32 a = 1 }",
+            "syntax = \"proto4\"; message A {}",
+            "syntax = \"proto3\"; enum E { A = 0; }",
+        ];
+        for definition in &invalid {
+            assert!(
+                matches!(
+                    SchemaValidator::new(&protobuf(definition)),
+                    Err(SchemaError::Definition(_))
+                ),
+                "{}",
+                definition
+            );
+        }
+    }
+}
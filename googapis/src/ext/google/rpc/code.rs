@@ -0,0 +1,105 @@
+use super::Code;
+
+impl Code {
+    /// The HTTP status of the code, as documented in `google/rpc/code.proto`.
+    pub fn http_status(self) -> u16 {
+        match self {
+            Code::Ok => 200,
+            Code::Cancelled => 499,
+            Code::Unknown | Code::Internal | Code::DataLoss => 500,
+            Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => 400,
+            Code::DeadlineExceeded => 504,
+            Code::NotFound => 404,
+            Code::AlreadyExists | Code::Aborted => 409,
+            Code::PermissionDenied => 403,
+            Code::Unauthenticated => 401,
+            Code::ResourceExhausted => 429,
+            Code::Unimplemented => 501,
+            Code::Unavailable => 503,
+        }
+    }
+
+    /// The code of an HTTP status, e.g. of an error returned by a REST API. Statuses shared by
+    /// several codes map to the most general one (`400` is `INVALID_ARGUMENT`, `409` is
+    /// `ABORTED` and `500` is `INTERNAL`), the `2xx` statuses are `OK` and the other statuses
+    /// are `UNKNOWN`.
+    pub fn from_http_status(status: u16) -> Code {
+        match status {
+            400 => Code::InvalidArgument,
+            401 => Code::Unauthenticated,
+            403 => Code::PermissionDenied,
+            404 => Code::NotFound,
+            409 => Code::Aborted,
+            429 => Code::ResourceExhausted,
+            499 => Code::Cancelled,
+            500 => Code::Internal,
+            501 => Code::Unimplemented,
+            503 => Code::Unavailable,
+            504 => Code::DeadlineExceeded,
+            200..=299 => Code::Ok,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+impl From<Code> for tonic::Code {
+    fn from(code: Code) -> Self {
+        tonic::Code::from_i32(code as i32)
+    }
+}
+
+impl From<tonic::Code> for Code {
+    fn from(code: tonic::Code) -> Self {
+        Code::from_i32(code as i32).unwrap_or(Code::Unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODES: [Code; 17] = [
+        Code::Ok,
+        Code::Cancelled,
+        Code::Unknown,
+        Code::InvalidArgument,
+        Code::DeadlineExceeded,
+        Code::NotFound,
+        Code::AlreadyExists,
+        Code::PermissionDenied,
+        Code::ResourceExhausted,
+        Code::FailedPrecondition,
+        Code::Aborted,
+        Code::OutOfRange,
+        Code::Unimplemented,
+        Code::Internal,
+        Code::Unavailable,
+        Code::DataLoss,
+        Code::Unauthenticated,
+    ];
+
+    #[test]
+    fn test_tonic_code() {
+        for &code in &CODES {
+            let tonic_code = tonic::Code::from(code);
+            assert_eq!(tonic_code as i32, code as i32);
+            assert_eq!(Code::from(tonic_code), code);
+        }
+        assert_eq!(tonic::Code::from(Code::NotFound), tonic::Code::NotFound);
+        assert_eq!(Code::from(tonic::Code::DataLoss), Code::DataLoss);
+    }
+
+    #[test]
+    fn test_http_status() {
+        for &code in &CODES {
+            let general = Code::from_http_status(code.http_status());
+            assert_eq!(general.http_status(), code.http_status(), "{:?}", code);
+        }
+        assert_eq!(Code::NotFound.http_status(), 404);
+        assert_eq!(Code::from_http_status(404), Code::NotFound);
+        assert_eq!(Code::from_http_status(400), Code::InvalidArgument);
+        assert_eq!(Code::from_http_status(204), Code::Ok);
+        assert_eq!(Code::from_http_status(302), Code::Unknown);
+        assert_eq!(Code::from_http_status(418), Code::Unknown);
+    }
+}
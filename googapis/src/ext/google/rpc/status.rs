@@ -0,0 +1,94 @@
+use prost::Message;
+
+use super::{Code, Status};
+
+impl Status {
+    /// A status without details.
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Status {
+            code: code as i32,
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    /// The code of the status, `UNKNOWN` if it is not a valid code.
+    pub fn code(&self) -> Code {
+        Code::from_i32(self.code).unwrap_or(Code::Unknown)
+    }
+}
+
+/// Converts a status, e.g. the `error` of a `google.longrunning.Operation`, into a gRPC status.
+/// The details are carried by [`tonic::Status::details`], encoded as the whole status like gRPC
+/// servers do in the `grpc-status-details-bin` trailer.
+impl From<Status> for tonic::Status {
+    fn from(status: Status) -> Self {
+        let code = status.code().into();
+        if status.details.is_empty() {
+            tonic::Status::new(code, status.message)
+        } else {
+            let details = status.encode_to_vec();
+            tonic::Status::with_details(code, status.message, details.into())
+        }
+    }
+}
+
+/// Converts a gRPC status, with the details of its `grpc-status-details-bin` trailer. Details that
+/// are not an encoded status are dropped.
+impl From<tonic::Status> for Status {
+    fn from(status: tonic::Status) -> Self {
+        let details = Status::decode(status.details())
+            .map(|s| s.details)
+            .unwrap_or_default();
+        Status {
+            code: status.code() as i32,
+            message: status.message().to_owned(),
+            details,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::rpc::ErrorInfo;
+
+    #[test]
+    fn test_tonic_status() {
+        let info = ErrorInfo {
+            reason: "API_DISABLED".to_owned(),
+            domain: "googleapis.com".to_owned(),
+            ..Default::default()
+        };
+        let status = Status {
+            details: vec![prost_types::Any {
+                type_url: "type.googleapis.com/google.rpc.ErrorInfo".to_owned(),
+                value: info.encode_to_vec(),
+            }],
+            ..Status::new(Code::PermissionDenied, "denied")
+        };
+
+        let tonic_status = tonic::Status::from(status.clone());
+        assert_eq!(tonic_status.code(), tonic::Code::PermissionDenied);
+        assert_eq!(tonic_status.message(), "denied");
+        assert_eq!(Status::decode(tonic_status.details()), Ok(status.clone()));
+        assert_eq!(Status::from(tonic_status), status);
+
+        let tonic_status = tonic::Status::from(Status::new(Code::NotFound, "missing"));
+        assert!(tonic_status.details().is_empty());
+        assert_eq!(
+            Status::from(tonic_status),
+            Status::new(Code::NotFound, "missing")
+        );
+
+        let garbage = tonic::Status::with_details(tonic::Code::Internal, "x", vec![0xff].into());
+        assert_eq!(Status::from(garbage), Status::new(Code::Internal, "x"));
+
+        assert_eq!(Status::new(Code::Ok, "").code(), Code::Ok);
+        let invalid = Status {
+            code: 42,
+            ..Default::default()
+        };
+        assert_eq!(invalid.code(), Code::Unknown);
+    }
+}
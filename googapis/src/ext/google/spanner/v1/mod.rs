@@ -0,0 +1,26 @@
+mod mutations;
+mod params;
+#[cfg(feature = "tokio")]
+mod pool;
+#[cfg(feature = "tokio")]
+mod result;
+mod row;
+#[cfg(feature = "tokio")]
+mod transaction;
+
+pub use self::mutations::WriteBuilder;
+#[doc(hidden)]
+pub use self::params::__struct_type;
+pub use self::params::{CommitTimestamp, Params, ToValue};
+#[cfg(feature = "tokio")]
+pub use self::pool::{
+    PooledSession, SessionClient, SessionError, SessionPool, SessionPoolSettings, SpannerFuture,
+};
+#[cfg(feature = "tokio")]
+pub use self::result::{PartialResultSetStream, QueryClient, ResultSetError, ResultStream};
+pub use self::row::{ColumnIndex, DecodeError, FromRow, FromValue, Row};
+#[cfg(feature = "tokio")]
+pub use self::transaction::{
+    ReadOnlyTransaction, ReadWriteTransaction, TransactionClient, TransactionError,
+    TransactionSettings,
+};
@@ -0,0 +1,323 @@
+use prost_types::ListValue;
+
+use super::{
+    key_range::{EndKeyType, StartKeyType},
+    mutation::{Delete, Operation, Write},
+    KeyRange, KeySet, Mutation, ToValue,
+};
+
+/// A builder of an insert, update, insert or update, or replace [`Mutation`], writing one or
+/// more rows of a table.
+///
+/// # Example
+/// ```ignore
+/// let insert = Mutation::insert("Singers")
+///     .set("SingerId", 1i64)
+///     .set("FirstName", "Marc")
+///     .build();
+/// let replace = Mutation::replace("Singers")
+///     .columns(["SingerId", "FirstName"])
+///     .row(&[&1i64, &"Marc"])
+///     .row(&[&2i64, &None::<String>])
+///     .build();
+/// transaction.buffer_write(vec![insert, replace]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WriteBuilder {
+    operation: fn(Write) -> Operation,
+    write: Write,
+}
+
+impl WriteBuilder {
+    fn new(operation: fn(Write) -> Operation, table: impl Into<String>) -> Self {
+        Self {
+            operation,
+            write: Write {
+                table: table.into(),
+                ..Write::default()
+            },
+        }
+    }
+
+    /// Adds a column, and its value in the last row, or in a first row if there are none. Use
+    /// [`columns`](Self::columns) and [`row`](Self::row) to write several rows.
+    pub fn set(mut self, column: impl Into<String>, value: impl ToValue) -> Self {
+        self.write.columns.push(column.into());
+        if self.write.values.is_empty() {
+            self.write.values.push(ListValue::default());
+        }
+        let row = self.write.values.last_mut().unwrap();
+        row.values.push(value.to_value());
+        self
+    }
+
+    /// Adds columns, whose values are given by each [`row`](Self::row).
+    pub fn columns<I>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.write
+            .columns
+            .extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds a row, with a value for each of the columns in order.
+    pub fn row(mut self, values: &[&dyn ToValue]) -> Self {
+        self.write.values.push(key(values));
+        self
+    }
+
+    pub fn build(self) -> Mutation {
+        Mutation {
+            operation: Some((self.operation)(self.write)),
+        }
+    }
+}
+
+impl From<WriteBuilder> for Mutation {
+    fn from(builder: WriteBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Mutation {
+    /// Inserts rows, failing with `ALREADY_EXISTS` if any of them exists.
+    pub fn insert(table: impl Into<String>) -> WriteBuilder {
+        WriteBuilder::new(Operation::Insert, table)
+    }
+
+    /// Updates existing rows, failing with `NOT_FOUND` if any of them does not exist.
+    pub fn update(table: impl Into<String>) -> WriteBuilder {
+        WriteBuilder::new(Operation::Update, table)
+    }
+
+    /// Inserts rows, or updates the given columns of those that exist.
+    pub fn insert_or_update(table: impl Into<String>) -> WriteBuilder {
+        WriteBuilder::new(Operation::InsertOrUpdate, table)
+    }
+
+    /// Inserts rows, deleting those that exist first, along with their interleaved rows in
+    /// tables with `ON DELETE CASCADE`.
+    pub fn replace(table: impl Into<String>) -> WriteBuilder {
+        WriteBuilder::new(Operation::Replace, table)
+    }
+
+    /// Deletes the rows of the keys, which need not exist.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let delete = Mutation::delete("Albums", KeySet::default().key(&[&1i64, &2i64]));
+    /// let delete_all = Mutation::delete("Singers", KeySet::all());
+    /// ```
+    pub fn delete(table: impl Into<String>, key_set: impl Into<KeySet>) -> Self {
+        Mutation {
+            operation: Some(Operation::Delete(Delete {
+                table: table.into(),
+                key_set: Some(key_set.into()),
+            })),
+        }
+    }
+}
+
+// Encodes a key or a row.
+fn key(values: &[&dyn ToValue]) -> ListValue {
+    ListValue {
+        values: values.iter().map(|v| v.to_value()).collect(),
+    }
+}
+
+impl KeySet {
+    /// The set of all the keys of a table or an index.
+    pub fn all() -> Self {
+        KeySet {
+            all: true,
+            ..KeySet::default()
+        }
+    }
+
+    /// Adds a key, with a value for each of the key columns in order.
+    pub fn key(mut self, key: &[&dyn ToValue]) -> Self {
+        self.keys.push(self::key(key));
+        self
+    }
+
+    pub fn range(mut self, range: KeyRange) -> Self {
+        self.ranges.push(range);
+        self
+    }
+}
+
+impl From<KeyRange> for KeySet {
+    fn from(range: KeyRange) -> Self {
+        KeySet::default().range(range)
+    }
+}
+
+/// A key range sets a start and an end key, either of which can be a prefix of the key columns,
+/// matching every key starting with it.
+///
+/// # Example
+/// ```ignore
+/// // The albums of singers 1 to 9.
+/// let range = KeyRange::default().start_closed(&[&1i64]).end_open(&[&10i64]);
+/// ```
+impl KeyRange {
+    /// The range of the keys starting with the key.
+    pub fn prefix(key: &[&dyn ToValue]) -> Self {
+        KeyRange::default().start_closed(key).end_closed(key)
+    }
+
+    /// Starts the range with the key, included.
+    pub fn start_closed(mut self, key: &[&dyn ToValue]) -> Self {
+        self.start_key_type = Some(StartKeyType::StartClosed(self::key(key)));
+        self
+    }
+
+    /// Starts the range after the key.
+    pub fn start_open(mut self, key: &[&dyn ToValue]) -> Self {
+        self.start_key_type = Some(StartKeyType::StartOpen(self::key(key)));
+        self
+    }
+
+    /// Ends the range with the key, included.
+    pub fn end_closed(mut self, key: &[&dyn ToValue]) -> Self {
+        self.end_key_type = Some(EndKeyType::EndClosed(self::key(key)));
+        self
+    }
+
+    /// Ends the range before the key.
+    pub fn end_open(mut self, key: &[&dyn ToValue]) -> Self {
+        self.end_key_type = Some(EndKeyType::EndOpen(self::key(key)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::{value::Kind, Value};
+
+    use super::{super::CommitTimestamp, *};
+
+    fn string(s: &str) -> Value {
+        Value {
+            kind: Some(Kind::StringValue(s.to_owned())),
+        }
+    }
+
+    type Constructor = fn(Write) -> Operation;
+
+    fn list(values: Vec<Value>) -> ListValue {
+        ListValue { values }
+    }
+
+    fn write(table: &str, columns: &[&str], values: Vec<ListValue>) -> Write {
+        Write {
+            table: table.to_owned(),
+            columns: columns.iter().map(|&c| c.to_owned()).collect(),
+            values,
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let insert = Mutation::insert("Singers")
+            .set("SingerId", 1i64)
+            .set("FirstName", "Marc")
+            .set("UpdatedAt", CommitTimestamp)
+            .build();
+        assert_eq!(
+            insert.operation,
+            Some(Operation::Insert(write(
+                "Singers",
+                &["SingerId", "FirstName", "UpdatedAt"],
+                vec![list(vec![
+                    string("1"),
+                    string("Marc"),
+                    string("spanner.commit_timestamp()")
+                ])]
+            )))
+        );
+
+        let rows = |builder: WriteBuilder| {
+            Mutation::from(
+                builder
+                    .columns(vec!["SingerId".to_owned(), "FirstName".to_owned()])
+                    .row(&[&1i64, &"Marc"])
+                    .row(&[&2i64, &None::<String>]),
+            )
+        };
+        let expected = write(
+            "Singers",
+            &["SingerId", "FirstName"],
+            vec![
+                list(vec![string("1"), string("Marc")]),
+                list(vec![
+                    string("2"),
+                    Value {
+                        kind: Some(Kind::NullValue(0)),
+                    },
+                ]),
+            ],
+        );
+        let cases = [
+            (
+                Mutation::insert("Singers"),
+                Operation::Insert as Constructor,
+            ),
+            (Mutation::update("Singers"), Operation::Update),
+            (
+                Mutation::insert_or_update("Singers"),
+                Operation::InsertOrUpdate,
+            ),
+            (Mutation::replace("Singers"), Operation::Replace),
+        ];
+        for (builder, operation) in cases {
+            assert_eq!(rows(builder).operation, Some(operation(expected.clone())));
+        }
+    }
+
+    #[test]
+    fn test_delete() {
+        let delete = Mutation::delete(
+            "Albums",
+            KeySet::default()
+                .key(&[&1i64, &2i64])
+                .range(KeyRange::prefix(&[&3i64]))
+                .range(KeyRange::default().start_open(&[&4i64]).end_open(&[&9i64])),
+        );
+        let key_set = KeySet {
+            keys: vec![list(vec![string("1"), string("2")])],
+            ranges: vec![
+                KeyRange {
+                    start_key_type: Some(StartKeyType::StartClosed(list(vec![string("3")]))),
+                    end_key_type: Some(EndKeyType::EndClosed(list(vec![string("3")]))),
+                },
+                KeyRange {
+                    start_key_type: Some(StartKeyType::StartOpen(list(vec![string("4")]))),
+                    end_key_type: Some(EndKeyType::EndOpen(list(vec![string("9")]))),
+                },
+            ],
+            all: false,
+        };
+        assert_eq!(
+            delete.operation,
+            Some(Operation::Delete(Delete {
+                table: "Albums".to_owned(),
+                key_set: Some(key_set),
+            }))
+        );
+
+        let all = Mutation::delete("Singers", KeySet::all());
+        assert!(matches!(
+            all.operation,
+            Some(Operation::Delete(Delete {
+                key_set: Some(KeySet { all: true, .. }),
+                ..
+            }))
+        ));
+        let range = KeyRange::default().end_closed(&[&"m"]);
+        assert_eq!(KeySet::from(range.clone()).ranges, vec![range]);
+    }
+}
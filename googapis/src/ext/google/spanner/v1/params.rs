@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+
+use prost_types::{value::Kind, ListValue, Struct, Timestamp, Value};
+
+use super::{struct_type::Field, StructType, Type, TypeCode};
+use crate::{base64, wkt::TimestampExt};
+
+/// A type that can be encoded as a value of a Spanner parameter, column or key, with its
+/// Spanner [`Type`].
+///
+/// `i64` and the smaller integers encode as `INT64`, `f64` and `f32` as `FLOAT64`, `bool` as
+/// `BOOL`, `String` and `&str` as `STRING`, `Vec<u8>` and `&[u8]` as `BYTES`,
+/// `prost_types::Timestamp` and [`CommitTimestamp`] as `TIMESTAMP`, `Vec` and slices as `ARRAY`,
+/// and structs with [`spanner_to_struct!`](crate::spanner_to_struct) as `STRUCT`. `None` encodes
+/// as a `NULL` of the type of its `Option`. With the `chrono` and `time` features, dates and UTC
+/// date times encode as `DATE` and `TIMESTAMP`, with `rust_decimal` `rust_decimal::Decimal`
+/// encodes as `NUMERIC`, and with `serde_json` `serde_json::Value` encodes as `JSON`.
+pub trait ToValue {
+    /// Returns the value as encoded for Spanner, e.g. `INT64` values as strings.
+    fn to_value(&self) -> Value;
+
+    fn spanner_type() -> Type
+    where
+        Self: Sized;
+}
+
+/// The commit timestamp of the transaction, written to a `TIMESTAMP` column with the
+/// `allow_commit_timestamp` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommitTimestamp;
+
+/// The parameters of a SQL statement, with their types, as the `params` and `param_types` of an
+/// `ExecuteSqlRequest`, a batch DML statement or a `PartitionQueryRequest`.
+///
+/// # Example
+/// ```ignore
+/// let mut params = Params::new();
+/// params.bind("id", 42i64).bind("name", "Marc");
+/// let (params, param_types) = params.into_parts();
+/// let request = ExecuteSqlRequest {
+///     sql: "SELECT * FROM Singers WHERE SingerId = @id OR FirstName = @name".to_owned(),
+///     params: Some(params),
+///     param_types,
+///     ..ExecuteSqlRequest::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params {
+    params: Struct,
+    param_types: HashMap<String, Type>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a parameter, named without its `@`, replacing any value bound to the same name.
+    pub fn bind<T: ToValue>(&mut self, name: impl Into<String>, value: T) -> &mut Self {
+        let name = name.into();
+        self.param_types.insert(name.clone(), T::spanner_type());
+        self.params.fields.insert(name, value.to_value());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.fields.is_empty()
+    }
+
+    /// Returns the `params` and `param_types` of the request.
+    pub fn into_parts(self) -> (Struct, HashMap<String, Type>) {
+        (self.params, self.param_types)
+    }
+}
+
+fn ty(code: TypeCode) -> Type {
+    Type {
+        code: code as i32,
+        ..Type::default()
+    }
+}
+
+fn value(kind: Kind) -> Value {
+    Value { kind: Some(kind) }
+}
+
+fn string(s: impl Into<String>) -> Value {
+    value(Kind::StringValue(s.into()))
+}
+
+impl<T: ToValue> ToValue for &T {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+
+    fn spanner_type() -> Type {
+        T::spanner_type()
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        match self {
+            Some(v) => v.to_value(),
+            None => value(Kind::NullValue(0)),
+        }
+    }
+
+    fn spanner_type() -> Type {
+        T::spanner_type()
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value {
+        value(Kind::BoolValue(*self))
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Bool)
+    }
+}
+
+macro_rules! int_to_value {
+    ($($ty: ty),+) => {
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self) -> Value {
+                    string(self.to_string())
+                }
+
+                fn spanner_type() -> Type {
+                    ty(TypeCode::Int64)
+                }
+            }
+        )+
+    };
+}
+
+int_to_value!(i64, i32, i16, i8, u32, u16);
+
+impl ToValue for f64 {
+    fn to_value(&self) -> Value {
+        if self.is_nan() {
+            string("NaN")
+        } else if self.is_infinite() {
+            string(if *self > 0.0 { "Infinity" } else { "-Infinity" })
+        } else {
+            value(Kind::NumberValue(*self))
+        }
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Float64)
+    }
+}
+
+impl ToValue for f32 {
+    fn to_value(&self) -> Value {
+        f64::from(*self).to_value()
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Float64)
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        string(self.as_str())
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::String)
+    }
+}
+
+impl ToValue for &str {
+    fn to_value(&self) -> Value {
+        string(*self)
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::String)
+    }
+}
+
+impl ToValue for Vec<u8> {
+    fn to_value(&self) -> Value {
+        string(base64::encode(self))
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Bytes)
+    }
+}
+
+impl ToValue for &[u8] {
+    fn to_value(&self) -> Value {
+        string(base64::encode(self))
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Bytes)
+    }
+}
+
+impl ToValue for Timestamp {
+    fn to_value(&self) -> Value {
+        string(format_timestamp(self.seconds, self.nanos))
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Timestamp)
+    }
+}
+
+impl ToValue for CommitTimestamp {
+    fn to_value(&self) -> Value {
+        string("spanner.commit_timestamp()")
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Timestamp)
+    }
+}
+
+fn array_type<T: ToValue>() -> Type {
+    Type {
+        code: TypeCode::Array as i32,
+        array_element_type: Some(Box::new(T::spanner_type())),
+        struct_type: None,
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        self.as_slice().to_value()
+    }
+
+    fn spanner_type() -> Type {
+        array_type::<T>()
+    }
+}
+
+impl<T: ToValue> ToValue for &[T] {
+    fn to_value(&self) -> Value {
+        let values = self.iter().map(T::to_value).collect();
+        value(Kind::ListValue(ListValue { values }))
+    }
+
+    fn spanner_type() -> Type {
+        array_type::<T>()
+    }
+}
+
+/// Returns the `STRUCT` type of the fields, for [`spanner_to_struct!`](crate::spanner_to_struct).
+#[doc(hidden)]
+pub fn __struct_type(fields: Vec<(&str, Type)>) -> Type {
+    let fields = fields
+        .into_iter()
+        .map(|(name, r#type)| Field {
+            name: name.to_owned(),
+            r#type: Some(r#type),
+        })
+        .collect();
+    Type {
+        code: TypeCode::Struct as i32,
+        array_element_type: None,
+        struct_type: Some(StructType { fields }),
+    }
+}
+
+/// Implements [`ToValue`](crate::google::spanner::v1::ToValue) for a struct, encoding it as a
+/// `STRUCT` of the listed fields, named as the field or as the name given after `=`.
+///
+/// # Example
+/// ```
+/// use googapis::google::spanner::v1::{Params, ToValue, Type, TypeCode};
+///
+/// struct Singer {
+///     id: i64,
+///     first_name: Option<String>,
+/// }
+///
+/// googapis::spanner_to_struct!(Singer {
+///     id = "SingerId",
+///     first_name,
+/// });
+///
+/// let singers = vec![Singer { id: 1, first_name: None }];
+/// let mut params = Params::new();
+/// params.bind("singers", &singers);
+/// let (_, param_types) = params.into_parts();
+///
+/// let ty = param_types["singers"].array_element_type.as_deref().unwrap();
+/// assert_eq!(ty.code, TypeCode::Struct as i32);
+/// let fields = &ty.struct_type.as_ref().unwrap().fields;
+/// assert_eq!(fields[0].name, "SingerId");
+/// assert_eq!(fields[1].r#type, Some(String::spanner_type()));
+/// ```
+#[macro_export]
+macro_rules! spanner_to_struct {
+    ($ty: ty { $($field: ident $(= $name: literal)?),* $(,)? }) => {
+        impl $crate::google::spanner::v1::ToValue for $ty {
+            fn to_value(&self) -> ::prost_types::Value {
+                let values = ::std::vec![
+                    $($crate::google::spanner::v1::ToValue::to_value(&self.$field),)*
+                ];
+                ::prost_types::Value {
+                    kind: ::std::option::Option::Some(::prost_types::value::Kind::ListValue(
+                        ::prost_types::ListValue { values },
+                    )),
+                }
+            }
+
+            fn spanner_type() -> $crate::google::spanner::v1::Type {
+                fn field_type<S, T: $crate::google::spanner::v1::ToValue>(
+                    _: fn(&S) -> &T,
+                ) -> $crate::google::spanner::v1::Type {
+                    T::spanner_type()
+                }
+                $crate::google::spanner::v1::__struct_type(::std::vec![
+                    $((
+                        $crate::__spanner_column!($field $($name)?),
+                        field_type(|s: &Self| &s.$field),
+                    ),)*
+                ])
+            }
+        }
+    };
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impl {
+    use chrono::{Datelike as _, NaiveDate, Utc};
+
+    use super::{format_date, format_timestamp, string, ty, ToValue, Type, TypeCode, Value};
+
+    impl ToValue for NaiveDate {
+        fn to_value(&self) -> Value {
+            string(format_date(self.year().into(), self.month(), self.day()))
+        }
+
+        fn spanner_type() -> Type {
+            ty(TypeCode::Date)
+        }
+    }
+
+    impl ToValue for chrono::DateTime<Utc> {
+        fn to_value(&self) -> Value {
+            string(format_timestamp(
+                self.timestamp(),
+                self.timestamp_subsec_nanos() as i32,
+            ))
+        }
+
+        fn spanner_type() -> Type {
+            ty(TypeCode::Timestamp)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impl {
+    use time::OffsetDateTime;
+
+    use super::{format_date, format_timestamp, string, ty, ToValue, Type, TypeCode, Value};
+
+    impl ToValue for time::Date {
+        fn to_value(&self) -> Value {
+            let month = u8::from(self.month());
+            string(format_date(
+                self.year().into(),
+                month.into(),
+                self.day().into(),
+            ))
+        }
+
+        fn spanner_type() -> Type {
+            ty(TypeCode::Date)
+        }
+    }
+
+    /// Date times are encoded in UTC, whatever their offset.
+    impl ToValue for OffsetDateTime {
+        fn to_value(&self) -> Value {
+            string(format_timestamp(
+                self.unix_timestamp(),
+                self.nanosecond() as i32,
+            ))
+        }
+
+        fn spanner_type() -> Type {
+            ty(TypeCode::Timestamp)
+        }
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl ToValue for rust_decimal::Decimal {
+    fn to_value(&self) -> Value {
+        string(self.to_string())
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Numeric)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl ToValue for serde_json::Value {
+    fn to_value(&self) -> Value {
+        string(self.to_string())
+    }
+
+    fn spanner_type() -> Type {
+        ty(TypeCode::Json)
+    }
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn format_date(year: i64, month: u32, day: u32) -> String {
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Formats a `TIMESTAMP` value in RFC 3339 and UTC, with 0, 3, 6 or 9 fractional digits. Spanner
+// rejects timestamps out of the years 1 to 9999, which are formatted as their error instead.
+fn format_timestamp(seconds: i64, nanos: i32) -> String {
+    let t = i128::from(seconds) * 1_000_000_000 + i128::from(nanos);
+    let t = Timestamp {
+        seconds: t.div_euclid(1_000_000_000) as i64,
+        nanos: t.rem_euclid(1_000_000_000) as i32,
+    };
+    t.to_rfc3339().unwrap_or_else(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::FromValue, *};
+
+    fn encode<T: ToValue>(value: T) -> (Value, TypeCode) {
+        let code = TypeCode::from_i32(T::spanner_type().code).unwrap();
+        (value.to_value(), code)
+    }
+
+    // Encodes a value, and decodes it back.
+    fn round_trip<T: ToValue + FromValue>(value: T) -> T {
+        T::from_value(&value.to_value(), &T::spanner_type()).unwrap()
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(encode(42i64), (string("42"), TypeCode::Int64));
+        assert_eq!(encode(-7i32), (string("-7"), TypeCode::Int64));
+        assert_eq!(
+            encode(0.5),
+            (value(Kind::NumberValue(0.5)), TypeCode::Float64)
+        );
+        assert_eq!(encode(f64::NAN), (string("NaN"), TypeCode::Float64));
+        assert_eq!(
+            encode(f32::NEG_INFINITY),
+            (string("-Infinity"), TypeCode::Float64)
+        );
+        assert_eq!(encode(true), (value(Kind::BoolValue(true)), TypeCode::Bool));
+        let names = ["Marc".to_owned()];
+        assert_eq!(encode("Marc"), (string("Marc"), TypeCode::String));
+        assert_eq!(encode(&names[0]), (string("Marc"), TypeCode::String));
+        assert_eq!(encode(&b"hello"[..]), (string("aGVsbG8="), TypeCode::Bytes));
+        assert_eq!(
+            encode(CommitTimestamp),
+            (string("spanner.commit_timestamp()"), TypeCode::Timestamp)
+        );
+        assert_eq!(round_trip(i64::MIN), i64::MIN);
+        assert_eq!(round_trip(f64::INFINITY), f64::INFINITY);
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|b| 255 - 31 * b).collect();
+            assert_eq!(round_trip(bytes.clone()), bytes);
+        }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let cases = [
+            (0, 0, "1970-01-01T00:00:00Z"),
+            (1_629_817_445, 123_000_000, "2021-08-24T15:04:05.123Z"),
+            (1_629_817_445, 123_456_000, "2021-08-24T15:04:05.123456Z"),
+            (-1, 999_999_999, "1969-12-31T23:59:59.999999999Z"),
+            (-62_135_596_800, 0, "0001-01-01T00:00:00Z"),
+            (253_402_300_799, 0, "9999-12-31T23:59:59Z"),
+        ];
+        for &(seconds, nanos, s) in &cases {
+            let t = Timestamp { seconds, nanos };
+            assert_eq!(encode(t.clone()), (string(s), TypeCode::Timestamp));
+            assert_eq!(round_trip(t.clone()), t);
+        }
+        assert_eq!(format_timestamp(0, -1), "1969-12-31T23:59:59.999999999Z");
+        assert_eq!(
+            format_timestamp(253_402_300_800, 0),
+            "timestamp out of range: seconds = 253402300800, nanos = 0"
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{NaiveDate, TimeZone as _, Utc};
+
+        let date = NaiveDate::from_ymd_opt(2021, 8, 24).unwrap();
+        assert_eq!(encode(date), (string("2021-08-24"), TypeCode::Date));
+        assert_eq!(round_trip(date), date);
+        let t = Utc.timestamp_opt(1_629_817_445, 5).unwrap();
+        assert_eq!(
+            encode(t),
+            (
+                string("2021-08-24T15:04:05.000000005Z"),
+                TypeCode::Timestamp
+            )
+        );
+        assert_eq!(round_trip(t), t);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time() {
+        use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+        let date = Date::from_calendar_date(2021, Month::August, 24).unwrap();
+        assert_eq!(encode(date), (string("2021-08-24"), TypeCode::Date));
+        assert_eq!(round_trip(date), date);
+        let t = OffsetDateTime::from_unix_timestamp(1_629_817_445)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(
+            encode(t),
+            (string("2021-08-24T15:04:05Z"), TypeCode::Timestamp)
+        );
+        assert_eq!(round_trip(t), t);
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_numeric() {
+        let n: rust_decimal::Decimal = "-123.450".parse().unwrap();
+        assert_eq!(encode(n), (string("-123.450"), TypeCode::Numeric));
+        assert_eq!(round_trip(n), n);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        let json = serde_json::json!({"name": "Marc"});
+        assert_eq!(
+            encode(json.clone()),
+            (string(r#"{"name":"Marc"}"#), TypeCode::Json)
+        );
+        assert_eq!(round_trip(json.clone()), json);
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut params = Params::new();
+        assert!(params.is_empty());
+        params
+            .bind("id", 42i64)
+            .bind("name", None::<String>)
+            .bind("ids", vec![1i64, 2])
+            .bind("tags", &["a", "b"][..])
+            .bind("id", 43i64);
+        assert_eq!(params.len(), 4);
+        let (params, param_types) = params.into_parts();
+
+        assert_eq!(params.fields["id"], string("43"));
+        assert_eq!(params.fields["name"], value(Kind::NullValue(0)));
+        assert_eq!(
+            params.fields["ids"],
+            value(Kind::ListValue(ListValue {
+                values: vec![string("1"), string("2")]
+            }))
+        );
+        assert_eq!(param_types["id"], ty(TypeCode::Int64));
+        assert_eq!(param_types["name"], ty(TypeCode::String));
+        assert_eq!(
+            param_types["ids"],
+            Type {
+                code: TypeCode::Array as i32,
+                array_element_type: Some(Box::new(ty(TypeCode::Int64))),
+                struct_type: None,
+            }
+        );
+        assert_eq!(param_types["tags"], array_type::<String>());
+        assert_eq!(
+            Vec::<Option<i64>>::from_value(&vec![Some(1i64), None].to_value(), &param_types["ids"]),
+            Ok(vec![Some(1), None])
+        );
+    }
+}
@@ -0,0 +1,885 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use tokio::{
+    sync::oneshot,
+    time::{self, Instant},
+};
+
+use super::{
+    spanner_client::SpannerClient, transaction_options, BatchCreateSessionsRequest,
+    BeginTransactionRequest, DeleteSessionRequest, GetSessionRequest, Session, Transaction,
+    TransactionOptions,
+};
+
+/// The future returned by the methods of [`SessionClient`].
+pub type SpannerFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, tonic::Status>> + Send + 'a>>;
+
+/// A client of the session methods of the `google.spanner.v1.Spanner` service, implemented by
+/// `SpannerClient`. The [`SessionPool`] clones it for every request.
+pub trait SessionClient: Clone + Send + 'static {
+    fn batch_create_sessions(
+        &mut self,
+        request: BatchCreateSessionsRequest,
+    ) -> SpannerFuture<'_, Vec<Session>>;
+
+    fn get_session(&mut self, name: String) -> SpannerFuture<'_, Session>;
+
+    fn delete_session(&mut self, name: String) -> SpannerFuture<'_, ()>;
+
+    fn begin_transaction(
+        &mut self,
+        request: BeginTransactionRequest,
+    ) -> SpannerFuture<'_, Transaction>;
+}
+
+impl<T> SessionClient for SpannerClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn batch_create_sessions(
+        &mut self,
+        request: BatchCreateSessionsRequest,
+    ) -> SpannerFuture<'_, Vec<Session>> {
+        Box::pin(async move {
+            let response = SpannerClient::batch_create_sessions(self, request).await?;
+            Ok(response.into_inner().session)
+        })
+    }
+
+    fn get_session(&mut self, name: String) -> SpannerFuture<'_, Session> {
+        Box::pin(async move {
+            let request = GetSessionRequest { name };
+            Ok(SpannerClient::get_session(self, request)
+                .await?
+                .into_inner())
+        })
+    }
+
+    fn delete_session(&mut self, name: String) -> SpannerFuture<'_, ()> {
+        Box::pin(async move {
+            let request = DeleteSessionRequest { name };
+            SpannerClient::delete_session(self, request).await?;
+            Ok(())
+        })
+    }
+
+    fn begin_transaction(
+        &mut self,
+        request: BeginTransactionRequest,
+    ) -> SpannerFuture<'_, Transaction> {
+        Box::pin(async move {
+            Ok(SpannerClient::begin_transaction(self, request)
+                .await?
+                .into_inner())
+        })
+    }
+}
+
+// How often idle sessions are checked for keepalive pings and eviction.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(10);
+// The most sessions of a `BatchCreateSessionsRequest`.
+const MAX_BATCH_SESSIONS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct SessionPoolSettings {
+    /// The sessions created with the pool, and kept open while idle.
+    pub min_sessions: usize,
+    /// The most sessions open at once; checkouts wait for one to be returned beyond it.
+    pub max_sessions: usize,
+    /// How many sessions are created at once when none is idle.
+    pub sessions_per_batch: usize,
+    /// The fraction of the open sessions kept with a read-write transaction already begun,
+    /// for [`SessionPool::get_write`].
+    pub write_sessions: f64,
+    /// The labels of the sessions.
+    pub labels: HashMap<String, String>,
+    /// How long a checkout waits for a session.
+    pub checkout_timeout: Duration,
+    /// Idle sessions are pinged with `GetSession` after this time, so that Spanner does not
+    /// delete them. It deletes sessions idle for more than an hour.
+    pub keepalive_interval: Duration,
+    /// Sessions idle for longer are deleted, as long as `min_sessions` are left.
+    pub idle_timeout: Duration,
+}
+
+impl Default for SessionPoolSettings {
+    fn default() -> Self {
+        SessionPoolSettings {
+            min_sessions: 100,
+            max_sessions: 400,
+            sessions_per_batch: 25,
+            write_sessions: 0.2,
+            labels: HashMap::new(),
+            checkout_timeout: Duration::from_secs(60),
+            keepalive_interval: Duration::from_secs(50 * 60),
+            idle_timeout: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// An error returned when checking out a session.
+#[derive(Debug)]
+pub enum SessionError {
+    /// Creating a session or beginning its transaction failed.
+    Status(tonic::Status),
+    /// No session was available within [`SessionPoolSettings::checkout_timeout`].
+    Timeout,
+    /// The pool was closed.
+    Closed,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Status(s) => write!(f, "session request failed: {}", s),
+            SessionError::Timeout => f.write_str("timed out waiting for a session"),
+            SessionError::Closed => f.write_str("session pool closed"),
+        }
+    }
+}
+
+impl error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SessionError::Status(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl From<tonic::Status> for SessionError {
+    fn from(s: tonic::Status) -> Self {
+        SessionError::Status(s)
+    }
+}
+
+#[derive(Debug)]
+struct Idle {
+    name: String,
+    // The read-write transaction begun for the session.
+    transaction: Option<Vec<u8>>,
+    // When the session was last used or pinged.
+    last_used: Instant,
+}
+
+type Waiter = oneshot::Sender<Result<Idle, SessionError>>;
+
+#[derive(Debug, Default)]
+struct State {
+    // The idle sessions, most recently used last, with and without a transaction.
+    idle: VecDeque<Idle>,
+    write: VecDeque<Idle>,
+    // The sessions created and not deleted, wherever they are.
+    open: usize,
+    creating: usize,
+    preparing: usize,
+    waiters: VecDeque<Waiter>,
+    closed: bool,
+}
+
+struct Inner<C> {
+    client: Mutex<C>,
+    database: String,
+    settings: SessionPoolSettings,
+    state: Mutex<State>,
+}
+
+/// A pool of the sessions of a Spanner database.
+///
+/// Sessions are created in batches with `BatchCreateSessions`, up to
+/// [`SessionPoolSettings::max_sessions`], and returned to the pool when the
+/// [`PooledSession`] checked out is dropped. Idle sessions are pinged with `GetSession` so
+/// that Spanner keeps them, and deleted after [`SessionPoolSettings::idle_timeout`] beyond
+/// [`SessionPoolSettings::min_sessions`]. A fraction of the idle sessions has a read-write
+/// transaction begun in the background, to be checked out with [`SessionPool::get_write`].
+///
+/// The pool is cheap to clone, and shared by the clones.
+///
+/// # Example
+/// ```ignore
+/// use googapis::google::spanner::v1::{spanner_client::SpannerClient, ExecuteSqlRequest, SessionPool};
+///
+/// let database = "projects/p/instances/i/databases/d";
+/// let pool = SessionPool::new(SpannerClient::new(channel.clone()), database);
+/// let session = pool.get().await?;
+/// let request = ExecuteSqlRequest {
+///     session: session.name().to_owned(),
+///     sql: "SELECT 1".to_owned(),
+///     ..Default::default()
+/// };
+/// let result = SpannerClient::new(channel).execute_sql(request).await?;
+/// ```
+pub struct SessionPool<C> {
+    inner: Arc<Inner<C>>,
+}
+
+impl<C> Clone for SessionPool<C> {
+    fn clone(&self) -> Self {
+        SessionPool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C> fmt::Debug for SessionPool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionPool")
+            .field("database", &self.inner.database)
+            .field("state", &self.inner.state)
+            .finish()
+    }
+}
+
+impl<C: SessionClient> SessionPool<C> {
+    /// A pool with the default settings. It must be created in a Tokio runtime.
+    pub fn new(client: C, database: impl Into<String>) -> Self {
+        Self::with_settings(client, database, SessionPoolSettings::default())
+    }
+
+    /// A pool with `settings`. It must be created in a Tokio runtime.
+    pub fn with_settings(
+        client: C,
+        database: impl Into<String>,
+        mut settings: SessionPoolSettings,
+    ) -> Self {
+        settings.max_sessions = settings.max_sessions.max(1);
+        settings.min_sessions = settings.min_sessions.min(settings.max_sessions);
+        settings.sessions_per_batch = settings.sessions_per_batch.max(1);
+        let inner = Arc::new(Inner {
+            client: Mutex::new(client),
+            database: database.into(),
+            settings,
+            state: Mutex::new(State::default()),
+        });
+        inner.replenish(&mut inner.state.lock().unwrap());
+        tokio::spawn(maintain(Arc::downgrade(&inner)));
+        SessionPool { inner }
+    }
+
+    pub fn database(&self) -> &str {
+        &self.inner.database
+    }
+
+    // The client of the sessions, for the requests of the transactions run on them.
+    pub(super) fn client(&self) -> C {
+        self.inner.client()
+    }
+
+    /// Checks out a session, for reads and single-use transactions.
+    pub async fn get(&self) -> Result<PooledSession<C>, SessionError> {
+        self.checkout(false).await
+    }
+
+    /// Checks out a session with a read-write transaction begun, see
+    /// [`PooledSession::take_transaction`]. The transaction is begun now if no idle session has
+    /// one.
+    pub async fn get_write(&self) -> Result<PooledSession<C>, SessionError> {
+        self.checkout(true).await
+    }
+
+    async fn checkout(&self, write: bool) -> Result<PooledSession<C>, SessionError> {
+        let deadline = Instant::now() + self.inner.settings.checkout_timeout;
+        loop {
+            let idle = self.take(write, deadline).await?;
+            let mut session = PooledSession {
+                name: idle.name,
+                transaction: idle.transaction,
+                pool: self.inner.clone(),
+                discarded: false,
+            };
+            if !write {
+                // The transaction is invalidated by the use of the session.
+                session.transaction = None;
+                return Ok(session);
+            }
+            if session.transaction.is_some() {
+                return Ok(session);
+            }
+            match self.inner.begin(&session.name).await {
+                Ok(id) => {
+                    session.transaction = Some(id);
+                    return Ok(session);
+                }
+                // Spanner deleted the session, another one is tried.
+                Err(status) if status.code() == tonic::Code::NotFound => session.discard(),
+                Err(status) => return Err(status.into()),
+            }
+        }
+    }
+
+    // Takes an idle session, or waits for one.
+    async fn take(&self, write: bool, deadline: Instant) -> Result<Idle, SessionError> {
+        let mut rx = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.closed {
+                return Err(SessionError::Closed);
+            }
+            let idle = if write {
+                state.write.pop_back().or_else(|| state.idle.pop_back())
+            } else {
+                state.idle.pop_back().or_else(|| state.write.pop_back())
+            };
+            if let Some(idle) = idle {
+                return Ok(idle);
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiters.push_back(tx);
+            self.inner.grow(&mut state);
+            rx
+        };
+        tokio::select! {
+            result = &mut rx => result.unwrap_or(Err(SessionError::Closed)),
+            _ = time::sleep_until(deadline) => {
+                // A session sent in the meantime goes back to the pool.
+                rx.close();
+                if let Ok(Ok(idle)) = rx.try_recv() {
+                    self.inner.release(idle);
+                }
+                Err(SessionError::Timeout)
+            }
+        }
+    }
+
+    /// The sessions open, whether idle or checked out.
+    pub fn open_sessions(&self) -> usize {
+        self.inner.state.lock().unwrap().open
+    }
+
+    /// The idle sessions, with a read-write transaction begun or not.
+    pub fn idle_sessions(&self) -> usize {
+        let state = self.inner.state.lock().unwrap();
+        state.idle.len() + state.write.len()
+    }
+
+    /// Deletes the idle sessions and fails the waiting checkouts. Sessions checked out are
+    /// deleted when they are returned.
+    pub async fn close(&self) {
+        let idle = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.closed = true;
+            for waiter in state.waiters.drain(..) {
+                let _ = waiter.send(Err(SessionError::Closed));
+            }
+            let mut idle = state.idle.drain(..).collect::<Vec<_>>();
+            idle.extend(state.write.drain(..));
+            state.open -= idle.len();
+            idle
+        };
+        for session in idle {
+            let _ = self.inner.client().delete_session(session.name).await;
+        }
+    }
+}
+
+impl<C: SessionClient> Inner<C> {
+    fn client(&self) -> C {
+        self.client.lock().unwrap().clone()
+    }
+
+    async fn begin(&self, session: &str) -> Result<Vec<u8>, tonic::Status> {
+        let request = BeginTransactionRequest {
+            session: session.to_owned(),
+            options: Some(TransactionOptions {
+                mode: Some(transaction_options::Mode::ReadWrite(
+                    transaction_options::ReadWrite {},
+                )),
+            }),
+            request_options: None,
+        };
+        Ok(self.client().begin_transaction(request).await?.id)
+    }
+
+    // Hands an idle session to a waiting checkout, or back to the pool.
+    fn release(self: &Arc<Self>, mut idle: Idle) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            state.open -= 1;
+            drop(state);
+            self.delete(idle.name);
+            return;
+        }
+        while let Some(waiter) = state.waiters.pop_front() {
+            match waiter.send(Ok(idle)) {
+                Ok(()) => return,
+                Err(Ok(returned)) => idle = returned,
+                Err(Err(_)) => unreachable!(),
+            }
+        }
+        let target = (state.open as f64 * self.settings.write_sessions).ceil() as usize;
+        if idle.transaction.is_none() && state.write.len() + state.preparing < target {
+            state.preparing += 1;
+            let inner = self.clone();
+            spawn(async move {
+                let id = inner.begin(&idle.name).await;
+                let mut state = inner.state.lock().unwrap();
+                state.preparing -= 1;
+                match id {
+                    Ok(id) => {
+                        idle.transaction = Some(id);
+                        drop(state);
+                        inner.release(idle);
+                    }
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        state.open -= 1;
+                        inner.replenish(&mut state);
+                    }
+                    Err(_) => {
+                        drop(state);
+                        inner.release(idle);
+                    }
+                }
+            });
+            return;
+        }
+        if idle.transaction.is_some() {
+            state.write.push_back(idle);
+        } else {
+            state.idle.push_back(idle);
+        }
+    }
+
+    fn delete(&self, name: String) {
+        let mut client = self.client();
+        spawn(async move {
+            let _ = client.delete_session(name).await;
+        });
+    }
+
+    // Creates sessions for the waiting checkouts.
+    fn grow(self: &Arc<Self>, state: &mut State) {
+        let settings = &self.settings;
+        let available = settings.max_sessions - state.open - state.creating;
+        let needed = state.waiters.len().saturating_sub(state.creating);
+        if needed > 0 && available > 0 {
+            self.create(
+                state,
+                settings.sessions_per_batch.max(needed).min(available),
+            );
+        }
+    }
+
+    // Creates sessions up to `min_sessions`.
+    fn replenish(self: &Arc<Self>, state: &mut State) {
+        let open = state.open + state.creating;
+        if open < self.settings.min_sessions && !state.closed {
+            self.create(state, self.settings.min_sessions - open);
+        } else {
+            self.grow(state);
+        }
+    }
+
+    fn create(self: &Arc<Self>, state: &mut State, count: usize) {
+        state.creating += count;
+        let inner = self.clone();
+        spawn(async move {
+            let mut remaining = count;
+            let mut client = inner.client();
+            while remaining > 0 {
+                let request = BatchCreateSessionsRequest {
+                    database: inner.database.clone(),
+                    session_template: Some(Session {
+                        labels: inner.settings.labels.clone(),
+                        ..Default::default()
+                    }),
+                    session_count: remaining.min(MAX_BATCH_SESSIONS) as i32,
+                };
+                match client.batch_create_sessions(request).await {
+                    Ok(sessions) if !sessions.is_empty() => {
+                        let n = sessions.len().min(remaining);
+                        remaining -= n;
+                        {
+                            let mut state = inner.state.lock().unwrap();
+                            state.creating -= n;
+                            state.open += n;
+                        }
+                        for session in sessions.into_iter().take(n) {
+                            inner.release(Idle {
+                                name: session.name,
+                                transaction: None,
+                                last_used: Instant::now(),
+                            });
+                        }
+                    }
+                    result => {
+                        let status = result
+                            .err()
+                            .unwrap_or_else(|| tonic::Status::internal("no session created"));
+                        // The checkouts waiting for the sessions not created fail.
+                        let mut state = inner.state.lock().unwrap();
+                        state.creating -= remaining;
+                        let unserved = state.waiters.len().saturating_sub(state.creating);
+                        for waiter in state.waiters.drain(..unserved) {
+                            let status = tonic::Status::new(status.code(), status.message());
+                            let _ = waiter.send(Err(SessionError::Status(status)));
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    // Deletes the sessions idle for too long, and pings the ones to keep alive.
+    fn maintain(self: &Arc<Self>) {
+        let now = Instant::now();
+        let settings = &self.settings;
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        let state = &mut *state;
+        let mut evicted = Vec::new();
+        let mut pinged = Vec::new();
+        for queue in [&mut state.idle, &mut state.write] {
+            let mut kept = VecDeque::new();
+            for idle in queue.drain(..) {
+                if now >= idle.last_used + settings.idle_timeout
+                    && state.open - evicted.len() > settings.min_sessions
+                {
+                    evicted.push(idle.name);
+                } else if now >= idle.last_used + settings.keepalive_interval {
+                    pinged.push(idle);
+                } else {
+                    kept.push_back(idle);
+                }
+            }
+            *queue = kept;
+        }
+        state.open -= evicted.len();
+        for name in evicted {
+            self.delete(name);
+        }
+        for mut idle in pinged {
+            let inner = self.clone();
+            spawn(async move {
+                let result = inner.client().get_session(idle.name.clone()).await;
+                match result {
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        let mut state = inner.state.lock().unwrap();
+                        state.open -= 1;
+                        inner.replenish(&mut state);
+                    }
+                    _ => {
+                        idle.last_used = Instant::now();
+                        inner.release(idle);
+                    }
+                }
+            });
+        }
+        self.replenish(state);
+    }
+}
+
+// Spawns the task if in a Tokio runtime, which `Drop` may not be.
+fn spawn(task: impl Future<Output = ()> + Send + 'static) {
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(task);
+    }
+}
+
+async fn maintain<C: SessionClient>(inner: Weak<Inner<C>>) {
+    let mut interval =
+        time::interval_at(Instant::now() + MAINTENANCE_INTERVAL, MAINTENANCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match inner.upgrade() {
+            Some(inner) => inner.maintain(),
+            None => return,
+        }
+    }
+}
+
+/// A session checked out of a [`SessionPool`], returned to it when dropped.
+pub struct PooledSession<C: SessionClient> {
+    name: String,
+    transaction: Option<Vec<u8>>,
+    pool: Arc<Inner<C>>,
+    discarded: bool,
+}
+
+impl<C: SessionClient> fmt::Debug for PooledSession<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledSession")
+            .field("name", &self.name)
+            .field("transaction", &self.transaction)
+            .finish()
+    }
+}
+
+impl<C: SessionClient> PooledSession<C> {
+    /// The name of the session, for the `session` field of requests.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Takes the id of the read-write transaction begun for the session by
+    /// [`SessionPool::get_write`].
+    pub fn take_transaction(&mut self) -> Option<Vec<u8>> {
+        self.transaction.take()
+    }
+
+    /// Deletes the session instead of returning it to the pool, for instance after a
+    /// `NOT_FOUND` error on it.
+    pub fn discard(mut self) {
+        self.discarded = true;
+    }
+}
+
+impl<C: SessionClient> Drop for PooledSession<C> {
+    fn drop(&mut self) {
+        let name = std::mem::take(&mut self.name);
+        if self.discarded {
+            let mut state = self.pool.state.lock().unwrap();
+            state.open -= 1;
+            self.pool.replenish(&mut state);
+            drop(state);
+            self.pool.delete(name);
+            return;
+        }
+        self.pool.release(Idle {
+            name,
+            transaction: self.transaction.take(),
+            last_used: Instant::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[derive(Debug, Default)]
+    struct FakeState {
+        sessions: HashSet<String>,
+        created: usize,
+        requests: Vec<BatchCreateSessionsRequest>,
+        pings: Vec<String>,
+        begun: Vec<String>,
+        failures: VecDeque<tonic::Status>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Fake {
+        state: Arc<Mutex<FakeState>>,
+    }
+
+    impl Fake {
+        fn with<T>(&self, f: impl FnOnce(&mut FakeState) -> T) -> T {
+            f(&mut self.state.lock().unwrap())
+        }
+
+        #[allow(clippy::result_large_err)]
+        fn found(&self, name: &str) -> Result<(), tonic::Status> {
+            let mut state = self.state.lock().unwrap();
+            if let Some(status) = state.failures.pop_front() {
+                return Err(status);
+            }
+            if state.sessions.contains(name) {
+                Ok(())
+            } else {
+                Err(tonic::Status::not_found("session not found"))
+            }
+        }
+    }
+
+    impl SessionClient for Fake {
+        fn batch_create_sessions(
+            &mut self,
+            request: BatchCreateSessionsRequest,
+        ) -> SpannerFuture<'_, Vec<Session>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                if let Some(status) = state.failures.pop_front() {
+                    return Err(status);
+                }
+                let mut sessions = Vec::new();
+                for _ in 0..request.session_count {
+                    state.created += 1;
+                    let name = format!("{}/sessions/{}", request.database, state.created);
+                    state.sessions.insert(name.clone());
+                    sessions.push(Session {
+                        name,
+                        ..Default::default()
+                    });
+                }
+                state.requests.push(request);
+                Ok(sessions)
+            })
+        }
+
+        fn get_session(&mut self, name: String) -> SpannerFuture<'_, Session> {
+            Box::pin(async move {
+                self.found(&name)?;
+                self.with(|s| s.pings.push(name.clone()));
+                Ok(Session {
+                    name,
+                    ..Default::default()
+                })
+            })
+        }
+
+        fn delete_session(&mut self, name: String) -> SpannerFuture<'_, ()> {
+            Box::pin(async move {
+                self.found(&name)?;
+                self.with(|s| s.sessions.remove(&name));
+                Ok(())
+            })
+        }
+
+        fn begin_transaction(
+            &mut self,
+            request: BeginTransactionRequest,
+        ) -> SpannerFuture<'_, Transaction> {
+            Box::pin(async move {
+                self.found(&request.session)?;
+                self.with(|s| s.begun.push(request.session.clone()));
+                Ok(Transaction {
+                    id: request.session.into_bytes(),
+                    read_timestamp: None,
+                })
+            })
+        }
+    }
+
+    fn settings(min_sessions: usize, max_sessions: usize) -> SessionPoolSettings {
+        SessionPoolSettings {
+            min_sessions,
+            max_sessions,
+            sessions_per_batch: 2,
+            write_sessions: 0.0,
+            checkout_timeout: Duration::from_secs(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_checkout() {
+        let fake = Fake::default();
+        let mut settings = settings(2, 3);
+        settings.labels.insert("env".to_owned(), "test".to_owned());
+        let pool = SessionPool::with_settings(fake.clone(), "db", settings);
+        let a = pool.get().await.unwrap();
+        let b = pool.get().await.unwrap();
+        assert_eq!(pool.open_sessions(), 2);
+        // A batch is capped at the maximum number of sessions.
+        let c = pool.get().await.unwrap();
+        assert_eq!(pool.open_sessions(), 3);
+        let counts = fake.with(|s| {
+            let requests = s.requests.iter();
+            requests.map(|r| r.session_count).collect::<Vec<_>>()
+        });
+        assert_eq!(counts, vec![2, 1]);
+        let labels = fake.with(|s| s.requests[0].session_template.clone().unwrap().labels);
+        assert_eq!(labels["env"], "test");
+
+        let start = Instant::now();
+        assert!(matches!(pool.get().await, Err(SessionError::Timeout)));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        // A returned session goes to the waiting checkout.
+        let name = a.name().to_owned();
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.get().await.unwrap().name().to_owned() }
+        });
+        time::sleep(Duration::from_millis(10)).await;
+        drop(a);
+        assert_eq!(waiting.await.unwrap(), name);
+        assert_eq!(pool.idle_sessions(), 1);
+        drop((b, c));
+        assert_eq!(pool.idle_sessions(), 3);
+
+        pool.close().await;
+        assert!(fake.with(|s| s.sessions.is_empty()));
+        assert_eq!(pool.open_sessions(), 0);
+        assert!(matches!(pool.get().await, Err(SessionError::Closed)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_write_sessions() {
+        let fake = Fake::default();
+        let mut settings = settings(4, 4);
+        settings.write_sessions = 0.5;
+        let pool = SessionPool::with_settings(fake.clone(), "db", settings);
+        time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(fake.with(|s| s.begun.len()), 2);
+
+        let mut write = pool.get_write().await.unwrap();
+        let id = write.take_transaction().unwrap();
+        assert_eq!(id, write.name().as_bytes());
+        // Read checkouts take the sessions without transaction first.
+        let read = pool.get().await.unwrap();
+        assert!(!fake.with(|s| s.begun.contains(&read.name().to_owned())));
+        let _second = pool.get_write().await.unwrap();
+        // The transaction is begun on checkout when no session has one.
+        let mut third = pool.get_write().await.unwrap();
+        assert!(third.take_transaction().is_some());
+        assert_eq!(fake.with(|s| s.begun.len()), 3);
+        drop(read);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_maintenance() {
+        let fake = Fake::default();
+        let mut settings = settings(1, 3);
+        settings.idle_timeout = Duration::from_secs(60);
+        settings.keepalive_interval = Duration::from_secs(120);
+        let pool = SessionPool::with_settings(fake.clone(), "db", settings);
+        let sessions = vec![
+            pool.get().await.unwrap(),
+            pool.get().await.unwrap(),
+            pool.get().await.unwrap(),
+        ];
+        drop(sessions);
+        assert_eq!(pool.idle_sessions(), 3);
+
+        // Sessions beyond the minimum are deleted once idle for long.
+        time::sleep(Duration::from_secs(70)).await;
+        assert_eq!(pool.open_sessions(), 1);
+        assert_eq!(fake.with(|s| s.sessions.len()), 1);
+        // The others are pinged.
+        time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(fake.with(|s| s.pings.len()), 1);
+
+        // Sessions deleted by Spanner are replaced.
+        fake.with(|s| s.sessions.clear());
+        time::sleep(Duration::from_secs(130)).await;
+        assert_eq!(pool.open_sessions(), 1);
+        let session = pool.get().await.unwrap();
+        assert!(fake.with(|s| s.sessions.contains(session.name())));
+
+        // So are discarded sessions.
+        session.discard();
+        time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.open_sessions(), 1);
+        assert_eq!(fake.with(|s| s.sessions.len()), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_errors() {
+        let fake = Fake::default();
+        fake.with(|s| {
+            s.failures
+                .push_back(tonic::Status::permission_denied("denied"))
+        });
+        let pool = SessionPool::with_settings(fake.clone(), "db", settings(0, 2));
+        match pool.get().await {
+            Err(SessionError::Status(s)) => assert_eq!(s.code(), tonic::Code::PermissionDenied),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(pool.open_sessions(), 0);
+        assert!(pool.get().await.is_ok());
+    }
+}
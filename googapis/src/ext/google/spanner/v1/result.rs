@@ -0,0 +1,701 @@
+use std::{
+    collections::VecDeque, error, fmt, future::poll_fn, mem, pin::Pin, sync::Arc, time::Duration,
+};
+
+use prost_types::{value::Kind, ListValue, Value};
+use tokio::time;
+use tonic::{codegen::futures_core::Stream, Code};
+
+use super::{
+    spanner_client::SpannerClient, struct_type::Field, ExecuteSqlRequest, PartialResultSet,
+    ReadRequest, ResultSetMetadata, ResultSetStats, Row, SpannerFuture,
+};
+
+/// The stream of `PartialResultSet` of an `ExecuteStreamingSql` or `StreamingRead` call.
+pub type PartialResultSetStream =
+    Pin<Box<dyn Stream<Item = Result<PartialResultSet, tonic::Status>> + Send>>;
+
+/// A client of the streaming query methods of the `google.spanner.v1.Spanner` service,
+/// implemented by `SpannerClient`.
+pub trait QueryClient: Clone + Send + 'static {
+    fn execute_streaming_sql(
+        &mut self,
+        request: ExecuteSqlRequest,
+    ) -> SpannerFuture<'_, PartialResultSetStream>;
+
+    fn streaming_read(&mut self, request: ReadRequest)
+        -> SpannerFuture<'_, PartialResultSetStream>;
+}
+
+impl<T> QueryClient for SpannerClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn execute_streaming_sql(
+        &mut self,
+        request: ExecuteSqlRequest,
+    ) -> SpannerFuture<'_, PartialResultSetStream> {
+        Box::pin(async move {
+            let stream = SpannerClient::execute_streaming_sql(self, request).await?;
+            Ok(Box::pin(stream.into_inner()) as PartialResultSetStream)
+        })
+    }
+
+    fn streaming_read(
+        &mut self,
+        request: ReadRequest,
+    ) -> SpannerFuture<'_, PartialResultSetStream> {
+        Box::pin(async move {
+            let stream = SpannerClient::streaming_read(self, request).await?;
+            Ok(Box::pin(stream.into_inner()) as PartialResultSetStream)
+        })
+    }
+}
+
+/// An error returned by a [`ResultStream`].
+#[derive(Debug)]
+pub enum ResultSetError {
+    /// The call failed, and could not be resumed.
+    Status(tonic::Status),
+    /// The `PartialResultSet` messages do not form a result set, e.g. the first one has no
+    /// metadata, or the last one ends within a row.
+    Malformed(String),
+}
+
+impl fmt::Display for ResultSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultSetError::Status(status) => write!(f, "query failed: {}", status),
+            ResultSetError::Malformed(reason) => write!(f, "malformed result set: {}", reason),
+        }
+    }
+}
+
+impl error::Error for ResultSetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ResultSetError::Status(status) => Some(status),
+            ResultSetError::Malformed(_) => None,
+        }
+    }
+}
+
+impl From<tonic::Status> for ResultSetError {
+    fn from(status: tonic::Status) -> Self {
+        ResultSetError::Status(status)
+    }
+}
+
+// Messages received since the last resume token are buffered, so that they can be dropped when
+// the call is resumed from it. Past this many, they are merged and the call cannot be resumed
+// until the next resume token.
+const MAX_BUFFERED: usize = 512;
+// The call is given up after this many consecutive failures without receiving a message.
+const MAX_ATTEMPTS: u32 = 8;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(32);
+
+type OnStatus = Box<dyn FnMut(&tonic::Status) + Send>;
+
+#[derive(Debug, Clone)]
+enum Query {
+    Sql(ExecuteSqlRequest),
+    Read(ReadRequest),
+}
+
+/// The rows of an `ExecuteStreamingSql` or `StreamingRead` call.
+///
+/// Values split across `PartialResultSet` messages by `chunked_value` are merged, and the values
+/// are grouped into rows by the `row_type` of the metadata. When the call fails with
+/// `UNAVAILABLE`, it is resumed from the last `resume_token` with an exponential backoff, and the
+/// values received after that token are dropped, as Spanner sends them again.
+///
+/// # Example
+/// ```ignore
+/// let request = ExecuteSqlRequest {
+///     session: session.name().to_owned(),
+///     sql: "SELECT SingerId, FirstName FROM Singers".to_owned(),
+///     ..Default::default()
+/// };
+/// let mut rows = ResultStream::execute_sql(client, request);
+/// while let Some(row) = rows.next().await {
+///     let (id, name): (i64, Option<String>) = row?.decode()?;
+/// }
+/// ```
+pub struct ResultStream<C> {
+    client: C,
+    query: Query,
+    stream: Option<PartialResultSetStream>,
+    metadata: Option<ResultSetMetadata>,
+    stats: Option<ResultSetStats>,
+    fields: Option<Arc<[Field]>>,
+    buffered: Vec<PartialResultSet>,
+    // Whether no message was merged since the last resume token.
+    resumable: bool,
+    merger: Merger,
+    rows: VecDeque<Row>,
+    attempts: u32,
+    done: bool,
+    // Sees the error the call failed with, e.g. for a transaction to learn it was aborted.
+    on_status: Option<OnStatus>,
+}
+
+impl<C: QueryClient> ResultStream<C> {
+    /// Streams the rows of an `ExecuteStreamingSql` call.
+    pub fn execute_sql(client: C, request: ExecuteSqlRequest) -> Self {
+        Self::new(client, Query::Sql(request))
+    }
+
+    /// Streams the rows of a `StreamingRead` call.
+    pub fn read(client: C, request: ReadRequest) -> Self {
+        Self::new(client, Query::Read(request))
+    }
+
+    fn new(client: C, query: Query) -> Self {
+        Self {
+            client,
+            query,
+            stream: None,
+            metadata: None,
+            stats: None,
+            fields: None,
+            buffered: Vec::new(),
+            resumable: true,
+            merger: Merger::default(),
+            rows: VecDeque::new(),
+            attempts: 0,
+            done: false,
+            on_status: None,
+        }
+    }
+
+    pub(super) fn on_status(&mut self, f: impl FnMut(&tonic::Status) + Send + 'static) {
+        self.on_status = Some(Box::new(f));
+    }
+
+    /// Returns the metadata of the result set, once the first message is received, e.g. with the
+    /// transaction begun by the call.
+    pub fn metadata(&self) -> Option<&ResultSetMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Returns the statistics of the result set, sent with the last message, e.g. the number of
+    /// rows modified by a DML statement.
+    pub fn stats(&self) -> Option<&ResultSetStats> {
+        self.stats.as_ref()
+    }
+
+    /// Returns the next row, or `None` once all rows have been returned or an error was.
+    pub async fn next(&mut self) -> Option<Result<Row, ResultSetError>> {
+        loop {
+            if let Some(row) = self.rows.pop_front() {
+                return Some(Ok(row));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.advance().await {
+                return Some(Err(self.fail(e)));
+            }
+        }
+    }
+
+    // Receives messages until the metadata is, e.g. to learn the transaction begun by the call.
+    pub(super) async fn wait_metadata(&mut self) -> Result<(), ResultSetError> {
+        while self.metadata.is_none() && !self.done {
+            if let Err(e) = self.advance().await {
+                return Err(self.fail(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, e: ResultSetError) -> ResultSetError {
+        self.done = true;
+        self.stream = None;
+        if let (ResultSetError::Status(status), Some(f)) = (&e, &mut self.on_status) {
+            f(status);
+        }
+        e
+    }
+
+    // Receives the next message, opening or resuming the call if needed.
+    async fn advance(&mut self) -> Result<(), ResultSetError> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let opened = match &self.query {
+                    Query::Sql(request) => self.client.execute_streaming_sql(request.clone()).await,
+                    Query::Read(request) => self.client.streaming_read(request.clone()).await,
+                };
+                match opened {
+                    Ok(stream) => self.stream.insert(stream),
+                    Err(status) => return self.retry(status).await,
+                }
+            }
+        };
+        match poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            Some(Ok(set)) => {
+                self.attempts = 0;
+                self.receive(set).map_err(ResultSetError::Malformed)
+            }
+            Some(Err(status)) => {
+                self.stream = None;
+                self.retry(status).await
+            }
+            None => {
+                self.stream = None;
+                self.done = true;
+                self.merge_buffered()
+                    .and_then(|()| self.merger.finish())
+                    .map_err(ResultSetError::Malformed)
+            }
+        }
+    }
+
+    fn receive(&mut self, mut set: PartialResultSet) -> Result<(), String> {
+        if self.metadata.is_none() {
+            let metadata = set
+                .metadata
+                .take()
+                .ok_or_else(|| "the first message has no metadata".to_owned())?;
+            let fields = metadata.row_type.clone().unwrap_or_default().fields;
+            self.fields = Some(fields.into());
+            self.metadata = Some(metadata);
+        }
+        if set.stats.is_some() {
+            self.stats = set.stats.take();
+        }
+
+        let token = (!set.resume_token.is_empty()).then(|| set.resume_token.clone());
+        self.buffered.push(set);
+        if let Some(token) = token {
+            self.merge_buffered()?;
+            self.resumable = true;
+            match &mut self.query {
+                Query::Sql(request) => request.resume_token = token,
+                Query::Read(request) => request.resume_token = token,
+            }
+        } else if self.buffered.len() > MAX_BUFFERED {
+            self.merge_buffered()?;
+            self.resumable = false;
+        }
+        Ok(())
+    }
+
+    fn merge_buffered(&mut self) -> Result<(), String> {
+        let fields = match &self.fields {
+            Some(fields) => fields,
+            None => return Ok(()),
+        };
+        for set in self.buffered.drain(..) {
+            self.merger.push(set.values, set.chunked_value)?;
+            for values in self.merger.rows(fields.len())? {
+                self.rows.push_back(Row::new(fields.clone(), values));
+            }
+        }
+        Ok(())
+    }
+
+    // Resumes the call after `UNAVAILABLE`, dropping the messages received since the last resume
+    // token.
+    async fn retry(&mut self, status: tonic::Status) -> Result<(), ResultSetError> {
+        if status.code() != Code::Unavailable || !self.resumable || self.attempts >= MAX_ATTEMPTS {
+            return Err(status.into());
+        }
+        let backoff = INITIAL_BACKOFF * 2u32.pow(self.attempts);
+        time::sleep(backoff.min(MAX_BACKOFF)).await;
+        self.attempts += 1;
+        self.buffered.clear();
+        Ok(())
+    }
+}
+
+impl<C> fmt::Debug for ResultStream<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultStream")
+            .field("query", &self.query)
+            .field("metadata", &self.metadata)
+            .field("rows", &self.rows.len())
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+// Merges chunked values, and groups values into rows.
+#[derive(Debug, Default)]
+struct Merger {
+    values: Vec<Value>,
+    // The last value of the previous message, to be completed by the first of the next one.
+    chunk: Option<Value>,
+}
+
+impl Merger {
+    fn push(&mut self, values: Vec<Value>, chunked_value: bool) -> Result<(), String> {
+        let mut values = values.into_iter();
+        if let Some(chunk) = self.chunk.take() {
+            let next = values
+                .next()
+                .ok_or_else(|| "a chunked value is not continued".to_owned())?;
+            self.values.push(merge(chunk, next)?);
+        }
+        self.values.extend(values);
+        if chunked_value {
+            self.chunk = Some(
+                self.values
+                    .pop()
+                    .ok_or_else(|| "a message without values is chunked".to_owned())?,
+            );
+        }
+        Ok(())
+    }
+
+    // Takes the complete rows of `width` values.
+    fn rows(&mut self, width: usize) -> Result<Vec<Vec<Value>>, String> {
+        if width == 0 {
+            if !self.values.is_empty() {
+                return Err("values without columns".to_owned());
+            }
+            return Ok(Vec::new());
+        }
+        let complete = self.values.len() / width * width;
+        let rest = self.values.split_off(complete);
+        let mut values = mem::replace(&mut self.values, rest).into_iter();
+        Ok((0..complete / width)
+            .map(|_| values.by_ref().take(width).collect())
+            .collect())
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        if self.chunk.is_some() || !self.values.is_empty() {
+            return Err("the last message ends within a row".to_owned());
+        }
+        Ok(())
+    }
+}
+
+// Merges a chunked value with the first value of the next message: strings are concatenated,
+// and lists, which also encode structs, are concatenated after merging the last element of the
+// first one with the first element of the second one if they are both strings or both lists.
+fn merge(chunk: Value, next: Value) -> Result<Value, String> {
+    let kind = match (chunk.kind, next.kind) {
+        (Some(Kind::StringValue(mut a)), Some(Kind::StringValue(b))) => {
+            a.push_str(&b);
+            Kind::StringValue(a)
+        }
+        (Some(Kind::ListValue(mut a)), Some(Kind::ListValue(b))) => {
+            let mut rest = b.values.into_iter();
+            if let Some(first) = rest.next() {
+                match a.values.pop() {
+                    Some(last) if mergeable(&last, &first) => a.values.push(merge(last, first)?),
+                    Some(last) => a.values.extend([last, first]),
+                    None => a.values.push(first),
+                }
+            }
+            a.values.extend(rest);
+            Kind::ListValue(ListValue { values: a.values })
+        }
+        (a, b) => return Err(format!("cannot merge {:?} with {:?}", a, b)),
+    };
+    Ok(Value { kind: Some(kind) })
+}
+
+fn mergeable(a: &Value, b: &Value) -> bool {
+    matches!(
+        (&a.kind, &b.kind),
+        (Some(Kind::StringValue(_)), Some(Kind::StringValue(_)))
+            | (Some(Kind::ListValue(_)), Some(Kind::ListValue(_)))
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+mod tests {
+    use std::{
+        sync::Mutex,
+        task::{Context, Poll},
+    };
+
+    use super::{
+        super::{struct_type, StructType, Type, TypeCode},
+        *,
+    };
+
+    fn string(s: &str) -> Value {
+        Value {
+            kind: Some(Kind::StringValue(s.to_owned())),
+        }
+    }
+
+    fn number(n: f64) -> Value {
+        Value {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    fn list(values: Vec<Value>) -> Value {
+        Value {
+            kind: Some(Kind::ListValue(ListValue { values })),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            merge(string("foo"), string("bar")).unwrap(),
+            string("foobar")
+        );
+        assert_eq!(
+            merge(
+                list(vec![string("a"), string("b")]),
+                list(vec![string("c"), string("d")])
+            )
+            .unwrap(),
+            list(vec![string("a"), string("bc"), string("d")])
+        );
+        assert_eq!(
+            merge(
+                list(vec![string("a"), list(vec![string("b"), string("c")])]),
+                list(vec![list(vec![string("d")]), string("e")])
+            )
+            .unwrap(),
+            list(vec![
+                string("a"),
+                list(vec![string("b"), string("cd")]),
+                string("e")
+            ])
+        );
+        assert_eq!(
+            merge(
+                list(vec![number(2.0), number(3.0)]),
+                list(vec![number(4.0)])
+            )
+            .unwrap(),
+            list(vec![number(2.0), number(3.0), number(4.0)])
+        );
+        assert!(merge(string("a"), list(vec![])).is_err());
+        assert!(merge(number(1.0), number(2.0)).is_err());
+    }
+
+    struct Scripted(VecDeque<Result<PartialResultSet, tonic::Status>>);
+
+    impl Stream for Scripted {
+        type Item = Result<PartialResultSet, tonic::Status>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    type Messages = Vec<Result<PartialResultSet, tonic::Status>>;
+
+    // Answers each call with the next list of messages.
+    #[derive(Clone, Default)]
+    struct Fake {
+        calls: Arc<Mutex<VecDeque<Messages>>>,
+        tokens: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl Fake {
+        fn new(calls: Vec<Messages>) -> Self {
+            Self {
+                calls: Arc::new(Mutex::new(calls.into())),
+                tokens: Arc::default(),
+            }
+        }
+
+        fn open(&self, token: Vec<u8>) -> SpannerFuture<'_, PartialResultSetStream> {
+            self.tokens.lock().unwrap().push(token);
+            let messages = self.calls.lock().unwrap().pop_front().unwrap_or_default();
+            Box::pin(
+                async move { Ok(Box::pin(Scripted(messages.into())) as PartialResultSetStream) },
+            )
+        }
+    }
+
+    impl QueryClient for Fake {
+        fn execute_streaming_sql(
+            &mut self,
+            request: ExecuteSqlRequest,
+        ) -> SpannerFuture<'_, PartialResultSetStream> {
+            self.open(request.resume_token)
+        }
+
+        fn streaming_read(
+            &mut self,
+            request: ReadRequest,
+        ) -> SpannerFuture<'_, PartialResultSetStream> {
+            self.open(request.resume_token)
+        }
+    }
+
+    fn metadata() -> ResultSetMetadata {
+        let field = |name: &str, code: TypeCode| struct_type::Field {
+            name: name.to_owned(),
+            r#type: Some(Type {
+                code: code as i32,
+                ..Type::default()
+            }),
+        };
+        ResultSetMetadata {
+            row_type: Some(StructType {
+                fields: vec![
+                    field("Id", TypeCode::Int64),
+                    field("Name", TypeCode::String),
+                ],
+            }),
+            ..ResultSetMetadata::default()
+        }
+    }
+
+    fn message(values: Vec<Value>, chunked_value: bool, token: &str) -> PartialResultSet {
+        PartialResultSet {
+            values,
+            chunked_value,
+            resume_token: token.as_bytes().to_vec(),
+            ..PartialResultSet::default()
+        }
+    }
+
+    fn first(values: Vec<Value>, chunked_value: bool, token: &str) -> PartialResultSet {
+        PartialResultSet {
+            metadata: Some(metadata()),
+            ..message(values, chunked_value, token)
+        }
+    }
+
+    async fn collect<C: QueryClient>(
+        stream: &mut ResultStream<C>,
+    ) -> Result<Vec<(i64, String)>, ResultSetError> {
+        let mut rows = Vec::new();
+        while let Some(row) = stream.next().await {
+            rows.push(row?.decode().unwrap());
+        }
+        Ok(rows)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_result_stream() {
+        let fake = Fake::new(vec![vec![
+            Ok(first(vec![string("1"), string("Mar")], true, "")),
+            Ok(message(vec![string("c"), string("2")], false, "")),
+            Ok(PartialResultSet {
+                stats: Some(ResultSetStats::default()),
+                ..message(vec![string("Catalina")], false, "")
+            }),
+        ]]);
+        let mut stream = ResultStream::execute_sql(fake, ExecuteSqlRequest::default());
+
+        assert_eq!(
+            collect(&mut stream).await.unwrap(),
+            vec![(1, "Marc".to_owned()), (2, "Catalina".to_owned())]
+        );
+        assert_eq!(stream.metadata(), Some(&metadata()));
+        assert!(stream.stats().is_some());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_result_stream_resume() {
+        let unavailable = || Err(tonic::Status::unavailable("unavailable"));
+        let fake = Fake::new(vec![
+            vec![unavailable()],
+            vec![
+                Ok(first(vec![string("1"), string("Ma")], true, "a")),
+                // Dropped, as received after the last resume token.
+                Ok(message(vec![string("rc"), string("2")], false, "")),
+                unavailable(),
+            ],
+            vec![
+                Ok(first(vec![string("rc")], false, "b")),
+                Ok(message(vec![string("2"), string("Catalina")], false, "")),
+            ],
+        ]);
+        let mut stream = ResultStream::read(fake.clone(), ReadRequest::default());
+
+        assert_eq!(
+            collect(&mut stream).await.unwrap(),
+            vec![(1, "Marc".to_owned()), (2, "Catalina".to_owned())]
+        );
+        assert_eq!(
+            *fake.tokens.lock().unwrap(),
+            vec![b"".to_vec(), b"".to_vec(), b"a".to_vec()]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_result_stream_errors() {
+        // Not retryable.
+        let fake = Fake::new(vec![vec![
+            Ok(first(vec![string("1"), string("Marc")], false, "a")),
+            Err(tonic::Status::invalid_argument("invalid")),
+        ]]);
+        let mut stream = ResultStream::execute_sql(fake.clone(), ExecuteSqlRequest::default());
+        assert!(matches!(stream.next().await, Some(Ok(_))));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(ResultSetError::Status(s))) if s.code() == Code::InvalidArgument
+        ));
+        assert!(stream.next().await.is_none());
+
+        // Ends within a row.
+        let fake = Fake::new(vec![vec![Ok(first(vec![string("1")], false, ""))]]);
+        let mut stream = ResultStream::execute_sql(fake, ExecuteSqlRequest::default());
+        assert!(matches!(
+            collect(&mut stream).await,
+            Err(ResultSetError::Malformed(_))
+        ));
+
+        // No metadata.
+        let fake = Fake::new(vec![vec![Ok(message(vec![], false, ""))]]);
+        let mut stream = ResultStream::execute_sql(fake, ExecuteSqlRequest::default());
+        assert!(matches!(
+            collect(&mut stream).await,
+            Err(ResultSetError::Malformed(_))
+        ));
+
+        // Gives up after consecutive failures.
+        let calls = (0..=MAX_ATTEMPTS)
+            .map(|_| vec![Err(tonic::Status::unavailable("unavailable"))])
+            .collect();
+        let fake = Fake::new(calls);
+        let mut stream = ResultStream::execute_sql(fake.clone(), ExecuteSqlRequest::default());
+        assert!(matches!(
+            collect(&mut stream).await,
+            Err(ResultSetError::Status(s)) if s.code() == Code::Unavailable
+        ));
+        assert_eq!(fake.tokens.lock().unwrap().len(), MAX_ATTEMPTS as usize + 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_result_stream_not_resumable() {
+        // Rows merged past the buffer limit cannot be received again.
+        let mut messages = vec![Ok(first(vec![], false, ""))];
+        messages.extend((0..=MAX_BUFFERED).map(|i| {
+            Ok(message(
+                vec![string(&i.to_string()), string("x")],
+                false,
+                "",
+            ))
+        }));
+        messages.push(Err(tonic::Status::unavailable("unavailable")));
+        let fake = Fake::new(vec![messages]);
+        let mut stream = ResultStream::execute_sql(fake.clone(), ExecuteSqlRequest::default());
+
+        let mut received = 0;
+        let err = loop {
+            match stream.next().await {
+                Some(Ok(_)) => received += 1,
+                Some(Err(e)) => break e,
+                None => panic!("ended without an error"),
+            }
+        };
+        assert_eq!(received, MAX_BUFFERED);
+        assert!(matches!(err, ResultSetError::Status(s) if s.code() == Code::Unavailable));
+        assert_eq!(fake.tokens.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,834 @@
+use std::{error, fmt, sync::Arc};
+
+use prost_types::{value::Kind, Timestamp, Value};
+
+use super::{struct_type::Field, Type, TypeCode};
+#[cfg(any(feature = "chrono", feature = "time"))]
+use crate::wkt::days_from_civil;
+use crate::{base64, wkt::TimestampExt};
+
+/// An error returned when a [`Row`] or one of its values cannot be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The row has no column of this name, or not that many columns.
+    NoSuchColumn(String),
+    /// The value of a column cannot be decoded.
+    Column {
+        column: String,
+        source: Box<DecodeError>,
+    },
+    /// The value is `NULL`, and the target type is not an `Option`.
+    Null,
+    /// Values of the Spanner type cannot be decoded as the target type.
+    TypeMismatch {
+        code: TypeCode,
+        target: &'static str,
+    },
+    /// The value does not match its type, e.g. an `INT64` that is not a decimal string.
+    Invalid { code: TypeCode, value: String },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NoSuchColumn(column) => write!(f, "no such column: {:?}", column),
+            DecodeError::Column { column, source } => write!(f, "column {:?}: {}", column, source),
+            DecodeError::Null => f.write_str("unexpected NULL value"),
+            DecodeError::TypeMismatch { code, target } => {
+                write!(f, "cannot decode {} as {}", type_name(*code), target)
+            }
+            DecodeError::Invalid { code, value } => {
+                write!(f, "invalid {} value: {:?}", type_name(*code), value)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DecodeError::Column { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+// The name of a type code in GoogleSQL.
+fn type_name(code: TypeCode) -> &'static str {
+    match code {
+        TypeCode::Unspecified => "TYPE_CODE_UNSPECIFIED",
+        TypeCode::Bool => "BOOL",
+        TypeCode::Int64 => "INT64",
+        TypeCode::Float64 => "FLOAT64",
+        TypeCode::Timestamp => "TIMESTAMP",
+        TypeCode::Date => "DATE",
+        TypeCode::String => "STRING",
+        TypeCode::Bytes => "BYTES",
+        TypeCode::Array => "ARRAY",
+        TypeCode::Struct => "STRUCT",
+        TypeCode::Numeric => "NUMERIC",
+        TypeCode::Json => "JSON",
+    }
+}
+
+/// A column of a [`Row`], by position or by name.
+pub trait ColumnIndex: fmt::Display {
+    /// Returns the position of the column among the fields of the row.
+    fn index_in(&self, fields: &[Field]) -> Option<usize>;
+}
+
+impl ColumnIndex for usize {
+    fn index_in(&self, fields: &[Field]) -> Option<usize> {
+        Some(*self).filter(|&i| i < fields.len())
+    }
+}
+
+/// The first column of that name, as Spanner allows duplicate column names.
+impl ColumnIndex for str {
+    fn index_in(&self, fields: &[Field]) -> Option<usize> {
+        fields.iter().position(|f| f.name == self)
+    }
+}
+
+impl ColumnIndex for String {
+    fn index_in(&self, fields: &[Field]) -> Option<usize> {
+        self.as_str().index_in(fields)
+    }
+}
+
+impl<T: ColumnIndex + ?Sized> ColumnIndex for &T {
+    fn index_in(&self, fields: &[Field]) -> Option<usize> {
+        (**self).index_in(fields)
+    }
+}
+
+/// A row of a result set, or a `STRUCT` value: a list of values typed by the fields of the
+/// `row_type` of the `ResultSetMetadata`, or of the `StructType`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    fields: Arc<[Field]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Creates a row whose values are typed by the fields at the same position. Rows of the same
+    /// result set share their fields.
+    pub fn new(fields: Arc<[Field]>, values: Vec<Value>) -> Self {
+        Self { fields, values }
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the values as encoded by Spanner, e.g. `INT64` values as strings.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Decodes the value of a column, by position or by name.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let id: i64 = row.get("SingerId")?;
+    /// let name: Option<String> = row.get(1)?;
+    /// ```
+    pub fn get<T: FromValue, I: ColumnIndex>(&self, column: I) -> Result<T, DecodeError> {
+        let (field, value) = column
+            .index_in(&self.fields)
+            .and_then(|i| Some((&self.fields[i], self.values.get(i)?)))
+            .ok_or_else(|| DecodeError::NoSuchColumn(column.to_string()))?;
+        let ty = field.r#type.clone().unwrap_or_default();
+        T::from_value(value, &ty).map_err(|e| DecodeError::Column {
+            column: column.to_string(),
+            source: Box::new(e),
+        })
+    }
+
+    /// Decodes the whole row, e.g. as a tuple or a struct implementing [`FromRow`].
+    pub fn decode<T: FromRow>(&self) -> Result<T, DecodeError> {
+        T::from_row(self)
+    }
+}
+
+/// A type that can be decoded from a value of a row, given its Spanner [`Type`].
+///
+/// `INT64` values decode as `i64`, `FLOAT64` as `f64`, `BOOL` as `bool`, `BYTES` as `Vec<u8>`,
+/// `TIMESTAMP` as `prost_types::Timestamp`, `ARRAY` as `Vec` and `STRUCT` as [`Row`]. `STRING`,
+/// `INT64`, `NUMERIC`, `JSON`, `DATE` and `TIMESTAMP` values also decode as their canonical
+/// `String`. `NULL` values decode as `None` if the target type is an `Option`, or fail with
+/// [`DecodeError::Null`]. With the `chrono` and `time` features, `DATE` and `TIMESTAMP` values
+/// decode as their dates and UTC date times, with `rust_decimal` `NUMERIC` values decode as
+/// `rust_decimal::Decimal`, and with `serde_json` `JSON` values decode as `serde_json::Value`.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError>;
+}
+
+fn type_code(ty: &Type) -> TypeCode {
+    TypeCode::from_i32(ty.code).unwrap_or(TypeCode::Unspecified)
+}
+
+// Checks the type code, and that the value is not null.
+fn kind<'a>(
+    value: &'a Value,
+    ty: &Type,
+    codes: &[TypeCode],
+    target: &'static str,
+) -> Result<(&'a Kind, TypeCode), DecodeError> {
+    let code = type_code(ty);
+    if !codes.contains(&code) {
+        return Err(DecodeError::TypeMismatch { code, target });
+    }
+    match &value.kind {
+        None | Some(Kind::NullValue(_)) => Err(DecodeError::Null),
+        Some(kind) => Ok((kind, code)),
+    }
+}
+
+fn invalid(code: TypeCode, kind: &Kind) -> DecodeError {
+    let value = match kind {
+        Kind::StringValue(s) => s.clone(),
+        kind => format!("{:?}", kind),
+    };
+    DecodeError::Invalid { code, value }
+}
+
+// Returns the string encoding of a value of one of the `codes`.
+fn string<'a>(
+    value: &'a Value,
+    ty: &Type,
+    codes: &[TypeCode],
+    target: &'static str,
+) -> Result<(&'a str, TypeCode), DecodeError> {
+    match kind(value, ty, codes, target)? {
+        (Kind::StringValue(s), code) => Ok((s, code)),
+        (kind, code) => Err(invalid(code, kind)),
+    }
+}
+
+fn list<'a>(
+    value: &'a Value,
+    ty: &Type,
+    code: TypeCode,
+    target: &'static str,
+) -> Result<&'a [Value], DecodeError> {
+    match kind(value, ty, &[code], target)? {
+        (Kind::ListValue(list), _) => Ok(&list.values),
+        (kind, code) => Err(invalid(code, kind)),
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value, _: &Type) -> Result<Self, DecodeError> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        match value.kind {
+            None | Some(Kind::NullValue(_)) => Ok(None),
+            _ => T::from_value(value, ty).map(Some),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        match kind(value, ty, &[TypeCode::Bool], "bool")? {
+            (Kind::BoolValue(b), _) => Ok(*b),
+            (kind, code) => Err(invalid(code, kind)),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let (s, code) = string(value, ty, &[TypeCode::Int64], "i64")?;
+        s.parse().map_err(|_| DecodeError::Invalid {
+            code,
+            value: s.to_owned(),
+        })
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        match kind(value, ty, &[TypeCode::Float64], "f64")? {
+            (Kind::NumberValue(n), _) => Ok(*n),
+            (Kind::StringValue(s), _) if s == "NaN" => Ok(f64::NAN),
+            (Kind::StringValue(s), _) if s == "Infinity" => Ok(f64::INFINITY),
+            (Kind::StringValue(s), _) if s == "-Infinity" => Ok(f64::NEG_INFINITY),
+            (kind, code) => Err(invalid(code, kind)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let codes = [
+            TypeCode::String,
+            TypeCode::Int64,
+            TypeCode::Numeric,
+            TypeCode::Json,
+            TypeCode::Date,
+            TypeCode::Timestamp,
+        ];
+        string(value, ty, &codes, "String").map(|(s, _)| s.to_owned())
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let (s, code) = string(value, ty, &[TypeCode::Bytes], "Vec<u8>")?;
+        base64::decode_standard(s).ok_or_else(|| DecodeError::Invalid {
+            code,
+            value: s.to_owned(),
+        })
+    }
+}
+
+impl FromValue for Timestamp {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let (s, code) = string(value, ty, &[TypeCode::Timestamp], "Timestamp")?;
+        parse_timestamp(s).ok_or_else(|| DecodeError::Invalid {
+            code,
+            value: s.to_owned(),
+        })
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let values = list(value, ty, TypeCode::Array, "Vec")?;
+        let element = ty
+            .array_element_type
+            .as_deref()
+            .cloned()
+            .unwrap_or_default();
+        values.iter().map(|v| T::from_value(v, &element)).collect()
+    }
+}
+
+impl FromValue for Row {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let values = list(value, ty, TypeCode::Struct, "Row")?;
+        let fields = ty.struct_type.clone().unwrap_or_default().fields;
+        if fields.len() != values.len() {
+            return Err(DecodeError::Invalid {
+                code: TypeCode::Struct,
+                value: format!("{} values for {} fields", values.len(), fields.len()),
+            });
+        }
+        Ok(Row::new(fields.into(), values.to_vec()))
+    }
+}
+
+/// A type that can be decoded from a whole [`Row`], implemented for tuples of [`FromValue`]
+/// types decoded by position, and for structs with [`spanner_from_row!`](crate::spanner_from_row).
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, DecodeError>;
+}
+
+impl FromRow for Row {
+    fn from_row(row: &Row) -> Result<Self, DecodeError> {
+        Ok(row.clone())
+    }
+}
+
+macro_rules! tuple_from_row {
+    ($($index: tt $name: ident),+) => {
+        impl<$($name: FromValue),+> FromRow for ($($name,)+) {
+            fn from_row(row: &Row) -> Result<Self, DecodeError> {
+                Ok(($(row.get::<$name, usize>($index)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(0 A);
+tuple_from_row!(0 A, 1 B);
+tuple_from_row!(0 A, 1 B, 2 C);
+tuple_from_row!(0 A, 1 B, 2 C, 3 D);
+tuple_from_row!(0 A, 1 B, 2 C, 3 D, 4 E);
+tuple_from_row!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+tuple_from_row!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+tuple_from_row!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+
+/// Implements [`FromRow`](crate::google::spanner::v1::FromRow) for a struct, decoding each of
+/// the listed fields from the column of the same name, or of the name given after `=`.
+///
+/// # Example
+/// ```
+/// use googapis::google::spanner::v1::{struct_type::Field, DecodeError, Row, Type, TypeCode};
+/// use prost_types::{value::Kind, Value};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Singer {
+///     id: i64,
+///     first_name: Option<String>,
+/// }
+///
+/// googapis::spanner_from_row!(Singer {
+///     id = "SingerId",
+///     first_name,
+/// });
+///
+/// let field = |name: &str, code: TypeCode| Field {
+///     name: name.to_owned(),
+///     r#type: Some(Type { code: code as i32, ..Type::default() }),
+/// };
+/// let string = |s: &str| Value { kind: Some(Kind::StringValue(s.to_owned())) };
+/// let fields = vec![field("SingerId", TypeCode::Int64), field("first_name", TypeCode::String)];
+/// let row = Row::new(fields.into(), vec![string("1"), string("Marc")]);
+///
+/// let singer: Singer = row.decode()?;
+/// assert_eq!(singer, Singer { id: 1, first_name: Some("Marc".to_owned()) });
+/// # Ok::<(), DecodeError>(())
+/// ```
+#[macro_export]
+macro_rules! spanner_from_row {
+    ($ty: ty { $($field: ident $(= $column: literal)?),* $(,)? }) => {
+        impl $crate::google::spanner::v1::FromRow for $ty {
+            fn from_row(
+                row: &$crate::google::spanner::v1::Row,
+            ) -> ::std::result::Result<Self, $crate::google::spanner::v1::DecodeError> {
+                ::std::result::Result::Ok(Self {
+                    $($field: row.get($crate::__spanner_column!($field $($column)?))?,)*
+                })
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __spanner_column {
+    ($field: ident) => {
+        stringify!($field)
+    };
+    ($field: ident $column: literal) => {
+        $column
+    };
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impl {
+    use chrono::{NaiveDate, TimeZone as _, Utc};
+
+    use super::{parse_date, string, DecodeError, FromValue, Timestamp, Type, TypeCode, Value};
+
+    impl FromValue for NaiveDate {
+        fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+            let (s, code) = string(value, ty, &[TypeCode::Date], "NaiveDate")?;
+            parse_date(s)
+                .and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d))
+                .ok_or_else(|| DecodeError::Invalid {
+                    code,
+                    value: s.to_owned(),
+                })
+        }
+    }
+
+    impl FromValue for chrono::DateTime<Utc> {
+        fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+            let Timestamp { seconds, nanos } = Timestamp::from_value(value, ty)?;
+            Utc.timestamp_opt(seconds, nanos as u32)
+                .single()
+                .ok_or_else(|| DecodeError::Invalid {
+                    code: TypeCode::Timestamp,
+                    value: format!("{}.{:09}", seconds, nanos),
+                })
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impl {
+    use std::convert::TryFrom;
+
+    use time::OffsetDateTime;
+
+    use super::{parse_date, string, DecodeError, FromValue, Timestamp, Type, TypeCode, Value};
+
+    impl FromValue for time::Date {
+        fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+            let (s, code) = string(value, ty, &[TypeCode::Date], "time::Date")?;
+            parse_date(s)
+                .and_then(|(y, m, d)| {
+                    let m = time::Month::try_from(m as u8).ok()?;
+                    time::Date::from_calendar_date(y, m, d as u8).ok()
+                })
+                .ok_or_else(|| DecodeError::Invalid {
+                    code,
+                    value: s.to_owned(),
+                })
+        }
+    }
+
+    impl FromValue for OffsetDateTime {
+        fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+            let Timestamp { seconds, nanos } = Timestamp::from_value(value, ty)?;
+            let t = i128::from(seconds) * 1_000_000_000 + i128::from(nanos);
+            OffsetDateTime::from_unix_timestamp_nanos(t).map_err(|_| DecodeError::Invalid {
+                code: TypeCode::Timestamp,
+                value: format!("{}.{:09}", seconds, nanos),
+            })
+        }
+    }
+}
+
+/// `NUMERIC` values with more than 28 significant digits are invalid.
+#[cfg(feature = "rust_decimal")]
+impl FromValue for rust_decimal::Decimal {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let (s, code) = string(value, ty, &[TypeCode::Numeric], "Decimal")?;
+        rust_decimal::Decimal::from_str_exact(s).map_err(|_| DecodeError::Invalid {
+            code,
+            value: s.to_owned(),
+        })
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl FromValue for serde_json::Value {
+    fn from_value(value: &Value, ty: &Type) -> Result<Self, DecodeError> {
+        let (s, code) = string(value, ty, &[TypeCode::Json], "serde_json::Value")?;
+        serde_json::from_str(s).map_err(|_| DecodeError::Invalid {
+            code,
+            value: s.to_owned(),
+        })
+    }
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn number(b: &[u8]) -> Option<i64> {
+    if b.is_empty() || !b.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(b.iter().fold(0, |n, d| n * 10 + i64::from(d - b'0')))
+}
+
+// Parses a `DATE` value, e.g. `2021-08-24`, as a year, month and day.
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn parse_date(s: &str) -> Option<(i32, u32, u32)> {
+    let b = s.as_bytes();
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' {
+        return None;
+    }
+    let (year, month, day) = (number(&b[..4])?, number(&b[5..7])?, number(&b[8..])?);
+    let days_in_month =
+        days_from_civil(year + month / 12, month % 12 + 1, 1) - days_from_civil(year, month, 1);
+    if !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    Some((year as i32, month as u32, day as u32))
+}
+
+// Parses a `TIMESTAMP` value, in RFC 3339 and UTC, e.g. `2021-08-24T15:04:05.123456Z`.
+fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    if !s.ends_with(['Z', 'z']) {
+        return None;
+    }
+    Timestamp::parse_rfc3339(s).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::ListValue;
+
+    use super::{super::StructType, *};
+
+    fn string(s: &str) -> Value {
+        Value {
+            kind: Some(Kind::StringValue(s.to_owned())),
+        }
+    }
+
+    fn null() -> Value {
+        Value {
+            kind: Some(Kind::NullValue(0)),
+        }
+    }
+
+    fn list(values: Vec<Value>) -> Value {
+        Value {
+            kind: Some(Kind::ListValue(ListValue { values })),
+        }
+    }
+
+    fn ty(code: TypeCode) -> Type {
+        Type {
+            code: code as i32,
+            ..Type::default()
+        }
+    }
+
+    fn field(name: &str, r#type: Type) -> Field {
+        Field {
+            name: name.to_owned(),
+            r#type: Some(r#type),
+        }
+    }
+
+    fn decode<T: FromValue>(value: Value, code: TypeCode) -> Result<T, DecodeError> {
+        T::from_value(&value, &ty(code))
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(decode::<i64>(string("-42"), TypeCode::Int64), Ok(-42));
+        assert_eq!(
+            decode::<i64>(string("9223372036854775807"), TypeCode::Int64),
+            Ok(i64::MAX)
+        );
+        assert_eq!(
+            decode::<String>(string("9223372036854775807"), TypeCode::Int64),
+            Ok("9223372036854775807".to_owned())
+        );
+        assert_eq!(
+            decode::<i64>(string("4.2"), TypeCode::Int64),
+            Err(DecodeError::Invalid {
+                code: TypeCode::Int64,
+                value: "4.2".to_owned()
+            })
+        );
+        let number = Value {
+            kind: Some(Kind::NumberValue(0.5)),
+        };
+        assert_eq!(decode::<f64>(number, TypeCode::Float64), Ok(0.5));
+        assert!(decode::<f64>(string("NaN"), TypeCode::Float64).is_ok_and(f64::is_nan));
+        assert_eq!(
+            decode::<f64>(string("-Infinity"), TypeCode::Float64),
+            Ok(f64::NEG_INFINITY)
+        );
+        let b = Value {
+            kind: Some(Kind::BoolValue(true)),
+        };
+        assert_eq!(decode::<bool>(b, TypeCode::Bool), Ok(true));
+        assert_eq!(
+            decode::<Vec<u8>>(string("aGVsbG8="), TypeCode::Bytes),
+            Ok(b"hello".to_vec())
+        );
+        assert_eq!(
+            decode::<Vec<u8>>(string("aGVsbG8"), TypeCode::Bytes),
+            Err(DecodeError::Invalid {
+                code: TypeCode::Bytes,
+                value: "aGVsbG8".to_owned()
+            })
+        );
+        assert_eq!(
+            decode::<Vec<u8>>(string("aGVsbG8"), TypeCode::String),
+            Err(DecodeError::TypeMismatch {
+                code: TypeCode::String,
+                target: "Vec<u8>"
+            })
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(
+            decode::<Timestamp>(string("2021-08-24T15:04:05.123456Z"), TypeCode::Timestamp),
+            Ok(Timestamp {
+                seconds: 1_629_817_445,
+                nanos: 123_456_000
+            })
+        );
+        assert_eq!(
+            decode::<Timestamp>(string("0001-01-01T00:00:00Z"), TypeCode::Timestamp),
+            Ok(Timestamp {
+                seconds: -62_135_596_800,
+                nanos: 0
+            })
+        );
+        for invalid in [
+            "2021-08-24T15:04:05",
+            "2021-08-24T15:04:05.Z",
+            "2021-02-29T15:04:05Z",
+            "2021-08-24T24:00:00Z",
+            "2021-08-24T15:04:60Z",
+            "2021-08-24T15:04:05.1234567890Z",
+            "2021-08-24T15:04:05+02:00",
+        ] {
+            assert!(
+                decode::<Timestamp>(string(invalid), TypeCode::Timestamp).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        assert_eq!(
+            decode::<NaiveDate>(string("2020-02-29"), TypeCode::Date),
+            Ok(NaiveDate::from_ymd_opt(2020, 2, 29).unwrap())
+        );
+        assert!(decode::<NaiveDate>(string("2021-02-29"), TypeCode::Date).is_err());
+        assert_eq!(
+            decode::<chrono::DateTime<Utc>>(string("2021-08-24T15:04:05.5Z"), TypeCode::Timestamp),
+            Ok(Utc.timestamp_opt(1_629_817_445, 500_000_000).unwrap())
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time() {
+        use time::{Month, OffsetDateTime};
+
+        assert_eq!(
+            decode::<time::Date>(string("2020-02-29"), TypeCode::Date),
+            Ok(time::Date::from_calendar_date(2020, Month::February, 29).unwrap())
+        );
+        assert_eq!(
+            decode::<OffsetDateTime>(string("1970-01-01T00:00:01Z"), TypeCode::Timestamp),
+            Ok(OffsetDateTime::from_unix_timestamp(1).unwrap())
+        );
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_numeric() {
+        use std::str::FromStr;
+
+        assert_eq!(
+            decode::<rust_decimal::Decimal>(string("-12.000000001"), TypeCode::Numeric),
+            Ok(rust_decimal::Decimal::from_str("-12.000000001").unwrap())
+        );
+        assert_eq!(
+            decode::<String>(string("12.5"), TypeCode::Numeric),
+            Ok("12.5".to_owned())
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            decode::<serde_json::Value>(string(r#"{"a":[1,null]}"#), TypeCode::Json),
+            Ok(serde_json::json!({"a": [1, null]}))
+        );
+    }
+
+    #[test]
+    fn test_null() {
+        assert_eq!(decode::<Option<i64>>(null(), TypeCode::Int64), Ok(None));
+        assert_eq!(
+            decode::<Option<i64>>(string("1"), TypeCode::Int64),
+            Ok(Some(1))
+        );
+        assert_eq!(
+            decode::<i64>(null(), TypeCode::Int64),
+            Err(DecodeError::Null)
+        );
+        // The type is checked even for NULL values.
+        assert_eq!(
+            decode::<String>(null(), TypeCode::Bytes),
+            Err(DecodeError::TypeMismatch {
+                code: TypeCode::Bytes,
+                target: "String"
+            })
+        );
+    }
+
+    #[test]
+    fn test_array_and_struct() {
+        let point = StructType {
+            fields: vec![
+                field("x", ty(TypeCode::Int64)),
+                field("", ty(TypeCode::String)),
+            ],
+        };
+        let points = Type {
+            code: TypeCode::Array as i32,
+            array_element_type: Some(Box::new(Type {
+                code: TypeCode::Struct as i32,
+                struct_type: Some(point),
+                ..Type::default()
+            })),
+            ..Type::default()
+        };
+        let value = list(vec![
+            list(vec![string("1"), string("a")]),
+            null(),
+            list(vec![string("2"), null()]),
+        ]);
+
+        let rows = Vec::<Option<Row>>::from_value(&value, &points).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].is_none());
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.get::<i64, _>("x"), Ok(1));
+        assert_eq!(first.get::<String, _>(1), Ok("a".to_owned()));
+        assert_eq!(
+            rows[2].as_ref().unwrap().decode::<(i64, Option<String>)>(),
+            Ok((2, None))
+        );
+
+        let short = list(vec![list(vec![string("1")])]);
+        assert!(Vec::<Row>::from_value(&short, &points).is_err());
+        assert_eq!(
+            Vec::<i64>::from_value(&value, &ty(TypeCode::Array)),
+            Err(DecodeError::TypeMismatch {
+                code: TypeCode::Unspecified,
+                target: "i64"
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_row() {
+        let strings = Type {
+            code: TypeCode::Array as i32,
+            array_element_type: Some(Box::new(ty(TypeCode::String))),
+            ..Type::default()
+        };
+        let fields = vec![
+            field("SingerId", ty(TypeCode::Int64)),
+            field("albums", strings),
+            field("FirstName", ty(TypeCode::String)),
+        ];
+        let row = Row::new(
+            fields.into(),
+            vec![string("7"), list(vec![string("Total Junk")]), null()],
+        );
+
+        assert_eq!(
+            row.decode::<(i64, Vec<String>, Option<String>)>(),
+            Ok((7, vec!["Total Junk".to_owned()], None))
+        );
+        assert_eq!(
+            row.get::<i64, _>("LastName"),
+            Err(DecodeError::NoSuchColumn("LastName".to_owned()))
+        );
+        assert_eq!(
+            row.get::<i64, _>(3),
+            Err(DecodeError::NoSuchColumn("3".to_owned()))
+        );
+        let err = row.get::<String, _>("FirstName").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column \"FirstName\": unexpected NULL value"
+        );
+        assert_eq!(
+            row.get::<bool, _>(0).unwrap_err().to_string(),
+            "column \"0\": cannot decode INT64 as bool"
+        );
+    }
+}
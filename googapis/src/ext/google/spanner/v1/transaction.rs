@@ -0,0 +1,990 @@
+use std::{
+    error, fmt,
+    future::Future,
+    mem,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use prost::Message;
+use prost_types::Timestamp;
+use tokio::time::{self, Instant};
+
+use super::{
+    commit_request,
+    result_set_stats::RowCount,
+    spanner_client::SpannerClient,
+    transaction_options::{self, read_only::TimestampBound},
+    transaction_selector::Selector,
+    BeginTransactionRequest, CommitRequest, CommitResponse, DecodeError, ExecuteSqlRequest,
+    Mutation, PooledSession, QueryClient, ReadRequest, ResultSet, ResultSetError, ResultStream,
+    RollbackRequest, SessionClient, SessionError, SessionPool, SpannerFuture, Transaction,
+    TransactionOptions, TransactionSelector,
+};
+use crate::google::rpc::{RetryInfo, Status};
+
+/// A client of the methods of the `google.spanner.v1.Spanner` service used by transactions,
+/// implemented by `SpannerClient`.
+pub trait TransactionClient: SessionClient + QueryClient {
+    fn execute_sql(&mut self, request: ExecuteSqlRequest) -> SpannerFuture<'_, ResultSet>;
+
+    fn commit(&mut self, request: CommitRequest) -> SpannerFuture<'_, CommitResponse>;
+
+    fn rollback(&mut self, request: RollbackRequest) -> SpannerFuture<'_, ()>;
+}
+
+impl<T> TransactionClient for SpannerClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + 'static,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{
+    fn execute_sql(&mut self, request: ExecuteSqlRequest) -> SpannerFuture<'_, ResultSet> {
+        Box::pin(async move {
+            let response = SpannerClient::execute_sql(self, request).await?;
+            Ok(response.into_inner())
+        })
+    }
+
+    fn commit(&mut self, request: CommitRequest) -> SpannerFuture<'_, CommitResponse> {
+        Box::pin(async move {
+            let response = SpannerClient::commit(self, request).await?;
+            Ok(response.into_inner())
+        })
+    }
+
+    fn rollback(&mut self, request: RollbackRequest) -> SpannerFuture<'_, ()> {
+        Box::pin(async move {
+            SpannerClient::rollback(self, request).await?;
+            Ok(())
+        })
+    }
+}
+
+/// An error returned by a transaction.
+#[derive(Debug)]
+pub enum TransactionError {
+    /// Checking out a session failed.
+    Session(SessionError),
+    /// A request failed. [`SessionPool::run_in_transaction`] retries the transaction when it
+    /// was `ABORTED`.
+    Status(tonic::Status),
+    /// A query failed.
+    ResultSet(ResultSetError),
+    /// A row could not be decoded.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Session(e) => e.fmt(f),
+            TransactionError::Status(s) => write!(f, "transaction request failed: {}", s),
+            TransactionError::ResultSet(e) => e.fmt(f),
+            TransactionError::Decode(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TransactionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TransactionError::Session(e) => Some(e),
+            TransactionError::Status(s) => Some(s),
+            TransactionError::ResultSet(e) => Some(e),
+            TransactionError::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl From<SessionError> for TransactionError {
+    fn from(e: SessionError) -> Self {
+        TransactionError::Session(e)
+    }
+}
+
+impl From<tonic::Status> for TransactionError {
+    fn from(s: tonic::Status) -> Self {
+        TransactionError::Status(s)
+    }
+}
+
+impl From<ResultSetError> for TransactionError {
+    fn from(e: ResultSetError) -> Self {
+        TransactionError::ResultSet(e)
+    }
+}
+
+impl From<DecodeError> for TransactionError {
+    fn from(e: DecodeError) -> Self {
+        TransactionError::Decode(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionSettings {
+    /// Whether the `commit_stats` of the `CommitResponse` are returned.
+    pub return_commit_stats: bool,
+    /// How long a transaction is retried after `ABORTED`, from its first attempt.
+    pub timeout: Duration,
+}
+
+impl Default for TransactionSettings {
+    fn default() -> Self {
+        TransactionSettings {
+            return_commit_stats: false,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+// The backoff after `ABORTED` without a `RetryInfo`, doubled at each attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(20);
+const MAX_BACKOFF: Duration = Duration::from_secs(32);
+
+// Spanner sends the `google.rpc.RetryInfo` of `ABORTED` errors in this trailer.
+const RETRY_INFO_KEY: &str = "google.rpc.retryinfo-bin";
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+// The delay requested by the `RetryInfo` of an error, in its trailer or among its details.
+fn retry_delay(status: &tonic::Status) -> Option<Duration> {
+    let info = match status.metadata().get_bin(RETRY_INFO_KEY) {
+        Some(value) => RetryInfo::decode(value.to_bytes().ok()?).ok()?,
+        None => {
+            let details = Status::decode(status.details()).ok()?;
+            let any = details
+                .details
+                .into_iter()
+                .find(|any| any.type_url == RETRY_INFO_TYPE)?;
+            RetryInfo::decode(any.value.as_slice()).ok()?
+        }
+    };
+    let delay = info.retry_delay?;
+    if delay.seconds < 0 || delay.nanos < 0 {
+        return None;
+    }
+    Some(Duration::new(delay.seconds as u64, delay.nanos as u32))
+}
+
+fn read_write() -> TransactionOptions {
+    TransactionOptions {
+        mode: Some(transaction_options::Mode::ReadWrite(
+            transaction_options::ReadWrite {},
+        )),
+    }
+}
+
+fn select(selector: Selector) -> Option<TransactionSelector> {
+    Some(TransactionSelector {
+        selector: Some(selector),
+    })
+}
+
+#[derive(Debug)]
+enum Begin {
+    // The transaction is begun by its first statement.
+    Inline,
+    // It is begun with `BeginTransaction`, as the statement that was to begin it failed.
+    Explicit,
+    Begun(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Shared {
+    // Locked while the transaction is being begun, so that other statements wait for its id.
+    begin: tokio::sync::Mutex<Begin>,
+    seqno: AtomicI64,
+    mutations: Mutex<Vec<Mutation>>,
+    // Set once a request was `ABORTED`, with the delay requested by Spanner.
+    aborted: Mutex<Option<Option<Duration>>>,
+}
+
+/// A read-write transaction run by [`SessionPool::run_in_transaction`].
+///
+/// The transaction is begun by its first statement, and the other statements wait for it to be.
+/// DML statements are numbered with `seqno`, and the mutations buffered with
+/// [`buffer_write`](Self::buffer_write) are applied by the commit. It is cheap to clone, and
+/// shared by the clones.
+pub struct ReadWriteTransaction<C> {
+    client: C,
+    session: Arc<str>,
+    shared: Arc<Shared>,
+}
+
+impl<C: Clone> Clone for ReadWriteTransaction<C> {
+    fn clone(&self) -> Self {
+        ReadWriteTransaction {
+            client: self.client.clone(),
+            session: self.session.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<C> fmt::Debug for ReadWriteTransaction<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadWriteTransaction")
+            .field("session", &self.session)
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+type BeginGuard<'a> = tokio::sync::MutexGuard<'a, Begin>;
+
+impl<C: TransactionClient> ReadWriteTransaction<C> {
+    fn new(client: C, session: &str) -> Self {
+        ReadWriteTransaction {
+            client,
+            session: session.into(),
+            shared: Arc::new(Shared {
+                begin: tokio::sync::Mutex::new(Begin::Inline),
+                seqno: AtomicI64::new(1),
+                mutations: Mutex::default(),
+                aborted: Mutex::default(),
+            }),
+        }
+    }
+
+    /// The name of the session of the transaction.
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Streams the rows of a query or of a DML statement with a `THEN RETURN` clause, setting
+    /// the `session`, `transaction` and `seqno` of the request.
+    pub async fn query(
+        &self,
+        mut request: ExecuteSqlRequest,
+    ) -> Result<ResultStream<C>, TransactionError> {
+        request.session = self.session.to_string();
+        request.seqno = self.shared.seqno.fetch_add(1, Ordering::Relaxed);
+        let (selector, guard) = self.selector().await?;
+        request.transaction = selector;
+        let stream = ResultStream::execute_sql(self.client.clone(), request);
+        self.stream(stream, guard).await
+    }
+
+    /// Streams the rows of a read, setting the `session` and `transaction` of the request.
+    pub async fn read(
+        &self,
+        mut request: ReadRequest,
+    ) -> Result<ResultStream<C>, TransactionError> {
+        request.session = self.session.to_string();
+        let (selector, guard) = self.selector().await?;
+        request.transaction = selector;
+        let stream = ResultStream::read(self.client.clone(), request);
+        self.stream(stream, guard).await
+    }
+
+    /// Executes a DML statement, setting the `session`, `transaction` and `seqno` of the request,
+    /// and returns the number of rows modified.
+    pub async fn execute_update(
+        &self,
+        mut request: ExecuteSqlRequest,
+    ) -> Result<i64, TransactionError> {
+        request.session = self.session.to_string();
+        request.seqno = self.shared.seqno.fetch_add(1, Ordering::Relaxed);
+        let (selector, guard) = self.selector().await?;
+        request.transaction = selector;
+        let result = self.client.clone().execute_sql(request).await;
+        let begun = result.as_ref().ok().and_then(|r| {
+            let transaction = r.metadata.as_ref()?.transaction.as_ref()?;
+            Some(transaction.id.clone())
+        });
+        self.begun(guard, begun);
+        let stats = result
+            .map_err(|s| self.failed(s))?
+            .stats
+            .unwrap_or_default();
+        match stats.row_count {
+            Some(RowCount::RowCountExact(n)) | Some(RowCount::RowCountLowerBound(n)) => Ok(n),
+            None => Ok(0),
+        }
+    }
+
+    /// Buffers mutations, applied atomically by the commit.
+    pub fn buffer_write(&self, mutations: impl IntoIterator<Item = Mutation>) {
+        self.shared.mutations.lock().unwrap().extend(mutations);
+    }
+
+    // Returns the selector of the next statement. The guard is returned when the statement is
+    // to begin the transaction, and must be given back with the id of the transaction begun.
+    async fn selector(
+        &self,
+    ) -> Result<(Option<TransactionSelector>, Option<BeginGuard<'_>>), TransactionError> {
+        let mut begin = self.shared.begin.lock().await;
+        match &*begin {
+            Begin::Begun(id) => Ok((select(Selector::Id(id.clone())), None)),
+            Begin::Inline => Ok((select(Selector::Begin(read_write())), Some(begin))),
+            Begin::Explicit => {
+                let request = BeginTransactionRequest {
+                    session: self.session.to_string(),
+                    options: Some(read_write()),
+                    request_options: None,
+                };
+                let transaction = self.client.clone().begin_transaction(request).await;
+                let id = transaction.map_err(|s| self.failed(s))?.id;
+                *begin = Begin::Begun(id.clone());
+                Ok((select(Selector::Id(id)), None))
+            }
+        }
+    }
+
+    // Records the id of the transaction begun by a statement, or that it failed to begin it.
+    fn begun(&self, guard: Option<BeginGuard<'_>>, id: Option<Vec<u8>>) {
+        if let Some(mut begin) = guard {
+            *begin = match id {
+                Some(id) if !id.is_empty() => Begin::Begun(id),
+                _ => Begin::Explicit,
+            };
+        }
+    }
+
+    async fn stream(
+        &self,
+        mut stream: ResultStream<C>,
+        guard: Option<BeginGuard<'_>>,
+    ) -> Result<ResultStream<C>, TransactionError> {
+        let shared = self.shared.clone();
+        stream.on_status(move |status| abort(&shared, status));
+        if guard.is_none() {
+            return Ok(stream);
+        }
+        let result = stream.wait_metadata().await;
+        let begun = stream
+            .metadata()
+            .and_then(|m| Some(m.transaction.as_ref()?.id.clone()));
+        self.begun(guard, begun);
+        result?;
+        Ok(stream)
+    }
+
+    fn failed(&self, status: tonic::Status) -> TransactionError {
+        abort(&self.shared, &status);
+        status.into()
+    }
+
+    fn aborted(&self) -> Option<Option<Duration>> {
+        *self.shared.aborted.lock().unwrap()
+    }
+
+    async fn commit(
+        &self,
+        settings: &TransactionSettings,
+    ) -> Result<CommitResponse, TransactionError> {
+        let transaction = match &*self.shared.begin.lock().await {
+            Begin::Begun(id) => commit_request::Transaction::TransactionId(id.clone()),
+            // Only mutations, or every statement failed.
+            _ => commit_request::Transaction::SingleUseTransaction(read_write()),
+        };
+        let request = CommitRequest {
+            session: self.session.to_string(),
+            mutations: mem::take(&mut *self.shared.mutations.lock().unwrap()),
+            return_commit_stats: settings.return_commit_stats,
+            request_options: None,
+            transaction: Some(transaction),
+        };
+        self.client
+            .clone()
+            .commit(request)
+            .await
+            .map_err(|s| self.failed(s))
+    }
+
+    async fn rollback(&self) {
+        let transaction_id = match &*self.shared.begin.lock().await {
+            Begin::Begun(id) => id.clone(),
+            _ => return,
+        };
+        let request = RollbackRequest {
+            session: self.session.to_string(),
+            transaction_id,
+        };
+        // The transaction is aborted by Spanner anyway after some time.
+        let _ = self.client.clone().rollback(request).await;
+    }
+}
+
+fn abort(shared: &Shared, status: &tonic::Status) {
+    if status.code() == tonic::Code::Aborted {
+        *shared.aborted.lock().unwrap() = Some(retry_delay(status));
+    }
+}
+
+/// A read-only transaction, reading a consistent snapshot of the database at its read
+/// timestamp. Its session is returned to the pool when it is dropped.
+pub struct ReadOnlyTransaction<C: SessionClient> {
+    client: C,
+    session: PooledSession<C>,
+    transaction: Transaction,
+}
+
+impl<C: SessionClient> fmt::Debug for ReadOnlyTransaction<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadOnlyTransaction")
+            .field("session", &self.session)
+            .field("transaction", &self.transaction)
+            .finish()
+    }
+}
+
+impl<C: TransactionClient> ReadOnlyTransaction<C> {
+    /// The timestamp of the snapshot read by the transaction.
+    pub fn read_timestamp(&self) -> Option<&Timestamp> {
+        self.transaction.read_timestamp.as_ref()
+    }
+
+    /// Streams the rows of a query, setting the `session` and `transaction` of the request.
+    pub fn query(&self, mut request: ExecuteSqlRequest) -> ResultStream<C> {
+        request.session = self.session.name().to_owned();
+        request.transaction = select(Selector::Id(self.transaction.id.clone()));
+        ResultStream::execute_sql(self.client.clone(), request)
+    }
+
+    /// Streams the rows of a read, setting the `session` and `transaction` of the request.
+    pub fn read(&self, mut request: ReadRequest) -> ResultStream<C> {
+        request.session = self.session.name().to_owned();
+        request.transaction = select(Selector::Id(self.transaction.id.clone()));
+        ResultStream::read(self.client.clone(), request)
+    }
+}
+
+impl<C: TransactionClient> SessionPool<C> {
+    /// Runs `f` in a read-write transaction with the default settings, see
+    /// [`run_in_transaction_with`](Self::run_in_transaction_with).
+    pub async fn run_in_transaction<F, Fut, T, E>(&self, f: F) -> Result<(T, CommitResponse), E>
+    where
+        F: FnMut(ReadWriteTransaction<C>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<TransactionError>,
+    {
+        self.run_in_transaction_with(TransactionSettings::default(), f)
+            .await
+    }
+
+    /// Runs `f` in a read-write transaction and commits it, returning the result of `f` and the
+    /// `CommitResponse`.
+    ///
+    /// When a request of the transaction, or its commit, is `ABORTED`, `f` is run again in a new
+    /// transaction on the same session, after the delay of the `RetryInfo` of the error or an
+    /// exponential backoff, until [`TransactionSettings::timeout`]. The transaction is rolled back
+    /// when `f` fails otherwise, and its error returned.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let (updated, _) = pool
+    ///     .run_in_transaction(|tx| async move {
+    ///         let request = ExecuteSqlRequest {
+    ///             sql: "UPDATE Albums SET Budget = Budget + 1 WHERE SingerId = 1".to_owned(),
+    ///             ..Default::default()
+    ///         };
+    ///         let updated = tx.execute_update(request).await?;
+    ///         tx.buffer_write(mutations);
+    ///         Ok::<_, TransactionError>(updated)
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn run_in_transaction_with<F, Fut, T, E>(
+        &self,
+        settings: TransactionSettings,
+        mut f: F,
+    ) -> Result<(T, CommitResponse), E>
+    where
+        F: FnMut(ReadWriteTransaction<C>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<TransactionError>,
+    {
+        let deadline = Instant::now() + settings.timeout;
+        let session = self.get().await.map_err(TransactionError::from)?;
+        let mut attempt = 0;
+        loop {
+            let tx = ReadWriteTransaction::new(self.client(), session.name());
+            let e = match f(tx.clone()).await {
+                Ok(value) => match tx.commit(&settings).await {
+                    Ok(response) => return Ok((value, response)),
+                    Err(e) => E::from(e),
+                },
+                Err(e) => {
+                    if tx.aborted().is_none() {
+                        tx.rollback().await;
+                    }
+                    e
+                }
+            };
+            let delay = match tx.aborted() {
+                Some(delay) => delay.unwrap_or_else(|| {
+                    (INITIAL_BACKOFF * 2u32.pow(attempt.min(16))).min(MAX_BACKOFF)
+                }),
+                None => return Err(e),
+            };
+            if Instant::now() + delay > deadline {
+                return Err(e);
+            }
+            time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Begins a read-only transaction reading at `bound`, which must not be a
+    /// `MinReadTimestamp` or a `MaxStaleness`, as they are only valid in single-use
+    /// transactions.
+    pub async fn read_only(
+        &self,
+        bound: TimestampBound,
+    ) -> Result<ReadOnlyTransaction<C>, TransactionError> {
+        let session = self.get().await?;
+        let request = BeginTransactionRequest {
+            session: session.name().to_owned(),
+            options: Some(TransactionOptions {
+                mode: Some(transaction_options::Mode::ReadOnly(
+                    transaction_options::ReadOnly {
+                        return_read_timestamp: true,
+                        timestamp_bound: Some(bound),
+                    },
+                )),
+            }),
+            request_options: None,
+        };
+        let client = self.client();
+        let transaction = client.clone().begin_transaction(request).await?;
+        Ok(ReadOnlyTransaction {
+            client,
+            session,
+            transaction,
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tonic::{
+        codegen::futures_core::Stream,
+        metadata::{MetadataMap, MetadataValue},
+        Code,
+    };
+
+    use super::{
+        super::{
+            commit_response, mutation, result_set_stats, BatchCreateSessionsRequest,
+            PartialResultSet, PartialResultSetStream, ResultSetMetadata, ResultSetStats, Session,
+            SessionPoolSettings,
+        },
+        *,
+    };
+
+    #[derive(Debug, Default)]
+    struct FakeState {
+        begun: usize,
+        sql: Vec<ExecuteSqlRequest>,
+        commits: Vec<CommitRequest>,
+        rollbacks: Vec<RollbackRequest>,
+        // The errors of the next requests of the transactions.
+        failures: VecDeque<tonic::Status>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Fake {
+        state: Arc<Mutex<FakeState>>,
+    }
+
+    impl Fake {
+        fn with<T>(&self, f: impl FnOnce(&mut FakeState) -> T) -> T {
+            f(&mut self.state.lock().unwrap())
+        }
+
+        // Begins a transaction if the selector asks to.
+        fn begin(&self, selector: &Option<TransactionSelector>) -> Option<Transaction> {
+            match selector.as_ref()?.selector.as_ref()? {
+                Selector::Begin(_) => Some(self.next_transaction()),
+                _ => None,
+            }
+        }
+
+        fn next_transaction(&self) -> Transaction {
+            self.with(|s| {
+                s.begun += 1;
+                Transaction {
+                    id: format!("tx{}", s.begun).into_bytes(),
+                    read_timestamp: Some(Timestamp {
+                        seconds: s.begun as i64,
+                        nanos: 0,
+                    }),
+                }
+            })
+        }
+
+        fn fail(&self) -> Result<(), tonic::Status> {
+            match self.with(|s| s.failures.pop_front()) {
+                Some(status) => Err(status),
+                None => Ok(()),
+            }
+        }
+    }
+
+    struct Messages(VecDeque<Result<PartialResultSet, tonic::Status>>);
+
+    impl Stream for Messages {
+        type Item = Result<PartialResultSet, tonic::Status>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    impl SessionClient for Fake {
+        fn batch_create_sessions(
+            &mut self,
+            request: BatchCreateSessionsRequest,
+        ) -> SpannerFuture<'_, Vec<Session>> {
+            let sessions = (0..request.session_count)
+                .map(|i| Session {
+                    name: format!("{}/sessions/{}", request.database, i),
+                    ..Session::default()
+                })
+                .collect();
+            Box::pin(async move { Ok(sessions) })
+        }
+
+        fn get_session(&mut self, name: String) -> SpannerFuture<'_, Session> {
+            Box::pin(async move {
+                Ok(Session {
+                    name,
+                    ..Session::default()
+                })
+            })
+        }
+
+        fn delete_session(&mut self, _: String) -> SpannerFuture<'_, ()> {
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn begin_transaction(
+            &mut self,
+            _: BeginTransactionRequest,
+        ) -> SpannerFuture<'_, Transaction> {
+            Box::pin(async move { Ok(self.next_transaction()) })
+        }
+    }
+
+    impl QueryClient for Fake {
+        fn execute_streaming_sql(
+            &mut self,
+            request: ExecuteSqlRequest,
+        ) -> SpannerFuture<'_, PartialResultSetStream> {
+            let transaction = self.begin(&request.transaction);
+            self.with(|s| s.sql.push(request));
+            let mut messages = VecDeque::new();
+            messages.push_back(Ok(PartialResultSet {
+                metadata: Some(ResultSetMetadata {
+                    transaction,
+                    ..ResultSetMetadata::default()
+                }),
+                ..PartialResultSet::default()
+            }));
+            if let Err(status) = self.fail() {
+                messages.push_back(Err(status));
+            }
+            Box::pin(async move { Ok(Box::pin(Messages(messages)) as PartialResultSetStream) })
+        }
+
+        fn streaming_read(&mut self, _: ReadRequest) -> SpannerFuture<'_, PartialResultSetStream> {
+            Box::pin(async { Err(tonic::Status::unimplemented("streaming_read")) })
+        }
+    }
+
+    impl TransactionClient for Fake {
+        fn execute_sql(&mut self, request: ExecuteSqlRequest) -> SpannerFuture<'_, ResultSet> {
+            Box::pin(async move {
+                self.fail()?;
+                let transaction = self.begin(&request.transaction);
+                self.with(|s| s.sql.push(request));
+                Ok(ResultSet {
+                    metadata: Some(ResultSetMetadata {
+                        transaction,
+                        ..ResultSetMetadata::default()
+                    }),
+                    stats: Some(ResultSetStats {
+                        row_count: Some(result_set_stats::RowCount::RowCountExact(1)),
+                        ..ResultSetStats::default()
+                    }),
+                    ..ResultSet::default()
+                })
+            })
+        }
+
+        fn commit(&mut self, request: CommitRequest) -> SpannerFuture<'_, CommitResponse> {
+            Box::pin(async move {
+                self.fail()?;
+                let mutation_count = request.mutations.len() as i64;
+                let stats = request.return_commit_stats;
+                self.with(|s| s.commits.push(request));
+                Ok(CommitResponse {
+                    commit_timestamp: Some(Timestamp::default()),
+                    commit_stats: stats.then_some(commit_response::CommitStats { mutation_count }),
+                })
+            })
+        }
+
+        fn rollback(&mut self, request: RollbackRequest) -> SpannerFuture<'_, ()> {
+            Box::pin(async move {
+                self.with(|s| s.rollbacks.push(request));
+                Ok(())
+            })
+        }
+    }
+
+    fn pool(fake: &Fake) -> SessionPool<Fake> {
+        let settings = SessionPoolSettings {
+            min_sessions: 1,
+            write_sessions: 0.0,
+            ..SessionPoolSettings::default()
+        };
+        SessionPool::with_settings(fake.clone(), "db", settings)
+    }
+
+    fn aborted(delay: Option<Duration>) -> tonic::Status {
+        let mut metadata = MetadataMap::new();
+        if let Some(delay) = delay {
+            let info = RetryInfo {
+                retry_delay: Some(prost_types::Duration {
+                    seconds: delay.as_secs() as i64,
+                    nanos: delay.subsec_nanos() as i32,
+                }),
+            };
+            metadata.insert_bin(
+                RETRY_INFO_KEY,
+                MetadataValue::from_bytes(&info.encode_to_vec()),
+            );
+        }
+        tonic::Status::with_metadata(Code::Aborted, "aborted", metadata)
+    }
+
+    fn update(sql: &str) -> ExecuteSqlRequest {
+        ExecuteSqlRequest {
+            sql: sql.to_owned(),
+            ..ExecuteSqlRequest::default()
+        }
+    }
+
+    fn delete(table: &str) -> Mutation {
+        Mutation {
+            operation: Some(mutation::Operation::Delete(mutation::Delete {
+                table: table.to_owned(),
+                key_set: None,
+            })),
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let delay = Duration::from_millis(1500);
+        assert_eq!(retry_delay(&aborted(Some(delay))), Some(delay));
+        assert_eq!(retry_delay(&aborted(None)), None);
+
+        let info = RetryInfo {
+            retry_delay: Some(prost_types::Duration {
+                seconds: 2,
+                nanos: 0,
+            }),
+        };
+        let details = Status {
+            code: Code::Aborted as i32,
+            message: "aborted".to_owned(),
+            details: vec![prost_types::Any {
+                type_url: RETRY_INFO_TYPE.to_owned(),
+                value: info.encode_to_vec(),
+            }],
+        };
+        let status =
+            tonic::Status::with_details(Code::Aborted, "aborted", details.encode_to_vec().into());
+        assert_eq!(retry_delay(&status), Some(Duration::from_secs(2)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_in_transaction() {
+        let fake = Fake::default();
+        let pool = pool(&fake);
+        let start = Instant::now();
+        let mut attempts = 0;
+        let settings = TransactionSettings {
+            return_commit_stats: true,
+            ..TransactionSettings::default()
+        };
+        let fake2 = fake.clone();
+        // The first commit is aborted, and the transaction run again after the delay.
+        let (updated, response) = pool
+            .run_in_transaction_with(settings, |tx| {
+                attempts += 1;
+                let fake = fake2.clone();
+                async move {
+                    let (first, second) = tokio::join!(
+                        tx.execute_update(update("UPDATE A")),
+                        tx.execute_update(update("UPDATE B"))
+                    );
+                    tx.buffer_write(vec![delete("C")]);
+                    if fake.with(|s| s.commits.is_empty() && s.begun == 1) {
+                        fake.with(|s| s.failures.push_back(aborted(Some(Duration::from_secs(5)))));
+                    }
+                    Ok::<_, TransactionError>(first? + second?)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(attempts, 2);
+        assert_eq!(updated, 2);
+        assert!(start.elapsed() >= Duration::from_secs(5));
+        assert_eq!(response.commit_stats.unwrap().mutation_count, 1);
+        fake.with(|s| {
+            // Each attempt begins its transaction with its first statement.
+            assert_eq!(s.begun, 2);
+            let selectors = s
+                .sql
+                .iter()
+                .map(|r| (r.seqno, r.transaction.clone().unwrap().selector.unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                selectors,
+                vec![
+                    (1, Selector::Begin(read_write())),
+                    (2, Selector::Id(b"tx1".to_vec())),
+                    (1, Selector::Begin(read_write())),
+                    (2, Selector::Id(b"tx2".to_vec())),
+                ]
+            );
+            assert_eq!(s.commits.len(), 1);
+            assert_eq!(
+                s.commits[0].transaction,
+                Some(commit_request::Transaction::TransactionId(b"tx2".to_vec()))
+            );
+            assert_eq!(s.commits[0].mutations, vec![delete("C")]);
+            assert!(s.rollbacks.is_empty());
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_in_transaction_rollback() {
+        let fake = Fake::default();
+        let pool = pool(&fake);
+
+        let result = pool
+            .run_in_transaction(|tx| async move {
+                tx.execute_update(update("UPDATE A")).await?;
+                Err::<(), _>(TransactionError::Status(tonic::Status::invalid_argument(
+                    "invalid",
+                )))
+            })
+            .await;
+
+        assert!(
+            matches!(result, Err(TransactionError::Status(s)) if s.code() == Code::InvalidArgument)
+        );
+        fake.with(|s| {
+            assert!(s.commits.is_empty());
+            assert_eq!(s.rollbacks.len(), 1);
+            assert_eq!(s.rollbacks[0].transaction_id, b"tx1".to_vec());
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_in_transaction_aborted_query() {
+        let fake = Fake::default();
+        let pool = pool(&fake);
+        fake.with(|s| s.failures.push_back(aborted(None)));
+
+        let (rows, response) = pool
+            .run_in_transaction(|tx| async move {
+                let mut stream = tx.query(update("SELECT 1")).await?;
+                let mut rows = 0;
+                while let Some(row) = stream.next().await {
+                    row?;
+                    rows += 1;
+                }
+                Ok::<_, TransactionError>(rows)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(rows, 0);
+        assert!(response.commit_stats.is_none());
+        fake.with(|s| {
+            assert_eq!(s.begun, 2);
+            assert!(s.rollbacks.is_empty());
+            assert_eq!(
+                s.commits[0].transaction,
+                Some(commit_request::Transaction::TransactionId(b"tx2".to_vec()))
+            );
+        });
+
+        // A transaction with only mutations is committed at once.
+        pool.run_in_transaction(|tx| async move {
+            tx.buffer_write(vec![delete("A")]);
+            Ok::<_, TransactionError>(())
+        })
+        .await
+        .unwrap();
+        fake.with(|s| {
+            assert_eq!(s.begun, 2);
+            assert_eq!(
+                s.commits[1].transaction,
+                Some(commit_request::Transaction::SingleUseTransaction(
+                    read_write()
+                ))
+            );
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_in_transaction_timeout() {
+        let fake = Fake::default();
+        let pool = pool(&fake);
+        let settings = TransactionSettings {
+            timeout: Duration::from_secs(10),
+            ..TransactionSettings::default()
+        };
+
+        let mut attempts = 0;
+        let fake2 = fake.clone();
+        let result = pool
+            .run_in_transaction_with(settings, |_| {
+                attempts += 1;
+                fake2.with(|s| s.failures.push_back(aborted(Some(Duration::from_secs(4)))));
+                async { Ok::<_, TransactionError>(()) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(TransactionError::Status(s)) if s.code() == Code::Aborted));
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_only() {
+        let fake = Fake::default();
+        let pool = pool(&fake);
+
+        let bound = TimestampBound::ExactStaleness(prost_types::Duration {
+            seconds: 10,
+            nanos: 0,
+        });
+        let tx = pool.read_only(bound).await.unwrap();
+        assert_eq!(tx.read_timestamp().unwrap().seconds, 1);
+        let mut stream = tx.query(update("SELECT 1"));
+        assert!(stream.next().await.is_none());
+        fake.with(|s| {
+            assert_eq!(s.sql[0].transaction, select(Selector::Id(b"tx1".to_vec())));
+        });
+    }
+}
@@ -0,0 +1,1183 @@
+use std::{error, fmt};
+
+use prost_types::Timestamp;
+
+use super::{date_time::TimeOffset, Date, DateTime, Interval, TimeOfDay};
+
+// A UTC offset must be between -18 hours and +18 hours.
+const MAX_OFFSET_SECONDS: i64 = 18 * 60 * 60;
+// The range of a `google.protobuf.Timestamp`, from 0001-01-01T00:00:00Z to
+// 9999-12-31T23:59:59.999999999Z.
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
+// Seconds and nanos since the Unix epoch.
+type Instant = (i64, u32);
+
+/// An error returned when a calendar type of `google.type` cannot be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarError {
+    /// The date has no year, month or day, e.g. a birthday without a year.
+    PartialDate { year: i32, month: i32, day: i32 },
+    /// The date does not exist, e.g. February 30.
+    InvalidDate { year: i32, month: i32, day: i32 },
+    /// A field is out of its documented range.
+    OutOfRange { field: &'static str, value: i64 },
+    /// The time is valid but cannot be represented by the target type, e.g. `24:00:00`.
+    UnsupportedTime {
+        hours: i32,
+        minutes: i32,
+        seconds: i32,
+    },
+    /// An enum value is unspecified.
+    Unspecified(&'static str),
+    /// The date time has neither a UTC offset nor a time zone, i.e. it is in local time.
+    MissingOffset,
+    /// The time zone is not in the IANA Time Zone Database, or time zones are not supported
+    /// because the `time-zone` feature is disabled.
+    UnknownTimeZone(String),
+    /// The local time occurs twice in the time zone, e.g. when clocks are turned back.
+    AmbiguousLocalTime(String),
+    /// The local time does not exist in the time zone, e.g. when clocks are turned forward.
+    NonexistentLocalTime(String),
+    /// The interval has no start or no end time.
+    UnboundedInterval,
+    /// The end time of the interval is before its start time.
+    InvalidInterval,
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarError::PartialDate { year, month, day } => {
+                write!(f, "partial date: {:04}-{:02}-{:02}", year, month, day)
+            }
+            CalendarError::InvalidDate { year, month, day } => {
+                write!(f, "invalid date: {:04}-{:02}-{:02}", year, month, day)
+            }
+            CalendarError::OutOfRange { field, value } => {
+                write!(f, "{} out of range: {}", field, value)
+            }
+            CalendarError::UnsupportedTime {
+                hours,
+                minutes,
+                seconds,
+            } => write!(
+                f,
+                "unsupported time: {:02}:{:02}:{:02}",
+                hours, minutes, seconds
+            ),
+            CalendarError::Unspecified(name) => write!(f, "unspecified {}", name),
+            CalendarError::MissingOffset => f.write_str("date time has no UTC offset or time zone"),
+            CalendarError::UnknownTimeZone(id) => write!(f, "unknown time zone: {:?}", id),
+            CalendarError::AmbiguousLocalTime(id) => {
+                write!(f, "ambiguous local time in time zone {:?}", id)
+            }
+            CalendarError::NonexistentLocalTime(id) => {
+                write!(f, "nonexistent local time in time zone {:?}", id)
+            }
+            CalendarError::UnboundedInterval => f.write_str("interval has no start or end time"),
+            CalendarError::InvalidInterval => f.write_str("interval ends before it starts"),
+        }
+    }
+}
+
+impl error::Error for CalendarError {}
+
+fn check_range(field: &'static str, value: i64, min: i64, max: i64) -> Result<(), CalendarError> {
+    if value < min || value > max {
+        return Err(CalendarError::OutOfRange { field, value });
+    }
+    Ok(())
+}
+
+// Returns the year, month and day of a full date. Whether the day exists is left to the target
+// type.
+pub(super) fn full_date(year: i32, month: i32, day: i32) -> Result<(i32, u32, u32), CalendarError> {
+    check_range("year", year.into(), 0, 9999)?;
+    check_range("month", month.into(), 0, 12)?;
+    check_range("day", day.into(), 0, 31)?;
+    if year == 0 || month == 0 || day == 0 {
+        return Err(CalendarError::PartialDate { year, month, day });
+    }
+    Ok((year, month as u32, day as u32))
+}
+
+pub(super) fn invalid_date(year: i32, month: i32, day: i32) -> CalendarError {
+    CalendarError::InvalidDate { year, month, day }
+}
+
+// Returns the hours, minutes, seconds and nanos of a time. `24:00:00` and leap seconds are valid
+// but rejected here, target types that support leap seconds handle them before calling this.
+pub(super) fn time_of_day(
+    hours: i32,
+    minutes: i32,
+    seconds: i32,
+    nanos: i32,
+) -> Result<(u32, u32, u32, u32), CalendarError> {
+    check_range("hours", hours.into(), 0, 24)?;
+    check_range("minutes", minutes.into(), 0, 59)?;
+    check_range("seconds", seconds.into(), 0, 60)?;
+    check_range("nanos", nanos.into(), 0, 999_999_999)?;
+    if hours == 24 || seconds == 60 {
+        return Err(CalendarError::UnsupportedTime {
+            hours,
+            minutes,
+            seconds,
+        });
+    }
+    Ok((hours as u32, minutes as u32, seconds as u32, nanos as u32))
+}
+
+// The offset of a date time from UTC in seconds, resolving its time zone if it has one. `local`
+// is the date time without offset.
+pub(super) fn utc_offset(dt: &DateTime, local: Instant) -> Result<i32, CalendarError> {
+    match dt.time_offset.as_ref() {
+        Some(TimeOffset::UtcOffset(d)) => {
+            check_range(
+                "utc_offset",
+                d.seconds,
+                -MAX_OFFSET_SECONDS,
+                MAX_OFFSET_SECONDS,
+            )?;
+            if d.nanos != 0 {
+                return Err(CalendarError::OutOfRange {
+                    field: "utc_offset",
+                    value: d.nanos.into(),
+                });
+            }
+            Ok(d.seconds as i32)
+        }
+        Some(TimeOffset::TimeZone(tz)) => zone_offset(&tz.id, local),
+        None => Err(CalendarError::MissingOffset),
+    }
+}
+
+// Returns the seconds and nanos since the Unix epoch of a timestamp.
+pub(super) fn timestamp(field: &'static str, ts: &Timestamp) -> Result<Instant, CalendarError> {
+    check_range(
+        field,
+        ts.seconds,
+        MIN_TIMESTAMP_SECONDS,
+        MAX_TIMESTAMP_SECONDS,
+    )?;
+    check_range(field, ts.nanos.into(), 0, 999_999_999)?;
+    Ok((ts.seconds, ts.nanos as u32))
+}
+
+// Returns the start and end of an interval, see `timestamp`.
+pub(super) fn interval(i: &Interval) -> Result<(Instant, Instant), CalendarError> {
+    let (start, end) = match (i.start_time.as_ref(), i.end_time.as_ref()) {
+        (Some(start), Some(end)) => (timestamp("start_time", start)?, timestamp("end_time", end)?),
+        _ => return Err(CalendarError::UnboundedInterval),
+    };
+    if end < start {
+        return Err(CalendarError::InvalidInterval);
+    }
+    Ok((start, end))
+}
+
+#[cfg(feature = "time-zone")]
+fn zone_offset(id: &str, local: Instant) -> Result<i32, CalendarError> {
+    use chrono::{LocalResult, Offset, TimeZone};
+
+    let tz = id
+        .parse::<chrono_tz::Tz>()
+        .map_err(|_| CalendarError::UnknownTimeZone(id.to_owned()))?;
+    let local = chrono::Utc
+        .timestamp_opt(local.0, local.1)
+        .single()
+        .ok_or(CalendarError::OutOfRange {
+            field: "seconds",
+            value: local.0,
+        })?
+        .naive_utc();
+    match tz.offset_from_local_datetime(&local) {
+        LocalResult::Single(offset) => Ok(offset.fix().local_minus_utc()),
+        LocalResult::Ambiguous(..) => Err(CalendarError::AmbiguousLocalTime(id.to_owned())),
+        LocalResult::None => Err(CalendarError::NonexistentLocalTime(id.to_owned())),
+    }
+}
+
+#[cfg(not(feature = "time-zone"))]
+fn zone_offset(id: &str, _: Instant) -> Result<i32, CalendarError> {
+    Err(CalendarError::UnknownTimeZone(id.to_owned()))
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impl {
+    use std::{convert::TryFrom, ops::Range};
+
+    use chrono::{
+        Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone as _, Timelike, Utc,
+    };
+
+    use super::{
+        super::{DayOfWeek, Month},
+        full_date, interval, invalid_date, time_of_day, utc_offset, CalendarError, Date, DateTime,
+        Instant, Interval, TimeOfDay, TimeOffset, Timestamp,
+    };
+
+    // chrono represents a leap second as the 59th second with one billion nanoseconds or more.
+    const NANOS_PER_SECOND: u32 = 1_000_000_000;
+
+    fn naive_date(year: i32, month: i32, day: i32) -> Result<NaiveDate, CalendarError> {
+        let (y, m, d) = full_date(year, month, day)?;
+        NaiveDate::from_ymd_opt(y, m, d).ok_or_else(|| invalid_date(year, month, day))
+    }
+
+    fn naive_time(
+        hours: i32,
+        minutes: i32,
+        seconds: i32,
+        nanos: i32,
+    ) -> Result<NaiveTime, CalendarError> {
+        let leap = seconds == 60;
+        let (h, m, s, n) = time_of_day(hours, minutes, if leap { 59 } else { seconds }, nanos)?;
+        let n = if leap { n + NANOS_PER_SECOND } else { n };
+        NaiveTime::from_hms_nano_opt(h, m, s, n).ok_or(CalendarError::UnsupportedTime {
+            hours,
+            minutes,
+            seconds,
+        })
+    }
+
+    // Returns the seconds and nanos of a time, with leap seconds as the 60th second.
+    fn seconds_nanos(t: &impl Timelike) -> (i32, i32) {
+        if t.nanosecond() >= NANOS_PER_SECOND {
+            (60, (t.nanosecond() - NANOS_PER_SECOND) as i32)
+        } else {
+            (t.second() as i32, t.nanosecond() as i32)
+        }
+    }
+
+    fn to_date_time(local: NaiveDateTime, time_offset: Option<TimeOffset>) -> DateTime {
+        let (seconds, nanos) = seconds_nanos(&local);
+        DateTime {
+            year: local.year(),
+            month: local.month() as i32,
+            day: local.day() as i32,
+            hours: local.hour() as i32,
+            minutes: local.minute() as i32,
+            seconds,
+            nanos,
+            time_offset,
+        }
+    }
+
+    fn utc_offset_of(seconds: i32) -> Option<TimeOffset> {
+        Some(TimeOffset::UtcOffset(prost_types::Duration {
+            seconds: seconds.into(),
+            nanos: 0,
+        }))
+    }
+
+    fn to_utc((seconds, nanos): Instant) -> Result<chrono::DateTime<Utc>, CalendarError> {
+        Utc.timestamp_opt(seconds, nanos)
+            .single()
+            .ok_or(CalendarError::OutOfRange {
+                field: "seconds",
+                value: seconds,
+            })
+    }
+
+    fn to_timestamp(t: &chrono::DateTime<Utc>) -> Timestamp {
+        Timestamp {
+            seconds: t.timestamp(),
+            nanos: t.timestamp_subsec_nanos().min(NANOS_PER_SECOND - 1) as i32,
+        }
+    }
+
+    impl TryFrom<&Date> for NaiveDate {
+        type Error = CalendarError;
+
+        /// Fails if the date is partial or does not exist.
+        fn try_from(value: &Date) -> Result<Self, Self::Error> {
+            naive_date(value.year, value.month, value.day)
+        }
+    }
+
+    impl TryFrom<Date> for NaiveDate {
+        type Error = CalendarError;
+
+        fn try_from(value: Date) -> Result<Self, Self::Error> {
+            NaiveDate::try_from(&value)
+        }
+    }
+
+    impl From<NaiveDate> for Date {
+        fn from(value: NaiveDate) -> Self {
+            Date {
+                year: value.year(),
+                month: value.month() as i32,
+                day: value.day() as i32,
+            }
+        }
+    }
+
+    impl TryFrom<&TimeOfDay> for NaiveTime {
+        type Error = CalendarError;
+
+        /// Converts leap seconds, but fails on `24:00:00`.
+        fn try_from(value: &TimeOfDay) -> Result<Self, Self::Error> {
+            naive_time(value.hours, value.minutes, value.seconds, value.nanos)
+        }
+    }
+
+    impl TryFrom<TimeOfDay> for NaiveTime {
+        type Error = CalendarError;
+
+        fn try_from(value: TimeOfDay) -> Result<Self, Self::Error> {
+            NaiveTime::try_from(&value)
+        }
+    }
+
+    impl From<NaiveTime> for TimeOfDay {
+        fn from(value: NaiveTime) -> Self {
+            let (seconds, nanos) = seconds_nanos(&value);
+            TimeOfDay {
+                hours: value.hour() as i32,
+                minutes: value.minute() as i32,
+                seconds,
+                nanos,
+            }
+        }
+    }
+
+    impl TryFrom<&DateTime> for NaiveDateTime {
+        type Error = CalendarError;
+
+        /// Converts the civil date and time, ignoring the UTC offset or time zone.
+        fn try_from(value: &DateTime) -> Result<Self, Self::Error> {
+            let date = naive_date(value.year, value.month, value.day)?;
+            let time = naive_time(value.hours, value.minutes, value.seconds, value.nanos)?;
+            Ok(date.and_time(time))
+        }
+    }
+
+    impl TryFrom<DateTime> for NaiveDateTime {
+        type Error = CalendarError;
+
+        fn try_from(value: DateTime) -> Result<Self, Self::Error> {
+            NaiveDateTime::try_from(&value)
+        }
+    }
+
+    impl From<NaiveDateTime> for DateTime {
+        /// Converts to a date time in local time.
+        fn from(value: NaiveDateTime) -> Self {
+            to_date_time(value, None)
+        }
+    }
+
+    impl TryFrom<&DateTime> for chrono::DateTime<FixedOffset> {
+        type Error = CalendarError;
+
+        /// Fails if the date time is in local time. A time zone is resolved with the IANA Time
+        /// Zone Database if the `time-zone` feature is enabled.
+        fn try_from(value: &DateTime) -> Result<Self, Self::Error> {
+            let local = NaiveDateTime::try_from(value)?;
+            let utc = local.and_utc();
+            let offset = utc_offset(
+                value,
+                (
+                    utc.timestamp(),
+                    utc.timestamp_subsec_nanos() % NANOS_PER_SECOND,
+                ),
+            )?;
+            FixedOffset::east_opt(offset)
+                .and_then(|offset| offset.from_local_datetime(&local).single())
+                .ok_or(CalendarError::OutOfRange {
+                    field: "utc_offset",
+                    value: offset.into(),
+                })
+        }
+    }
+
+    impl TryFrom<DateTime> for chrono::DateTime<FixedOffset> {
+        type Error = CalendarError;
+
+        fn try_from(value: DateTime) -> Result<Self, Self::Error> {
+            chrono::DateTime::try_from(&value)
+        }
+    }
+
+    impl TryFrom<&DateTime> for chrono::DateTime<Utc> {
+        type Error = CalendarError;
+
+        fn try_from(value: &DateTime) -> Result<Self, Self::Error> {
+            chrono::DateTime::<FixedOffset>::try_from(value).map(|t| t.with_timezone(&Utc))
+        }
+    }
+
+    impl TryFrom<DateTime> for chrono::DateTime<Utc> {
+        type Error = CalendarError;
+
+        fn try_from(value: DateTime) -> Result<Self, Self::Error> {
+            chrono::DateTime::try_from(&value)
+        }
+    }
+
+    impl From<chrono::DateTime<FixedOffset>> for DateTime {
+        fn from(value: chrono::DateTime<FixedOffset>) -> Self {
+            let offset = value.offset().local_minus_utc();
+            to_date_time(value.naive_local(), utc_offset_of(offset))
+        }
+    }
+
+    impl From<chrono::DateTime<Utc>> for DateTime {
+        fn from(value: chrono::DateTime<Utc>) -> Self {
+            to_date_time(value.naive_utc(), utc_offset_of(0))
+        }
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    impl From<chrono::DateTime<chrono_tz::Tz>> for DateTime {
+        /// Converts to a date time with the IANA time zone id, leaving its version empty.
+        fn from(value: chrono::DateTime<chrono_tz::Tz>) -> Self {
+            let tz = super::super::TimeZone {
+                id: value.timezone().name().to_owned(),
+                version: String::new(),
+            };
+            to_date_time(value.naive_local(), Some(TimeOffset::TimeZone(tz)))
+        }
+    }
+
+    impl TryFrom<&Interval> for Range<chrono::DateTime<Utc>> {
+        type Error = CalendarError;
+
+        /// Fails if the interval has no start or no end time, or ends before it starts.
+        fn try_from(value: &Interval) -> Result<Self, Self::Error> {
+            let (start, end) = interval(value)?;
+            Ok(to_utc(start)?..to_utc(end)?)
+        }
+    }
+
+    impl TryFrom<Interval> for Range<chrono::DateTime<Utc>> {
+        type Error = CalendarError;
+
+        fn try_from(value: Interval) -> Result<Self, Self::Error> {
+            Range::try_from(&value)
+        }
+    }
+
+    impl From<Range<chrono::DateTime<Utc>>> for Interval {
+        fn from(value: Range<chrono::DateTime<Utc>>) -> Self {
+            Interval {
+                start_time: Some(to_timestamp(&value.start)),
+                end_time: Some(to_timestamp(&value.end)),
+            }
+        }
+    }
+
+    impl TryFrom<DayOfWeek> for chrono::Weekday {
+        type Error = CalendarError;
+
+        fn try_from(value: DayOfWeek) -> Result<Self, Self::Error> {
+            Ok(match value {
+                DayOfWeek::Unspecified => return Err(CalendarError::Unspecified("day of week")),
+                DayOfWeek::Monday => chrono::Weekday::Mon,
+                DayOfWeek::Tuesday => chrono::Weekday::Tue,
+                DayOfWeek::Wednesday => chrono::Weekday::Wed,
+                DayOfWeek::Thursday => chrono::Weekday::Thu,
+                DayOfWeek::Friday => chrono::Weekday::Fri,
+                DayOfWeek::Saturday => chrono::Weekday::Sat,
+                DayOfWeek::Sunday => chrono::Weekday::Sun,
+            })
+        }
+    }
+
+    impl From<chrono::Weekday> for DayOfWeek {
+        fn from(value: chrono::Weekday) -> Self {
+            match value {
+                chrono::Weekday::Mon => DayOfWeek::Monday,
+                chrono::Weekday::Tue => DayOfWeek::Tuesday,
+                chrono::Weekday::Wed => DayOfWeek::Wednesday,
+                chrono::Weekday::Thu => DayOfWeek::Thursday,
+                chrono::Weekday::Fri => DayOfWeek::Friday,
+                chrono::Weekday::Sat => DayOfWeek::Saturday,
+                chrono::Weekday::Sun => DayOfWeek::Sunday,
+            }
+        }
+    }
+
+    impl TryFrom<Month> for chrono::Month {
+        type Error = CalendarError;
+
+        fn try_from(value: Month) -> Result<Self, Self::Error> {
+            Ok(match value {
+                Month::Unspecified => return Err(CalendarError::Unspecified("month")),
+                Month::January => chrono::Month::January,
+                Month::February => chrono::Month::February,
+                Month::March => chrono::Month::March,
+                Month::April => chrono::Month::April,
+                Month::May => chrono::Month::May,
+                Month::June => chrono::Month::June,
+                Month::July => chrono::Month::July,
+                Month::August => chrono::Month::August,
+                Month::September => chrono::Month::September,
+                Month::October => chrono::Month::October,
+                Month::November => chrono::Month::November,
+                Month::December => chrono::Month::December,
+            })
+        }
+    }
+
+    impl From<chrono::Month> for Month {
+        fn from(value: chrono::Month) -> Self {
+            match value {
+                chrono::Month::January => Month::January,
+                chrono::Month::February => Month::February,
+                chrono::Month::March => Month::March,
+                chrono::Month::April => Month::April,
+                chrono::Month::May => Month::May,
+                chrono::Month::June => Month::June,
+                chrono::Month::July => Month::July,
+                chrono::Month::August => Month::August,
+                chrono::Month::September => Month::September,
+                chrono::Month::October => Month::October,
+                chrono::Month::November => Month::November,
+                chrono::Month::December => Month::December,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impl {
+    use std::{convert::TryFrom, ops::Range};
+
+    use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+    use super::{
+        super::{DayOfWeek, Month},
+        full_date, interval, invalid_date, time_of_day, utc_offset, CalendarError, Date, DateTime,
+        Instant, Interval, TimeOfDay, TimeOffset, Timestamp,
+    };
+
+    fn to_date(year: i32, month: i32, day: i32) -> Result<time::Date, CalendarError> {
+        let (y, m, d) = full_date(year, month, day)?;
+        let m = time::Month::try_from(m as u8).map_err(|_| invalid_date(year, month, day))?;
+        time::Date::from_calendar_date(y, m, d as u8).map_err(|_| invalid_date(year, month, day))
+    }
+
+    // time does not support leap seconds, they are reported as unsupported.
+    fn to_time(
+        hours: i32,
+        minutes: i32,
+        seconds: i32,
+        nanos: i32,
+    ) -> Result<time::Time, CalendarError> {
+        let (h, m, s, n) = time_of_day(hours, minutes, seconds, nanos)?;
+        time::Time::from_hms_nano(h as u8, m as u8, s as u8, n).map_err(|_| {
+            CalendarError::UnsupportedTime {
+                hours,
+                minutes,
+                seconds,
+            }
+        })
+    }
+
+    fn to_date_time(local: PrimitiveDateTime, time_offset: Option<TimeOffset>) -> DateTime {
+        DateTime {
+            year: local.year(),
+            month: u8::from(local.month()).into(),
+            day: local.day().into(),
+            hours: local.hour().into(),
+            minutes: local.minute().into(),
+            seconds: local.second().into(),
+            nanos: local.nanosecond() as i32,
+            time_offset,
+        }
+    }
+
+    fn to_offset_date_time((seconds, nanos): Instant) -> Result<OffsetDateTime, CalendarError> {
+        let t = i128::from(seconds) * 1_000_000_000 + i128::from(nanos);
+        OffsetDateTime::from_unix_timestamp_nanos(t).map_err(|_| CalendarError::OutOfRange {
+            field: "seconds",
+            value: seconds,
+        })
+    }
+
+    fn to_timestamp(t: &OffsetDateTime) -> Timestamp {
+        Timestamp {
+            seconds: t.unix_timestamp(),
+            nanos: t.nanosecond() as i32,
+        }
+    }
+
+    impl TryFrom<&Date> for time::Date {
+        type Error = CalendarError;
+
+        /// Fails if the date is partial or does not exist.
+        fn try_from(value: &Date) -> Result<Self, Self::Error> {
+            to_date(value.year, value.month, value.day)
+        }
+    }
+
+    impl TryFrom<Date> for time::Date {
+        type Error = CalendarError;
+
+        fn try_from(value: Date) -> Result<Self, Self::Error> {
+            time::Date::try_from(&value)
+        }
+    }
+
+    impl From<time::Date> for Date {
+        fn from(value: time::Date) -> Self {
+            Date {
+                year: value.year(),
+                month: u8::from(value.month()).into(),
+                day: value.day().into(),
+            }
+        }
+    }
+
+    impl TryFrom<&TimeOfDay> for time::Time {
+        type Error = CalendarError;
+
+        /// Fails on leap seconds and `24:00:00`.
+        fn try_from(value: &TimeOfDay) -> Result<Self, Self::Error> {
+            to_time(value.hours, value.minutes, value.seconds, value.nanos)
+        }
+    }
+
+    impl TryFrom<TimeOfDay> for time::Time {
+        type Error = CalendarError;
+
+        fn try_from(value: TimeOfDay) -> Result<Self, Self::Error> {
+            time::Time::try_from(&value)
+        }
+    }
+
+    impl From<time::Time> for TimeOfDay {
+        fn from(value: time::Time) -> Self {
+            TimeOfDay {
+                hours: value.hour().into(),
+                minutes: value.minute().into(),
+                seconds: value.second().into(),
+                nanos: value.nanosecond() as i32,
+            }
+        }
+    }
+
+    impl TryFrom<&DateTime> for PrimitiveDateTime {
+        type Error = CalendarError;
+
+        /// Converts the civil date and time, ignoring the UTC offset or time zone.
+        fn try_from(value: &DateTime) -> Result<Self, Self::Error> {
+            let date = to_date(value.year, value.month, value.day)?;
+            let time = to_time(value.hours, value.minutes, value.seconds, value.nanos)?;
+            Ok(PrimitiveDateTime::new(date, time))
+        }
+    }
+
+    impl TryFrom<DateTime> for PrimitiveDateTime {
+        type Error = CalendarError;
+
+        fn try_from(value: DateTime) -> Result<Self, Self::Error> {
+            PrimitiveDateTime::try_from(&value)
+        }
+    }
+
+    impl From<PrimitiveDateTime> for DateTime {
+        /// Converts to a date time in local time.
+        fn from(value: PrimitiveDateTime) -> Self {
+            to_date_time(value, None)
+        }
+    }
+
+    impl TryFrom<&DateTime> for OffsetDateTime {
+        type Error = CalendarError;
+
+        /// Fails if the date time is in local time. A time zone is resolved with the IANA Time
+        /// Zone Database if the `time-zone` feature is enabled.
+        fn try_from(value: &DateTime) -> Result<Self, Self::Error> {
+            let local = PrimitiveDateTime::try_from(value)?;
+            let utc = local.assume_utc();
+            let offset = utc_offset(value, (utc.unix_timestamp(), utc.nanosecond()))?;
+            let offset =
+                UtcOffset::from_whole_seconds(offset).map_err(|_| CalendarError::OutOfRange {
+                    field: "utc_offset",
+                    value: offset.into(),
+                })?;
+            Ok(local.assume_offset(offset))
+        }
+    }
+
+    impl TryFrom<DateTime> for OffsetDateTime {
+        type Error = CalendarError;
+
+        fn try_from(value: DateTime) -> Result<Self, Self::Error> {
+            OffsetDateTime::try_from(&value)
+        }
+    }
+
+    impl From<OffsetDateTime> for DateTime {
+        fn from(value: OffsetDateTime) -> Self {
+            let offset = TimeOffset::UtcOffset(prost_types::Duration {
+                seconds: value.offset().whole_seconds().into(),
+                nanos: 0,
+            });
+            to_date_time(
+                PrimitiveDateTime::new(value.date(), value.time()),
+                Some(offset),
+            )
+        }
+    }
+
+    impl TryFrom<&Interval> for Range<OffsetDateTime> {
+        type Error = CalendarError;
+
+        /// Fails if the interval has no start or no end time, or ends before it starts.
+        fn try_from(value: &Interval) -> Result<Self, Self::Error> {
+            let (start, end) = interval(value)?;
+            Ok(to_offset_date_time(start)?..to_offset_date_time(end)?)
+        }
+    }
+
+    impl TryFrom<Interval> for Range<OffsetDateTime> {
+        type Error = CalendarError;
+
+        fn try_from(value: Interval) -> Result<Self, Self::Error> {
+            Range::try_from(&value)
+        }
+    }
+
+    impl From<Range<OffsetDateTime>> for Interval {
+        fn from(value: Range<OffsetDateTime>) -> Self {
+            Interval {
+                start_time: Some(to_timestamp(&value.start)),
+                end_time: Some(to_timestamp(&value.end)),
+            }
+        }
+    }
+
+    impl TryFrom<DayOfWeek> for time::Weekday {
+        type Error = CalendarError;
+
+        fn try_from(value: DayOfWeek) -> Result<Self, Self::Error> {
+            Ok(match value {
+                DayOfWeek::Unspecified => return Err(CalendarError::Unspecified("day of week")),
+                DayOfWeek::Monday => time::Weekday::Monday,
+                DayOfWeek::Tuesday => time::Weekday::Tuesday,
+                DayOfWeek::Wednesday => time::Weekday::Wednesday,
+                DayOfWeek::Thursday => time::Weekday::Thursday,
+                DayOfWeek::Friday => time::Weekday::Friday,
+                DayOfWeek::Saturday => time::Weekday::Saturday,
+                DayOfWeek::Sunday => time::Weekday::Sunday,
+            })
+        }
+    }
+
+    impl From<time::Weekday> for DayOfWeek {
+        fn from(value: time::Weekday) -> Self {
+            match value {
+                time::Weekday::Monday => DayOfWeek::Monday,
+                time::Weekday::Tuesday => DayOfWeek::Tuesday,
+                time::Weekday::Wednesday => DayOfWeek::Wednesday,
+                time::Weekday::Thursday => DayOfWeek::Thursday,
+                time::Weekday::Friday => DayOfWeek::Friday,
+                time::Weekday::Saturday => DayOfWeek::Saturday,
+                time::Weekday::Sunday => DayOfWeek::Sunday,
+            }
+        }
+    }
+
+    impl TryFrom<Month> for time::Month {
+        type Error = CalendarError;
+
+        fn try_from(value: Month) -> Result<Self, Self::Error> {
+            if value == Month::Unspecified {
+                return Err(CalendarError::Unspecified("month"));
+            }
+            time::Month::try_from(value as u8).map_err(|_| CalendarError::OutOfRange {
+                field: "month",
+                value: value as i64,
+            })
+        }
+    }
+
+    impl From<time::Month> for Month {
+        fn from(value: time::Month) -> Self {
+            Month::from_i32(u8::from(value).into()).unwrap_or(Month::Unspecified)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_date() {
+        assert_eq!(full_date(2021, 2, 28), Ok((2021, 2, 28)));
+        assert_eq!(
+            full_date(0, 12, 25),
+            Err(CalendarError::PartialDate {
+                year: 0,
+                month: 12,
+                day: 25
+            })
+        );
+        assert_eq!(
+            full_date(2021, 0, 0),
+            Err(CalendarError::PartialDate {
+                year: 2021,
+                month: 0,
+                day: 0
+            })
+        );
+        assert_eq!(
+            full_date(2021, 13, 1),
+            Err(CalendarError::OutOfRange {
+                field: "month",
+                value: 13
+            })
+        );
+        assert_eq!(
+            full_date(-1, 1, 1),
+            Err(CalendarError::OutOfRange {
+                field: "year",
+                value: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        assert_eq!(
+            time_of_day(23, 59, 59, 999_999_999),
+            Ok((23, 59, 59, 999_999_999))
+        );
+        assert_eq!(
+            time_of_day(24, 0, 0, 0),
+            Err(CalendarError::UnsupportedTime {
+                hours: 24,
+                minutes: 0,
+                seconds: 0
+            })
+        );
+        assert_eq!(
+            time_of_day(12, 60, 0, 0),
+            Err(CalendarError::OutOfRange {
+                field: "minutes",
+                value: 60
+            })
+        );
+        assert_eq!(
+            time_of_day(12, 0, 0, -1),
+            Err(CalendarError::OutOfRange {
+                field: "nanos",
+                value: -1
+            })
+        );
+    }
+
+    fn date_time(time_offset: Option<TimeOffset>) -> DateTime {
+        DateTime {
+            year: 2021,
+            month: 3,
+            day: 28,
+            hours: 2,
+            minutes: 30,
+            seconds: 0,
+            nanos: 0,
+            time_offset,
+        }
+    }
+
+    #[test]
+    fn test_utc_offset() {
+        let offset = |seconds, nanos| {
+            Some(TimeOffset::UtcOffset(prost_types::Duration {
+                seconds,
+                nanos,
+            }))
+        };
+        assert_eq!(
+            utc_offset(&date_time(offset(-14400, 0)), (0, 0)),
+            Ok(-14400)
+        );
+        assert_eq!(
+            utc_offset(&date_time(offset(18 * 3600 + 1, 0)), (0, 0)),
+            Err(CalendarError::OutOfRange {
+                field: "utc_offset",
+                value: 18 * 3600 + 1
+            })
+        );
+        assert_eq!(
+            utc_offset(&date_time(offset(0, 1)), (0, 0)),
+            Err(CalendarError::OutOfRange {
+                field: "utc_offset",
+                value: 1
+            })
+        );
+        assert_eq!(
+            utc_offset(&date_time(None), (0, 0)),
+            Err(CalendarError::MissingOffset)
+        );
+    }
+
+    #[test]
+    fn test_interval() {
+        let ts = |seconds| Some(Timestamp { seconds, nanos: 0 });
+        let i = |start_time, end_time| Interval {
+            start_time,
+            end_time,
+        };
+        assert_eq!(interval(&i(ts(1), ts(2))), Ok(((1, 0), (2, 0))));
+        assert_eq!(interval(&i(ts(2), ts(2))), Ok(((2, 0), (2, 0))));
+        assert_eq!(
+            interval(&i(ts(2), ts(1))),
+            Err(CalendarError::InvalidInterval)
+        );
+        assert_eq!(
+            interval(&i(None, ts(1))),
+            Err(CalendarError::UnboundedInterval)
+        );
+        assert_eq!(
+            interval(&i(ts(1), ts(MAX_TIMESTAMP_SECONDS + 1))),
+            Err(CalendarError::OutOfRange {
+                field: "end_time",
+                value: MAX_TIMESTAMP_SECONDS + 1
+            })
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use std::convert::TryFrom;
+
+        use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+        use super::super::{DayOfWeek, Month};
+
+        let d = NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        let date = Date {
+            year: 2020,
+            month: 2,
+            day: 29,
+        };
+        assert_eq!(NaiveDate::try_from(&date), Ok(d));
+        assert_eq!(Date::from(d), date);
+        assert_eq!(
+            NaiveDate::try_from(Date {
+                year: 2021,
+                month: 2,
+                day: 29
+            }),
+            Err(CalendarError::InvalidDate {
+                year: 2021,
+                month: 2,
+                day: 29
+            })
+        );
+
+        let leap = TimeOfDay {
+            hours: 23,
+            minutes: 59,
+            seconds: 60,
+            nanos: 5,
+        };
+        let t = NaiveTime::try_from(&leap).unwrap();
+        assert_eq!(
+            t,
+            NaiveTime::from_hms_nano_opt(23, 59, 59, 1_000_000_005).unwrap()
+        );
+        assert_eq!(TimeOfDay::from(t), leap);
+
+        let offset = Some(TimeOffset::UtcOffset(prost_types::Duration {
+            seconds: -14400,
+            nanos: 0,
+        }));
+        let dt = chrono::DateTime::<FixedOffset>::try_from(&date_time(offset.clone())).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2021-03-28T02:30:00-04:00");
+        assert_eq!(DateTime::from(dt), date_time(offset));
+        assert_eq!(
+            chrono::DateTime::<Utc>::try_from(&date_time(None)),
+            Err(CalendarError::MissingOffset)
+        );
+        let local = NaiveDateTime::try_from(&date_time(None)).unwrap();
+        assert_eq!(DateTime::from(local), date_time(None));
+
+        let start = Utc.timestamp_opt(1_000, 1).unwrap();
+        let end = Utc.timestamp_opt(2_000, 0).unwrap();
+        let interval = Interval::from(start..end);
+        assert_eq!(
+            interval.start_time,
+            Some(Timestamp {
+                seconds: 1_000,
+                nanos: 1
+            })
+        );
+        assert_eq!(std::ops::Range::try_from(&interval), Ok(start..end));
+
+        assert_eq!(
+            chrono::Weekday::try_from(DayOfWeek::Sunday),
+            Ok(chrono::Weekday::Sun)
+        );
+        assert_eq!(DayOfWeek::from(chrono::Weekday::Mon), DayOfWeek::Monday);
+        assert_eq!(
+            chrono::Month::try_from(Month::Unspecified),
+            Err(CalendarError::Unspecified("month"))
+        );
+        assert_eq!(Month::from(chrono::Month::December), Month::December);
+    }
+
+    #[cfg(feature = "time-zone")]
+    fn berlin(hours: i32) -> DateTime {
+        let mut dt = date_time(Some(TimeOffset::TimeZone(super::super::TimeZone {
+            id: "Europe/Berlin".into(),
+            version: String::new(),
+        })));
+        dt.hours = hours;
+        dt
+    }
+
+    #[cfg(all(feature = "chrono", feature = "time-zone"))]
+    #[test]
+    fn test_chrono_time_zone() {
+        use std::convert::TryFrom;
+
+        let dt = chrono::DateTime::<chrono::FixedOffset>::try_from(&berlin(3)).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2021-03-28T03:30:00+02:00");
+        assert_eq!(
+            chrono::DateTime::<chrono::FixedOffset>::try_from(&berlin(2)),
+            Err(CalendarError::NonexistentLocalTime("Europe/Berlin".into()))
+        );
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn test_chrono_tz() {
+        use chrono::TimeZone;
+
+        let dt = chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2021, 3, 28, 3, 30, 0)
+            .unwrap();
+        assert_eq!(DateTime::from(dt), berlin(3));
+    }
+
+    #[cfg(all(feature = "time", feature = "time-zone"))]
+    #[test]
+    fn test_time_time_zone() {
+        use std::convert::TryFrom;
+
+        use time::OffsetDateTime;
+
+        let dt = OffsetDateTime::try_from(&berlin(3)).unwrap();
+        assert_eq!(dt.offset().whole_seconds(), 7200);
+        assert_eq!(dt.unix_timestamp(), 1_616_895_000);
+        assert_eq!(
+            OffsetDateTime::try_from(&berlin(2)),
+            Err(CalendarError::NonexistentLocalTime("Europe/Berlin".into()))
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time() {
+        use std::convert::TryFrom;
+
+        use time::{OffsetDateTime, PrimitiveDateTime};
+
+        use super::super::{DayOfWeek, Month};
+
+        let date = Date {
+            year: 2020,
+            month: 2,
+            day: 29,
+        };
+        let d = time::Date::try_from(&date).unwrap();
+        assert_eq!(d.to_string(), "2020-02-29");
+        assert_eq!(Date::from(d), date);
+        assert_eq!(
+            time::Date::try_from(Date {
+                year: 2020,
+                month: 0,
+                day: 0
+            }),
+            Err(CalendarError::PartialDate {
+                year: 2020,
+                month: 0,
+                day: 0
+            })
+        );
+
+        let t = TimeOfDay {
+            hours: 9,
+            minutes: 5,
+            seconds: 7,
+            nanos: 500,
+        };
+        assert_eq!(TimeOfDay::from(time::Time::try_from(&t).unwrap()), t);
+        assert_eq!(
+            time::Time::try_from(TimeOfDay {
+                hours: 23,
+                minutes: 59,
+                seconds: 60,
+                nanos: 0
+            }),
+            Err(CalendarError::UnsupportedTime {
+                hours: 23,
+                minutes: 59,
+                seconds: 60
+            })
+        );
+
+        let offset = Some(TimeOffset::UtcOffset(prost_types::Duration {
+            seconds: 19800,
+            nanos: 0,
+        }));
+        let dt = OffsetDateTime::try_from(&date_time(offset.clone())).unwrap();
+        assert_eq!(dt.unix_timestamp(), 1_616_878_800);
+        assert_eq!(dt.offset().whole_seconds(), 19800);
+        assert_eq!(DateTime::from(dt), date_time(offset));
+        let local = PrimitiveDateTime::try_from(&date_time(None)).unwrap();
+        assert_eq!(DateTime::from(local), date_time(None));
+
+        let interval = Interval {
+            start_time: Some(Timestamp {
+                seconds: -1,
+                nanos: 999_999_999,
+            }),
+            end_time: Some(Timestamp {
+                seconds: 0,
+                nanos: 0,
+            }),
+        };
+        let range = std::ops::Range::<OffsetDateTime>::try_from(&interval).unwrap();
+        assert_eq!(range.start.unix_timestamp_nanos(), -1);
+        assert_eq!(Interval::from(range), interval);
+
+        assert_eq!(
+            time::Weekday::try_from(DayOfWeek::Unspecified),
+            Err(CalendarError::Unspecified("day of week"))
+        );
+        assert_eq!(
+            DayOfWeek::from(time::Weekday::Saturday),
+            DayOfWeek::Saturday
+        );
+        assert_eq!(time::Month::try_from(Month::May), Ok(time::Month::May));
+        assert_eq!(Month::from(time::Month::January), Month::January);
+    }
+
+    #[cfg(feature = "time-zone")]
+    #[test]
+    fn test_zone_offset() {
+        // 2021-03-28 01:00 UTC, clocks in Europe/Berlin go from 02:00 to 03:00.
+        let at = |hours: i64| 1_616_889_600 + hours * 3600;
+        assert_eq!(zone_offset("Europe/Berlin", (at(1), 0)), Ok(3600));
+        assert_eq!(zone_offset("Europe/Berlin", (at(4), 0)), Ok(7200));
+        assert_eq!(
+            zone_offset("Europe/Berlin", (at(2), 0)),
+            Err(CalendarError::NonexistentLocalTime("Europe/Berlin".into()))
+        );
+        assert_eq!(
+            zone_offset("Mars/Olympus_Mons", (at(1), 0)),
+            Err(CalendarError::UnknownTimeZone("Mars/Olympus_Mons".into()))
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{error, fmt, str::FromStr};
+
+use super::Decimal;
+
+/// An error returned when a [`Decimal`] is not a valid decimal string, or does not fit in the
+/// requested type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecimalError {
+    /// The value does not match the grammar documented on [`Decimal::value`].
+    Invalid(String),
+    /// The value is out of the range or precision of the target type.
+    Overflow(String),
+}
+
+impl fmt::Display for DecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimalError::Invalid(v) => write!(f, "invalid decimal value: {:?}", v),
+            DecimalError::Overflow(v) => write!(f, "decimal value out of range: {:?}", v),
+        }
+    }
+}
+
+impl error::Error for DecimalError {}
+
+// The parts of a decimal string: `[sign] integer [. fraction] [e exponent]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Parts<'a> {
+    pub negative: bool,
+    pub integer: &'a str,
+    // `None` if the value has no decimal point.
+    pub fraction: Option<&'a str>,
+    // The exponent as written, with its sign.
+    pub exponent: Option<&'a str>,
+}
+
+impl<'a> Parts<'a> {
+    // DecimalString = [Sign] Significand [Exponent];
+    // Significand = Digits ['.'] [Digits] | [Digits] '.' Digits;
+    // Exponent = ('e' | 'E') [Sign] Digits;
+    pub fn parse(value: &'a str) -> Result<Self, DecimalError> {
+        let invalid = || DecimalError::Invalid(value.to_owned());
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+
+        let (negative, rest) = match value.as_bytes().first() {
+            Some(b'-') => (true, &value[1..]),
+            Some(b'+') => (false, &value[1..]),
+            _ => (false, value),
+        };
+        let (significand, exponent) = match rest.find(['e', 'E']) {
+            Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+            None => (rest, None),
+        };
+        let (integer, fraction) = match significand.find('.') {
+            Some(i) => (&significand[..i], Some(&significand[i + 1..])),
+            None => (significand, None),
+        };
+
+        if !digits(integer) || matches!(fraction, Some(f) if !digits(f)) {
+            return Err(invalid());
+        }
+        if integer.is_empty() && fraction.unwrap_or_default().is_empty() {
+            return Err(invalid());
+        }
+        if let Some(e) = exponent {
+            let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+            if e.is_empty() || !digits(e) {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self {
+            negative,
+            integer,
+            fraction,
+            exponent,
+        })
+    }
+
+    // The exponent as a number, `None` if it does not fit in an `i64`.
+    pub fn exponent(&self) -> Option<i64> {
+        match self.exponent {
+            Some(e) => e.trim_start_matches('+').parse().ok(),
+            None => Some(0),
+        }
+    }
+
+    // The value without exponent and sign, as significant digits and the number of them that
+    // are after the decimal point, which is negative for trailing zeros of the integer.
+    // `None` if the exponent is out of the range of `max_scale`.
+    pub fn digits(&self, max_scale: i64) -> Option<(String, i64)> {
+        let fraction = self.fraction.unwrap_or_default();
+        let digits = format!("{}{}", self.integer, fraction);
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            // Zero is in range whatever its exponent.
+            let scale = fraction.len() as i64 - self.exponent().unwrap_or_default();
+            return Some(("0".to_owned(), scale.clamp(-max_scale, max_scale)));
+        }
+        let scale = (fraction.len() as i64).checked_sub(self.exponent()?)?;
+        if scale.checked_abs()? > max_scale {
+            return None;
+        }
+        Some((digits.to_owned(), scale))
+    }
+}
+
+impl Decimal {
+    /// Checks that the value matches the grammar documented on [`Decimal::value`].
+    pub fn validate(&self) -> Result<(), DecimalError> {
+        Parts::parse(&self.value).map(|_| ())
+    }
+
+    /// Returns the value normalized as recommended by the documentation of [`Decimal::value`]:
+    /// an explicit `+` sign and a zero exponent are removed, an empty integer is replaced by `0`
+    /// and the exponent character is lower-cased, e.g. `+.5E0` becomes `0.5`.
+    pub fn normalize(&self) -> Result<Decimal, DecimalError> {
+        let parts = Parts::parse(&self.value)?;
+
+        let mut value = String::with_capacity(self.value.len() + 1);
+        if parts.negative {
+            value.push('-');
+        }
+        value.push_str(if parts.integer.is_empty() {
+            "0"
+        } else {
+            parts.integer
+        });
+        if let Some(fraction) = parts.fraction {
+            value.push('.');
+            value.push_str(fraction);
+        }
+        if let Some(exponent) = parts.exponent {
+            if exponent
+                .bytes()
+                .any(|b| b != b'0' && b != b'+' && b != b'-')
+            {
+                value.push('e');
+                value.push_str(exponent);
+            }
+        }
+
+        Ok(Decimal { value })
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    /// Parses and normalizes a decimal string.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal {
+            value: s.to_owned(),
+        }
+        .normalize()
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_impl {
+    use std::convert::TryFrom;
+
+    use super::{Decimal, DecimalError, Parts};
+
+    // rust_decimal supports at most 28 digits after the decimal point.
+    const MAX_SCALE: i64 = 28;
+
+    impl TryFrom<&Decimal> for rust_decimal::Decimal {
+        type Error = DecimalError;
+
+        /// Converts exactly, failing if the value is out of range or has too many digits.
+        fn try_from(value: &Decimal) -> Result<Self, Self::Error> {
+            let parts = Parts::parse(&value.value)?;
+            let overflow = || DecimalError::Overflow(value.value.clone());
+            let (digits, scale) = parts.digits(MAX_SCALE).ok_or_else(overflow)?;
+
+            let mut plain = String::with_capacity(digits.len() + MAX_SCALE as usize + 3);
+            if parts.negative {
+                plain.push('-');
+            }
+            if scale <= 0 {
+                plain.push_str(&digits);
+                plain.push_str(&"0".repeat(-scale as usize));
+            } else {
+                let scale = scale as usize;
+                let digits = format!("{:0>width$}", digits, width = scale + 1);
+                let (integer, fraction) = digits.split_at(digits.len() - scale);
+                plain.push_str(integer);
+                plain.push('.');
+                plain.push_str(fraction);
+            }
+
+            rust_decimal::Decimal::from_str_exact(&plain).map_err(|_| overflow())
+        }
+    }
+
+    impl TryFrom<Decimal> for rust_decimal::Decimal {
+        type Error = DecimalError;
+
+        fn try_from(value: Decimal) -> Result<Self, Self::Error> {
+            rust_decimal::Decimal::try_from(&value)
+        }
+    }
+
+    impl From<rust_decimal::Decimal> for Decimal {
+        fn from(value: rust_decimal::Decimal) -> Self {
+            Decimal {
+                value: value.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal {
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        for v in &[
+            "0", "-1", "+2.5", ".5", "5.", "2.5E8", "2.5e-8", "1e+3", "007.700",
+        ] {
+            assert_eq!(decimal(v).validate(), Ok(()), "{}", v);
+        }
+        for v in &[
+            "", "+", "-", ".", "e5", ".e5", "1e", "1e+", "1.2.3", "1,000", "1 000", "--1", "0x10",
+            "1e5.5", "NaN", "Infinity",
+        ] {
+            assert_eq!(
+                decimal(v).validate(),
+                Err(DecimalError::Invalid(v.to_string())),
+                "{}",
+                v
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        for (v, want) in &[
+            ("+2.5", "2.5"),
+            (".5", "0.5"),
+            ("-.5", "-0.5"),
+            ("2.5E8", "2.5e8"),
+            ("2.5e0", "2.5"),
+            ("2.5e-00", "2.5"),
+            ("2.5E-8", "2.5e-8"),
+            ("+.5E+0", "0.5"),
+            ("2.50", "2.50"),
+            ("5.", "5."),
+        ] {
+            assert_eq!(decimal(v).normalize(), Ok(decimal(want)), "{}", v);
+        }
+        assert_eq!(
+            "1,5".parse::<Decimal>(),
+            Err(DecimalError::Invalid("1,5".into()))
+        );
+        assert_eq!("+.5E0".parse::<Decimal>().unwrap().to_string(), "0.5");
+    }
+
+    #[test]
+    fn test_digits() {
+        let digits = |v| Parts::parse(v).unwrap().digits(28);
+        assert_eq!(digits("12.340"), Some(("12340".into(), 3)));
+        assert_eq!(digits("-0.05"), Some(("5".into(), 2)));
+        assert_eq!(digits("1.5e3"), Some(("15".into(), -2)));
+        assert_eq!(digits("00e-2"), Some(("0".into(), 2)));
+        assert_eq!(digits("0.0e-99"), Some(("0".into(), 28)));
+        assert_eq!(digits("1e-29"), None);
+        assert_eq!(digits("1e99999999999999999999"), None);
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_rust_decimal() {
+        use std::convert::TryFrom;
+
+        let convert = |v| rust_decimal::Decimal::try_from(&decimal(v)).map(|d| d.to_string());
+        assert_eq!(convert("2.50"), Ok("2.50".into()));
+        assert_eq!(convert("-.5"), Ok("-0.5".into()));
+        assert_eq!(convert("1.5e3"), Ok("1500".into()));
+        assert_eq!(convert("25e-3"), Ok("0.025".into()));
+        assert_eq!(
+            convert("1e-28"),
+            Ok("0.0000000000000000000000000001".into())
+        );
+        assert_eq!(
+            convert("1e-29"),
+            Err(DecimalError::Overflow("1e-29".into()))
+        );
+        assert_eq!(convert("1e29"), Err(DecimalError::Overflow("1e29".into())));
+        assert_eq!(convert("x"), Err(DecimalError::Invalid("x".into())));
+
+        let d = rust_decimal::Decimal::new(-1234, 2);
+        assert_eq!(Decimal::from(d), decimal("-12.34"));
+    }
+}
@@ -0,0 +1,327 @@
+use std::{error, fmt};
+
+use prost_types::{value::Kind, ListValue, Struct, Value};
+
+use super::LatLng;
+
+/// The precision of the encoded polylines of the Routes API and Fleet Engine, in decimal digits.
+pub const POLYLINE_PRECISION: u32 = 5;
+const MAX_PRECISION: u32 = 10;
+// The mean radius of the Earth in meters.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// An error returned when encoding or decoding a polyline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolylineError {
+    /// The precision is above 10 decimal digits.
+    Precision(u32),
+    /// A latitude or a longitude is out of range or not finite.
+    OutOfRange(LatLng),
+    /// The encoded polyline has an invalid character, or a value overflowing 64 bits, at the
+    /// byte offset.
+    InvalidCharacter(usize),
+    /// The encoded polyline ends in the middle of a value or of a point.
+    Truncated,
+    /// The `Struct` is not a GeoJSON `LineString`.
+    GeoJson(String),
+}
+
+impl fmt::Display for PolylineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolylineError::Precision(p) => write!(f, "polyline precision out of range: {}", p),
+            PolylineError::OutOfRange(p) => write!(
+                f,
+                "coordinates out of range: latitude = {}, longitude = {}",
+                p.latitude, p.longitude
+            ),
+            PolylineError::InvalidCharacter(offset) => {
+                write!(f, "invalid encoded polyline at offset {}", offset)
+            }
+            PolylineError::Truncated => f.write_str("truncated encoded polyline"),
+            PolylineError::GeoJson(message) => write!(f, "invalid GeoJSON LineString: {}", message),
+        }
+    }
+}
+
+impl error::Error for PolylineError {}
+
+fn check_precision(precision: u32) -> Result<f64, PolylineError> {
+    if precision > MAX_PRECISION {
+        return Err(PolylineError::Precision(precision));
+    }
+    Ok(10f64.powi(precision as i32))
+}
+
+fn push_value(s: &mut String, v: i64) {
+    let mut v = ((v << 1) ^ (v >> 63)) as u64;
+    while v >= 0x20 {
+        s.push(char::from((0x20 | (v & 0x1f) as u8) + 63));
+        v >>= 5;
+    }
+    s.push(char::from(v as u8 + 63));
+}
+
+impl LatLng {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        LatLng {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Checks that the latitude is in `-90.0..=90.0` and the longitude in `-180.0..=180.0`.
+    pub fn validate(&self) -> Result<(), PolylineError> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(PolylineError::OutOfRange(self.clone()));
+        }
+        Ok(())
+    }
+
+    /// The great-circle distance to `other` in meters, with the haversine formula on a sphere
+    /// of the mean radius of the Earth.
+    pub fn distance(&self, other: &LatLng) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlng = (other.longitude - self.longitude).to_radians();
+        let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+    }
+
+    /// The length of a path in meters, see [`LatLng::distance`].
+    pub fn path_length(points: &[LatLng]) -> f64 {
+        points.windows(2).map(|w| w[0].distance(&w[1])).sum()
+    }
+
+    /// Encodes points with the [polyline encoding algorithm], the coordinates rounded to
+    /// `precision` decimal digits, usually [`POLYLINE_PRECISION`].
+    ///
+    /// [polyline encoding algorithm]: https://developers.google.com/maps/documentation/utilities/polylinealgorithm
+    pub fn encode_polyline(points: &[LatLng], precision: u32) -> Result<String, PolylineError> {
+        let factor = check_precision(precision)?;
+        let mut s = String::new();
+        let (mut lat, mut lng) = (0, 0);
+        for p in points {
+            p.validate()?;
+            let (next_lat, next_lng) = (
+                (p.latitude * factor).round() as i64,
+                (p.longitude * factor).round() as i64,
+            );
+            push_value(&mut s, next_lat - lat);
+            push_value(&mut s, next_lng - lng);
+            lat = next_lat;
+            lng = next_lng;
+        }
+        Ok(s)
+    }
+
+    /// Decodes a polyline encoded with `precision` decimal digits, see
+    /// [`LatLng::encode_polyline`].
+    pub fn decode_polyline(encoded: &str, precision: u32) -> Result<Vec<LatLng>, PolylineError> {
+        let factor = check_precision(precision)?;
+        let mut values = Vec::new();
+        let mut value = 0u64;
+        let mut shift = 0;
+        for (i, b) in encoded.bytes().enumerate() {
+            if !(63..=126).contains(&b) || shift > 60 {
+                return Err(PolylineError::InvalidCharacter(i));
+            }
+            let chunk = u64::from(b - 63);
+            value |= (chunk & 0x1f) << shift;
+            if chunk & 0x20 == 0 {
+                values.push((value >> 1) as i64 ^ -((value & 1) as i64));
+                value = 0;
+                shift = 0;
+            } else {
+                shift += 5;
+            }
+        }
+        if shift != 0 || values.len() % 2 != 0 {
+            return Err(PolylineError::Truncated);
+        }
+
+        let mut points = Vec::with_capacity(values.len() / 2);
+        let (mut lat, mut lng) = (0i64, 0i64);
+        for pair in values.chunks(2) {
+            // Saturated sums are out of range.
+            lat = lat.saturating_add(pair[0]);
+            lng = lng.saturating_add(pair[1]);
+            let p = LatLng::new(lat as f64 / factor, lng as f64 / factor);
+            p.validate()?;
+            points.push(p);
+        }
+        Ok(points)
+    }
+
+    /// A GeoJSON `LineString` geometry, see [RFC 7946](https://tools.ietf.org/html/rfc7946#section-3.1.4).
+    pub fn to_geo_json_line_string(points: &[LatLng]) -> Struct {
+        let number = |v: f64| Value {
+            kind: Some(Kind::NumberValue(v)),
+        };
+        let coordinates = points
+            .iter()
+            .map(|p| Value {
+                kind: Some(Kind::ListValue(ListValue {
+                    values: vec![number(p.longitude), number(p.latitude)],
+                })),
+            })
+            .collect();
+        let mut s = Struct::default();
+        s.fields.insert(
+            "type".to_owned(),
+            Value {
+                kind: Some(Kind::StringValue("LineString".to_owned())),
+            },
+        );
+        s.fields.insert(
+            "coordinates".to_owned(),
+            Value {
+                kind: Some(Kind::ListValue(ListValue {
+                    values: coordinates,
+                })),
+            },
+        );
+        s
+    }
+
+    /// The points of a GeoJSON `LineString` geometry. Altitudes are ignored.
+    pub fn from_geo_json_line_string(s: &Struct) -> Result<Vec<LatLng>, PolylineError> {
+        let invalid = |message: &str| PolylineError::GeoJson(message.to_owned());
+        match s.fields.get("type").and_then(|v| v.kind.as_ref()) {
+            Some(Kind::StringValue(t)) if t == "LineString" => {}
+            _ => return Err(invalid("type is not \"LineString\"")),
+        }
+        let coordinates = match s.fields.get("coordinates").and_then(|v| v.kind.as_ref()) {
+            Some(Kind::ListValue(l)) => &l.values,
+            _ => return Err(invalid("missing coordinates")),
+        };
+        coordinates
+            .iter()
+            .map(|position| {
+                let numbers = match &position.kind {
+                    Some(Kind::ListValue(l)) => l
+                        .values
+                        .iter()
+                        .map(|v| match v.kind {
+                            Some(Kind::NumberValue(n)) => Some(n),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>(),
+                    _ => None,
+                };
+                match numbers.as_deref() {
+                    Some([lng, lat]) | Some([lng, lat, _]) => {
+                        let p = LatLng::new(*lat, *lng);
+                        p.validate()?;
+                        Ok(p)
+                    }
+                    _ => Err(invalid("a position is not [longitude, latitude]")),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example of the polyline encoding algorithm documentation.
+    const EXAMPLE: &str = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+
+    fn example() -> Vec<LatLng> {
+        vec![
+            LatLng::new(38.5, -120.2),
+            LatLng::new(40.7, -120.95),
+            LatLng::new(43.252, -126.453),
+        ]
+    }
+
+    #[test]
+    fn test_encode_polyline() {
+        assert_eq!(LatLng::encode_polyline(&example(), 5).unwrap(), EXAMPLE);
+        assert_eq!(LatLng::encode_polyline(&[], 5).unwrap(), "");
+        assert_eq!(
+            LatLng::encode_polyline(&[LatLng::new(91.0, 0.0)], 5),
+            Err(PolylineError::OutOfRange(LatLng::new(91.0, 0.0)))
+        );
+        assert_eq!(
+            LatLng::encode_polyline(&example(), 11),
+            Err(PolylineError::Precision(11))
+        );
+    }
+
+    #[test]
+    fn test_decode_polyline() {
+        assert_eq!(LatLng::decode_polyline(EXAMPLE, 5).unwrap(), example());
+        let p6 = LatLng::encode_polyline(&example(), 6).unwrap();
+        assert_eq!(LatLng::decode_polyline(&p6, 6).unwrap(), example());
+        assert_eq!(
+            LatLng::decode_polyline("_p~iF~ps|U_ulL", 5),
+            Err(PolylineError::Truncated)
+        );
+        assert_eq!(
+            LatLng::decode_polyline("_p~iF~ps|", 5),
+            Err(PolylineError::Truncated)
+        );
+        assert_eq!(
+            LatLng::decode_polyline("_p~iF ps|U", 5),
+            Err(PolylineError::InvalidCharacter(5))
+        );
+        assert_eq!(
+            LatLng::decode_polyline(&"~".repeat(20), 5),
+            Err(PolylineError::InvalidCharacter(13))
+        );
+        // 38.5 with 5 digits is 3850 with 3.
+        assert!(matches!(
+            LatLng::decode_polyline(EXAMPLE, 3),
+            Err(PolylineError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_distance() {
+        let paris = LatLng::new(48.8566, 2.3522);
+        let london = LatLng::new(51.5074, -0.1278);
+        assert!((paris.distance(&london) - 343_560.0).abs() < 500.0);
+        assert_eq!(paris.distance(&paris), 0.0);
+        let antipode = LatLng::new(-48.8566, -177.6478);
+        assert!((paris.distance(&antipode) - std::f64::consts::PI * EARTH_RADIUS).abs() < 1.0);
+        assert_eq!(
+            LatLng::path_length(&[paris.clone(), london.clone(), paris.clone()]),
+            2.0 * paris.distance(&london)
+        );
+    }
+
+    #[test]
+    fn test_geo_json_line_string() {
+        let s = LatLng::to_geo_json_line_string(&example());
+        assert_eq!(LatLng::from_geo_json_line_string(&s).unwrap(), example());
+
+        let mut point = s.clone();
+        point.fields.insert(
+            "type".to_owned(),
+            Value {
+                kind: Some(Kind::StringValue("Point".to_owned())),
+            },
+        );
+        assert!(matches!(
+            LatLng::from_geo_json_line_string(&point),
+            Err(PolylineError::GeoJson(_))
+        ));
+        let mut short = s;
+        if let Some(Kind::ListValue(l)) = short
+            .fields
+            .get_mut("coordinates")
+            .and_then(|v| v.kind.as_mut())
+        {
+            if let Some(Kind::ListValue(position)) = l.values[0].kind.as_mut() {
+                position.values.pop();
+            }
+        }
+        assert!(matches!(
+            LatLng::from_geo_json_line_string(&short),
+            Err(PolylineError::GeoJson(_))
+        ));
+    }
+}
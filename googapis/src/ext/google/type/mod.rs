@@ -0,0 +1,13 @@
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod calendar;
+mod decimal;
+mod lat_lng;
+mod money;
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub use self::calendar::CalendarError;
+pub use self::{
+    decimal::DecimalError,
+    lat_lng::{PolylineError, POLYLINE_PRECISION},
+    money::{MoneyError, RoundingMode},
+};
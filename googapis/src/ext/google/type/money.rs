@@ -0,0 +1,698 @@
+use std::{convert::TryFrom, error, fmt};
+
+use super::{decimal::Parts, Decimal, DecimalError, Money};
+
+const NANOS_PER_UNIT: i128 = 1_000_000_000;
+const MAX_NANOS: i32 = 999_999_999;
+
+/// An error returned by the checked operations of [`Money`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    /// The operands have different currency codes.
+    CurrencyMismatch(String, String),
+    /// `nanos` is out of the `-999,999,999..=999,999,999` range.
+    NanosOutOfRange(i32),
+    /// `units` and `nanos` have different signs.
+    SignMismatch(i64, i32),
+    /// The result does not fit in `units`.
+    Overflow,
+    /// The amount has more than nine digits after the decimal point.
+    Precision(String),
+    /// The amount is not a valid decimal string.
+    Decimal(DecimalError),
+    /// The denominator of a ratio is zero.
+    DivisionByZero,
+}
+
+/// How to round a result that falls between two amounts representable with the requested
+/// precision, e.g. nanos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    /// Toward zero, i.e. truncation.
+    Down,
+    /// Away from zero.
+    Up,
+    /// Toward negative infinity.
+    Floor,
+    /// Toward positive infinity.
+    Ceiling,
+    /// To the nearest amount, ties away from zero.
+    HalfUp,
+    /// To the nearest amount, ties toward zero.
+    HalfDown,
+    /// To the nearest amount, ties to the even one, also known as banker's rounding.
+    HalfEven,
+}
+
+impl RoundingMode {
+    // Rounds `truncated + remainder / divisor`, where `|remainder| < |divisor|` and
+    // `truncated` is the quotient truncated toward zero.
+    fn round(self, truncated: i128, remainder: i128, divisor: i128) -> Option<i128> {
+        if remainder == 0 {
+            return Some(truncated);
+        }
+        // The direction of the discarded fraction, away from `truncated`.
+        let sign = remainder.signum() * divisor.signum();
+        let half = (2 * remainder.abs()).cmp(&divisor.abs());
+        let away = match self {
+            RoundingMode::Down => false,
+            RoundingMode::Up => true,
+            RoundingMode::Floor => sign < 0,
+            RoundingMode::Ceiling => sign > 0,
+            RoundingMode::HalfUp => half.is_ge(),
+            RoundingMode::HalfDown => half.is_gt(),
+            RoundingMode::HalfEven => half.is_gt() || (half.is_eq() && truncated % 2 != 0),
+        };
+        if away {
+            truncated.checked_add(sign)
+        } else {
+            Some(truncated)
+        }
+    }
+}
+
+// Returns `value * numerator / denominator` rounded with `mode`, `None` on overflow.
+fn mul_ratio(value: i128, numerator: i64, denominator: i64, mode: RoundingMode) -> Option<i128> {
+    let (numerator, denominator) = (i128::from(numerator), i128::from(denominator));
+    // `value = q * denominator + r`, so `value * numerator / denominator` is
+    // `q * numerator + r * numerator / denominator`, and `r * numerator` cannot overflow.
+    let (q, r) = (value / denominator, value % denominator);
+    let rn = r * numerator;
+    let truncated = q.checked_mul(numerator)?.checked_add(rn / denominator)?;
+    mode.round(truncated, rn % denominator, denominator)
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::CurrencyMismatch(a, b) => {
+                write!(f, "currency mismatch: {:?} and {:?}", a, b)
+            }
+            MoneyError::NanosOutOfRange(nanos) => write!(f, "nanos out of range: {}", nanos),
+            MoneyError::SignMismatch(units, nanos) => write!(
+                f,
+                "units and nanos have different signs: units = {}, nanos = {}",
+                units, nanos
+            ),
+            MoneyError::Overflow => f.write_str("money amount overflow"),
+            MoneyError::Precision(v) => {
+                write!(f, "more than nine fractional digits: {:?}", v)
+            }
+            MoneyError::Decimal(e) => e.fmt(f),
+            MoneyError::DivisionByZero => f.write_str("division by zero"),
+        }
+    }
+}
+
+impl error::Error for MoneyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MoneyError::Decimal(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecimalError> for MoneyError {
+    fn from(e: DecimalError) -> Self {
+        MoneyError::Decimal(e)
+    }
+}
+
+impl Money {
+    /// Creates an amount, checking the sign rules documented on [`Money::nanos`].
+    pub fn new(
+        currency_code: impl Into<String>,
+        units: i64,
+        nanos: i32,
+    ) -> Result<Self, MoneyError> {
+        let money = Money {
+            currency_code: currency_code.into(),
+            units,
+            nanos,
+        };
+        money.validate()?;
+        Ok(money)
+    }
+
+    /// Checks that `nanos` is in range and has the same sign as `units`.
+    pub fn validate(&self) -> Result<(), MoneyError> {
+        if !(-MAX_NANOS..=MAX_NANOS).contains(&self.nanos) {
+            return Err(MoneyError::NanosOutOfRange(self.nanos));
+        }
+        if (self.units > 0 && self.nanos < 0) || (self.units < 0 && self.nanos > 0) {
+            return Err(MoneyError::SignMismatch(self.units, self.nanos));
+        }
+        Ok(())
+    }
+
+    /// Returns the same amount with `nanos` carried into `units` so that the sign rules hold,
+    /// e.g. `units = 1, nanos = -250_000_000` becomes `units = 0, nanos = 750_000_000`.
+    pub fn normalize(&self) -> Result<Money, MoneyError> {
+        self.with_total_nanos(self.total_nanos())
+    }
+
+    /// Returns `self + rhs`, failing if an operand is invalid, the currencies differ or the result
+    /// overflows.
+    pub fn checked_add(&self, rhs: &Money) -> Result<Money, MoneyError> {
+        self.check_operand(rhs)?;
+        self.with_total_nanos(self.total_nanos() + rhs.total_nanos())
+    }
+
+    /// Returns `self - rhs`, failing if an operand is invalid, the currencies differ or the result
+    /// overflows.
+    pub fn checked_sub(&self, rhs: &Money) -> Result<Money, MoneyError> {
+        self.check_operand(rhs)?;
+        self.with_total_nanos(self.total_nanos() - rhs.total_nanos())
+    }
+
+    /// Returns `-self`, failing if `self` is invalid or the result overflows.
+    pub fn checked_neg(&self) -> Result<Money, MoneyError> {
+        self.validate()?;
+        self.with_total_nanos(-self.total_nanos())
+    }
+
+    /// Returns `self * factor`, failing if `self` is invalid or the result overflows.
+    pub fn checked_mul(&self, factor: i64) -> Result<Money, MoneyError> {
+        self.validate()?;
+        let total = self
+            .total_nanos()
+            .checked_mul(i128::from(factor))
+            .ok_or(MoneyError::Overflow)?;
+        self.with_total_nanos(total)
+    }
+
+    /// Returns `self * numerator / denominator`, rounded to nanos with `mode`, failing if `self`
+    /// is invalid, `denominator` is zero or the result overflows.
+    ///
+    /// ```
+    /// # use googapis::google::r#type::{Money, RoundingMode};
+    /// let third = Money::new("USD", 10, 0)?.checked_mul_ratio(1, 3, RoundingMode::HalfEven)?;
+    /// assert_eq!(third.to_decimal_string(), "3.333333333");
+    /// # Ok::<(), googapis::google::r#type::MoneyError>(())
+    /// ```
+    pub fn checked_mul_ratio(
+        &self,
+        numerator: i64,
+        denominator: i64,
+        mode: RoundingMode,
+    ) -> Result<Money, MoneyError> {
+        self.validate()?;
+        if denominator == 0 {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let total = mul_ratio(self.total_nanos(), numerator, denominator, mode)
+            .ok_or(MoneyError::Overflow)?;
+        self.with_total_nanos(total)
+    }
+
+    /// Returns `self * factor`, rounded to nanos with `mode`, failing if the result overflows.
+    ///
+    /// The factor must have at most 18 significant digits, and at most 18 digits after the
+    /// decimal point, e.g. a tax rate of `0.0825`.
+    pub fn checked_mul_decimal(
+        &self,
+        factor: &Decimal,
+        mode: RoundingMode,
+    ) -> Result<Money, MoneyError> {
+        const MAX_SCALE: i64 = 18;
+        let overflow = || DecimalError::Overflow(factor.value.clone());
+
+        let parts = Parts::parse(&factor.value)?;
+        let (digits, scale) = parts.digits(MAX_SCALE).ok_or_else(overflow)?;
+        let mut numerator = digits.parse::<i64>().map_err(|_| overflow())?;
+        let mut denominator = 1i64;
+        if scale >= 0 {
+            denominator = 10i64.pow(scale as u32);
+        } else {
+            numerator = 10i64
+                .checked_pow((-scale) as u32)
+                .and_then(|p| numerator.checked_mul(p))
+                .ok_or_else(overflow)?;
+        }
+        if parts.negative {
+            numerator = -numerator;
+        }
+        self.checked_mul_ratio(numerator, denominator, mode)
+    }
+
+    /// Returns the amount rounded with `mode` to `fraction_digits` digits after the decimal
+    /// point, e.g. to the minor unit of the currency. Amounts are kept as is for nine or more
+    /// digits.
+    pub fn round(&self, fraction_digits: u32, mode: RoundingMode) -> Result<Money, MoneyError> {
+        self.validate()?;
+        let unit = 10i64.pow(9u32.saturating_sub(fraction_digits));
+        let total = mul_ratio(self.total_nanos(), 1, unit, mode)
+            .and_then(|t| t.checked_mul(i128::from(unit)))
+            .ok_or(MoneyError::Overflow)?;
+        self.with_total_nanos(total)
+    }
+
+    /// Parses a decimal string such as `-1.75` as an amount of `currency_code`.
+    ///
+    /// The string follows the grammar of [`Decimal::value`] and must not have more than nine
+    /// digits after the decimal point.
+    pub fn from_decimal_str(
+        currency_code: impl Into<String>,
+        s: &str,
+    ) -> Result<Money, MoneyError> {
+        let parts = Parts::parse(s)?;
+
+        // `digits * 10^-scale` units are `digits * 10^(9 - scale)` nanos.
+        let (digits, scale) = parts.digits(i64::MAX).ok_or(MoneyError::Overflow)?;
+        let digits = if scale > 9 {
+            let kept = digits.len().saturating_sub((scale - 9) as usize);
+            if digits[kept..].bytes().any(|b| b != b'0') {
+                return Err(MoneyError::Precision(s.to_owned()));
+            }
+            &digits[..kept]
+        } else {
+            &digits[..]
+        };
+
+        let mut total = match digits {
+            "" | "0" => 0,
+            _ => 9i64
+                .checked_sub(scale.min(9))
+                .and_then(|shift| u32::try_from(shift).ok())
+                .and_then(|shift| 10i128.checked_pow(shift))
+                .zip(digits.parse::<i128>().ok())
+                .and_then(|(p, d)| d.checked_mul(p))
+                .ok_or(MoneyError::Overflow)?,
+        };
+        if parts.negative {
+            total = -total;
+        }
+
+        let money = Money {
+            currency_code: currency_code.into(),
+            units: 0,
+            nanos: 0,
+        };
+        money.with_total_nanos(total)
+    }
+
+    /// Formats the amount as a decimal string without trailing zeros, e.g. `-1.75`.
+    ///
+    /// The amount is expected to be valid, see [`Money::validate`].
+    pub fn to_decimal_string(&self) -> String {
+        let total = self.total_nanos();
+        let sign = if total < 0 { "-" } else { "" };
+        let units = (total / NANOS_PER_UNIT).abs();
+        let nanos = (total % NANOS_PER_UNIT).abs();
+        if nanos == 0 {
+            format!("{}{}", sign, units)
+        } else {
+            let nanos = format!("{:09}", nanos);
+            format!("{}{}.{}", sign, units, nanos.trim_end_matches('0'))
+        }
+    }
+
+    /// Returns the amount as a [`Decimal`].
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal {
+            value: self.to_decimal_string(),
+        }
+    }
+
+    /// Converts a [`Decimal`] to an amount of `currency_code`, see [`Money::from_decimal_str`].
+    pub fn from_decimal(
+        currency_code: impl Into<String>,
+        decimal: &Decimal,
+    ) -> Result<Money, MoneyError> {
+        Money::from_decimal_str(currency_code, &decimal.value)
+    }
+
+    fn check_operand(&self, rhs: &Money) -> Result<(), MoneyError> {
+        self.validate()?;
+        rhs.validate()?;
+        if self.currency_code != rhs.currency_code {
+            return Err(MoneyError::CurrencyMismatch(
+                self.currency_code.clone(),
+                rhs.currency_code.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn total_nanos(&self) -> i128 {
+        i128::from(self.units) * NANOS_PER_UNIT + i128::from(self.nanos)
+    }
+
+    // Division truncates toward zero, so `units` and `nanos` get the sign of `total`.
+    fn with_total_nanos(&self, total: i128) -> Result<Money, MoneyError> {
+        let units = i64::try_from(total / NANOS_PER_UNIT).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money {
+            currency_code: self.currency_code.clone(),
+            units,
+            nanos: (total % NANOS_PER_UNIT) as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn usd(units: i64, nanos: i32) -> Money {
+        Money {
+            currency_code: "USD".into(),
+            units,
+            nanos,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(usd(1, 750_000_000).validate(), Ok(()));
+        assert_eq!(usd(-1, -750_000_000).validate(), Ok(()));
+        assert_eq!(usd(0, -1).validate(), Ok(()));
+        assert_eq!(usd(0, 1).validate(), Ok(()));
+        assert_eq!(usd(-1, 1).validate(), Err(MoneyError::SignMismatch(-1, 1)));
+        assert_eq!(usd(1, -1).validate(), Err(MoneyError::SignMismatch(1, -1)));
+        assert_eq!(
+            usd(0, 1_000_000_000).validate(),
+            Err(MoneyError::NanosOutOfRange(1_000_000_000))
+        );
+        assert_eq!(
+            Money::new("USD", 1, -1),
+            Err(MoneyError::SignMismatch(1, -1))
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            usd(1, 500_000_000).checked_add(&usd(-2, -750_000_000)),
+            Ok(usd(-1, -250_000_000))
+        );
+        assert_eq!(
+            usd(1, 500_000_000).checked_sub(&usd(0, 600_000_000)),
+            Ok(usd(0, 900_000_000))
+        );
+        assert_eq!(usd(0, 5).checked_neg(), Ok(usd(0, -5)));
+        assert_eq!(
+            usd(1, 250_000_000).checked_mul(-3),
+            Ok(usd(-3, -750_000_000))
+        );
+        assert_eq!(usd(1, -250_000_000).normalize(), Ok(usd(0, 750_000_000)));
+        assert_eq!(
+            usd(0, 2_500_000_000u32 as i32)
+                .normalize()
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+
+        let eur = Money::new("EUR", 1, 0).unwrap();
+        assert_eq!(
+            usd(1, 0).checked_add(&eur),
+            Err(MoneyError::CurrencyMismatch("USD".into(), "EUR".into()))
+        );
+        assert_eq!(
+            usd(i64::MAX, 0).checked_add(&usd(1, 0)),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(usd(i64::MIN, 0).checked_neg(), Err(MoneyError::Overflow));
+        assert_eq!(usd(i64::MAX, 0).checked_mul(2), Err(MoneyError::Overflow));
+
+        let invalid = usd(1, 2_000_000_000);
+        assert_eq!(
+            invalid.checked_add(&usd(1, 0)),
+            Err(MoneyError::NanosOutOfRange(2_000_000_000))
+        );
+        assert_eq!(
+            usd(1, 0).checked_sub(&usd(1, -1)),
+            Err(MoneyError::SignMismatch(1, -1))
+        );
+        assert_eq!(
+            invalid.checked_mul(2),
+            Err(MoneyError::NanosOutOfRange(2_000_000_000))
+        );
+        assert_eq!(
+            invalid.checked_neg(),
+            Err(MoneyError::NanosOutOfRange(2_000_000_000))
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        use RoundingMode::*;
+
+        let modes = [Down, Up, Floor, Ceiling, HalfUp, HalfDown, HalfEven];
+        for (amount, digits, want) in &[
+            ("1.005", 2, ["1", "1.01", "1", "1.01", "1.01", "1", "1"]),
+            (
+                "1.015",
+                2,
+                ["1.01", "1.02", "1.01", "1.02", "1.02", "1.01", "1.02"],
+            ),
+            (
+                "-1.005",
+                2,
+                ["-1", "-1.01", "-1.01", "-1", "-1.01", "-1", "-1"],
+            ),
+            (
+                "1.006",
+                2,
+                ["1", "1.01", "1", "1.01", "1.01", "1.01", "1.01"],
+            ),
+            ("-2.5", 0, ["-2", "-3", "-3", "-2", "-3", "-2", "-2"]),
+            ("0.000000001", 9, ["0.000000001"; 7]),
+            ("7", 2, ["7"; 7]),
+        ] {
+            let m = Money::from_decimal_str("USD", amount).unwrap();
+            for (mode, want) in modes.iter().zip(want.iter()) {
+                let r = m.round(*digits, *mode).unwrap();
+                assert_eq!(r.to_decimal_string(), *want, "{} {:?}", amount, mode);
+            }
+        }
+        assert_eq!(
+            usd(i64::MAX, MAX_NANOS).round(0, Up),
+            Err(MoneyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_mul_ratio() {
+        use RoundingMode::*;
+
+        let m = usd(10, 0);
+        assert_eq!(m.checked_mul_ratio(2, 3, Down), Ok(usd(6, 666_666_666)));
+        assert_eq!(m.checked_mul_ratio(2, 3, HalfEven), Ok(usd(6, 666_666_667)));
+        assert_eq!(m.checked_mul_ratio(-2, 3, Floor), Ok(usd(-6, -666_666_667)));
+        assert_eq!(
+            m.checked_mul_ratio(2, -3, Ceiling),
+            Ok(usd(-6, -666_666_666))
+        );
+        assert_eq!(
+            m.checked_mul_ratio(1, 0, Down),
+            Err(MoneyError::DivisionByZero)
+        );
+        // The intermediate product does not fit in 128 bits, the result does.
+        assert_eq!(
+            usd(i64::MAX, 0).checked_mul_ratio(i64::MAX, i64::MAX, Down),
+            Ok(usd(i64::MAX, 0))
+        );
+        assert_eq!(
+            usd(i64::MAX, 0).checked_mul_ratio(3, 2, Down),
+            Err(MoneyError::Overflow)
+        );
+
+        let decimal = |value: &str| Decimal {
+            value: value.to_owned(),
+        };
+        assert_eq!(
+            usd(19, 990_000_000).checked_mul_decimal(&decimal("0.0825"), HalfUp),
+            Ok(usd(1, 649_175_000))
+        );
+        assert_eq!(
+            usd(0, 3).checked_mul_decimal(&decimal("-0.5"), HalfEven),
+            Ok(usd(0, -2))
+        );
+        assert_eq!(
+            usd(1, 0).checked_mul_decimal(&decimal("1.5e2"), Down),
+            Ok(usd(150, 0))
+        );
+        for value in &["1e-19", "1e19", "0.1234567890123456789"] {
+            assert_eq!(
+                usd(1, 0).checked_mul_decimal(&decimal(value), Down),
+                Err(MoneyError::Decimal(DecimalError::Overflow((*value).into())))
+            );
+        }
+    }
+
+    #[test]
+    fn test_decimal_string() {
+        for (s, money, formatted) in &[
+            ("1.75", usd(1, 750_000_000), "1.75"),
+            ("-1.75", usd(-1, -750_000_000), "-1.75"),
+            ("-0.000000001", usd(0, -1), "-0.000000001"),
+            ("+.5", usd(0, 500_000_000), "0.5"),
+            ("12", usd(12, 0), "12"),
+            ("1.5e2", usd(150, 0), "150"),
+            ("25e-3", usd(0, 25_000_000), "0.025"),
+            ("1.0000000000", usd(1, 0), "1"),
+            ("-0", usd(0, 0), "0"),
+            ("0e999", usd(0, 0), "0"),
+            ("10e-10", usd(0, 1), "0.000000001"),
+            (
+                "9223372036854775807.999999999",
+                usd(i64::MAX, MAX_NANOS),
+                "9223372036854775807.999999999",
+            ),
+        ] {
+            let m = Money::from_decimal_str("USD", s);
+            assert_eq!(m.as_ref(), Ok(money), "{}", s);
+            assert_eq!(m.unwrap().to_decimal_string(), *formatted, "{}", s);
+        }
+
+        assert_eq!(
+            Money::from_decimal_str("USD", "0.0000000001"),
+            Err(MoneyError::Precision("0.0000000001".into()))
+        );
+        assert_eq!(
+            Money::from_decimal_str("USD", "1.5e-9"),
+            Err(MoneyError::Precision("1.5e-9".into()))
+        );
+        assert_eq!(
+            Money::from_decimal_str("USD", "9223372036854775808"),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            Money::from_decimal_str("USD", "1e100"),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            Money::from_decimal_str("USD", "1,5"),
+            Err(MoneyError::Decimal(DecimalError::Invalid("1,5".into())))
+        );
+        assert_eq!(
+            Money::from_decimal("USD", &usd(-3, -50_000_000).to_decimal()),
+            Ok(usd(-3, -50_000_000))
+        );
+    }
+
+    fn money() -> impl Strategy<Value = Money> {
+        (any::<i64>(), 0..=MAX_NANOS).prop_map(|(units, nanos)| {
+            let nanos = if units < 0 { -nanos } else { nanos };
+            usd(units, nanos)
+        })
+    }
+
+    fn small_money() -> impl Strategy<Value = Money> {
+        (-1_000_000_000_000i64..1_000_000_000_000, 0..=MAX_NANOS).prop_map(|(units, nanos)| {
+            let nanos = if units < 0 { -nanos } else { nanos };
+            usd(units, nanos)
+        })
+    }
+
+    fn rounding_mode() -> impl Strategy<Value = RoundingMode> {
+        use RoundingMode::*;
+        prop::sample::select(vec![Down, Up, Floor, Ceiling, HalfUp, HalfDown, HalfEven])
+    }
+
+    fn nonzero(max: i64) -> impl Strategy<Value = i64> {
+        (-max..=max).prop_filter("nonzero", |n| *n != 0)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_mul_ratio_bounds(
+            m in small_money(),
+            numerator in -1_000_000i64..=1_000_000,
+            denominator in nonzero(1_000_000),
+            mode in rounding_mode(),
+        ) {
+            // The exact result is `exact / denominator` nanos.
+            let exact = m.total_nanos() * i128::from(numerator);
+            let r = m.checked_mul_ratio(numerator, denominator, mode).unwrap();
+            prop_assert_eq!(r.validate(), Ok(()));
+            let d = i128::from(denominator);
+            // `r - exact / denominator`, scaled by `|denominator|` to stay in integers.
+            let error = (r.total_nanos() * d - exact) * d.signum();
+            let d = d.abs();
+            prop_assert!(-d < error && error < d);
+            match mode {
+                RoundingMode::Floor => prop_assert!(error <= 0),
+                RoundingMode::Ceiling => prop_assert!(error >= 0),
+                RoundingMode::Down => prop_assert!(r.total_nanos().abs() * d <= exact.abs()),
+                RoundingMode::Up => prop_assert!(r.total_nanos().abs() * d >= exact.abs()),
+                _ => prop_assert!(2 * error.abs() <= d),
+            }
+        }
+
+        #[test]
+        fn prop_mul_ratio_identity(m in money(), n in nonzero(i64::MAX), mode in rounding_mode()) {
+            prop_assert_eq!(m.checked_mul_ratio(n, n, mode), Ok(m.clone()));
+            prop_assert_eq!(m.checked_mul_ratio(1, 1, mode), Ok(m));
+        }
+
+        #[test]
+        fn prop_mul_decimal(
+            m in small_money(),
+            digits in -1_000_000i64..=1_000_000,
+            scale in 0u32..=9,
+            mode in rounding_mode(),
+        ) {
+            let value = if scale == 0 {
+                digits.to_string()
+            } else {
+                format!("{}e-{}", digits, scale)
+            };
+            let factor = Decimal { value };
+            prop_assert_eq!(
+                m.checked_mul_decimal(&factor, mode),
+                m.checked_mul_ratio(digits, 10i64.pow(scale), mode)
+            );
+        }
+
+        #[test]
+        fn prop_round(m in small_money(), digits in 0u32..=9, mode in rounding_mode()) {
+            let r = m.round(digits, mode).unwrap();
+            let unit = 10i128.pow(9 - digits);
+            prop_assert_eq!(r.validate(), Ok(()));
+            prop_assert_eq!(r.total_nanos() % unit, 0);
+            prop_assert!((r.total_nanos() - m.total_nanos()).abs() < unit);
+            prop_assert_eq!(r.round(digits, mode), Ok(r.clone()));
+        }
+
+        #[test]
+        fn prop_results_are_valid(a in money(), b in money(), factor in any::<i64>()) {
+            for r in [a.checked_add(&b), a.checked_sub(&b), a.checked_neg(), a.checked_mul(factor)] {
+                match r {
+                    Ok(m) => prop_assert_eq!(m.validate(), Ok(())),
+                    Err(e) => prop_assert_eq!(e, MoneyError::Overflow),
+                }
+            }
+        }
+
+        #[test]
+        fn prop_normalize(units in any::<i64>(), nanos in any::<i32>()) {
+            let m = usd(units, nanos);
+            match m.normalize() {
+                Ok(n) => {
+                    prop_assert_eq!(n.validate(), Ok(()));
+                    prop_assert_eq!(n.total_nanos(), m.total_nanos());
+                }
+                Err(e) => prop_assert_eq!(e, MoneyError::Overflow),
+            }
+        }
+
+        #[test]
+        fn prop_add_sub_neg(a in small_money(), b in small_money()) {
+            let sum = a.checked_add(&b).unwrap();
+            prop_assert_eq!(sum.checked_sub(&b).unwrap(), a.clone());
+            prop_assert_eq!(a.checked_sub(&b).unwrap(), a.checked_add(&b.checked_neg().unwrap()).unwrap());
+            prop_assert_eq!(a.checked_neg().unwrap().checked_neg().unwrap(), a);
+        }
+
+        #[test]
+        fn prop_decimal_string_roundtrip(m in money()) {
+            let s = m.to_decimal_string();
+            prop_assert_eq!(Money::from_decimal_str("USD", &s), Ok(m.clone()));
+            prop_assert_eq!(m.to_decimal().validate(), Ok(()));
+        }
+    }
+}
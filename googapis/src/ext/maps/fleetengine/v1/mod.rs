@@ -0,0 +1,32 @@
+use crate::google::r#type::{LatLng, PolylineError, POLYLINE_PRECISION};
+
+impl Vehicle {
+    /// The points of the encoded `current_route_segment`.
+    pub fn current_route_segment_points(&self) -> Result<Vec<LatLng>, PolylineError> {
+        LatLng::decode_polyline(&self.current_route_segment, POLYLINE_PRECISION)
+    }
+
+    /// Sets `current_route_segment` to the encoded points.
+    pub fn set_current_route_segment_points(
+        &mut self,
+        points: &[LatLng],
+    ) -> Result<(), PolylineError> {
+        self.current_route_segment = LatLng::encode_polyline(points, POLYLINE_PRECISION)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_route_segment() {
+        let mut vehicle = Vehicle::default();
+        assert_eq!(vehicle.current_route_segment_points().unwrap(), vec![]);
+        let points = vec![LatLng::new(38.5, -120.2), LatLng::new(40.7, -120.95)];
+        vehicle.set_current_route_segment_points(&points).unwrap();
+        assert_eq!(vehicle.current_route_segment, "_p~iF~ps|U_ulLnnqC");
+        assert_eq!(vehicle.current_route_segment_points().unwrap(), points);
+    }
+}
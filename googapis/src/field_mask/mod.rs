@@ -0,0 +1,593 @@
+//! Field masks, as described in [AIP-161](https://google.aip.dev/161).
+//!
+//! With the `reflect` feature, every generated message implements
+//! [`Reflect`](crate::reflect::Reflect) and has accessors on [`FieldPath`] to build masks
+//! without spelling paths as strings. [`FieldMaskExt`] validates masks against the message,
+//! computes the mask of the fields that differ between two messages, and applies a mask to copy
+//! fields from one message to another as in an [AIP-134](https://google.aip.dev/134) update.
+//!
+//! # Example
+//! ```ignore
+//! use googapis::{
+//!     field_mask::{FieldMaskBuilder, FieldMaskExt},
+//!     google::cloud::secretmanager::v1::Secret,
+//! };
+//!
+//! let mask = FieldMaskBuilder::<Secret>::new()
+//!     .field(|s| s.labels().key("env"))
+//!     .field(|s| s.replication().automatic())
+//!     .build();
+//! mask.apply(&mut secret, &update)?;
+//! ```
+
+mod path;
+
+use std::{error, fmt, marker::PhantomData};
+
+use prost::DecodeError;
+
+use crate::reflect::{wire, FieldKind, Reflect};
+
+#[doc(hidden)]
+pub use self::path::child;
+pub use self::path::{path, FieldPath, MapField};
+pub use prost_types::FieldMask;
+
+/// An error returned when a field mask is invalid for a message or cannot be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldMaskError {
+    /// The path is not a list of field names separated by `.`, e.g. it has an unterminated
+    /// backquote.
+    Syntax(String),
+    /// The path names a field that the message does not have.
+    UnknownField { path: String, message: &'static str },
+    /// The path continues past a field that is neither a singular message nor a map, or past
+    /// the key of a map.
+    NotTraversable(String),
+    /// A message could not be decoded.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FieldMaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldMaskError::Syntax(path) => write!(f, "invalid field path: {:?}", path),
+            FieldMaskError::UnknownField { path, message } => {
+                write!(f, "{} has no field {:?}", message, path)
+            }
+            FieldMaskError::NotTraversable(path) => {
+                write!(
+                    f,
+                    "field path goes past a scalar, repeated field or map key: {:?}",
+                    path
+                )
+            }
+            FieldMaskError::Decode(e) => write!(f, "failed to decode message: {}", e),
+        }
+    }
+}
+
+impl error::Error for FieldMaskError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FieldMaskError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for FieldMaskError {
+    fn from(e: DecodeError) -> Self {
+        FieldMaskError::Decode(e)
+    }
+}
+
+// Checks the path against the descriptor of `M` and returns its segments.
+fn validate_path<M: Reflect>(path: &str) -> Result<Vec<String>, FieldMaskError> {
+    if path == "*" {
+        return Ok(vec![path.to_owned()]);
+    }
+    let segments = path::segments(path)?;
+    let mut d = M::descriptor();
+    let mut i = 0;
+    while i < segments.len() {
+        let field = d
+            .field(&segments[i])
+            .ok_or_else(|| FieldMaskError::UnknownField {
+                path: path.to_owned(),
+                message: d.full_name,
+            })?;
+        let last = i + 1 == segments.len();
+        match field.kind {
+            _ if last => {}
+            FieldKind::Message(m) if !field.repeated => d = m(),
+            // The next segment is a key.
+            FieldKind::Map { .. } if i + 2 == segments.len() => i += 1,
+            _ => return Err(FieldMaskError::NotTraversable(path.to_owned())),
+        }
+        i += 1;
+    }
+    Ok(segments)
+}
+
+// Whether `path` selects a part of `prefix`.
+fn is_covered(path: &str, prefix: &str) -> bool {
+    prefix == "*"
+        || path == prefix
+        || (path.starts_with(prefix) && path[prefix.len()..].starts_with('.'))
+}
+
+/// Builds a [`FieldMask`] for the message `M`.
+pub struct FieldMaskBuilder<M> {
+    paths: Vec<String>,
+    _marker: PhantomData<fn(M)>,
+}
+
+impl<M> Default for FieldMaskBuilder<M> {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M> FieldMaskBuilder<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the path built by `f` from the root of `M`, e.g. `|m| m.author().name()`.
+    pub fn field<T>(mut self, f: impl FnOnce(FieldPath<M, M>) -> FieldPath<M, T>) -> Self {
+        self.paths.push(f(path()).into());
+        self
+    }
+
+    /// Adds `*`, which selects every field.
+    pub fn all(mut self) -> Self {
+        self.paths.push("*".to_owned());
+        self
+    }
+
+    /// Builds the normalized mask.
+    pub fn build(self) -> FieldMask {
+        FieldMask { paths: self.paths }.normalize()
+    }
+}
+
+impl<M: Reflect> FieldMaskBuilder<M> {
+    /// Adds a path given as a string, checking it against `M`.
+    pub fn try_path(mut self, path: &str) -> Result<Self, FieldMaskError> {
+        validate_path::<M>(path)?;
+        self.paths.push(path.to_owned());
+        Ok(self)
+    }
+}
+
+/// Extension methods for [`FieldMask`].
+pub trait FieldMaskExt: Sized {
+    /// Builds a mask from paths, checking them against `M`.
+    fn from_paths<M: Reflect>(
+        paths: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self, FieldMaskError>;
+
+    /// Checks that every path names a field of `M`, or a key of one of its maps.
+    fn validate<M: Reflect>(&self) -> Result<(), FieldMaskError>;
+
+    /// Sorts the paths and removes the duplicates and the paths covered by another one, e.g.
+    /// `author.name` by `author`.
+    fn normalize(&self) -> Self;
+
+    /// Returns the normalized mask of the fields that differ between `old` and `new`.
+    ///
+    /// Singular messages set in both are compared field by field, other fields as a whole.
+    fn diff<M: Reflect>(old: &M, new: &M) -> Result<Self, FieldMaskError>;
+
+    /// Copies the fields selected by the mask from `source` to `target`.
+    ///
+    /// As in AIP-134, an empty mask selects the fields set in `source` and `*` replaces the whole
+    /// message. Repeated fields and maps are replaced, and a path ending with a map key replaces
+    /// or removes the entry. Setting a member of a oneof clears the others.
+    fn apply<M: Reflect>(&self, target: &mut M, source: &M) -> Result<(), FieldMaskError>;
+}
+
+impl FieldMaskExt for FieldMask {
+    fn from_paths<M: Reflect>(
+        paths: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self, FieldMaskError> {
+        let mask = FieldMask {
+            paths: paths.into_iter().map(Into::into).collect(),
+        };
+        mask.validate::<M>()?;
+        Ok(mask)
+    }
+
+    fn validate<M: Reflect>(&self) -> Result<(), FieldMaskError> {
+        for path in &self.paths {
+            validate_path::<M>(path)?;
+        }
+        Ok(())
+    }
+
+    fn normalize(&self) -> Self {
+        let mut paths = self.paths.clone();
+        paths.sort();
+        paths.dedup();
+        if paths.iter().any(|p| p == "*") {
+            paths = vec!["*".to_owned()];
+        }
+        // A path sorts after the paths it is covered by.
+        let mut normalized = Vec::<String>::new();
+        for path in paths {
+            if !normalized.iter().any(|prefix| is_covered(&path, prefix)) {
+                normalized.push(path);
+            }
+        }
+        FieldMask { paths: normalized }
+    }
+
+    fn diff<M: Reflect>(old: &M, new: &M) -> Result<Self, FieldMaskError> {
+        let old = wire::Fields::decode(&old.encode_to_vec())?;
+        let new = wire::Fields::decode(&new.encode_to_vec())?;
+        let mut paths = Vec::new();
+        wire::diff(&old, &new, M::descriptor(), "", &mut paths)?;
+        Ok(FieldMask { paths }.normalize())
+    }
+
+    fn apply<M: Reflect>(&self, target: &mut M, source: &M) -> Result<(), FieldMaskError> {
+        let mask = self.normalize();
+        let paths = mask
+            .paths
+            .iter()
+            .map(|p| validate_path::<M>(p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut fields = wire::Fields::decode(&target.encode_to_vec())?;
+        let source_fields = wire::Fields::decode(&source.encode_to_vec())?;
+        let d = M::descriptor();
+        if paths.is_empty() {
+            for number in source_fields.numbers() {
+                if let Some(field) = d.field_by_number(number) {
+                    wire::apply(&mut fields, &source_fields, d, &[field.name.to_owned()])?;
+                }
+            }
+        }
+        for segments in &paths {
+            if segments[0] == "*" {
+                fields = source_fields.clone();
+            } else {
+                wire::apply(&mut fields, &source_fields, d, segments)?;
+            }
+        }
+        *target = M::decode(fields.encode().as_slice())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::reflect::{FieldDescriptor, MessageDescriptor};
+
+    // Mirrors the code generated by `cargo xtask gen`.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct Book {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(message, optional, tag = "2")]
+        author: Option<Author>,
+        #[prost(string, repeated, tag = "3")]
+        tags: Vec<String>,
+        #[prost(map = "string, string", tag = "4")]
+        labels: HashMap<String, String>,
+        #[prost(map = "int32, message", tag = "5")]
+        reviewers: HashMap<i32, Author>,
+        #[prost(oneof = "book::Format", tags = "6, 7")]
+        format: Option<book::Format>,
+    }
+
+    mod book {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub(super) enum Format {
+            #[prost(string, tag = "6")]
+            Isbn(String),
+            #[prost(message, tag = "7")]
+            Scan(super::Author),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct Author {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(string, tag = "2")]
+        email: String,
+    }
+
+    static BOOK: MessageDescriptor = MessageDescriptor {
+        full_name: "test.Book",
+        fields: &[
+            FieldDescriptor {
+                name: "name",
+                number: 1,
+                kind: FieldKind::Scalar,
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "author",
+                number: 2,
+                kind: FieldKind::Message(<Author as Reflect>::descriptor),
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "tags",
+                number: 3,
+                kind: FieldKind::Scalar,
+                repeated: true,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "labels",
+                number: 4,
+                kind: FieldKind::Map { value: None },
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "reviewers",
+                number: 5,
+                kind: FieldKind::Map {
+                    value: Some(<Author as Reflect>::descriptor),
+                },
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "isbn",
+                number: 6,
+                kind: FieldKind::Scalar,
+                repeated: false,
+                oneof: Some("format"),
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "scan",
+                number: 7,
+                kind: FieldKind::Message(<Author as Reflect>::descriptor),
+                repeated: false,
+                oneof: Some("format"),
+                behaviors: &[],
+            },
+        ],
+    };
+
+    static AUTHOR: MessageDescriptor = MessageDescriptor {
+        full_name: "test.Author",
+        fields: &[
+            FieldDescriptor {
+                name: "name",
+                number: 1,
+                kind: FieldKind::Scalar,
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+            FieldDescriptor {
+                name: "email",
+                number: 2,
+                kind: FieldKind::Scalar,
+                repeated: false,
+                oneof: None,
+                behaviors: &[],
+            },
+        ],
+    };
+
+    impl Reflect for Book {
+        fn descriptor() -> &'static MessageDescriptor {
+            &BOOK
+        }
+    }
+
+    impl Reflect for Author {
+        fn descriptor() -> &'static MessageDescriptor {
+            &AUTHOR
+        }
+    }
+
+    impl<R> FieldPath<R, Book> {
+        fn name(self) -> FieldPath<R, ()> {
+            child(self, "name")
+        }
+        fn author(self) -> FieldPath<R, Author> {
+            child(self, "author")
+        }
+        fn labels(self) -> FieldPath<R, MapField> {
+            child(self, "labels")
+        }
+    }
+
+    impl<R> FieldPath<R, Author> {
+        fn email(self) -> FieldPath<R, ()> {
+            child(self, "email")
+        }
+    }
+
+    fn author(name: &str, email: &str) -> Author {
+        Author {
+            name: name.to_owned(),
+            email: email.to_owned(),
+        }
+    }
+
+    fn book() -> Book {
+        Book {
+            name: "dune".to_owned(),
+            author: Some(author("frank", "frank@example.com")),
+            tags: vec!["sf".to_owned()],
+            labels: vec![("a", "1"), ("b.c", "2")]
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+            reviewers: vec![(1, author("x", "")), (2, author("y", ""))]
+                .into_iter()
+                .collect(),
+            format: Some(book::Format::Isbn("0441013597".to_owned())),
+        }
+    }
+
+    fn mask(paths: &[&str]) -> FieldMask {
+        FieldMask {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let m = FieldMaskBuilder::<Book>::new()
+            .field(|b| b.labels().key("b.c"))
+            .field(|b| b.author().email())
+            .field(|b| b.name())
+            .field(|b| b.author())
+            .build();
+        assert_eq!(m, mask(&["author", "labels.`b.c`", "name"]));
+        assert_eq!(m.validate::<Book>(), Ok(()));
+
+        let m = FieldMaskBuilder::<Book>::new()
+            .try_path("reviewers.1")
+            .unwrap()
+            .all()
+            .build();
+        assert_eq!(m, mask(&["*"]));
+        assert!(FieldMaskBuilder::<Book>::new().try_path("title").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(FieldMask::from_paths::<Book>(vec!["author.name", "labels.`x`", "*"]).is_ok());
+        assert_eq!(
+            mask(&["author.title"]).validate::<Book>(),
+            Err(FieldMaskError::UnknownField {
+                path: "author.title".to_owned(),
+                message: "test.Author",
+            })
+        );
+        for p in &["name.x", "tags.x", "labels.a.b", "author.name.x"] {
+            assert_eq!(
+                mask(&[p]).validate::<Book>(),
+                Err(FieldMaskError::NotTraversable(p.to_string()))
+            );
+        }
+        assert_eq!(
+            mask(&["author..name"]).validate::<Book>(),
+            Err(FieldMaskError::Syntax("author..name".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            mask(&["b", "a.b", "a", "ab", "b"]).normalize(),
+            mask(&["a", "ab", "b"])
+        );
+        assert_eq!(mask(&["a", "*"]).normalize(), mask(&["*"]));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = book();
+        assert_eq!(FieldMask::diff(&old, &old.clone()), Ok(mask(&[])));
+
+        let mut new = old.clone();
+        new.author.as_mut().unwrap().email.clear();
+        new.labels.insert("d".to_owned(), "4".to_owned());
+        new.reviewers.get_mut(&2).unwrap().name = "z".to_owned();
+        new.format = Some(book::Format::Scan(Author::default()));
+        assert_eq!(
+            FieldMask::diff(&old, &new),
+            Ok(mask(&[
+                "author.email",
+                "isbn",
+                "labels",
+                "reviewers",
+                "scan"
+            ]))
+        );
+
+        new.author = None;
+        assert_eq!(
+            FieldMask::diff(&old, &new).unwrap().paths[0],
+            "author".to_owned()
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let source = Book {
+            name: "emma".to_owned(),
+            author: Some(author("jane", "")),
+            labels: vec![("b.c".to_owned(), "3".to_owned())]
+                .into_iter()
+                .collect(),
+            reviewers: vec![(3, author("w", ""))].into_iter().collect(),
+            format: Some(book::Format::Scan(author("s", ""))),
+            ..Default::default()
+        };
+
+        let mut target = book();
+        mask(&[
+            "author.email",
+            "tags",
+            "labels.`b.c`",
+            "labels.a",
+            "reviewers.2",
+            "reviewers.3",
+        ])
+        .apply(&mut target, &source)
+        .unwrap();
+        let mut expected = book();
+        expected.author = Some(author("frank", ""));
+        expected.tags.clear();
+        expected.labels = source.labels.clone();
+        expected.reviewers.remove(&2);
+        expected.reviewers.insert(3, author("w", ""));
+        assert_eq!(target, expected);
+
+        let mut target = book();
+        mask(&["scan.name"]).apply(&mut target, &source).unwrap();
+        expected = book();
+        expected.format = Some(book::Format::Scan(author("s", "")));
+        assert_eq!(target, expected);
+
+        let mut target = Book::default();
+        mask(&["author.email"])
+            .apply(&mut target, &Book::default())
+            .unwrap();
+        assert_eq!(target, Book::default());
+
+        let mut target = book();
+        mask(&["*"]).apply(&mut target, &source).unwrap();
+        assert_eq!(target, source);
+
+        let mut target = book();
+        mask(&[]).apply(&mut target, &source).unwrap();
+        expected = book();
+        expected.name = "emma".to_owned();
+        expected.author = source.author.clone();
+        expected.labels = source.labels.clone();
+        expected.reviewers = source.reviewers.clone();
+        expected.format = source.format.clone();
+        assert_eq!(target, expected);
+
+        assert!(mask(&["title"]).apply(&mut target, &source).is_err());
+    }
+}
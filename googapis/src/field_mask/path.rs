@@ -0,0 +1,158 @@
+use std::{fmt, marker::PhantomData};
+
+use super::FieldMaskError;
+
+/// A path from a message `R` to one of its fields, of type `T`.
+///
+/// Paths are built from [`path`] with the accessors generated for every message field, e.g.
+/// `path::<Secret>().replication().automatic()`. Singular message fields return a path to the
+/// message, map fields a path to a [`MapField`], and other fields a path to `()`.
+///
+/// `FieldPath` has no inherent methods besides the generated accessors so that they cannot
+/// collide with field names.
+pub struct FieldPath<R, T> {
+    path: String,
+    _marker: PhantomData<fn(R) -> T>,
+}
+
+/// The type of the map fields in a [`FieldPath`].
+pub struct MapField;
+
+/// Returns the empty path to the root of a message.
+pub fn path<M>() -> FieldPath<M, M> {
+    FieldPath {
+        path: String::new(),
+        _marker: PhantomData,
+    }
+}
+
+// Used by the generated accessors.
+#[doc(hidden)]
+pub fn child<R, T, U>(parent: FieldPath<R, T>, name: &str) -> FieldPath<R, U> {
+    let mut path = parent.path;
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(name);
+    FieldPath {
+        path,
+        _marker: PhantomData,
+    }
+}
+
+impl<R> FieldPath<R, MapField> {
+    /// Returns the path to the value of `key`, quoted with backquotes if it is not an
+    /// identifier, as in AIP-161.
+    pub fn key(self, key: &str) -> FieldPath<R, ()> {
+        let is_ident = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_ident {
+            child(self, key)
+        } else {
+            child(self, &format!("`{}`", key.replace('`', "``")))
+        }
+    }
+}
+
+impl<R, T> fmt::Display for FieldPath<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+impl<R, T> fmt::Debug for FieldPath<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FieldPath").field(&self.path).finish()
+    }
+}
+
+impl<R, T> AsRef<str> for FieldPath<R, T> {
+    fn as_ref(&self) -> &str {
+        &self.path
+    }
+}
+
+impl<R, T> From<FieldPath<R, T>> for String {
+    fn from(p: FieldPath<R, T>) -> Self {
+        p.path
+    }
+}
+
+/// Splits a path into its segments, unquoting the backquoted ones.
+pub(super) fn segments(path: &str) -> Result<Vec<String>, FieldMaskError> {
+    let syntax_error = || FieldMaskError::Syntax(path.to_owned());
+
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    loop {
+        let mut segment = String::new();
+        if chars.peek() == Some(&'`') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('`') if chars.peek() == Some(&'`') => {
+                        chars.next();
+                        segment.push('`');
+                    }
+                    Some('`') => break,
+                    Some(c) => segment.push(c),
+                    None => return Err(syntax_error()),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == '.' {
+                    break;
+                }
+                segment.push(c);
+                chars.next();
+            }
+            if segment.is_empty() || segment.contains('`') {
+                return Err(syntax_error());
+            }
+        }
+        segments.push(segment);
+
+        match chars.next() {
+            None => return Ok(segments),
+            Some('.') if chars.peek().is_some() => {}
+            Some(_) => return Err(syntax_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        let p = child::<(), (), MapField>(child(path::<()>(), "a"), "labels");
+        assert_eq!(p.to_string(), "a.labels");
+        let p = child::<(), (), MapField>(path::<()>(), "labels");
+        assert_eq!(String::from(p.key("env_1")), "labels.env_1");
+        let p = child::<(), (), MapField>(path::<()>(), "labels");
+        assert_eq!(p.key("a.b`c").as_ref(), "labels.`a.b``c`");
+        let p = child::<(), (), MapField>(path::<()>(), "labels");
+        assert_eq!(p.key("1x").as_ref(), "labels.`1x`");
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(segments("a.b_c").unwrap(), vec!["a", "b_c"]);
+        assert_eq!(segments("*").unwrap(), vec!["*"]);
+        assert_eq!(
+            segments("labels.`a.b``c`.d").unwrap(),
+            vec!["labels", "a.b`c", "d"]
+        );
+        assert_eq!(segments("labels.``").unwrap(), vec!["labels", ""]);
+        for p in &["", ".", "a.", ".a", "a..b", "a.`b", "a.`b`c", "a`b"] {
+            assert_eq!(
+                segments(p),
+                Err(FieldMaskError::Syntax(p.to_string())),
+                "{}",
+                p
+            );
+        }
+    }
+}
@@ -0,0 +1,510 @@
+//! Filters and orderings of list and search methods, as described in
+//! [AIP-160](https://google.aip.dev/160) and [AIP-132](https://google.aip.dev/132).
+//!
+//! [`Filter`] is the syntax tree of a filter. It is built with [`field`] and [`function`], or
+//! parsed from a string, and formatted with `to_string()` into the `filter` field of a request.
+//! Strings are quoted and escaped when formatted, so values never need to be spliced into a
+//! filter by hand. Note that `OR` binds tighter than `AND` in this grammar.
+//!
+//! # Example
+//! ```
+//! use googapis::filter::{field, function, Filter, OrderBy};
+//!
+//! let filter = field("state").eq("ACTIVE")
+//!     .and(field("labels.env").has("prod").or(!field("name").eq("a \"b\"")))
+//!     .and(function("regex", vec![field("name").into(), "^a".into()]).into());
+//! assert_eq!(
+//!     filter.to_string(),
+//!     r#"state = "ACTIVE" AND labels.env:"prod" OR NOT name = "a \"b\"" AND regex(name, "^a")"#,
+//! );
+//! assert_eq!(filter.to_string().parse::<Filter>(), Ok(filter));
+//!
+//! let order_by = OrderBy::new().desc("create_time").asc("name");
+//! assert_eq!(order_by.to_string(), "create_time desc, name");
+//! ```
+
+mod order_by;
+mod parse;
+
+use std::{convert::TryFrom, error, fmt, ops, str::FromStr};
+
+use prost_types::{Duration, Timestamp};
+
+use crate::wkt::{DurationError, DurationExt, TimestampError, TimestampExt};
+
+pub use self::order_by::{Direction, OrderBy, OrderByError};
+
+/// An error returned when a filter cannot be parsed. Positions are byte offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    /// A string literal is not terminated.
+    UnterminatedString(usize),
+    /// A string literal has an unknown escape sequence.
+    InvalidEscape(usize),
+    /// A token is not expected here, e.g. an unbalanced parenthesis.
+    Unexpected { position: usize, found: String },
+    /// The filter ends where an expression was expected.
+    UnexpectedEnd,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::UnterminatedString(p) => write!(f, "unterminated string at {}", p),
+            FilterError::InvalidEscape(p) => write!(f, "invalid escape sequence at {}", p),
+            FilterError::Unexpected { position, found } => {
+                write!(f, "unexpected {:?} at {}", found, position)
+            }
+            FilterError::UnexpectedEnd => f.write_str("unexpected end of filter"),
+        }
+    }
+}
+
+impl error::Error for FilterError {}
+
+/// A filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Filters that must all match, joined by `AND` or by whitespace.
+    And(Vec<Filter>),
+    /// Filters of which one must match, joined by `OR`.
+    Or(Vec<Filter>),
+    /// A negated filter, prefixed by `NOT` or `-`.
+    Not(Box<Filter>),
+    /// A comparison, e.g. `create_time > "2021-01-01T00:00:00Z"` or `labels:env`.
+    Restriction {
+        comparable: Comparable,
+        comparator: Comparator,
+        arg: Arg,
+    },
+    /// A value matched against any field, e.g. `prod` or `"web server"`.
+    Global(Comparable),
+}
+
+/// The operator of a [`Filter::Restriction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `:`, which tests that a collection contains a value or that a field is set.
+    Has,
+}
+
+/// The left-hand side of a restriction, or an argument of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparable {
+    /// A value optionally followed by fields, e.g. `labels.env`, `42` or `"x"`.
+    Member(Vec<Literal>),
+    /// A function call, e.g. `regex(name, "^a")`. Names may be qualified, e.g. `math.mem`.
+    Function { name: Vec<String>, args: Vec<Arg> },
+}
+
+/// The right-hand side of a restriction, or an argument of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Comparable(Comparable),
+    /// A parenthesized filter, e.g. `(a OR b)` in `labels:(a OR b)`.
+    Composite(Box<Filter>),
+}
+
+/// A value or a field name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    /// An unquoted word, e.g. a field name, a number, `true`, `20s` or `*`.
+    Text(String),
+    /// A quoted string, formatted with double quotes.
+    String(String),
+}
+
+/// Returns the member at `path`, with fields separated by `.`. Fields that are not words are
+/// quoted, e.g. `labels."my key"`.
+pub fn field(path: impl AsRef<str>) -> Comparable {
+    Comparable::Member(
+        path.as_ref()
+            .split('.')
+            .map(|s| {
+                if parse::is_text(s) {
+                    Literal::Text(s.to_owned())
+                } else {
+                    Literal::String(s.to_owned())
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Returns a call of the function `name`, which may be qualified, e.g. `math.mem`.
+pub fn function(name: &str, args: impl IntoIterator<Item = Arg>) -> Comparable {
+    Comparable::Function {
+        name: name.split('.').map(ToOwned::to_owned).collect(),
+        args: args.into_iter().collect(),
+    }
+}
+
+impl Comparable {
+    fn restriction(self, comparator: Comparator, arg: impl Into<Arg>) -> Filter {
+        Filter::Restriction {
+            comparable: self,
+            comparator,
+            arg: arg.into(),
+        }
+    }
+
+    pub fn eq(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Eq, arg)
+    }
+
+    pub fn ne(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Ne, arg)
+    }
+
+    pub fn lt(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Lt, arg)
+    }
+
+    pub fn le(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Le, arg)
+    }
+
+    pub fn gt(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Gt, arg)
+    }
+
+    pub fn ge(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Ge, arg)
+    }
+
+    /// Returns `self:arg`.
+    pub fn has(self, arg: impl Into<Arg>) -> Filter {
+        self.restriction(Comparator::Has, arg)
+    }
+}
+
+impl Filter {
+    /// Parses a filter. Empty filters, which match everything, are not expressions and cannot
+    /// be parsed.
+    pub fn parse(s: &str) -> Result<Self, FilterError> {
+        parse::parse(s)
+    }
+
+    /// Returns a filter matching both `self` and `other`.
+    pub fn and(self, other: Filter) -> Filter {
+        match (self, other) {
+            (Filter::And(mut a), Filter::And(b)) => {
+                a.extend(b);
+                Filter::And(a)
+            }
+            (Filter::And(mut a), b) => {
+                a.push(b);
+                Filter::And(a)
+            }
+            (a, Filter::And(mut b)) => {
+                b.insert(0, a);
+                Filter::And(b)
+            }
+            (a, b) => Filter::And(vec![a, b]),
+        }
+    }
+
+    /// Returns a filter matching `self` or `other`.
+    pub fn or(self, other: Filter) -> Filter {
+        match (self, other) {
+            (Filter::Or(mut a), Filter::Or(b)) => {
+                a.extend(b);
+                Filter::Or(a)
+            }
+            (Filter::Or(mut a), b) => {
+                a.push(b);
+                Filter::Or(a)
+            }
+            (a, Filter::Or(mut b)) => {
+                b.insert(0, a);
+                Filter::Or(b)
+            }
+            (a, b) => Filter::Or(vec![a, b]),
+        }
+    }
+
+    // Whether the filter is a `simple` of the grammar, which needs no parentheses.
+    fn is_simple(&self) -> bool {
+        matches!(self, Filter::Restriction { .. } | Filter::Global(_))
+    }
+}
+
+impl ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+impl From<Comparable> for Filter {
+    fn from(c: Comparable) -> Self {
+        Filter::Global(c)
+    }
+}
+
+impl From<Comparable> for Arg {
+    fn from(c: Comparable) -> Self {
+        Arg::Comparable(c)
+    }
+}
+
+impl From<Filter> for Arg {
+    fn from(f: Filter) -> Self {
+        Arg::Composite(Box::new(f))
+    }
+}
+
+impl From<Literal> for Arg {
+    fn from(l: Literal) -> Self {
+        Arg::Comparable(Comparable::Member(vec![l]))
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(s: &str) -> Self {
+        Literal::String(s.to_owned()).into()
+    }
+}
+
+impl From<String> for Arg {
+    fn from(s: String) -> Self {
+        Literal::String(s).into()
+    }
+}
+
+macro_rules! impl_from_for_arg {
+    ($($t: ty),*) => {
+        $(
+            impl From<$t> for Arg {
+                fn from(v: $t) -> Self {
+                    Literal::Text(v.to_string()).into()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_arg!(bool, i32, i64, u32, u64, f32, f64);
+
+/// Converts to an RFC 3339 string, e.g. `"2021-01-01T00:00:00Z"`.
+impl TryFrom<&Timestamp> for Arg {
+    type Error = TimestampError;
+
+    fn try_from(t: &Timestamp) -> Result<Self, Self::Error> {
+        Ok(Literal::String(t.to_rfc3339()?).into())
+    }
+}
+
+/// Converts to seconds with an `s` suffix, e.g. `1.500s`.
+impl TryFrom<&Duration> for Arg {
+    type Error = DurationError;
+
+    fn try_from(d: &Duration) -> Result<Self, Self::Error> {
+        Ok(Literal::Text(d.to_json_string()?).into())
+    }
+}
+
+fn write_joined<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    items: &[T],
+    sep: &str,
+) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(sep)?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::And(filters) => write_joined(f, filters, " AND "),
+            Filter::Or(filters) => {
+                // `OR` binds tighter than `AND`.
+                for (i, filter) in filters.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" OR ")?;
+                    }
+                    match filter {
+                        Filter::And(_) => write!(f, "({})", filter)?,
+                        _ => write!(f, "{}", filter)?,
+                    }
+                }
+                Ok(())
+            }
+            Filter::Not(filter) if filter.is_simple() => write!(f, "NOT {}", filter),
+            Filter::Not(filter) => write!(f, "NOT ({})", filter),
+            Filter::Restriction {
+                comparable,
+                comparator: Comparator::Has,
+                arg,
+            } => write!(f, "{}:{}", comparable, arg),
+            Filter::Restriction {
+                comparable,
+                comparator,
+                arg,
+            } => write!(f, "{} {} {}", comparable, comparator, arg),
+            Filter::Global(comparable) => write!(f, "{}", comparable),
+        }
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparator::Eq => "=",
+            Comparator::Ne => "!=",
+            Comparator::Lt => "<",
+            Comparator::Le => "<=",
+            Comparator::Gt => ">",
+            Comparator::Ge => ">=",
+            Comparator::Has => ":",
+        })
+    }
+}
+
+impl fmt::Display for Comparable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparable::Member(literals) => write_joined(f, literals, "."),
+            Comparable::Function { name, args } => {
+                write_joined(f, name, ".")?;
+                f.write_str("(")?;
+                write_joined(f, args, ", ")?;
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Comparable(c) => write!(f, "{}", c),
+            Arg::Composite(filter) => write!(f, "({})", filter),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Text(s) => f.write_str(s),
+            Literal::String(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Literal {
+        Literal::Text(s.to_owned())
+    }
+
+    #[test]
+    fn test_builder() {
+        assert_eq!(
+            field("labels.my key").eq(1),
+            Filter::Restriction {
+                comparable: Comparable::Member(vec![
+                    text("labels"),
+                    Literal::String("my key".to_owned())
+                ]),
+                comparator: Comparator::Eq,
+                arg: text("1").into(),
+            }
+        );
+
+        let f = field("a")
+            .eq(true)
+            .and(field("b").lt(-1.5).and(field("c").ge(2u32)));
+        assert_eq!(f.to_string(), "a = true AND b < -1.5 AND c >= 2");
+
+        let f = field("a")
+            .ne("x")
+            .or(field("b").gt(1).and(field("c").le(2)))
+            .or(!field("d").has("*"));
+        assert_eq!(
+            f.to_string(),
+            r#"a != "x" OR (b > 1 AND c <= 2) OR NOT d:"*""#
+        );
+        assert_eq!(
+            (!(!field("a").has(Literal::Text("*".to_owned())))).to_string(),
+            "NOT (NOT a:*)"
+        );
+        assert_eq!(
+            field("tags")
+                .has(Filter::from(field("x")).or(field("y").into()))
+                .to_string(),
+            "tags:(x OR y)"
+        );
+        assert_eq!(
+            Filter::from(function("math.mem", vec![field("a").into(), 3.into()])).to_string(),
+            "math.mem(a, 3)"
+        );
+    }
+
+    #[test]
+    fn test_well_known_types() {
+        let t = Timestamp {
+            seconds: 1_609_459_200,
+            nanos: 0,
+        };
+        let d = Duration {
+            seconds: 20,
+            nanos: 0,
+        };
+        let f = field("create_time")
+            .gt(Arg::try_from(&t).unwrap())
+            .and(field("ttl").lt(Arg::try_from(&d).unwrap()));
+        assert_eq!(
+            f.to_string(),
+            r#"create_time > "2021-01-01T00:00:00Z" AND ttl < 20s"#
+        );
+        assert!(Arg::try_from(&Timestamp {
+            seconds: i64::MAX,
+            nanos: 0
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        let f = field("a").eq("\"q\" \\ \n\t'");
+        assert_eq!(f.to_string(), r#"a = "\"q\" \\ \n\t'""#);
+        assert_eq!(f.to_string().parse::<Filter>(), Ok(f));
+    }
+}
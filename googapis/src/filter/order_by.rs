@@ -0,0 +1,145 @@
+use std::{error, fmt, str::FromStr};
+
+/// An error returned when an ordering cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderByError {
+    /// The field is not a path of names separated by `.`.
+    InvalidField(String),
+    /// The field is followed by something else than `asc` or `desc`.
+    InvalidDirection(String),
+}
+
+impl fmt::Display for OrderByError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderByError::InvalidField(s) => write!(f, "invalid field: {:?}", s),
+            OrderByError::InvalidDirection(s) => write!(f, "invalid direction: {:?}", s),
+        }
+    }
+}
+
+impl error::Error for OrderByError {}
+
+/// The direction in which a field is sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// The `order_by` field of a list request: fields separated by commas, each optionally followed
+/// by `desc`, e.g. `create_time desc, name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderBy {
+    fields: Vec<(String, Direction)>,
+}
+
+fn is_field(s: &str) -> bool {
+    s.split('.').all(|name| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+impl OrderBy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sorts by `field` in ascending order, after the fields added before.
+    pub fn asc(mut self, field: impl Into<String>) -> Self {
+        self.fields.push((field.into(), Direction::Asc));
+        self
+    }
+
+    /// Sorts by `field` in descending order, after the fields added before.
+    pub fn desc(mut self, field: impl Into<String>) -> Self {
+        self.fields.push((field.into(), Direction::Desc));
+        self
+    }
+
+    pub fn fields(&self) -> &[(String, Direction)] {
+        &self.fields
+    }
+
+    /// Parses an ordering. Whitespace around fields is ignored and directions are case
+    /// insensitive. An empty string has no fields.
+    pub fn parse(s: &str) -> Result<Self, OrderByError> {
+        let mut order_by = OrderBy::new();
+        if s.trim().is_empty() {
+            return Ok(order_by);
+        }
+        for item in s.split(',') {
+            let mut words = item.split_whitespace();
+            let field = match words.next() {
+                Some(field) if is_field(field) => field,
+                _ => return Err(OrderByError::InvalidField(item.trim().to_owned())),
+            };
+            let direction = match words.next() {
+                None => Direction::Asc,
+                Some(d) if d.eq_ignore_ascii_case("asc") => Direction::Asc,
+                Some(d) if d.eq_ignore_ascii_case("desc") => Direction::Desc,
+                Some(_) => return Err(OrderByError::InvalidDirection(item.trim().to_owned())),
+            };
+            if words.next().is_some() {
+                return Err(OrderByError::InvalidDirection(item.trim().to_owned()));
+            }
+            order_by.fields.push((field.to_owned(), direction));
+        }
+        Ok(order_by)
+    }
+}
+
+impl FromStr for OrderBy {
+    type Err = OrderByError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OrderBy::parse(s)
+    }
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, direction)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(field)?;
+            if *direction == Direction::Desc {
+                f.write_str(" desc")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_by() {
+        let o = OrderBy::new().desc("create_time").asc("a.b_1");
+        assert_eq!(o.to_string(), "create_time desc, a.b_1");
+        assert_eq!(o.to_string().parse(), Ok(o.clone()));
+        assert_eq!(OrderBy::parse(" create_time  DESC ,a.b_1 asc"), Ok(o));
+        assert_eq!(OrderBy::parse(""), Ok(OrderBy::new()));
+        assert_eq!(OrderBy::new().to_string(), "");
+
+        for s in &["a,", "a..b", "1a", "a-b", ", a"] {
+            assert!(
+                matches!(OrderBy::parse(s), Err(OrderByError::InvalidField(_))),
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            OrderBy::parse("a up"),
+            Err(OrderByError::InvalidDirection("a up".to_owned()))
+        );
+        assert_eq!(
+            OrderBy::parse("a desc x"),
+            Err(OrderByError::InvalidDirection("a desc x".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use super::{Arg, Comparable, Comparator, Filter, FilterError, Literal};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Dot,
+    Comma,
+    Minus,
+    Comparator(Comparator),
+    Text(String),
+    String(String),
+}
+
+// Characters that end a word.
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || ".:=<>!(),\"'\\".contains(c)
+}
+
+/// Whether `s` can be written as a word without quotes.
+pub(super) fn is_text(s: &str) -> bool {
+    !s.is_empty()
+        && !s.contains(is_delimiter)
+        && !s.starts_with('-')
+        && !matches!(s, "AND" | "OR" | "NOT")
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        chars.next();
+        let next = chars.peek().map(|&(_, c)| c);
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            ':' => Token::Comparator(Comparator::Has),
+            '=' => Token::Comparator(Comparator::Eq),
+            '<' | '>' | '!' if next == Some('=') => {
+                chars.next();
+                Token::Comparator(match c {
+                    '<' => Comparator::Le,
+                    '>' => Comparator::Ge,
+                    _ => Comparator::Ne,
+                })
+            }
+            '<' => Token::Comparator(Comparator::Lt),
+            '>' => Token::Comparator(Comparator::Gt),
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((i, '\\')) => match chars.next() {
+                            Some((_, e @ ('"' | '\'' | '\\'))) => value.push(e),
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 'r')) => value.push('\r'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some(_) => return Err(FilterError::InvalidEscape(i)),
+                            None => return Err(FilterError::UnterminatedString(start)),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(FilterError::UnterminatedString(start)),
+                    }
+                }
+                Token::String(value)
+            }
+            // A `-` before a number is a sign, otherwise a negation.
+            '-' if !next.is_some_and(|c| c.is_ascii_digit()) => Token::Minus,
+            c if !is_delimiter(c) => {
+                // Numbers may have a fractional part, e.g. `-1.5` or `2.5s`.
+                let number = c.is_ascii_digit() || c == '-';
+                let mut text = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    let fraction = number
+                        && c == '.'
+                        && text.bytes().all(|b| b.is_ascii_digit() || b == b'-')
+                        && s[start + text.len() + 1..].starts_with(|c: char| c.is_ascii_digit());
+                    if is_delimiter(c) && !fraction {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+                Token::Text(text)
+            }
+            c => {
+                return Err(FilterError::Unexpected {
+                    position: start,
+                    found: c.to_string(),
+                })
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Text(t)) if t == keyword) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self) -> FilterError {
+        match self.tokens.get(self.next) {
+            Some((position, token)) => FilterError::Unexpected {
+                position: *position,
+                found: match token {
+                    Token::LParen => "(".to_owned(),
+                    Token::RParen => ")".to_owned(),
+                    Token::Dot => ".".to_owned(),
+                    Token::Comma => ",".to_owned(),
+                    Token::Minus => "-".to_owned(),
+                    Token::Comparator(c) => c.to_string(),
+                    Token::Text(t) => t.clone(),
+                    Token::String(s) => Literal::String(s.clone()).to_string(),
+                },
+            },
+            None => FilterError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), FilterError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // expression : sequence {AND sequence}
+    fn expression(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.sequence()?;
+        while self.keyword("AND") {
+            filter = filter.and(self.sequence()?);
+        }
+        Ok(filter)
+    }
+
+    // sequence : factor {factor}
+    fn sequence(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.factor()?;
+        loop {
+            match self.peek() {
+                None | Some(Token::RParen) | Some(Token::Comma) => break,
+                Some(Token::Text(t)) if t == "AND" => break,
+                _ => filter = filter.and(self.factor()?),
+            }
+        }
+        Ok(filter)
+    }
+
+    // factor : term {OR term}
+    fn factor(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.term()?;
+        while self.keyword("OR") {
+            filter = filter.or(self.term()?);
+        }
+        Ok(filter)
+    }
+
+    // term : [NOT | -] simple
+    fn term(&mut self) -> Result<Filter, FilterError> {
+        if self.keyword("NOT") || self.eat(&Token::Minus) {
+            Ok(!self.simple()?)
+        } else {
+            self.simple()
+        }
+    }
+
+    // simple : restriction | ( expression )
+    fn simple(&mut self) -> Result<Filter, FilterError> {
+        if self.eat(&Token::LParen) {
+            let filter = self.expression()?;
+            self.expect(&Token::RParen)?;
+            return Ok(filter);
+        }
+        let comparable = self.comparable()?;
+        match self.peek() {
+            Some(&Token::Comparator(comparator)) => {
+                self.next += 1;
+                Ok(Filter::Restriction {
+                    comparable,
+                    comparator,
+                    arg: self.arg()?,
+                })
+            }
+            _ => Ok(Filter::Global(comparable)),
+        }
+    }
+
+    fn literal(&mut self, keyword: bool) -> Result<Literal, FilterError> {
+        let literal = match self.peek() {
+            Some(Token::Text(t)) if keyword || !matches!(t.as_str(), "AND" | "OR" | "NOT") => {
+                Literal::Text(t.clone())
+            }
+            Some(Token::String(s)) => Literal::String(s.clone()),
+            _ => return Err(self.unexpected()),
+        };
+        self.next += 1;
+        Ok(literal)
+    }
+
+    // comparable : value {. field} | name {. name} ( [arg {, arg}] )
+    fn comparable(&mut self) -> Result<Comparable, FilterError> {
+        let mut literals = vec![self.literal(false)?];
+        while self.eat(&Token::Dot) {
+            literals.push(self.literal(true)?);
+        }
+        if self.peek() != Some(&Token::LParen) {
+            return Ok(Comparable::Member(literals));
+        }
+
+        let name = literals
+            .into_iter()
+            .map(|l| match l {
+                Literal::Text(t) => Ok(t),
+                Literal::String(_) => Err(self.unexpected()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.next += 1;
+        let mut args = Vec::new();
+        if !self.eat(&Token::RParen) {
+            loop {
+                args.push(self.arg()?);
+                if self.eat(&Token::RParen) {
+                    break;
+                }
+                self.expect(&Token::Comma)?;
+            }
+        }
+        Ok(Comparable::Function { name, args })
+    }
+
+    // arg : comparable | ( expression )
+    fn arg(&mut self) -> Result<Arg, FilterError> {
+        if self.eat(&Token::LParen) {
+            let filter = self.expression()?;
+            self.expect(&Token::RParen)?;
+            Ok(filter.into())
+        } else {
+            Ok(self.comparable()?.into())
+        }
+    }
+}
+
+pub(super) fn parse(s: &str) -> Result<Filter, FilterError> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        next: 0,
+    };
+    let filter = parser.expression()?;
+    match parser.peek() {
+        None => Ok(filter),
+        Some(_) => Err(parser.unexpected()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{field, function};
+    use super::*;
+
+    fn text(s: &str) -> Literal {
+        Literal::Text(s.to_owned())
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"a.b<=-1.5 AND -c:"x\"y" 2021-01-01 1.5s v1.2"#).unwrap(),
+            vec![
+                (0, Token::Text("a".to_owned())),
+                (1, Token::Dot),
+                (2, Token::Text("b".to_owned())),
+                (3, Token::Comparator(Comparator::Le)),
+                (5, Token::Text("-1.5".to_owned())),
+                (10, Token::Text("AND".to_owned())),
+                (14, Token::Minus),
+                (15, Token::Text("c".to_owned())),
+                (16, Token::Comparator(Comparator::Has)),
+                (17, Token::String("x\"y".to_owned())),
+                (24, Token::Text("2021-01-01".to_owned())),
+                (35, Token::Text("1.5s".to_owned())),
+                (40, Token::Text("v1".to_owned())),
+                (42, Token::Dot),
+                (43, Token::Text("2".to_owned())),
+            ]
+        );
+        assert_eq!(
+            tokenize("'it\\'s'").unwrap(),
+            vec![(0, Token::String("it's".to_owned()))]
+        );
+        assert_eq!(tokenize("a = \"x"), Err(FilterError::UnterminatedString(4)));
+        assert_eq!(tokenize("a = \"\\x\""), Err(FilterError::InvalidEscape(5)));
+        assert_eq!(
+            tokenize("a ! b"),
+            Err(FilterError::Unexpected {
+                position: 2,
+                found: "!".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("a b AND c OR NOT d").unwrap(),
+            Filter::from(field("a"))
+                .and(field("b").into())
+                .and(Filter::from(field("c")).or(!Filter::from(field("d"))))
+        );
+        assert_eq!(
+            parse("-(a = 1 b) OR c:*").unwrap(),
+            (!field("a").eq(1).and(field("b").into())).or(field("c").has(text("*")))
+        );
+        assert_eq!(
+            parse(r#"labels."my key" != 'x' AND tags:(a OR b)"#).unwrap(),
+            field("labels.my key")
+                .ne("x")
+                .and(field("tags").has(Filter::from(field("a")).or(field("b").into())))
+        );
+        assert_eq!(
+            parse("math.mem(a, (b c), 3) > regex()").unwrap(),
+            function(
+                "math.mem",
+                vec![
+                    field("a").into(),
+                    Filter::from(field("b")).and(field("c").into()).into(),
+                    3.into(),
+                ]
+            )
+            .gt(function("regex", vec![]))
+        );
+        assert_eq!(
+            parse("x.AND = 1").unwrap(),
+            Comparable::Member(vec![text("x"), text("AND")]).eq(1)
+        );
+
+        for (s, e) in &[
+            ("", FilterError::UnexpectedEnd),
+            ("a AND", FilterError::UnexpectedEnd),
+            ("(a", FilterError::UnexpectedEnd),
+            ("a =", FilterError::UnexpectedEnd),
+            (
+                "a)",
+                FilterError::Unexpected {
+                    position: 1,
+                    found: ")".to_owned(),
+                },
+            ),
+            (
+                "OR a",
+                FilterError::Unexpected {
+                    position: 0,
+                    found: "OR".to_owned(),
+                },
+            ),
+            (
+                "a = = b",
+                FilterError::Unexpected {
+                    position: 4,
+                    found: "=".to_owned(),
+                },
+            ),
+            (
+                r#"a."b"(c)"#,
+                FilterError::Unexpected {
+                    position: 5,
+                    found: "(".to_owned(),
+                },
+            ),
+            (
+                "f(a b)",
+                FilterError::Unexpected {
+                    position: 4,
+                    found: "b".to_owned(),
+                },
+            ),
+        ] {
+            assert_eq!(parse(s).as_ref(), Err(e), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in &[
+            r#"a = 1 AND b:"x" OR NOT (c < -2.5 AND d)"#,
+            r#"NOT (a OR b) AND f(x, (y OR z)) >= "2021-01-01T00:00:00Z""#,
+            "a AND (b AND c OR d) AND NOT (NOT e)",
+        ] {
+            let f = parse(s).unwrap();
+            assert_eq!(parse(&f.to_string()).unwrap(), f, "{}", s);
+        }
+        assert_eq!(parse("a  b   AND(c)").unwrap().to_string(), "a AND b AND c");
+    }
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text("a_1"));
+        assert!(is_text("*"));
+        assert!(!is_text(""));
+        assert!(!is_text("a b"));
+        assert!(!is_text("a.b"));
+        assert!(!is_text("-a"));
+        assert!(!is_text("OR"));
+    }
+}
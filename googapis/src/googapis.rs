@@ -59,46 +59,6 @@ pub mod google {
             }
         }
         pub mod googleads {
-            pub mod v6 {
-                pub mod common {
-                    #[cfg(any(
-                        feature = "google-ads-googleads-v6-common",
-                        feature = "google-ads-googleads-v6-errors",
-                        feature = "google-ads-googleads-v6-resources",
-                        feature = "google-ads-googleads-v6-services",
-                    ))]
-                    include_proto!("google.ads.googleads.v6.common");
-                }
-                pub mod enums {
-                    #[cfg(any(
-                        feature = "google-ads-googleads-v6-common",
-                        feature = "google-ads-googleads-v6-enums",
-                        feature = "google-ads-googleads-v6-errors",
-                        feature = "google-ads-googleads-v6-resources",
-                        feature = "google-ads-googleads-v6-services",
-                    ))]
-                    include_proto!("google.ads.googleads.v6.enums");
-                }
-                pub mod errors {
-                    #[cfg(any(
-                        feature = "google-ads-googleads-v6-errors",
-                        feature = "google-ads-googleads-v6-resources",
-                        feature = "google-ads-googleads-v6-services",
-                    ))]
-                    include_proto!("google.ads.googleads.v6.errors");
-                }
-                pub mod resources {
-                    #[cfg(any(
-                        feature = "google-ads-googleads-v6-resources",
-                        feature = "google-ads-googleads-v6-services",
-                    ))]
-                    include_proto!("google.ads.googleads.v6.resources");
-                }
-                pub mod services {
-                    #[cfg(any(feature = "google-ads-googleads-v6-services",))]
-                    include_proto!("google.ads.googleads.v6.services");
-                }
-            }
             pub mod v7 {
                 pub mod common {
                     #[cfg(any(
@@ -207,11 +167,6 @@ pub mod google {
             feature = "google-actions-sdk-v2-interactionmodel-prompt",
             feature = "google-actions-sdk-v2-interactionmodel-type",
             feature = "google-ads-admob-v1",
-            feature = "google-ads-googleads-v6-common",
-            feature = "google-ads-googleads-v6-enums",
-            feature = "google-ads-googleads-v6-errors",
-            feature = "google-ads-googleads-v6-resources",
-            feature = "google-ads-googleads-v6-services",
             feature = "google-ads-googleads-v7-common",
             feature = "google-ads-googleads-v7-enums",
             feature = "google-ads-googleads-v7-errors",
@@ -511,6 +466,9 @@ pub mod google {
             feature = "google-watcher-v1",
             feature = "grafeas-v1",
             feature = "grafeas-v1beta1",
+            feature = "grafeas-v1beta1-attestation",
+            feature = "grafeas-v1beta1-discovery",
+            feature = "grafeas-v1beta1-vulnerability",
             feature = "maps-fleetengine-v1",
         ))]
         include_proto!("google.api");
@@ -518,6 +476,8 @@ pub mod google {
             pub mod v1alpha1 {
                 #[cfg(any(feature = "google-api-expr-v1alpha1",))]
                 include_proto!("google.api.expr.v1alpha1");
+                #[cfg(any(feature = "google-api-expr-v1alpha1",))]
+                include_ext!("google/api/expr/v1alpha1");
             }
             pub mod v1beta1 {
                 #[cfg(any(feature = "google-api-expr-v1beta1",))]
@@ -1444,6 +1404,8 @@ pub mod google {
             pub mod v1 {
                 #[cfg(any(feature = "google-cloud-pubsublite-v1",))]
                 include_proto!("google.cloud.pubsublite.v1");
+                #[cfg(any(feature = "google-cloud-pubsublite-v1",))]
+                include_ext!("google/cloud/pubsublite/v1");
             }
         }
         pub mod recaptchaenterprise {
@@ -2021,6 +1983,12 @@ pub mod google {
                 feature = "google-maps-routes-v1alpha",
             ))]
             include_proto!("google.geo.r#type");
+            #[cfg(any(
+                feature = "google-geo-type",
+                feature = "google-maps-routes-v1",
+                feature = "google-maps-routes-v1alpha",
+            ))]
+            include_ext!("google/geo/type");
         }
     }
     pub mod home {
@@ -2099,6 +2067,52 @@ pub mod google {
                 feature = "google-storage-v2",
             ))]
             include_proto!("google.iam.v1");
+            #[cfg(any(
+                feature = "google-bigtable-admin-v2",
+                feature = "google-cloud-asset-v1",
+                feature = "google-cloud-asset-v1p1beta1",
+                feature = "google-cloud-asset-v1p2beta1",
+                feature = "google-cloud-asset-v1p4beta1",
+                feature = "google-cloud-asset-v1p5beta1",
+                feature = "google-cloud-asset-v1p7beta1",
+                feature = "google-cloud-bigquery-connection-v1",
+                feature = "google-cloud-bigquery-connection-v1beta1",
+                feature = "google-cloud-bigquery-logging-v1",
+                feature = "google-cloud-billing-v1",
+                feature = "google-cloud-datacatalog-v1",
+                feature = "google-cloud-datacatalog-v1beta1",
+                feature = "google-cloud-datafusion-v1beta1",
+                feature = "google-cloud-functions-v1",
+                feature = "google-cloud-iap-v1",
+                feature = "google-cloud-iap-v1beta1",
+                feature = "google-cloud-iot-v1",
+                feature = "google-cloud-policytroubleshooter-v1",
+                feature = "google-cloud-resourcemanager-v2",
+                feature = "google-cloud-resourcemanager-v3",
+                feature = "google-cloud-secretmanager-v1",
+                feature = "google-cloud-secrets-v1beta1",
+                feature = "google-cloud-securitycenter-v1",
+                feature = "google-cloud-securitycenter-v1beta1",
+                feature = "google-cloud-securitycenter-v1p1beta1",
+                feature = "google-cloud-servicedirectory-v1",
+                feature = "google-cloud-servicedirectory-v1beta1",
+                feature = "google-cloud-tasks-v2",
+                feature = "google-cloud-tasks-v2beta2",
+                feature = "google-cloud-tasks-v2beta3",
+                feature = "google-devtools-artifactregistry-v1beta2",
+                feature = "google-devtools-containeranalysis-v1",
+                feature = "google-devtools-containeranalysis-v1beta1",
+                feature = "google-devtools-sourcerepo-v1",
+                feature = "google-genomics-v1",
+                feature = "google-iam-admin-v1",
+                feature = "google-iam-v1",
+                feature = "google-iam-v1-logging",
+                feature = "google-spanner-admin-database-v1",
+                feature = "google-spanner-admin-instance-v1",
+                feature = "google-storage-v1",
+                feature = "google-storage-v2",
+            ))]
+            include_ext!("google/iam/v1");
             pub mod logging {
                 #[cfg(any(feature = "google-iam-v1-logging",))]
                 include_proto!("google.iam.v1.logging");
@@ -2149,7 +2163,6 @@ pub mod google {
     }
     pub mod longrunning {
         #[cfg(any(
-            feature = "google-ads-googleads-v6-services",
             feature = "google-ads-googleads-v7-services",
             feature = "google-ads-googleads-v8-services",
             feature = "google-api-servicemanagement-v1",
@@ -2165,6 +2178,7 @@ pub mod google {
             feature = "google-cloud-asset-v1",
             feature = "google-cloud-asset-v1p2beta1",
             feature = "google-cloud-asset-v1p4beta1",
+            feature = "google-cloud-asset-v1p5beta1",
             feature = "google-cloud-asset-v1p7beta1",
             feature = "google-cloud-assuredworkloads-v1beta1",
             feature = "google-cloud-automl-v1",
@@ -2299,6 +2313,11 @@ pub mod google {
                     feature = "google-maps-routes-v1alpha",
                 ))]
                 include_proto!("google.maps.routes.v1");
+                #[cfg(any(
+                    feature = "google-maps-routes-v1",
+                    feature = "google-maps-routes-v1alpha",
+                ))]
+                include_ext!("google/maps/routes/v1");
             }
             pub mod v1alpha {
                 #[cfg(any(feature = "google-maps-routes-v1alpha",))]
@@ -2345,6 +2364,8 @@ pub mod google {
         pub mod v1 {
             #[cfg(any(feature = "google-pubsub-v1",))]
             include_proto!("google.pubsub.v1");
+            #[cfg(any(feature = "google-pubsub-v1",))]
+            include_ext!("google/pubsub/v1");
         }
         pub mod v1beta2 {
             #[cfg(any(feature = "google-pubsub-v1beta2",))]
@@ -2462,11 +2483,120 @@ pub mod google {
             feature = "maps-fleetengine-v1",
         ))]
         include_proto!("google.r#type");
+        #[cfg(any(
+            feature = "google-actions-sdk-v2",
+            feature = "google-actions-type",
+            feature = "google-ads-admob-v1",
+            feature = "google-assistant-embedded-v1alpha2",
+            feature = "google-bigtable-admin-v2",
+            feature = "google-cloud-aiplatform-v1",
+            feature = "google-cloud-aiplatform-v1beta1",
+            feature = "google-cloud-aiplatform-v1beta1-schema",
+            feature = "google-cloud-asset-v1",
+            feature = "google-cloud-asset-v1p1beta1",
+            feature = "google-cloud-asset-v1p2beta1",
+            feature = "google-cloud-asset-v1p4beta1",
+            feature = "google-cloud-asset-v1p5beta1",
+            feature = "google-cloud-asset-v1p7beta1",
+            feature = "google-cloud-bigquery-connection-v1",
+            feature = "google-cloud-bigquery-connection-v1beta1",
+            feature = "google-cloud-bigquery-logging-v1",
+            feature = "google-cloud-billing-budgets-v1",
+            feature = "google-cloud-billing-budgets-v1beta1",
+            feature = "google-cloud-billing-v1",
+            feature = "google-cloud-channel-v1",
+            feature = "google-cloud-datacatalog-v1",
+            feature = "google-cloud-datacatalog-v1beta1",
+            feature = "google-cloud-datafusion-v1beta1",
+            feature = "google-cloud-dialogflow-cx-v3",
+            feature = "google-cloud-dialogflow-cx-v3beta1",
+            feature = "google-cloud-dialogflow-v2",
+            feature = "google-cloud-dialogflow-v2beta1",
+            feature = "google-cloud-documentai-v1",
+            feature = "google-cloud-documentai-v1beta1",
+            feature = "google-cloud-documentai-v1beta2",
+            feature = "google-cloud-documentai-v1beta3",
+            feature = "google-cloud-domains-v1alpha2",
+            feature = "google-cloud-domains-v1beta1",
+            feature = "google-cloud-functions-v1",
+            feature = "google-cloud-iap-v1",
+            feature = "google-cloud-iap-v1beta1",
+            feature = "google-cloud-iot-v1",
+            feature = "google-cloud-metastore-v1",
+            feature = "google-cloud-metastore-v1alpha",
+            feature = "google-cloud-metastore-v1beta",
+            feature = "google-cloud-orchestration-airflow-service-v1",
+            feature = "google-cloud-orchestration-airflow-service-v1beta1",
+            feature = "google-cloud-orgpolicy-v2",
+            feature = "google-cloud-osconfig-agentendpoint-v1",
+            feature = "google-cloud-osconfig-v1",
+            feature = "google-cloud-osconfig-v1beta",
+            feature = "google-cloud-policytroubleshooter-v1",
+            feature = "google-cloud-recommendationengine-v1beta1",
+            feature = "google-cloud-recommender-logging-v1",
+            feature = "google-cloud-recommender-logging-v1beta1",
+            feature = "google-cloud-recommender-v1",
+            feature = "google-cloud-recommender-v1beta1",
+            feature = "google-cloud-resourcemanager-v2",
+            feature = "google-cloud-resourcemanager-v3",
+            feature = "google-cloud-retail-v2alpha",
+            feature = "google-cloud-secretmanager-v1",
+            feature = "google-cloud-secrets-v1beta1",
+            feature = "google-cloud-security-privateca-v1",
+            feature = "google-cloud-securitycenter-v1",
+            feature = "google-cloud-securitycenter-v1beta1",
+            feature = "google-cloud-securitycenter-v1p1beta1",
+            feature = "google-cloud-servicedirectory-v1",
+            feature = "google-cloud-servicedirectory-v1beta1",
+            feature = "google-cloud-talent-v4",
+            feature = "google-cloud-talent-v4beta1",
+            feature = "google-cloud-tasks-v2",
+            feature = "google-cloud-tasks-v2beta2",
+            feature = "google-cloud-tasks-v2beta3",
+            feature = "google-cloud-vision-v1",
+            feature = "google-cloud-vision-v1p1beta1",
+            feature = "google-cloud-vision-v1p2beta1",
+            feature = "google-cloud-vision-v1p3beta1",
+            feature = "google-cloud-vision-v1p4beta1",
+            feature = "google-datastore-v1",
+            feature = "google-datastore-v1beta3",
+            feature = "google-devtools-artifactregistry-v1beta2",
+            feature = "google-devtools-containeranalysis-v1",
+            feature = "google-devtools-containeranalysis-v1beta1",
+            feature = "google-devtools-sourcerepo-v1",
+            feature = "google-devtools-testing-v1",
+            feature = "google-firestore-admin-v1",
+            feature = "google-firestore-admin-v1beta1",
+            feature = "google-firestore-bundle",
+            feature = "google-firestore-v1",
+            feature = "google-firestore-v1beta1",
+            feature = "google-genomics-v1",
+            feature = "google-geo-type",
+            feature = "google-iam-admin-v1",
+            feature = "google-iam-v1",
+            feature = "google-iam-v1-logging",
+            feature = "google-identity-accesscontextmanager-v1",
+            feature = "google-maps-playablelocations-v3",
+            feature = "google-maps-playablelocations-v3-sample",
+            feature = "google-maps-roads-v1op",
+            feature = "google-maps-routes-v1",
+            feature = "google-maps-routes-v1alpha",
+            feature = "google-monitoring-v3",
+            feature = "google-privacy-dlp-v2",
+            feature = "google-spanner-admin-database-v1",
+            feature = "google-spanner-admin-instance-v1",
+            feature = "google-storage-v1",
+            feature = "google-storage-v2",
+            feature = "google-storagetransfer-v1",
+            feature = "google-streetview-publish-v1",
+            feature = "google-type",
+            feature = "maps-fleetengine-v1",
+        ))]
+        include_ext!("google/type");
     }
     pub mod rpc {
         #[cfg(any(
             feature = "google-actions-sdk-v2",
-            feature = "google-ads-googleads-v6-services",
             feature = "google-ads-googleads-v7-services",
             feature = "google-ads-googleads-v8-services",
             feature = "google-api-expr-v1alpha1",
@@ -2491,6 +2621,7 @@ pub mod google {
             feature = "google-cloud-asset-v1",
             feature = "google-cloud-asset-v1p2beta1",
             feature = "google-cloud-asset-v1p4beta1",
+            feature = "google-cloud-asset-v1p5beta1",
             feature = "google-cloud-asset-v1p7beta1",
             feature = "google-cloud-assuredworkloads-v1beta1",
             feature = "google-cloud-audit",
@@ -2606,10 +2737,12 @@ pub mod google {
             feature = "google-devtools-artifactregistry-v1beta2",
             feature = "google-devtools-cloudbuild-v1",
             feature = "google-devtools-cloudtrace-v2",
+            feature = "google-devtools-containeranalysis-v1",
             feature = "google-devtools-remoteworkers-v1test2",
             feature = "google-firestore-admin-v1",
             feature = "google-firestore-admin-v1beta1",
             feature = "google-firestore-admin-v1beta2",
+            feature = "google-firestore-bundle",
             feature = "google-firestore-v1",
             feature = "google-firestore-v1beta1",
             feature = "google-genomics-v1",
@@ -2631,10 +2764,187 @@ pub mod google {
             feature = "google-streetview-publish-v1",
             feature = "grafeas-v1",
             feature = "grafeas-v1beta1",
+            feature = "grafeas-v1beta1-attestation",
             feature = "grafeas-v1beta1-discovery",
+            feature = "grafeas-v1beta1-vulnerability",
             feature = "storage-clouddms-logging-v1",
         ))]
         include_proto!("google.rpc");
+        #[cfg(any(
+            feature = "google-actions-sdk-v2",
+            feature = "google-ads-googleads-v7-services",
+            feature = "google-ads-googleads-v8-services",
+            feature = "google-api-expr-v1alpha1",
+            feature = "google-api-expr-v1beta1",
+            feature = "google-api-servicecontrol-v1",
+            feature = "google-api-servicemanagement-v1",
+            feature = "google-api-serviceusage-v1",
+            feature = "google-api-serviceusage-v1beta1",
+            feature = "google-appengine-v1",
+            feature = "google-appengine-v1beta",
+            feature = "google-apps-alertcenter-v1beta1",
+            feature = "google-assistant-embedded-v1alpha1",
+            feature = "google-bigtable-admin-v2",
+            feature = "google-bigtable-v2",
+            feature = "google-chromeos-moblab-v1beta1",
+            feature = "google-cloud-aiplatform-logging",
+            feature = "google-cloud-aiplatform-v1",
+            feature = "google-cloud-aiplatform-v1beta1",
+            feature = "google-cloud-aiplatform-v1beta1-schema",
+            feature = "google-cloud-apigateway-v1",
+            feature = "google-cloud-apigeeconnect-v1",
+            feature = "google-cloud-asset-v1",
+            feature = "google-cloud-asset-v1p2beta1",
+            feature = "google-cloud-asset-v1p4beta1",
+            feature = "google-cloud-asset-v1p5beta1",
+            feature = "google-cloud-asset-v1p7beta1",
+            feature = "google-cloud-assuredworkloads-v1beta1",
+            feature = "google-cloud-audit",
+            feature = "google-cloud-automl-v1",
+            feature = "google-cloud-automl-v1beta1",
+            feature = "google-cloud-bigquery-datatransfer-v1",
+            feature = "google-cloud-bigquery-logging-v1",
+            feature = "google-cloud-bigquery-migration-v2alpha",
+            feature = "google-cloud-bigquery-reservation-v1",
+            feature = "google-cloud-bigquery-reservation-v1beta1",
+            feature = "google-cloud-bigquery-storage-v1beta2",
+            feature = "google-cloud-channel-v1",
+            feature = "google-cloud-clouddms-logging-v1",
+            feature = "google-cloud-clouddms-v1",
+            feature = "google-cloud-contactcenterinsights-v1",
+            feature = "google-cloud-datafusion-v1",
+            feature = "google-cloud-datafusion-v1beta1",
+            feature = "google-cloud-datalabeling-v1beta1",
+            feature = "google-cloud-dataproc-v1",
+            feature = "google-cloud-dataproc-v1beta2",
+            feature = "google-cloud-dataqna-v1alpha",
+            feature = "google-cloud-datastream-v1alpha1",
+            feature = "google-cloud-dialogflow-cx-v3",
+            feature = "google-cloud-dialogflow-cx-v3beta1",
+            feature = "google-cloud-dialogflow-v2",
+            feature = "google-cloud-dialogflow-v2beta1",
+            feature = "google-cloud-documentai-v1",
+            feature = "google-cloud-documentai-v1beta1",
+            feature = "google-cloud-documentai-v1beta2",
+            feature = "google-cloud-documentai-v1beta3",
+            feature = "google-cloud-domains-v1alpha2",
+            feature = "google-cloud-domains-v1beta1",
+            feature = "google-cloud-eventarc-v1",
+            feature = "google-cloud-filestore-v1",
+            feature = "google-cloud-filestore-v1beta1",
+            feature = "google-cloud-functions-v1",
+            feature = "google-cloud-gaming-v1",
+            feature = "google-cloud-gaming-v1beta",
+            feature = "google-cloud-gkehub-v1",
+            feature = "google-cloud-gkehub-v1alpha",
+            feature = "google-cloud-gkehub-v1alpha2",
+            feature = "google-cloud-gkehub-v1beta",
+            feature = "google-cloud-gkehub-v1beta1",
+            feature = "google-cloud-iot-v1",
+            feature = "google-cloud-lifesciences-v2beta",
+            feature = "google-cloud-managedidentities-v1",
+            feature = "google-cloud-managedidentities-v1beta1",
+            feature = "google-cloud-mediatranslation-v1alpha1",
+            feature = "google-cloud-mediatranslation-v1beta1",
+            feature = "google-cloud-memcache-v1",
+            feature = "google-cloud-memcache-v1beta2",
+            feature = "google-cloud-metastore-v1",
+            feature = "google-cloud-metastore-v1alpha",
+            feature = "google-cloud-metastore-v1beta",
+            feature = "google-cloud-ml-v1",
+            feature = "google-cloud-networkconnectivity-v1alpha1",
+            feature = "google-cloud-networkmanagement-v1",
+            feature = "google-cloud-networkmanagement-v1beta1",
+            feature = "google-cloud-networksecurity-v1beta1",
+            feature = "google-cloud-networkservices-v1beta1",
+            feature = "google-cloud-notebooks-v1beta1",
+            feature = "google-cloud-orchestration-airflow-service-v1",
+            feature = "google-cloud-orchestration-airflow-service-v1beta1",
+            feature = "google-cloud-osconfig-v1alpha",
+            feature = "google-cloud-privatecatalog-v1beta1",
+            feature = "google-cloud-pubsublite-v1",
+            feature = "google-cloud-recommendationengine-v1beta1",
+            feature = "google-cloud-redis-v1",
+            feature = "google-cloud-redis-v1beta1",
+            feature = "google-cloud-resourcemanager-v2",
+            feature = "google-cloud-resourcemanager-v3",
+            feature = "google-cloud-retail-logging",
+            feature = "google-cloud-retail-v2",
+            feature = "google-cloud-retail-v2alpha",
+            feature = "google-cloud-retail-v2beta",
+            feature = "google-cloud-runtimeconfig-v1beta1",
+            feature = "google-cloud-scheduler-v1",
+            feature = "google-cloud-scheduler-v1beta1",
+            feature = "google-cloud-security-privateca-v1",
+            feature = "google-cloud-security-privateca-v1beta1",
+            feature = "google-cloud-securitycenter-v1",
+            feature = "google-cloud-securitycenter-v1beta1",
+            feature = "google-cloud-securitycenter-v1p1beta1",
+            feature = "google-cloud-shell-v1",
+            feature = "google-cloud-speech-v1",
+            feature = "google-cloud-speech-v1p1beta1",
+            feature = "google-cloud-talent-v4",
+            feature = "google-cloud-talent-v4beta1",
+            feature = "google-cloud-tasks-v2",
+            feature = "google-cloud-tasks-v2beta2",
+            feature = "google-cloud-tasks-v2beta3",
+            feature = "google-cloud-tpu-v1",
+            feature = "google-cloud-translation-v3",
+            feature = "google-cloud-translation-v3beta1",
+            feature = "google-cloud-video-transcoder-v1",
+            feature = "google-cloud-videointelligence-v1",
+            feature = "google-cloud-videointelligence-v1beta2",
+            feature = "google-cloud-videointelligence-v1p1beta1",
+            feature = "google-cloud-videointelligence-v1p2beta1",
+            feature = "google-cloud-videointelligence-v1p3beta1",
+            feature = "google-cloud-vision-v1",
+            feature = "google-cloud-vision-v1p1beta1",
+            feature = "google-cloud-vision-v1p2beta1",
+            feature = "google-cloud-vision-v1p3beta1",
+            feature = "google-cloud-vision-v1p4beta1",
+            feature = "google-cloud-vpcaccess-v1",
+            feature = "google-cloud-workflows-v1",
+            feature = "google-cloud-workflows-v1beta",
+            feature = "google-container-v1beta1",
+            feature = "google-dataflow-v1beta3",
+            feature = "google-datastore-admin-v1",
+            feature = "google-datastore-admin-v1beta1",
+            feature = "google-devtools-artifactregistry-v1beta2",
+            feature = "google-devtools-cloudbuild-v1",
+            feature = "google-devtools-cloudtrace-v2",
+            feature = "google-devtools-containeranalysis-v1",
+            feature = "google-devtools-remoteworkers-v1test2",
+            feature = "google-firestore-admin-v1",
+            feature = "google-firestore-admin-v1beta1",
+            feature = "google-firestore-admin-v1beta2",
+            feature = "google-firestore-bundle",
+            feature = "google-firestore-v1",
+            feature = "google-firestore-v1beta1",
+            feature = "google-genomics-v1",
+            feature = "google-genomics-v1alpha2",
+            feature = "google-iam-v1beta",
+            feature = "google-identity-accesscontextmanager-v1",
+            feature = "google-logging-v2",
+            feature = "google-longrunning",
+            feature = "google-maps-routes-v1",
+            feature = "google-maps-routes-v1alpha",
+            feature = "google-monitoring-v3",
+            feature = "google-partner-aistreams-v1alpha1",
+            feature = "google-privacy-dlp-v2",
+            feature = "google-rpc",
+            feature = "google-spanner-admin-database-v1",
+            feature = "google-spanner-admin-instance-v1",
+            feature = "google-spanner-v1",
+            feature = "google-storagetransfer-v1",
+            feature = "google-streetview-publish-v1",
+            feature = "grafeas-v1",
+            feature = "grafeas-v1beta1",
+            feature = "grafeas-v1beta1-attestation",
+            feature = "grafeas-v1beta1-discovery",
+            feature = "grafeas-v1beta1-vulnerability",
+            feature = "storage-clouddms-logging-v1",
+        ))]
+        include_ext!("google/rpc");
         pub mod context {
             #[cfg(any(feature = "google-cloud-audit", feature = "google-rpc-context",))]
             include_proto!("google.rpc.context");
@@ -2668,6 +2978,8 @@ pub mod google {
         pub mod v1 {
             #[cfg(any(feature = "google-spanner-v1",))]
             include_proto!("google.spanner.v1");
+            #[cfg(any(feature = "google-spanner-v1",))]
+            include_ext!("google/spanner/v1");
         }
     }
     pub mod storage {
@@ -2718,28 +3030,58 @@ pub mod grafeas {
         ))]
         include_proto!("grafeas.v1beta1");
         pub mod attestation {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-attestation",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.attestation");
         }
         pub mod build {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-build",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-build",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.build");
         }
         pub mod deployment {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-deployment",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-deployment",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.deployment");
         }
         pub mod discovery {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-discovery",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.discovery");
         }
         pub mod image {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-image",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-image",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.image");
         }
         pub mod package {
             #[cfg(any(
                 feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-discovery",
                 feature = "grafeas-v1beta1-package",
                 feature = "grafeas-v1beta1-vulnerability",
             ))]
@@ -2748,22 +3090,33 @@ pub mod grafeas {
         pub mod provenance {
             #[cfg(any(
                 feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
                 feature = "grafeas-v1beta1-build",
+                feature = "grafeas-v1beta1-discovery",
                 feature = "grafeas-v1beta1-provenance",
+                feature = "grafeas-v1beta1-vulnerability",
             ))]
             include_proto!("grafeas.v1beta1.provenance");
         }
         pub mod source {
             #[cfg(any(
                 feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
                 feature = "grafeas-v1beta1-build",
+                feature = "grafeas-v1beta1-discovery",
                 feature = "grafeas-v1beta1-provenance",
                 feature = "grafeas-v1beta1-source",
+                feature = "grafeas-v1beta1-vulnerability",
             ))]
             include_proto!("grafeas.v1beta1.source");
         }
         pub mod vulnerability {
-            #[cfg(any(feature = "grafeas-v1beta1", feature = "grafeas-v1beta1-vulnerability",))]
+            #[cfg(any(
+                feature = "grafeas-v1beta1",
+                feature = "grafeas-v1beta1-attestation",
+                feature = "grafeas-v1beta1-discovery",
+                feature = "grafeas-v1beta1-vulnerability",
+            ))]
             include_proto!("grafeas.v1beta1.vulnerability");
         }
     }
@@ -2773,6 +3126,8 @@ pub mod maps {
         pub mod v1 {
             #[cfg(any(feature = "maps-fleetengine-v1",))]
             include_proto!("maps.fleetengine.v1");
+            #[cfg(any(feature = "maps-fleetengine-v1",))]
+            include_ext!("maps/fleetengine/v1");
         }
     }
 }
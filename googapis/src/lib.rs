@@ -81,6 +81,15 @@
 /// ````
 pub const CERTIFICATES: &[u8] = include_bytes!("../data/roots.pem");
 
+// Used by the package extensions.
+#[allow(dead_code)]
+mod base64;
+pub mod field_mask;
+pub mod filter;
+pub mod reflect;
+pub mod validate;
+pub mod wkt;
+
 #[allow(unused_macros)]
 macro_rules! include_proto {
     ($package: tt) => {
@@ -88,4 +97,12 @@ macro_rules! include_proto {
     };
 }
 
+// Includes the hand-written code of a package from `ext/<package path>/mod.rs`.
+#[allow(unused_macros)]
+macro_rules! include_ext {
+    ($path: tt) => {
+        include!(concat!("ext/", $path, "/mod.rs"));
+    };
+}
+
 include!("googapis.rs");
@@ -0,0 +1,76 @@
+//! Descriptors of the generated messages.
+//!
+//! `cargo xtask gen` implements [`Reflect`] for every message, compiled when the `reflect`
+//! feature is enabled. The descriptors are used to validate, compute and apply field masks, see
+//! [`field_mask`](crate::field_mask), and to validate requests, see [`validate`](crate::validate).
+
+pub(crate) mod wire;
+
+/// The descriptor of a message.
+#[derive(Debug)]
+pub struct MessageDescriptor {
+    /// The fully-qualified name, e.g. `google.type.Date`.
+    pub full_name: &'static str,
+    /// The fields, ordered by number.
+    pub fields: &'static [FieldDescriptor],
+}
+
+impl MessageDescriptor {
+    /// Returns the field with the given proto name.
+    pub fn field(&self, name: &str) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Returns the field with the given number.
+    pub fn field_by_number(&self, number: u32) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|f| f.number == number)
+    }
+}
+
+/// The descriptor of a field of a message.
+#[derive(Debug)]
+pub struct FieldDescriptor {
+    /// The proto name, e.g. `update_time`.
+    pub name: &'static str,
+    pub number: u32,
+    pub kind: FieldKind,
+    /// Whether the field is `repeated`. Map fields are not.
+    pub repeated: bool,
+    /// The name of the oneof containing the field. Proto3 `optional` fields have none.
+    pub oneof: Option<&'static str>,
+    /// The `google.api.field_behavior` annotations of the field.
+    pub behaviors: &'static [FieldBehavior],
+}
+
+/// A `google.api.field_behavior` annotation, see [AIP-203](https://google.aip.dev/203).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldBehavior {
+    Optional,
+    /// The field must be set in requests.
+    Required,
+    /// The field is set by the server and ignored in requests.
+    OutputOnly,
+    InputOnly,
+    /// The field cannot be changed after creation.
+    Immutable,
+    UnorderedList,
+    NonEmptyDefault,
+}
+
+/// The type of a field, as far as field masks are concerned.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldKind {
+    /// A scalar, an enum or a well-known type: a field mask cannot select its parts.
+    Scalar,
+    /// A generated message.
+    Message(fn() -> &'static MessageDescriptor),
+    /// A map, with the descriptor of its values if they are generated messages.
+    Map {
+        value: Option<fn() -> &'static MessageDescriptor>,
+    },
+}
+
+/// A generated message with a descriptor.
+pub trait Reflect: prost::Message + Default {
+    fn descriptor() -> &'static MessageDescriptor;
+}
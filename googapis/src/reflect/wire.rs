@@ -0,0 +1,330 @@
+// prost has no reflection: messages are inspected, compared and merged on their encoding, guided
+// by the descriptors generated by `cargo xtask gen`.
+
+use std::collections::BTreeMap;
+
+use prost::{
+    encoding::{decode_key, decode_varint, encode_key, encode_varint, WireType},
+    DecodeError,
+};
+
+use crate::reflect::{FieldDescriptor, FieldKind, MessageDescriptor};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Varint(u64),
+    Fixed64(u64),
+    LengthDelimited(Vec<u8>),
+    Fixed32(u32),
+}
+
+/// The fields of an encoded message, in encoding order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Fields(Vec<(u32, Value)>);
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if buf.len() < len {
+        return Err(DecodeError::new("buffer underflow"));
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+fn encode_value(number: u32, value: &Value, buf: &mut Vec<u8>) {
+    match value {
+        Value::Varint(v) => {
+            encode_key(number, WireType::Varint, buf);
+            encode_varint(*v, buf);
+        }
+        Value::Fixed64(v) => {
+            encode_key(number, WireType::SixtyFourBit, buf);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::LengthDelimited(v) => {
+            encode_key(number, WireType::LengthDelimited, buf);
+            encode_varint(v.len() as u64, buf);
+            buf.extend_from_slice(v);
+        }
+        Value::Fixed32(v) => {
+            encode_key(number, WireType::ThirtyTwoBit, buf);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+impl Fields {
+    pub(crate) fn decode(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let (number, wire_type) = decode_key(&mut buf)?;
+            let value = match wire_type {
+                WireType::Varint => Value::Varint(decode_varint(&mut buf)?),
+                WireType::SixtyFourBit => {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(take(&mut buf, 8)?);
+                    Value::Fixed64(u64::from_le_bytes(bytes))
+                }
+                WireType::LengthDelimited => {
+                    let len = decode_varint(&mut buf)?;
+                    Value::LengthDelimited(take(&mut buf, len as usize)?.to_vec())
+                }
+                WireType::ThirtyTwoBit => {
+                    let mut bytes = [0; 4];
+                    bytes.copy_from_slice(take(&mut buf, 4)?);
+                    Value::Fixed32(u32::from_le_bytes(bytes))
+                }
+                WireType::StartGroup | WireType::EndGroup => {
+                    return Err(DecodeError::new("groups are not supported"))
+                }
+            };
+            fields.push((number, value));
+        }
+        Ok(Fields(fields))
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for (number, value) in &self.0 {
+            encode_value(*number, value, &mut buf);
+        }
+        buf
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the numbers of the fields present, in encoding order and without duplicates.
+    pub(crate) fn numbers(&self) -> Vec<u32> {
+        let mut numbers = Vec::<u32>::new();
+        for (number, _) in &self.0 {
+            if !numbers.contains(number) {
+                numbers.push(*number);
+            }
+        }
+        numbers
+    }
+
+    pub(crate) fn has(&self, number: u32) -> bool {
+        self.0.iter().any(|(n, _)| *n == number)
+    }
+
+    pub(crate) fn values(&self, number: u32) -> impl Iterator<Item = &Value> {
+        self.0
+            .iter()
+            .filter(move |(n, _)| *n == number)
+            .map(|(_, v)| v)
+    }
+
+    fn remove(&mut self, number: u32) {
+        self.0.retain(|(n, _)| *n != number);
+    }
+
+    fn extend(&mut self, number: u32, values: impl IntoIterator<Item = Value>) {
+        self.0.extend(values.into_iter().map(|v| (number, v)));
+    }
+
+    /// Decodes a singular message field. Its occurrences are concatenated, which merges them.
+    pub(crate) fn message(&self, number: u32) -> Result<Fields, DecodeError> {
+        let mut buf = Vec::new();
+        for value in self.values(number) {
+            match value {
+                Value::LengthDelimited(bytes) => buf.extend_from_slice(bytes),
+                _ => return Err(DecodeError::new("invalid wire type for a message")),
+            }
+        }
+        Fields::decode(&buf)
+    }
+}
+
+pub(crate) fn bytes(value: &Value) -> Result<&[u8], DecodeError> {
+    match value {
+        Value::LengthDelimited(bytes) => Ok(bytes),
+        _ => Err(DecodeError::new("invalid wire type for a message")),
+    }
+}
+
+/// Encodes the values of a field so that equal values have equal encodings: the occurrences of
+/// singular messages are merged and map entries are sorted by key.
+fn canonical(fields: &Fields, field: &FieldDescriptor) -> Result<Vec<u8>, DecodeError> {
+    let number = field.number;
+    let mut buf = Vec::new();
+    match field.kind {
+        FieldKind::Message(d) if !field.repeated => {
+            if fields.has(number) {
+                let message = canonical_message(&fields.message(number)?, d())?;
+                encode_value(number, &Value::LengthDelimited(message), &mut buf);
+            }
+        }
+        FieldKind::Message(d) => {
+            for value in fields.values(number) {
+                let message = canonical_message(&Fields::decode(bytes(value)?)?, d())?;
+                encode_value(number, &Value::LengthDelimited(message), &mut buf);
+            }
+        }
+        FieldKind::Map { value } => {
+            // Later entries replace earlier ones with the same key.
+            let mut entries = BTreeMap::new();
+            for entry in fields.values(number) {
+                let entry = Fields::decode(bytes(entry)?)?;
+                let mut key = Vec::new();
+                if let Some(k) = entry.values(1).last() {
+                    encode_value(1, k, &mut key);
+                }
+                let mut value_buf = Vec::new();
+                match value {
+                    Some(d) if entry.has(2) => {
+                        let message = canonical_message(&entry.message(2)?, d())?;
+                        encode_value(2, &Value::LengthDelimited(message), &mut value_buf);
+                    }
+                    _ => {
+                        if let Some(v) = entry.values(2).last() {
+                            encode_value(2, v, &mut value_buf);
+                        }
+                    }
+                }
+                entries.insert(key, value_buf);
+            }
+            for (mut key, value) in entries {
+                key.extend(value);
+                encode_value(number, &Value::LengthDelimited(key), &mut buf);
+            }
+        }
+        FieldKind::Scalar => {
+            for value in fields.values(number) {
+                encode_value(number, value, &mut buf);
+            }
+        }
+    }
+    Ok(buf)
+}
+
+fn canonical_message(fields: &Fields, d: &MessageDescriptor) -> Result<Vec<u8>, DecodeError> {
+    let mut buf = Vec::new();
+    for field in d.fields {
+        buf.extend(canonical(fields, field)?);
+    }
+    for (number, value) in &fields.0 {
+        if d.field_by_number(*number).is_none() {
+            encode_value(*number, value, &mut buf);
+        }
+    }
+    Ok(buf)
+}
+
+pub(crate) fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// Pushes the paths of the fields that differ between `a` and `b`.
+pub(crate) fn diff(
+    a: &Fields,
+    b: &Fields,
+    d: &MessageDescriptor,
+    prefix: &str,
+    paths: &mut Vec<String>,
+) -> Result<(), DecodeError> {
+    for field in d.fields {
+        let number = field.number;
+        let path = join(prefix, field.name);
+        match field.kind {
+            FieldKind::Message(m) if !field.repeated && a.has(number) && b.has(number) => {
+                diff(&a.message(number)?, &b.message(number)?, m(), &path, paths)?
+            }
+            _ => {
+                if canonical(a, field)? != canonical(b, field)? {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Keys of `sint` types are zigzag encoded and never match.
+fn key_matches(entry: &Fields, key: &str) -> bool {
+    let as_u64 = |key: &str| {
+        key.parse::<u64>()
+            .ok()
+            .or_else(|| key.parse::<i64>().ok().map(|k| k as u64))
+    };
+    match entry.values(1).last() {
+        Some(Value::LengthDelimited(k)) => k == key.as_bytes(),
+        Some(Value::Varint(k)) => match key {
+            "true" => *k == 1,
+            "false" => *k == 0,
+            _ => as_u64(key) == Some(*k),
+        },
+        Some(Value::Fixed64(k)) => as_u64(key) == Some(*k),
+        Some(Value::Fixed32(k)) => {
+            key.parse::<u32>()
+                .ok()
+                .or_else(|| key.parse::<i32>().ok().map(|k| k as u32))
+                == Some(*k)
+        }
+        // The key has its default value.
+        None => matches!(key, "" | "0" | "false"),
+    }
+}
+
+/// Copies the field at the path given by `segments` from `source` to `target`. The path must be
+/// valid for `d`.
+pub(crate) fn apply(
+    target: &mut Fields,
+    source: &Fields,
+    d: &MessageDescriptor,
+    segments: &[String],
+) -> Result<(), DecodeError> {
+    let field = match d.field(&segments[0]) {
+        Some(field) => field,
+        None => return Ok(()),
+    };
+    let number = field.number;
+    match (field.kind, &segments[1..]) {
+        (_, []) => {
+            target.remove(number);
+            target.extend(number, source.values(number).cloned());
+        }
+        (FieldKind::Message(m), rest) if !field.repeated => {
+            let present = target.has(number);
+            let mut message = target.message(number)?;
+            apply(&mut message, &source.message(number)?, m(), rest)?;
+            target.remove(number);
+            // Selecting a field of an unset message in both messages leaves it unset.
+            if present || !message.is_empty() {
+                target.extend(number, Some(Value::LengthDelimited(message.encode())));
+            }
+        }
+        (FieldKind::Map { .. }, [key]) => {
+            let mut entries = Vec::new();
+            for entry in target.values(number) {
+                if !key_matches(&Fields::decode(bytes(entry)?)?, key) {
+                    entries.push(entry.clone());
+                }
+            }
+            for entry in source.values(number) {
+                if key_matches(&Fields::decode(bytes(entry)?)?, key) {
+                    entries.push(entry.clone());
+                }
+            }
+            target.remove(number);
+            target.extend(number, entries);
+        }
+        _ => {}
+    }
+
+    // Setting a member of a oneof clears the others.
+    if field.oneof.is_some() && target.has(number) {
+        for other in d.fields {
+            if other.oneof == field.oneof && other.number != number {
+                target.remove(other.number);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,467 @@
+//! Client-side validation of requests against their `google.api.field_behavior` annotations,
+//! see [AIP-203](https://google.aip.dev/203).
+//!
+//! With the `reflect` feature, every generated message implements [`Validate`], which reports
+//! the `REQUIRED` fields that are not set and the `OUTPUT_ONLY` fields that are. [`request`]
+//! validates a message before it is sent, turning violations into an `INVALID_ARGUMENT` status
+//! instead of a network round trip.
+//!
+//! Tonic interceptors only see the metadata of a request, not its message, so validation
+//! happens above the generated clients: wrap a client in a [`ValidatingClient`] to validate
+//! every request sent through it, or wrap single messages with [`request`].
+//!
+//! # Example
+//! ```ignore
+//! use googapis::validate::{self, ValidatingClient};
+//!
+//! let mut client = ValidatingClient::new(SecretManagerServiceClient::new(channel));
+//! let response = client
+//!     .call(CreateSecretRequest { ..Default::default() }, |c, r| c.create_secret(r))
+//!     .await?;
+//!
+//! let response = other_client
+//!     .create_secret(validate::request(CreateSecretRequest { ..Default::default() })?)
+//!     .await?;
+//! ```
+
+use std::{error, fmt, future::Future};
+
+use crate::reflect::{
+    wire::{bytes, join, Fields},
+    FieldBehavior, FieldKind, MessageDescriptor, Reflect,
+};
+
+/// A field that does not comply with its annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A `REQUIRED` field is not set, or has its default value.
+    Missing(String),
+    /// An `OUTPUT_ONLY` field is set.
+    OutputOnly(String),
+    /// A message field, or the message itself if the path is empty, cannot be decoded to be
+    /// checked, e.g. because it has proto2 groups.
+    Unchecked(String),
+}
+
+/// An error returned when a message has violations. Paths of repeated fields have the index of
+/// the element, e.g. `requests[1].topic`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, v) in self.violations.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match v {
+                Violation::Missing(path) => write!(f, "missing required field {}", path)?,
+                Violation::OutputOnly(path) => write!(f, "output only field {} is set", path)?,
+                Violation::Unchecked(path) if path.is_empty() => {
+                    f.write_str("message cannot be checked")?
+                }
+                Violation::Unchecked(path) => write!(f, "field {} cannot be checked", path)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Validation of a message against its field behaviors.
+pub trait Validate {
+    /// Checks that the `REQUIRED` fields are set and the `OUTPUT_ONLY` fields are not, in the
+    /// message and in the messages it contains.
+    ///
+    /// Required fields of nested messages are not checked in messages with an `update_mask`
+    /// field: partial updates leave them unset, see [AIP-134](https://google.aip.dev/134).
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+impl<M: Reflect> Validate for M {
+    fn validate(&self) -> Result<(), ValidationError> {
+        let mut violations = Vec::new();
+        match Fields::decode(&self.encode_to_vec()) {
+            Ok(fields) => check(&fields, M::descriptor(), "", true, &mut violations),
+            Err(_) => violations.push(Violation::Unchecked(String::new())),
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { violations })
+        }
+    }
+}
+
+fn check(
+    fields: &Fields,
+    d: &MessageDescriptor,
+    prefix: &str,
+    required: bool,
+    violations: &mut Vec<Violation>,
+) {
+    let nested_required = required && d.field("update_mask").is_none();
+    for field in d.fields {
+        let path = join(prefix, field.name);
+        let present = fields.has(field.number);
+        if required && !present && field.behaviors.contains(&FieldBehavior::Required) {
+            violations.push(Violation::Missing(path.clone()));
+        }
+        if present && field.behaviors.contains(&FieldBehavior::OutputOnly) {
+            violations.push(Violation::OutputOnly(path.clone()));
+        }
+        match field.kind {
+            FieldKind::Message(m) if present && !field.repeated => {
+                match fields.message(field.number) {
+                    Ok(message) => check(&message, m(), &path, nested_required, violations),
+                    Err(_) => violations.push(Violation::Unchecked(path)),
+                }
+            }
+            FieldKind::Message(m) if present => {
+                for (i, value) in fields.values(field.number).enumerate() {
+                    let path = format!("{}[{}]", path, i);
+                    match bytes(value).and_then(Fields::decode) {
+                        Ok(message) => check(&message, m(), &path, nested_required, violations),
+                        Err(_) => violations.push(Violation::Unchecked(path)),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Validates `message` and wraps it in a request, failing with `INVALID_ARGUMENT` if it has
+/// violations.
+#[allow(clippy::result_large_err)]
+pub fn request<M: Reflect>(message: M) -> Result<tonic::Request<M>, tonic::Status> {
+    message
+        .validate()
+        .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+    Ok(tonic::Request::new(message))
+}
+
+/// A client, usually a generated one, whose requests are validated before they are sent.
+#[derive(Debug, Clone)]
+pub struct ValidatingClient<C> {
+    inner: C,
+}
+
+impl<C> ValidatingClient<C> {
+    pub fn new(inner: C) -> Self {
+        ValidatingClient { inner }
+    }
+
+    /// Returns the wrapped client, to call it without validation.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    /// Validates `message` and calls `method` of the client with it, e.g.
+    /// `|c, r| c.create_secret(r)`. Fails with `INVALID_ARGUMENT` without calling the method if
+    /// the message has violations.
+    pub async fn call<'a, M, F, Fut, R>(
+        &'a mut self,
+        message: M,
+        method: F,
+    ) -> Result<R, tonic::Status>
+    where
+        M: Reflect,
+        F: FnOnce(&'a mut C, tonic::Request<M>) -> Fut,
+        Fut: Future<Output = Result<R, tonic::Status>>,
+    {
+        let request = request(message)?;
+        method(&mut self.inner, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::FieldDescriptor;
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct CreateBookRequest {
+        #[prost(string, tag = "1")]
+        parent: String,
+        #[prost(message, optional, tag = "2")]
+        book: Option<Book>,
+        #[prost(message, repeated, tag = "3")]
+        books: Vec<Book>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct UpdateBookRequest {
+        #[prost(message, optional, tag = "1")]
+        book: Option<Book>,
+        #[prost(message, optional, tag = "2")]
+        update_mask: Option<prost_types::FieldMask>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct Book {
+        #[prost(string, tag = "1")]
+        title: String,
+        #[prost(int64, tag = "2")]
+        pages: i64,
+        #[prost(string, tag = "3")]
+        etag: String,
+    }
+
+    const fn field(
+        name: &'static str,
+        number: u32,
+        kind: FieldKind,
+        repeated: bool,
+        behaviors: &'static [FieldBehavior],
+    ) -> FieldDescriptor {
+        FieldDescriptor {
+            name,
+            number,
+            kind,
+            repeated,
+            oneof: None,
+            behaviors,
+        }
+    }
+
+    fn book() -> &'static MessageDescriptor {
+        <Book as Reflect>::descriptor()
+    }
+
+    impl Reflect for CreateBookRequest {
+        fn descriptor() -> &'static MessageDescriptor {
+            static DESCRIPTOR: MessageDescriptor = MessageDescriptor {
+                full_name: "test.CreateBookRequest",
+                fields: &[
+                    field(
+                        "parent",
+                        1,
+                        FieldKind::Scalar,
+                        false,
+                        &[FieldBehavior::Required],
+                    ),
+                    field(
+                        "book",
+                        2,
+                        FieldKind::Message(book),
+                        false,
+                        &[FieldBehavior::Required],
+                    ),
+                    field("books", 3, FieldKind::Message(book), true, &[]),
+                ],
+            };
+            &DESCRIPTOR
+        }
+    }
+
+    impl Reflect for UpdateBookRequest {
+        fn descriptor() -> &'static MessageDescriptor {
+            static DESCRIPTOR: MessageDescriptor = MessageDescriptor {
+                full_name: "test.UpdateBookRequest",
+                fields: &[
+                    field(
+                        "book",
+                        1,
+                        FieldKind::Message(book),
+                        false,
+                        &[FieldBehavior::Required],
+                    ),
+                    field("update_mask", 2, FieldKind::Scalar, false, &[]),
+                ],
+            };
+            &DESCRIPTOR
+        }
+    }
+
+    impl Reflect for Book {
+        fn descriptor() -> &'static MessageDescriptor {
+            static DESCRIPTOR: MessageDescriptor = MessageDescriptor {
+                full_name: "test.Book",
+                fields: &[
+                    field(
+                        "title",
+                        1,
+                        FieldKind::Scalar,
+                        false,
+                        &[FieldBehavior::Required, FieldBehavior::Immutable],
+                    ),
+                    field(
+                        "pages",
+                        2,
+                        FieldKind::Scalar,
+                        false,
+                        &[FieldBehavior::Required],
+                    ),
+                    field(
+                        "etag",
+                        3,
+                        FieldKind::Scalar,
+                        false,
+                        &[FieldBehavior::OutputOnly],
+                    ),
+                ],
+            };
+            &DESCRIPTOR
+        }
+    }
+
+    fn missing(path: &str) -> Violation {
+        Violation::Missing(path.to_owned())
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = Book {
+            title: "dune".to_owned(),
+            pages: 412,
+            etag: String::new(),
+        };
+        let request = CreateBookRequest {
+            parent: "shelves/1".to_owned(),
+            book: Some(valid.clone()),
+            books: vec![valid.clone()],
+        };
+        assert_eq!(request.validate(), Ok(()));
+
+        let request = CreateBookRequest {
+            parent: String::new(),
+            book: Some(Book {
+                etag: "x".to_owned(),
+                ..valid.clone()
+            }),
+            books: vec![valid, Book::default()],
+        };
+        let e = request.validate().unwrap_err();
+        assert_eq!(
+            e.violations,
+            vec![
+                missing("parent"),
+                Violation::OutputOnly("book.etag".to_owned()),
+                missing("books[1].title"),
+                missing("books[1].pages"),
+            ]
+        );
+        assert_eq!(
+            e.to_string(),
+            "missing required field parent, output only field book.etag is set, \
+             missing required field books[1].title, missing required field books[1].pages"
+        );
+
+        assert_eq!(
+            CreateBookRequest::default()
+                .validate()
+                .unwrap_err()
+                .violations,
+            vec![missing("parent"), missing("book")]
+        );
+    }
+
+    #[test]
+    fn test_validate_update() {
+        let request = UpdateBookRequest {
+            book: Some(Book {
+                pages: 1,
+                etag: "x".to_owned(),
+                ..Default::default()
+            }),
+            update_mask: None,
+        };
+        assert_eq!(
+            request.validate().unwrap_err().violations,
+            vec![Violation::OutputOnly("book.etag".to_owned())]
+        );
+        assert_eq!(
+            UpdateBookRequest::default()
+                .validate()
+                .unwrap_err()
+                .violations,
+            vec![missing("book")]
+        );
+    }
+
+    #[test]
+    fn test_unchecked() {
+        // A proto2 message with a group, which cannot be decoded.
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        struct Shelf {
+            #[prost(group, optional, tag = "1")]
+            book: Option<Book>,
+        }
+
+        impl Reflect for Shelf {
+            fn descriptor() -> &'static MessageDescriptor {
+                static DESCRIPTOR: MessageDescriptor = MessageDescriptor {
+                    full_name: "test.Shelf",
+                    fields: &[field("book", 1, FieldKind::Message(book), false, &[])],
+                };
+                &DESCRIPTOR
+            }
+        }
+
+        let shelf = Shelf {
+            book: Some(Book::default()),
+        };
+        let e = shelf.validate().unwrap_err();
+        assert_eq!(e.violations, vec![Violation::Unchecked(String::new())]);
+        assert_eq!(e.to_string(), "message cannot be checked");
+    }
+
+    #[derive(Default)]
+    struct Shelves {
+        created: Vec<CreateBookRequest>,
+    }
+
+    impl Shelves {
+        async fn create_book(
+            &mut self,
+            request: impl tonic::IntoRequest<CreateBookRequest>,
+        ) -> Result<tonic::Response<()>, tonic::Status> {
+            self.created.push(request.into_request().into_inner());
+            Ok(tonic::Response::new(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validating_client() {
+        let mut client = ValidatingClient::new(Shelves::default());
+        let status = client
+            .call(CreateBookRequest::default(), |c, r| c.create_book(r))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(client.get_mut().created.is_empty());
+
+        let request = CreateBookRequest {
+            parent: "shelves/1".to_owned(),
+            book: Some(Book {
+                title: "dune".to_owned(),
+                pages: 412,
+                etag: String::new(),
+            }),
+            books: Vec::new(),
+        };
+        client
+            .call(request.clone(), |c, r| c.create_book(r))
+            .await
+            .unwrap();
+        assert_eq!(client.into_inner().created, vec![request]);
+    }
+
+    #[test]
+    fn test_request() {
+        let status = request(CreateBookRequest::default()).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            status.message(),
+            "missing required field parent, missing required field book"
+        );
+    }
+}
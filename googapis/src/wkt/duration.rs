@@ -0,0 +1,289 @@
+use std::{convert::TryFrom, error, fmt};
+
+use prost_types::Duration;
+
+use super::timestamp::{parse_fraction, push_fraction};
+
+// The range documented on `google.protobuf.Duration`, approximately +-10,000 years.
+const MAX_SECONDS: i64 = 315_576_000_000;
+const MAX_NANOS: i32 = 999_999_999;
+
+/// An error returned when a [`Duration`] is out of range or cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DurationError {
+    /// The duration is out of the range documented on [`Duration`], or `nanos` is out of the
+    /// `-999,999,999..=999,999,999` range.
+    OutOfRange { seconds: i64, nanos: i32 },
+    /// `seconds` and `nanos` have different signs.
+    SignMismatch { seconds: i64, nanos: i32 },
+    /// The duration is negative and cannot be converted to a [`std::time::Duration`].
+    Negative { seconds: i64, nanos: i32 },
+    /// The string is not a duration in seconds with an `s` suffix.
+    Parse(String),
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationError::OutOfRange { seconds, nanos } => write!(
+                f,
+                "duration out of range: seconds = {}, nanos = {}",
+                seconds, nanos
+            ),
+            DurationError::SignMismatch { seconds, nanos } => write!(
+                f,
+                "seconds and nanos have different signs: seconds = {}, nanos = {}",
+                seconds, nanos
+            ),
+            DurationError::Negative { seconds, nanos } => write!(
+                f,
+                "negative duration: seconds = {}, nanos = {}",
+                seconds, nanos
+            ),
+            DurationError::Parse(s) => write!(f, "invalid duration: {:?}", s),
+        }
+    }
+}
+
+impl error::Error for DurationError {}
+
+/// Extension methods for [`Duration`].
+pub trait DurationExt: Sized {
+    /// Checks that the duration is in the range documented on [`Duration`] and that `seconds`
+    /// and `nanos` have the same sign.
+    fn validate(&self) -> Result<(), DurationError>;
+
+    /// Formats the duration as in the protobuf JSON mapping: seconds with 0, 3, 6 or 9
+    /// fractional digits and an `s` suffix, e.g. `-1.5s`.
+    fn to_json_string(&self) -> Result<String, DurationError>;
+
+    /// Parses seconds with up to 9 fractional digits and an `s` suffix, e.g. `0.000001s`.
+    fn parse_json_string(s: &str) -> Result<Self, DurationError>;
+
+    /// Converts to a [`std::time::Duration`], failing if the duration is negative.
+    fn to_std(&self) -> Result<std::time::Duration, DurationError>;
+
+    /// Converts from a [`std::time::Duration`], failing if it is out of range.
+    fn from_std(d: std::time::Duration) -> Result<Self, DurationError>;
+
+    /// Converts to a [`chrono::Duration`].
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::Duration, DurationError>;
+
+    /// Converts from a [`chrono::Duration`], failing if it is out of range.
+    #[cfg(feature = "chrono")]
+    fn from_chrono(d: chrono::Duration) -> Result<Self, DurationError>;
+}
+
+impl DurationExt for Duration {
+    fn validate(&self) -> Result<(), DurationError> {
+        if !(-MAX_SECONDS..=MAX_SECONDS).contains(&self.seconds)
+            || !(-MAX_NANOS..=MAX_NANOS).contains(&self.nanos)
+        {
+            return Err(DurationError::OutOfRange {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            });
+        }
+        if (self.seconds < 0 && self.nanos > 0) || (self.seconds > 0 && self.nanos < 0) {
+            return Err(DurationError::SignMismatch {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            });
+        }
+        Ok(())
+    }
+
+    fn to_json_string(&self) -> Result<String, DurationError> {
+        self.validate()?;
+
+        let mut s = String::new();
+        if self.seconds < 0 || self.nanos < 0 {
+            s.push('-');
+        }
+        s.push_str(&self.seconds.unsigned_abs().to_string());
+        push_fraction(&mut s, self.nanos.abs());
+        s.push('s');
+        Ok(s)
+    }
+
+    fn parse_json_string(s: &str) -> Result<Self, DurationError> {
+        let parse_error = || DurationError::Parse(s.to_owned());
+
+        let rest = s.strip_suffix('s').ok_or_else(parse_error)?;
+        let (negative, rest) = match rest.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (integer, fraction) = match rest.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (rest, None),
+        };
+        if integer.is_empty() || !integer.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(parse_error());
+        }
+        let seconds = integer.parse::<i64>().map_err(|_| parse_error())?;
+        let nanos = match fraction {
+            Some(f) => parse_fraction(f).ok_or_else(parse_error)?,
+            None => 0,
+        };
+
+        let d = if negative {
+            Duration {
+                seconds: -seconds,
+                nanos: -nanos,
+            }
+        } else {
+            Duration { seconds, nanos }
+        };
+        d.validate()?;
+        Ok(d)
+    }
+
+    fn to_std(&self) -> Result<std::time::Duration, DurationError> {
+        self.validate()?;
+        if self.seconds < 0 || self.nanos < 0 {
+            return Err(DurationError::Negative {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            });
+        }
+        Ok(std::time::Duration::new(
+            self.seconds as u64,
+            self.nanos as u32,
+        ))
+    }
+
+    fn from_std(d: std::time::Duration) -> Result<Self, DurationError> {
+        let duration = Duration {
+            seconds: i64::try_from(d.as_secs()).unwrap_or(i64::MAX),
+            nanos: d.subsec_nanos() as i32,
+        };
+        duration.validate()?;
+        Ok(duration)
+    }
+
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::Duration, DurationError> {
+        self.validate()?;
+        Ok(chrono::Duration::seconds(self.seconds)
+            + chrono::Duration::nanoseconds(self.nanos.into()))
+    }
+
+    #[cfg(feature = "chrono")]
+    fn from_chrono(d: chrono::Duration) -> Result<Self, DurationError> {
+        let duration = Duration {
+            seconds: d.num_seconds(),
+            nanos: d.subsec_nanos(),
+        };
+        duration.validate()?;
+        Ok(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(seconds: i64, nanos: i32) -> Duration {
+        Duration { seconds, nanos }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(d(-MAX_SECONDS, -MAX_NANOS).validate(), Ok(()));
+        assert_eq!(d(0, -1).validate(), Ok(()));
+        assert_eq!(
+            d(MAX_SECONDS + 1, 0).validate(),
+            Err(DurationError::OutOfRange {
+                seconds: MAX_SECONDS + 1,
+                nanos: 0
+            })
+        );
+        assert_eq!(
+            d(1, -1).validate(),
+            Err(DurationError::SignMismatch {
+                seconds: 1,
+                nanos: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_string() {
+        for (duration, s) in &[
+            (d(0, 0), "0s"),
+            (d(1, 500_000_000), "1.500s"),
+            (d(0, -1_000), "-0.000001s"),
+            (d(-3, -1), "-3.000000001s"),
+            (d(MAX_SECONDS, 0), "315576000000s"),
+        ] {
+            assert_eq!(
+                duration.to_json_string().as_deref(),
+                Ok(*s),
+                "{:?}",
+                duration
+            );
+            assert_eq!(
+                Duration::parse_json_string(s).as_ref(),
+                Ok(duration),
+                "{}",
+                s
+            );
+        }
+
+        assert_eq!(Duration::parse_json_string("1.5s"), Ok(d(1, 500_000_000)));
+        assert_eq!(
+            Duration::parse_json_string("315576000001s"),
+            Err(DurationError::OutOfRange {
+                seconds: 315_576_000_001,
+                nanos: 0
+            })
+        );
+        for s in &[
+            "",
+            "s",
+            "1",
+            "-s",
+            ".5s",
+            "1.s",
+            "+1s",
+            "1.0000000001s",
+            "1 s",
+            "1.5ms",
+        ] {
+            assert_eq!(
+                Duration::parse_json_string(s),
+                Err(DurationError::Parse(s.to_string())),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_std() {
+        assert_eq!(d(1, 5).to_std(), Ok(std::time::Duration::new(1, 5)));
+        assert_eq!(
+            d(0, -5).to_std(),
+            Err(DurationError::Negative {
+                seconds: 0,
+                nanos: -5
+            })
+        );
+        assert_eq!(
+            Duration::from_std(std::time::Duration::from_millis(1500)),
+            Ok(d(1, 500_000_000))
+        );
+        assert!(Duration::from_std(std::time::Duration::from_secs(u64::MAX)).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        let c = d(-1, -500_000_000).to_chrono().unwrap();
+        assert_eq!(c, chrono::Duration::milliseconds(-1500));
+        assert_eq!(Duration::from_chrono(c), Ok(d(-1, -500_000_000)));
+        assert!(Duration::from_chrono(chrono::Duration::weeks(1_000_000)).is_err());
+    }
+}
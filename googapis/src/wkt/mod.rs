@@ -0,0 +1,26 @@
+//! Helpers for the well-known types of [`prost_types`] used throughout Google APIs.
+//!
+//! [`TimestampExt`] and [`DurationExt`] validate, format and parse timestamps and durations as
+//! in the protobuf JSON mapping, and convert them from and to [`std::time`] and, with the
+//! `chrono` feature, [chrono](https://docs.rs/chrono) types.
+//!
+//! [`IntoValue`] and the [`struct_value!`](crate::struct_value) macro build [`Struct`] and
+//! [`Value`], and with the `serde_json` feature [`StructExt`] and [`ValueExt`] convert them from
+//! and to [`serde_json::Value`](https://docs.rs/serde_json).
+
+mod duration;
+mod timestamp;
+mod value;
+
+pub use prost_types::{Duration, ListValue, Struct, Timestamp, Value};
+
+// Used by the package extensions.
+#[allow(unused_imports)]
+pub(crate) use self::timestamp::{civil_from_days, days_from_civil};
+#[cfg(feature = "serde_json")]
+pub use self::value::{StructExt, ValueError, ValueExt};
+pub use self::{
+    duration::{DurationError, DurationExt},
+    timestamp::{TimestampError, TimestampExt},
+    value::IntoValue,
+};
@@ -0,0 +1,385 @@
+use std::{
+    error, fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use prost_types::Timestamp;
+
+// The range documented on `google.protobuf.Timestamp`, from 0001-01-01T00:00:00Z to
+// 9999-12-31T23:59:59.999999999Z.
+const MIN_SECONDS: i64 = -62_135_596_800;
+const MAX_SECONDS: i64 = 253_402_300_799;
+const MAX_NANOS: i32 = 999_999_999;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// An error returned when a [`Timestamp`] is out of range or cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampError {
+    /// The timestamp is out of the range documented on [`Timestamp`], or `nanos` is out of the
+    /// `0..=999,999,999` range.
+    OutOfRange { seconds: i64, nanos: i32 },
+    /// The string is not an RFC 3339 date time.
+    Parse(String),
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::OutOfRange { seconds, nanos } => write!(
+                f,
+                "timestamp out of range: seconds = {}, nanos = {}",
+                seconds, nanos
+            ),
+            TimestampError::Parse(s) => write!(f, "invalid RFC 3339 date time: {:?}", s),
+        }
+    }
+}
+
+impl error::Error for TimestampError {}
+
+/// Extension methods for [`Timestamp`].
+pub trait TimestampExt: Sized {
+    /// Returns the current time.
+    fn now() -> Self;
+
+    /// Checks that the timestamp is in the range documented on [`Timestamp`] and that `nanos` is
+    /// normalized.
+    fn validate(&self) -> Result<(), TimestampError>;
+
+    /// Formats the timestamp as in the protobuf JSON mapping: an RFC 3339 date time in UTC with
+    /// 0, 3, 6 or 9 fractional digits, e.g. `1972-01-01T10:00:20.021Z`.
+    fn to_rfc3339(&self) -> Result<String, TimestampError>;
+
+    /// Parses an RFC 3339 date time with any UTC offset and up to 9 fractional digits, e.g.
+    /// `1972-01-01T10:00:20.021-05:00`.
+    fn parse_rfc3339(s: &str) -> Result<Self, TimestampError>;
+
+    /// Converts to a [`SystemTime`], checking the range of the timestamp first.
+    fn to_system_time(&self) -> Result<SystemTime, TimestampError>;
+
+    /// Converts to a UTC date time, checking the range of the timestamp first.
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::DateTime<chrono::Utc>, TimestampError>;
+
+    /// Converts from a date time. A leap second is rounded down to the last nanosecond before it.
+    #[cfg(feature = "chrono")]
+    fn from_chrono<Tz: chrono::TimeZone>(t: &chrono::DateTime<Tz>) -> Self;
+}
+
+impl TimestampExt for Timestamp {
+    fn now() -> Self {
+        Timestamp::from(SystemTime::now())
+    }
+
+    fn validate(&self) -> Result<(), TimestampError> {
+        if !(MIN_SECONDS..=MAX_SECONDS).contains(&self.seconds)
+            || !(0..=MAX_NANOS).contains(&self.nanos)
+        {
+            return Err(TimestampError::OutOfRange {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            });
+        }
+        Ok(())
+    }
+
+    fn to_rfc3339(&self) -> Result<String, TimestampError> {
+        self.validate()?;
+
+        let days = self.seconds.div_euclid(SECONDS_PER_DAY);
+        let secs = self.seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        push_fraction(&mut s, self.nanos);
+        s.push('Z');
+        Ok(s)
+    }
+
+    fn parse_rfc3339(s: &str) -> Result<Self, TimestampError> {
+        let ts = parse(s).ok_or_else(|| TimestampError::Parse(s.to_owned()))?;
+        ts.validate()?;
+        Ok(ts)
+    }
+
+    fn to_system_time(&self) -> Result<SystemTime, TimestampError> {
+        self.validate()?;
+        let since_epoch = std::time::Duration::new(self.seconds.unsigned_abs(), 0);
+        let t = if self.seconds < 0 {
+            UNIX_EPOCH.checked_sub(since_epoch)
+        } else {
+            UNIX_EPOCH.checked_add(since_epoch)
+        };
+        t.and_then(|t| t.checked_add(std::time::Duration::from_nanos(self.nanos as u64)))
+            .ok_or(TimestampError::OutOfRange {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            })
+    }
+
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> Result<chrono::DateTime<chrono::Utc>, TimestampError> {
+        use chrono::TimeZone;
+
+        self.validate()?;
+        chrono::Utc
+            .timestamp_opt(self.seconds, self.nanos as u32)
+            .single()
+            .ok_or(TimestampError::OutOfRange {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            })
+    }
+
+    #[cfg(feature = "chrono")]
+    fn from_chrono<Tz: chrono::TimeZone>(t: &chrono::DateTime<Tz>) -> Self {
+        Timestamp {
+            seconds: t.timestamp(),
+            nanos: (t.timestamp_subsec_nanos() as i32).min(MAX_NANOS),
+        }
+    }
+}
+
+// Appends the fractional seconds with 0, 3, 6 or 9 digits.
+pub(super) fn push_fraction(s: &mut String, nanos: i32) {
+    if nanos == 0 {
+        return;
+    }
+    let digits = format!("{:09}", nanos);
+    let len = if nanos % 1_000_000 == 0 {
+        3
+    } else if nanos % 1_000 == 0 {
+        6
+    } else {
+        9
+    };
+    s.push('.');
+    s.push_str(&digits[..len]);
+}
+
+// Parses up to 9 fractional digits as nanoseconds.
+pub(super) fn parse_fraction(digits: &str) -> Option<i32> {
+    if digits.is_empty() || digits.len() > 9 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    format!("{:0<9}", digits).parse().ok()
+}
+
+// date-time = full-date "T" partial-time time-offset, see RFC 3339 section 5.6.
+fn parse(s: &str) -> Option<Timestamp> {
+    let number = |s: &str| -> Option<i64> {
+        if !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    };
+    let b = s.as_bytes();
+    if b.len() < 20
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !matches!(b[10], b'T' | b't')
+        || b[13] != b':'
+        || b[16] != b':'
+    {
+        return None;
+    }
+    let year = number(s.get(0..4)?)?;
+    let month = number(s.get(5..7)?)?;
+    let day = number(s.get(8..10)?)?;
+    let hour = number(s.get(11..13)?)?;
+    let minute = number(s.get(14..16)?)?;
+    let second = number(s.get(17..19)?)?;
+
+    let mut rest = s.get(19..)?;
+    let mut nanos = 0;
+    if let Some(r) = rest.strip_prefix('.') {
+        let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+        nanos = parse_fraction(&r[..end])?;
+        rest = &r[end..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z'] | [b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let hours = number(&rest[1..3])?;
+            let minutes = number(&rest[4..6])?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        // A leap second cannot be represented and is rejected.
+        || second > 59
+    {
+        return None;
+    }
+
+    let seconds =
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second
+            - offset;
+    Some(Timestamp { seconds, nanos })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar, see
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// The inverse of `days_from_civil`, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(seconds: i64, nanos: i32) -> Timestamp {
+        Timestamp { seconds, nanos }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(ts(MIN_SECONDS, 0).validate(), Ok(()));
+        assert_eq!(ts(MAX_SECONDS, MAX_NANOS).validate(), Ok(()));
+        assert_eq!(
+            ts(MAX_SECONDS + 1, 0).validate(),
+            Err(TimestampError::OutOfRange {
+                seconds: MAX_SECONDS + 1,
+                nanos: 0
+            })
+        );
+        assert_eq!(
+            ts(0, -1).validate(),
+            Err(TimestampError::OutOfRange {
+                seconds: 0,
+                nanos: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        for (t, s) in &[
+            (ts(0, 0), "1970-01-01T00:00:00Z"),
+            (ts(63_108_020, 21_000_000), "1972-01-01T10:00:20.021Z"),
+            (ts(-1, 999_999_000), "1969-12-31T23:59:59.999999Z"),
+            (ts(951_782_400, 1), "2000-02-29T00:00:00.000000001Z"),
+            (ts(MIN_SECONDS, 0), "0001-01-01T00:00:00Z"),
+            (ts(MAX_SECONDS, MAX_NANOS), "9999-12-31T23:59:59.999999999Z"),
+        ] {
+            assert_eq!(t.to_rfc3339().as_deref(), Ok(*s), "{:?}", t);
+            assert_eq!(Timestamp::parse_rfc3339(s).as_ref(), Ok(t), "{}", s);
+        }
+
+        assert_eq!(
+            Timestamp::parse_rfc3339("1972-01-01t05:00:20.5-05:00"),
+            Ok(ts(63_108_020, 500_000_000))
+        );
+        assert_eq!(
+            Timestamp::parse_rfc3339("0001-01-01T00:00:00+00:01"),
+            Err(TimestampError::OutOfRange {
+                seconds: MIN_SECONDS - 60,
+                nanos: 0
+            })
+        );
+        for s in &[
+            "",
+            "1970-01-01",
+            "1970-01-01T00:00:00",
+            "1970-01-01 00:00:00Z",
+            "1970-13-01T00:00:00Z",
+            "2021-02-29T00:00:00Z",
+            "1970-01-01T24:00:00Z",
+            "1970-01-01T23:59:60Z",
+            "1970-01-01T00:00:00.Z",
+            "1970-01-01T00:00:00.0123456789Z",
+            "1970-01-01T00:00:00+0100",
+            "1970-01-01T00:00:00+1:00",
+            "+970-01-01T00:00:00Z",
+        ] {
+            assert_eq!(
+                Timestamp::parse_rfc3339(s),
+                Err(TimestampError::Parse(s.to_string())),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(days_from_civil(2000, 2, 29)), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_system_time() {
+        let t = ts(-2, 500_000_000).to_system_time().unwrap();
+        assert_eq!(
+            UNIX_EPOCH.duration_since(t).unwrap(),
+            std::time::Duration::from_millis(1500)
+        );
+        assert_eq!(Timestamp::from(t), ts(-2, 500_000_000));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        let t = ts(63_108_020, 21_000_000);
+        let dt = t.to_chrono().unwrap();
+        assert_eq!(dt.to_rfc3339(), "1972-01-01T10:00:20.021+00:00");
+        assert_eq!(Timestamp::from_chrono(&dt), t);
+        assert_eq!(
+            Timestamp::from_chrono(
+                &dt.with_timezone(&chrono::FixedOffset::east_opt(3600).unwrap())
+            ),
+            t
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+
+use prost_types::{value::Kind, ListValue, NullValue, Struct, Value};
+
+/// A conversion into a dynamically typed [`Value`], used by the
+/// [`struct_value!`](crate::struct_value) macro.
+///
+/// Numbers are converted to `f64`, integers with a magnitude above 2^53 lose precision.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+fn value(kind: Kind) -> Value {
+    Value { kind: Some(kind) }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+/// Converts to a null value.
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        value(Kind::NullValue(NullValue::NullValue as i32))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        value(Kind::BoolValue(self))
+    }
+}
+
+macro_rules! impl_into_value_for_number {
+    ($($t: ty),*) => {
+        $(
+            impl IntoValue for $t {
+                fn into_value(self) -> Value {
+                    value(Kind::NumberValue(self as f64))
+                }
+            }
+        )*
+    };
+}
+
+impl_into_value_for_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        value(Kind::StringValue(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        value(Kind::StringValue(self.to_owned()))
+    }
+}
+
+impl IntoValue for Struct {
+    fn into_value(self) -> Value {
+        value(Kind::StructValue(self))
+    }
+}
+
+impl IntoValue for ListValue {
+    fn into_value(self) -> Value {
+        value(Kind::ListValue(self))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        ListValue {
+            values: self.into_iter().map(IntoValue::into_value).collect(),
+        }
+        .into_value()
+    }
+}
+
+impl<T: IntoValue> IntoValue for BTreeMap<String, T> {
+    fn into_value(self) -> Value {
+        Struct {
+            fields: self.into_iter().map(|(k, v)| (k, v.into_value())).collect(),
+        }
+        .into_value()
+    }
+}
+
+/// Converts `None` to a null value.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(v) => v.into_value(),
+            None => ().into_value(),
+        }
+    }
+}
+
+/// Builds a [`Struct`](crate::wkt::Struct) from JSON-like syntax.
+///
+/// Keys are string literals. Values are `null`, lists in `[]`, nested structs in `{}`, or any
+/// expression implementing [`IntoValue`](crate::wkt::IntoValue).
+///
+/// # Example
+/// ```
+/// # use googapis::struct_value;
+/// let name = "x";
+/// let params = struct_value! {
+///     "k": [1, name],
+///     "nested": { "enabled": true, "limit": null },
+///     "ratio": 1.0 / 3.0,
+/// };
+/// ```
+#[macro_export]
+macro_rules! struct_value {
+    ($($tt: tt)*) => {{
+        #[allow(unused_mut)]
+        let mut fields = ::std::collections::BTreeMap::new();
+        $crate::__struct_fields!(fields () () $($tt)*);
+        $crate::wkt::Struct { fields }
+    }};
+}
+
+// Munches `key: value` pairs separated by commas. The key and the tokens of the value read so
+// far are kept in the first two groups.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __struct_fields {
+    ($fields: ident () ()) => {};
+    ($fields: ident () () $key: literal : $($rest: tt)*) => {
+        $crate::__struct_fields!($fields ($key) () $($rest)*);
+    };
+    ($fields: ident ($key: literal) ($($value: tt)+) , $($rest: tt)*) => {
+        $fields.insert(::std::string::String::from($key), $crate::__value!($($value)+));
+        $crate::__struct_fields!($fields () () $($rest)*);
+    };
+    ($fields: ident ($key: literal) ($($value: tt)+)) => {
+        $fields.insert(::std::string::String::from($key), $crate::__value!($($value)+));
+    };
+    ($fields: ident ($key: literal) ($($value: tt)*) $next: tt $($rest: tt)*) => {
+        $crate::__struct_fields!($fields ($key) ($($value)* $next) $($rest)*);
+    };
+}
+
+// Munches values separated by commas. The tokens of the value read so far are kept in the first
+// group.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __list_values {
+    ($values: ident ()) => {};
+    ($values: ident ($($value: tt)+) , $($rest: tt)*) => {
+        $values.push($crate::__value!($($value)+));
+        $crate::__list_values!($values () $($rest)*);
+    };
+    ($values: ident ($($value: tt)+)) => {
+        $values.push($crate::__value!($($value)+));
+    };
+    ($values: ident ($($value: tt)*) $next: tt $($rest: tt)*) => {
+        $crate::__list_values!($values ($($value)* $next) $($rest)*);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __value {
+    (null) => {
+        $crate::wkt::IntoValue::into_value(())
+    };
+    ([$($tt: tt)*]) => {{
+        #[allow(unused_mut)]
+        let mut values = ::std::vec::Vec::<$crate::wkt::Value>::new();
+        $crate::__list_values!(values () $($tt)*);
+        $crate::wkt::IntoValue::into_value(values)
+    }};
+    ({$($tt: tt)*}) => {
+        $crate::wkt::IntoValue::into_value($crate::struct_value!($($tt)*))
+    };
+    ($($tt: tt)+) => {
+        $crate::wkt::IntoValue::into_value($($tt)+)
+    };
+}
+
+#[cfg(feature = "serde_json")]
+mod serde_json_impl {
+    use std::{error, fmt};
+
+    use super::{value, IntoValue, Kind, ListValue, Struct, Value};
+
+    // Integers up to 2^53 are exactly representable as `f64`.
+    const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+    /// An error returned when a [`Value`] cannot be converted to JSON.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ValueError {
+        /// The number is NaN or infinite.
+        NonFinite(f64),
+        /// The value has no kind.
+        MissingKind,
+    }
+
+    impl fmt::Display for ValueError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ValueError::NonFinite(n) => write!(f, "number is not finite: {}", n),
+                ValueError::MissingKind => f.write_str("value has no kind"),
+            }
+        }
+    }
+
+    impl error::Error for ValueError {}
+
+    /// Conversions of [`Value`] from and to JSON.
+    pub trait ValueExt: Sized {
+        /// Converts to JSON, writing integral numbers without a fractional part.
+        fn to_json(&self) -> Result<serde_json::Value, ValueError>;
+
+        /// Converts from JSON. Numbers are converted to `f64`.
+        fn from_json(json: serde_json::Value) -> Self;
+    }
+
+    /// Conversions of [`Struct`] from and to JSON objects.
+    pub trait StructExt: Sized {
+        fn to_json(&self) -> Result<serde_json::Map<String, serde_json::Value>, ValueError>;
+
+        fn from_json(json: serde_json::Map<String, serde_json::Value>) -> Self;
+    }
+
+    impl ValueExt for Value {
+        fn to_json(&self) -> Result<serde_json::Value, ValueError> {
+            Ok(match self.kind.as_ref().ok_or(ValueError::MissingKind)? {
+                Kind::NullValue(_) => serde_json::Value::Null,
+                Kind::NumberValue(n) => {
+                    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+                        serde_json::Value::from(*n as i64)
+                    } else {
+                        serde_json::Number::from_f64(*n)
+                            .map(serde_json::Value::Number)
+                            .ok_or(ValueError::NonFinite(*n))?
+                    }
+                }
+                Kind::StringValue(s) => serde_json::Value::String(s.clone()),
+                Kind::BoolValue(b) => serde_json::Value::Bool(*b),
+                Kind::StructValue(s) => serde_json::Value::Object(s.to_json()?),
+                Kind::ListValue(l) => serde_json::Value::Array(
+                    l.values
+                        .iter()
+                        .map(Value::to_json)
+                        .collect::<Result<_, _>>()?,
+                ),
+            })
+        }
+
+        fn from_json(json: serde_json::Value) -> Self {
+            json.into_value()
+        }
+    }
+
+    impl StructExt for Struct {
+        fn to_json(&self) -> Result<serde_json::Map<String, serde_json::Value>, ValueError> {
+            self.fields
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.to_json()?)))
+                .collect()
+        }
+
+        fn from_json(json: serde_json::Map<String, serde_json::Value>) -> Self {
+            Struct {
+                fields: json.into_iter().map(|(k, v)| (k, v.into_value())).collect(),
+            }
+        }
+    }
+
+    impl IntoValue for serde_json::Value {
+        fn into_value(self) -> Value {
+            match self {
+                serde_json::Value::Null => ().into_value(),
+                serde_json::Value::Bool(b) => b.into_value(),
+                // Without the `arbitrary_precision` feature of serde_json every number is an f64.
+                serde_json::Value::Number(n) => {
+                    value(Kind::NumberValue(n.as_f64().unwrap_or(f64::NAN)))
+                }
+                serde_json::Value::String(s) => s.into_value(),
+                serde_json::Value::Array(a) => ListValue {
+                    values: a.into_iter().map(IntoValue::into_value).collect(),
+                }
+                .into_value(),
+                serde_json::Value::Object(o) => Struct::from_json(o).into_value(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+pub use self::serde_json_impl::{StructExt, ValueError, ValueExt};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(n: f64) -> Value {
+        value(Kind::NumberValue(n))
+    }
+
+    fn string(s: &str) -> Value {
+        value(Kind::StringValue(s.to_owned()))
+    }
+
+    #[test]
+    fn test_struct_value() {
+        let name = "x";
+        let s = crate::struct_value! {
+            "k": [1, name, -2.5, null, []],
+            "nested": { "ok": true, "none": Option::<i32>::None },
+            "sum": 1 + 2,
+            "empty": {},
+        };
+
+        let mut nested = BTreeMap::new();
+        nested.insert("ok".to_owned(), true.into_value());
+        nested.insert("none".to_owned(), ().into_value());
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            "k".to_owned(),
+            vec![
+                number(1.0),
+                string("x"),
+                number(-2.5),
+                ().into_value(),
+                Vec::<Value>::new().into_value(),
+            ]
+            .into_value(),
+        );
+        fields.insert("nested".to_owned(), nested.into_value());
+        fields.insert("sum".to_owned(), number(3.0));
+        fields.insert("empty".to_owned(), Struct::default().into_value());
+        assert_eq!(s, Struct { fields });
+
+        assert_eq!(crate::struct_value! {}, Struct::default());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        let json = serde_json::json!({
+            "k": [1, "x", -2.5, null, [], true],
+            "nested": { "big": 1e300, "empty": {} },
+        });
+        let v = Value::from_json(json.clone());
+        assert_eq!(v.to_json(), Ok(json.clone()));
+
+        let object = json.as_object().unwrap().clone();
+        let s = Struct::from_json(object.clone());
+        assert_eq!(s.to_json(), Ok(object));
+        assert_eq!(s.into_value(), v);
+
+        assert!(matches!(number(f64::NAN).to_json(), Err(ValueError::NonFinite(n)) if n.is_nan()));
+        assert_eq!(
+            number(f64::INFINITY).to_json(),
+            Err(ValueError::NonFinite(f64::INFINITY))
+        );
+        assert_eq!(Value { kind: None }.to_json(), Err(ValueError::MissingKind));
+    }
+}
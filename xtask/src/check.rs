@@ -0,0 +1,350 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    gen::{self, Package},
+    patch,
+};
+
+// Features declared in the `[features]` table of a manifest, with the features they enable.
+// Optional dependencies declare an implicit feature of the same name.
+fn declared_features(manifest: &str) -> Vec<(String, Vec<String>)> {
+    let mut ret = Vec::new();
+    let mut in_features = false;
+    let mut in_dependencies = false;
+
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') && !line.starts_with("[\"") {
+            in_features = line == "[features]";
+            in_dependencies = line == "[dependencies]";
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if in_dependencies {
+            if let Some((name, value)) = line.split_once('=') {
+                if value.replace(' ', "").contains("optional=true") {
+                    ret.push((name.trim().to_owned(), Vec::new()));
+                }
+            }
+            continue;
+        }
+        if !in_features {
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once('=') {
+            let enables = value
+                .trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(|f| f.trim().trim_matches('"').to_owned())
+                .filter(|f| !f.is_empty())
+                .collect();
+            ret.push((name.trim().to_owned(), enables));
+        }
+    }
+
+    ret
+}
+
+// Features referenced by `feature = "..."` in a source file.
+fn referenced_features(src: &str) -> Vec<String> {
+    const FEATURE: &str = "feature = \"";
+
+    src.match_indices(FEATURE)
+        .map(|(i, _)| {
+            src[i + FEATURE.len()..]
+                .chars()
+                .take_while(|&c| c != '"')
+                .collect()
+        })
+        .collect()
+}
+
+fn find_rs(dir: impl AsRef<Path>) -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    for path in fs::read_dir(dir.as_ref())
+        .unwrap()
+        .map(Result::unwrap)
+        .map(|e| e.path())
+    {
+        if path.is_dir() {
+            ret.append(&mut find_rs(path));
+        } else if path.extension().filter(|ex| ex == &"rs").is_some() {
+            ret.push(path);
+        }
+    }
+    ret.sort();
+    ret
+}
+
+/// Checks that every feature referenced in the sources under `src_dir`, and every feature enabled
+/// by another feature, is declared in `manifest`.
+pub fn check_features(manifest: &str, src_dir: PathBuf) -> Vec<String> {
+    let declared = declared_features(manifest);
+    let names = declared
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let mut errors = Vec::new();
+
+    for (name, enables) in declared.iter() {
+        for f in enables.iter() {
+            // `dep:name` and `name/feature` refer to dependencies.
+            if !f.contains(':') && !f.contains('/') && !names.contains(f.as_str()) {
+                errors.push(format!(
+                    "feature `{}` enables undeclared feature `{}`",
+                    name, f
+                ));
+            }
+        }
+    }
+
+    for path in find_rs(src_dir) {
+        let src = fs::read_to_string(path.as_path()).unwrap();
+        let missing = referenced_features(&src)
+            .into_iter()
+            .filter(|f| !names.contains(f.as_str()))
+            .collect::<BTreeSet<_>>();
+        for f in missing {
+            errors.push(format!(
+                "{} references undeclared feature `{}`",
+                path.display(),
+                f
+            ));
+        }
+    }
+
+    errors
+}
+
+// The lines of the generated part of the `[features]` table between two headers.
+fn generated_section<'a>(manifest: &'a str, header: &str, next: &str) -> &'a str {
+    let section = manifest
+        .split(gen::FEATURES_BEGIN)
+        .nth(1)
+        .and_then(|s| s.split(gen::FEATURES_END).next())
+        .unwrap_or_default();
+    section
+        .split(header)
+        .nth(1)
+        .map_or("", |s| s.split(next).next().unwrap_or_default())
+}
+
+/// Checks that the umbrella features are the ones derived from the package features by
+/// [`gen::umbrella_features`], so that each enables exactly the latest stable version of the APIs
+/// under its package prefix.
+pub fn check_umbrellas(manifest: &str) -> Vec<String> {
+    let parse = |section: &str| -> BTreeMap<String, BTreeSet<String>> {
+        declared_features(&format!("[features]\n{}", section))
+            .into_iter()
+            .map(|(name, enables)| (name, enables.into_iter().collect()))
+            .collect()
+    };
+    let umbrellas = parse(generated_section(
+        manifest,
+        gen::UMBRELLAS_HEADER,
+        gen::ALIASES_HEADER,
+    ));
+    let aliases = parse(generated_section(
+        manifest,
+        gen::ALIASES_HEADER,
+        gen::PACKAGES_HEADER,
+    ));
+    let packages = parse(generated_section(
+        manifest,
+        gen::PACKAGES_HEADER,
+        gen::FEATURES_END,
+    ))
+    .into_keys()
+    .collect::<HashSet<_>>();
+    let pkgs = packages
+        .iter()
+        .map(|f| Package::from(f.replace('-', ".").as_str()))
+        .collect::<Vec<_>>();
+    let expected = gen::umbrella_features(&pkgs.iter().collect::<Vec<_>>());
+
+    let mut errors = Vec::new();
+    for (name, enables) in umbrellas.iter() {
+        match expected.get(name) {
+            None => errors.push(format!(
+                "umbrella feature `{}` is not the package prefix of a stable API",
+                name
+            )),
+            Some(features) if features != enables => errors.push(format!(
+                "umbrella feature `{}` enables {:?} instead of {:?}",
+                name, enables, features
+            )),
+            Some(_) => {}
+        }
+    }
+    for name in expected.keys().filter(|&n| !umbrellas.contains_key(n)) {
+        errors.push(format!("missing umbrella feature `{}`", name));
+    }
+
+    let expected = gen::umbrella_aliases(&expected, &packages);
+    for (alias, enables) in aliases.iter() {
+        match expected.get(alias) {
+            Some(umbrella) if enables.len() == 1 && enables.contains(umbrella) => {}
+            Some(umbrella) => errors.push(format!(
+                "umbrella alias `{}` enables {:?} instead of {:?}",
+                alias, enables, umbrella
+            )),
+            None => errors.push(format!(
+                "`{}` is not the alias of an umbrella feature",
+                alias
+            )),
+        }
+    }
+    for alias in expected.keys().filter(|&a| !aliases.contains_key(a)) {
+        errors.push(format!("missing umbrella alias `{}`", alias));
+    }
+    errors
+}
+
+/// Checks that the patches in `patch_dir` are valid and apply cleanly, in order, to the protos
+/// under `proto_root`. Applying is skipped when the googleapis submodule is not checked out.
+pub fn check_patches(patch_dir: &Path, proto_root: &Path, staged_root: &Path) -> Vec<String> {
+    let patches = match patch::find_patches(patch_dir) {
+        Ok(patches) => patches,
+        Err(e) => return vec![e.to_string()],
+    };
+    let checked_out = fs::read_dir(proto_root)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    if !checked_out {
+        return Vec::new();
+    }
+    match patch::stage(proto_root, staged_root, &patches) {
+        Ok(()) => Vec::new(),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "mechiru"
+
+[features]
+default = []
+# mechiru-storage-v2 = []
+storage = ["mechiru-storage-v1", "mechiru-type"]
+mechiru-storage-v1 = []
+
+[dependencies]
+prost = "0.8.0"
+serde = { version = "1.0", optional = true }
+"#;
+
+    #[test]
+    fn test_declared_features() {
+        assert_eq!(
+            declared_features(MANIFEST),
+            vec![
+                ("default".into(), vec![]),
+                (
+                    "storage".into(),
+                    vec!["mechiru-storage-v1".into(), "mechiru-type".into()]
+                ),
+                ("mechiru-storage-v1".into(), vec![]),
+                ("serde".into(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_umbrellas() {
+        let manifest = |umbrellas: &str, aliases: &str| {
+            format!(
+                "[features]\ndefault = []\n{}\n{}\n{}\n\n{}\n{}\n\n{}\n{}\n{}\n",
+                gen::FEATURES_BEGIN,
+                gen::UMBRELLAS_HEADER,
+                umbrellas,
+                gen::ALIASES_HEADER,
+                aliases,
+                gen::PACKAGES_HEADER,
+                "google-ads-v1 = []\ngoogle-ads-admob-v1 = []\ngoogle-ads-admob-v2beta = []",
+                gen::FEATURES_END
+            )
+        };
+        let valid = r#"google-ads = ["google-ads-admob-v1", "google-ads-v1"]
+google-ads-admob = ["google-ads-admob-v1"]"#;
+        let aliases = r#"ads = ["google-ads"]
+ads-admob = ["google-ads-admob"]"#;
+        assert!(check_umbrellas(&manifest(valid, aliases)).is_empty());
+
+        let invalid = r#"google-ads = ["google-ads-v1"]
+google = ["google-ads-v1"]"#;
+        let invalid_aliases = r#"ads = ["google-ads-admob"]
+admob = ["google-ads-admob"]"#;
+        assert_eq!(
+            check_umbrellas(&manifest(invalid, invalid_aliases)),
+            vec![
+                "umbrella feature `google` is not the package prefix of a stable API".to_owned(),
+                "umbrella feature `google-ads` enables {\"google-ads-v1\"} instead of {\"google-ads-admob-v1\", \"google-ads-v1\"}".into(),
+                "missing umbrella feature `google-ads-admob`".into(),
+                "`admob` is not the alias of an umbrella feature".into(),
+                "umbrella alias `ads` enables {\"google-ads-admob\"} instead of \"google-ads\"".into(),
+                "missing umbrella alias `ads-admob`".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_patches() {
+        let root = std::env::temp_dir().join(format!("xtask-check-{}", std::process::id()));
+        let (patches, protos, staged) = (
+            root.join("patches"),
+            root.join("googleapis"),
+            root.join("staged"),
+        );
+        fs::create_dir_all(&patches).unwrap();
+        fs::create_dir_all(&protos).unwrap();
+        fs::write(
+            patches.join("0001-fix.patch"),
+            "--- a/mechiru/a.proto\n+++ b/mechiru/a.proto\n@@ -1 +1 @@\n-message A {}\n+message A { }\n\nbase-commit: 2f9af297c84c55c8b871ba4495e01ade42476c92\n",
+        )
+        .unwrap();
+
+        // The submodule is not checked out.
+        assert!(check_patches(&patches, &protos, &staged).is_empty());
+
+        fs::create_dir(protos.join("mechiru")).unwrap();
+        fs::write(protos.join("mechiru/a.proto"), "message B {}\n").unwrap();
+        assert_eq!(
+            check_patches(&patches, &protos, &staged),
+            vec!["0001-fix.patch: hunk #1 of mechiru/a.proto does not apply".to_owned()]
+        );
+        fs::write(protos.join("mechiru/a.proto"), "message A {}\n").unwrap();
+        assert!(check_patches(&patches, &protos, &staged).is_empty());
+
+        fs::write(patches.join("fix.patch"), "").unwrap();
+        assert_eq!(
+            check_patches(&patches, &protos, &staged),
+            vec!["fix.patch: expected a `NNNN-<name>.patch` file name".to_owned()]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_referenced_features() {
+        assert_eq!(
+            referenced_features(
+                r#"#[cfg(any(feature = "mechiru-storage-v1", feature = "mechiru-storage-v2",))]
+include_proto!("mechiru.storage.v1");"#
+            ),
+            vec!["mechiru-storage-v1".to_owned(), "mechiru-storage-v2".into()]
+        );
+    }
+}
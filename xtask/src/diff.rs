@@ -0,0 +1,589 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+};
+use serde_json::json;
+
+// The API surface of the generated code: what users of `googapis` can name and depend on.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Surface {
+    packages: BTreeMap<String, Package>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Package {
+    // The features that include the package.
+    features: BTreeSet<String>,
+    // Nested messages and enums are named relative to the package, e.g. `Outer.Inner`.
+    messages: BTreeMap<String, BTreeMap<String, Field>>,
+    enums: BTreeMap<String, BTreeMap<String, i32>>,
+    services: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    number: i32,
+    // e.g. `optional string`, `repeated .google.pubsub.v1.Topic` or `map<string, string>`.
+    ty: String,
+}
+
+impl Surface {
+    /// Builds the surface from the features of each package and the descriptors of its files.
+    pub fn new(features: BTreeMap<String, BTreeSet<String>>, set: &FileDescriptorSet) -> Self {
+        let mut packages = features
+            .into_iter()
+            .map(|(name, features)| {
+                let pkg = Package {
+                    features,
+                    ..Default::default()
+                };
+                (name, pkg)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        for file in set.file.iter() {
+            if let Some(pkg) = packages.get_mut(file.package()) {
+                for msg in file.message_type.iter() {
+                    add_message(pkg, "", msg);
+                }
+                for e in file.enum_type.iter() {
+                    add_enum(pkg, "", e);
+                }
+                for svc in file.service.iter() {
+                    let rpcs = svc
+                        .method
+                        .iter()
+                        .map(|m| {
+                            let signature = format!(
+                                "({}{}) returns ({}{})",
+                                if m.client_streaming() { "stream " } else { "" },
+                                m.input_type(),
+                                if m.server_streaming() { "stream " } else { "" },
+                                m.output_type(),
+                            );
+                            (m.name().to_owned(), signature)
+                        })
+                        .collect();
+                    pkg.services.insert(svc.name().to_owned(), rpcs);
+                }
+            }
+        }
+
+        Self { packages }
+    }
+}
+
+fn add_message(pkg: &mut Package, prefix: &str, msg: &DescriptorProto) {
+    let name = format!("{}{}", prefix, msg.name());
+
+    // Map fields are repeated fields of a generated `*Entry` message.
+    let entries = msg
+        .nested_type
+        .iter()
+        .filter(|m| {
+            m.options
+                .as_ref()
+                .and_then(|o| o.map_entry)
+                .unwrap_or(false)
+        })
+        .map(|m| (m.name(), m))
+        .collect::<BTreeMap<_, _>>();
+
+    let fields = msg
+        .field
+        .iter()
+        .map(|f| {
+            let entry = f
+                .type_name()
+                .rsplit('.')
+                .next()
+                .and_then(|n| entries.get(n))
+                .filter(|_| f.label() == Label::Repeated);
+            let ty = match entry {
+                Some(entry) => format!(
+                    "map<{}, {}>",
+                    field_type(&entry.field[0]),
+                    field_type(&entry.field[1])
+                ),
+                None if f.label() == Label::Repeated => format!("repeated {}", field_type(f)),
+                None if f.proto3_optional() => format!("optional {}", field_type(f)),
+                None => field_type(f),
+            };
+            let field = Field {
+                number: f.number(),
+                ty,
+            };
+            (f.name().to_owned(), field)
+        })
+        .collect();
+    pkg.messages.insert(name.clone(), fields);
+
+    let prefix = format!("{}.", name);
+    for nested in msg.nested_type.iter() {
+        if !entries.contains_key(nested.name()) {
+            add_message(pkg, &prefix, nested);
+        }
+    }
+    for e in msg.enum_type.iter() {
+        add_enum(pkg, &prefix, e);
+    }
+}
+
+fn add_enum(pkg: &mut Package, prefix: &str, e: &EnumDescriptorProto) {
+    let values = e
+        .value
+        .iter()
+        .map(|v| (v.name().to_owned(), v.number()))
+        .collect();
+    pkg.enums.insert(format!("{}{}", prefix, e.name()), values);
+}
+
+fn field_type(f: &FieldDescriptorProto) -> String {
+    match f.r#type() {
+        Type::Message | Type::Enum | Type::Group => f.type_name().to_owned(),
+        Type::Double => "double".into(),
+        Type::Float => "float".into(),
+        Type::Int64 => "int64".into(),
+        Type::Uint64 => "uint64".into(),
+        Type::Int32 => "int32".into(),
+        Type::Fixed64 => "fixed64".into(),
+        Type::Fixed32 => "fixed32".into(),
+        Type::Bool => "bool".into(),
+        Type::String => "string".into(),
+        Type::Bytes => "bytes".into(),
+        Type::Uint32 => "uint32".into(),
+        Type::Sfixed32 => "sfixed32".into(),
+        Type::Sfixed64 => "sfixed64".into(),
+        Type::Sint32 => "sint32".into(),
+        Type::Sint64 => "sint64".into(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Added => "added",
+            Kind::Removed => "removed",
+            Kind::Changed => "changed",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    package: String,
+    kind: Kind,
+    // package, feature, message, field, enum, enum value, service or rpc
+    item: &'static str,
+    // The name of the item relative to the package, e.g. `Topic.labels`.
+    name: String,
+    // What changed, e.g. `int32 -> int64`.
+    detail: Option<String>,
+    breaking: bool,
+}
+
+impl Change {
+    fn new(package: &str, kind: Kind, item: &'static str, name: impl Into<String>) -> Self {
+        Self {
+            package: package.to_owned(),
+            kind,
+            item,
+            name: name.into(),
+            detail: None,
+            // Removing anything breaks the code using it.
+            breaking: kind == Kind::Removed,
+        }
+    }
+
+    fn changed(package: &str, item: &'static str, name: impl Into<String>, detail: String) -> Self {
+        Self {
+            detail: Some(detail),
+            breaking: true,
+            ..Self::new(package, Kind::Changed, item, name)
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} `{}`", self.kind, self.item, self.name)?;
+        if let Some(detail) = self.detail.as_ref() {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+// Calls `f` with the added, removed and kept keys of two maps.
+fn diff_keys<'a, V>(
+    old: &'a BTreeMap<String, V>,
+    new: &'a BTreeMap<String, V>,
+    mut f: impl FnMut(Kind, &'a str, Option<&'a V>, Option<&'a V>),
+) {
+    let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    for key in keys {
+        match (old.get(key), new.get(key)) {
+            (None, new @ Some(_)) => f(Kind::Added, key, None, new),
+            (old @ Some(_), None) => f(Kind::Removed, key, old, None),
+            (old, new) => f(Kind::Changed, key, old, new),
+        }
+    }
+}
+
+fn diff_package(name: &str, old: &Package, new: &Package, changes: &mut Vec<Change>) {
+    for f in new.features.difference(&old.features) {
+        changes.push(Change::new(name, Kind::Added, "feature", f.as_str()));
+    }
+    for f in old.features.difference(&new.features) {
+        changes.push(Change::new(name, Kind::Removed, "feature", f.as_str()));
+    }
+
+    diff_keys(&old.messages, &new.messages, |kind, msg, old, new| {
+        if kind != Kind::Changed {
+            changes.push(Change::new(name, kind, "message", msg));
+            return;
+        }
+        diff_keys(old.unwrap(), new.unwrap(), |kind, field, old, new| {
+            let path = format!("{}.{}", msg, field);
+            match (kind, old, new) {
+                (Kind::Changed, Some(old), Some(new)) if old != new => {
+                    let mut detail = Vec::new();
+                    if old.ty != new.ty {
+                        detail.push(format!("`{}` -> `{}`", old.ty, new.ty));
+                    }
+                    if old.number != new.number {
+                        detail.push(format!("number {} -> {}", old.number, new.number));
+                    }
+                    changes.push(Change::changed(name, "field", path, detail.join(", ")));
+                }
+                (Kind::Changed, _, _) => {}
+                (kind, _, _) => changes.push(Change::new(name, kind, "field", path)),
+            }
+        });
+    });
+
+    diff_keys(&old.enums, &new.enums, |kind, e, old, new| {
+        if kind != Kind::Changed {
+            changes.push(Change::new(name, kind, "enum", e));
+            return;
+        }
+        diff_keys(old.unwrap(), new.unwrap(), |kind, value, old, new| {
+            let path = format!("{}.{}", e, value);
+            match (kind, old, new) {
+                (Kind::Changed, Some(old), Some(new)) if old != new => {
+                    let detail = format!("{} -> {}", old, new);
+                    changes.push(Change::changed(name, "enum value", path, detail));
+                }
+                (Kind::Changed, _, _) => {}
+                (kind, _, _) => changes.push(Change::new(name, kind, "enum value", path)),
+            }
+        });
+    });
+
+    diff_keys(&old.services, &new.services, |kind, svc, old, new| {
+        if kind != Kind::Changed {
+            changes.push(Change::new(name, kind, "service", svc));
+            return;
+        }
+        diff_keys(old.unwrap(), new.unwrap(), |kind, rpc, old, new| {
+            let path = format!("{}.{}", svc, rpc);
+            match (kind, old, new) {
+                (Kind::Changed, Some(old), Some(new)) if old != new => {
+                    let detail = format!("`{}` -> `{}`", old, new);
+                    changes.push(Change::changed(name, "rpc", path, detail));
+                }
+                (Kind::Changed, _, _) => {}
+                (kind, _, _) => changes.push(Change::new(name, kind, "rpc", path)),
+            }
+        });
+    });
+}
+
+pub fn diff(old: &Surface, new: &Surface) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_keys(&old.packages, &new.packages, |kind, name, old, new| {
+        if kind == Kind::Changed {
+            diff_package(name, old.unwrap(), new.unwrap(), &mut changes);
+        } else {
+            changes.push(Change::new(name, kind, "package", name));
+        }
+    });
+    changes
+}
+
+pub fn to_markdown(old_rev: &str, new_rev: &str, changes: &[Change]) -> String {
+    let mut ret = format!("# API changes `{}`..`{}`\n\n", old_rev, new_rev);
+    if changes.is_empty() {
+        ret.push_str("No changes.\n");
+        return ret;
+    }
+
+    let breaking = changes.iter().filter(|c| c.breaking).collect::<Vec<_>>();
+    ret.push_str(&format!(
+        "{} changes, {} breaking.\n",
+        changes.len(),
+        breaking.len()
+    ));
+
+    if !breaking.is_empty() {
+        ret.push_str("\n## Breaking changes\n\n");
+        for c in breaking {
+            ret.push_str(&format!("- `{}`: {}\n", c.package, c));
+        }
+    }
+
+    let mut packages = BTreeMap::<&str, Vec<&Change>>::new();
+    for c in changes {
+        packages.entry(&c.package).or_default().push(c);
+    }
+    for (package, changes) in packages {
+        ret.push_str(&format!("\n## `{}`\n\n", package));
+        for c in changes {
+            let mark = if c.breaking { " **(breaking)**" } else { "" };
+            ret.push_str(&format!("- {}{}\n", c, mark));
+        }
+    }
+    ret
+}
+
+pub fn to_json(old_rev: &str, new_rev: &str, changes: &[Change]) -> String {
+    let changes = changes
+        .iter()
+        .map(|c| {
+            json!({
+                "package": c.package,
+                "kind": c.kind.to_string(),
+                "item": c.item,
+                "name": c.name,
+                "detail": c.detail,
+                "breaking": c.breaking,
+            })
+        })
+        .collect::<Vec<_>>();
+    let report = json!({
+        "old": old_rev,
+        "new": new_rev,
+        "changes": changes,
+    });
+    serde_json::to_string_pretty(&report).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{
+        EnumValueDescriptorProto, FileDescriptorProto, MessageOptions, MethodDescriptorProto,
+        ServiceDescriptorProto,
+    };
+
+    fn field(
+        name: &str,
+        number: i32,
+        ty: Type,
+        type_name: &str,
+        label: Label,
+    ) -> FieldDescriptorProto {
+        let mut f = FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            type_name: Some(type_name.into()).filter(|n: &String| !n.is_empty()),
+            ..Default::default()
+        };
+        f.set_type(ty);
+        f.set_label(label);
+        f
+    }
+
+    fn surface(topic_name: Type, streaming: bool, extra_value: bool) -> Surface {
+        let labels = DescriptorProto {
+            name: Some("LabelsEntry".into()),
+            field: vec![
+                field("key", 1, Type::String, "", Label::Optional),
+                field("value", 2, Type::String, "", Label::Optional),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let topic = DescriptorProto {
+            name: Some("Topic".into()),
+            field: vec![
+                field("name", 1, topic_name, "", Label::Optional),
+                field(
+                    "labels",
+                    2,
+                    Type::Message,
+                    ".mechiru.pubsub.v1.Topic.LabelsEntry",
+                    Label::Repeated,
+                ),
+            ],
+            nested_type: vec![labels],
+            ..Default::default()
+        };
+        let mut values = vec![EnumValueDescriptorProto {
+            name: Some("STATE_UNSPECIFIED".into()),
+            number: Some(0),
+            ..Default::default()
+        }];
+        if extra_value {
+            values.push(EnumValueDescriptorProto {
+                name: Some("ACTIVE".into()),
+                number: Some(1),
+                ..Default::default()
+            });
+        }
+        let state = EnumDescriptorProto {
+            name: Some("State".into()),
+            value: values,
+            ..Default::default()
+        };
+        let publisher = ServiceDescriptorProto {
+            name: Some("Publisher".into()),
+            method: vec![MethodDescriptorProto {
+                name: Some("CreateTopic".into()),
+                input_type: Some(".mechiru.pubsub.v1.Topic".into()),
+                output_type: Some(".mechiru.pubsub.v1.Topic".into()),
+                server_streaming: Some(streaming),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("mechiru/pubsub/v1/pubsub.proto".into()),
+                package: Some("mechiru.pubsub.v1".into()),
+                message_type: vec![topic],
+                enum_type: vec![state],
+                service: vec![publisher],
+                ..Default::default()
+            }],
+        };
+
+        let mut features = BTreeMap::new();
+        features.insert(
+            "mechiru.pubsub.v1".to_owned(),
+            vec!["mechiru-pubsub-v1".to_owned()].into_iter().collect(),
+        );
+        Surface::new(features, &set)
+    }
+
+    #[test]
+    fn test_surface() {
+        let surface = surface(Type::String, false, false);
+        let pkg = &surface.packages["mechiru.pubsub.v1"];
+        assert_eq!(
+            pkg.messages["Topic"]["labels"],
+            Field {
+                number: 2,
+                ty: "map<string, string>".into(),
+            }
+        );
+        assert!(!pkg.messages.contains_key("Topic.LabelsEntry"));
+        assert_eq!(
+            pkg.services["Publisher"]["CreateTopic"],
+            "(.mechiru.pubsub.v1.Topic) returns (.mechiru.pubsub.v1.Topic)"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = surface(Type::String, false, false);
+        let mut new = surface(Type::Bytes, true, true);
+        new.packages
+            .insert("mechiru.pubsub.v2".into(), Default::default());
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "changed field `Topic.name`: `string` -> `bytes`",
+                "added enum value `State.ACTIVE`",
+                "changed rpc `Publisher.CreateTopic`: `(.mechiru.pubsub.v1.Topic) returns (.mechiru.pubsub.v1.Topic)` -> `(.mechiru.pubsub.v1.Topic) returns (stream .mechiru.pubsub.v1.Topic)`",
+                "added package `mechiru.pubsub.v2`",
+            ]
+        );
+        assert_eq!(
+            changes.iter().map(|c| c.breaking).collect::<Vec<_>>(),
+            vec![true, false, true, false]
+        );
+
+        let changes = diff(&new, &old);
+        assert_eq!(
+            changes
+                .iter()
+                .filter(|c| c.kind == Kind::Removed)
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "removed enum value `State.ACTIVE`",
+                "removed package `mechiru.pubsub.v2`"
+            ]
+        );
+        assert!(changes.iter().all(|c| c.breaking));
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let old = surface(Type::String, false, false);
+        let new = surface(Type::Bytes, false, true);
+        assert_eq!(
+            to_markdown("v1", "v2", &diff(&old, &new)),
+            r#"# API changes `v1`..`v2`
+
+2 changes, 1 breaking.
+
+## Breaking changes
+
+- `mechiru.pubsub.v1`: changed field `Topic.name`: `string` -> `bytes`
+
+## `mechiru.pubsub.v1`
+
+- changed field `Topic.name`: `string` -> `bytes` **(breaking)**
+- added enum value `State.ACTIVE`
+"#
+        );
+        assert_eq!(
+            to_markdown("v1", "v1", &[]),
+            "# API changes `v1`..`v1`\n\nNo changes.\n"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let old = surface(Type::String, false, false);
+        let new = surface(Type::String, false, true);
+        let json =
+            serde_json::from_str::<serde_json::Value>(&to_json("v1", "v2", &diff(&old, &new)))
+                .unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "old": "v1",
+                "new": "v2",
+                "changes": [{
+                    "package": "mechiru.pubsub.v1",
+                    "kind": "added",
+                    "item": "enum value",
+                    "name": "State.ACTIVE",
+                    "detail": null,
+                    "breaking": false,
+                }],
+            })
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fmt, fs,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, fs, io,
     path::{Path, PathBuf},
 };
 
+use crate::proto;
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct Package {
     raw: String,
@@ -52,9 +54,26 @@ impl Package {
         }
     }
 
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn escaped(&self) -> &str {
+        &self.escaped
+    }
+
+    pub fn escaped_vec(&self) -> &[String] {
+        &self.escaped_vec
+    }
+
+    // The directory of the hand-written code for the package, relative to `googapis/src/ext`.
+    pub fn ext_path(&self) -> String {
+        self.raw.replace('.', "/")
+    }
+
     // https://doc.rust-lang.org/cargo/reference/features.html#features
     // crates.io requires feature names to only contain ASCII letters, digits, _, or -.
-    fn feature_name(&self) -> String {
+    pub fn feature_name(&self) -> String {
         self.raw.split('.').collect::<Vec<_>>().join("-")
     }
 }
@@ -63,13 +82,32 @@ impl Package {
 pub struct Proto {
     path: PathBuf,
     package: Package,
-    imports: Vec<Proto>,
+    imports: Vec<PathBuf>,
+}
+
+impl Proto {
+    pub fn package(&self) -> &Package {
+        &self.package
+    }
+}
+
+#[cfg(test)]
+impl Proto {
+    pub fn new(path: &str, package: &str, imports: &[&str]) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            package: package.into(),
+            imports: imports.iter().map(PathBuf::from).collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Module {
     package: Package,
     include: bool,
+    // Whether hand-written code for the package exists in `googapis/src/ext`.
+    extended: bool,
     imported_by: HashSet<Package>,
     children: HashMap<String, Module>,
 }
@@ -79,6 +117,7 @@ impl Module {
         Self {
             package,
             include: false,
+            extended: false,
             imported_by: HashSet::new(),
             children: HashMap::new(),
         }
@@ -87,22 +126,33 @@ impl Module {
 
 impl Module {
     fn gen_code(&self) -> String {
+        let mut attr = self
+            .imported_by
+            .iter()
+            .map(|p| p.feature_name())
+            .collect::<Vec<_>>();
+        attr.sort();
+
         let include = if self.include {
-            let mut attr = self
-                .imported_by
-                .iter()
-                .map(|p| p.feature_name())
-                .collect::<Vec<_>>();
-            attr.sort();
             let attr = attr
                 .into_iter()
                 .map(|f| format!(r#"feature = "{}","#, f))
                 .collect::<String>();
             let attr = format!("#[cfg(any({}))]", attr);
+            let ext = if self.extended {
+                format!(
+                    "{attr}\ninclude_ext!(\"{path}\");\n",
+                    attr = attr,
+                    path = self.package.ext_path(),
+                )
+            } else {
+                String::new()
+            };
             format!(
-                "{attr}\ninclude_proto!(\"{package}\");\n",
+                "{attr}\ninclude_proto!(\"{package}\");\n{ext}",
                 attr = attr,
                 package = self.package.escaped,
+                ext = ext,
             )
         } else {
             String::new()
@@ -133,6 +183,42 @@ impl Module {
 pub struct RootModule(HashMap<String, Module>);
 
 impl RootModule {
+    /// Returns the features that include each generated package.
+    pub fn features(&self) -> BTreeMap<String, BTreeSet<String>> {
+        fn rec(module: &Module, map: &mut BTreeMap<String, BTreeSet<String>>) {
+            if module.include {
+                let features = module.imported_by.iter().map(|p| p.feature_name());
+                map.insert(module.package.raw.clone(), features.collect());
+            }
+            for child in module.children.values() {
+                rec(child, map);
+            }
+        }
+
+        let mut map = BTreeMap::new();
+        for module in self.0.values() {
+            rec(module, &mut map);
+        }
+        map
+    }
+
+    /// Marks the packages that have hand-written code in `dir`, i.e. a `<package path>/mod.rs`
+    /// file such as `google/type/mod.rs` for `google.type`.
+    pub fn find_extensions(&mut self, dir: &Path) {
+        fn rec(module: &mut Module, dir: &Path) {
+            if module.include {
+                module.extended = dir.join(module.package.ext_path()).join("mod.rs").is_file();
+            }
+            for child in module.children.values_mut() {
+                rec(child, dir);
+            }
+        }
+
+        for module in self.0.values_mut() {
+            rec(module, dir);
+        }
+    }
+
     pub fn gen_code(&self) -> String {
         let mut vec = self.0.iter().collect::<Vec<_>>();
         vec.sort_by_key(|v| v.0);
@@ -208,163 +294,342 @@ fn escape(s: &str) -> &str {
     }
 }
 
-// https://developers.google.com/protocol-buffers/docs/reference/proto3-spec#package
-// package = "package" fullIdent ";"
-fn parse_package(line: &str) -> Option<Package> {
-    const PACKAGE: &str = "package";
-
-    if line.starts_with(PACKAGE) {
-        let package = line
-            .chars()
-            .skip(PACKAGE.len())
-            .take_while(|&c| c != ';')
-            .collect::<String>()
-            .trim()
-            .to_owned();
-        Some(package.as_str().into())
-    } else {
-        None
-    }
-}
-
-// https://developers.google.com/protocol-buffers/docs/reference/proto3-spec#import_statement
-// import = "import" [ "weak" | "public" ] strLit ";"
-fn parse_import(line: &str) -> Option<String> {
-    const IMPORT: &str = "import";
-
-    if line.starts_with(IMPORT) {
-        let import = line
-            .chars()
-            .skip(IMPORT.len())
-            .skip_while(|&c| c != '\'' && c != '"')
-            .skip(1)
-            .take_while(|&c| c != '\'' && c != '"') // TODO: Should I check to see if it's the same quart?
-            .collect::<String>() // TODO: Should I check to see if the following letter is semicolon?
-            .trim()
-            .to_owned();
-        Some(import)
-    } else {
-        None
-    }
-}
-
-pub fn find_proto(root: PathBuf) -> Vec<Proto> {
-    let mut cache = HashMap::new();
-    find_proto_rec(root.clone(), root, &mut cache)
-}
-
-fn find_proto_rec(
-    root: PathBuf,
-    dir: impl AsRef<Path>,
-    cache: &mut HashMap<PathBuf, Proto>,
-) -> Vec<Proto> {
-    let mut ret = Vec::new();
-
-    let cur = fs::read_dir(dir.as_ref())
-        .unwrap()
-        .map(Result::unwrap)
-        .map(|dir| dir.path())
-        .map(|path| (path.metadata().unwrap(), path))
-        .collect::<Vec<_>>();
+// TODO: There is a syntax error in google.ads.googleads.v5 and google.ads.googleads.v6.
+// See https://github.com/googleapis/googleapis/pull/622 and https://github.com/mechiru/googapis/pull/9.
+// Remove these once the fix is added to `xtask/patches`.
+fn is_excluded(path: &Path) -> bool {
+    let path = path.to_str().unwrap();
+    path.contains("google/ads/googleads/v5") || path.contains("google/ads/googleads/v6")
+}
 
-    let iter = cur
-        .clone()
-        .into_iter()
-        .filter(|(meta, _)| meta.is_file())
-        .filter(|(_, path)| path.extension().filter(|ex| ex == &"proto").is_some());
-    for (_, path) in iter {
-        let proto = if let Some(proto) = cache.get(&path) {
-            proto.clone()
-        } else {
-            let prot = proto_rec(root.clone(), path.clone(), cache);
-            cache.insert(path, prot.clone());
-            prot
-        };
-        ret.push(proto);
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, proto::ParseError),
+    MissingPackage(PathBuf),
+    MissingImport(PathBuf, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Parse(path, e) => write!(f, "{}:{}", path.display(), e),
+            Error::MissingPackage(path) => {
+                write!(f, "{}: missing package statement", path.display())
+            }
+            Error::MissingImport(path, import) => {
+                write!(f, "{}: imported file not found: {}", path.display(), import)
+            }
+        }
     }
+}
+
+impl std::error::Error for Error {}
+
+pub fn find_proto(root: PathBuf) -> Result<Vec<Proto>, Error> {
+    let mut paths = Vec::new();
+    find_proto_rec(root.as_path(), &mut paths)?;
+    paths.sort();
+
+    let protos = paths
+        .into_iter()
+        .filter(|p| !is_excluded(p))
+        .map(|p| read_proto(root.as_path(), p))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let iter = cur.into_iter().filter(|(meta, _)| meta.is_dir());
-    for (_, path) in iter {
-        let mut protos = find_proto_rec(root.clone(), path, cache);
-        ret.append(&mut protos);
+    let found = protos.iter().map(|p| &p.path).collect::<HashSet<_>>();
+    for proto in protos.iter() {
+        if let Some(import) = proto.imports.iter().find(|i| !found.contains(i)) {
+            let import = import.strip_prefix(root.as_path()).unwrap_or(import);
+            return Err(Error::MissingImport(
+                proto.path.clone(),
+                import.display().to_string(),
+            ));
+        }
     }
 
-    ret
+    Ok(protos)
 }
 
-fn proto_rec(root: PathBuf, path: PathBuf, map: &mut HashMap<PathBuf, Proto>) -> Proto {
-    let mut package = None;
-    let mut imports = Vec::new();
-
-    for line in fs::read_to_string(path.as_path()).unwrap().lines() {
-        if let Some(pkg) = parse_package(line) {
-            package = Some(pkg);
-        } else if let Some(import) = parse_import(line) {
-            if !import.starts_with("google/protobuf") {
-                let mut path = root.clone();
-                path.push(import);
-                if let Some(proto) = map.get(path.as_path()) {
-                    imports.push(proto.clone());
-                } else {
-                    let prot = proto_rec(root.clone(), path.clone(), map);
-                    imports.push(prot.clone());
-                    map.insert(path, prot);
-                }
-            }
+fn find_proto_rec(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::Io(dir.to_owned(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| Error::Io(dir.to_owned(), e))?.path();
+        if path.is_dir() {
+            find_proto_rec(path.as_path(), paths)?;
+        } else if path.extension().filter(|ex| ex == &"proto").is_some() {
+            paths.push(path);
         }
     }
+    Ok(())
+}
 
-    Proto {
+fn read_proto(root: &Path, path: PathBuf) -> Result<Proto, Error> {
+    let src = match fs::read_to_string(path.as_path()) {
+        Ok(src) => src,
+        Err(e) => return Err(Error::Io(path, e)),
+    };
+    let file = match proto::parse(&src) {
+        Ok(file) => file,
+        Err(e) => return Err(Error::Parse(path, e)),
+    };
+    let package = match file.package {
+        Some(package) => package.as_str().into(),
+        None => return Err(Error::MissingPackage(path)),
+    };
+
+    // The well-known types are provided by prost-types.
+    let imports = file
+        .imports
+        .into_iter()
+        .filter(|i| !i.path.starts_with("google/protobuf/"))
+        .map(|i| root.join(i.path))
+        .collect();
+
+    Ok(Proto {
         path,
-        package: package.unwrap(),
+        package,
         imports,
-    }
+    })
 }
 
-fn add_deps_rec(src: &Proto, proto: &Proto, map: &mut HashMap<Package, HashSet<Package>>) {
-    let e = map
-        .entry(proto.package.clone())
-        .or_insert_with(HashSet::new);
-    e.insert(src.package.clone());
+// `include_proto!` includes every file of a package, so dependencies are resolved between
+// packages: a package depends on the packages of the files imported by any of its files.
+pub fn package_graph(protos: &[Proto]) -> HashMap<&Package, HashSet<&Package>> {
+    let packages = protos
+        .iter()
+        .map(|p| (&p.path, &p.package))
+        .collect::<HashMap<_, _>>();
 
-    for import in proto.imports.iter() {
-        add_deps_rec(src, &import, map);
+    let mut graph = HashMap::<&Package, HashSet<&Package>>::new();
+    for proto in protos.iter() {
+        let deps = graph.entry(&proto.package).or_default();
+        deps.extend(
+            proto
+                .imports
+                .iter()
+                .filter_map(|i| packages.get(i).copied())
+                .filter(|&p| p != &proto.package),
+        );
     }
+    graph
 }
 
+// Returns the packages that each package is (transitively) imported by, including itself.
 fn deps_resolver(protos: &[Proto]) -> HashMap<Package, HashSet<Package>> {
-    let mut map = HashMap::new();
-    for p in protos.iter() {
-        add_deps_rec(p, p, &mut map);
+    let graph = package_graph(protos);
+
+    let mut map = HashMap::<Package, HashSet<Package>>::new();
+    for &src in graph.keys() {
+        // Imports may be cyclic, so every package is visited at most once.
+        let mut visited = HashSet::new();
+        let mut stack = vec![src];
+        while let Some(pkg) = stack.pop() {
+            if visited.insert(pkg) {
+                map.entry(pkg.clone()).or_default().insert(src.clone());
+                stack.extend(graph[pkg].iter().copied());
+            }
+        }
     }
     map
 }
 
-#[allow(dead_code)]
-pub fn feature_gates(protos: &[Proto]) -> String {
-    let pkgs = protos
+// A version segment of a package, e.g. `v1`, `v1beta1` or `v1p5beta1`.
+fn is_version(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars.next() == Some('v') && chars.next().filter(char::is_ascii_digit).is_some()
+}
+
+// A stable version segment such as `v1`, returned as its number.
+fn stable_version(segment: &str) -> Option<u32> {
+    segment.strip_prefix('v').and_then(|v| v.parse().ok())
+}
+
+// The API of a package and its version, if it is stable: the segments before the version, e.g.
+// `google.spanner.admin.database` and 1 for `google.spanner.admin.database.v1`.
+fn stable_api(package: &str) -> Option<(String, u32)> {
+    let segments = package.split('.').collect::<Vec<_>>();
+    let pos = segments
+        .iter()
+        .position(|s| is_version(s))
+        .filter(|&pos| pos > 0)?;
+    let version = stable_version(segments[pos])?;
+    Some((segments[..pos].join("."), version))
+}
+
+/// Umbrella features follow the package hierarchy: each API with a stable version has one, named
+/// after its package prefix, enabling the latest stable version of every API under that prefix.
+/// E.g. `google-spanner` enables `google-spanner-v1`, `google-spanner-admin-database-v1` and
+/// `google-spanner-admin-instance-v1`, and `google-spanner-admin-database` only the second.
+pub fn umbrella_features(pkgs: &[&Package]) -> BTreeMap<String, BTreeSet<String>> {
+    // api -> (latest stable version, packages of that version)
+    let mut apis = BTreeMap::<String, (u32, BTreeSet<String>)>::new();
+    for pkg in pkgs {
+        let (api, version) = match stable_api(pkg.raw()) {
+            Some(api) => api,
+            None => continue,
+        };
+        let e = apis
+            .entry(api)
+            .or_insert_with(|| (version, BTreeSet::new()));
+        if e.0 < version {
+            *e = (version, BTreeSet::new());
+        }
+        if e.0 == version {
+            e.1.insert(pkg.feature_name());
+        }
+    }
+
+    // A package without a version, e.g. `google.api`, would have the same feature name.
+    let packages = pkgs
         .iter()
-        .map(|p| p.package.feature_name())
+        .map(|p| p.feature_name())
         .collect::<HashSet<_>>();
-    let mut pkgs = pkgs.into_iter().collect::<Vec<_>>();
-    pkgs.sort();
-    pkgs.into_iter()
-        .map(|f| format!("{} = []", f))
-        .collect::<Vec<_>>()
-        .join("\n")
+    let mut ret = BTreeMap::<String, BTreeSet<String>>::new();
+    for prefix in apis.keys() {
+        let name = Package::from(prefix.as_str()).feature_name();
+        if packages.contains(&name) {
+            continue;
+        }
+        let under = format!("{}.", prefix);
+        let features = apis
+            .iter()
+            .filter(|(api, _)| *api == prefix || api.starts_with(&under))
+            .flat_map(|(_, (_, features))| features.iter().cloned())
+            .collect();
+        ret.insert(name, features);
+    }
+    ret
 }
 
-pub fn proto_path(protos: &[Proto]) -> Vec<PathBuf> {
-    let mut ret = protos
+/// Short aliases of the umbrella features, named without their `google-cloud-` or `google-` prefix,
+/// e.g. `pubsub` for `google-pubsub` and `bigquery` for `google-cloud-bigquery`. An alias shared
+/// by several umbrellas, or already the name of a package or umbrella, is left out.
+pub fn umbrella_aliases(
+    umbrellas: &BTreeMap<String, BTreeSet<String>>,
+    packages: &HashSet<String>,
+) -> BTreeMap<String, String> {
+    let mut aliases = BTreeMap::<String, Vec<&String>>::new();
+    for name in umbrellas.keys() {
+        if let Some(alias) = name
+            .strip_prefix("google-cloud-")
+            .or_else(|| name.strip_prefix("google-"))
+        {
+            aliases.entry(alias.to_owned()).or_default().push(name);
+        }
+    }
+    aliases
+        .into_iter()
+        .filter(|(alias, _)| !umbrellas.contains_key(alias) && !packages.contains(alias))
+        .filter_map(|(alias, names)| match names.as_slice() {
+            [name] => Some((alias, (*name).clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+// Features of other packages used by the hand-written code of a package, which its feature
+// enables, e.g. `google.spanner.v1` decodes the `google.rpc.RetryInfo` of `ABORTED` errors.
+const EXTENSION_FEATURES: &[(&str, &[&str])] = &[("google.spanner.v1", &["google-rpc"])];
+
+/// The features enabled by the feature of a package for its hand-written code.
+pub fn extension_features(package: &Package) -> Vec<String> {
+    EXTENSION_FEATURES
         .iter()
-        .map(|p| p.path.clone())
-        // TODO: There is a syntax error in google.ads.googleads.v5 and google.ads.googleads.v6.
-        // See https://github.com/googleapis/googleapis/pull/622 and https://github.com/mechiru/googapis/pull/9.
-        .filter(|p| {
-            let path = p.to_str().unwrap();
-            !(path.contains("google/ads/googleads/v5") || path.contains("google/ads/googleads/v6"))
+        .filter(|(raw, _)| *raw == package.raw())
+        .flat_map(|(_, features)| features.iter().map(|&f| f.to_owned()))
+        .collect()
+}
+
+pub fn feature_gates(protos: &[Proto]) -> String {
+    feature_gates_with(protos, extension_features)
+}
+
+/// Same as `feature_gates`, but each package feature enables the features returned by `enables`.
+pub fn feature_gates_with<F>(protos: &[Proto], enables: F) -> String
+where
+    F: Fn(&Package) -> Vec<String>,
+{
+    let pkgs = protos.iter().map(|p| &p.package).collect::<HashSet<_>>();
+    let pkgs = pkgs.into_iter().collect::<Vec<_>>();
+
+    let umbrellas = umbrella_features(&pkgs);
+    let names = pkgs
+        .iter()
+        .map(|p| p.feature_name())
+        .collect::<HashSet<_>>();
+    let aliases = umbrella_aliases(&umbrellas, &names)
+        .into_iter()
+        .map(|(alias, umbrella)| format!("{} = [\"{}\"]", alias, umbrella))
+        .collect::<Vec<_>>();
+    let umbrellas = umbrellas
+        .into_iter()
+        .map(|(product, features)| {
+            let features = features
+                .into_iter()
+                .map(|f| format!("\"{}\"", f))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{} = [{}]", product, features)
+        })
+        .collect::<Vec<_>>();
+
+    let mut pkgs = pkgs;
+    pkgs.sort_by_key(|p| p.feature_name());
+    let features = pkgs
+        .into_iter()
+        .map(|p| {
+            let enables = enables(p)
+                .into_iter()
+                .map(|f| format!("\"{}\"", f))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{} = [{}]", p.feature_name(), enables)
         })
         .collect::<Vec<_>>();
+
+    format!(
+        "{}\n{}\n\n{}\n{}\n\n{}\n{}",
+        UMBRELLAS_HEADER,
+        umbrellas.join("\n"),
+        ALIASES_HEADER,
+        aliases.join("\n"),
+        PACKAGES_HEADER,
+        features.join("\n")
+    )
+}
+
+pub const UMBRELLAS_HEADER: &str =
+    "# APIs, enabling the latest stable version of every API under their package prefix.";
+pub const ALIASES_HEADER: &str =
+    "# Short names of the umbrella features, without their `google-cloud-` or `google-` prefix.";
+pub const PACKAGES_HEADER: &str = "# Packages.";
+
+pub const FEATURES_BEGIN: &str = "# @generated by `cargo xtask gen`, do not edit by hand.";
+pub const FEATURES_END: &str = "# @generated end";
+
+/// Replaces the generated part of the `[features]` table of a manifest with `gates`.
+pub fn update_manifest(manifest: &str, gates: &str) -> String {
+    let begin = manifest
+        .find(FEATURES_BEGIN)
+        .expect("missing generated features begin marker");
+    let end = manifest[begin..]
+        .find(FEATURES_END)
+        .map(|i| begin + i)
+        .expect("missing generated features end marker");
+
+    format!(
+        "{}{}\n{}\n{}",
+        &manifest[..begin],
+        FEATURES_BEGIN,
+        gates,
+        &manifest[end..]
+    )
+}
+
+pub fn proto_path(protos: &[Proto]) -> Vec<PathBuf> {
+    let mut ret = protos.iter().map(|p| p.path.clone()).collect::<Vec<_>>();
     ret.sort();
     ret
 }
@@ -384,7 +649,7 @@ pub fn from_protos(protos: Vec<Proto>) -> RootModule {
             .entry(pkg)
             .or_insert_with(|| Module::empty(Package::from_escaped_vec(package.clone())));
 
-        while let Some(pkg) = iter.next() {
+        for pkg in iter {
             package.push(pkg.clone());
             e = e
                 .children
@@ -437,144 +702,179 @@ mod tests {
         );
     }
 
+    fn proto(path: &str, package: &str, imports: &[&str]) -> Proto {
+        Proto::new(path, package, imports)
+    }
+
+    fn protos() -> Vec<Proto> {
+        vec![
+            proto("/a/b.proto", "a", &["/a/c.proto", "/b/d.proto"]),
+            proto("/a/c.proto", "a", &["/c/e.proto"]),
+            proto("/b/d.proto", "b", &[]),
+            proto("/b/e.proto", "b", &["/c/e.proto"]),
+            proto("/c/e.proto", "c", &["/d/f.proto"]),
+            proto("/d/f.proto", "d", &[]),
+        ]
+    }
+
+    fn set(pkgs: &[&str]) -> HashSet<Package> {
+        pkgs.iter().map(|&p| p.into()).collect()
+    }
+
     #[test]
-    fn test_parse_package() {
-        assert_eq!(parse_package("package mechiru;"), Some("mechiru".into()));
-        assert_eq!(
-            parse_package("package mechiru.storage;"),
-            Some("mechiru.storage".into())
-        );
+    fn test_deps_resolver() {
+        // `/b/d.proto` does not import package `c`, but `/b/e.proto` of the same package does.
+        let mut map = HashMap::new();
+        map.insert("a".into(), set(&["a"]));
+        map.insert("b".into(), set(&["a", "b"]));
+        map.insert("c".into(), set(&["a", "b", "c"]));
+        map.insert("d".into(), set(&["a", "b", "c", "d"]));
+        assert_eq!(deps_resolver(&protos()), map);
     }
 
     #[test]
-    fn test_parse_import() {
-        assert_eq!(
-            parse_import(r#"import "mechiru/common.proto";"#),
-            Some("mechiru/common.proto".into())
+    fn test_deps_resolver_cyclic() {
+        let protos = vec![
+            proto("/a/a.proto", "a", &["/b/b.proto"]),
+            proto("/b/b.proto", "b", &["/c/c.proto"]),
+            proto("/c/c.proto", "c", &["/a/a.proto"]),
+            proto("/d/d.proto", "d", &["/b/b.proto"]),
+        ];
+
+        let mut map = HashMap::new();
+        map.insert("a".into(), set(&["a", "b", "c", "d"]));
+        map.insert("b".into(), set(&["a", "b", "c", "d"]));
+        map.insert("c".into(), set(&["a", "b", "c", "d"]));
+        map.insert("d".into(), set(&["d"]));
+        assert_eq!(deps_resolver(&protos), map);
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("xtask-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(root.as_path());
+            for (path, src) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, src).unwrap();
+            }
+            Self(root)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.as_path());
+        }
+    }
+
+    #[test]
+    fn test_find_proto() {
+        let dir = TempDir::new(
+            "find-proto",
+            &[
+                (
+                    "mechiru/a/a.proto",
+                    r#"syntax = "proto3";
+  package mechiru.a;
+/* import "mechiru/c/c.proto"; */
+import "google/protobuf/empty.proto";
+import
+  "mechiru/b/b.proto";
+option java_package = "dev.mechiru.a";"#,
+                ),
+                (
+                    "mechiru/b/b.proto",
+                    r#"package mechiru.b; import public "mechiru/c/c.proto";"#,
+                ),
+                (
+                    "mechiru/c/c.proto",
+                    r#"package mechiru.c; import "mechiru/a/a.proto";"#,
+                ),
+                ("mechiru/d/d.proto", "package mechiru.d;"),
+            ],
         );
+        let root = dir.0.clone();
 
+        let protos = find_proto(root.clone()).unwrap();
         assert_eq!(
-            parse_import(r#"import weak "mechiru/common.proto";"#),
-            Some("mechiru/common.proto".into())
+            protos,
+            vec![
+                Proto {
+                    path: root.join("mechiru/a/a.proto"),
+                    package: "mechiru.a".into(),
+                    imports: vec![root.join("mechiru/b/b.proto")],
+                },
+                Proto {
+                    path: root.join("mechiru/b/b.proto"),
+                    package: "mechiru.b".into(),
+                    imports: vec![root.join("mechiru/c/c.proto")],
+                },
+                Proto {
+                    path: root.join("mechiru/c/c.proto"),
+                    package: "mechiru.c".into(),
+                    imports: vec![root.join("mechiru/a/a.proto")],
+                },
+                Proto {
+                    path: root.join("mechiru/d/d.proto"),
+                    package: "mechiru.d".into(),
+                    imports: vec![],
+                },
+            ]
         );
 
+        // `mechiru.c` is re-exported by `mechiru/b/b.proto` and imports `mechiru.a` back.
+        let deps = deps_resolver(&protos);
+        assert_eq!(deps[&"mechiru.c".into()], deps[&"mechiru.a".into()]);
         assert_eq!(
-            parse_import(r#"import public "mechiru/common.proto";"#),
-            Some("mechiru/common.proto".into())
+            deps[&"mechiru.c".into()],
+            set(&["mechiru.a", "mechiru.b", "mechiru.c"])
         );
-    }
-
-    fn protos() -> Vec<Proto> {
-        vec![
-            Proto {
-                path: PathBuf::from("/a/b.proto"),
-                package: "a".into(),
-                imports: vec![
-                    Proto {
-                        path: PathBuf::from("/a/c.proto"),
-                        package: "a".into(),
-                        imports: vec![Proto {
-                            path: PathBuf::from("/c/e.proto"),
-                            package: "c".into(),
-                            imports: vec![Proto {
-                                path: PathBuf::from("/d/f.proto"),
-                                package: "d".into(),
-                                imports: Vec::new(),
-                            }],
-                        }],
-                    },
-                    Proto {
-                        path: PathBuf::from("/b/d.proto"),
-                        package: "b".into(),
-                        imports: vec![Proto {
-                            path: PathBuf::from("/c/e.proto"),
-                            package: "c".into(),
-                            imports: Vec::new(),
-                        }],
-                    },
-                ],
-            },
-            Proto {
-                path: PathBuf::from("/a/c.proto"),
-                package: "a".into(),
-                imports: vec![Proto {
-                    path: PathBuf::from("/c/e.proto"),
-                    package: "c".into(),
-                    imports: vec![Proto {
-                        path: PathBuf::from("/d/f.proto"),
-                        package: "d".into(),
-                        imports: Vec::new(),
-                    }],
-                }],
-            },
-            Proto {
-                path: PathBuf::from("/c/e.proto"),
-                package: "c".into(),
-                imports: vec![Proto {
-                    path: PathBuf::from("/d/f.proto"),
-                    package: "d".into(),
-                    imports: Vec::new(),
-                }],
-            },
-            Proto {
-                path: PathBuf::from("/d/f.proto"),
-                package: "d".into(),
-                imports: Vec::new(),
-            },
-            Proto {
-                path: PathBuf::from("/b/d.proto"),
-                package: "b".into(),
-                imports: vec![Proto {
-                    path: PathBuf::from("/c/e.proto"),
-                    package: "c".into(),
-                    imports: Vec::new(),
-                }],
-            },
-            Proto {
-                path: PathBuf::from("/c/e.proto"),
-                package: "c".into(),
-                imports: Vec::new(),
-            },
-        ]
+        assert_eq!(deps[&"mechiru.d".into()], set(&["mechiru.d"]));
     }
 
     #[test]
-    fn test_add_deps_rec() {
-        let protos = protos();
-        let mut map = HashMap::new();
-        for proto in protos {
-            add_deps_rec(&proto, &proto, &mut map);
-        }
+    fn test_find_proto_error() {
+        let dir = TempDir::new(
+            "missing-package",
+            &[("mechiru/a.proto", r#"syntax = "proto3";"#)],
+        );
+        assert_eq!(
+            find_proto(dir.0.clone()).unwrap_err().to_string(),
+            format!(
+                "{}: missing package statement",
+                dir.0.join("mechiru/a.proto").display()
+            )
+        );
 
-        assert_eq!(map, {
-            let mut map = HashMap::new();
-            map.insert("a".into(), {
-                let mut set = HashSet::new();
-                set.insert("a".into());
-                set
-            });
-            map.insert("b".into(), {
-                let mut set = HashSet::new();
-                set.insert("a".into());
-                set.insert("b".into());
-                set
-            });
-            map.insert("c".into(), {
-                let mut set = HashSet::new();
-                set.insert("a".into());
-                set.insert("b".into());
-                set.insert("c".into());
-                set
-            });
-            map.insert("d".into(), {
-                let mut set = HashSet::new();
-                set.insert("a".into());
-                set.insert("c".into());
-                set.insert("d".into());
-                set
-            });
-            map
-        });
+        let dir = TempDir::new(
+            "missing-import",
+            &[(
+                "mechiru/a.proto",
+                r#"package mechiru; import "mechiru/b.proto";"#,
+            )],
+        );
+        assert_eq!(
+            find_proto(dir.0.clone()).unwrap_err().to_string(),
+            format!(
+                "{}: imported file not found: mechiru/b.proto",
+                dir.0.join("mechiru/a.proto").display()
+            )
+        );
+
+        let dir = TempDir::new(
+            "parse-error",
+            &[("mechiru/a.proto", "package mechiru\nimport \"b.proto\";")],
+        );
+        assert_eq!(
+            find_proto(dir.0.clone()).unwrap_err().to_string(),
+            format!(
+                "{}:2:1: expected `;`, found `import`",
+                dir.0.join("mechiru/a.proto").display()
+            )
+        );
     }
 
     #[test]
@@ -588,6 +888,7 @@ mod tests {
                 Module {
                     package: "a".into(),
                     include: true,
+                    extended: false,
                     imported_by: {
                         let mut set = HashSet::new();
                         set.insert("a".into());
@@ -601,6 +902,7 @@ mod tests {
                 Module {
                     package: "b".into(),
                     include: true,
+                    extended: false,
                     imported_by: {
                         let mut set = HashSet::new();
                         set.insert("a".into());
@@ -615,6 +917,7 @@ mod tests {
                 Module {
                     package: "c".into(),
                     include: true,
+                    extended: false,
                     imported_by: {
                         let mut set = HashSet::new();
                         set.insert("a".into());
@@ -630,9 +933,11 @@ mod tests {
                 Module {
                     package: "d".into(),
                     include: true,
+                    extended: false,
                     imported_by: {
                         let mut set = HashSet::new();
                         set.insert("a".into());
+                        set.insert("b".into());
                         set.insert("c".into());
                         set.insert("d".into());
                         set
@@ -644,6 +949,23 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_root_module_features() {
+        let features = from_protos(protos()).features();
+        assert_eq!(
+            features
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.iter().map(String::as_str).collect::<Vec<_>>()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", vec!["a"]),
+                ("b", vec!["a", "b"]),
+                ("c", vec!["a", "b", "c"]),
+                ("d", vec!["a", "b", "c", "d"]),
+            ]
+        );
+    }
+
     #[test]
     fn test_root_module_gen_code() {
         let root = from_protos(protos());
@@ -658,7 +980,7 @@ include_proto!("b");
 pub mod c { #[cfg(any(feature = "a",feature = "b",feature = "c",))]
 include_proto!("c");
   }
-pub mod d { #[cfg(any(feature = "a",feature = "c",feature = "d",))]
+pub mod d { #[cfg(any(feature = "a",feature = "b",feature = "c",feature = "d",))]
 include_proto!("d");
   }
 "###
@@ -670,6 +992,7 @@ include_proto!("d");
         let module = Module {
             package: "mechiru.type".into(),
             include: true,
+            extended: false,
             imported_by: {
                 let mut set = HashSet::new();
                 set.insert("mechiru.type".into());
@@ -684,4 +1007,179 @@ include_proto!("mechiru.r#type");
  "###
         );
     }
+
+    #[test]
+    fn test_find_extensions() {
+        let dir = TempDir::new("ext", &[("mechiru/type/mod.rs", "")]);
+        let mut root = from_protos(vec![
+            proto("/mechiru/type/a.proto", "mechiru.type", &[]),
+            proto(
+                "/mechiru/v1/b.proto",
+                "mechiru.v1",
+                &["/mechiru/type/a.proto"],
+            ),
+        ]);
+        root.find_extensions(&dir.0);
+        assert_eq!(
+            root.gen_code(),
+            r###"pub mod mechiru {  pub mod r#type { #[cfg(any(feature = "mechiru-type",feature = "mechiru-v1",))]
+include_proto!("mechiru.r#type");
+#[cfg(any(feature = "mechiru-type",feature = "mechiru-v1",))]
+include_ext!("mechiru/type");
+  }
+pub mod v1 { #[cfg(any(feature = "mechiru-v1",))]
+include_proto!("mechiru.v1");
+  }
+ }
+"###
+        );
+    }
+
+    #[test]
+    fn test_umbrella_features() {
+        let pkgs = vec![
+            "google.spanner.v1",
+            "google.spanner.admin.database.v1",
+            "google.pubsub.v1",
+            "google.pubsub.v1beta2",
+            "google.cloud.bigquery.storage.v1",
+            "google.cloud.bigquery.storage.v1beta2",
+            "google.cloud.aiplatform.v1",
+            "google.cloud.aiplatform.v1.schema.predict.instance",
+            "google.cloud.asset.v1",
+            "google.cloud.asset.v1p5beta1",
+            "google.ads.admob.v1",
+            "google.ads.googleads.v7.common",
+            "google.ads.googleads.v8.common",
+            "google.ads.googleads.v8.services",
+            "google.api",
+            "google.api.expr.v1alpha1",
+            "grafeas.v1",
+            "mechiru.type",
+            "mechiru.type.v1",
+        ]
+        .into_iter()
+        .map(Package::from)
+        .collect::<Vec<_>>();
+
+        let umbrellas = umbrella_features(&pkgs.iter().collect::<Vec<_>>())
+            .into_iter()
+            .map(|(k, v)| (k, v.into_iter().collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let umbrella = |name: &str, features: &[&str]| {
+            (
+                name.to_owned(),
+                features.iter().map(|&f| f.to_owned()).collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            umbrellas,
+            vec![
+                umbrella("google-ads-admob", &["google-ads-admob-v1"]),
+                umbrella(
+                    "google-ads-googleads",
+                    &[
+                        "google-ads-googleads-v8-common",
+                        "google-ads-googleads-v8-services"
+                    ]
+                ),
+                umbrella(
+                    "google-cloud-aiplatform",
+                    &[
+                        "google-cloud-aiplatform-v1",
+                        "google-cloud-aiplatform-v1-schema-predict-instance"
+                    ]
+                ),
+                umbrella("google-cloud-asset", &["google-cloud-asset-v1"]),
+                umbrella(
+                    "google-cloud-bigquery-storage",
+                    &["google-cloud-bigquery-storage-v1"]
+                ),
+                umbrella("google-pubsub", &["google-pubsub-v1"]),
+                umbrella(
+                    "google-spanner",
+                    &["google-spanner-admin-database-v1", "google-spanner-v1"]
+                ),
+                umbrella(
+                    "google-spanner-admin-database",
+                    &["google-spanner-admin-database-v1"]
+                ),
+                umbrella("grafeas", &["grafeas-v1"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_umbrella_aliases() {
+        let umbrellas = [
+            "google-cloud-bigquery",
+            "google-cloud-bigquery-storage",
+            "google-pubsub",
+            "google-cloud-logging",
+            "google-logging",
+            "google-cloud-storage",
+            "storage",
+            "google-spanner",
+            "grafeas",
+        ]
+        .iter()
+        .map(|&u| (u.to_owned(), BTreeSet::new()))
+        .collect();
+        let packages = vec!["spanner".to_owned()].into_iter().collect();
+        assert_eq!(
+            umbrella_aliases(&umbrellas, &packages)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![
+                ("bigquery".to_owned(), "google-cloud-bigquery".to_owned()),
+                (
+                    "bigquery-storage".into(),
+                    "google-cloud-bigquery-storage".into()
+                ),
+                ("pubsub".into(), "google-pubsub".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_feature_gates() {
+        assert_eq!(
+            feature_gates(&protos()),
+            r#"# APIs, enabling the latest stable version of every API under their package prefix.
+
+
+# Short names of the umbrella features, without their `google-cloud-` or `google-` prefix.
+
+
+# Packages.
+a = []
+b = []
+c = []
+d = []"#
+        );
+    }
+
+    #[test]
+    fn test_extension_features() {
+        assert_eq!(
+            extension_features(&Package::from("google.spanner.v1")),
+            vec!["google-rpc".to_owned()]
+        );
+        assert!(extension_features(&Package::from("google.spanner.admin.database.v1")).is_empty());
+    }
+
+    #[test]
+    fn test_update_manifest() {
+        let manifest = format!(
+            "[features]\ndefault = []\n{}\nold = []\n{}\n\n[dependencies]\n",
+            FEATURES_BEGIN, FEATURES_END
+        );
+        assert_eq!(
+            update_manifest(&manifest, "a = []\nb = []"),
+            format!(
+                "[features]\ndefault = []\n{}\na = []\nb = []\n{}\n\n[dependencies]\n",
+                FEATURES_BEGIN, FEATURES_END
+            )
+        );
+    }
 }
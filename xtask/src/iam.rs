@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use prost_types::{FileDescriptorSet, ServiceDescriptorProto};
+
+use crate::{
+    gen::Package,
+    reflect::{to_snake, to_upper_camel},
+};
+
+// The methods of a service implementing `IamClient`, with their input and output types.
+const GET_IAM_POLICY: (&str, &str, &str) = (
+    "GetIamPolicy",
+    ".google.iam.v1.GetIamPolicyRequest",
+    ".google.iam.v1.Policy",
+);
+const SET_IAM_POLICY: (&str, &str, &str) = (
+    "SetIamPolicy",
+    ".google.iam.v1.SetIamPolicyRequest",
+    ".google.iam.v1.Policy",
+);
+
+// The module name tonic-build gives to the client of a service, see
+// `tonic_build::naive_snake_case`.
+fn client_mod(service: &str) -> String {
+    let mut s = String::new();
+    let mut it = service.chars().peekable();
+    while let Some(x) = it.next() {
+        s.push(x.to_ascii_lowercase());
+        if it.peek().is_some_and(|y| y.is_uppercase()) {
+            s.push('_');
+        }
+    }
+    s + "_client"
+}
+
+fn has_method(service: &ServiceDescriptorProto, (name, input, output): (&str, &str, &str)) -> bool {
+    service.method.iter().any(|m| {
+        m.name() == name
+            && m.input_type() == input
+            && m.output_type() == output
+            && !m.client_streaming()
+            && !m.server_streaming()
+    })
+}
+
+fn gen_service(service: &ServiceDescriptorProto, buf: &mut String) {
+    let name = to_upper_camel(service.name());
+    buf.push_str(&format!(
+        r#"
+impl<T> crate::google::iam::v1::IamClient for {module}::{name}Client<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Send,
+    T::Future: Send,
+    T::ResponseBody: tonic::codegen::Body + Send + Sync + 'static,
+    T::Error: Into<tonic::codegen::StdError>,
+    <T::ResponseBody as tonic::codegen::Body>::Error: Into<tonic::codegen::StdError> + Send,
+{{
+    fn get_policy(
+        &mut self,
+        request: crate::google::iam::v1::GetIamPolicyRequest,
+    ) -> crate::google::iam::v1::PolicyFuture<'_> {{
+        Box::pin(async move {{ Ok(self.{get}(request).await?.into_inner()) }})
+    }}
+
+    fn set_policy(
+        &mut self,
+        request: crate::google::iam::v1::SetIamPolicyRequest,
+    ) -> crate::google::iam::v1::PolicyFuture<'_> {{
+        Box::pin(async move {{ Ok(self.{set}(request).await?.into_inner()) }})
+    }}
+}}
+"#,
+        module = client_mod(&name),
+        name = name,
+        get = to_snake(GET_IAM_POLICY.0),
+        set = to_snake(SET_IAM_POLICY.0),
+    ));
+}
+
+/// Generates the `IamClient` implementations of the clients of the services with the
+/// `GetIamPolicy` and `SetIamPolicy` methods, by package.
+pub fn gen_iam(set: &FileDescriptorSet) -> BTreeMap<String, String> {
+    let mut code = BTreeMap::<String, String>::new();
+    for file in set.file.iter() {
+        let package = Package::from(file.package());
+        for service in file.service.iter() {
+            if has_method(service, GET_IAM_POLICY) && has_method(service, SET_IAM_POLICY) {
+                gen_service(service, code.entry(package.raw().to_owned()).or_default());
+            }
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{FileDescriptorProto, MethodDescriptorProto};
+
+    fn method(name: &str, input: &str, output: &str) -> MethodDescriptorProto {
+        MethodDescriptorProto {
+            name: Some(name.into()),
+            input_type: Some(input.into()),
+            output_type: Some(output.into()),
+            ..Default::default()
+        }
+    }
+
+    fn service(name: &str, methods: Vec<MethodDescriptorProto>) -> ServiceDescriptorProto {
+        ServiceDescriptorProto {
+            name: Some(name.into()),
+            method: methods,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_client_mod() {
+        assert_eq!(client_mod("IamPolicy"), "iam_policy_client");
+        assert_eq!(client_mod("Publisher"), "publisher_client");
+        assert_eq!(
+            client_mod("SecretManagerService"),
+            "secret_manager_service_client"
+        );
+    }
+
+    #[test]
+    fn test_gen_iam() {
+        let get = || method(GET_IAM_POLICY.0, GET_IAM_POLICY.1, GET_IAM_POLICY.2);
+        let set = || method(SET_IAM_POLICY.0, SET_IAM_POLICY.1, SET_IAM_POLICY.2);
+        let streaming = MethodDescriptorProto {
+            server_streaming: Some(true),
+            ..get()
+        };
+        let file = FileDescriptorProto {
+            package: Some("mechiru.v1".into()),
+            service: vec![
+                service("IAMPolicy", vec![set(), get()]),
+                service("Reader", vec![get()]),
+                service("Streamer", vec![streaming, set()]),
+                service(
+                    "Other",
+                    vec![
+                        method(
+                            "GetIamPolicy",
+                            ".mechiru.v1.GetPolicy",
+                            ".mechiru.v1.Policy",
+                        ),
+                        set(),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+        let code = gen_iam(&FileDescriptorSet { file: vec![file] });
+
+        assert_eq!(code.keys().collect::<Vec<_>>(), vec!["mechiru.v1"]);
+        let code = &code["mechiru.v1"];
+        assert_eq!(code.matches("impl<T>").count(), 1);
+        assert!(code.contains(
+            "impl<T> crate::google::iam::v1::IamClient for iam_policy_client::IamPolicyClient<T>"
+        ));
+        assert!(code.contains("self.get_iam_policy(request).await?.into_inner()"));
+        assert!(code.contains("self.set_iam_policy(request).await?.into_inner()"));
+    }
+}
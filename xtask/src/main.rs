@@ -1,11 +1,28 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, error, fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
 
+use prost::Message;
+
+mod check;
+mod diff;
 mod gen;
+mod iam;
+mod patch;
+mod proto;
+mod reflect;
+mod split;
 
 fn main() {
     match env::args().nth(1) {
         Some(cmd) => match cmd.as_str() {
             "gen" => gen(),
+            "check" => check(),
+            "patches" => patches(),
+            "diff" => diff(),
             _ => print_help(),
         },
         _ => print_help(),
@@ -13,20 +30,42 @@ fn main() {
 }
 
 fn print_help() {
-    println!(r#"cargo xtask gen"#)
+    println!(
+        r#"cargo xtask gen [--split <dir>]
+cargo xtask check
+cargo xtask patches
+cargo xtask diff [--json] [--output <file>] <old-rev> <new-rev>"#
+    )
 }
 
 fn gen() {
+    let mut split_dir = None;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--split" => split_dir = args.next().map(PathBuf::from),
+            _ => {
+                print_help();
+                process::exit(1);
+            }
+        }
+    }
+
     let proto_root = PathBuf::from("xtask/proto/googleapis");
-    let protos = gen::find_proto(proto_root.clone());
+    let patches = or_exit(patch::find_patches("xtask/patches"));
+    let staged_root = PathBuf::from("target/xtask/googleapis");
+    or_exit(patch::stage(&proto_root, &staged_root, &patches));
+    let protos = or_exit(gen::find_proto(staged_root.clone()));
 
-    // let gates = gen::feature_gates(&protos);
-    // println!("{}", gates);
+    let manifest_path = PathBuf::from("googapis/Cargo.toml");
+    let manifest = fs::read_to_string(manifest_path.as_path()).unwrap();
+    let gates = gen::feature_gates(&protos);
+    fs::write(manifest_path, gen::update_manifest(&manifest, &gates)).unwrap();
 
     let out_dir = PathBuf::from("googapis/genproto");
     let _ = fs::remove_dir_all(out_dir.as_path());
     let _ = fs::create_dir(out_dir.as_path());
-    let includes = [proto_root];
+    let includes = [staged_root];
 
     tonic_build::configure()
         .build_server(false)
@@ -34,12 +73,197 @@ fn gen() {
         .out_dir(out_dir.clone())
         .compile(&gen::proto_path(&protos), &includes)
         .unwrap();
+    // Appends the `reflect` implementations, see `googapis::reflect`, and the `IamClient`
+    // implementations, see `googapis::google::iam::v1`.
+    let set = or_exit(descriptors(
+        &includes[0],
+        &gen::proto_path(&protos),
+        Path::new("target/xtask/descriptors.bin"),
+    ));
+    let behaviors = or_exit(
+        fs::read("target/xtask/descriptors.bin")
+            .map_err(|e| e.to_string())
+            .and_then(|set| reflect::field_behaviors(&set).map_err(|e| e.to_string())),
+    );
+    let mut generated = reflect::gen_reflect(&set, &behaviors);
+    for (package, code) in iam::gen_iam(&set) {
+        generated.entry(package).or_default().push_str(&code);
+    }
+    for (package, code) in generated {
+        let path = out_dir.join(format!(
+            "{}.rs",
+            gen::Package::from(package.as_str()).escaped()
+        ));
+        if path.exists() {
+            let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+            file.write_all(code.as_bytes()).unwrap();
+        }
+    }
     tonic_build::fmt(out_dir.to_str().unwrap());
 
     let mut out_path = PathBuf::from("googapis/src/googapis.rs");
-    let root = gen::from_protos(protos);
+    let mut root = gen::from_protos(protos.clone());
+    root.find_extensions(Path::new("googapis/src/ext"));
     fs::write(out_path.clone(), root.gen_code()).unwrap();
 
     out_path.pop();
     tonic_build::fmt(out_path.to_str().unwrap());
+
+    if let Some(dir) = split_dir {
+        or_exit(split::write_workspace(&dir, Path::new("googapis"), &protos));
+    }
+}
+
+fn check() {
+    let manifest = fs::read_to_string("googapis/Cargo.toml").unwrap();
+    let mut errors = check::check_features(&manifest, PathBuf::from("googapis/src"));
+    errors.extend(check::check_umbrellas(&manifest));
+    errors.extend(check::check_patches(
+        Path::new("xtask/patches"),
+        Path::new("xtask/proto/googleapis"),
+        Path::new("target/xtask/check/googleapis"),
+    ));
+    if errors.is_empty() {
+        println!("ok");
+    } else {
+        for e in errors.iter() {
+            eprintln!("error: {}", e);
+        }
+        process::exit(1);
+    }
+}
+
+fn patches() {
+    let proto_root = PathBuf::from("xtask/proto/googleapis");
+    let patches = or_exit(patch::find_patches("xtask/patches"));
+    if patches.is_empty() {
+        println!("no patches");
+        return;
+    }
+
+    let staged_root = PathBuf::from("target/xtask/googleapis");
+    or_exit(patch::stage(&proto_root, &staged_root, &[]));
+    let mut failed = false;
+    for p in patches.iter() {
+        print!("{}", p);
+        if let Err(e) = p.apply(&staged_root) {
+            eprintln!("error: {}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn diff() {
+    let mut json = false;
+    let mut output = None;
+    let mut revs = Vec::new();
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--output" => output = args.next(),
+            _ => revs.push(arg),
+        }
+    }
+    if revs.len() != 2 {
+        print_help();
+        process::exit(1);
+    }
+
+    let old = or_exit(surface(&revs[0]));
+    let new = or_exit(surface(&revs[1]));
+    let changes = diff::diff(&old, &new);
+    let report = if json {
+        diff::to_json(&revs[0], &revs[1], &changes)
+    } else {
+        diff::to_markdown(&revs[0], &revs[1], &changes)
+    };
+
+    match output {
+        Some(path) => fs::write(path, report).unwrap(),
+        None => io::stdout().write_all(report.as_bytes()).unwrap(),
+    }
+}
+
+// Generates the module tree and the descriptors of googleapis at `rev`.
+fn surface(rev: &str) -> Result<diff::Surface, Box<dyn error::Error>> {
+    let root = PathBuf::from("target/xtask/diff").join(rev.replace('/', "-"));
+    let export = root.join("export");
+    let staged_root = root.join("googleapis");
+    let _ = fs::remove_dir_all(root.as_path());
+    fs::create_dir_all(export.as_path())?;
+
+    // `git archive` is used instead of a checkout to leave the submodule alone.
+    let mut archive = Command::new("git")
+        .args([
+            "-C",
+            "xtask/proto/googleapis",
+            "archive",
+            "--format=tar",
+            rev,
+        ])
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let status = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(export.as_path())
+        .stdin(archive.stdout.take().unwrap())
+        .status()?;
+    if !archive.wait()?.success() || !status.success() {
+        return Err(format!("failed to export googleapis at {}", rev).into());
+    }
+
+    // Patches are made for the current revision: they may not apply to others.
+    patch::stage(&export, &staged_root, &[])?;
+    for p in patch::find_patches("xtask/patches")? {
+        if let Err(e) = p.apply(&staged_root) {
+            eprintln!("warning: {}: {}", rev, e);
+        }
+    }
+
+    let protos = gen::find_proto(staged_root.clone())?;
+    let set = descriptors(
+        &staged_root,
+        &gen::proto_path(&protos),
+        &root.join("descriptors.bin"),
+    )?;
+    Ok(diff::Surface::new(
+        gen::from_protos(protos).features(),
+        &set,
+    ))
+}
+
+fn descriptors(
+    root: &Path,
+    protos: &[PathBuf],
+    out: &Path,
+) -> Result<prost_types::FileDescriptorSet, Box<dyn error::Error>> {
+    let status = Command::new(prost_build::protoc())
+        .arg("-I")
+        .arg(root)
+        .arg("-I")
+        .arg(prost_build::protoc_include())
+        .arg("--include_imports")
+        .arg("--experimental_allow_proto3_optional")
+        .arg(format!("--descriptor_set_out={}", out.display()))
+        .args(protos)
+        .status()?;
+    if !status.success() {
+        return Err(format!("protoc failed: {}", status).into());
+    }
+    Ok(prost_types::FileDescriptorSet::decode(&*fs::read(out)?)?)
+}
+
+fn or_exit<T, E: fmt::Display>(r: Result<T, E>) -> T {
+    match r {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,636 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// Patches fix upstream protos that do not compile as is. They are unified diffs produced by
+// `git format-patch --base=<commit>` relative to the googleapis root, stored in `xtask/patches` as
+// `NNNN-<name>.patch` and applied in sequence order to a copy of the protos before generating
+// code. The `base-commit` of a patch is the googleapis revision it was made against. A patch that
+// no longer applies, e.g. because the fix landed upstream, fails the generation.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hunk {
+    old_start: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Modify,
+    // `--- /dev/null`
+    Create,
+    // `+++ /dev/null`
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilePatch {
+    path: String,
+    change: Change,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    name: String,
+    // The `NNNN` prefix of the file name.
+    sequence: u32,
+    // The googleapis commit the patch was made against.
+    base: String,
+    description: String,
+    files: Vec<FilePatch>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(String, usize, String),
+    Apply(String, String, usize, &'static str),
+    Name(String),
+    Sequence(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Parse(name, line, msg) => write!(f, "{}:{}: {}", name, line, msg),
+            Error::Apply(name, path, hunk, msg) => {
+                write!(f, "{}: hunk #{} of {} {}", name, hunk, path, msg)
+            }
+            Error::Name(name) => write!(f, "{}: expected a `NNNN-<name>.patch` file name", name),
+            Error::Sequence(a, b) => write!(f, "{} and {} have the same sequence number", a, b),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// @@ -old_start[,old_len] +new_start[,new_len] @@
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut iter = line.strip_prefix("@@ -")?.split(' ');
+    let range = |r: &str| -> Option<(usize, usize)> {
+        let mut iter = r.splitn(2, ',');
+        let start = iter.next()?.parse().ok()?;
+        let len = iter.next().map(str::parse).unwrap_or(Ok(1)).ok()?;
+        Some((start, len))
+    };
+    let (old_start, old_len) = range(iter.next()?)?;
+    let (_, new_len) = range(iter.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_len, new_len))
+}
+
+const DEV_NULL: &str = "/dev/null";
+
+// The path of a `---` or `+++` file header.
+fn header_path(line: &str) -> &str {
+    let path = line[4..].split('\t').next().unwrap().trim();
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+}
+
+// The sequence number of a `NNNN-<name>.patch` file name.
+fn sequence(name: &str) -> Option<u32> {
+    let (number, rest) = name.split_once('-')?;
+    if number.len() != 4 || rest == ".patch" || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+impl Patch {
+    pub fn parse(name: &str, src: &str) -> Result<Self, Error> {
+        let err = |line: usize, msg: &str| Error::Parse(name.to_owned(), line + 1, msg.to_owned());
+
+        let sequence = sequence(name).ok_or_else(|| Error::Name(name.to_owned()))?;
+        let lines = src.lines().collect::<Vec<_>>();
+        let mut i = 0;
+
+        // Everything before the first file header describes the patch, e.g. the mail header and
+        // message of `git format-patch`.
+        let mut description = Vec::new();
+        while i < lines.len() && !lines[i].starts_with("--- ") && !lines[i].starts_with("diff ") {
+            let line = lines[i].trim();
+            let line = line.strip_prefix("Subject:").map(str::trim).unwrap_or(line);
+            let line = line.strip_prefix("[PATCH]").map(str::trim).unwrap_or(line);
+            if !line.is_empty() && !line.starts_with("From") && !line.starts_with("Date:") {
+                description.push(line);
+            }
+            if line == "---" {
+                description.pop();
+                break;
+            }
+            i += 1;
+        }
+
+        let mut files = Vec::new();
+        while i < lines.len() {
+            if !lines[i].starts_with("--- ") {
+                i += 1;
+                continue;
+            }
+            let new = lines
+                .get(i + 1)
+                .filter(|l| l.starts_with("+++ "))
+                .ok_or_else(|| err(i + 1, "expected `+++` file header"))?;
+            let (old, new) = (header_path(lines[i]), header_path(new));
+            let (path, change) = match (old, new) {
+                (DEV_NULL, DEV_NULL) => return Err(err(i, "invalid file header")),
+                (DEV_NULL, path) => (path, Change::Create),
+                (path, DEV_NULL) => (path, Change::Delete),
+                (_, path) => (path, Change::Modify),
+            };
+            let path = path.to_owned();
+            i += 2;
+
+            let mut hunks = Vec::new();
+            while let Some(line) = lines.get(i).filter(|l| l.starts_with("@@ ")) {
+                let (old_start, old_len, new_len) =
+                    parse_hunk_header(line).ok_or_else(|| err(i, "invalid hunk header"))?;
+                i += 1;
+
+                let mut hunk = Hunk {
+                    old_start,
+                    old: Vec::new(),
+                    new: Vec::new(),
+                };
+                while hunk.old.len() < old_len || hunk.new.len() < new_len {
+                    let line = lines
+                        .get(i)
+                        .ok_or_else(|| err(i, "unexpected end of hunk"))?;
+                    match line.chars().next() {
+                        // Some editors strip the space of empty context lines.
+                        Some(' ') | None => {
+                            let l = line.get(1..).unwrap_or_default();
+                            hunk.old.push(l.to_owned());
+                            hunk.new.push(l.to_owned());
+                        }
+                        Some('-') => hunk.old.push(line[1..].to_owned()),
+                        Some('+') => hunk.new.push(line[1..].to_owned()),
+                        Some('\\') => {}
+                        _ => return Err(err(i, "unexpected line in hunk")),
+                    }
+                    i += 1;
+                }
+                if hunk.old.len() != old_len || hunk.new.len() != new_len {
+                    return Err(err(i - 1, "hunk does not match its header"));
+                }
+                hunks.push(hunk);
+            }
+
+            if hunks.is_empty() {
+                return Err(err(i, "expected a hunk"));
+            }
+            files.push(FilePatch {
+                path,
+                change,
+                hunks,
+            });
+        }
+
+        if files.is_empty() {
+            return Err(err(0, "no file to patch"));
+        }
+        // `git format-patch --base` appends the commit after the diff.
+        let base = lines
+            .iter()
+            .find_map(|l| l.strip_prefix("base-commit:"))
+            .map(|c| c.trim().to_owned())
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| {
+                err(
+                    0,
+                    "missing `base-commit`, create the patch with `git format-patch --base`",
+                )
+            })?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            sequence,
+            base,
+            description: description.join(" "),
+            files,
+        })
+    }
+
+    /// Applies the patch to the files under `root` in place.
+    pub fn apply(&self, root: &Path) -> Result<(), Error> {
+        // Every file is patched before any is written, so a failing patch leaves `root` untouched.
+        let mut patched = Vec::new();
+        for file in self.files.iter() {
+            let path = root.join(&file.path);
+            let exists = path.is_file();
+            let err = |msg| Error::Apply(self.name.clone(), file.path.clone(), 1, msg);
+            let src = match file.change {
+                Change::Create if exists => return Err(err("is already applied")),
+                Change::Create => String::new(),
+                Change::Delete if !exists => return Err(err("is already applied")),
+                _ => fs::read_to_string(path.as_path()).map_err(|e| Error::Io(path.clone(), e))?,
+            };
+            let src = self.apply_file(file, &src)?;
+            if file.change == Change::Delete && !src.is_empty() {
+                return Err(err("does not delete the whole file"));
+            }
+            patched.push((path, file.change, src));
+        }
+        for (path, change, src) in patched {
+            let written = match change {
+                Change::Delete => fs::remove_file(path.as_path()),
+                Change::Create => path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(path.as_path(), src)),
+                Change::Modify => fs::write(path.as_path(), src),
+            };
+            written.map_err(|e| Error::Io(path.clone(), e))?;
+        }
+        Ok(())
+    }
+
+    fn apply_file(&self, file: &FilePatch, src: &str) -> Result<String, Error> {
+        let mut lines = src.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+        // Line numbers of later hunks move by the lines added or removed by earlier hunks.
+        let mut offset = 0isize;
+
+        for (n, hunk) in file.hunks.iter().enumerate() {
+            let err = |msg| Error::Apply(self.name.clone(), file.path.clone(), n + 1, msg);
+
+            // A hunk without old lines, e.g. `@@ -3,0 +4,2 @@`, inserts after its start line.
+            let start = if hunk.old.is_empty() {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let expected = (start as isize + offset).max(0) as usize;
+            let pos = find_nearest(&lines, &hunk.old, expected).ok_or_else(|| {
+                // Pure deletions cannot tell, nothing is left to find.
+                let applied = !hunk.new.is_empty() && hunk.old != hunk.new;
+                if applied && find_nearest(&lines, &hunk.new, expected).is_some() {
+                    err("is already applied")
+                } else {
+                    err("does not apply")
+                }
+            })?;
+
+            lines.splice(pos..pos + hunk.old.len(), hunk.new.iter().cloned());
+            offset += hunk.new.len() as isize - hunk.old.len() as isize;
+        }
+
+        let mut ret = lines.join("\n");
+        // A created file ends with a newline, as `\ No newline at end of file` is ignored.
+        if !ret.is_empty() && (src.ends_with('\n') || file.change == Change::Create) {
+            ret.push('\n');
+        }
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.name, self.description)?;
+        writeln!(f, "    base-commit: {}", self.base)?;
+        for file in self.files.iter() {
+            let change = match file.change {
+                Change::Modify => "",
+                Change::Create => " (created)",
+                Change::Delete => " (deleted)",
+            };
+            writeln!(f, "    {}{}", file.path, change)?;
+        }
+        Ok(())
+    }
+}
+
+// The position of `needle` in `lines` closest to `expected`.
+fn find_nearest(lines: &[String], needle: &[String], expected: usize) -> Option<usize> {
+    if needle.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - needle.len();
+    let matches = |pos: usize| lines[pos..pos + needle.len()] == *needle;
+
+    let expected = expected.min(last);
+    (0..=last.max(expected)).find_map(|d| {
+        let before = expected.checked_sub(d).filter(|&p| matches(p));
+        let after = Some(expected + d).filter(|&p| p <= last && matches(p));
+        before.or(after)
+    })
+}
+
+/// Reads the patches in `dir` in file name order.
+pub fn find_patches(dir: impl AsRef<Path>) -> Result<Vec<Patch>, Error> {
+    let dir = dir.as_ref();
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Io(dir.to_owned(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(Error::Io(dir.to_owned(), e)),
+    };
+    paths.retain(|p| p.extension().filter(|ex| ex == &"patch").is_some());
+    paths.sort();
+
+    let patches = paths
+        .into_iter()
+        .map(|path| {
+            let src = fs::read_to_string(path.as_path()).map_err(|e| Error::Io(path.clone(), e))?;
+            let name = path.file_name().unwrap().to_string_lossy();
+            Patch::parse(&name, &src)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    check_sequence(&patches)?;
+    Ok(patches)
+}
+
+// Sorted by name, patches are in sequence order unless two have the same number.
+fn check_sequence(patches: &[Patch]) -> Result<(), Error> {
+    match patches.windows(2).find(|w| w[0].sequence == w[1].sequence) {
+        Some(w) => Err(Error::Sequence(w[0].name.clone(), w[1].name.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Copies the protos under `src` to `dst` and applies `patches` to the copy.
+pub fn stage(src: &Path, dst: &Path, patches: &[Patch]) -> Result<(), Error> {
+    let _ = fs::remove_dir_all(dst);
+    copy_protos(src, dst)?;
+    for patch in patches {
+        patch.apply(dst)?;
+    }
+    Ok(())
+}
+
+fn copy_protos(src: &Path, dst: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dst).map_err(|e| Error::Io(dst.to_owned(), e))?;
+    for entry in fs::read_dir(src).map_err(|e| Error::Io(src.to_owned(), e))? {
+        let path = entry.map_err(|e| Error::Io(src.to_owned(), e))?.path();
+        let to = dst.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_protos(path.as_path(), to.as_path())?;
+        } else if path.extension().filter(|ex| ex == &"proto").is_some() {
+            fs::copy(path.as_path(), to).map_err(|e| Error::Io(path.clone(), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = r#"From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: mechiru <u9053u6d41@gmail.com>
+Date: Thu, 5 Aug 2021 00:00:00 +0900
+Subject: [PATCH] fix: syntax error in mechiru.storage.v1
+
+---
+ mechiru/storage/v1/storage.proto | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/mechiru/storage/v1/storage.proto b/mechiru/storage/v1/storage.proto
+index 0000000..1111111 100644
+--- a/mechiru/storage/v1/storage.proto
++++ b/mechiru/storage/v1/storage.proto
+@@ -2,3 +2,3 @@ syntax = "proto3";
+ package mechiru.storage.v1;
+-message Bucket { string name = 1 }
++message Bucket { string name = 1; }
+ message Object { string name = 1; }
+@@ -6,2 +6,3 @@ message Object { string name = 1; }
+ message Empty {}
++message Other {}
+ // end
+
+base-commit: 2f9af297c84c55c8b871ba4495e01ade42476c92
+-- 
+2.32.0
+"#;
+
+    const SRC: &str = r#"syntax = "proto3";
+package mechiru.storage.v1;
+message Bucket { string name = 1 }
+message Object { string name = 1; }
+
+message Empty {}
+// end
+"#;
+
+    #[test]
+    fn test_parse() {
+        let patch = Patch::parse("0001-fix.patch", PATCH).unwrap();
+        assert_eq!(patch.description, "fix: syntax error in mechiru.storage.v1");
+        assert_eq!(patch.sequence, 1);
+        assert_eq!(patch.base, "2f9af297c84c55c8b871ba4495e01ade42476c92");
+        assert_eq!(
+            patch
+                .files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["mechiru/storage/v1/storage.proto"]
+        );
+        assert_eq!(patch.files[0].hunks.len(), 2);
+        assert_eq!(
+            patch.files[0].hunks[1],
+            Hunk {
+                old_start: 6,
+                old: vec!["message Empty {}".into(), "// end".into()],
+                new: vec![
+                    "message Empty {}".into(),
+                    "message Other {}".into(),
+                    "// end".into()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Patch::parse("0001-fix.patch", "fix\n")
+                .unwrap_err()
+                .to_string(),
+            "0001-fix.patch:1: no file to patch"
+        );
+        assert_eq!(
+            Patch::parse(
+                "0001-fix.patch",
+                "--- a/a.proto\n+++ b/a.proto\n@@ -x +1 @@\n"
+            )
+            .unwrap_err()
+            .to_string(),
+            "0001-fix.patch:3: invalid hunk header"
+        );
+        assert_eq!(
+            Patch::parse(
+                "0001-fix.patch",
+                "--- a/a.proto\n+++ b/a.proto\n@@ -1,2 +1,2 @@\n a\n-b\n"
+            )
+            .unwrap_err()
+            .to_string(),
+            "0001-fix.patch:6: unexpected end of hunk"
+        );
+        assert_eq!(
+            Patch::parse("0001-fix.patch", &PATCH.replace("base-commit", "base"))
+                .unwrap_err()
+                .to_string(),
+            "0001-fix.patch:1: missing `base-commit`, create the patch with `git format-patch --base`"
+        );
+        for name in ["fix.patch", "1-fix.patch", "000a-fix.patch", "0001-.patch"] {
+            assert_eq!(
+                Patch::parse(name, PATCH).unwrap_err().to_string(),
+                format!("{}: expected a `NNNN-<name>.patch` file name", name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_sequence() {
+        let patches = ["0001-a.patch", "0002-b.patch", "0002-c.patch"]
+            .iter()
+            .map(|name| Patch::parse(name, PATCH).unwrap())
+            .collect::<Vec<_>>();
+        assert!(check_sequence(&patches[..2]).is_ok());
+        assert_eq!(
+            check_sequence(&patches).unwrap_err().to_string(),
+            "0002-b.patch and 0002-c.patch have the same sequence number"
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let patch = Patch::parse("0001-fix.patch", PATCH).unwrap();
+        assert_eq!(
+            patch.apply_file(&patch.files[0], SRC).unwrap(),
+            r#"syntax = "proto3";
+package mechiru.storage.v1;
+message Bucket { string name = 1; }
+message Object { string name = 1; }
+
+message Empty {}
+message Other {}
+// end
+"#
+        );
+
+        // The hunks still apply when upstream added lines before them.
+        let src = format!("// Copyright 2021 mechiru\n\n{}", SRC);
+        assert!(patch.apply_file(&patch.files[0], &src).is_ok());
+    }
+
+    #[test]
+    fn test_apply_insertion() {
+        // `git diff -U0` hunks have no context, the lines are inserted after line 4.
+        let src = "--- a/a.proto\n+++ b/a.proto\n@@ -4,0 +5,2 @@\n+message A {}\n+message B {}\n\nbase-commit: 2f9af297c84c55c8b871ba4495e01ade42476c92\n";
+        let patch = Patch::parse("0001-fix.patch", src).unwrap();
+        assert_eq!(
+            patch.apply_file(&patch.files[0], SRC).unwrap(),
+            SRC.replace(
+                "message Object { string name = 1; }\n",
+                "message Object { string name = 1; }\nmessage A {}\nmessage B {}\n"
+            )
+        );
+
+        // At the start of the file.
+        let patch = Patch::parse("0001-fix.patch", &src.replace("-4,0 +5,2", "-0,0 +1,2")).unwrap();
+        assert_eq!(
+            patch.apply_file(&patch.files[0], SRC).unwrap(),
+            format!("message A {{}}\nmessage B {{}}\n{}", SRC)
+        );
+    }
+
+    #[test]
+    fn test_apply_create_and_delete() {
+        let src = r#"Subject: [PATCH] chore: move mechiru.storage.v1 to v2
+
+---
+diff --git a/mechiru/storage/v1/storage.proto b/mechiru/storage/v1/storage.proto
+deleted file mode 100644
+--- a/mechiru/storage/v1/storage.proto
++++ /dev/null
+@@ -1,3 +0,0 @@
+-syntax = "proto3";
+-package mechiru.storage.v1;
+-message Bucket {}
+diff --git a/mechiru/storage/v2/storage.proto b/mechiru/storage/v2/storage.proto
+new file mode 100644
+--- /dev/null
++++ b/mechiru/storage/v2/storage.proto
+@@ -0,0 +1,2 @@
++syntax = "proto3";
++package mechiru.storage.v2;
+\ No newline at end of file
+
+base-commit: 2f9af297c84c55c8b871ba4495e01ade42476c92
+"#;
+        let patch = Patch::parse("0001-move.patch", src).unwrap();
+        assert_eq!(
+            patch.to_string(),
+            "0001-move.patch: chore: move mechiru.storage.v1 to v2
+    base-commit: 2f9af297c84c55c8b871ba4495e01ade42476c92
+    mechiru/storage/v1/storage.proto (deleted)
+    mechiru/storage/v2/storage.proto (created)
+"
+        );
+
+        let root = std::env::temp_dir().join(format!("xtask-patch-{}", std::process::id()));
+        let (v1, v2) = (
+            root.join("mechiru/storage/v1/storage.proto"),
+            root.join("mechiru/storage/v2/storage.proto"),
+        );
+        fs::create_dir_all(v1.parent().unwrap()).unwrap();
+
+        // The deleted file must match the patch.
+        fs::write(&v1, "syntax = \"proto3\";\npackage mechiru.storage.v1;\n").unwrap();
+        assert_eq!(
+            patch.apply(&root).unwrap_err().to_string(),
+            "0001-move.patch: hunk #1 of mechiru/storage/v1/storage.proto does not apply"
+        );
+        assert!(!v2.exists());
+
+        fs::write(
+            &v1,
+            "syntax = \"proto3\";\npackage mechiru.storage.v1;\nmessage Bucket {}\n",
+        )
+        .unwrap();
+        patch.apply(&root).unwrap();
+        assert!(!v1.exists());
+        assert_eq!(
+            fs::read_to_string(&v2).unwrap(),
+            "syntax = \"proto3\";\npackage mechiru.storage.v2;\n"
+        );
+        assert_eq!(
+            patch.apply(&root).unwrap_err().to_string(),
+            "0001-move.patch: hunk #1 of mechiru/storage/v1/storage.proto is already applied"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_apply_error() {
+        let patch = Patch::parse("0001-fix.patch", PATCH).unwrap();
+
+        let fixed = SRC.replace("name = 1 }", "name = 1; }");
+        assert_eq!(
+            patch
+                .apply_file(&patch.files[0], &fixed)
+                .unwrap_err()
+                .to_string(),
+            "0001-fix.patch: hunk #1 of mechiru/storage/v1/storage.proto is already applied"
+        );
+
+        let changed = SRC.replace("message Bucket", "message Folder");
+        assert_eq!(
+            patch
+                .apply_file(&patch.files[0], &changed)
+                .unwrap_err()
+                .to_string(),
+            "0001-fix.patch: hunk #1 of mechiru/storage/v1/storage.proto does not apply"
+        );
+    }
+}
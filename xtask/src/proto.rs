@@ -0,0 +1,416 @@
+use std::fmt;
+
+// https://developers.google.com/protocol-buffers/docs/reference/proto3-spec
+// Only the top level statements needed to discover the dependencies of a proto file are parsed:
+// syntax = "syntax" "=" quote "proto3" quote ";"
+// package = "package" fullIdent ";"
+// import = "import" [ "weak" | "public" ] strLit ";"
+// Any other statement (option, message, enum, service, extend) is skipped.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    Default,
+    Weak,
+    Public,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub path: String,
+    pub kind: ImportKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtoFile {
+    pub package: Option<String>,
+    pub imports: Vec<Import>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    // Identifiers, keywords and numbers.
+    Word(String),
+    Str(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "`{}`", w),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Symbol(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    pos: Pos,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            chars: src.chars().peekable(),
+            pos: Pos { line: 1, column: 1 },
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('/') => {
+                    let start = self.pos;
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+                    match ahead.next() {
+                        Some('/') => while !matches!(self.bump(), Some('\n') | None) {},
+                        Some('*') => {
+                            self.bump();
+                            self.bump();
+                            let mut prev = '\0';
+                            loop {
+                                match self.bump() {
+                                    Some('/') if prev == '*' => break,
+                                    Some(c) => prev = c,
+                                    None => return Err(start.error("unterminated block comment")),
+                                }
+                            }
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut ret = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => return Ok(ret),
+                Some('\\') => match self.bump() {
+                    Some('n') => ret.push('\n'),
+                    Some('t') => ret.push('\t'),
+                    Some(c) if c != '\n' => ret.push(c),
+                    _ => return Err(start.error("unterminated string literal")),
+                },
+                Some('\n') | None => return Err(start.error("unterminated string literal")),
+                Some(c) => ret.push(c),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Pos, Token)>, ParseError> {
+        self.skip_trivia()?;
+        let pos = self.pos;
+        let token = match self.chars.peek().copied() {
+            None => return Ok(None),
+            Some(q @ '"') | Some(q @ '\'') => Token::Str(self.string(q)?),
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    word.push(c);
+                    self.bump();
+                }
+                Token::Word(word)
+            }
+            Some(c) => {
+                self.bump();
+                Token::Symbol(c)
+            }
+        };
+        Ok(Some((pos, token)))
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<(Pos, Token)>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<Option<(Pos, Token)>, ParseError> {
+        match self.peeked.take() {
+            Some(v) => Ok(Some(v)),
+            None => self.lexer.next_token(),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+        Ok(self.peeked.as_ref().map(|(_, t)| t))
+    }
+
+    fn expect(&mut self, what: &str) -> Result<(Pos, Token), ParseError> {
+        let eof = self.lexer.pos;
+        self.next()?
+            .ok_or_else(|| eof.error(format!("expected {}, found end of file", what)))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        match self.expect(&format!("`{}`", symbol))? {
+            (_, Token::Symbol(c)) if c == symbol => Ok(()),
+            (pos, t) => Err(pos.error(format!("expected `{}`, found {}", symbol, t))),
+        }
+    }
+
+    fn expect_str(&mut self) -> Result<String, ParseError> {
+        match self.expect("a string literal")? {
+            (_, Token::Str(s)) => Ok(s),
+            (pos, t) => Err(pos.error(format!("expected a string literal, found {}", t))),
+        }
+    }
+
+    // fullIdent = ident { "." ident }
+    fn full_ident(&mut self) -> Result<String, ParseError> {
+        let mut ret = String::new();
+        loop {
+            match self.expect("an identifier")? {
+                (_, Token::Word(w)) => ret.push_str(&w),
+                (pos, t) => return Err(pos.error(format!("expected an identifier, found {}", t))),
+            }
+            if self.peek()? != Some(&Token::Symbol('.')) {
+                return Ok(ret);
+            }
+            self.next()?;
+            ret.push('.');
+        }
+    }
+
+    // Skips the rest of a statement: up to the next `;` or the end of a `{ ... }` block.
+    fn skip_statement(&mut self, start: Pos) -> Result<(), ParseError> {
+        let mut depth = 0usize;
+        loop {
+            match self.next()? {
+                None => return Err(start.error("unterminated statement")),
+                Some((_, Token::Symbol(';'))) if depth == 0 => return Ok(()),
+                Some((_, Token::Symbol('{'))) => depth += 1,
+                Some((pos, Token::Symbol('}'))) => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| pos.error("unexpected `}`"))?;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<ProtoFile, ParseError> {
+    let mut parser = Parser {
+        lexer: Lexer::new(src),
+        peeked: None,
+    };
+    let mut ret = ProtoFile::default();
+
+    while let Some((pos, token)) = parser.next()? {
+        match token {
+            Token::Symbol(';') => {}
+            Token::Word(w) if w == "package" => {
+                let package = parser.full_ident()?;
+                parser.expect_symbol(';')?;
+                if ret.package.replace(package).is_some() {
+                    return Err(pos.error("duplicate package statement"));
+                }
+            }
+            Token::Word(w) if w == "import" => {
+                let kind = match parser.peek()? {
+                    Some(Token::Word(w)) if w == "weak" => ImportKind::Weak,
+                    Some(Token::Word(w)) if w == "public" => ImportKind::Public,
+                    _ => ImportKind::Default,
+                };
+                if kind != ImportKind::Default {
+                    parser.next()?;
+                }
+                let path = parser.expect_str()?;
+                parser.expect_symbol(';')?;
+                ret.imports.push(Import { path, kind });
+            }
+            Token::Word(_) => parser.skip_statement(pos)?,
+            t => return Err(pos.error(format!("unexpected {}", t))),
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(path: &str, kind: ImportKind) -> Import {
+        Import {
+            path: path.into(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let src = r#"
+// Copyright 2021 mechiru
+syntax = "proto3";
+
+  package   mechiru
+    .storage.v1 ;
+
+import "mechiru/common.proto";
+import weak 'mechiru/weak.proto';
+import public "mechiru/public.proto";
+
+option go_package = "import \"mechiru/option.proto\";";
+option (mechiru.resource_definition) = {
+  type: "storage.mechiru.dev/Bucket"
+  pattern: "buckets/{bucket}"
+};
+
+message Bucket {
+  // import "mechiru/field.proto";
+  string import = 1;
+  message Inner { int32 package = 1; }
+}
+"#;
+        assert_eq!(
+            parse(src),
+            Ok(ProtoFile {
+                package: Some("mechiru.storage.v1".into()),
+                imports: vec![
+                    import("mechiru/common.proto", ImportKind::Default),
+                    import("mechiru/weak.proto", ImportKind::Weak),
+                    import("mechiru/public.proto", ImportKind::Public),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let src = r#"syntax = "proto3";
+/* package mechiru.commented;
+import "mechiru/commented.proto"; */
+package /* inline */ mechiru; // package mechiru.trailing;
+/**
+ * import "mechiru/doc.proto";
+ */
+import "mechiru/common.proto"; /* import "mechiru/after.proto"; */
+"#;
+        assert_eq!(
+            parse(src),
+            Ok(ProtoFile {
+                package: Some("mechiru".into()),
+                imports: vec![import("mechiru/common.proto", ImportKind::Default)],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_no_package() {
+        assert_eq!(
+            parse(r#"syntax = "proto3"; import "mechiru/common.proto";"#),
+            Ok(ProtoFile {
+                package: None,
+                imports: vec![import("mechiru/common.proto", ImportKind::Default)],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            parse("package mechiru\nimport \"mechiru/common.proto\";"),
+            Err(ParseError {
+                line: 2,
+                column: 1,
+                message: "expected `;`, found `import`".into(),
+            })
+        );
+        assert_eq!(
+            parse("package mechiru;\n/* import \"mechiru/common.proto\";"),
+            Err(ParseError {
+                line: 2,
+                column: 1,
+                message: "unterminated block comment".into(),
+            })
+        );
+        assert_eq!(
+            parse("import \"mechiru/common.proto;\n"),
+            Err(ParseError {
+                line: 1,
+                column: 8,
+                message: "unterminated string literal".into(),
+            })
+        );
+        assert_eq!(
+            parse("message Bucket {\n  string name = 1;\n"),
+            Err(ParseError {
+                line: 1,
+                column: 1,
+                message: "unterminated statement".into(),
+            })
+        );
+        assert_eq!(
+            parse("package mechiru;\npackage mechiru.storage;"),
+            Err(ParseError {
+                line: 2,
+                column: 1,
+                message: "duplicate package statement".into(),
+            })
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use std::collections::{BTreeMap, HashMap};
+
+use heck::{CamelCase, SnakeCase};
+use prost::Message as _;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+};
+
+use crate::gen::Package;
+
+// The implementations of `googapis::reflect::Reflect` and the `googapis::field_mask::FieldPath`
+// accessors of the messages, appended to the code generated by prost.
+
+// The variants of `google.api.FieldBehavior` in `googapis::reflect::FieldBehavior`, by number.
+const FIELD_BEHAVIORS: &[(i32, &str)] = &[
+    (1, "Optional"),
+    (2, "Required"),
+    (3, "OutputOnly"),
+    (4, "InputOnly"),
+    (5, "Immutable"),
+    (6, "UnorderedList"),
+    (7, "NonEmptyDefault"),
+];
+
+// prost-types drops the extensions of the options, these messages keep the
+// `google.api.field_behavior` extension of the field options.
+mod annotated {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FileDescriptorSet {
+        #[prost(message, repeated, tag = "1")]
+        pub file: Vec<FileDescriptorProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FileDescriptorProto {
+        #[prost(string, optional, tag = "2")]
+        pub package: Option<String>,
+        #[prost(message, repeated, tag = "4")]
+        pub message_type: Vec<DescriptorProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DescriptorProto {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(message, repeated, tag = "2")]
+        pub field: Vec<FieldDescriptorProto>,
+        #[prost(message, repeated, tag = "3")]
+        pub nested_type: Vec<DescriptorProto>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FieldDescriptorProto {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(message, optional, tag = "8")]
+        pub options: Option<FieldOptions>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FieldOptions {
+        #[prost(int32, repeated, tag = "1052")]
+        pub field_behavior: Vec<i32>,
+    }
+}
+
+/// Returns the `google.api.field_behavior` annotations of the fields of an encoded
+/// `FileDescriptorSet`, by fully-qualified field name, e.g. `google.pubsub.v1.Topic.name`.
+pub fn field_behaviors(set: &[u8]) -> Result<HashMap<String, Vec<i32>>, prost::DecodeError> {
+    fn collect(
+        behaviors: &mut HashMap<String, Vec<i32>>,
+        prefix: &str,
+        message: &annotated::DescriptorProto,
+    ) {
+        let full_name = format!("{}.{}", prefix, message.name.as_deref().unwrap_or_default());
+        for f in message.field.iter() {
+            if let Some(options) = f.options.as_ref().filter(|o| !o.field_behavior.is_empty()) {
+                behaviors.insert(
+                    format!("{}.{}", full_name, f.name.as_deref().unwrap_or_default()),
+                    options.field_behavior.clone(),
+                );
+            }
+        }
+        for nested in message.nested_type.iter() {
+            collect(behaviors, &full_name, nested);
+        }
+    }
+
+    let mut behaviors = HashMap::new();
+    for file in annotated::FileDescriptorSet::decode(set)?.file {
+        for message in file.message_type.iter() {
+            collect(
+                &mut behaviors,
+                file.package.as_deref().unwrap_or_default(),
+                message,
+            );
+        }
+    }
+    Ok(behaviors)
+}
+
+// The well-known types are provided by `prost-types` and opaque to field masks.
+const WELL_KNOWN_PACKAGE: &str = "google.protobuf";
+
+struct Message<'a> {
+    proto: &'a DescriptorProto,
+    package: &'a str,
+    // e.g. `google.pubsub.v1.Topic`.
+    full_name: String,
+    // e.g. `crate::google::pubsub::v1::Topic`.
+    rust_path: String,
+}
+
+impl Message<'_> {
+    fn is_map_entry(&self) -> bool {
+        self.proto.options.as_ref().is_some_and(|o| o.map_entry())
+    }
+}
+
+// The same conversions as prost-build, see `prost_build::ident`.
+pub(crate) fn to_snake(s: &str) -> String {
+    let mut ident = s.to_snake_case();
+    match ident.as_str() {
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "false" | "fn" | "for" | "if"
+        | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref"
+        | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe" | "use"
+        | "where" | "while" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro"
+        | "override" | "priv" | "typeof" | "unsized" | "virtual" | "yield" | "async" | "await"
+        | "try" => ident.insert_str(0, "r#"),
+        "self" | "super" | "extern" | "crate" => ident += "_",
+        _ => (),
+    }
+    ident
+}
+
+pub(crate) fn to_upper_camel(s: &str) -> String {
+    let mut ident = s.to_camel_case();
+    if ident == "Self" {
+        ident += "_";
+    }
+    ident
+}
+
+fn collect<'a>(
+    messages: &mut HashMap<String, Message<'a>>,
+    package: &'a str,
+    proto_prefix: &str,
+    rust_prefix: &str,
+    proto: &'a DescriptorProto,
+) {
+    let full_name = format!("{}.{}", proto_prefix, proto.name());
+    for nested in proto.nested_type.iter() {
+        collect(
+            messages,
+            package,
+            &full_name,
+            &format!("{}::{}", rust_prefix, to_snake(proto.name())),
+            nested,
+        );
+    }
+    let rust_path = format!("{}::{}", rust_prefix, to_upper_camel(proto.name()));
+    messages.insert(
+        format!(".{}", full_name),
+        Message {
+            proto,
+            package,
+            full_name,
+            rust_path,
+        },
+    );
+}
+
+// The descriptor function of a message field, if it has a generated type.
+fn descriptor_fn(messages: &HashMap<String, Message>, f: &FieldDescriptorProto) -> Option<String> {
+    match f.r#type() {
+        Type::Message => messages
+            .get(f.type_name())
+            .filter(|m| m.package != WELL_KNOWN_PACKAGE)
+            .map(|m| format!("<{} as crate::reflect::Reflect>::descriptor", m.rust_path)),
+        _ => None,
+    }
+}
+
+fn gen_message(
+    messages: &HashMap<String, Message>,
+    behaviors: &HashMap<String, Vec<i32>>,
+    message: &Message,
+    buf: &mut String,
+) {
+    let mut fields = message.proto.field.iter().collect::<Vec<_>>();
+    fields.sort_by_key(|f| f.number());
+
+    let mut descriptors = String::new();
+    let mut accessors = String::new();
+    for f in fields {
+        let map_entry = messages.get(f.type_name()).filter(|m| m.is_map_entry());
+        let repeated = f.label() == Label::Repeated;
+        let (kind, path_type) = match (map_entry, descriptor_fn(messages, f)) {
+            (Some(entry), _) => {
+                let value = entry
+                    .proto
+                    .field
+                    .iter()
+                    .find(|f| f.number() == 2)
+                    .and_then(|v| descriptor_fn(messages, v))
+                    .map_or("None".to_owned(), |d| format!("Some({})", d));
+                (
+                    format!("crate::reflect::FieldKind::Map {{ value: {} }}", value),
+                    "crate::field_mask::MapField".to_owned(),
+                )
+            }
+            (None, Some(d)) => {
+                let path_type = if repeated {
+                    "()".to_owned()
+                } else {
+                    messages[f.type_name()].rust_path.clone()
+                };
+                (
+                    format!("crate::reflect::FieldKind::Message({})", d),
+                    path_type,
+                )
+            }
+            (None, None) => (
+                "crate::reflect::FieldKind::Scalar".to_owned(),
+                "()".to_owned(),
+            ),
+        };
+        // Proto3 `optional` fields are in a synthetic oneof that prost ignores.
+        let oneof = match f.oneof_index {
+            Some(i) if !f.proto3_optional() => {
+                format!("Some({:?})", message.proto.oneof_decl[i as usize].name())
+            }
+            _ => "None".to_owned(),
+        };
+
+        let field_behaviors = behaviors
+            .get(&format!("{}.{}", message.full_name, f.name()))
+            .into_iter()
+            .flatten()
+            .filter_map(|b| FIELD_BEHAVIORS.iter().find(|(n, _)| n == b))
+            .map(|(_, name)| format!("crate::reflect::FieldBehavior::{}", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        descriptors.push_str(&format!(
+            "crate::reflect::FieldDescriptor {{ name: {:?}, number: {}, kind: {}, repeated: {}, oneof: {}, behaviors: &[{}] }},\n",
+            f.name(),
+            f.number(),
+            kind,
+            repeated && map_entry.is_none(),
+            oneof,
+            field_behaviors,
+        ));
+        accessors.push_str(&format!(
+            "pub fn {}(self) -> crate::field_mask::FieldPath<R, {}> {{ crate::field_mask::child(self, {:?}) }}\n",
+            to_snake(f.name()),
+            path_type,
+            f.name(),
+        ));
+    }
+
+    buf.push_str(&format!(
+        r#"#[cfg(feature = "reflect")]
+impl crate::reflect::Reflect for {path} {{
+fn descriptor() -> &'static crate::reflect::MessageDescriptor {{
+static DESCRIPTOR: crate::reflect::MessageDescriptor = crate::reflect::MessageDescriptor {{
+full_name: {name:?},
+fields: &[
+{descriptors}],
+}};
+&DESCRIPTOR
+}}
+}}
+#[cfg(feature = "reflect")]
+impl<R> crate::field_mask::FieldPath<R, {path}> {{
+{accessors}}}
+"#,
+        path = message.rust_path,
+        name = message.full_name,
+        descriptors = descriptors,
+        accessors = accessors,
+    ));
+}
+
+/// Generates the code to append to the file of each package, by package. `behaviors` are the
+/// field behaviors returned by [`field_behaviors`].
+pub fn gen_reflect(
+    set: &FileDescriptorSet,
+    behaviors: &HashMap<String, Vec<i32>>,
+) -> BTreeMap<String, String> {
+    let mut messages = HashMap::new();
+    for file in set.file.iter() {
+        let package = Package::from(file.package());
+        let rust_prefix = format!("crate::{}", package.escaped_vec().join("::"));
+        for proto in file.message_type.iter() {
+            collect(
+                &mut messages,
+                file.package(),
+                file.package(),
+                &rust_prefix,
+                proto,
+            );
+        }
+    }
+
+    let mut sorted = messages
+        .values()
+        .filter(|m| m.package != WELL_KNOWN_PACKAGE && !m.is_map_entry())
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+
+    let mut code = BTreeMap::<String, String>::new();
+    for message in sorted {
+        gen_message(
+            &messages,
+            behaviors,
+            message,
+            code.entry(message.package.to_owned()).or_default(),
+        );
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{FileDescriptorProto, MessageOptions, OneofDescriptorProto};
+
+    fn field(
+        name: &str,
+        number: i32,
+        ty: Type,
+        type_name: &str,
+        label: Label,
+    ) -> FieldDescriptorProto {
+        let mut f = FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            type_name: Some(type_name.into()).filter(|n: &String| !n.is_empty()),
+            ..Default::default()
+        };
+        f.set_type(ty);
+        f.set_label(label);
+        f
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(to_snake("updateTime"), "update_time");
+        assert_eq!(to_snake("type"), "r#type");
+        assert_eq!(to_snake("self"), "self_");
+        assert_eq!(to_upper_camel("LabelsEntry"), "LabelsEntry");
+        assert_eq!(to_upper_camel("Self"), "Self_");
+    }
+
+    #[test]
+    fn test_gen_reflect() {
+        let labels = DescriptorProto {
+            name: Some("LabelsEntry".into()),
+            field: vec![
+                field("key", 1, Type::String, "", Label::Optional),
+                field(
+                    "value",
+                    2,
+                    Type::Message,
+                    ".mechiru.type.Schema",
+                    Label::Optional,
+                ),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut optional = field("ttl", 6, Type::Int64, "", Label::Optional);
+        optional.oneof_index = Some(1);
+        optional.proto3_optional = Some(true);
+        let mut text = field("text", 4, Type::String, "", Label::Optional);
+        text.oneof_index = Some(0);
+        let topic = DescriptorProto {
+            name: Some("Topic".into()),
+            field: vec![
+                field(
+                    "labels",
+                    2,
+                    Type::Message,
+                    ".mechiru.type.Topic.LabelsEntry",
+                    Label::Repeated,
+                ),
+                field("name", 1, Type::String, "", Label::Optional),
+                field(
+                    "schema",
+                    3,
+                    Type::Message,
+                    ".mechiru.type.Schema",
+                    Label::Optional,
+                ),
+                text,
+                field(
+                    "updateTime",
+                    5,
+                    Type::Message,
+                    ".google.protobuf.Timestamp",
+                    Label::Optional,
+                ),
+                optional,
+                field(
+                    "history",
+                    7,
+                    Type::Message,
+                    ".mechiru.type.Schema",
+                    Label::Repeated,
+                ),
+            ],
+            nested_type: vec![labels],
+            oneof_decl: vec![
+                OneofDescriptorProto {
+                    name: Some("payload".into()),
+                    ..Default::default()
+                },
+                OneofDescriptorProto {
+                    name: Some("_ttl".into()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let schema = DescriptorProto {
+            name: Some("Schema".into()),
+            ..Default::default()
+        };
+        let timestamp = DescriptorProto {
+            name: Some("Timestamp".into()),
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    package: Some("google.protobuf".into()),
+                    message_type: vec![timestamp],
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    package: Some("mechiru.type".into()),
+                    message_type: vec![topic, schema],
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let mut behaviors = HashMap::new();
+        behaviors.insert("mechiru.type.Topic.name".to_owned(), vec![2, 5, 99]);
+        behaviors.insert("mechiru.type.Topic.ttl".to_owned(), vec![3]);
+        let code = gen_reflect(&set, &behaviors);
+        assert_eq!(code.keys().collect::<Vec<_>>(), vec!["mechiru.type"]);
+        let code = &code["mechiru.type"];
+        let schema = "<crate::mechiru::r#type::Schema as crate::reflect::Reflect>::descriptor";
+        let expected = [
+            "impl crate::reflect::Reflect for crate::mechiru::r#type::Topic {".to_owned(),
+            "full_name: \"mechiru.type.Topic\",".to_owned(),
+            "crate::reflect::FieldDescriptor { name: \"name\", number: 1, kind: crate::reflect::FieldKind::Scalar, repeated: false, oneof: None, behaviors: &[crate::reflect::FieldBehavior::Required, crate::reflect::FieldBehavior::Immutable] },\ncrate::reflect::FieldDescriptor { name: \"labels\"".to_owned(),
+            format!("name: \"labels\", number: 2, kind: crate::reflect::FieldKind::Map {{ value: Some({}) }}, repeated: false, oneof: None, behaviors: &[] }}", schema),
+            format!("name: \"schema\", number: 3, kind: crate::reflect::FieldKind::Message({}), repeated: false, oneof: None, behaviors: &[] }}", schema),
+            "name: \"text\", number: 4, kind: crate::reflect::FieldKind::Scalar, repeated: false, oneof: Some(\"payload\"), behaviors: &[] }".to_owned(),
+            "name: \"updateTime\", number: 5, kind: crate::reflect::FieldKind::Scalar, repeated: false, oneof: None, behaviors: &[] }".to_owned(),
+            "name: \"ttl\", number: 6, kind: crate::reflect::FieldKind::Scalar, repeated: false, oneof: None, behaviors: &[crate::reflect::FieldBehavior::OutputOnly] }".to_owned(),
+            format!("name: \"history\", number: 7, kind: crate::reflect::FieldKind::Message({}), repeated: true, oneof: None, behaviors: &[] }}", schema),
+            "impl<R> crate::field_mask::FieldPath<R, crate::mechiru::r#type::Topic> {".to_owned(),
+            "pub fn labels(self) -> crate::field_mask::FieldPath<R, crate::field_mask::MapField> { crate::field_mask::child(self, \"labels\") }".to_owned(),
+            "pub fn schema(self) -> crate::field_mask::FieldPath<R, crate::mechiru::r#type::Schema> {".to_owned(),
+            "pub fn update_time(self) -> crate::field_mask::FieldPath<R, ()> { crate::field_mask::child(self, \"updateTime\") }".to_owned(),
+            "pub fn history(self) -> crate::field_mask::FieldPath<R, ()> {".to_owned(),
+            "impl crate::reflect::Reflect for crate::mechiru::r#type::Schema {".to_owned(),
+        ];
+        for e in expected.iter() {
+            assert!(code.contains(e.as_str()), "{}\nnot found in:\n{}", e, code);
+        }
+        assert!(!code.contains("LabelsEntry"), "{}", code);
+        assert_eq!(code.matches("#[cfg(feature = \"reflect\")]").count(), 4);
+    }
+
+    #[test]
+    fn test_field_behaviors() {
+        use annotated::*;
+
+        let field = |name: &str, behaviors: Vec<i32>| FieldDescriptorProto {
+            name: Some(name.into()),
+            options: Some(FieldOptions {
+                field_behavior: behaviors,
+            }),
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                package: Some("mechiru.v1".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Topic".into()),
+                    field: vec![field("name", vec![2]), field("labels", vec![])],
+                    nested_type: vec![DescriptorProto {
+                        name: Some("Schema".into()),
+                        field: vec![field("etag", vec![3, 5])],
+                        nested_type: vec![],
+                    }],
+                }],
+            }],
+        };
+
+        let behaviors = field_behaviors(&set.encode_to_vec()).unwrap();
+        let mut behaviors = behaviors.into_iter().collect::<Vec<_>>();
+        behaviors.sort();
+        assert_eq!(
+            behaviors,
+            vec![
+                ("mechiru.v1.Topic.Schema.etag".to_owned(), vec![3, 5]),
+                ("mechiru.v1.Topic.name".to_owned(), vec![2]),
+            ]
+        );
+
+        // The extension is dropped by prost-types but kept here.
+        let full = prost_types::FileDescriptorSet::decode(&*set.encode_to_vec()).unwrap();
+        assert!(field_behaviors(&full.encode_to_vec()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,668 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs, io,
+    path::Path,
+};
+
+use crate::gen::{self, Package, Proto, RootModule};
+
+// A crate of the split workspace. Packages that import each other cannot live in different crates,
+// so each strongly connected component of the package graph becomes one crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crate {
+    name: String,
+    packages: Vec<Package>,
+    // Packages of other crates imported by `packages`.
+    deps: Vec<Package>,
+}
+
+impl Crate {
+    fn ident(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
+
+// https://en.wikipedia.org/wiki/Tarjan%27s_strongly_connected_components_algorithm
+fn components<'a>(graph: &HashMap<&'a Package, HashSet<&'a Package>>) -> Vec<Vec<&'a Package>> {
+    struct State<'a, 'b> {
+        graph: &'b HashMap<&'a Package, HashSet<&'a Package>>,
+        index: HashMap<&'a Package, usize>,
+        low: HashMap<&'a Package, usize>,
+        stack: Vec<&'a Package>,
+        on_stack: HashSet<&'a Package>,
+        ret: Vec<Vec<&'a Package>>,
+    }
+
+    fn sorted<'a>(pkgs: impl Iterator<Item = &'a Package>) -> Vec<&'a Package> {
+        let mut pkgs = pkgs.collect::<Vec<_>>();
+        pkgs.sort_by_key(|p| p.raw());
+        pkgs
+    }
+
+    fn visit<'a>(s: &mut State<'a, '_>, v: &'a Package) {
+        let index = s.index.len();
+        s.index.insert(v, index);
+        s.low.insert(v, index);
+        s.stack.push(v);
+        s.on_stack.insert(v);
+
+        let graph = s.graph;
+        for w in sorted(graph[v].iter().copied()) {
+            if !s.index.contains_key(w) {
+                visit(s, w);
+                let low = s.low[v].min(s.low[w]);
+                s.low.insert(v, low);
+            } else if s.on_stack.contains(w) {
+                let low = s.low[v].min(s.index[w]);
+                s.low.insert(v, low);
+            }
+        }
+
+        if s.low[v] == s.index[v] {
+            let mut component = Vec::new();
+            while let Some(w) = s.stack.pop() {
+                s.on_stack.remove(w);
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            s.ret.push(sorted(component.into_iter()));
+        }
+    }
+
+    let mut s = State {
+        graph,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        ret: Vec::new(),
+    };
+    for v in sorted(graph.keys().copied()) {
+        if !s.index.contains_key(v) {
+            visit(&mut s, v);
+        }
+    }
+    s.ret
+}
+
+/// Groups the packages into crates, named after the first package of each crate.
+pub fn crates(protos: &[Proto]) -> Vec<Crate> {
+    let graph = gen::package_graph(protos);
+    let mut ret = components(&graph)
+        .into_iter()
+        .map(|pkgs| {
+            let own = pkgs.iter().copied().collect::<HashSet<_>>();
+            let mut deps = pkgs
+                .iter()
+                .flat_map(|p| graph[p].iter().copied())
+                .filter(|p| !own.contains(p))
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            deps.sort_by(|a, b| a.raw().cmp(b.raw()));
+            Crate {
+                name: format!("googapis-{}", pkgs[0].feature_name()),
+                packages: pkgs.into_iter().cloned().collect(),
+                deps,
+            }
+        })
+        .collect::<Vec<_>>();
+    ret.sort_by(|a, b| a.name.cmp(&b.name));
+    ret
+}
+
+#[derive(Default)]
+struct Node {
+    items: Vec<String>,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn insert(&mut self, path: &[String], item: String) {
+        match path.split_first() {
+            Some((name, rest)) => self
+                .children
+                .entry(name.clone())
+                .or_default()
+                .insert(rest, item),
+            None => self.items.push(item),
+        }
+    }
+
+    fn gen_code(&self) -> String {
+        let items = self.items.iter().map(|i| format!("{}\n", i));
+        let children = self
+            .children
+            .iter()
+            .map(|(name, child)| format!("pub mod {} {{\n{}}}\n", name, child.gen_code()));
+        items.chain(children).collect()
+    }
+}
+
+fn crate_of(crates: &[Crate]) -> HashMap<&str, &Crate> {
+    crates
+        .iter()
+        .flat_map(|c| c.packages.iter().map(move |p| (p.raw(), c)))
+        .collect()
+}
+
+// Private helpers of `googapis/src` the package extensions use through `crate::`, e.g.
+// `crate::wkt`. They do not depend on any package, so the crates with extensions get a copy.
+const SHARED: &[&str] = &["base64.rs", "filter", "wkt"];
+
+fn reexport(krate: &Crate, package: &Package) -> String {
+    format!(
+        "pub use {}::{}::*;",
+        krate.ident(),
+        package.escaped_vec().join("::")
+    )
+}
+
+// The module tree of a crate: its own packages are included and the packages it imports are
+// re-exported at the same paths, so that the `super::` paths of the generated code resolve.
+fn gen_crate_code(
+    krate: &Crate,
+    crate_of: &HashMap<&str, &Crate>,
+    extended: &HashSet<&str>,
+) -> String {
+    let mut root = Node::default();
+    if krate.packages.iter().any(|p| extended.contains(p.raw())) {
+        for name in SHARED {
+            root.items.push(format!(
+                "#[allow(dead_code, unused_imports)]\nmod {};",
+                name.trim_end_matches(".rs")
+            ));
+        }
+    }
+    for p in krate.packages.iter() {
+        let include = format!("include!(\"../genproto/{}.rs\");", p.escaped());
+        root.insert(p.escaped_vec(), include);
+        if extended.contains(p.raw()) {
+            let include = format!("include!(\"ext/{}/mod.rs\");", p.ext_path());
+            root.insert(p.escaped_vec(), include);
+        }
+    }
+    for p in krate.deps.iter() {
+        root.insert(p.escaped_vec(), reexport(crate_of[p.raw()], p));
+    }
+    root.gen_code()
+}
+
+// The module tree of the facade: every package is re-exported behind the features including it.
+fn gen_facade_code(root: &RootModule, crate_of: &HashMap<&str, &Crate>) -> String {
+    let mut tree = Node::default();
+    for (raw, features) in root.features() {
+        let attr = features
+            .iter()
+            .map(|f| format!(r#"feature = "{}","#, f))
+            .collect::<String>();
+        let package = Package::from(raw.as_str());
+        let item = format!(
+            "#[cfg(any({}))]\n{}",
+            attr,
+            reexport(crate_of[raw.as_str()], &package)
+        );
+        tree.insert(package.escaped_vec(), item);
+    }
+    tree.gen_code()
+}
+
+// Returns the lines of a table of a manifest.
+fn table<'a>(manifest: &'a str, name: &str) -> Vec<&'a str> {
+    let header = format!("[{}]", name);
+    manifest
+        .lines()
+        .skip_while(|l| l.trim() != header)
+        .skip(1)
+        .take_while(|l| !l.starts_with('['))
+        .filter(|l| !l.trim().is_empty())
+        .collect()
+}
+
+fn path_dep(krate: &Crate, version: &str, path: &str, optional: bool) -> String {
+    format!(
+        "{} = {{ version = \"{}\", path = \"{}/{}\"{} }}",
+        krate.name,
+        version,
+        path,
+        krate.name,
+        if optional { ", optional = true" } else { "" }
+    )
+}
+
+fn package_value<'a>(package: &[&'a str], key: &str) -> Option<&'a str> {
+    package.iter().find_map(|l| {
+        let (k, v) = l.split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"'))
+    })
+}
+
+fn gen_crate_manifest(krate: &Crate, crate_of: &HashMap<&str, &Crate>, manifest: &str) -> String {
+    let package = table(manifest, "package");
+    let version = package_value(&package, "version").expect("missing package version");
+    let inherited = package
+        .iter()
+        .filter(|l| {
+            let key = l.split('=').next().unwrap_or_default().trim();
+            ["version", "authors", "edition", "license", "repository"].contains(&key)
+        })
+        .copied()
+        .collect::<Vec<_>>();
+
+    let packages = krate
+        .packages
+        .iter()
+        .map(|p| format!("`{}`", p.raw()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut deps = table(manifest, "dependencies")
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    let mut dep_crates = krate
+        .deps
+        .iter()
+        .map(|p| crate_of[p.raw()])
+        .collect::<Vec<_>>();
+    dep_crates.sort_by(|a, b| a.name.cmp(&b.name));
+    dep_crates.dedup_by(|a, b| a.name == b.name);
+    deps.extend(dep_crates.iter().map(|c| path_dep(c, version, "..", false)));
+
+    format!(
+        "# @generated by `cargo xtask gen --split`, do not edit by hand.\n\
+         [package]\n\
+         name = \"{name}\"\n\
+         {inherited}\n\
+         description = \"Generated code of {packages} for googapis.\"\n\
+         \n\
+         [lib]\n\
+         doctest = false\n\
+         \n\
+         [dependencies]\n\
+         {deps}\n",
+        name = krate.name,
+        inherited = inherited.join("\n"),
+        packages = packages,
+        deps = deps.join("\n"),
+    )
+}
+
+// The manifest of `googapis` where each package feature enables the crates of the packages it
+// needs instead of the `include_proto!` gates.
+fn gen_facade_manifest(
+    protos: &[Proto],
+    root: &RootModule,
+    crates: &[Crate],
+    crate_of: &HashMap<&str, &Crate>,
+    manifest: &str,
+) -> String {
+    let mut enables = HashMap::<String, BTreeSet<String>>::new();
+    for (raw, features) in root.features() {
+        for f in features {
+            enables
+                .entry(f)
+                .or_default()
+                .insert(crate_of[raw.as_str()].name.clone());
+        }
+    }
+    let gates = gen::feature_gates_with(protos, |p| {
+        let crates = enables
+            .get(&p.feature_name())
+            .into_iter()
+            .flatten()
+            .cloned();
+        crates.chain(gen::extension_features(p)).collect()
+    });
+    let manifest = gen::update_manifest(manifest, &gates);
+
+    let version = package_value(&table(&manifest, "package"), "version")
+        .expect("missing package version")
+        .to_owned();
+    let deps = crates
+        .iter()
+        .map(|c| path_dep(c, &version, "../crates", true))
+        .collect::<Vec<_>>()
+        .join("\n");
+    manifest.replacen(
+        "[dependencies]\n",
+        &format!("[dependencies]\n{}\n", deps),
+        1,
+    )
+}
+
+// Copies `src` to `dst` recursively, skipping the subdirectories for which `skip` is true.
+fn copy_dir(src: &Path, dst: &Path, skip: &dyn Fn(&Path) -> bool) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let path = entry?.path();
+        let to = dst.join(path.file_name().unwrap());
+        if !path.is_dir() {
+            fs::copy(&path, to)?;
+        } else if !skip(&path) {
+            copy_dir(&path, &to, skip)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a workspace to `out_dir` with a crate per package (or cycle of packages) of `protos`,
+/// and a `googapis` facade re-exporting them behind the same features.
+///
+/// `googapis_dir` is the `googapis` crate generated by `cargo xtask gen`: the generated code is
+/// taken from its `genproto` directory, and its manifest and sources are used for the facade.
+pub fn write_workspace(out_dir: &Path, googapis_dir: &Path, protos: &[Proto]) -> io::Result<()> {
+    let crates = crates(protos);
+    let crate_of = crate_of(&crates);
+    let root = gen::from_protos(protos.to_vec());
+    let manifest = fs::read_to_string(googapis_dir.join("Cargo.toml"))?;
+
+    let _ = fs::remove_dir_all(out_dir);
+    fs::create_dir_all(out_dir)?;
+    fs::write(
+        out_dir.join("Cargo.toml"),
+        "# @generated by `cargo xtask gen --split`, do not edit by hand.\n\
+         [workspace]\n\
+         members = [\"googapis\", \"crates/*\"]\n",
+    )?;
+
+    // Hand-written code is moved to the crate of its package, with the directories of its
+    // submodules. The directories of other packages, and their parents, are skipped.
+    let ext_dir = googapis_dir.join("src/ext");
+    let package_dirs = protos
+        .iter()
+        .flat_map(|p| {
+            let dir = ext_dir.join(p.package().ext_path());
+            dir.ancestors().map(Path::to_path_buf).collect::<Vec<_>>()
+        })
+        .collect::<HashSet<_>>();
+    let is_package_dir = |path: &Path| package_dirs.contains(path);
+    let extended = protos
+        .iter()
+        .map(|p| p.package().raw())
+        .filter(|&p| {
+            let path = Package::from(p).ext_path();
+            ext_dir.join(path).join("mod.rs").is_file()
+        })
+        .collect::<HashSet<_>>();
+
+    for krate in crates.iter() {
+        let dir = out_dir.join("crates").join(&krate.name);
+        fs::create_dir_all(dir.join("src"))?;
+        fs::create_dir_all(dir.join("genproto"))?;
+        for p in krate.packages.iter() {
+            let file = format!("{}.rs", p.escaped());
+            fs::copy(
+                googapis_dir.join("genproto").join(&file),
+                dir.join("genproto").join(&file),
+            )?;
+            if extended.contains(p.raw()) {
+                copy_dir(
+                    &ext_dir.join(p.ext_path()),
+                    &dir.join("src/ext").join(p.ext_path()),
+                    &is_package_dir,
+                )?;
+            }
+        }
+        if krate.packages.iter().any(|p| extended.contains(p.raw())) {
+            for name in SHARED {
+                let src = googapis_dir.join("src").join(name);
+                if src.is_dir() {
+                    copy_dir(&src, &dir.join("src").join(name), &|_| false)?;
+                } else {
+                    fs::copy(&src, dir.join("src").join(name))?;
+                }
+            }
+        }
+        fs::write(
+            dir.join("Cargo.toml"),
+            gen_crate_manifest(krate, &crate_of, &manifest),
+        )?;
+        fs::write(
+            dir.join("src/lib.rs"),
+            gen_crate_code(krate, &crate_of, &extended),
+        )?;
+        tonic_build::fmt(dir.join("src").to_str().unwrap());
+    }
+
+    let facade = out_dir.join("googapis");
+    fs::create_dir_all(facade.join("src"))?;
+    fs::create_dir_all(facade.join("data"))?;
+    fs::write(
+        facade.join("Cargo.toml"),
+        gen_facade_manifest(protos, &root, &crates, &crate_of, &manifest),
+    )?;
+    // The helpers (well-known types, field masks, filters, validation...) do not depend on any
+    // package and stay in the facade, the package extensions in `ext` moved to their crates with
+    // a private copy of the `SHARED` helpers. The
+    // `reflect` implementations generated in the crates of the packages are not compiled: the
+    // crates have no `reflect` feature.
+    for entry in fs::read_dir(googapis_dir.join("src"))? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap();
+        if name == "ext" || name == "googapis.rs" {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir(&path, &facade.join("src").join(name), &|_| false)?;
+        } else {
+            fs::copy(&path, facade.join("src").join(name))?;
+        }
+    }
+    fs::copy(
+        googapis_dir.join("data/roots.pem"),
+        facade.join("data/roots.pem"),
+    )?;
+    fs::write(
+        facade.join("src/googapis.rs"),
+        gen_facade_code(&root, &crate_of),
+    )?;
+    tonic_build::fmt(facade.join("src").to_str().unwrap());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `b.v1` and `b.v1.types` import each other.
+    fn protos() -> Vec<Proto> {
+        vec![
+            Proto::new("/a/a.proto", "a", &["/b/v1/b.proto"]),
+            Proto::new("/b/v1/b.proto", "b.v1", &["/b/v1/types/c.proto"]),
+            Proto::new(
+                "/b/v1/types/c.proto",
+                "b.v1.types",
+                &["/b/v1/b.proto", "/d/d.proto"],
+            ),
+            Proto::new("/d/d.proto", "d", &[]),
+            Proto::new("/d/type/e.proto", "d.type", &["/d/d.proto"]),
+        ]
+    }
+
+    fn krate(name: &str, packages: &[&str], deps: &[&str]) -> Crate {
+        Crate {
+            name: name.into(),
+            packages: packages.iter().map(|&p| p.into()).collect(),
+            deps: deps.iter().map(|&p| p.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_crates() {
+        assert_eq!(
+            crates(&protos()),
+            vec![
+                krate("googapis-a", &["a"], &["b.v1"]),
+                krate("googapis-b-v1", &["b.v1", "b.v1.types"], &["d"]),
+                krate("googapis-d", &["d"], &[]),
+                krate("googapis-d-type", &["d.type"], &["d"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_crate_code() {
+        let crates = crates(&protos());
+        let crate_of = crate_of(&crates);
+        assert_eq!(
+            gen_crate_code(&crates[1], &crate_of, &HashSet::new()),
+            r###"pub mod b {
+pub mod v1 {
+include!("../genproto/b.v1.rs");
+pub mod types {
+include!("../genproto/b.v1.types.rs");
+}
+}
+}
+pub mod d {
+pub use googapis_d::d::*;
+}
+"###
+        );
+        let mut extended = HashSet::new();
+        extended.insert("d.type");
+        assert_eq!(
+            gen_crate_code(&crates[3], &crate_of, &extended),
+            r###"#[allow(dead_code, unused_imports)]
+mod base64;
+#[allow(dead_code, unused_imports)]
+mod filter;
+#[allow(dead_code, unused_imports)]
+mod wkt;
+pub mod d {
+pub use googapis_d::d::*;
+pub mod r#type {
+include!("../genproto/d.r#type.rs");
+include!("ext/d/type/mod.rs");
+}
+}
+"###
+        );
+    }
+
+    #[test]
+    fn test_copy_dir() {
+        let root = std::env::temp_dir().join(format!("xtask-split-{}", std::process::id()));
+        let src = root.join("src");
+        for file in &["mod.rs", "a/mod.rs", "a/b/mod.rs", "a/c/x.rs"] {
+            let path = src.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        let dst = root.join("dst");
+        copy_dir(&src, &dst, &|path| path.ends_with("a/b")).unwrap();
+        assert_eq!(
+            fs::read_to_string(dst.join("a/c/x.rs")).unwrap(),
+            "a/c/x.rs"
+        );
+        assert!(dst.join("a/mod.rs").is_file());
+        assert!(!dst.join("a/b").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_gen_facade_code() {
+        let protos = protos();
+        let crates = crates(&protos);
+        let root = gen::from_protos(protos.clone());
+        assert_eq!(
+            gen_facade_code(&root, &crate_of(&crates)),
+            r###"pub mod a {
+#[cfg(any(feature = "a",))]
+pub use googapis_a::a::*;
+}
+pub mod b {
+pub mod v1 {
+#[cfg(any(feature = "a",feature = "b-v1",feature = "b-v1-types",))]
+pub use googapis_b_v1::b::v1::*;
+pub mod types {
+#[cfg(any(feature = "a",feature = "b-v1",feature = "b-v1-types",))]
+pub use googapis_b_v1::b::v1::types::*;
+}
+}
+}
+pub mod d {
+#[cfg(any(feature = "a",feature = "b-v1",feature = "b-v1-types",feature = "d",feature = "d-type",))]
+pub use googapis_d::d::*;
+pub mod r#type {
+#[cfg(any(feature = "d-type",))]
+pub use googapis_d_type::d::r#type::*;
+}
+}
+"###
+        );
+    }
+
+    const MANIFEST: &str = r#"[package]
+name = "googapis"
+version = "0.5.0"
+authors = ["mechiru <u9053u6d41@gmail.com>"]
+edition = "2018"
+license = "MIT OR Apache-2.0"
+readme = "./../README.md"
+
+[features]
+default = []
+
+# @generated by `cargo xtask gen`, do not edit by hand.
+# @generated end
+
+[dependencies]
+tonic = "0.5.0"
+prost = "0.8.0"
+"#;
+
+    #[test]
+    fn test_gen_crate_manifest() {
+        let crates = crates(&protos());
+        assert_eq!(
+            gen_crate_manifest(&crates[1], &crate_of(&crates), MANIFEST),
+            r#"# @generated by `cargo xtask gen --split`, do not edit by hand.
+[package]
+name = "googapis-b-v1"
+version = "0.5.0"
+authors = ["mechiru <u9053u6d41@gmail.com>"]
+edition = "2018"
+license = "MIT OR Apache-2.0"
+description = "Generated code of `b.v1`, `b.v1.types` for googapis."
+
+[lib]
+doctest = false
+
+[dependencies]
+tonic = "0.5.0"
+prost = "0.8.0"
+googapis-d = { version = "0.5.0", path = "../googapis-d" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_gen_facade_manifest() {
+        let protos = protos();
+        let crates = crates(&protos);
+        let root = gen::from_protos(protos.clone());
+        let manifest = gen_facade_manifest(&protos, &root, &crates, &crate_of(&crates), MANIFEST);
+        assert!(manifest.contains(
+            "\n# Packages.\n\
+             a = [\"googapis-a\", \"googapis-b-v1\", \"googapis-d\"]\n\
+             b-v1 = [\"googapis-b-v1\", \"googapis-d\"]\n\
+             b-v1-types = [\"googapis-b-v1\", \"googapis-d\"]\n\
+             d = [\"googapis-d\"]\n\
+             d-type = [\"googapis-d\", \"googapis-d-type\"]\n"
+        ));
+        assert!(manifest.ends_with(
+            "[dependencies]\n\
+             googapis-a = { version = \"0.5.0\", path = \"../crates/googapis-a\", optional = true }\n\
+             googapis-b-v1 = { version = \"0.5.0\", path = \"../crates/googapis-b-v1\", optional = true }\n\
+             googapis-d = { version = \"0.5.0\", path = \"../crates/googapis-d\", optional = true }\n\
+             googapis-d-type = { version = \"0.5.0\", path = \"../crates/googapis-d-type\", optional = true }\n\
+             tonic = \"0.5.0\"\nprost = \"0.8.0\"\n"
+        ));
+    }
+}